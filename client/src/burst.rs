@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use crate::response_event::ResponseEvent;
+
+const UNKNOWN_WORKER: &str = "unknown";
+
+#[derive(Clone, Copy, Debug)]
+pub struct BurstSettings {
+    pub count: usize,
+    pub multiplier: u64,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct BurstSummary {
+    pub total: usize,
+    pub elapsed: Duration,
+    pub statuses: BTreeMap<String, usize>,
+    pub workers: BTreeMap<String, usize>,
+}
+
+impl BurstSummary {
+    pub fn new(events: &[ResponseEvent], elapsed: Duration) -> Self {
+        let mut summary = BurstSummary {
+            total: events.len(),
+            elapsed,
+            ..BurstSummary::default()
+        };
+        for event in events {
+            let status = match event.status {
+                Ok(status) => status.as_u16().to_string(),
+                Err(kind) => kind.to_string(),
+            };
+            *summary.statuses.entry(status).or_default() += 1;
+
+            let worker = event.worker.as_deref().unwrap_or(UNKNOWN_WORKER);
+            *summary.workers.entry(worker.to_string()).or_default() += 1;
+        }
+        summary
+    }
+}
+
+impl fmt::Display for BurstSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |counts: &BTreeMap<String, usize>| {
+            counts
+                .iter()
+                .map(|(key, count)| format!("{}: {}", key, count))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        write!(
+            f,
+            "{} requests in {}ms, statuses [{}], workers [{}]",
+            self.total,
+            self.elapsed.as_millis(),
+            join(&self.statuses),
+            join(&self.workers)
+        )
+    }
+}
+
+// Sends `count` requests at once, forwarding each event to `tx` as it completes.
+pub async fn run<F, Fut>(
+    settings: BurstSettings,
+    in_flight: Arc<AtomicUsize>,
+    send: F,
+    tx: mpsc::Sender<ResponseEvent>,
+) -> BurstSummary
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = ResponseEvent> + Send + 'static,
+{
+    let started_at = Instant::now();
+
+    let handles = (0..settings.count)
+        .map(|_| {
+            in_flight.fetch_add(1, Ordering::SeqCst);
+            let request = send();
+            let in_flight = in_flight.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let event = request.await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                let _ = tx.send(event.clone()).await;
+                event
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut events = Vec::with_capacity(handles.len());
+    for handle in handles {
+        if let Ok(event) = handle.await {
+            events.push(event);
+        }
+    }
+
+    BurstSummary::new(&events, started_at.elapsed())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use reqwest::StatusCode;
+
+    use super::*;
+    use crate::mock_server::{self, Reply};
+    use crate::requests::{self, RequestType};
+    use crate::response_event::FailureKind;
+
+    // Answers the Nth request as worker `w<N % 2>`, with a 503 for every fourth one.
+    async fn mock_balancer() -> std::net::SocketAddr {
+        mock_server::serve(|n, _| {
+            let served = n - 1;
+            let status = if served % 4 == 3 {
+                "503 Service Unavailable"
+            } else {
+                "200 OK"
+            };
+            Reply::new(status, &[("x-served-by", &format!("w{}", served % 2))], "")
+        })
+        .await
+    }
+
+    fn event(status: Result<StatusCode, FailureKind>, worker: Option<&str>) -> ResponseEvent {
+        ResponseEvent {
+            request: RequestType::WORK,
+            sent_at: SystemTime::UNIX_EPOCH,
+            target: String::new(),
+            status,
+            latency: Duration::ZERO,
+            worker: worker.map(|worker| worker.to_string()),
+            body_snippet: String::new(),
+            error: None,
+            attempts: 1,
+        }
+    }
+
+    #[test]
+    fn summaries_break_down_statuses_and_workers() {
+        let summary = BurstSummary::new(
+            &[
+                event(Ok(StatusCode::OK), Some("w1")),
+                event(Ok(StatusCode::OK), Some("w2")),
+                event(Ok(StatusCode::BAD_GATEWAY), Some("w1")),
+                event(Err(FailureKind::Timeout), None),
+            ],
+            Duration::from_millis(250),
+        );
+
+        assert_eq!(
+            summary.to_string(),
+            "4 requests in 250ms, statuses [200: 2, 502: 1, timeout: 1], \
+             workers [unknown: 1, w1: 2, w2: 1]"
+        );
+        assert_eq!(
+            BurstSummary::new(&[], Duration::ZERO).to_string(),
+            "0 requests in 0ms, statuses [], workers []"
+        );
+    }
+
+    #[tokio::test]
+    async fn a_burst_against_a_mock_server_sums_up_every_response() {
+        let address = mock_balancer().await;
+        let client = reqwest::Client::new();
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let (tx, mut rx) = mpsc::channel(100);
+
+        let send = || {
+            let client = client.clone();
+            async move {
+                let req = client
+                    .get(format!("http://{}/work", address))
+                    .build()
+                    .unwrap();
+                requests::execute(&client, RequestType::WORK, req).await
+            }
+        };
+        let settings = BurstSettings {
+            count: 8,
+            multiplier: 1,
+        };
+        let summary = run(settings, in_flight.clone(), send, tx).await;
+
+        assert_eq!(summary.total, 8);
+        assert_eq!(
+            summary.statuses,
+            BTreeMap::from([("200".to_string(), 6), ("503".to_string(), 2)])
+        );
+        assert_eq!(
+            summary.workers,
+            BTreeMap::from([("w0".to_string(), 4), ("w1".to_string(), 4)])
+        );
+        assert_eq!(in_flight.load(Ordering::SeqCst), 0);
+        let mut forwarded = 0;
+        while rx.try_recv().is_ok() {
+            forwarded += 1;
+        }
+        assert_eq!(forwarded, 8);
+    }
+}
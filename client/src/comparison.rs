@@ -0,0 +1,238 @@
+use reqwest::Url;
+
+use crate::requests::RequestType;
+use crate::response_event::{target_of, ResponseEvent};
+use crate::stats::Stats;
+
+// Work stats of two balancers receiving the same traffic, told apart by the event target.
+pub struct Comparison {
+    a: Side,
+    b: Side,
+}
+
+struct Side {
+    target: String,
+    stats: Stats,
+}
+
+impl Side {
+    fn new(url: &Url) -> Self {
+        Side {
+            target: target_of(url),
+            stats: Stats::default(),
+        }
+    }
+}
+
+impl Comparison {
+    pub fn new(a: &Url, b: &Url) -> Self {
+        Comparison {
+            a: Side::new(a),
+            b: Side::new(b),
+        }
+    }
+
+    // Events of other requests or other targets are ignored.
+    pub fn record(&mut self, event: &ResponseEvent) {
+        if event.request != RequestType::WORK {
+            return;
+        }
+        if event.target == self.a.target {
+            self.a.stats.record(event);
+        } else if event.target == self.b.target {
+            self.b.stats.record(event);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.a.stats.reset();
+        self.b.stats.reset();
+    }
+
+    pub fn render(&self) -> String {
+        let (a, b) = (&self.a.stats, &self.b.stats);
+        let ms = |value: Option<u64>| value.map_or("-".to_string(), |v| format!("{}ms", v));
+        let row = |label: &str, a: String, b: String| format!("{:<7}{:>10}{:>10}\n", label, a, b);
+
+        let mut text = format!("A {}\nB {}\n\n", self.a.target, self.b.target);
+        text.push_str(&row("", "A".to_string(), "B".to_string()));
+        text.push_str(&row("req", a.total().to_string(), b.total().to_string()));
+        text.push_str(&row("p50", ms(a.percentile(50.0)), ms(b.percentile(50.0))));
+        text.push_str(&row("p95", ms(a.percentile(95.0)), ms(b.percentile(95.0))));
+        text.push_str(&row(
+            "errors",
+            format!("{:.1}%", a.error_rate() * 100.0),
+            format!("{:.1}%", b.error_rate() * 100.0),
+        ));
+        text.push_str(&row(
+            "spread",
+            format!("{:.0}pp", spread(a)),
+            format!("{:.0}pp", spread(b)),
+        ));
+        text
+    }
+}
+
+// Difference between the busiest and the idlest worker's share, in percentage points.
+fn spread(stats: &Stats) -> f64 {
+    let counts = stats.per_worker();
+    match (counts.values().max(), counts.values().min()) {
+        (Some(max), Some(min)) if stats.total() > 0 => {
+            (max - min) as f64 * 100.0 / stats.total() as f64
+        }
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::config::{Targets, WorkTarget};
+    use crate::mock_server::{self, Reply};
+    use crate::requests::{self, WorkOverrides};
+    use crate::retry::RetryPolicy;
+
+    // A balancer answering after `latency`, every `fail_every`th request with 503,
+    // naming one of `workers` in turn.
+    async fn mock_balancer(latency: Duration, fail_every: usize, workers: &'static [&str]) -> Url {
+        let address = mock_server::serve(move |n, _| {
+            let status = if n.is_multiple_of(fail_every) {
+                "503 Service Unavailable"
+            } else {
+                "200 OK"
+            };
+            Reply::new(status, &[("x-served-by", workers[n % workers.len()])], "").after(latency)
+        })
+        .await;
+        format!("http://{}", address).parse().unwrap()
+    }
+
+    // Sends `count` work requests to A, each duplicated to B, and records both sides.
+    async fn compared(a: Url, b: Url, count: usize) -> Comparison {
+        let client = Arc::new(reqwest::Client::new());
+        let targets = Targets {
+            load_balancer: a.clone(),
+            compare: Some(b.clone()),
+            workers: Vec::new(),
+        };
+        let mut comparison = Comparison::new(&a, &b);
+        for _ in 0..count {
+            let request = RequestType::Work {
+                multiplier: 1,
+                overrides: WorkOverrides::default(),
+                target: WorkTarget::LoadBalancer,
+            };
+            let copy = request.compare_copy().unwrap();
+            let (event, copy) = tokio::join!(
+                requests::perform(&client, &targets, request, RetryPolicy::NONE),
+                requests::perform(&client, &targets, copy, RetryPolicy::NONE)
+            );
+            comparison.record(&event);
+            comparison.record(&copy);
+        }
+        comparison
+    }
+
+    #[test]
+    fn only_work_is_duplicated() {
+        let work = RequestType::Work {
+            multiplier: 2,
+            overrides: WorkOverrides::default(),
+            target: WorkTarget::LoadBalancer,
+        };
+        assert!(matches!(
+            work.compare_copy(),
+            Some(RequestType::Work {
+                multiplier: 2,
+                target: WorkTarget::Compare,
+                ..
+            })
+        ));
+
+        let direct = RequestType::Work {
+            multiplier: 2,
+            overrides: WorkOverrides::default(),
+            target: WorkTarget::Worker(0),
+        };
+        assert!(direct.compare_copy().is_none());
+        assert!(RequestType::ListServers.compare_copy().is_none());
+    }
+
+    #[tokio::test]
+    async fn both_sides_get_the_same_traffic_and_diverge() {
+        let a = mock_balancer(Duration::from_millis(5), usize::MAX, &["w0", "w1"]).await;
+        let b = mock_balancer(Duration::from_millis(60), 4, &["w0", "w0", "w1"]).await;
+
+        let comparison = compared(a.clone(), b.clone(), 8).await;
+
+        let (a_stats, b_stats) = (&comparison.a.stats, &comparison.b.stats);
+        assert_eq!((a_stats.total(), b_stats.total()), (8, 8));
+        assert!(a_stats.percentile(95.0).unwrap() < 60);
+        assert!(b_stats.percentile(50.0).unwrap() >= 60);
+        assert_eq!((a_stats.error_rate(), b_stats.error_rate()), (0.0, 0.25));
+
+        let rendered = comparison.render();
+        assert!(rendered.starts_with(&format!("A {}\nB {}\n\n", target_of(&a), target_of(&b))));
+        assert!(
+            rendered.contains("req             8         8\n"),
+            "{}",
+            rendered
+        );
+        assert!(
+            rendered.contains("errors       0.0%     25.0%\n"),
+            "{}",
+            rendered
+        );
+        // A alternates between two workers, B sends 5 of 8 to w0.
+        assert!(
+            rendered.contains("spread        0pp      25pp\n"),
+            "{}",
+            rendered
+        );
+    }
+
+    #[tokio::test]
+    async fn a_failing_side_leaves_the_other_alone() {
+        let a = mock_balancer(Duration::ZERO, usize::MAX, &["w0", "w1"]).await;
+        let closed = mock_server::closed_port().await;
+        let b: Url = format!("http://{}", closed).parse().unwrap();
+
+        let comparison = compared(a, b, 4).await;
+
+        assert_eq!(comparison.a.stats.error_rate(), 0.0);
+        assert_eq!(comparison.b.stats.total(), 4);
+        assert_eq!(comparison.b.stats.error_rate(), 1.0);
+    }
+
+    #[test]
+    fn other_requests_and_targets_are_ignored() {
+        let a: Url = "http://127.0.0.1:8080".parse().unwrap();
+        let b: Url = "http://127.0.0.1:8081".parse().unwrap();
+        let mut comparison = Comparison::new(&a, &b);
+        let event = |request, target: &str| ResponseEvent {
+            request,
+            sent_at: std::time::SystemTime::UNIX_EPOCH,
+            target: target.to_string(),
+            status: Ok(reqwest::StatusCode::OK),
+            latency: Duration::from_millis(10),
+            worker: None,
+            body_snippet: String::new(),
+            error: None,
+            attempts: 1,
+        };
+
+        comparison.record(&event("change_algo", "127.0.0.1:8080"));
+        comparison.record(&event(RequestType::WORK, "127.0.0.1:3000"));
+        comparison.record(&event(RequestType::WORK, "127.0.0.1:8081"));
+        assert_eq!(
+            (comparison.a.stats.total(), comparison.b.stats.total()),
+            (0, 1)
+        );
+
+        comparison.reset();
+        assert_eq!(comparison.b.stats.total(), 0);
+    }
+}
@@ -0,0 +1,440 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand};
+use environment::{Environment, EnvironmentError, Topology};
+use reqwest::Url;
+
+use crate::burst::BurstSettings;
+use crate::load::LoadSettings;
+use crate::retry::RetryPolicy;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Interactive client driving the load balancer demo")]
+pub struct Config {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Base URL of the load balancer, where APP_ENVIRONMENT publishes it by default
+    #[arg(long, env = "LB_URL")]
+    pub target: Option<Url>,
+
+    /// Base URL of a second load balancer, work sent to --target is duplicated to it for comparison
+    #[arg(long, env = "COMPARE_LB_URL")]
+    pub compare_target: Option<Url>,
+
+    /// Base URL of the worker servers, the port is taken from --worker-base-port.
+    /// Where APP_ENVIRONMENT or BACKENDS puts them by default
+    #[arg(long, env = "WORKER_BASE_URL")]
+    pub worker_base_url: Option<Url>,
+
+    /// Port of the first worker server, worker N listens on this port + N [default: 3000]
+    #[arg(long, env = "WORKER_BASE_PORT")]
+    pub worker_base_port: Option<u16>,
+
+    /// Number of worker servers, on consecutive ports from --worker-base-port [default: 3]
+    #[arg(long, env = "WORKER_COUNT", value_parser = clap::value_parser!(u64).range(1..=9))]
+    pub worker_count: Option<u64>,
+
+    /// Total time allowed for a request, including reading the body, in milliseconds
+    #[arg(long, env = "CLIENT_REQUEST_TIMEOUT_MS", default_value_t = 10_000)]
+    pub request_timeout_ms: u64,
+
+    /// Time allowed for establishing a connection, in milliseconds
+    #[arg(long, env = "CLIENT_CONNECT_TIMEOUT_MS", default_value_t = 2_000)]
+    pub connect_timeout_ms: u64,
+
+    /// Start with retries on connect errors and 5xx responses enabled, toggled from the menu
+    #[arg(long, env = "RETRY")]
+    pub retry: bool,
+
+    /// Maximum number of retries of a failed request
+    #[arg(long, env = "RETRY_MAX", default_value_t = 3)]
+    pub retry_max: u32,
+
+    /// Delay before the first retry in milliseconds, doubled for every following one
+    #[arg(long, env = "RETRY_BACKOFF_MS", default_value_t = 100)]
+    pub retry_backoff_ms: u64,
+
+    /// How often the connection to the load balancer is checked, in seconds
+    #[arg(long, env = "CONNECTION_CHECK_SECS", default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+    pub connection_check_secs: u64,
+
+    /// Target requests per second of a load run
+    #[arg(long, env = "LOAD_RPS", default_value_t = 10.0)]
+    pub load_rps: f64,
+
+    /// Maximum number of requests in flight during a load run
+    #[arg(long, env = "LOAD_CONCURRENCY", default_value_t = 20)]
+    pub load_concurrency: usize,
+
+    /// Length of a load run in seconds
+    #[arg(long, env = "LOAD_DURATION_SECS", default_value_t = 30)]
+    pub load_duration_secs: u64,
+
+    /// Work multiplier sent with every request of a load run
+    #[arg(long, env = "LOAD_MULTIPLIER", default_value_t = 1)]
+    pub load_multiplier: u64,
+
+    /// Requests per second of the background load toggled from the menu
+    #[arg(long, env = "BACKGROUND_RPS", default_value_t = 2.0)]
+    pub background_rps: f64,
+
+    /// Work multiplier of background load requests
+    #[arg(long, env = "BACKGROUND_MULTIPLIER", default_value_t = 1)]
+    pub background_multiplier: u64,
+
+    /// Default number of concurrent requests sent by a burst
+    #[arg(long, env = "BURST_COUNT", default_value_t = 20)]
+    pub burst_count: usize,
+
+    /// Default work multiplier of burst requests
+    #[arg(long, env = "BURST_MULTIPLIER", default_value_t = 1)]
+    pub burst_multiplier: u64,
+
+    /// Number of most recent responses the per-worker distribution is computed over
+    #[arg(long, env = "DISTRIBUTION_WINDOW", default_value_t = 100)]
+    pub distribution_window: usize,
+
+    /// Number of lines kept in the output pane for scrolling back
+    #[arg(long, env = "MAX_OUTPUT_LINES", default_value_t = 1000, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_output_lines: u32,
+
+    /// How long quitting waits for requests still in flight, in milliseconds
+    #[arg(long, env = "DRAIN_TIMEOUT_MS", default_value_t = 5000)]
+    pub drain_timeout_ms: u64,
+
+    /// File the results are exported to, CSV for a .csv extension and JSON lines otherwise
+    #[arg(long, env = "EXPORT_PATH", default_value = "results.csv")]
+    pub export_path: PathBuf,
+
+    /// Append every output line with a timestamp to this file
+    #[arg(long, env = "LOG_FILE")]
+    pub log_file: Option<PathBuf>,
+
+    /// Number of lines of an existing log file shown on startup, 0 to start empty
+    #[arg(long, env = "RESTORE_LINES", default_value_t = 20)]
+    pub restore_lines: u32,
+
+    /// JSON file of scenarios bound to menu keys, ignored when it doesn't exist
+    #[arg(long, env = "SCENARIOS_FILE", default_value = "scenarios.json")]
+    pub scenarios: PathBuf,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run a scenario file without the TUI and print a JSON report
+    Run(RunArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    /// JSON scenario file describing the steps to run
+    #[arg(long)]
+    pub scenario: PathBuf,
+
+    /// Highest error rate (0 to 1) of work requests that still passes, overrides the scenario
+    #[arg(long, env = "MAX_ERROR_RATE")]
+    pub max_error_rate: Option<f64>,
+
+    /// Also write the JSON report to this file
+    #[arg(long)]
+    pub report: Option<PathBuf>,
+
+    /// Write every response to this file, CSV for a .csv extension and JSON lines otherwise
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn load_settings(&self) -> LoadSettings {
+        LoadSettings {
+            rps: self.load_rps,
+            concurrency: self.load_concurrency,
+            duration: Duration::from_secs(self.load_duration_secs),
+            multiplier: self.load_multiplier,
+            requests: None,
+        }
+    }
+
+    // Runs until toggled off, with the same concurrency cap as a load run.
+    pub fn background_load_settings(&self) -> LoadSettings {
+        LoadSettings {
+            rps: self.background_rps,
+            concurrency: self.load_concurrency,
+            duration: Duration::MAX,
+            multiplier: self.background_multiplier,
+            requests: None,
+        }
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: self.retry_max,
+            backoff: Duration::from_millis(self.retry_backoff_ms),
+        }
+    }
+
+    pub fn burst_settings(&self) -> BurstSettings {
+        BurstSettings {
+            count: self.burst_count,
+            multiplier: self.burst_multiplier,
+        }
+    }
+
+    // The topology APP_ENVIRONMENT, WORKER_COUNT, WORKER_BASE_PORT and BACKENDS describe,
+    // with the options given on the command line on top.
+    pub fn targets(&self) -> Result<Targets, EnvironmentError> {
+        let mut topology = Topology::from_env(Environment::from_env()?)?;
+        if let Some(worker_count) = self.worker_count {
+            topology = topology.with_worker_count(worker_count as usize);
+        }
+        if let Some(worker_base_port) = self.worker_base_port {
+            topology = topology.with_worker_base_port(worker_base_port);
+        }
+        let topology = topology.validated()?;
+        let load_balancer = match &self.target {
+            Some(target) => target.clone(),
+            None => parse_url(&topology.load_balancer_url())?,
+        };
+        let workers = topology
+            .worker_addresses()
+            .iter()
+            .map(|worker| match &self.worker_base_url {
+                Some(base) => {
+                    let mut url = base.clone();
+                    // Only fails for URLs that cannot have a port, which clap already rejected as invalid bases.
+                    let _ = url.set_port(Some(worker.port));
+                    Ok(url)
+                }
+                None => parse_url(&format!("http://{}", worker)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Targets {
+            load_balancer,
+            compare: self.compare_target.clone(),
+            workers,
+        })
+    }
+}
+
+// Where work requests are sent, the balancer or a worker addressed directly for comparison.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WorkTarget {
+    #[default]
+    LoadBalancer,
+    Worker(u64),
+    // The second balancer of comparison mode, the first one without it.
+    Compare,
+}
+
+#[derive(Clone, Debug)]
+pub struct Targets {
+    pub load_balancer: Url,
+    pub compare: Option<Url>,
+    // Base URL of every worker, in order.
+    pub workers: Vec<Url>,
+}
+
+impl Targets {
+    pub fn worker_count(&self) -> u64 {
+        self.workers.len() as u64
+    }
+
+    pub fn load_balancer(&self, path: &str) -> Url {
+        join(&self.load_balancer, path)
+    }
+
+    pub fn work(&self, target: WorkTarget, path: &str) -> Url {
+        match target {
+            WorkTarget::LoadBalancer => self.load_balancer(path),
+            WorkTarget::Worker(server) => self.worker(server, path),
+            WorkTarget::Compare => join(self.compare.as_ref().unwrap_or(&self.load_balancer), path),
+        }
+    }
+
+    // The balancer first, then every worker in order, wrapping around.
+    pub fn next_work_target(&self, target: WorkTarget) -> WorkTarget {
+        match target {
+            WorkTarget::LoadBalancer => WorkTarget::Worker(0),
+            WorkTarget::Worker(server) if server + 1 < self.worker_count() => {
+                WorkTarget::Worker(server + 1)
+            }
+            WorkTarget::Worker(_) | WorkTarget::Compare => WorkTarget::LoadBalancer,
+        }
+    }
+
+    pub fn worker(&self, server: u64, path: &str) -> Url {
+        join(&self.workers[server as usize], path)
+    }
+}
+
+// A BACKENDS host that doesn't make a URL is reported like any other invalid topology.
+fn parse_url(value: &str) -> Result<Url, EnvironmentError> {
+    value
+        .parse()
+        .map_err(|_| EnvironmentError::InvalidVariable {
+            variable: "BACKENDS",
+            value: value.to_string(),
+        })
+}
+
+// Appends `path` to the base path, unlike `Url::join` which replaces the last segment.
+fn join(base: &Url, path: &str) -> Url {
+    let mut url = base.clone();
+    let base_path = url.path().trim_end_matches('/').to_string();
+    url.set_path(&format!("{}/{}", base_path, path.trim_start_matches('/')));
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(value: &str) -> Url {
+        value.parse().unwrap()
+    }
+
+    fn targets(load_balancer: &str, workers: &[&str]) -> Targets {
+        Targets {
+            load_balancer: url(load_balancer),
+            compare: None,
+            workers: workers.iter().map(|worker| url(worker)).collect(),
+        }
+    }
+
+    #[test]
+    fn paths_are_appended_to_the_base_with_or_without_trailing_slashes() {
+        for (base, path, expected) in [
+            ("http://127.0.0.1", "/work", "http://127.0.0.1/work"),
+            (
+                "http://127.0.0.1:8080/",
+                "/work",
+                "http://127.0.0.1:8080/work",
+            ),
+            (
+                "http://127.0.0.1:8080/",
+                "work",
+                "http://127.0.0.1:8080/work",
+            ),
+            (
+                "https://lb.example.com",
+                "/lb/stats",
+                "https://lb.example.com/lb/stats",
+            ),
+            (
+                "https://lb.example.com/demo",
+                "/algo",
+                "https://lb.example.com/demo/algo",
+            ),
+            (
+                "https://lb.example.com/demo//",
+                "/algo",
+                "https://lb.example.com/demo/algo",
+            ),
+        ] {
+            assert_eq!(
+                targets(base, &[]).load_balancer(path).as_str(),
+                expected,
+                "{} + {}",
+                base,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn work_goes_to_the_chosen_target() {
+        let mut targets = targets(
+            "http://127.0.0.1",
+            &[
+                "http://127.0.0.1:3000",
+                "https://workers.example.com:3001/w/",
+            ],
+        );
+
+        assert_eq!(
+            targets.work(WorkTarget::LoadBalancer, "/work").as_str(),
+            "http://127.0.0.1/work"
+        );
+        assert_eq!(
+            targets.work(WorkTarget::Worker(1), "/work").as_str(),
+            "https://workers.example.com:3001/w/work"
+        );
+        // Without a second balancer, comparison work goes to the first one.
+        assert_eq!(
+            targets.work(WorkTarget::Compare, "/work").as_str(),
+            "http://127.0.0.1/work"
+        );
+        targets.compare = Some(url("https://other.example.com/"));
+        assert_eq!(
+            targets.work(WorkTarget::Compare, "/work").as_str(),
+            "https://other.example.com/work"
+        );
+    }
+
+    #[test]
+    fn work_targets_cycle_through_every_worker() {
+        let targets = targets(
+            "http://127.0.0.1",
+            &["http://127.0.0.1:3000", "http://127.0.0.1:3001"],
+        );
+
+        let mut target = WorkTarget::LoadBalancer;
+        let mut cycle = Vec::new();
+        for _ in 0..4 {
+            target = targets.next_work_target(target);
+            cycle.push(target);
+        }
+        assert_eq!(
+            cycle,
+            [
+                WorkTarget::Worker(0),
+                WorkTarget::Worker(1),
+                WorkTarget::LoadBalancer,
+                WorkTarget::Worker(0)
+            ]
+        );
+        assert_eq!(
+            targets.next_work_target(WorkTarget::Compare),
+            WorkTarget::LoadBalancer
+        );
+    }
+
+    #[test]
+    fn worker_urls_take_consecutive_ports_on_the_worker_base() {
+        let config = Config::try_parse_from([
+            "client",
+            "--target",
+            "https://lb.example.com/demo/",
+            "--worker-base-url",
+            "https://workers.example.com/base/",
+            "--worker-base-port",
+            "4000",
+            "--worker-count",
+            "2",
+        ])
+        .unwrap();
+
+        let targets = config.targets().unwrap();
+        assert_eq!(
+            targets.load_balancer("/work").as_str(),
+            "https://lb.example.com/demo/work"
+        );
+        assert_eq!(
+            targets
+                .workers
+                .iter()
+                .map(|worker| worker.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "https://workers.example.com:4000/base/",
+                "https://workers.example.com:4001/base/"
+            ]
+        );
+        assert_eq!(
+            targets.worker(1, "/setup").as_str(),
+            "https://workers.example.com:4001/base/setup"
+        );
+    }
+}
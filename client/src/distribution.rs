@@ -0,0 +1,123 @@
+use std::collections::{BTreeMap, VecDeque};
+
+const UNKNOWN_WORKER: &str = "unknown";
+
+// Which worker served each of the last `capacity` responses.
+pub struct Distribution {
+    capacity: usize,
+    window: VecDeque<String>,
+    // Kept in sync with `window` so shares don't need a full scan.
+    counts: BTreeMap<String, usize>,
+}
+
+impl Distribution {
+    pub fn new(capacity: usize) -> Self {
+        Distribution {
+            capacity: capacity.max(1),
+            window: VecDeque::new(),
+            counts: BTreeMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, worker: Option<&str>) {
+        if self.window.len() == self.capacity {
+            if let Some(oldest) = self.window.pop_front() {
+                self.forget(&oldest);
+            }
+        }
+        let worker = worker.unwrap_or(UNKNOWN_WORKER).to_string();
+        *self.counts.entry(worker.clone()).or_default() += 1;
+        self.window.push_back(worker);
+    }
+
+    fn forget(&mut self, worker: &str) {
+        if let Some(count) = self.counts.get_mut(worker) {
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(worker);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.window.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.window.is_empty()
+    }
+
+    pub fn reset(&mut self) {
+        self.window.clear();
+        self.counts.clear();
+    }
+
+    // Percentage of the window served by each worker, ordered by worker name.
+    pub fn shares(&self) -> Vec<(&str, f64)> {
+        let total = self.window.len() as f64;
+        self.counts
+            .iter()
+            .map(|(worker, count)| (worker.as_str(), *count as f64 * 100.0 / total))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorded(capacity: usize, workers: &[Option<&str>]) -> Distribution {
+        let mut distribution = Distribution::new(capacity);
+        for worker in workers {
+            distribution.record(*worker);
+        }
+        distribution
+    }
+
+    #[test]
+    fn shares_add_up_over_the_window() {
+        let distribution = recorded(10, &[Some("w2"), Some("w1"), Some("w1"), None]);
+
+        assert_eq!(distribution.len(), 4);
+        assert_eq!(
+            distribution.shares(),
+            [("unknown", 25.0), ("w1", 50.0), ("w2", 25.0)]
+        );
+    }
+
+    #[test]
+    fn the_oldest_responses_leave_the_window() {
+        let distribution = recorded(
+            3,
+            &[Some("w1"), Some("w1"), Some("w2"), Some("w3"), Some("w3")],
+        );
+
+        assert_eq!(distribution.len(), 3);
+        assert_eq!(
+            distribution.shares(),
+            [("w2", 100.0 / 3.0), ("w3", 200.0 / 3.0)]
+        );
+    }
+
+    #[test]
+    fn new_workers_get_their_own_share() {
+        let mut distribution = recorded(4, &[Some("w1"), Some("w1")]);
+        distribution.record(Some("w4"));
+
+        assert_eq!(
+            distribution.shares(),
+            [("w1", 200.0 / 3.0), ("w4", 100.0 / 3.0)]
+        );
+    }
+
+    #[test]
+    fn empty_windows_have_no_shares() {
+        let mut distribution = recorded(0, &[Some("w1"), Some("w2")]);
+        // A capacity of at least one.
+        assert_eq!(distribution.shares(), [("w2", 100.0)]);
+
+        distribution.reset();
+        assert!(distribution.is_empty());
+        assert!(distribution.shares().is_empty());
+    }
+}
@@ -0,0 +1,313 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use tokio::sync::watch;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+
+use crate::config::WorkTarget;
+use crate::load::{self, LoadProgress, LoadSettings};
+use crate::requests::{RequestType, WorkOverrides};
+use crate::response_event::ResponseEvent;
+use crate::scenario::{Scenario, Step};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+// Runs every step of the scenario in order, `repeat` times. `send` performs a request and
+// `progress` receives a line per step and per outcome. Returns the steps that failed.
+pub async fn run<F, Fut>(
+    scenario: &Scenario,
+    send: F,
+    mut progress: impl FnMut(String),
+) -> Vec<String>
+where
+    F: Fn(RequestType) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = ResponseEvent> + Send + 'static,
+{
+    let mut failed_steps = Vec::new();
+
+    let repeat = scenario.repeat.max(1);
+    let step_count = scenario.steps.len();
+    for iteration in 1..=repeat {
+        for (index, step) in scenario.steps.iter().enumerate() {
+            progress(format!(
+                "[{}/{}] step {}/{}: {}",
+                iteration,
+                repeat,
+                index + 1,
+                step_count,
+                step
+            ));
+            if let Err(msg) = run_step(step, &send, &mut progress).await {
+                progress(format!("  failed: {}", msg));
+                failed_steps.push(format!("{}: {}", step, msg));
+            }
+        }
+    }
+    failed_steps
+}
+
+// Only control requests fail a step, failed work shows up in the stats instead.
+async fn run_step<F, Fut>(
+    step: &Step,
+    send: &F,
+    progress: &mut impl FnMut(String),
+) -> Result<(), String>
+where
+    F: Fn(RequestType) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = ResponseEvent> + Send + 'static,
+{
+    let request = match step {
+        Step::Algo { algo } => RequestType::ChangeAlgorithm {
+            new_algo: algo.clone(),
+        },
+        Step::SetupWorker {
+            server,
+            min_duration,
+            max_duration,
+            error_rate,
+        } => RequestType::SetupWorker {
+            server: *server,
+            min_duration: *min_duration,
+            max_duration: *max_duration,
+            error_rate: *error_rate,
+        },
+        Step::ResetWorker { server } => RequestType::ResetWorker { server: *server },
+        Step::Work {
+            requests,
+            multiplier,
+        } => {
+            let handles = (0..*requests)
+                .map(|_| {
+                    tokio::spawn(send(RequestType::Work {
+                        multiplier: *multiplier,
+                        overrides: WorkOverrides::default(),
+                        target: WorkTarget::LoadBalancer,
+                    }))
+                })
+                .collect::<Vec<_>>();
+            let mut succeeded = 0;
+            for handle in handles {
+                if handle.await.is_ok_and(|event| event.is_success()) {
+                    succeeded += 1;
+                }
+            }
+            progress(format!("  {} of {} succeeded", succeeded, requests));
+            return Ok(());
+        }
+        Step::Load {
+            requests,
+            rps,
+            concurrency,
+            multiplier,
+        } => {
+            let settings = LoadSettings {
+                rps: *rps,
+                concurrency: *concurrency,
+                // One interval of slack so the last request isn't cut off by the deadline.
+                duration: Duration::from_secs_f64((*requests + 1) as f64 / rps),
+                multiplier: *multiplier,
+                requests: Some(*requests),
+            };
+            run_load(settings, send.clone(), progress).await;
+            return Ok(());
+        }
+        Step::Wait { ms } => {
+            tokio::time::sleep(Duration::from_millis(*ms)).await;
+            return Ok(());
+        }
+    };
+
+    let event = send(request).await;
+    progress(format!("  {}", event));
+    if event.is_success() {
+        Ok(())
+    } else {
+        Err(event.to_string())
+    }
+}
+
+async fn run_load<F, Fut>(settings: LoadSettings, send: F, progress: &mut impl FnMut(String))
+where
+    F: Fn(RequestType) -> Fut + Send + 'static,
+    Fut: Future<Output = ResponseEvent> + Send + 'static,
+{
+    let load_progress = Arc::new(LoadProgress::new());
+    // Steps are never stopped early, the sender only keeps the channel open.
+    let (_stop, stop_rx) = watch::channel(false);
+
+    let send_work = move || {
+        let request = send(RequestType::Work {
+            multiplier: settings.multiplier,
+            overrides: WorkOverrides::default(),
+            target: WorkTarget::LoadBalancer,
+        });
+        async move { request.await.is_success() }
+    };
+
+    let load = load::run(settings, load_progress.clone(), stop_rx, send_work);
+    tokio::pin!(load);
+    let mut ticker = interval(PROGRESS_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ticker.tick().await;
+    loop {
+        tokio::select! {
+            _ = &mut load => break,
+            _ = ticker.tick() => progress(format!("  {}", load_progress.summary())),
+        }
+    }
+    progress(format!("  done: {}", load_progress.summary()));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::time::SystemTime;
+
+    use reqwest::StatusCode;
+
+    use super::*;
+
+    // Answers every request without a network, recording a short description of each.
+    // Changing the algorithm to "broken" fails, and so does every second work request.
+    fn mock_sender(
+        sent: Arc<Mutex<Vec<String>>>,
+    ) -> impl Fn(RequestType) -> std::future::Ready<ResponseEvent> + Clone + Send + 'static {
+        move |request| {
+            let mut sent = sent.lock().unwrap();
+            let (description, status) = match &request {
+                RequestType::ChangeAlgorithm { new_algo } if new_algo == "broken" => {
+                    (format!("algo {}", new_algo), StatusCode::BAD_REQUEST)
+                }
+                RequestType::ChangeAlgorithm { new_algo } => {
+                    (format!("algo {}", new_algo), StatusCode::OK)
+                }
+                RequestType::SetupWorker { server, .. } => {
+                    (format!("setup {}", server), StatusCode::OK)
+                }
+                RequestType::ResetWorker { server } => {
+                    (format!("reset {}", server), StatusCode::OK)
+                }
+                RequestType::Work { multiplier, .. } => {
+                    let works = sent.iter().filter(|line| line.starts_with("work")).count();
+                    let status = if works % 2 == 0 {
+                        StatusCode::OK
+                    } else {
+                        StatusCode::SERVICE_UNAVAILABLE
+                    };
+                    (format!("work x{}", multiplier), status)
+                }
+                RequestType::GetAlgorithm | RequestType::ListServers => {
+                    (request.name().to_string(), StatusCode::OK)
+                }
+            };
+            sent.push(description);
+            std::future::ready(ResponseEvent {
+                request: request.name(),
+                sent_at: SystemTime::now(),
+                target: "127.0.0.1:80".to_string(),
+                status: Ok(status),
+                latency: Duration::from_millis(1),
+                worker: None,
+                body_snippet: String::new(),
+                error: None,
+                attempts: 1,
+            })
+        }
+    }
+
+    async fn executed(scenario: &str) -> (Vec<String>, Vec<String>, Vec<String>) {
+        let scenario = Scenario::parse(scenario).unwrap();
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let mut lines = Vec::new();
+        let failed = run(&scenario, mock_sender(sent.clone()), |line| {
+            lines.push(line)
+        })
+        .await;
+        let sent = sent.lock().unwrap().clone();
+        (sent, lines, failed)
+    }
+
+    #[tokio::test]
+    async fn steps_run_in_order_for_every_repeat() {
+        let (sent, lines, failed) = executed(
+            r#"{"repeat": 2, "steps": [
+                {"action": "algo", "algo": "least_connections"},
+                {"action": "setup_worker", "server": 1, "min_duration": 1, "max_duration": 2},
+                {"action": "reset_worker", "server": 1}
+            ]}"#,
+        )
+        .await;
+
+        assert_eq!(
+            sent,
+            [
+                "algo least_connections",
+                "setup 1",
+                "reset 1",
+                "algo least_connections",
+                "setup 1",
+                "reset 1",
+            ]
+        );
+        assert!(failed.is_empty(), "{:?}", failed);
+        assert_eq!(lines[0], "[1/2] step 1/3: change algo to least_connections");
+        assert!(lines[1].starts_with("  HTTP 200 "), "{}", lines[1]);
+        assert_eq!(lines[6], "[2/2] step 1/3: change algo to least_connections");
+        assert_eq!(lines.len(), 12);
+    }
+
+    #[tokio::test]
+    async fn failed_control_requests_fail_their_step_and_the_rest_still_run() {
+        let (sent, lines, failed) = executed(
+            r#"{"steps": [
+                {"action": "algo", "algo": "broken"},
+                {"action": "reset_worker", "server": 0}
+            ]}"#,
+        )
+        .await;
+
+        assert_eq!(sent, ["algo broken", "reset 0"]);
+        assert_eq!(failed.len(), 1);
+        assert!(
+            failed[0].starts_with("change algo to broken: HTTP 400 "),
+            "{}",
+            failed[0]
+        );
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with("  failed: HTTP 400 ")));
+    }
+
+    #[tokio::test]
+    async fn failed_work_is_counted_without_failing_the_step() {
+        let (sent, lines, failed) =
+            executed(r#"{"steps": [{"action": "work", "requests": 4, "multiplier": 3}]}"#).await;
+
+        assert_eq!(sent, ["work x3"; 4]);
+        assert!(failed.is_empty(), "{:?}", failed);
+        assert_eq!(lines.last().unwrap(), "  2 of 4 succeeded");
+    }
+
+    #[tokio::test]
+    async fn load_steps_send_the_requested_number_of_requests() {
+        let (sent, lines, failed) = executed(
+            r#"{"steps": [{"action": "load", "requests": 5, "rps": 200, "multiplier": 2}]}"#,
+        )
+        .await;
+
+        assert_eq!(sent, ["work x2"; 5]);
+        assert!(failed.is_empty(), "{:?}", failed);
+        assert!(lines.last().unwrap().starts_with("  done: "), "{:?}", lines);
+    }
+
+    #[tokio::test]
+    async fn wait_steps_sleep_for_their_duration() {
+        let started = tokio::time::Instant::now();
+        let (sent, _, failed) = executed(r#"{"steps": [{"action": "wait", "ms": 50}]}"#).await;
+
+        assert!(sent.is_empty());
+        assert!(failed.is_empty());
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(50), "{:?}", elapsed);
+    }
+}
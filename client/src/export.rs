@@ -0,0 +1,272 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::response_event::ResponseEvent;
+
+const CSV_HEADER: &str = "timestamp_ms,request,status,latency_ms,worker,error";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    JsonLines,
+}
+
+impl ExportFormat {
+    // `.csv` files are written as CSV, anything else as JSON lines.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => ExportFormat::Csv,
+            _ => ExportFormat::JsonLines,
+        }
+    }
+}
+
+struct Record<'a> {
+    timestamp_ms: u128,
+    request: &'static str,
+    status: String,
+    latency_ms: u128,
+    worker: &'a str,
+    error: &'a str,
+}
+
+impl<'a> Record<'a> {
+    fn new(event: &'a ResponseEvent) -> Self {
+        let status = match event.status {
+            Ok(status) => status.as_u16().to_string(),
+            Err(kind) => kind.to_string(),
+        };
+        // Error statuses carry their explanation in the body.
+        let error = match &event.error {
+            Some(error) => error.as_str(),
+            None if !event.is_success() => event.body_snippet.as_str(),
+            None => "",
+        };
+        Record {
+            timestamp_ms: event
+                .sent_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis(),
+            request: event.request,
+            status,
+            latency_ms: event.latency.as_millis(),
+            worker: event.worker.as_deref().unwrap_or_default(),
+            error,
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{}",
+            self.timestamp_ms,
+            self.request,
+            csv_field(&self.status),
+            self.latency_ms,
+            csv_field(self.worker),
+            csv_field(self.error)
+        )
+    }
+
+    fn to_json(&self) -> String {
+        serde_json::json!({
+            "timestamp_ms": self.timestamp_ms,
+            "request": self.request,
+            "status": self.status,
+            "latency_ms": self.latency_ms,
+            "worker": self.worker,
+            "error": self.error,
+        })
+        .to_string()
+    }
+}
+
+// Quotes the field when it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn serialize(events: &[ResponseEvent], format: ExportFormat) -> String {
+    let mut lines = Vec::with_capacity(events.len() + 1);
+    if format == ExportFormat::Csv {
+        lines.push(CSV_HEADER.to_string());
+    }
+    for event in events {
+        let record = Record::new(event);
+        lines.push(match format {
+            ExportFormat::Csv => record.to_csv(),
+            ExportFormat::JsonLines => record.to_json(),
+        });
+    }
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+// Writes the events to `path` in the format matching its extension, returns how many were written.
+pub fn export(events: &[ResponseEvent], path: &Path) -> io::Result<usize> {
+    let text = serialize(events, ExportFormat::from_path(path));
+    write_atomic(path, text.as_bytes())?;
+    Ok(events.len())
+}
+
+// Writes to a temporary file next to `path` and renames it over, so readers never see a partial file.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp = temp_path(path);
+    let result = File::create(&tmp).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    match result.and_then(|_| fs::rename(&tmp, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.tmp", std::process::id()));
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use reqwest::StatusCode;
+
+    use super::*;
+    use crate::response_event::FailureKind;
+
+    fn events() -> Vec<ResponseEvent> {
+        let event = ResponseEvent {
+            request: "work",
+            sent_at: UNIX_EPOCH + Duration::from_millis(1_700_000_000_123),
+            target: "127.0.0.1:80".to_string(),
+            status: Ok(StatusCode::OK),
+            latency: Duration::from_millis(42),
+            worker: Some("127.0.0.1:3001".to_string()),
+            body_snippet: "Work done".to_string(),
+            error: None,
+            attempts: 1,
+        };
+        vec![
+            event.clone(),
+            ResponseEvent {
+                status: Ok(StatusCode::SERVICE_UNAVAILABLE),
+                worker: None,
+                body_snippet: "No healthy \"workers\", try again".to_string(),
+                ..event.clone()
+            },
+            ResponseEvent {
+                sent_at: SystemTime::UNIX_EPOCH,
+                status: Err(FailureKind::Timeout),
+                latency: Duration::from_millis(10_000),
+                worker: None,
+                error: Some("operation timed out".to_string()),
+                ..event
+            },
+        ]
+    }
+
+    #[test]
+    fn the_extension_picks_the_format() {
+        assert_eq!(
+            ExportFormat::from_path(Path::new("out.csv")),
+            ExportFormat::Csv
+        );
+        assert_eq!(
+            ExportFormat::from_path(Path::new("out.CSV")),
+            ExportFormat::Csv
+        );
+        assert_eq!(
+            ExportFormat::from_path(Path::new("out.jsonl")),
+            ExportFormat::JsonLines
+        );
+        assert_eq!(
+            ExportFormat::from_path(Path::new("results")),
+            ExportFormat::JsonLines
+        );
+    }
+
+    #[test]
+    fn csv_rows_quote_what_needs_it() {
+        assert_eq!(
+            serialize(&events(), ExportFormat::Csv),
+            "timestamp_ms,request,status,latency_ms,worker,error\n\
+             1700000000123,work,200,42,127.0.0.1:3001,\n\
+             1700000000123,work,503,42,,\"No healthy \"\"workers\"\", try again\"\n\
+             0,work,timeout,10000,,operation timed out\n"
+        );
+        assert_eq!(
+            serialize(&[], ExportFormat::Csv),
+            "timestamp_ms,request,status,latency_ms,worker,error\n"
+        );
+    }
+
+    #[test]
+    fn json_lines_hold_one_object_per_event() {
+        let text = serialize(&events(), ExportFormat::JsonLines);
+        let lines = text
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            lines[0],
+            serde_json::json!({
+                "timestamp_ms": 1_700_000_000_123u64,
+                "request": "work",
+                "status": "200",
+                "latency_ms": 42,
+                "worker": "127.0.0.1:3001",
+                "error": "",
+            })
+        );
+        assert_eq!(lines[1]["error"], "No healthy \"workers\", try again");
+        assert_eq!(lines[2]["status"], "timeout");
+        assert_eq!(lines.len(), 3);
+    }
+
+    #[test]
+    fn exports_replace_the_file_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.csv");
+        fs::write(
+            &path,
+            "a much longer previous export than the new one\n".repeat(100),
+        )
+        .unwrap();
+
+        assert_eq!(export(&events(), &path).unwrap(), 3);
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            serialize(&events(), ExportFormat::Csv)
+        );
+        let files = fs::read_dir(dir.path()).unwrap().count();
+        assert_eq!(files, 1, "the temporary file was left behind");
+    }
+
+    #[test]
+    fn failed_exports_leave_nothing_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        // Renaming a file over a directory fails after the temporary file was written.
+        let path = dir.path().join("results.jsonl");
+        fs::create_dir(&path).unwrap();
+
+        assert!(export(&events(), &path).is_err());
+        assert!(path.is_dir());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert!(export(&events(), &dir.path().join("missing/results.csv")).is_err());
+    }
+}
@@ -0,0 +1,200 @@
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tokio::time::{Duration, Instant};
+
+use crate::config::Targets;
+use crate::executor;
+use crate::requests::{self, RequestType};
+use crate::response_event::ResponseEvent;
+use crate::retry::RetryPolicy;
+use crate::scenario::Scenario;
+use crate::stats::Stats;
+
+const DEFAULT_MAX_ERROR_RATE: f64 = 0.0;
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub scenario: String,
+    pub passed: bool,
+    pub max_error_rate: f64,
+    pub error_rate: f64,
+    pub elapsed_ms: u128,
+    // Control steps (algo, setup, reset) that did not get a 2xx response.
+    pub failed_steps: Vec<String>,
+    // Work requests sent by the load steps.
+    pub requests: serde_json::Value,
+}
+
+impl Report {
+    fn new(
+        scenario: String,
+        max_error_rate: f64,
+        stats: &Stats,
+        failed_steps: Vec<String>,
+        elapsed: Duration,
+    ) -> Self {
+        Report {
+            scenario,
+            passed: passes(stats.error_rate(), max_error_rate, &failed_steps),
+            max_error_rate,
+            error_rate: stats.error_rate(),
+            elapsed_ms: elapsed.as_millis(),
+            failed_steps,
+            requests: stats.to_json(),
+        }
+    }
+}
+
+// Everything received during a run, only work requests count towards the stats.
+#[derive(Default)]
+struct Recording {
+    stats: Stats,
+    events: Vec<ResponseEvent>,
+}
+
+pub fn passes(error_rate: f64, max_error_rate: f64, failed_steps: &[String]) -> bool {
+    failed_steps.is_empty() && error_rate <= max_error_rate
+}
+
+// Runs the scenario, printing progress to stdout.
+// `max_error_rate` overrides the threshold set in the scenario.
+// Returns the report along with every response received.
+pub async fn run(
+    scenario: &Scenario,
+    max_error_rate: Option<f64>,
+    client: Arc<reqwest::Client>,
+    targets: &Targets,
+) -> (Report, Vec<ResponseEvent>) {
+    let started_at = Instant::now();
+    let name = scenario
+        .name
+        .clone()
+        .unwrap_or_else(|| "scenario".to_string());
+    let max_error_rate = max_error_rate
+        .or(scenario.max_error_rate)
+        .unwrap_or(DEFAULT_MAX_ERROR_RATE);
+
+    let recording = Arc::new(Mutex::new(Recording::default()));
+
+    let targets = targets.clone();
+    let sink = recording.clone();
+    let send = move |request: RequestType| {
+        let is_work = request.name() == RequestType::WORK;
+        let response = requests::perform(&client, &targets, request, RetryPolicy::NONE);
+        let sink = sink.clone();
+        async move {
+            let event = response.await;
+            let mut recording = sink.lock().unwrap();
+            if is_work {
+                recording.stats.record(&event);
+            }
+            recording.events.push(event.clone());
+            event
+        }
+    };
+    let failed_steps = executor::run(scenario, send, |line| println!("{}", line)).await;
+
+    let recording = std::mem::take(&mut *recording.lock().unwrap());
+    let report = Report::new(
+        name,
+        max_error_rate,
+        &recording.stats,
+        failed_steps,
+        started_at.elapsed(),
+    );
+    (report, recording.events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{self, Reply};
+
+    // Answers every /work request with `work_status` and anything else with 200.
+    async fn work_server(work_status: &'static str) -> reqwest::Url {
+        let address = mock_server::serve(move |_, request| {
+            let status = if request.contains(" /work ") {
+                work_status
+            } else {
+                "200 OK"
+            };
+            Reply::new(status, &[("x-served-by", "w1")], "")
+        })
+        .await;
+        format!("http://{}", address).parse().unwrap()
+    }
+
+    async fn run_against(
+        scenario: &str,
+        max_error_rate: Option<f64>,
+        work_status: &'static str,
+    ) -> (Report, Vec<ResponseEvent>) {
+        let server = work_server(work_status).await;
+        let targets = Targets {
+            load_balancer: server.clone(),
+            compare: None,
+            workers: vec![server],
+        };
+        let scenario = Scenario::parse(scenario).unwrap();
+        run(
+            &scenario,
+            max_error_rate,
+            Arc::new(reqwest::Client::new()),
+            &targets,
+        )
+        .await
+    }
+
+    #[test]
+    fn runs_pass_within_the_error_rate_and_without_failed_steps() {
+        assert!(passes(0.0, 0.0, &[]));
+        assert!(passes(0.05, 0.05, &[]));
+        assert!(!passes(0.06, 0.05, &[]));
+        assert!(!passes(0.0, 1.0, &["reset worker 1: HTTP 500".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn a_scenario_against_mock_servers_reports_its_work() {
+        let (report, events) = run_against(
+            r#"{"name": "smoke", "repeat": 2, "steps": [
+                {"action": "algo", "algo": "round_robin"},
+                {"action": "setup_worker", "server": 0, "min_duration": 1, "max_duration": 2},
+                {"action": "work", "requests": 3},
+                {"action": "load", "requests": 4, "rps": 100}
+            ]}"#,
+            None,
+            "200 OK",
+        )
+        .await;
+
+        assert_eq!(report.scenario, "smoke");
+        assert!(report.passed, "{:?}", report);
+        assert!(report.failed_steps.is_empty());
+        assert_eq!(report.error_rate, 0.0);
+        // Only the 14 work requests are in the stats, every response is recorded.
+        assert_eq!(report.requests["total"], 14);
+        assert_eq!(report.requests["per_worker"]["w1"], 14);
+        assert_eq!(events.len(), 18);
+    }
+
+    #[tokio::test]
+    async fn failing_work_fails_the_run_past_the_threshold() {
+        let scenario = r#"{"max_error_rate": 0.5, "steps": [{"action": "work", "requests": 2}]}"#;
+
+        let (report, _) = run_against(scenario, None, "500 Internal Server Error").await;
+        assert!(!report.passed);
+        assert_eq!(report.error_rate, 1.0);
+        assert_eq!(report.max_error_rate, 0.5);
+        assert!(report.failed_steps.is_empty());
+
+        // The command line threshold wins over the scenario's.
+        let (report, _) = run_against(scenario, Some(1.0), "500 Internal Server Error").await;
+        assert!(report.passed);
+        assert_eq!(report.max_error_rate, 1.0);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["scenario"], "scenario");
+        assert_eq!(json["requests"]["errors"], 2);
+    }
+}
@@ -0,0 +1,221 @@
+use lb_api::{AlgorithmsResponse, StatsResponse};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+
+// Fetches one of the balancer's read endpoints and renders its response with `format`.
+// Balancers predating the endpoint answer 404, which is reported as such rather than as an error.
+pub async fn fetch<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    req: reqwest::Request,
+    format: fn(&T) -> Vec<String>,
+) -> Result<Vec<String>, String> {
+    let path = req.url().path().to_string();
+    let response = client.execute(req).await.map_err(|e| e.to_string())?;
+    let status = response.status();
+    if status == StatusCode::NOT_FOUND {
+        return Err(format!(
+            "The load balancer doesn't support GET {}, it may be an older version",
+            path
+        ));
+    }
+    let text = response.text().await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(format!(
+            "GET {} returned HTTP {}: {}",
+            path,
+            status.as_u16(),
+            text.trim()
+        ));
+    }
+    let body = serde_json::from_str::<T>(&text)
+        .map_err(|e| format!("GET {} returned an unexpected response: {}", path, e))?;
+    Ok(format(&body))
+}
+
+// The current algorithm, then every algorithm with whether it may be switched to, e.g.
+// `  round_robin        enabled   Each server in turn`.
+pub fn format_algorithm(response: &AlgorithmsResponse) -> Vec<String> {
+    let mut lines = vec![format!("Algorithm: {}", response.current)];
+    for algorithm in &response.algorithms {
+        lines.push(format!(
+            "  {:<18} {:<9} {}",
+            algorithm.name,
+            if algorithm.enabled {
+                "enabled"
+            } else {
+                "disabled"
+            },
+            algorithm.description
+        ));
+    }
+    lines
+}
+
+// A table with one row per server.
+pub fn format_servers(response: &StatsResponse) -> Vec<String> {
+    if response.servers.is_empty() {
+        return vec!["The load balancer has no servers".to_string()];
+    }
+
+    let mut lines = vec![format!(
+        "{:<24} {:<9} {:>11} {:>10} {:>10}",
+        "Server", "Health", "Connections", "Bytes in", "Bytes out"
+    )];
+    for server in &response.servers {
+        lines.push(format!(
+            "{:<24} {:<9} {:>11} {:>10} {:>10}",
+            server.address,
+            if server.healthy {
+                "healthy"
+            } else {
+                "unhealthy"
+            },
+            server.connections,
+            server.bytes_in,
+            server.bytes_out
+        ));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use lb_api::{AlgorithmInfo, ServerStats};
+
+    use super::*;
+    use crate::mock_server::{self, Reply};
+
+    #[test]
+    fn algorithms_list_the_current_one_first() {
+        let response = AlgorithmsResponse {
+            current: "maglev".to_string(),
+            algorithms: vec![
+                AlgorithmInfo {
+                    name: "round_robin".to_string(),
+                    enabled: true,
+                    description: "Each server in turn".to_string(),
+                },
+                AlgorithmInfo {
+                    name: "maglev".to_string(),
+                    enabled: false,
+                    description: "Consistent hashing".to_string(),
+                },
+            ],
+        };
+
+        assert_eq!(
+            format_algorithm(&response),
+            vec![
+                "Algorithm: maglev",
+                "  round_robin        enabled   Each server in turn",
+                "  maglev             disabled  Consistent hashing",
+            ]
+        );
+    }
+
+    #[test]
+    fn servers_are_tabled_with_their_counts() {
+        let response = StatsResponse {
+            algorithm: "round_robin".to_string(),
+            servers: vec![
+                ServerStats {
+                    address: "127.0.0.1:3001".to_string(),
+                    healthy: true,
+                    connections: 2,
+                    bytes_in: 512,
+                    bytes_out: 2048,
+                },
+                ServerStats {
+                    address: "127.0.0.1:3002".to_string(),
+                    healthy: false,
+                    connections: 0,
+                    bytes_in: 0,
+                    bytes_out: 0,
+                },
+            ],
+            queue: None,
+        };
+
+        assert_eq!(
+            format_servers(&response),
+            vec![
+                "Server                   Health    Connections   Bytes in  Bytes out",
+                "127.0.0.1:3001           healthy             2        512       2048",
+                "127.0.0.1:3002           unhealthy           0          0          0",
+            ]
+        );
+    }
+
+    #[test]
+    fn no_servers_is_said_plainly() {
+        let response = StatsResponse {
+            algorithm: "round_robin".to_string(),
+            servers: Vec::new(),
+            queue: None,
+        };
+        assert_eq!(
+            format_servers(&response),
+            vec!["The load balancer has no servers"]
+        );
+    }
+
+    // Answers a single request with `status` and `body`, then closes the connection.
+    async fn fetched(status: &str, body: &str) -> Result<Vec<String>, String> {
+        let (status, body) = (status.to_string(), body.to_string());
+        let address = mock_server::serve(move |_, _| Reply::new(&status, &[], &body)).await;
+
+        let client = reqwest::Client::new();
+        let req = client
+            .get(format!("http://{}/lb/algorithms", address))
+            .build()
+            .unwrap();
+        fetch(&client, req, format_algorithm).await
+    }
+
+    #[tokio::test]
+    async fn fetched_responses_are_formatted() {
+        let lines = fetched(
+            "200 OK",
+            r#"{"current": "round_robin", "algorithms": [
+                {"name": "round_robin", "enabled": true, "description": "Each server in turn"}
+            ]}"#,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            lines,
+            vec![
+                "Algorithm: round_robin",
+                "  round_robin        enabled   Each server in turn",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn older_balancers_are_reported_gracefully() {
+        assert_eq!(
+            fetched("404 Not Found", "").await.unwrap_err(),
+            "The load balancer doesn't support GET /lb/algorithms, it may be an older version"
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_and_unexpected_responses_are_errors() {
+        assert_eq!(
+            fetched("503 Service Unavailable", "draining\n")
+                .await
+                .unwrap_err(),
+            "GET /lb/algorithms returned HTTP 503: draining"
+        );
+
+        let error = fetched("200 OK", r#"{"algo": "round_robin"}"#)
+            .await
+            .unwrap_err();
+        assert!(
+            error.starts_with("GET /lb/algorithms returned an unexpected response: "),
+            "{}",
+            error
+        );
+    }
+}
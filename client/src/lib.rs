@@ -0,0 +1,19 @@
+pub mod burst;
+pub mod comparison;
+pub mod config;
+pub mod distribution;
+pub mod executor;
+pub mod export;
+pub mod headless;
+pub mod lb_info;
+pub mod load;
+#[cfg(test)]
+mod mock_server;
+pub mod reachability;
+pub mod requests;
+pub mod response_event;
+pub mod retry;
+pub mod scenario;
+pub mod session_log;
+pub mod stats;
+pub mod throughput;
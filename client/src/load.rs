@@ -0,0 +1,454 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use tokio::runtime::Handle;
+use tokio::sync::{watch, Semaphore};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep_until, timeout, Duration, Instant};
+
+#[derive(Clone, Copy, Debug)]
+pub struct LoadSettings {
+    pub rps: f64,
+    pub concurrency: usize,
+    // `Duration::MAX` keeps running until stopped.
+    pub duration: Duration,
+    pub multiplier: u64,
+    // Stops after this many sends even if the duration hasn't elapsed.
+    pub requests: Option<u64>,
+}
+
+// Counters shared between the generator and whoever displays its progress.
+pub struct LoadProgress {
+    started_at: Instant,
+    sent: AtomicU64,
+    completed: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl LoadProgress {
+    pub fn new() -> Self {
+        LoadProgress {
+            started_at: Instant::now(),
+            sent: AtomicU64::new(0),
+            completed: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+        }
+    }
+
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::SeqCst)
+    }
+
+    pub fn completed(&self) -> u64 {
+        self.completed.load(Ordering::SeqCst)
+    }
+
+    pub fn failed(&self) -> u64 {
+        self.failed.load(Ordering::SeqCst)
+    }
+
+    pub fn achieved_rps(&self) -> f64 {
+        let elapsed = self.started_at.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            (self.completed() + self.failed()) as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "sent: {}, completed: {}, failed: {}, achieved: {:.1} rps",
+            self.sent(),
+            self.completed(),
+            self.failed(),
+            self.achieved_rps()
+        )
+    }
+}
+
+impl Default for LoadProgress {
+    fn default() -> Self {
+        LoadProgress::new()
+    }
+}
+
+// Spaces sends evenly at the target rate, measured from the start so delays don't accumulate.
+pub struct Pacer {
+    start: Instant,
+    interval: Duration,
+    scheduled: u32,
+}
+
+impl Pacer {
+    pub fn new(start: Instant, rps: f64) -> Self {
+        Pacer {
+            start,
+            interval: Duration::from_secs_f64(1.0 / rps.max(0.001)),
+            scheduled: 0,
+        }
+    }
+
+    pub fn next_deadline(&mut self) -> Instant {
+        let deadline = self.start + self.interval * self.scheduled;
+        self.scheduled += 1;
+        deadline
+    }
+}
+
+// Fires `send` at the configured pace until the duration elapses or `stop` is set.
+// `send` resolves to whether the request succeeded.
+pub async fn run<F, Fut>(
+    settings: LoadSettings,
+    progress: Arc<LoadProgress>,
+    mut stop: watch::Receiver<bool>,
+    send: F,
+) where
+    F: Fn() -> Fut,
+    Fut: Future<Output = bool> + Send + 'static,
+{
+    let start = Instant::now();
+    let end = start.checked_add(settings.duration);
+    let mut pacer = Pacer::new(start, settings.rps);
+    let concurrency = settings.concurrency.max(1);
+    let slots = Arc::new(Semaphore::new(concurrency));
+
+    loop {
+        let deadline = pacer.next_deadline();
+        if end.is_some_and(|end| deadline >= end)
+            || settings
+                .requests
+                .is_some_and(|limit| progress.sent() >= limit)
+        {
+            break;
+        }
+
+        tokio::select! {
+            _ = sleep_until(deadline) => {}
+            _ = stop.wait_for(|stopped| *stopped) => return,
+        }
+
+        // At the concurrency cap sends are delayed, which lowers the achieved rate.
+        let permit = tokio::select! {
+            permit = slots.clone().acquire_owned() => permit.unwrap(),
+            _ = stop.wait_for(|stopped| *stopped) => return,
+        };
+        if end.is_some_and(|end| Instant::now() >= end) {
+            break;
+        }
+
+        progress.sent.fetch_add(1, Ordering::SeqCst);
+        let request = send();
+        let progress = progress.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            if request.await {
+                progress.completed.fetch_add(1, Ordering::SeqCst);
+            } else {
+                progress.failed.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+    }
+
+    // Let the requests still in flight finish so the final counts are complete.
+    tokio::select! {
+        _ = slots.acquire_many(concurrency as u32) => {}
+        _ = stop.wait_for(|stopped| *stopped) => {}
+    }
+}
+
+// A generator spawned with `run`, stopped through its handle.
+pub struct LoadRun {
+    pub progress: Arc<LoadProgress>,
+    stop: watch::Sender<bool>,
+    handle: JoinHandle<()>,
+}
+
+impl LoadRun {
+    pub fn spawn<F, Fut>(runtime: &Handle, settings: LoadSettings, send: F) -> Self
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = bool> + Send + 'static,
+    {
+        let progress = Arc::new(LoadProgress::new());
+        let (stop, stop_rx) = watch::channel(false);
+        let handle = runtime.spawn(run(settings, progress.clone(), stop_rx, send));
+        LoadRun {
+            progress,
+            stop,
+            handle,
+        }
+    }
+
+    // Asks the generator to stop without waiting for it.
+    pub fn request_stop(&self) {
+        let _ = self.stop.send(true);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    // Stops the generator, aborting it if it hasn't ended within `grace`.
+    pub async fn stop(self, grace: Duration) {
+        self.request_stop();
+        let abort = self.handle.abort_handle();
+        if timeout(grace, self.handle).await.is_err() {
+            abort.abort();
+        }
+    }
+}
+
+// Continuous load toggled on and off, with at most one generator running at a time.
+pub struct BackgroundLoad {
+    settings: LoadSettings,
+    run: Option<LoadRun>,
+}
+
+impl BackgroundLoad {
+    pub fn new(settings: LoadSettings) -> Self {
+        BackgroundLoad {
+            settings,
+            run: None,
+        }
+    }
+
+    pub fn settings(&self) -> LoadSettings {
+        self.settings
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.run.is_some()
+    }
+
+    // Starts a generator with `spawn` unless one is running, returns whether it did.
+    pub fn start(&mut self, spawn: impl FnOnce(LoadSettings) -> LoadRun) -> bool {
+        if self.run.is_some() {
+            return false;
+        }
+        self.run = Some(spawn(self.settings));
+        true
+    }
+
+    // Stops the running generator, if any, returns whether there was one.
+    pub async fn stop(&mut self, grace: Duration) -> bool {
+        match self.run.take() {
+            Some(run) => {
+                run.stop(grace).await;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    type SinkFuture = std::pin::Pin<Box<dyn Future<Output = bool> + Send>>;
+
+    // A sink recording when each request was sent, answering `succeed` after `latency`.
+    fn sink(
+        latency: Duration,
+        succeed: bool,
+    ) -> (Arc<Mutex<Vec<Instant>>>, impl Fn() -> SinkFuture) {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let record = sent.clone();
+        let send = move || {
+            record.lock().unwrap().push(Instant::now());
+            Box::pin(async move {
+                tokio::time::sleep(latency).await;
+                succeed
+            }) as SinkFuture
+        };
+        (sent, send)
+    }
+
+    fn settings(rps: f64, duration_ms: u64) -> LoadSettings {
+        LoadSettings {
+            rps,
+            concurrency: 100,
+            duration: Duration::from_millis(duration_ms),
+            multiplier: 1,
+            requests: None,
+        }
+    }
+
+    #[test]
+    fn deadlines_are_spaced_from_the_start() {
+        let start = Instant::now();
+        let mut pacer = Pacer::new(start, 4.0);
+
+        let deadlines = (0..4)
+            .map(|_| pacer.next_deadline() - start)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            deadlines,
+            [0, 250, 500, 750].map(Duration::from_millis).to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn sends_keep_to_the_target_rate() {
+        let (sent, send) = sink(Duration::ZERO, true);
+        let progress = Arc::new(LoadProgress::new());
+        let (_stop, stopped) = watch::channel(false);
+
+        let start = Instant::now();
+        run(settings(50.0, 500), progress.clone(), stopped, send).await;
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 25);
+        // Each send in order and no earlier than its slot, 20ms apart.
+        for (i, at) in sent.iter().enumerate() {
+            let slot = Duration::from_millis(20 * i as u64);
+            let offset = *at - start;
+            assert!(offset >= slot, "send {} at {:?}", i, offset);
+        }
+        assert!(sent.is_sorted());
+        assert_eq!((progress.sent(), progress.completed()), (25, 25));
+        assert_eq!(progress.failed(), 0);
+    }
+
+    #[tokio::test]
+    async fn the_concurrency_cap_slows_sends_down() {
+        let (sent, send) = sink(Duration::from_millis(100), true);
+        let progress = Arc::new(LoadProgress::new());
+        let (_stop, stopped) = watch::channel(false);
+
+        let settings = LoadSettings {
+            concurrency: 1,
+            ..settings(100.0, 350)
+        };
+        run(settings, progress.clone(), stopped, send).await;
+
+        // One at a time, each taking 100ms.
+        assert_eq!(sent.lock().unwrap().len(), 4);
+        assert_eq!(progress.completed(), 4);
+    }
+
+    #[tokio::test]
+    async fn failures_are_counted_apart() {
+        let (_, send) = sink(Duration::ZERO, false);
+        let progress = Arc::new(LoadProgress::new());
+        let (_stop, stopped) = watch::channel(false);
+
+        let settings = LoadSettings {
+            requests: Some(3),
+            ..settings(1000.0, 10_000)
+        };
+        run(settings, progress.clone(), stopped, send).await;
+
+        assert_eq!(
+            (progress.sent(), progress.completed(), progress.failed()),
+            (3, 0, 3)
+        );
+        assert!(progress
+            .summary()
+            .starts_with("sent: 3, completed: 0, failed: 3, achieved: "));
+    }
+
+    #[tokio::test]
+    async fn stopping_ends_the_run_promptly() {
+        let (sent, send) = sink(Duration::ZERO, true);
+        let progress = Arc::new(LoadProgress::new());
+        let (stop, stopped) = watch::channel(false);
+
+        let started_at = Instant::now();
+        let running = tokio::spawn(run(
+            LoadSettings {
+                duration: Duration::MAX,
+                ..settings(20.0, 0)
+            },
+            progress,
+            stopped,
+            send,
+        ));
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        stop.send(true).unwrap();
+        running.await.unwrap();
+
+        assert!(started_at.elapsed() >= Duration::from_millis(120));
+        assert_eq!(sent.lock().unwrap().len(), 3);
+    }
+
+    fn background() -> BackgroundLoad {
+        BackgroundLoad::new(LoadSettings {
+            duration: Duration::MAX,
+            ..settings(50.0, 0)
+        })
+    }
+
+    #[tokio::test]
+    async fn background_load_starts_and_stops_once() {
+        let (sent, send) = sink(Duration::ZERO, true);
+        let send = Arc::new(send);
+        let mut load = background();
+        let spawn = |settings| {
+            let send = send.clone();
+            LoadRun::spawn(&Handle::current(), settings, move || send())
+        };
+
+        assert!(!load.is_running());
+        assert!(load.start(spawn));
+        assert!(!load.start(spawn));
+        assert!(load.is_running());
+
+        tokio::time::sleep(Duration::from_millis(110)).await;
+        assert!(load.stop(Duration::from_secs(1)).await);
+        assert!(!load.stop(Duration::from_secs(1)).await);
+        assert!(!load.is_running());
+
+        // One generator at 50 rps, a second one would have doubled the sends.
+        let count = sent.lock().unwrap().len();
+        assert!((5..=7).contains(&count), "{} sends", count);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(sent.lock().unwrap().len(), count);
+
+        assert!(load.start(spawn));
+        assert!(load.stop(Duration::from_secs(1)).await);
+    }
+
+    #[tokio::test]
+    async fn stopping_cancels_the_generator_promptly() {
+        let (_, send) = sink(Duration::from_secs(60), true);
+        let run = LoadRun::spawn(
+            &Handle::current(),
+            LoadSettings {
+                duration: Duration::MAX,
+                ..settings(1000.0, 0)
+            },
+            send,
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!run.is_finished());
+
+        // Requests still in flight don't hold the generator up: given as long as they take, it
+        // still ends well before them.
+        tokio::time::timeout(Duration::from_secs(10), run.stop(Duration::from_secs(60)))
+            .await
+            .expect("stopped with requests in flight");
+    }
+
+    #[tokio::test]
+    async fn generators_ignoring_the_stop_are_aborted_after_the_grace() {
+        let (stop, _) = watch::channel(false);
+        let handle = tokio::spawn(std::future::pending::<()>());
+        let abort = handle.abort_handle();
+        let run = LoadRun {
+            progress: Arc::new(LoadProgress::new()),
+            stop,
+            handle,
+        };
+
+        let started_at = Instant::now();
+        run.stop(Duration::from_millis(50)).await;
+        assert!(started_at.elapsed() >= Duration::from_millis(50));
+        tokio::task::yield_now().await;
+        assert!(abort.is_finished());
+    }
+}
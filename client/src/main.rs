@@ -1,81 +1,330 @@
+use client::burst::{self, BurstSettings, BurstSummary};
+use client::comparison::Comparison;
+use client::config::{Command, Config, RunArgs, Targets, WorkTarget};
+use client::distribution::Distribution;
+use client::executor;
+use client::export;
+use client::headless;
+use client::lb_info;
+use client::load::{BackgroundLoad, LoadRun, LoadSettings};
+use client::reachability::{self, Reachability};
+use client::requests::{self, send_request, InFlight, RequestType, WorkOverrides};
+use client::response_event::ResponseEvent;
+use client::retry::{self, RetryPolicy};
+use client::scenario::Scenario;
+use client::session_log::{self, SessionLog};
+use client::stats::Stats;
+use client::throughput::Throughput;
 use crossterm::event::{self, Event, KeyCode};
+use menu::{background_load_label, menu_items, retry_label, Action, Menu, MenuItem};
+use output::{LineKind, Output};
+use prompt::{Prompt, PromptField, PromptState};
 use ratatui::{
     layout::{Constraint, Direction, Layout},
-    widgets::{Block, Borders, Paragraph, Wrap},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Bar, BarChart, BarGroup, Block, Borders, Paragraph, Wrap},
 };
-use requests::{send_request, RequestType};
+use serde::de::DeserializeOwned;
+use setup_menu::{worker_name, SetupMenu, SetupMenuState};
 use std::{
+    cell::Cell,
     io::{self, Error},
-    sync::Arc,
+    ops::RangeInclusive,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
 };
-use tui_utils::{cleanup_terminal, get_end_of_wrapped_text, setup_terminal};
+use tui_utils::{cleanup_terminal, setup_terminal, Scrollback};
 
-mod requests;
+mod menu;
+mod output;
+mod prompt;
+mod setup_menu;
 
-const MAX_LOG_LINES: usize = 100;
+const STATS_WIDTH: u16 = 32;
+const PROMPT_HEIGHT: u16 = 3;
+const MULTIPLIER_RANGE: RangeInclusive<u64> = 1..=10;
+const BURST_COUNT_RANGE: RangeInclusive<u64> = 1..=1000;
+const DURATION_RANGE: RangeInclusive<u64> = 0..=60_000;
+const ERROR_PERCENT_RANGE: RangeInclusive<u64> = 0..=100;
+// How long a stopped load generator gets to end before it's aborted.
+const LOAD_STOP_GRACE: std::time::Duration = std::time::Duration::from_secs(1);
+
+// What the values of the active prompt are used for once submitted.
+enum PromptAction {
+    Work,
+    Burst,
+    SetupWorker { server: u64 },
+}
+
+struct ScenarioRun {
+    name: String,
+    progress: tokio::sync::mpsc::UnboundedReceiver<String>,
+    handle: tokio::task::JoinHandle<Vec<String>>,
+}
+
+struct BurstRun {
+    in_flight: Arc<AtomicUsize>,
+    handle: tokio::task::JoinHandle<BurstSummary>,
+}
+
+struct Context {
+    runtime: tokio::runtime::Runtime,
+    client: Arc<reqwest::Client>,
+    targets: Targets,
+    tx: tokio::sync::mpsc::Sender<ResponseEvent>,
+    in_flight: InFlight,
+    // Lines from background queries that aren't response events.
+    messages: tokio::sync::mpsc::UnboundedSender<(LineKind, String)>,
+    // Fed by response events and by load runs, whose responses aren't events.
+    throughput: Arc<Mutex<Throughput>>,
+    retry_policy: RetryPolicy,
+    // Toggled from the menu, runs already started keep the policy they started with.
+    retry_enabled: Cell<bool>,
+    // Where new work requests go, requests already created keep theirs.
+    work_target: Cell<WorkTarget>,
+    // Set in comparison mode, fed like `throughput`.
+    comparison: Option<Arc<Mutex<Comparison>>>,
+}
+
+impl Context {
+    fn retry(&self) -> RetryPolicy {
+        if self.retry_enabled.get() {
+            self.retry_policy
+        } else {
+            RetryPolicy::NONE
+        }
+    }
+}
 
 fn main() -> Result<(), Error> {
-    let client = Arc::new(
-        reqwest::Client::builder()
-            .pool_max_idle_per_host(50)
-            .build()
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?,
-    );
+    let config = lb_config::load::<Config>("client");
+    // The terminal is the UI, and `run` prints its report to stdout.
+    lb_telemetry::init_file(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+        .map_err(io::Error::other)?;
+    if let Some(Command::Run(args)) = &config.command {
+        return run_headless(&config, args);
+    }
+
+    let client = build_client(&config)?;
+    let targets = config.targets().map_err(io::Error::other)?;
 
     let mut terminal = setup_terminal()?;
     let (tx, mut rx) = tokio::sync::mpsc::channel(100);
+    let (messages, mut messages_rx) = tokio::sync::mpsc::unbounded_channel();
+
+    let ctx = Context {
+        runtime: tokio::runtime::Runtime::new().unwrap(),
+        client,
+        comparison: config
+            .compare_target
+            .as_ref()
+            .map(|compare| Arc::new(Mutex::new(Comparison::new(&targets.load_balancer, compare)))),
+        targets,
+        tx,
+        in_flight: InFlight::default(),
+        messages,
+        throughput: Arc::new(Mutex::new(Throughput::new(std::time::Instant::now()))),
+        retry_policy: config.retry_policy(),
+        retry_enabled: Cell::new(config.retry),
+        work_target: Cell::new(WorkTarget::LoadBalancer),
+    };
+    let drain_timeout = std::time::Duration::from_millis(config.drain_timeout_ms);
+    // Set once quitting while requests are still in flight.
+    let mut draining_since: Option<std::time::Instant> = None;
 
-    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let load_settings = config.load_settings();
+    let mut load_run: Option<LoadRun> = None;
+    // Independent of load runs, only the menu toggle and quitting stop it.
+    let mut background_load = BackgroundLoad::new(config.background_load_settings());
+    let burst_settings = config.burst_settings();
+    let mut prompt: Option<(PromptAction, Prompt)> = None;
+    let mut setup_menu: Option<SetupMenu> = None;
+    let mut burst_run: Option<BurstRun> = None;
 
-    let mut output = String::new();
+    let mut output = Output::new(config.max_output_lines as usize);
+    if let Some(path) = &config.log_file {
+        open_session_log(path, config.restore_lines as usize, &mut output);
+    }
+    let mut scrollback = Scrollback::default();
+    let mut stats = Stats::default();
+    let mut distribution = Distribution::new(config.distribution_window);
+    let mut events: Vec<ResponseEvent> = Vec::new();
+    let mut export_run: Option<tokio::task::JoinHandle<io::Result<usize>>> = None;
 
     terminal.clear()?;
 
-    let menu_items = vec![
-        "1 - Change algo to round_robin",
-        "2 - Change algo to least_connections",
-        "3 - Send short work",
-        "4 - Send long work",
-        "5 - Reset worker servers",
-        "6 - Worker server 2 increased duration",
-        "7 - Worker server 2 increased error rate",
-        "a - Scenario A",
-        "c - Clear output",
-        "q - Quit",
-    ];
-    let menu_item_max_len = menu_items.iter().map(|item| item.len()).max().unwrap();
+    let scenarios = load_scenarios(&config.scenarios, &mut output);
+    let scenario_items = scenarios
+        .iter()
+        .enumerate()
+        .map(|(index, (key, scenario))| {
+            MenuItem::new(*key, scenario_name(scenario), Action::Scenario(index))
+        })
+        .collect();
+    let mut menu = Menu::new(menu_items(scenario_items));
+    menu.set_label(Action::Retry, retry_label(ctx.retry_enabled.get()));
+    let mut scenario_run: Option<ScenarioRun> = None;
+
+    let check_interval = std::time::Duration::from_secs(config.connection_check_secs);
+    let mut reachability = Reachability::default();
+    let mut connection_check = Some(check_connection(&ctx));
+    let mut last_check = std::time::Instant::now();
+    // Set by the warning for an action needing the unreachable balancer, repeating it confirms.
+    let mut unconfirmed: Option<Action> = None;
 
     loop {
         terminal.draw(|frame| {
             let width = frame.area().width as usize;
 
-            let mut menu_text = String::new();
-            for item in &menu_items {
-                let last_line_len = menu_text.lines().last().map_or(0, |line| line.len());
-                if last_line_len + menu_item_max_len + 1 > width {
-                    menu_text.push('\n');
-                }
-                menu_text.push_str(&format!(" {:width$}", item, width = menu_item_max_len));
-            }
+            let (menu_title, items, selected) = match &setup_menu {
+                Some(setup_menu) => (
+                    setup_menu.title(&ctx.targets),
+                    setup_menu.items(&ctx.targets),
+                    None,
+                ),
+                None => (
+                    format!(
+                        "Menu - target: {} - Up/Down and Enter, PgUp/PgDn/Home/End to scroll",
+                        work_target_name(&ctx.targets, ctx.work_target.get())
+                    ),
+                    menu.labels(),
+                    Some(menu.selected()),
+                ),
+            };
+            let menu_lines = menu_lines(&items, selected, width);
 
-            let menu_text_height = menu_text.lines().count() as u16;
+            let menu_text_height = menu_lines.len() as u16;
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints(
-                    [Constraint::Length(menu_text_height + 2), Constraint::Min(0)].as_ref(),
+                    [
+                        Constraint::Length(1),
+                        Constraint::Length(menu_text_height + 2),
+                        Constraint::Min(0),
+                    ]
+                    .as_ref(),
                 )
                 .split(frame.area());
+            let banner_style = match reachability {
+                Reachability::Unknown => LineKind::Info.style(),
+                Reachability::Reachable => LineKind::Success.style(),
+                Reachability::Unreachable(_) => LineKind::Failure.style(),
+            };
+            let banner = Paragraph::new(Line::styled(
+                format!(
+                    " Load balancer {}: {}",
+                    ctx.targets.load_balancer, reachability
+                ),
+                banner_style.add_modifier(Modifier::REVERSED),
+            ));
+            frame.render_widget(banner, chunks[0]);
+            let bottom_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(0), Constraint::Length(STATS_WIDTH)].as_ref())
+                .split(chunks[2]);
+
+            let menu_block = Paragraph::new(menu_lines)
+                .block(Block::default().borders(Borders::ALL).title(menu_title));
 
-            let menu = Paragraph::new(menu_text)
-                .block(Block::default().borders(Borders::ALL).title("Menu"));
+            let mut live_output = output.styled_lines();
+            let info_style = LineKind::Info.style();
+            let load_line = load_run
+                .as_ref()
+                .map(|run| format!("Load run: {}", run.progress.summary()));
+            let burst_line = burst_run
+                .as_ref()
+                .map(|run| format!("Burst in flight: {}", run.in_flight.load(Ordering::SeqCst)));
+            for line in [&load_line, &burst_line].into_iter().flatten() {
+                live_output.push(Line::styled(line.as_str(), info_style));
+            }
 
-            let text = get_end_of_wrapped_text(&output, chunks[1]);
+            let output_area = match &prompt {
+                Some((_, prompt)) => {
+                    let output_chunks = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Length(PROMPT_HEIGHT), Constraint::Min(0)])
+                        .split(bottom_chunks[0]);
+                    let prompt_block = Paragraph::new(prompt.render())
+                        .block(Block::default().borders(Borders::ALL).title("Input"));
+                    frame.render_widget(prompt_block, output_chunks[0]);
+                    output_chunks[1]
+                }
+                None => bottom_chunks[0],
+            };
+            let text = scrollback.view(&live_output, output_area);
+            let mut output_title = format!(
+                "Output - {}",
+                ctx.throughput
+                    .lock()
+                    .unwrap()
+                    .summary(std::time::Instant::now())
+            );
+            let in_flight = ctx.in_flight.count();
+            if in_flight > 0 {
+                output_title.push_str(&format!(" - {} in flight", in_flight));
+            }
+            if scrollback.is_following() {
+                output_title.push_str(" - following");
+            } else {
+                let (line, total) = scrollback.position();
+                output_title.push_str(&format!(" - line {}/{}, End to follow", line, total));
+            }
             let output_block = Paragraph::new(text)
-                .block(Block::default().borders(Borders::ALL).title("Output"))
+                .block(Block::default().borders(Borders::ALL).title(output_title))
                 .wrap(Wrap { trim: false });
 
-            frame.render_widget(menu, chunks[0]);
-            frame.render_widget(output_block, chunks[1]);
+            frame.render_widget(menu_block, chunks[1]);
+            let stats_block = Paragraph::new(stats.render())
+                .block(Block::default().borders(Borders::ALL).title("Stats"));
+
+            let shares = distribution.shares();
+            let comparison_text = ctx
+                .comparison
+                .as_ref()
+                .map(|comparison| comparison.lock().unwrap().render());
+            let comparison_height = comparison_text
+                .as_ref()
+                .map_or(0, |text| text.lines().count() as u16 + 2);
+            let side_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Min(0),
+                    Constraint::Length(comparison_height),
+                    Constraint::Length(shares.len().max(1) as u16 + 2),
+                ])
+                .split(bottom_chunks[1]);
+            let bars = shares
+                .iter()
+                .map(|(worker, share)| {
+                    Bar::default()
+                        .value(share.round() as u64)
+                        .label(Line::from(worker.to_string()))
+                        .text_value(format!("{:.0}%", share))
+                })
+                .collect::<Vec<_>>();
+            let distribution_chart = BarChart::default()
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(format!("Workers - last {}", distribution.len())),
+                )
+                .direction(Direction::Horizontal)
+                .bar_width(1)
+                .bar_gap(0)
+                .max(100)
+                .data(BarGroup::default().bars(&bars));
+
+            frame.render_widget(output_block, output_area);
+            frame.render_widget(stats_block, side_chunks[0]);
+            if let Some(text) = comparison_text {
+                let comparison_block = Paragraph::new(text)
+                    .block(Block::default().borders(Borders::ALL).title("A vs B"));
+                frame.render_widget(comparison_block, side_chunks[1]);
+            }
+            frame.render_widget(distribution_chart, side_chunks[2]);
         })?;
 
         if event::poll(std::time::Duration::from_millis(100))? {
@@ -84,138 +333,816 @@ fn main() -> Result<(), Error> {
                     continue;
                 }
 
+                if let Some((action, active)) = &mut prompt {
+                    match active.handle_key(key_event.code) {
+                        PromptState::Pending => {}
+                        PromptState::Cancelled => {
+                            prompt = None;
+                            output.info("Input cancelled");
+                        }
+                        PromptState::Done(values) => match action {
+                            PromptAction::Work => {
+                                prompt = None;
+                                let multiplier = values[0].parse().unwrap_or(1);
+                                output.info(format!(
+                                    "Sending request to do work with multiplier {}...",
+                                    multiplier
+                                ));
+                                do_work(&ctx, multiplier, WorkOverrides::default());
+                            }
+                            PromptAction::SetupWorker { server } => {
+                                let server = *server;
+                                prompt = None;
+                                let (min_duration, max_duration, error_percent) =
+                                    parse_custom_setup(&values);
+                                output.info(format!(
+                                    "Applying custom setup to {}: {}-{}ms, {}% errors...",
+                                    worker_name(&ctx.targets, server),
+                                    min_duration,
+                                    max_duration,
+                                    error_percent
+                                ));
+                                setup_worker(
+                                    &ctx,
+                                    server,
+                                    min_duration,
+                                    max_duration,
+                                    error_percent as f64 / 100.0,
+                                );
+                            }
+                            PromptAction::Burst => {
+                                prompt = None;
+                                let settings = parse_burst_settings(&values);
+                                output.info(format!(
+                                    "Sending burst of {} requests with multiplier {}...",
+                                    settings.count, settings.multiplier
+                                ));
+                                burst_run = Some(start_burst(&ctx, settings));
+                            }
+                        },
+                    }
+                    continue;
+                }
+
+                if let Some(menu) = &mut setup_menu {
+                    match menu.handle_key(key_event.code, ctx.targets.worker_count()) {
+                        SetupMenuState::Pending => {}
+                        SetupMenuState::Cancelled => setup_menu = None,
+                        SetupMenuState::Selected { server, preset } => {
+                            setup_menu = None;
+                            match preset.request(server) {
+                                Some(request) => {
+                                    output.info(format!(
+                                        "Applying preset {} to {}...",
+                                        preset,
+                                        worker_name(&ctx.targets, server)
+                                    ));
+                                    send(&ctx, request);
+                                }
+                                None => prompt = Some(custom_setup_prompt(server)),
+                            }
+                        }
+                    }
+                    continue;
+                }
+
                 match key_event.code {
-                    KeyCode::Char('1') => {
-                        output.push_str("\nSending request to change algo to round_robin...\n");
-                        change_algorithm(&runtime, client.clone(), tx.clone(), "round_robin");
-                    }
-                    KeyCode::Char('2') => {
-                        output
-                            .push_str("\nSending request to change algo to least_connections...\n");
-                        change_algorithm(&runtime, client.clone(), tx.clone(), "least_connections");
-                    }
-                    KeyCode::Char('3') => {
-                        output.push_str("\nSending request to do short work...\n");
-                        do_work(&runtime, client.clone(), tx.clone(), 1);
-                    }
-                    KeyCode::Char('4') => {
-                        output.push_str("\nSending request to do long work...\n");
-                        do_work(&runtime, client.clone(), tx.clone(), 10);
-                    }
-                    KeyCode::Char('5') => {
-                        output.push_str("\nSending requests to reset worker servers...\n");
-                        setup_worker(&runtime, client.clone(), tx.clone(), 0, 10, 1000, 0.0);
-                        setup_worker(&runtime, client.clone(), tx.clone(), 1, 10, 1000, 0.0);
-                        setup_worker(&runtime, client.clone(), tx.clone(), 2, 10, 1000, 0.0);
-                    }
-                    KeyCode::Char('6') => {
-                        output.push_str(
-                            "\nSending request to worker server 2 to increse duration...\n",
-                        );
-                        setup_worker(&runtime, client.clone(), tx.clone(), 1, 1000, 2000, 0.0);
+                    KeyCode::PageUp => scrollback.page_up(),
+                    KeyCode::PageDown => scrollback.page_down(),
+                    KeyCode::Home => scrollback.home(),
+                    KeyCode::End => scrollback.end(),
+                    KeyCode::Esc => {
+                        if let Some(run) = scenario_run.take() {
+                            run.handle.abort();
+                            output.info(format!("Scenario {} aborted", run.name));
+                        }
+                    }
+                    _ => {}
+                }
+
+                let Some(action) = menu.handle_key(key_event.code) else {
+                    continue;
+                };
+                if let Reachability::Unreachable(reason) = &reachability {
+                    if action.needs_balancer() && unconfirmed != Some(action) {
+                        output.warning(format!(
+                            "The load balancer is unreachable ({}), press again to send anyway",
+                            reason
+                        ));
+                        unconfirmed = Some(action);
+                        continue;
+                    }
+                }
+                unconfirmed = None;
+                match action {
+                    Action::RoundRobin => {
+                        output.info("Sending request to change algo to round_robin...");
+                        change_algorithm(&ctx, "round_robin");
+                    }
+                    Action::LeastConnections => {
+                        output.info("Sending request to change algo to least_connections...");
+                        change_algorithm(&ctx, "least_connections");
+                    }
+                    Action::ShortWork => {
+                        output.info("Sending request to do short work...");
+                        do_work(&ctx, 1, WorkOverrides::default());
+                    }
+                    Action::LongWork => {
+                        output.info("Sending request to do long work...");
+                        do_work(&ctx, 10, WorkOverrides::default());
+                    }
+                    Action::CustomWork => {
+                        prompt = Some((
+                            PromptAction::Work,
+                            Prompt::new(vec![PromptField::number(
+                                "Work multiplier",
+                                1,
+                                MULTIPLIER_RANGE,
+                            )]),
+                        ));
+                    }
+                    Action::SimulatedError => {
+                        output.info("Sending request to do work with simulated error...");
+                        let overrides = WorkOverrides {
+                            error: true,
+                            ..WorkOverrides::default()
+                        };
+                        do_work(&ctx, 1, overrides);
+                    }
+                    Action::SimulatedDelay => {
+                        output.info("Sending request to do work with simulated delay...");
+                        let overrides = WorkOverrides {
+                            delay_ms: Some(3000),
+                            ..WorkOverrides::default()
+                        };
+                        do_work(&ctx, 1, overrides);
+                    }
+                    Action::SwitchTarget => {
+                        let target = ctx.targets.next_work_target(ctx.work_target.get());
+                        ctx.work_target.set(target);
+                        output.info(format!(
+                            "Sending work to {}",
+                            work_target_name(&ctx.targets, target)
+                        ));
                     }
-                    KeyCode::Char('7') => {
-                        output.push_str(
-                            "\nSending request to worker server 2 to increase error rate...\n",
+                    Action::SetupWorker => setup_menu = Some(SetupMenu::SelectWorker),
+                    Action::ScenarioA => {
+                        output.info("Running scenario A...");
+                        scenario_a(&ctx);
+                    }
+                    Action::StartLoad => {
+                        if load_run.is_some() {
+                            output.info("A load run is already active, stop it first");
+                        } else {
+                            output.info(format!(
+                                "Starting load run: {} rps, concurrency {}, {}s, multiplier {}",
+                                load_settings.rps,
+                                load_settings.concurrency,
+                                load_settings.duration.as_secs(),
+                                load_settings.multiplier
+                            ));
+                            load_run = Some(start_load(&ctx, load_settings));
+                        }
+                    }
+                    Action::StopLoad => {
+                        if let Some(run) = &load_run {
+                            output.info("Stopping load run...");
+                            run.request_stop();
+                        }
+                    }
+                    Action::BackgroundLoad => {
+                        if ctx.runtime.block_on(background_load.stop(LOAD_STOP_GRACE)) {
+                            output.info("Background load stopped");
+                        } else {
+                            let settings = background_load.settings();
+                            output.info(format!(
+                                "Background load started: {} rps, multiplier {}",
+                                settings.rps, settings.multiplier
+                            ));
+                            background_load.start(|settings| start_load(&ctx, settings));
+                        }
+                        menu.set_label(
+                            Action::BackgroundLoad,
+                            background_load_label(background_load.is_running()),
                         );
-                        setup_worker(&runtime, client.clone(), tx.clone(), 1, 500, 1000, 0.33);
                     }
-                    KeyCode::Char('a') => {
-                        output.push_str("\nRunning scenario A...\n");
-                        scenario_a(&runtime, client.clone(), tx.clone());
+                    Action::Retry => {
+                        let enabled = !ctx.retry_enabled.get();
+                        ctx.retry_enabled.set(enabled);
+                        menu.set_label(Action::Retry, retry_label(enabled));
+                        if enabled {
+                            output.info(format!(
+                                "Retries enabled: up to {}, backoff from {}ms",
+                                ctx.retry_policy.max_retries,
+                                ctx.retry_policy.backoff.as_millis()
+                            ));
+                        } else {
+                            output.info("Retries disabled");
+                        }
                     }
-                    KeyCode::Char('c') => {
-                        output = String::new();
+                    Action::Burst => {
+                        if burst_run.is_some() {
+                            output.info("A burst is still in flight, wait for it to finish");
+                        } else {
+                            prompt = Some((
+                                PromptAction::Burst,
+                                Prompt::new(vec![
+                                    PromptField::number(
+                                        "Burst request count",
+                                        burst_settings.count as u64,
+                                        BURST_COUNT_RANGE,
+                                    ),
+                                    PromptField::number(
+                                        "Work multiplier",
+                                        burst_settings.multiplier,
+                                        MULTIPLIER_RANGE,
+                                    ),
+                                ]),
+                            ));
+                        }
                     }
-                    KeyCode::Char('q') => {
-                        output.push_str("\nQuitting...");
-                        break;
+                    Action::Export => {
+                        if export_run.is_some() {
+                            output.info("An export is already in progress");
+                        } else {
+                            output.info(format!(
+                                "Exporting {} responses to {}...",
+                                events.len(),
+                                config.export_path.display()
+                            ));
+                            let events = events.clone();
+                            let path = config.export_path.clone();
+                            export_run = Some(
+                                ctx.runtime
+                                    .spawn_blocking(move || export::export(&events, &path)),
+                            );
+                        }
+                    }
+                    Action::Scenario(index) => {
+                        let scenario = &scenarios[index].1;
+                        if let Some(run) = &scenario_run {
+                            output.info(format!(
+                                "Scenario {} is still running, Esc to abort it",
+                                run.name
+                            ));
+                        } else {
+                            output.info(format!(
+                                "Running scenario {}, Esc to abort...",
+                                scenario_name(scenario)
+                            ));
+                            scenario_run = Some(start_scenario(&ctx, scenario));
+                        }
+                    }
+                    Action::ShowAlgorithm => {
+                        output.info("Fetching current algo...");
+                        query(&ctx, RequestType::GetAlgorithm, lb_info::format_algorithm);
+                    }
+                    Action::ListServers => {
+                        output.info("Fetching balancer servers...");
+                        query(&ctx, RequestType::ListServers, lb_info::format_servers);
+                    }
+                    Action::CheckConnection => {
+                        if connection_check.is_none() {
+                            output.info("Checking the connection to the load balancer...");
+                            connection_check = Some(check_connection(&ctx));
+                            last_check = std::time::Instant::now();
+                        }
+                    }
+                    Action::Clear => {
+                        output.clear();
+                        stats.reset();
+                        if let Some(comparison) = &ctx.comparison {
+                            comparison.lock().unwrap().reset();
+                        }
+                        distribution.reset();
+                        events.clear();
+                    }
+                    Action::Quit => {
+                        if let Some(run) = load_run.take() {
+                            stop_load(&ctx, run);
+                        }
+                        if ctx.runtime.block_on(background_load.stop(LOAD_STOP_GRACE)) {
+                            menu.set_label(Action::BackgroundLoad, background_load_label(false));
+                        }
+                        let in_flight = ctx.in_flight.count();
+                        // A second quit while draining doesn't wait any longer.
+                        if in_flight == 0 || draining_since.is_some() {
+                            break;
+                        }
+                        output.info(format!(
+                            "Draining {} requests in flight, up to {}ms, q again to quit now...",
+                            in_flight,
+                            drain_timeout.as_millis()
+                        ));
+                        draining_since = Some(std::time::Instant::now());
                     }
-                    _ => {}
                 }
             }
         }
 
-        while let Ok(message) = rx.try_recv() {
-            output.push_str(&format!("\n{}\n", message));
+        if load_run.as_ref().is_some_and(|run| run.is_finished()) {
+            let run = load_run.take().unwrap();
+            output.info(format!("Load run finished: {}", run.progress.summary()));
+        }
+
+        if burst_run
+            .as_ref()
+            .is_some_and(|run| run.handle.is_finished())
+        {
+            let run = burst_run.take().unwrap();
+            if let Ok(summary) = ctx.runtime.block_on(run.handle) {
+                output.info(format!("Burst finished: {}", summary));
+            }
+        }
+
+        if export_run
+            .as_ref()
+            .is_some_and(|handle| handle.is_finished())
+        {
+            let handle = export_run.take().unwrap();
+            match ctx.runtime.block_on(handle) {
+                Ok(Ok(written)) => output.info(format!(
+                    "Exported {} responses to {}",
+                    written,
+                    config.export_path.display()
+                )),
+                Ok(Err(e)) => output.failure(format!("Export failed: {}", e)),
+                Err(e) => output.failure(format!("Export failed: {}", e)),
+            }
+        }
+
+        while let Ok(event) = rx.try_recv() {
+            ctx.throughput
+                .lock()
+                .unwrap()
+                .record(std::time::Instant::now());
+            if let Some(comparison) = &ctx.comparison {
+                comparison.lock().unwrap().record(&event);
+            }
+            stats.record(&event);
+            // Only work goes through the balancer, setup requests target a worker directly.
+            if event.request == RequestType::WORK {
+                distribution.record(event.worker.as_deref());
+            }
+            output.event(&event);
+            events.push(event);
+        }
 
-            let log_lines: Vec<&str> = output.lines().collect();
-            if log_lines.len() > MAX_LOG_LINES {
-                output = log_lines[log_lines.len() - MAX_LOG_LINES..].join("\n");
+        if connection_check.is_none() && last_check.elapsed() >= check_interval {
+            connection_check = Some(check_connection(&ctx));
+            last_check = std::time::Instant::now();
+        }
+        if connection_check
+            .as_ref()
+            .is_some_and(|handle| handle.is_finished())
+        {
+            let handle = connection_check.take().unwrap();
+            if let Ok(result) = ctx.runtime.block_on(handle) {
+                // Only changes are worth a line, periodic checks would flood the output.
+                if result != reachability {
+                    match &result {
+                        Reachability::Unreachable(_) => {
+                            output.failure(format!("Load balancer {}", result))
+                        }
+                        _ => output.info(format!("Load balancer {}", result)),
+                    }
+                }
+                reachability = result;
+            }
+        }
+
+        while let Ok((kind, line)) = messages_rx.try_recv() {
+            output.push(line, kind);
+        }
+
+        if let Some(run) = &mut scenario_run {
+            while let Ok(line) = run.progress.try_recv() {
+                output.info(line);
+            }
+            if run.handle.is_finished() {
+                let run = scenario_run.take().unwrap();
+                if let Ok(failed_steps) = ctx.runtime.block_on(run.handle) {
+                    let message = format!(
+                        "Scenario {} finished, {} failed steps",
+                        run.name,
+                        failed_steps.len()
+                    );
+                    if failed_steps.is_empty() {
+                        output.info(message);
+                    } else {
+                        output.failure(message);
+                    }
+                }
             }
         }
+
+        if draining_since
+            .is_some_and(|since| ctx.in_flight.count() == 0 || since.elapsed() >= drain_timeout)
+        {
+            break;
+        }
     }
 
     cleanup_terminal()?;
+    if let Err(e) = output.close_log() {
+        eprintln!("Failed to write the session log: {}", e);
+    }
+    // Requests still in flight are cancelled rather than waited for.
+    ctx.runtime
+        .shutdown_timeout(std::time::Duration::from_millis(100));
     Ok(())
 }
 
-fn scenario_a(
-    runtime: &tokio::runtime::Runtime,
-    client: Arc<reqwest::Client>,
-    tx: tokio::sync::mpsc::Sender<String>,
-) {
-    change_algorithm(&runtime, client.clone(), tx.clone(), "round_robin");
+// Lays the items out in as many columns as fit the width, highlighting the selected one.
+fn menu_lines(items: &[String], selected: Option<usize>, width: usize) -> Vec<Line<'static>> {
+    let item_width = items.iter().map(|item| item.len()).max().unwrap_or(0);
+    let per_line = (width.saturating_sub(2) / (item_width + 1)).max(1);
+
+    items
+        .chunks(per_line)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let spans = chunk
+                .iter()
+                .enumerate()
+                .flat_map(|(column, item)| {
+                    let style = if selected == Some(row * per_line + column) {
+                        Style::default().add_modifier(Modifier::REVERSED)
+                    } else {
+                        Style::default()
+                    };
+                    [
+                        Span::raw(" "),
+                        Span::styled(format!("{:width$}", item, width = item_width), style),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            Line::from(spans)
+        })
+        .collect()
+}
+
+fn work_target_name(targets: &Targets, target: WorkTarget) -> String {
+    match target {
+        WorkTarget::LoadBalancer => format!("load balancer ({})", targets.load_balancer),
+        WorkTarget::Worker(server) => worker_name(targets, server),
+        WorkTarget::Compare => format!(
+            "comparison balancer ({})",
+            targets.work(WorkTarget::Compare, "/")
+        ),
+    }
+}
+
+fn scenario_name(scenario: &Scenario) -> String {
+    scenario
+        .name
+        .clone()
+        .unwrap_or_else(|| "unnamed".to_string())
+}
+
+// Scenarios that can't be used are reported in the output instead of stopping the client.
+fn open_session_log(path: &std::path::Path, restore_lines: usize, output: &mut Output) {
+    match session_log::tail(path, restore_lines) {
+        Ok(lines) if !lines.is_empty() => {
+            let count = lines.len();
+            output.restore(lines);
+            output.info(format!(
+                "Restored {} lines of the previous session from {}",
+                count,
+                path.display()
+            ));
+        }
+        Ok(_) => {}
+        Err(e) => output.failure(format!("Could not read {}: {}", path.display(), e)),
+    }
+    match SessionLog::open(path) {
+        Ok(log) => output.set_log(log),
+        Err(e) => output.failure(format!(
+            "Could not open session log {}: {}",
+            path.display(),
+            e
+        )),
+    }
+}
+
+fn load_scenarios(path: &std::path::Path, output: &mut Output) -> Vec<(char, Scenario)> {
+    if !path.exists() {
+        return Vec::new();
+    }
+    let scenarios = match Scenario::load_all(path) {
+        Ok(scenarios) => scenarios,
+        Err(msg) => {
+            output.failure(msg);
+            return Vec::new();
+        }
+    };
+
+    let mut bound: Vec<(char, Scenario)> = Vec::new();
+    for scenario in scenarios {
+        let name = scenario_name(&scenario);
+        match scenario.key {
+            None => output.failure(format!("Scenario {} has no key, skipped", name)),
+            Some(key) if Menu::is_bound(key) || bound.iter().any(|(k, _)| *k == key) => output
+                .failure(format!(
+                    "Scenario {} key '{}' is already in use, skipped",
+                    name, key
+                )),
+            Some(key) => bound.push((key, scenario)),
+        }
+    }
+    if !bound.is_empty() {
+        output.info(format!(
+            "Loaded {} scenarios from {}",
+            bound.len(),
+            path.display()
+        ));
+    }
+    bound
+}
+
+fn start_scenario(ctx: &Context, scenario: &Scenario) -> ScenarioRun {
+    let (progress_tx, progress) = tokio::sync::mpsc::unbounded_channel();
+
+    let client = ctx.client.clone();
+    let targets = ctx.targets.clone();
+    let retry = ctx.retry();
+    let tx = ctx.tx.clone();
+    let in_flight = ctx.in_flight.clone();
+    let send = move |request: RequestType| {
+        let guard = in_flight.start();
+        let response = requests::perform(&client, &targets, request, retry);
+        let tx = tx.clone();
+        async move {
+            let event = response.await;
+            let _ = tx.send(event.clone()).await;
+            drop(guard);
+            event
+        }
+    };
+
+    let name = scenario_name(scenario);
+    let scenario = scenario.clone();
+    let handle = ctx.runtime.spawn(async move {
+        executor::run(&scenario, send, |line| {
+            let _ = progress_tx.send(line);
+        })
+        .await
+    });
+    ScenarioRun {
+        name,
+        progress,
+        handle,
+    }
+}
+
+fn build_client(config: &Config) -> Result<Arc<reqwest::Client>, Error> {
+    let client = reqwest::Client::builder()
+        .pool_max_idle_per_host(50)
+        .timeout(std::time::Duration::from_millis(config.request_timeout_ms))
+        .connect_timeout(std::time::Duration::from_millis(config.connect_timeout_ms))
+        .build()
+        .map_err(io::Error::other)?;
+    Ok(Arc::new(client))
+}
+
+// Runs a scenario without the TUI, the process exits with 1 when the report doesn't pass.
+fn run_headless(config: &Config, args: &RunArgs) -> Result<(), Error> {
+    let scenario = Scenario::load(&args.scenario).map_err(io::Error::other)?;
+    let client = build_client(config)?;
+    let targets = config.targets().map_err(io::Error::other)?;
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    let (report, events) = runtime.block_on(headless::run(
+        &scenario,
+        args.max_error_rate,
+        client,
+        &targets,
+    ));
+
+    if let Some(path) = &args.output {
+        let written = export::export(&events, path)?;
+        println!("Exported {} responses to {}", written, path.display());
+    }
+
+    let json = serde_json::to_string_pretty(&report).map_err(io::Error::other)?;
+    if let Some(path) = &args.report {
+        std::fs::write(path, &json)?;
+    }
+    println!("{}", json);
+
+    if !report.passed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn scenario_a(ctx: &Context) {
+    change_algorithm(ctx, "round_robin");
     std::thread::sleep(std::time::Duration::from_secs(1));
-    setup_worker(&runtime, client.clone(), tx.clone(), 0, 10, 20, 0.0);
-    setup_worker(&runtime, client.clone(), tx.clone(), 1, 1000, 2000, 0.0);
-    setup_worker(&runtime, client.clone(), tx.clone(), 2, 10, 20, 0.0);
+    setup_worker(ctx, 0, 10, 20, 0.0);
+    setup_worker(ctx, 1, 1000, 2000, 0.0);
+    setup_worker(ctx, 2, 10, 20, 0.0);
     std::thread::sleep(std::time::Duration::from_secs(1));
     for _ in 0..18 {
-        do_work(&runtime, client.clone(), tx.clone(), 10);
+        do_work(ctx, 10, WorkOverrides::default());
         std::thread::sleep(std::time::Duration::from_secs(1));
     }
-    setup_worker(&runtime, client.clone(), tx.clone(), 2, 10, 20, 0.0);
+    setup_worker(ctx, 2, 10, 20, 0.0);
     for _ in 0..12 {
-        do_work(&runtime, client.clone(), tx.clone(), 1);
+        do_work(ctx, 1, WorkOverrides::default());
         std::thread::sleep(std::time::Duration::from_secs(1));
     }
 }
 
-fn change_algorithm(
-    runtime: &tokio::runtime::Runtime,
-    client: Arc<reqwest::Client>,
-    tx: tokio::sync::mpsc::Sender<String>,
-    algo: &str,
-) {
-    let req = RequestType::ChangeAlgorithm {
-        new_algo: algo.to_string(),
+fn send(ctx: &Context, request: RequestType) {
+    if ctx.comparison.is_some() {
+        if let Some(copy) = request.compare_copy() {
+            send(ctx, copy);
+        }
     }
-    .build(client.clone())
-    .unwrap();
-    runtime.spawn(send_request(client.clone(), req, tx.clone()));
+    let req = request.build(ctx.client.clone(), &ctx.targets).unwrap();
+    ctx.runtime.spawn(send_request(
+        ctx.client.clone(),
+        request.name(),
+        req,
+        ctx.tx.clone(),
+        ctx.retry(),
+        ctx.in_flight.start(),
+    ));
 }
 
-fn do_work(
-    runtime: &tokio::runtime::Runtime,
-    client: Arc<reqwest::Client>,
-    tx: tokio::sync::mpsc::Sender<String>,
-    multiplier: u64,
+fn check_connection(ctx: &Context) -> tokio::task::JoinHandle<Reachability> {
+    let client = ctx.client.clone();
+    let url = ctx.targets.load_balancer.clone();
+    ctx.runtime
+        .spawn(async move { reachability::check(&client, url).await })
+}
+
+// Read-only queries skip stats and events, their result goes straight to the output.
+fn query<T: DeserializeOwned + 'static>(
+    ctx: &Context,
+    request: RequestType,
+    format: fn(&T) -> Vec<String>,
 ) {
-    let req = RequestType::Work {
-        multiplier: multiplier,
+    let req = request.build(ctx.client.clone(), &ctx.targets).unwrap();
+    let client = ctx.client.clone();
+    let messages = ctx.messages.clone();
+    ctx.runtime.spawn(async move {
+        match lb_info::fetch(&client, req, format).await {
+            Ok(lines) => {
+                for line in lines {
+                    let _ = messages.send((LineKind::Success, line));
+                }
+            }
+            Err(e) => {
+                let _ = messages.send((LineKind::Failure, e));
+            }
+        }
+    });
+}
+
+fn change_algorithm(ctx: &Context, algo: &str) {
+    let request = RequestType::ChangeAlgorithm {
+        new_algo: algo.to_string(),
+    };
+    send(ctx, request);
+}
+
+fn do_work(ctx: &Context, multiplier: u64, overrides: WorkOverrides) {
+    let request = RequestType::Work {
+        multiplier,
+        overrides,
+        target: ctx.work_target.get(),
+    };
+    send(ctx, request);
+}
+
+fn setup_worker(ctx: &Context, server: u64, min_duration: u64, max_duration: u64, error_rate: f64) {
+    let request = RequestType::SetupWorker {
+        server,
+        min_duration,
+        max_duration,
+        error_rate,
+    };
+    send(ctx, request);
+}
+
+fn start_load(ctx: &Context, settings: LoadSettings) -> LoadRun {
+    let client = ctx.client.clone();
+    let targets = ctx.targets.clone();
+    let retry = ctx.retry();
+    let target = ctx.work_target.get();
+    let throughput = ctx.throughput.clone();
+    let comparison = ctx.comparison.clone();
+    let send = move || {
+        let client = client.clone();
+        let throughput = throughput.clone();
+        let comparison = comparison.clone();
+        let request = RequestType::Work {
+            multiplier: settings.multiplier,
+            overrides: WorkOverrides::default(),
+            target,
+        };
+        let copy = comparison
+            .as_ref()
+            .and(request.compare_copy())
+            .map(|copy| requests::perform(&client, &targets, copy, retry));
+        let name = request.name();
+        let req = request.build(client.clone(), &targets);
+        async move {
+            // The copy runs on its own, so a slow or failing comparison target can't hold up the original.
+            if let (Some(copy), Some(comparison)) = (copy, comparison.clone()) {
+                tokio::spawn(async move {
+                    let event = copy.await;
+                    comparison.lock().unwrap().record(&event);
+                });
+            }
+            let event = match req {
+                Ok(req) => retry::execute(&client, name, req, retry).await,
+                Err(e) => requests::failed(name, &e),
+            };
+            throughput.lock().unwrap().record(std::time::Instant::now());
+            if let Some(comparison) = &comparison {
+                comparison.lock().unwrap().record(&event);
+            }
+            event.is_success()
+        }
+    };
+
+    LoadRun::spawn(ctx.runtime.handle(), settings, send)
+}
+
+fn stop_load(ctx: &Context, run: LoadRun) {
+    ctx.runtime.block_on(run.stop(LOAD_STOP_GRACE));
+}
+
+fn custom_setup_prompt(server: u64) -> (PromptAction, Prompt) {
+    (
+        PromptAction::SetupWorker { server },
+        Prompt::new(vec![
+            PromptField::number("Min duration ms", 500, DURATION_RANGE),
+            PromptField::number("Max duration ms", 1000, DURATION_RANGE),
+            PromptField::number("Error rate %", 0, ERROR_PERCENT_RANGE),
+        ]),
+    )
+}
+
+// The prompt already validated the values.
+fn parse_custom_setup(values: &[String]) -> (u64, u64, u64) {
+    let value = |index: usize| values[index].parse().unwrap_or(0);
+    (value(0), value(1), value(2))
+}
+
+// The prompt already validated both values.
+fn parse_burst_settings(values: &[String]) -> BurstSettings {
+    BurstSettings {
+        count: values[0].parse().unwrap_or(1),
+        multiplier: values[1].parse().unwrap_or(1),
     }
-    .build(client.clone())
-    .unwrap();
-    runtime.spawn(send_request(client.clone(), req, tx.clone()));
 }
 
-fn setup_worker(
-    runtime: &tokio::runtime::Runtime,
-    client: Arc<reqwest::Client>,
-    tx: tokio::sync::mpsc::Sender<String>,
-    server: u64,
-    min_duration: u64,
-    max_duration: u64,
-    error_rate: f64,
-) {
-    let req = RequestType::SetupWorker {
-        server: server,
-        min_duration: min_duration,
-        max_duration: max_duration,
-        error_rate: error_rate,
-    }
-    .build(client.clone())
-    .unwrap();
-    runtime.spawn(send_request(client.clone(), req, tx.clone()));
+fn start_burst(ctx: &Context, settings: BurstSettings) -> BurstRun {
+    let in_flight = Arc::new(AtomicUsize::new(0));
+
+    let client = ctx.client.clone();
+    let targets = ctx.targets.clone();
+    let retry = ctx.retry();
+    let target = ctx.work_target.get();
+    let compare = ctx.comparison.is_some();
+    let tx = ctx.tx.clone();
+    let send = move || {
+        let client = client.clone();
+        let request = RequestType::Work {
+            multiplier: settings.multiplier,
+            overrides: WorkOverrides::default(),
+            target,
+        };
+        // Copies only report their events, they aren't part of the burst summary.
+        let copy = request
+            .compare_copy()
+            .filter(|_| compare)
+            .map(|copy| requests::perform(&client, &targets, copy, retry));
+        let name = request.name();
+        let req = request.build(client.clone(), &targets);
+        let tx = tx.clone();
+        async move {
+            if let Some(copy) = copy {
+                tokio::spawn(async move {
+                    let _ = tx.send(copy.await).await;
+                });
+            }
+            match req {
+                Ok(req) => retry::execute(&client, name, req, retry).await,
+                Err(e) => requests::failed(name, &e),
+            }
+        }
+    };
+
+    let handle = ctx.runtime.spawn(burst::run(
+        settings,
+        in_flight.clone(),
+        send,
+        ctx.tx.clone(),
+    ));
+    BurstRun { in_flight, handle }
 }
@@ -0,0 +1,264 @@
+use crossterm::event::KeyCode;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    RoundRobin,
+    LeastConnections,
+    ShortWork,
+    LongWork,
+    CustomWork,
+    SwitchTarget,
+    SetupWorker,
+    SimulatedError,
+    SimulatedDelay,
+    ScenarioA,
+    StartLoad,
+    StopLoad,
+    BackgroundLoad,
+    Retry,
+    Burst,
+    Export,
+    ShowAlgorithm,
+    ListServers,
+    CheckConnection,
+    Clear,
+    Quit,
+    // Index into the scenarios loaded from the scenarios file.
+    Scenario(usize),
+}
+
+impl Action {
+    // Actions that go through the balancer, worth a warning while it's unreachable.
+    pub fn needs_balancer(&self) -> bool {
+        matches!(
+            self,
+            Action::RoundRobin
+                | Action::LeastConnections
+                | Action::ShortWork
+                | Action::LongWork
+                | Action::CustomWork
+                | Action::SimulatedError
+                | Action::SimulatedDelay
+                | Action::ScenarioA
+                | Action::StartLoad
+                | Action::BackgroundLoad
+                | Action::Burst
+                | Action::ShowAlgorithm
+                | Action::ListServers
+                | Action::Scenario(_)
+        )
+    }
+}
+
+pub struct MenuItem {
+    pub shortcut: char,
+    pub label: String,
+    pub action: Action,
+}
+
+impl MenuItem {
+    pub fn new(shortcut: char, label: impl Into<String>, action: Action) -> Self {
+        MenuItem {
+            shortcut,
+            label: label.into(),
+            action,
+        }
+    }
+}
+
+// The built-in items with `extra` inserted before Quit.
+pub fn menu_items(extra: Vec<MenuItem>) -> Vec<MenuItem> {
+    let mut items = vec![
+        MenuItem::new('1', "Change algo to round_robin", Action::RoundRobin),
+        MenuItem::new(
+            '2',
+            "Change algo to least_connections",
+            Action::LeastConnections,
+        ),
+        MenuItem::new('3', "Send short work", Action::ShortWork),
+        MenuItem::new('4', "Send long work", Action::LongWork),
+        MenuItem::new('m', "Send work with custom multiplier", Action::CustomWork),
+        MenuItem::new(
+            'o',
+            "Switch work target (balancer/worker)",
+            Action::SwitchTarget,
+        ),
+        MenuItem::new('w', "Setup worker", Action::SetupWorker),
+        MenuItem::new(
+            'e',
+            "Send work with simulated error",
+            Action::SimulatedError,
+        ),
+        MenuItem::new(
+            'd',
+            "Send work with simulated 3s delay",
+            Action::SimulatedDelay,
+        ),
+        MenuItem::new('a', "Scenario A", Action::ScenarioA),
+        MenuItem::new('l', "Start load run", Action::StartLoad),
+        MenuItem::new('s', "Stop load run", Action::StopLoad),
+        MenuItem::new('t', background_load_label(false), Action::BackgroundLoad),
+        MenuItem::new('r', retry_label(false), Action::Retry),
+        MenuItem::new('b', "Send burst of concurrent work", Action::Burst),
+        MenuItem::new('x', "Export results", Action::Export),
+        MenuItem::new('g', "Show current algo", Action::ShowAlgorithm),
+        MenuItem::new('v', "List balancer servers", Action::ListServers),
+        MenuItem::new('h', "Check balancer connection", Action::CheckConnection),
+        MenuItem::new('c', "Clear output and stats", Action::Clear),
+    ];
+    items.extend(extra);
+    items.push(MenuItem::new('q', "Quit", Action::Quit));
+    items
+}
+
+pub fn background_load_label(on: bool) -> String {
+    format!("Background load: {}", if on { "ON" } else { "OFF" })
+}
+
+pub fn retry_label(on: bool) -> String {
+    format!("Retries: {}", if on { "ON" } else { "OFF" })
+}
+
+// Up/Down (or k/j) move the selection with wrap-around, Enter activates it and
+// the shortcut of an item activates it directly.
+pub struct Menu {
+    items: Vec<MenuItem>,
+    selected: usize,
+}
+
+impl Menu {
+    pub fn new(items: Vec<MenuItem>) -> Self {
+        Menu { items, selected: 0 }
+    }
+
+    // Whether the key already does something in the menu.
+    pub fn is_bound(key: char) -> bool {
+        matches!(key, 'j' | 'k')
+            || menu_items(Vec::new())
+                .iter()
+                .any(|item| item.shortcut == key)
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn set_label(&mut self, action: Action, label: impl Into<String>) {
+        if let Some(item) = self.items.iter_mut().find(|item| item.action == action) {
+            item.label = label.into();
+        }
+    }
+
+    pub fn labels(&self) -> Vec<String> {
+        self.items
+            .iter()
+            .map(|item| format!("{} - {}", item.shortcut, item.label))
+            .collect()
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode) -> Option<Action> {
+        match code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+                None
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1) % self.items.len();
+                None
+            }
+            KeyCode::Enter => Some(self.items[self.selected].action),
+            KeyCode::Char(c) => {
+                let index = self.items.iter().position(|item| item.shortcut == c)?;
+                self.selected = index;
+                Some(self.items[index].action)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn menu() -> Menu {
+        Menu::new(vec![
+            MenuItem::new('1', "Change algo to round_robin", Action::RoundRobin),
+            MenuItem::new('3', "Send short work", Action::ShortWork),
+            MenuItem::new('q', "Quit", Action::Quit),
+        ])
+    }
+
+    #[test]
+    fn arrows_and_vim_keys_move_the_selection() {
+        let mut menu = menu();
+        assert_eq!(menu.selected(), 0);
+
+        assert_eq!(menu.handle_key(KeyCode::Down), None);
+        assert_eq!(menu.selected(), 1);
+        assert_eq!(menu.handle_key(KeyCode::Char('j')), None);
+        assert_eq!(menu.selected(), 2);
+        assert_eq!(menu.handle_key(KeyCode::Char('k')), None);
+        assert_eq!(menu.handle_key(KeyCode::Up), None);
+        assert_eq!(menu.selected(), 0);
+    }
+
+    #[test]
+    fn the_selection_wraps_around() {
+        let mut menu = menu();
+        menu.handle_key(KeyCode::Up);
+        assert_eq!(menu.selected(), 2);
+        menu.handle_key(KeyCode::Down);
+        assert_eq!(menu.selected(), 0);
+    }
+
+    #[test]
+    fn enter_activates_the_selected_item() {
+        let mut menu = menu();
+        assert_eq!(menu.handle_key(KeyCode::Enter), Some(Action::RoundRobin));
+        menu.handle_key(KeyCode::Down);
+        assert_eq!(menu.handle_key(KeyCode::Enter), Some(Action::ShortWork));
+    }
+
+    #[test]
+    fn shortcuts_activate_and_select_their_item() {
+        let mut menu = menu();
+        assert_eq!(menu.handle_key(KeyCode::Char('q')), Some(Action::Quit));
+        assert_eq!(menu.selected(), 2);
+        assert_eq!(menu.handle_key(KeyCode::Char('3')), Some(Action::ShortWork));
+        assert_eq!(menu.selected(), 1);
+
+        assert_eq!(menu.handle_key(KeyCode::Char('z')), None);
+        assert_eq!(menu.handle_key(KeyCode::Tab), None);
+        assert_eq!(menu.selected(), 1);
+    }
+
+    #[test]
+    fn labels_show_shortcuts_and_follow_updates() {
+        let mut menu = Menu::new(menu_items(vec![MenuItem::new(
+            'z',
+            "Warmup",
+            Action::Scenario(0),
+        )]));
+        menu.set_label(Action::BackgroundLoad, background_load_label(true));
+
+        let labels = menu.labels();
+        assert_eq!(labels.first().unwrap(), "1 - Change algo to round_robin");
+        assert!(labels.contains(&"t - Background load: ON".to_string()));
+        // Extra items come right before Quit.
+        assert_eq!(&labels[labels.len() - 2..], ["z - Warmup", "q - Quit"]);
+    }
+
+    #[test]
+    fn built_in_shortcuts_are_unique_and_bound() {
+        let items = menu_items(Vec::new());
+        let mut shortcuts = items.iter().map(|item| item.shortcut).collect::<Vec<_>>();
+        shortcuts.sort();
+        shortcuts.dedup();
+        assert_eq!(shortcuts.len(), items.len());
+
+        assert!(items.iter().all(|item| Menu::is_bound(item.shortcut)));
+        assert!(Menu::is_bound('j') && Menu::is_bound('k'));
+        assert!(!Menu::is_bound('z'));
+    }
+}
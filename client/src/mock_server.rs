@@ -0,0 +1,90 @@
+use std::future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+// What a connection gets once its request was read.
+pub struct Reply {
+    raw: String,
+    delay: Duration,
+    held_open: bool,
+}
+
+impl Reply {
+    // A whole response, the connection closed after it.
+    pub fn new(status: &str, headers: &[(&str, &str)], body: &str) -> Self {
+        let headers = headers
+            .iter()
+            .map(|(name, value)| format!("{}: {}\r\n", name, value))
+            .collect::<String>();
+        Reply::raw(format!(
+            "HTTP/1.1 {}\r\n{}content-length: {}\r\nconnection: close\r\n\r\n{}",
+            status,
+            headers,
+            body.len(),
+            body
+        ))
+    }
+
+    // `raw` written as is, e.g. a head promising more body than follows.
+    pub fn raw(raw: impl Into<String>) -> Self {
+        Reply {
+            raw: raw.into(),
+            delay: Duration::ZERO,
+            held_open: false,
+        }
+    }
+
+    pub fn after(self, delay: Duration) -> Self {
+        Reply { delay, ..self }
+    }
+
+    // The connection stays open after the reply without another byte sent.
+    pub fn held_open(self) -> Self {
+        Reply {
+            held_open: true,
+            ..self
+        }
+    }
+}
+
+// Answers every connection with what `answer` returns for its number, from 1 in the order they
+// were accepted, and its request. Each is answered on its own task, slow replies don't queue.
+pub async fn serve<F>(answer: F) -> SocketAddr
+where
+    F: Fn(usize, &str) -> Reply + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let answer = Arc::new(answer);
+    tokio::spawn(async move {
+        let mut accepted = 0;
+        while let Ok((mut stream, _)) = listener.accept().await {
+            accepted += 1;
+            let (n, answer) = (accepted, answer.clone());
+            tokio::spawn(async move {
+                let mut request = [0; 4096];
+                let read = stream.read(&mut request).await.unwrap_or(0);
+                let reply = answer(n, &String::from_utf8_lossy(&request[..read]));
+                tokio::time::sleep(reply.delay).await;
+                let _ = stream.write_all(reply.raw.as_bytes()).await;
+                if reply.held_open {
+                    future::pending::<()>().await;
+                }
+            });
+        }
+    });
+    address
+}
+
+// A local address nothing listens on any more, connecting to it is refused.
+pub async fn closed_port() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
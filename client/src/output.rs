@@ -0,0 +1,207 @@
+use std::collections::VecDeque;
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::Line,
+};
+
+use client::response_event::ResponseEvent;
+use client::session_log::SessionLog;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineKind {
+    Info,
+    Success,
+    Warning,
+    Failure,
+}
+
+impl LineKind {
+    pub fn for_event(event: &ResponseEvent) -> Self {
+        match event.status {
+            Ok(status) if status.is_success() => LineKind::Success,
+            Ok(status) if status.is_client_error() => LineKind::Warning,
+            Ok(status) if status.is_server_error() => LineKind::Failure,
+            Ok(_) => LineKind::Info,
+            Err(_) => LineKind::Failure,
+        }
+    }
+
+    pub fn style(&self) -> Style {
+        match self {
+            LineKind::Info => Style::default()
+                .fg(Color::DarkGray)
+                .add_modifier(Modifier::DIM),
+            LineKind::Success => Style::default().fg(Color::Green),
+            LineKind::Warning => Style::default().fg(Color::Yellow),
+            LineKind::Failure => Style::default().fg(Color::Red),
+        }
+    }
+}
+
+// Messages shown in the output pane, oldest first, trimmed to `max_lines`.
+// With a session log every new line is also appended to it, so trimming loses nothing.
+pub struct Output {
+    lines: VecDeque<(String, LineKind)>,
+    max_lines: usize,
+    log: Option<SessionLog>,
+}
+
+impl Output {
+    pub fn new(max_lines: usize) -> Self {
+        Output {
+            lines: VecDeque::new(),
+            max_lines: max_lines.max(1),
+            log: None,
+        }
+    }
+
+    pub fn set_log(&mut self, log: SessionLog) {
+        self.log = Some(log);
+    }
+
+    // Flushes and closes the session log, if any.
+    pub fn close_log(&mut self) -> std::io::Result<()> {
+        match self.log.take() {
+            Some(log) => log.close(),
+            None => Ok(()),
+        }
+    }
+
+    // Shows lines of an earlier session without appending them to the log again.
+    pub fn restore(&mut self, lines: Vec<String>) {
+        for line in lines {
+            self.show(line, LineKind::Info);
+        }
+    }
+
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.push(text.into(), LineKind::Info);
+    }
+
+    pub fn warning(&mut self, text: impl Into<String>) {
+        self.push(text.into(), LineKind::Warning);
+    }
+
+    pub fn failure(&mut self, text: impl Into<String>) {
+        self.push(text.into(), LineKind::Failure);
+    }
+
+    pub fn event(&mut self, event: &ResponseEvent) {
+        self.push(event.to_string(), LineKind::for_event(event));
+    }
+
+    pub fn push(&mut self, text: String, kind: LineKind) {
+        if let Some(log) = &self.log {
+            log.write(&text);
+        }
+        self.show(text, kind);
+    }
+
+    fn show(&mut self, text: String, kind: LineKind) {
+        if self.lines.len() == self.max_lines {
+            self.lines.pop_front();
+        }
+        self.lines.push_back((text, kind));
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    pub fn styled_lines(&self) -> Vec<Line<'_>> {
+        self.lines
+            .iter()
+            .map(|(text, kind)| Line::styled(text.as_str(), kind.style()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use client::response_event::FailureKind;
+    use reqwest::StatusCode;
+
+    use super::*;
+
+    fn event(status: Result<StatusCode, FailureKind>) -> ResponseEvent {
+        ResponseEvent {
+            request: "work",
+            sent_at: SystemTime::UNIX_EPOCH,
+            target: "127.0.0.1:80".to_string(),
+            status,
+            latency: Duration::from_millis(20),
+            worker: None,
+            body_snippet: String::new(),
+            error: None,
+            attempts: 1,
+        }
+    }
+
+    #[test]
+    fn events_are_styled_by_outcome() {
+        for (status, kind) in [
+            (Ok(StatusCode::OK), LineKind::Success),
+            (Ok(StatusCode::NO_CONTENT), LineKind::Success),
+            (Ok(StatusCode::NOT_FOUND), LineKind::Warning),
+            (Ok(StatusCode::TOO_MANY_REQUESTS), LineKind::Warning),
+            (Ok(StatusCode::INTERNAL_SERVER_ERROR), LineKind::Failure),
+            (Ok(StatusCode::SERVICE_UNAVAILABLE), LineKind::Failure),
+            (Ok(StatusCode::MOVED_PERMANENTLY), LineKind::Info),
+            (Err(FailureKind::Timeout), LineKind::Failure),
+            (Err(FailureKind::Connect), LineKind::Failure),
+            (Err(FailureKind::Body), LineKind::Failure),
+        ] {
+            assert_eq!(LineKind::for_event(&event(status)), kind, "{:?}", status);
+        }
+    }
+
+    #[test]
+    fn kinds_map_to_colors() {
+        assert_eq!(LineKind::Success.style().fg, Some(Color::Green));
+        assert_eq!(LineKind::Warning.style().fg, Some(Color::Yellow));
+        assert_eq!(LineKind::Failure.style().fg, Some(Color::Red));
+        let info = LineKind::Info.style();
+        assert_eq!(info.fg, Some(Color::DarkGray));
+        assert!(info.add_modifier.contains(Modifier::DIM));
+    }
+
+    #[test]
+    fn lines_keep_their_style_and_are_trimmed_oldest_first() {
+        let mut output = Output::new(2);
+        output.info("Sending request...");
+        output.event(&event(Ok(StatusCode::OK)));
+        output.failure("Failed to send request.");
+
+        let lines = output.styled_lines();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].to_string(), event(Ok(StatusCode::OK)).to_string());
+        assert_eq!(lines[0].style, LineKind::Success.style());
+        assert_eq!(lines[1].to_string(), "Failed to send request.");
+        assert_eq!(lines[1].style, LineKind::Failure.style());
+
+        output.clear();
+        assert!(output.styled_lines().is_empty());
+    }
+
+    #[test]
+    fn restored_lines_are_shown_without_being_logged_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.log");
+        let mut output = Output::new(10);
+        output.set_log(SessionLog::open(&path).unwrap());
+
+        output.restore(vec!["2024-05-01 12:00:00.000 earlier".to_string()]);
+        output.warning("Balancer unreachable");
+        output.close_log().unwrap();
+
+        let lines = output.styled_lines();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].style, LineKind::Info.style());
+        let logged = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(logged.lines().count(), 1, "{}", logged);
+        assert!(logged.ends_with(" Balancer unreachable\n"), "{}", logged);
+    }
+}
@@ -0,0 +1,229 @@
+use std::ops::RangeInclusive;
+
+use crossterm::event::KeyCode;
+
+pub enum PromptState {
+    Pending,
+    Done(Vec<String>),
+    Cancelled,
+}
+
+// A whole number input, only digits are typed in and the value must fall within `range`.
+pub struct PromptField {
+    label: &'static str,
+    default: String,
+    range: RangeInclusive<u64>,
+}
+
+impl PromptField {
+    pub fn number(label: &'static str, default: u64, range: RangeInclusive<u64>) -> Self {
+        PromptField {
+            label,
+            default: default.to_string(),
+            range,
+        }
+    }
+
+    fn validate(&self, value: &str) -> Result<(), String> {
+        match value.parse::<u64>() {
+            Ok(number) if self.range.contains(&number) => Ok(()),
+            _ => Err(format!(
+                "{} must be between {} and {}",
+                self.label,
+                self.range.start(),
+                self.range.end()
+            )),
+        }
+    }
+}
+
+// Asks for a series of values one after another, Enter on an empty input takes the default.
+pub struct Prompt {
+    fields: Vec<PromptField>,
+    values: Vec<String>,
+    input: String,
+    error: Option<String>,
+}
+
+impl Prompt {
+    pub fn new(fields: Vec<PromptField>) -> Self {
+        Prompt {
+            fields,
+            values: Vec::new(),
+            input: String::new(),
+            error: None,
+        }
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode) -> PromptState {
+        let field = &self.fields[self.values.len()];
+        match code {
+            KeyCode::Esc => return PromptState::Cancelled,
+            KeyCode::Backspace => {
+                self.input.pop();
+                self.error = None;
+            }
+            KeyCode::Char(c) if c.is_ascii_digit() => {
+                self.input.push(c);
+                self.error = None;
+            }
+            KeyCode::Enter => {
+                let value = if self.input.trim().is_empty() {
+                    field.default.clone()
+                } else {
+                    self.input.trim().to_string()
+                };
+                if let Err(msg) = field.validate(&value) {
+                    self.error = Some(msg);
+                    return PromptState::Pending;
+                }
+                self.values.push(value);
+                self.input.clear();
+
+                if self.values.len() == self.fields.len() {
+                    return PromptState::Done(std::mem::take(&mut self.values));
+                }
+            }
+            _ => {}
+        }
+        PromptState::Pending
+    }
+
+    pub fn render(&self) -> String {
+        let field = &self.fields[self.values.len()];
+        let mut text = format!(
+            "{} ({}-{}) [{}]: {}_ (Enter to confirm, Esc to cancel)",
+            field.label,
+            field.range.start(),
+            field.range.end(),
+            field.default,
+            self.input
+        );
+        if let Some(error) = &self.error {
+            text.push_str(&format!("  {}", error));
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn multiplier() -> Prompt {
+        Prompt::new(vec![PromptField::number("Multiplier", 1, 1..=10)])
+    }
+
+    // The state after typing `keys`, `Enter` for '\n' and `Backspace` for '\x08'.
+    fn typed(prompt: &mut Prompt, keys: &str) -> PromptState {
+        let mut state = PromptState::Pending;
+        for key in keys.chars() {
+            state = prompt.handle_key(match key {
+                '\n' => KeyCode::Enter,
+                '\x08' => KeyCode::Backspace,
+                c => KeyCode::Char(c),
+            });
+        }
+        state
+    }
+
+    fn done(state: PromptState) -> Option<Vec<String>> {
+        match state {
+            PromptState::Done(values) => Some(values),
+            PromptState::Pending | PromptState::Cancelled => None,
+        }
+    }
+
+    #[test]
+    fn digits_are_typed_and_submitted() {
+        let mut prompt = multiplier();
+        assert!(matches!(typed(&mut prompt, "7"), PromptState::Pending));
+        assert_eq!(
+            prompt.render(),
+            "Multiplier (1-10) [1]: 7_ (Enter to confirm, Esc to cancel)"
+        );
+        assert_eq!(done(typed(&mut prompt, "\n")), Some(vec!["7".to_string()]));
+    }
+
+    #[test]
+    fn backspace_removes_the_last_digit() {
+        let mut prompt = multiplier();
+        assert_eq!(
+            done(typed(&mut prompt, "12\x08\n")),
+            Some(vec!["1".to_string()])
+        );
+
+        // Nothing to remove.
+        let mut prompt = multiplier();
+        assert_eq!(
+            done(typed(&mut prompt, "\x08\x084\n")),
+            Some(vec!["4".to_string()])
+        );
+    }
+
+    #[test]
+    fn other_characters_are_ignored() {
+        let mut prompt = multiplier();
+        assert_eq!(
+            done(typed(&mut prompt, "a-3.x \n")),
+            Some(vec!["3".to_string()])
+        );
+    }
+
+    #[test]
+    fn an_empty_input_takes_the_default() {
+        let mut prompt = multiplier();
+        assert_eq!(done(typed(&mut prompt, "\n")), Some(vec!["1".to_string()]));
+    }
+
+    #[test]
+    fn values_out_of_range_are_refused_until_corrected() {
+        let mut prompt = multiplier();
+        assert!(matches!(typed(&mut prompt, "11\n"), PromptState::Pending));
+        assert_eq!(
+            prompt.render(),
+            "Multiplier (1-10) [1]: 11_ (Enter to confirm, Esc to cancel)  \
+             Multiplier must be between 1 and 10"
+        );
+        assert!(matches!(
+            typed(&mut prompt, "\x08\x080\n"),
+            PromptState::Pending
+        ));
+
+        // Typing clears the error.
+        typed(&mut prompt, "\x08");
+        assert_eq!(
+            prompt.render(),
+            "Multiplier (1-10) [1]: _ (Enter to confirm, Esc to cancel)"
+        );
+        assert_eq!(
+            done(typed(&mut prompt, "10\n")),
+            Some(vec!["10".to_string()])
+        );
+    }
+
+    #[test]
+    fn escape_cancels_at_any_point() {
+        let mut prompt = multiplier();
+        typed(&mut prompt, "5");
+        assert!(matches!(
+            prompt.handle_key(KeyCode::Esc),
+            PromptState::Cancelled
+        ));
+    }
+
+    #[test]
+    fn fields_are_asked_one_after_another() {
+        let mut prompt = Prompt::new(vec![
+            PromptField::number("Count", 20, 1..=500),
+            PromptField::number("Multiplier", 1, 1..=10),
+        ]);
+
+        assert!(matches!(typed(&mut prompt, "50\n"), PromptState::Pending));
+        assert!(prompt.render().starts_with("Multiplier (1-10) [1]: _"));
+        assert_eq!(
+            done(typed(&mut prompt, "\n")),
+            Some(vec!["50".to_string(), "1".to_string()])
+        );
+    }
+}
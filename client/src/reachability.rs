@@ -0,0 +1,125 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use reqwest::Url;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Reachability {
+    #[default]
+    Unknown,
+    Reachable,
+    Unreachable(String),
+}
+
+impl fmt::Display for Reachability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reachability::Unknown => write!(f, "checking..."),
+            Reachability::Reachable => write!(f, "reachable"),
+            Reachability::Unreachable(reason) => write!(f, "unreachable: {}", reason),
+        }
+    }
+}
+
+// Any HTTP response counts as reachable, the balancer doesn't route HEAD and answers 404.
+pub async fn check(client: &reqwest::Client, url: Url) -> Reachability {
+    match client.head(url).send().await {
+        Ok(_) => Reachability::Reachable,
+        Err(e) => Reachability::Unreachable(diagnose(&e)),
+    }
+}
+
+// Names the usual reasons, anything else is described by its innermost cause.
+fn diagnose(error: &reqwest::Error) -> String {
+    if error.is_timeout() {
+        return "timeout".to_string();
+    }
+    let mut reason = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        if let Some(io_error) = cause.downcast_ref::<io::Error>() {
+            if io_error.kind() == io::ErrorKind::ConnectionRefused {
+                return "connection refused".to_string();
+            }
+        }
+        if cause.to_string().starts_with("dns error") {
+            return format!("DNS failure ({})", cause);
+        }
+        reason = cause.to_string();
+        source = cause.source();
+    }
+    reason
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::mock_server::{self, Reply};
+
+    // Accepts connections and answers with `reply`, or holds them open when it's empty.
+    async fn server(reply: &'static str) -> Url {
+        let address = mock_server::serve(move |_, _| Reply::raw(reply).held_open()).await;
+        format!("http://{}/", address).parse().unwrap()
+    }
+
+    fn client() -> reqwest::Client {
+        reqwest::Client::builder()
+            .timeout(Duration::from_millis(200))
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn any_response_is_reachable() {
+        for reply in [
+            "HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n",
+            "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n",
+        ] {
+            let url = server(reply).await;
+            assert_eq!(check(&client(), url).await, Reachability::Reachable);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_closed_port_is_refused() {
+        let address = mock_server::closed_port().await;
+        let url = format!("http://{}/", address).parse().unwrap();
+
+        let reachability = check(&client(), url).await;
+
+        assert_eq!(
+            reachability,
+            Reachability::Unreachable("connection refused".to_string())
+        );
+        assert_eq!(reachability.to_string(), "unreachable: connection refused");
+    }
+
+    #[tokio::test]
+    async fn a_silent_server_times_out() {
+        let url = server("").await;
+        assert_eq!(
+            check(&client(), url).await,
+            Reachability::Unreachable("timeout".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_hosts_are_dns_failures() {
+        let url = "http://balancer.invalid/".parse().unwrap();
+        match check(&client(), url).await {
+            Reachability::Unreachable(reason) => {
+                assert!(reason.starts_with("DNS failure (dns error"), "{}", reason)
+            }
+            reachability => panic!("{:?}", reachability),
+        }
+    }
+
+    #[test]
+    fn states_read_plainly() {
+        assert_eq!(Reachability::default().to_string(), "checking...");
+        assert_eq!(Reachability::Reachable.to_string(), "reachable");
+    }
+}
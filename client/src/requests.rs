@@ -1,6 +1,26 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
 
+use lb_api::{ChangeAlgoRequest, SetupRequest, WorkRequest};
 use tokio::task;
+use tokio::time::{Duration, Instant};
+
+use crate::config::{Targets, WorkTarget};
+use crate::response_event::{snippet, target_of, FailureKind, ResponseEvent, SERVED_BY_HEADER};
+use crate::retry::{self, RetryPolicy};
+
+#[derive(Clone, Copy, Default)]
+pub struct WorkOverrides {
+    pub delay_ms: Option<u64>,
+    pub status: Option<u16>,
+    pub error: bool,
+}
 
 pub enum RequestType {
     ChangeAlgorithm {
@@ -8,6 +28,8 @@ pub enum RequestType {
     },
     Work {
         multiplier: u64,
+        overrides: WorkOverrides,
+        target: WorkTarget,
     },
     SetupWorker {
         server: u64,
@@ -15,75 +37,613 @@ pub enum RequestType {
         max_duration: u64,
         error_rate: f64,
     },
+    ResetWorker {
+        server: u64,
+    },
+    GetAlgorithm,
+    ListServers,
 }
 
 impl RequestType {
-    pub fn build(&self, client: Arc<reqwest::Client>) -> Result<reqwest::Request, reqwest::Error> {
+    pub const WORK: &'static str = "work";
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RequestType::ChangeAlgorithm { .. } => "change_algo",
+            RequestType::Work { .. } => RequestType::WORK,
+            RequestType::SetupWorker { .. } => "setup_worker",
+            RequestType::ResetWorker { .. } => "reset_worker",
+            RequestType::GetAlgorithm => "get_algo",
+            RequestType::ListServers => "list_servers",
+        }
+    }
+
+    // The same work aimed at the comparison balancer, only for work sent to the first one.
+    pub fn compare_copy(&self) -> Option<RequestType> {
+        match self {
+            RequestType::Work {
+                multiplier,
+                overrides,
+                target: WorkTarget::LoadBalancer,
+            } => Some(RequestType::Work {
+                multiplier: *multiplier,
+                overrides: *overrides,
+                target: WorkTarget::Compare,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn build(
+        &self,
+        client: Arc<reqwest::Client>,
+        targets: &Targets,
+    ) -> Result<reqwest::Request, reqwest::Error> {
         match self {
             RequestType::ChangeAlgorithm { new_algo } => {
-                build_change_algo_request(client, new_algo)
+                build_change_algo_request(client, targets, new_algo)
             }
-            RequestType::Work { multiplier } => build_work_request(client, multiplier),
+            RequestType::Work {
+                multiplier,
+                overrides,
+                target,
+            } => build_work_request(client, targets, multiplier, overrides, *target),
             RequestType::SetupWorker {
                 server,
                 min_duration,
                 max_duration,
                 error_rate,
-            } => build_setup_worker_request(client, server, min_duration, max_duration, error_rate),
+            } => build_setup_worker_request(
+                client,
+                targets,
+                server,
+                min_duration,
+                max_duration,
+                error_rate,
+            ),
+            RequestType::ResetWorker { server } => {
+                build_reset_worker_request(client, targets, server)
+            }
+            RequestType::GetAlgorithm => build_get_request(client, targets, "/lb/algorithms"),
+            RequestType::ListServers => build_get_request(client, targets, "/lb/stats"),
         }
     }
 }
 
 fn build_change_algo_request(
     client: Arc<reqwest::Client>,
+    targets: &Targets,
     new_algo: &str,
 ) -> Result<reqwest::Request, reqwest::Error> {
-    let mut data = HashMap::new();
-    data.insert("algo", new_algo.to_string());
+    let data = ChangeAlgoRequest {
+        algo: new_algo.to_string(),
+    };
 
-    client.post("http://127.0.0.1/algo").json(&data).build()
+    client
+        .post(targets.load_balancer("/algo"))
+        .json(&data)
+        .build()
 }
 
 fn build_work_request(
     client: Arc<reqwest::Client>,
+    targets: &Targets,
     multiplier: &u64,
+    overrides: &WorkOverrides,
+    target: WorkTarget,
 ) -> Result<reqwest::Request, reqwest::Error> {
-    let mut data = HashMap::new();
-    data.insert("multiplier", multiplier.to_string());
+    let data = WorkRequest {
+        multiplier: Some(*multiplier),
+        ..WorkRequest::default()
+    };
 
-    client.post("http://127.0.0.1/work").json(&data).build()
+    let mut builder = client
+        .post(targets.work(target, "/work"))
+        .header(reqwest::header::ACCEPT, "application/json")
+        .json(&data);
+    if let Some(delay_ms) = overrides.delay_ms {
+        builder = builder.header("X-Simulate-Delay-Ms", delay_ms.to_string());
+    }
+    if let Some(status) = overrides.status {
+        builder = builder.header("X-Simulate-Status", status.to_string());
+    }
+    if overrides.error {
+        builder = builder.header("X-Simulate-Error", "true");
+    }
+    builder.build()
 }
 
 fn build_setup_worker_request(
     client: Arc<reqwest::Client>,
+    targets: &Targets,
     server: &u64,
     min_duration: &u64,
     max_duration: &u64,
     error_rate: &f64,
 ) -> Result<reqwest::Request, reqwest::Error> {
-    let mut data = HashMap::new();
-    data.insert("min_duration", min_duration.to_string());
-    data.insert("max_duration", max_duration.to_string());
-    data.insert("error_rate", error_rate.to_string());
+    let data = SetupRequest {
+        min_duration: Some(*min_duration),
+        max_duration: Some(*max_duration),
+        error_rate: Some(*error_rate),
+        allow_overrides: Some(true),
+        ..SetupRequest::default()
+    };
 
-    let url = format!("http://127.0.0.1:{}/setup", server + 3000);
-    client.post(url).json(&data).build()
+    client
+        .post(targets.worker(*server, "/setup"))
+        .header(reqwest::header::ACCEPT, "application/json")
+        .json(&data)
+        .build()
+}
+
+fn build_reset_worker_request(
+    client: Arc<reqwest::Client>,
+    targets: &Targets,
+    server: &u64,
+) -> Result<reqwest::Request, reqwest::Error> {
+    client.post(targets.worker(*server, "/reset")).build()
+}
+
+fn build_get_request(
+    client: Arc<reqwest::Client>,
+    targets: &Targets,
+    path: &str,
+) -> Result<reqwest::Request, reqwest::Error> {
+    client
+        .get(targets.load_balancer(path))
+        .header(reqwest::header::ACCEPT, "application/json")
+        .build()
+}
+
+// Number of requests sent with `send_request` that haven't delivered their event yet.
+#[derive(Clone, Default)]
+pub struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    pub fn start(&self) -> InFlightGuard {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(self.0.clone())
+    }
+
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+// Decrements the count when dropped, so aborted and panicked requests are let go of too.
+pub struct InFlightGuard(Arc<AtomicUsize>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 pub async fn send_request(
     client: Arc<reqwest::Client>,
+    request: &'static str,
     req: reqwest::Request,
-    tx: tokio::sync::mpsc::Sender<String>,
+    tx: tokio::sync::mpsc::Sender<ResponseEvent>,
+    retry: RetryPolicy,
+    guard: InFlightGuard,
 ) {
+    // The guard is held across retries, a retried request stays in flight until its last attempt.
     task::spawn(async move {
-        if let Ok(response) = client.execute(req).await {
-            if let Ok(text) = response.text().await {
-                let _ = tx.send(format!("Response received: {}", text)).await;
-            } else {
-                let _ = tx.send(String::from("Failed to read response text.")).await;
+        let event = retry::execute(&client, request, req, retry).await;
+        let _ = tx.send(event).await;
+        drop(guard);
+    });
+}
+
+// Builds and executes the request, the returned future doesn't borrow anything.
+pub fn perform(
+    client: &Arc<reqwest::Client>,
+    targets: &Targets,
+    request: RequestType,
+    retry: RetryPolicy,
+) -> impl Future<Output = ResponseEvent> + Send + 'static {
+    let client = client.clone();
+    let name = request.name();
+    let req = request.build(client.clone(), targets);
+    async move {
+        match req {
+            Ok(req) => retry::execute(&client, name, req, retry).await,
+            Err(e) => failed(name, &e),
+        }
+    }
+}
+
+pub async fn execute(
+    client: &reqwest::Client,
+    request: &'static str,
+    req: reqwest::Request,
+) -> ResponseEvent {
+    let sent_at = SystemTime::now();
+    let started_at = Instant::now();
+    let target = target_of(req.url());
+    let response = match client.execute(req).await {
+        Ok(response) => response,
+        Err(e) => {
+            return ResponseEvent {
+                sent_at,
+                target,
+                latency: started_at.elapsed(),
+                ..failed(request, &e)
             }
-        } else {
-            let _ = tx.send(String::from("Failed to send request.")).await;
         }
-    });
+    };
+
+    let status = response.status();
+    let mut worker = response
+        .headers()
+        .get(SERVED_BY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    let (status, body_snippet, error) = match response.text().await {
+        Ok(text) => {
+            worker = worker.or_else(|| worker_from_body(&text));
+            (Ok(status), snippet(&render_response(&text)), None)
+        }
+        Err(e) => (
+            Err(FailureKind::classify(&e)),
+            String::new(),
+            Some(e.to_string()),
+        ),
+    };
+
+    ResponseEvent {
+        request,
+        sent_at,
+        target,
+        status,
+        latency: started_at.elapsed(),
+        worker,
+        body_snippet,
+        error,
+        attempts: 1,
+    }
+}
+
+pub fn failed(request: &'static str, error: &reqwest::Error) -> ResponseEvent {
+    ResponseEvent {
+        request,
+        sent_at: SystemTime::now(),
+        target: error.url().map(target_of).unwrap_or_default(),
+        status: Err(FailureKind::classify(error)),
+        latency: Duration::ZERO,
+        worker: None,
+        body_snippet: String::new(),
+        error: Some(error.to_string()),
+        attempts: 1,
+    }
+}
+
+// Fallback for when the header didn't make it through, JSON bodies name the worker too.
+fn worker_from_body(text: &str) -> Option<String> {
+    let value = serde_json::from_str::<serde_json::Value>(text).ok()?;
+    value
+        .get("worker")?
+        .as_str()
+        .map(|worker| worker.to_string())
+}
+
+// JSON responses are flattened to `key: value` pairs, anything else is shown as is.
+fn render_response(text: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(serde_json::Value::Object(fields)) => fields
+            .iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(key, value)| match value {
+                serde_json::Value::String(str) => format!("{}: {}", key, str),
+                value => format!("{}: {}", key, value),
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        _ => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{self, Reply};
+
+    const TIMEOUT: Duration = Duration::from_millis(200);
+
+    // Accepts connections, writes `reply` and then holds them open without another byte.
+    async fn hanging_server(reply: &'static str) -> std::net::SocketAddr {
+        mock_server::serve(move |_, _| Reply::raw(reply).held_open()).await
+    }
+
+    fn client() -> reqwest::Client {
+        reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .connect_timeout(TIMEOUT)
+            .build()
+            .unwrap()
+    }
+
+    async fn get(address: std::net::SocketAddr) -> ResponseEvent {
+        let client = client();
+        let req = client
+            .get(format!("http://{}/work", address))
+            .build()
+            .unwrap();
+        execute(&client, RequestType::WORK, req).await
+    }
+
+    #[tokio::test]
+    async fn a_server_that_never_responds_times_out() {
+        let address = hanging_server("").await;
+
+        let event = get(address).await;
+
+        assert_eq!(event.status, Err(FailureKind::Timeout));
+        assert!(event.is_timeout());
+        assert!(event.latency >= TIMEOUT, "{:?}", event.latency);
+        assert!(event.latency < TIMEOUT * 5, "{:?}", event.latency);
+        assert_eq!(event.target, address.to_string());
+        assert!(event.error.is_some());
+        assert_eq!(
+            event.to_string(),
+            format!("timeout {}ms", event.latency.as_millis())
+        );
+    }
+
+    #[tokio::test]
+    async fn a_body_that_never_ends_times_out() {
+        let address = hanging_server("HTTP/1.1 200 OK\r\ncontent-length: 100\r\n\r\npartial").await;
+
+        let event = get(address).await;
+
+        assert_eq!(event.status, Err(FailureKind::Timeout));
+        assert!(event.latency >= TIMEOUT, "{:?}", event.latency);
+        assert!(event.latency < TIMEOUT * 5, "{:?}", event.latency);
+    }
+
+    #[tokio::test]
+    async fn a_closed_port_is_a_connect_error() {
+        let address = mock_server::closed_port().await;
+
+        let event = get(address).await;
+
+        assert_eq!(event.status, Err(FailureKind::Connect));
+        assert!(!event.is_timeout());
+        assert!(event.latency < TIMEOUT, "{:?}", event.latency);
+        assert!(event.to_string().starts_with("connect error "));
+    }
+
+    #[tokio::test]
+    async fn timeouts_count_apart_from_other_errors() {
+        let mut stats = crate::stats::Stats::default();
+
+        stats.record(&get(hanging_server("").await).await);
+        stats.record(
+            &get(
+                hanging_server("HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n")
+                    .await,
+            )
+            .await,
+        );
+
+        let rendered = stats.render();
+        assert!(rendered.contains("Errors:   2 (100.0%)"), "{}", rendered);
+        assert!(rendered.contains("Timeouts: 1\n"), "{}", rendered);
+    }
+
+    fn targets() -> Targets {
+        Targets {
+            load_balancer: "https://lb.example.com/demo/".parse().unwrap(),
+            compare: None,
+            workers: vec!["http://127.0.0.1:3000".parse().unwrap()],
+        }
+    }
+
+    fn built(request: RequestType) -> reqwest::Request {
+        request
+            .build(Arc::new(reqwest::Client::new()), &targets())
+            .unwrap()
+    }
+
+    #[test]
+    fn requests_are_built_against_the_configured_targets() {
+        for (request, method, url) in [
+            (
+                RequestType::ChangeAlgorithm {
+                    new_algo: "round-robin".to_string(),
+                },
+                reqwest::Method::POST,
+                "https://lb.example.com/demo/algo",
+            ),
+            (
+                RequestType::Work {
+                    multiplier: 2,
+                    overrides: WorkOverrides::default(),
+                    target: WorkTarget::LoadBalancer,
+                },
+                reqwest::Method::POST,
+                "https://lb.example.com/demo/work",
+            ),
+            (
+                RequestType::SetupWorker {
+                    server: 0,
+                    min_duration: 10,
+                    max_duration: 20,
+                    error_rate: 0.5,
+                },
+                reqwest::Method::POST,
+                "http://127.0.0.1:3000/setup",
+            ),
+            (
+                RequestType::ResetWorker { server: 0 },
+                reqwest::Method::POST,
+                "http://127.0.0.1:3000/reset",
+            ),
+            (
+                RequestType::ListServers,
+                reqwest::Method::GET,
+                "https://lb.example.com/demo/lb/stats",
+            ),
+        ] {
+            let name = request.name();
+            let req = built(request);
+            assert_eq!(
+                (req.method(), req.url().as_str()),
+                (&method, url),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn work_requests_carry_the_multiplier_and_overrides() {
+        let req = built(RequestType::Work {
+            multiplier: 3,
+            overrides: WorkOverrides {
+                delay_ms: Some(200),
+                status: Some(503),
+                error: true,
+            },
+            target: WorkTarget::Worker(0),
+        });
+
+        assert_eq!(req.url().as_str(), "http://127.0.0.1:3000/work");
+        let body = req.body().and_then(|body| body.as_bytes()).unwrap();
+        assert_eq!(body, br#"{"multiplier":3}"#);
+        let header = |name: &str| req.headers()[name].to_str().unwrap();
+        assert_eq!(header("accept"), "application/json");
+        assert_eq!(header("x-simulate-delay-ms"), "200");
+        assert_eq!(header("x-simulate-status"), "503");
+        assert_eq!(header("x-simulate-error"), "true");
+    }
+
+    #[tokio::test]
+    async fn a_body_cut_short_is_a_body_error() {
+        let address = mock_server::serve(|_, _| {
+            Reply::raw("HTTP/1.1 200 OK\r\ncontent-length: 100\r\n\r\npartial")
+        })
+        .await;
+
+        let event = get(address).await;
+
+        assert_eq!(event.status, Err(FailureKind::Body));
+        assert!(event.to_string().starts_with("body error "));
+    }
+
+    #[tokio::test]
+    async fn responses_name_the_worker_from_the_header_or_the_body() {
+        let address = hanging_server(
+            "HTTP/1.1 200 OK\r\nx-served-by: 127.0.0.1:3001\r\ncontent-length: 9\r\n\r\nWork done",
+        )
+        .await;
+        let event = get(address).await;
+        assert_eq!(event.status, Ok(reqwest::StatusCode::OK));
+        assert_eq!(event.worker.as_deref(), Some("127.0.0.1:3001"));
+        assert_eq!(event.body_snippet, "Work done");
+
+        let body = br#"{"worker":"w2","duration_ms":12,"overrides":null}"#;
+        let reply = format!(
+            "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{}",
+            body.len(),
+            String::from_utf8_lossy(body)
+        );
+        let event = get(hanging_server(reply.leak()).await).await;
+        assert_eq!(event.worker.as_deref(), Some("w2"));
+        assert_eq!(event.body_snippet, "duration_ms: 12, worker: w2");
+    }
+
+    // The guard is dropped right after the event is sent, give the task a moment to get there.
+    async fn settled(in_flight: &InFlight) -> usize {
+        for _ in 0..100 {
+            if in_flight.count() == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        in_flight.count()
+    }
+
+    #[test]
+    fn guards_count_until_dropped() {
+        let in_flight = InFlight::default();
+        let first = in_flight.start();
+        let second = in_flight.clone().start();
+        assert_eq!(in_flight.count(), 2);
+
+        drop(first);
+        assert_eq!(in_flight.count(), 1);
+        drop(second);
+        assert_eq!(in_flight.count(), 0);
+    }
+
+    #[tokio::test]
+    async fn requests_stay_in_flight_until_their_event_arrives() {
+        let ok = hanging_server("HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok").await;
+        let closed = mock_server::closed_port().await;
+        let client = Arc::new(client());
+        let in_flight = InFlight::default();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+
+        for address in [ok, closed] {
+            let req = client
+                .get(format!("http://{}/work", address))
+                .build()
+                .unwrap();
+            send_request(
+                client.clone(),
+                RequestType::WORK,
+                req,
+                tx.clone(),
+                RetryPolicy::NONE,
+                in_flight.start(),
+            )
+            .await;
+        }
+        assert_eq!(in_flight.count(), 2);
+
+        let mut statuses = vec![
+            rx.recv().await.unwrap().status,
+            rx.recv().await.unwrap().status,
+        ];
+        statuses.sort_by_key(|status| status.is_ok());
+        assert_eq!(
+            statuses,
+            [Err(FailureKind::Connect), Ok(reqwest::StatusCode::OK)]
+        );
+        assert_eq!(settled(&in_flight).await, 0);
+    }
+
+    #[tokio::test]
+    async fn timed_out_and_aborted_requests_are_let_go_of() {
+        let client = Arc::new(client());
+        let in_flight = InFlight::default();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+
+        let req = client
+            .get(format!("http://{}/work", hanging_server("").await))
+            .build()
+            .unwrap();
+        send_request(
+            client.clone(),
+            RequestType::WORK,
+            req,
+            tx,
+            RetryPolicy::NONE,
+            in_flight.start(),
+        )
+        .await;
+        assert!(rx.recv().await.unwrap().is_timeout());
+        assert_eq!(settled(&in_flight).await, 0);
+
+        let guard = in_flight.start();
+        let task = tokio::spawn(async move {
+            let _guard = guard;
+            std::future::pending::<()>().await
+        });
+        assert_eq!(in_flight.count(), 1);
+        task.abort();
+        assert!(task.await.unwrap_err().is_cancelled());
+        assert_eq!(in_flight.count(), 0);
+    }
 }
@@ -0,0 +1,193 @@
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use reqwest::StatusCode;
+
+pub const SERVED_BY_HEADER: &str = "x-served-by";
+
+const MAX_SNIPPET_LEN: usize = 80;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FailureKind {
+    Timeout,
+    Connect,
+    Body,
+    Other,
+}
+
+impl FailureKind {
+    pub fn classify(error: &reqwest::Error) -> Self {
+        if error.is_timeout() {
+            FailureKind::Timeout
+        } else if error.is_connect() {
+            FailureKind::Connect
+        } else if error.is_body() || error.is_decode() {
+            FailureKind::Body
+        } else {
+            FailureKind::Other
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FailureKind::Timeout => "timeout",
+            FailureKind::Connect => "connect error",
+            FailureKind::Body => "body error",
+            FailureKind::Other => "request failed",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ResponseEvent {
+    // Name of the request type, see `RequestType::name`.
+    pub request: &'static str,
+    pub sent_at: SystemTime,
+    // `host:port` the request was sent to, empty when it never got a URL.
+    pub target: String,
+    pub status: Result<StatusCode, FailureKind>,
+    pub latency: Duration,
+    pub worker: Option<String>,
+    pub body_snippet: String,
+    // Full error text of a failed request, the status only keeps its kind.
+    pub error: Option<String>,
+    // 1 unless the request was retried, see `retry::execute`.
+    pub attempts: u32,
+}
+
+impl ResponseEvent {
+    pub fn is_success(&self) -> bool {
+        self.status.is_ok_and(|status| status.is_success())
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        self.status == Err(FailureKind::Timeout)
+    }
+
+    pub fn retries(&self) -> u32 {
+        self.attempts.saturating_sub(1)
+    }
+}
+
+// Rendered as a compact line, e.g. `HTTP 200 127.0.0.1:3001 512ms Work done` or `timeout 10000ms`.
+// Retried requests show the total instead, e.g. `HTTP 200 127.0.0.1:3001 after 2 retries, 730ms total`.
+impl fmt::Display for ResponseEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.status {
+            Ok(status) => write!(f, "HTTP {}", status.as_u16())?,
+            Err(kind) => write!(f, "{}", kind)?,
+        }
+        if let Some(worker) = &self.worker {
+            write!(f, " {}", worker)?;
+        }
+        match self.retries() {
+            0 => write!(f, " {}ms", self.latency.as_millis())?,
+            1 => write!(f, " after 1 retry, {}ms total", self.latency.as_millis())?,
+            retries => write!(
+                f,
+                " after {} retries, {}ms total",
+                retries,
+                self.latency.as_millis()
+            )?,
+        }
+        if !self.body_snippet.is_empty() {
+            write!(f, " {}", self.body_snippet)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn target_of(url: &reqwest::Url) -> String {
+    match (url.host_str(), url.port_or_known_default()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        _ => String::new(),
+    }
+}
+
+pub fn snippet(body: &str) -> String {
+    let line = body.lines().next().unwrap_or_default().trim();
+    if line.chars().count() > MAX_SNIPPET_LEN {
+        let truncated: String = line.chars().take(MAX_SNIPPET_LEN).collect();
+        format!("{}...", truncated)
+    } else {
+        line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(status: Result<StatusCode, FailureKind>, worker: Option<&str>) -> ResponseEvent {
+        ResponseEvent {
+            request: "work",
+            sent_at: SystemTime::UNIX_EPOCH,
+            target: "127.0.0.1:80".to_string(),
+            status,
+            latency: Duration::from_millis(512),
+            worker: worker.map(|worker| worker.to_string()),
+            body_snippet: String::new(),
+            error: None,
+            attempts: 1,
+        }
+    }
+
+    #[test]
+    fn responses_render_as_a_compact_line() {
+        let mut served = event(Ok(StatusCode::OK), Some("127.0.0.1:3001"));
+        served.body_snippet = "Work done".to_string();
+        assert_eq!(
+            served.to_string(),
+            "HTTP 200 127.0.0.1:3001 512ms Work done"
+        );
+
+        let unattributed = event(Ok(StatusCode::SERVICE_UNAVAILABLE), None);
+        assert_eq!(unattributed.to_string(), "HTTP 503 512ms");
+    }
+
+    #[test]
+    fn failures_render_their_kind() {
+        for (kind, expected) in [
+            (FailureKind::Timeout, "timeout 512ms"),
+            (FailureKind::Connect, "connect error 512ms"),
+            (FailureKind::Body, "body error 512ms"),
+            (FailureKind::Other, "request failed 512ms"),
+        ] {
+            assert_eq!(event(Err(kind), None).to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn only_2xx_responses_are_successes() {
+        assert!(event(Ok(StatusCode::OK), None).is_success());
+        assert!(!event(Ok(StatusCode::TOO_MANY_REQUESTS), None).is_success());
+        assert!(!event(Ok(StatusCode::BAD_GATEWAY), None).is_success());
+        assert!(!event(Err(FailureKind::Timeout), None).is_success());
+        assert!(event(Err(FailureKind::Timeout), None).is_timeout());
+        assert!(!event(Err(FailureKind::Connect), None).is_timeout());
+    }
+
+    #[test]
+    fn snippets_keep_the_first_line_within_the_limit() {
+        assert_eq!(snippet("  Work done  \nsecond line"), "Work done");
+        assert_eq!(snippet(""), "");
+        let long = "é".repeat(MAX_SNIPPET_LEN + 1);
+        assert_eq!(
+            snippet(&long),
+            format!("{}...", "é".repeat(MAX_SNIPPET_LEN))
+        );
+        assert_eq!(snippet(&long[2..]), "é".repeat(MAX_SNIPPET_LEN));
+    }
+
+    #[test]
+    fn targets_include_the_default_port() {
+        let target = |url: &str| target_of(&url.parse().unwrap());
+        assert_eq!(target("http://127.0.0.1:3001/work"), "127.0.0.1:3001");
+        assert_eq!(target("http://lb.example.com/work"), "lb.example.com:80");
+        assert_eq!(target("https://lb.example.com/work"), "lb.example.com:443");
+    }
+}
@@ -0,0 +1,178 @@
+use std::time::SystemTime;
+
+use tokio::time::{Duration, Instant};
+
+use crate::requests;
+use crate::response_event::{FailureKind, ResponseEvent};
+
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    // Delay before the first retry, doubled for every following one.
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub const NONE: RetryPolicy = RetryPolicy {
+        max_retries: 0,
+        backoff: Duration::ZERO,
+    };
+
+    // Delay before retry number `retry`, counting from 1.
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+    }
+
+    // Only failures a later attempt can plausibly avoid, 4xx are the client's own fault.
+    pub fn should_retry(event: &ResponseEvent) -> bool {
+        match event.status {
+            Ok(status) => status.is_server_error(),
+            Err(kind) => kind == FailureKind::Connect,
+        }
+    }
+}
+
+// Executes the request, retrying per `policy`. The event is the last attempt's, with the
+// latency covering every attempt and the backoff in between.
+pub async fn execute(
+    client: &reqwest::Client,
+    request: &'static str,
+    req: reqwest::Request,
+    policy: RetryPolicy,
+) -> ResponseEvent {
+    let sent_at = SystemTime::now();
+    let started_at = Instant::now();
+    let mut attempts = 1;
+    let mut next = req.try_clone();
+    let mut event = requests::execute(client, request, req).await;
+    while attempts <= policy.max_retries && RetryPolicy::should_retry(&event) {
+        // Streaming bodies can't be cloned, those requests are only sent once.
+        let Some(req) = next.take() else {
+            break;
+        };
+        next = req.try_clone();
+        tokio::time::sleep(policy.delay(attempts)).await;
+        attempts += 1;
+        event = requests::execute(client, request, req).await;
+    }
+
+    ResponseEvent {
+        sent_at,
+        latency: started_at.elapsed(),
+        attempts,
+        ..event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::mock_server::{self, Reply};
+
+    const BACKOFF: Duration = Duration::from_millis(50);
+
+    // Answers the first `failures` requests with `failure`, later ones with 200, one connection each.
+    async fn flaky_server(failures: usize, failure: &'static str) -> (String, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let address = mock_server::serve(move |_, _| {
+            let status = if counter.fetch_add(1, Ordering::SeqCst) < failures {
+                failure
+            } else {
+                "200 OK"
+            };
+            Reply::new(status, &[], "")
+        })
+        .await;
+        (format!("http://{}/work", address), requests)
+    }
+
+    async fn executed(url: &str, max_retries: u32) -> ResponseEvent {
+        let client = reqwest::Client::new();
+        let req = client.post(url).body("{}").build().unwrap();
+        let policy = RetryPolicy {
+            max_retries,
+            backoff: BACKOFF,
+        };
+        execute(&client, "work", req, policy).await
+    }
+
+    #[test]
+    fn the_backoff_doubles_with_every_retry() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            backoff: Duration::from_millis(100),
+        };
+        assert_eq!(
+            (1..=4).map(|retry| policy.delay(retry)).collect::<Vec<_>>(),
+            [100, 200, 400, 800].map(Duration::from_millis).to_vec()
+        );
+        assert_eq!(RetryPolicy::NONE.delay(3), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn failed_attempts_are_retried_and_annotated() {
+        let (url, requests) = flaky_server(2, "503 Service Unavailable").await;
+
+        let event = executed(&url, 3).await;
+
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+        assert!(event.is_success());
+        assert_eq!(event.attempts, 3);
+        // 50ms before the first retry, 100ms before the second.
+        assert!(event.latency >= BACKOFF * 3, "{:?}", event.latency);
+        assert!(event.latency < BACKOFF * 6, "{:?}", event.latency);
+        assert_eq!(
+            event.to_string(),
+            format!(
+                "HTTP 200 after 2 retries, {}ms total",
+                event.latency.as_millis()
+            )
+        );
+
+        let mut stats = crate::stats::Stats::default();
+        stats.record(&event);
+        let rendered = stats.render();
+        assert!(rendered.starts_with("Requests: 1\n"), "{}", rendered);
+        assert!(rendered.contains("Retries:  2\n"), "{}", rendered);
+    }
+
+    #[tokio::test]
+    async fn retries_stop_at_the_limit() {
+        let (url, requests) = flaky_server(usize::MAX, "502 Bad Gateway").await;
+
+        let event = executed(&url, 1).await;
+
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        assert_eq!(event.status, Ok(reqwest::StatusCode::BAD_GATEWAY));
+        assert!(event.to_string().starts_with("HTTP 502 after 1 retry, "));
+    }
+
+    #[tokio::test]
+    async fn client_errors_and_successes_are_not_retried() {
+        let (url, requests) = flaky_server(1, "429 Too Many Requests").await;
+        let event = executed(&url, 3).await;
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert_eq!(event.attempts, 1);
+        assert_eq!(event.status, Ok(reqwest::StatusCode::TOO_MANY_REQUESTS));
+
+        let (url, requests) = flaky_server(0, "").await;
+        assert_eq!(executed(&url, 3).await.attempts, 1);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn connect_errors_are_retried() {
+        let address = mock_server::closed_port().await;
+
+        let event = executed(&format!("http://{}/work", address), 2).await;
+
+        assert_eq!(event.status, Err(FailureKind::Connect));
+        assert_eq!(event.attempts, 3);
+        assert!(event.latency >= BACKOFF * 3, "{:?}", event.latency);
+    }
+}
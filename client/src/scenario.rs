@@ -0,0 +1,406 @@
+use std::fmt;
+use std::path::Path;
+
+use serde::Deserialize;
+
+const DEFAULT_CONCURRENCY: usize = 20;
+
+// A scripted sequence of steps, read from a JSON file, e.g.
+// {"name": "warmup", "repeat": 2, "max_error_rate": 0.05, "steps": [
+//     {"action": "algo", "algo": "round_robin"},
+//     {"action": "setup_worker", "server": 1, "min_duration": 1000, "max_duration": 2000},
+//     {"action": "work", "requests": 5, "multiplier": 10},
+//     {"action": "load", "requests": 100, "rps": 20},
+//     {"action": "wait", "ms": 1000}
+// ]}
+// In the TUI, `key` binds the scenario to a key of the menu.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub key: Option<char>,
+    #[serde(default = "default_repeat")]
+    pub repeat: u32,
+    #[serde(default)]
+    pub max_error_rate: Option<f64>,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Step {
+    Algo {
+        algo: String,
+    },
+    SetupWorker {
+        server: u64,
+        min_duration: u64,
+        max_duration: u64,
+        #[serde(default)]
+        error_rate: f64,
+    },
+    ResetWorker {
+        server: u64,
+    },
+    Work {
+        #[serde(default = "default_requests")]
+        requests: u64,
+        #[serde(default = "default_multiplier")]
+        multiplier: u64,
+    },
+    Load {
+        requests: u64,
+        rps: f64,
+        #[serde(default = "default_concurrency")]
+        concurrency: usize,
+        #[serde(default = "default_multiplier")]
+        multiplier: u64,
+    },
+    Wait {
+        ms: u64,
+    },
+}
+
+fn default_repeat() -> u32 {
+    1
+}
+
+fn default_requests() -> u64 {
+    1
+}
+
+fn default_concurrency() -> usize {
+    DEFAULT_CONCURRENCY
+}
+
+fn default_multiplier() -> u64 {
+    1
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read scenario {}: {}", path.display(), e))?;
+        Scenario::parse(&text).map_err(|e| format!("Invalid scenario {}: {}", path.display(), e))
+    }
+
+    // A file of scenarios bound to keys, e.g. {"scenarios": [{"name": ..., "key": "z", "steps": [...]}]}.
+    pub fn load_all(path: &Path) -> Result<Vec<Self>, String> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct ScenarioFile {
+            scenarios: Vec<Scenario>,
+        }
+
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read scenarios {}: {}", path.display(), e))?;
+        let file: ScenarioFile = serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid scenarios {}: {}", path.display(), e))?;
+        for (index, scenario) in file.scenarios.iter().enumerate() {
+            scenario.validate().map_err(|e| {
+                format!(
+                    "Invalid scenarios {}: scenario {}: {}",
+                    path.display(),
+                    index + 1,
+                    e
+                )
+            })?;
+        }
+        Ok(file.scenarios)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let scenario: Scenario = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.steps.is_empty() {
+            return Err("scenario has no steps".to_string());
+        }
+        if let Some(rate) = self.max_error_rate {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(format!(
+                    "max_error_rate must be between 0 and 1, got {}",
+                    rate
+                ));
+            }
+        }
+        for (index, step) in self.steps.iter().enumerate() {
+            step.validate()
+                .map_err(|e| format!("step {} ({}): {}", index + 1, step, e))?;
+        }
+        Ok(())
+    }
+}
+
+impl Step {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            Step::SetupWorker {
+                min_duration,
+                max_duration,
+                error_rate,
+                ..
+            } => {
+                if min_duration > max_duration {
+                    return Err("min_duration must not be greater than max_duration".to_string());
+                }
+                if !(0.0..=1.0).contains(error_rate) {
+                    return Err("error_rate must be between 0 and 1".to_string());
+                }
+            }
+            Step::Work { requests, .. } => {
+                if *requests == 0 {
+                    return Err("requests must be at least 1".to_string());
+                }
+            }
+            Step::Load {
+                requests,
+                rps,
+                concurrency,
+                ..
+            } => {
+                if *requests == 0 {
+                    return Err("requests must be at least 1".to_string());
+                }
+                if !rps.is_finite() || *rps <= 0.0 {
+                    return Err("rps must be greater than 0".to_string());
+                }
+                if *concurrency == 0 {
+                    return Err("concurrency must be at least 1".to_string());
+                }
+            }
+            Step::Algo { .. } | Step::ResetWorker { .. } | Step::Wait { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Algo { algo } => write!(f, "change algo to {}", algo),
+            Step::SetupWorker {
+                server,
+                min_duration,
+                max_duration,
+                error_rate,
+            } => write!(
+                f,
+                "setup worker {}: {}-{}ms, error rate {}",
+                server, min_duration, max_duration, error_rate
+            ),
+            Step::ResetWorker { server } => write!(f, "reset worker {}", server),
+            Step::Work {
+                requests,
+                multiplier,
+            } => write!(
+                f,
+                "send {} work requests, multiplier {}",
+                requests, multiplier
+            ),
+            Step::Load {
+                requests,
+                rps,
+                concurrency,
+                multiplier,
+            } => write!(
+                f,
+                "load {} requests at {} rps, concurrency {}, multiplier {}",
+                requests, rps, concurrency, multiplier
+            ),
+            Step::Wait { ms } => write!(f, "wait {}ms", ms),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn omitted_fields_take_their_defaults() {
+        let scenario = Scenario::parse(
+            r#"{"steps": [
+                {"action": "work"},
+                {"action": "load", "requests": 10, "rps": 5},
+                {"action": "setup_worker", "server": 1, "min_duration": 10, "max_duration": 20}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(scenario.name, None);
+        assert_eq!(scenario.repeat, 1);
+        assert_eq!(scenario.max_error_rate, None);
+        assert_eq!(
+            scenario
+                .steps
+                .iter()
+                .map(|step| step.to_string())
+                .collect::<Vec<_>>(),
+            [
+                "send 1 work requests, multiplier 1",
+                "load 10 requests at 5 rps, concurrency 20, multiplier 1",
+                "setup worker 1: 10-20ms, error rate 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn every_action_parses() {
+        let scenario = Scenario::parse(
+            r#"{"name": "warmup", "repeat": 2, "max_error_rate": 0.05, "steps": [
+                {"action": "algo", "algo": "round_robin"},
+                {"action": "setup_worker", "server": 1, "min_duration": 1000, "max_duration": 2000, "error_rate": 0.5},
+                {"action": "reset_worker", "server": 1},
+                {"action": "work", "requests": 5, "multiplier": 10},
+                {"action": "load", "requests": 100, "rps": 20, "concurrency": 4, "multiplier": 2},
+                {"action": "wait", "ms": 1000}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(scenario.name.as_deref(), Some("warmup"));
+        assert_eq!(scenario.repeat, 2);
+        assert_eq!(scenario.max_error_rate, Some(0.05));
+        assert_eq!(
+            scenario
+                .steps
+                .iter()
+                .map(|step| step.to_string())
+                .collect::<Vec<_>>(),
+            [
+                "change algo to round_robin",
+                "setup worker 1: 1000-2000ms, error rate 0.5",
+                "reset worker 1",
+                "send 5 work requests, multiplier 10",
+                "load 100 requests at 20 rps, concurrency 4, multiplier 2",
+                "wait 1000ms",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_scenarios_name_the_step_at_fault() {
+        for (text, expected) in [
+            (r#"{"steps": []}"#, "scenario has no steps"),
+            (
+                r#"{"max_error_rate": 1.5, "steps": [{"action": "wait", "ms": 1}]}"#,
+                "max_error_rate must be between 0 and 1, got 1.5",
+            ),
+            (
+                r#"{"steps": [{"action": "wait", "ms": 1}, {"action": "work", "requests": 0}]}"#,
+                "step 2 (send 0 work requests, multiplier 1): requests must be at least 1",
+            ),
+            (
+                r#"{"steps": [{"action": "setup_worker", "server": 0, "min_duration": 20, "max_duration": 10}]}"#,
+                "step 1 (setup worker 0: 20-10ms, error rate 0): min_duration must not be greater than max_duration",
+            ),
+            (
+                r#"{"steps": [{"action": "load", "requests": 1, "rps": 0}]}"#,
+                "step 1 (load 1 requests at 0 rps, concurrency 20, multiplier 1): rps must be greater than 0",
+            ),
+        ] {
+            assert_eq!(Scenario::parse(text).unwrap_err(), expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn unknown_fields_and_actions_are_refused() {
+        for text in [
+            r#"{"steps": [{"action": "wait", "ms": 1}], "extra": true}"#,
+            r#"{"steps": [{"action": "wait", "ms": 1, "extra": true}]}"#,
+            r#"{"steps": [{"action": "sleep", "ms": 1}]}"#,
+            r#"{"steps": [{"action": "wait"}]}"#,
+            "not json",
+        ] {
+            assert!(Scenario::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn missing_files_are_reported_with_their_path() {
+        let error = Scenario::load(Path::new("/nonexistent/scenario.json")).unwrap_err();
+        assert!(
+            error.starts_with("Failed to read scenario /nonexistent/scenario.json: "),
+            "{}",
+            error
+        );
+    }
+
+    // Writes `text` as a scenarios file in a fresh directory, kept alive by the returned guard.
+    fn scenarios_file(text: &str) -> (tempfile::TempDir, std::path::PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scenarios.json");
+        std::fs::write(&path, text).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn scenario_files_bind_scenarios_to_keys() {
+        let (_dir, path) = scenarios_file(
+            r#"{"scenarios": [
+                {"name": "warmup", "key": "z", "steps": [{"action": "work", "requests": 3}]},
+                {"name": "degrade", "key": "x", "steps": [
+                    {"action": "setup_worker", "server": 0, "min_duration": 500, "max_duration": 900},
+                    {"action": "wait", "ms": 1000},
+                    {"action": "reset_worker", "server": 0}
+                ]}
+            ]}"#,
+        );
+
+        let scenarios = Scenario::load_all(&path).unwrap();
+        assert_eq!(
+            scenarios
+                .iter()
+                .map(|scenario| (scenario.name.as_deref(), scenario.key, scenario.steps.len()))
+                .collect::<Vec<_>>(),
+            [
+                (Some("warmup"), Some('z'), 1),
+                (Some("degrade"), Some('x'), 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_scenario_files_name_the_scenario_at_fault() {
+        let (_dir, path) = scenarios_file(
+            r#"{"scenarios": [
+                {"key": "z", "steps": [{"action": "wait", "ms": 1}]},
+                {"key": "x", "steps": [{"action": "work", "requests": 0}]}
+            ]}"#,
+        );
+        assert_eq!(
+            Scenario::load_all(&path).unwrap_err(),
+            format!(
+                "Invalid scenarios {}: scenario 2: step 1 (send 0 work requests, multiplier 1): requests must be at least 1",
+                path.display()
+            )
+        );
+
+        for text in [
+            r#"{"scenarios": [{"steps": []}], "extra": 1}"#,
+            "[]",
+            "not json",
+        ] {
+            let (_dir, path) = scenarios_file(text);
+            let error = Scenario::load_all(&path).unwrap_err();
+            assert!(
+                error.starts_with(&format!("Invalid scenarios {}: ", path.display())),
+                "{}",
+                error
+            );
+        }
+
+        let error = Scenario::load_all(Path::new("/nonexistent/scenarios.json")).unwrap_err();
+        assert!(
+            error.starts_with("Failed to read scenarios /nonexistent/scenarios.json: "),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+
+use chrono::Local;
+
+// Appends timestamped lines to a file from a writer thread, so the UI loop never waits on disk.
+pub struct SessionLog {
+    tx: mpsc::Sender<String>,
+    writer: JoinHandle<io::Result<()>>,
+}
+
+impl SessionLog {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let (tx, rx) = mpsc::channel();
+        let writer = std::thread::spawn(move || write_lines(file, rx));
+        Ok(SessionLog { tx, writer })
+    }
+
+    pub fn write(&self, line: &str) {
+        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+        // The writer only stops early on an IO error, which `close` reports.
+        let _ = self.tx.send(format!("{} {}", timestamp, line));
+    }
+
+    // Flushes everything written so far and closes the file.
+    pub fn close(self) -> io::Result<()> {
+        drop(self.tx);
+        self.writer
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("session log writer panicked")))
+    }
+}
+
+// Flushes whenever the queue runs empty, so a crash loses at most the current batch.
+fn write_lines(file: File, rx: mpsc::Receiver<String>) -> io::Result<()> {
+    let mut out = BufWriter::new(file);
+    while let Ok(line) = rx.recv() {
+        writeln!(out, "{}", line)?;
+        while let Ok(line) = rx.try_recv() {
+            writeln!(out, "{}", line)?;
+        }
+        out.flush()?;
+    }
+    out.flush()
+}
+
+// The last `count` lines of an earlier session, empty when there is no log yet.
+pub fn tail(path: &Path, count: usize) -> io::Result<Vec<String>> {
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut lines = std::collections::VecDeque::with_capacity(count);
+    for line in BufReader::new(file).lines() {
+        if lines.len() == count {
+            lines.pop_front();
+        }
+        lines.push_back(line?);
+    }
+    Ok(lines.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Splits `2024-05-01 12:00:00.123 text` into its timestamp and text.
+    fn parsed(line: &str) -> (chrono::NaiveDateTime, &str) {
+        let (timestamp, text) = line.split_at(23);
+        let timestamp =
+            chrono::NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S%.3f").unwrap();
+        (timestamp, text.strip_prefix(' ').unwrap())
+    }
+
+    #[test]
+    fn lines_are_appended_with_a_timestamp_and_flushed_on_close() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.log");
+        std::fs::write(&path, "2024-05-01 12:00:00.000 earlier session\n").unwrap();
+
+        let log = SessionLog::open(&path).unwrap();
+        log.write("Sending request...");
+        log.write("HTTP 200 12ms");
+        log.close().unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3, "{}", text);
+        assert_eq!(lines[0], "2024-05-01 12:00:00.000 earlier session");
+        let (first, text) = parsed(lines[1]);
+        assert_eq!(text, "Sending request...");
+        let (second, text) = parsed(lines[2]);
+        assert_eq!(text, "HTTP 200 12ms");
+        assert!(first <= second);
+        let age = Local::now().naive_local() - first;
+        assert!(age < chrono::Duration::seconds(10), "{}", age);
+    }
+
+    #[test]
+    fn opening_fails_for_an_unwritable_path() {
+        assert!(SessionLog::open(Path::new("/nonexistent/session.log")).is_err());
+    }
+
+    #[test]
+    fn the_tail_of_an_earlier_session_is_restored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.log");
+        let fixture = (1..=5)
+            .map(|i| format!("2024-05-01 12:00:0{}.000 line {}\n", i, i))
+            .collect::<String>();
+        std::fs::write(&path, fixture).unwrap();
+
+        assert_eq!(
+            tail(&path, 2).unwrap(),
+            [
+                "2024-05-01 12:00:04.000 line 4",
+                "2024-05-01 12:00:05.000 line 5"
+            ]
+        );
+        assert_eq!(tail(&path, 10).unwrap().len(), 5);
+        assert!(tail(&path, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn there_is_nothing_to_restore_without_a_log() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(tail(&dir.path().join("session.log"), 10)
+            .unwrap()
+            .is_empty());
+        assert!(tail(dir.path(), 10).is_err());
+    }
+}
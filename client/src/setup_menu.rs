@@ -0,0 +1,283 @@
+use std::fmt;
+
+use crossterm::event::KeyCode;
+
+use client::config::Targets;
+use client::requests::RequestType;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Preset {
+    Reset,
+    Slow,
+    Flaky,
+    VerySlow,
+    Custom,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 5] = [
+        Preset::Reset,
+        Preset::Slow,
+        Preset::Flaky,
+        Preset::VerySlow,
+        Preset::Custom,
+    ];
+
+    fn key(&self) -> char {
+        match self {
+            Preset::Reset => 'r',
+            Preset::Slow => 's',
+            Preset::Flaky => 'f',
+            Preset::VerySlow => 'v',
+            Preset::Custom => 'c',
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Preset::Reset => "defaults",
+            Preset::Slow => "1000-2000ms",
+            Preset::Flaky => "500-1000ms, 33% errors",
+            Preset::VerySlow => "3000-5000ms",
+            Preset::Custom => "enter values",
+        }
+    }
+
+    // The request applying the preset, custom values have to be asked for first.
+    pub fn request(&self, server: u64) -> Option<RequestType> {
+        let (min_duration, max_duration, error_rate) = match self {
+            Preset::Reset => return Some(RequestType::ResetWorker { server }),
+            Preset::Slow => (1000, 2000, 0.0),
+            Preset::Flaky => (500, 1000, 0.33),
+            Preset::VerySlow => (3000, 5000, 0.0),
+            Preset::Custom => return None,
+        };
+        Some(RequestType::SetupWorker {
+            server,
+            min_duration,
+            max_duration,
+            error_rate,
+        })
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Preset::Reset => "reset",
+            Preset::Slow => "slow",
+            Preset::Flaky => "flaky",
+            Preset::VerySlow => "very slow",
+            Preset::Custom => "custom",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+pub enum SetupMenuState {
+    Pending,
+    Cancelled,
+    Selected { server: u64, preset: Preset },
+}
+
+// Two step menu: pick a worker by its number, then the preset to apply to it.
+pub enum SetupMenu {
+    SelectWorker,
+    SelectPreset { server: u64 },
+}
+
+impl SetupMenu {
+    pub fn handle_key(&mut self, code: KeyCode, worker_count: u64) -> SetupMenuState {
+        match self {
+            SetupMenu::SelectWorker => match code {
+                KeyCode::Esc => return SetupMenuState::Cancelled,
+                KeyCode::Char(c) => {
+                    let number = c.to_digit(10).map_or(0, u64::from);
+                    if (1..=worker_count).contains(&number) {
+                        *self = SetupMenu::SelectPreset { server: number - 1 };
+                    }
+                }
+                _ => {}
+            },
+            SetupMenu::SelectPreset { server } => match code {
+                KeyCode::Esc => *self = SetupMenu::SelectWorker,
+                KeyCode::Char(c) => {
+                    if let Some(preset) = Preset::ALL.into_iter().find(|p| p.key() == c) {
+                        return SetupMenuState::Selected {
+                            server: *server,
+                            preset,
+                        };
+                    }
+                }
+                _ => {}
+            },
+        }
+        SetupMenuState::Pending
+    }
+
+    pub fn title(&self, targets: &Targets) -> String {
+        match self {
+            SetupMenu::SelectWorker => "Setup - choose worker, Esc to cancel".to_string(),
+            SetupMenu::SelectPreset { server } => format!(
+                "Setup {} - choose preset, Esc to go back",
+                worker_name(targets, *server)
+            ),
+        }
+    }
+
+    pub fn items(&self, targets: &Targets) -> Vec<String> {
+        match self {
+            SetupMenu::SelectWorker => (0..targets.worker_count())
+                .map(|server| format!("{} - {}", server + 1, worker_name(targets, server)))
+                .collect(),
+            SetupMenu::SelectPreset { .. } => Preset::ALL
+                .iter()
+                .map(|preset| format!("{} - {} ({})", preset.key(), preset, preset.description()))
+                .collect(),
+        }
+    }
+}
+
+pub fn worker_name(targets: &Targets, server: u64) -> String {
+    format!("worker {} ({})", server + 1, targets.worker(server, "/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets() -> Targets {
+        Targets {
+            load_balancer: "http://127.0.0.1".parse().unwrap(),
+            compare: None,
+            workers: (0..3)
+                .map(|i| format!("http://127.0.0.1:{}", 3000 + i).parse().unwrap())
+                .collect(),
+        }
+    }
+
+    fn server(menu: &SetupMenu) -> Option<u64> {
+        match menu {
+            SetupMenu::SelectWorker => None,
+            SetupMenu::SelectPreset { server } => Some(*server),
+        }
+    }
+
+    // The setup a request applies, None for a reset.
+    fn setup(request: RequestType) -> Option<(u64, u64, u64, f64)> {
+        match request {
+            RequestType::SetupWorker {
+                server,
+                min_duration,
+                max_duration,
+                error_rate,
+            } => Some((server, min_duration, max_duration, error_rate)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn only_configured_workers_can_be_chosen() {
+        let mut menu = SetupMenu::SelectWorker;
+        for key in [
+            KeyCode::Char('0'),
+            KeyCode::Char('4'),
+            KeyCode::Char('x'),
+            KeyCode::Enter,
+        ] {
+            assert!(matches!(menu.handle_key(key, 3), SetupMenuState::Pending));
+            assert_eq!(server(&menu), None);
+        }
+
+        menu.handle_key(KeyCode::Char('3'), 3);
+        assert_eq!(server(&menu), Some(2));
+    }
+
+    #[test]
+    fn escape_goes_back_then_cancels() {
+        let mut menu = SetupMenu::SelectWorker;
+        menu.handle_key(KeyCode::Char('2'), 3);
+
+        assert!(matches!(
+            menu.handle_key(KeyCode::Esc, 3),
+            SetupMenuState::Pending
+        ));
+        assert_eq!(server(&menu), None);
+        assert!(matches!(
+            menu.handle_key(KeyCode::Esc, 3),
+            SetupMenuState::Cancelled
+        ));
+    }
+
+    #[test]
+    fn a_preset_key_selects_it_for_the_chosen_worker() {
+        for preset in Preset::ALL {
+            let mut menu = SetupMenu::SelectWorker;
+            menu.handle_key(KeyCode::Char('2'), 3);
+            assert!(matches!(
+                menu.handle_key(KeyCode::Char('x'), 3),
+                SetupMenuState::Pending
+            ));
+
+            match menu.handle_key(KeyCode::Char(preset.key()), 3) {
+                SetupMenuState::Selected {
+                    server,
+                    preset: selected,
+                } => assert_eq!((server, selected), (1, preset)),
+                _ => panic!("{} wasn't selected", preset),
+            }
+        }
+    }
+
+    #[test]
+    fn presets_build_their_setup_requests() {
+        assert!(matches!(
+            Preset::Reset.request(2),
+            Some(RequestType::ResetWorker { server: 2 })
+        ));
+        assert_eq!(
+            Preset::Slow.request(0).and_then(setup),
+            Some((0, 1000, 2000, 0.0))
+        );
+        assert_eq!(
+            Preset::Flaky.request(1).and_then(setup),
+            Some((1, 500, 1000, 0.33))
+        );
+        assert_eq!(
+            Preset::VerySlow.request(2).and_then(setup),
+            Some((2, 3000, 5000, 0.0))
+        );
+        assert!(Preset::Custom.request(0).is_none());
+    }
+
+    #[test]
+    fn items_list_the_configured_workers_then_the_presets() {
+        let targets = targets();
+        let mut menu = SetupMenu::SelectWorker;
+        assert_eq!(menu.title(&targets), "Setup - choose worker, Esc to cancel");
+        assert_eq!(
+            menu.items(&targets),
+            [
+                "1 - worker 1 (http://127.0.0.1:3000/)",
+                "2 - worker 2 (http://127.0.0.1:3001/)",
+                "3 - worker 3 (http://127.0.0.1:3002/)",
+            ]
+        );
+
+        menu.handle_key(KeyCode::Char('2'), targets.worker_count());
+        assert_eq!(
+            menu.title(&targets),
+            "Setup worker 2 (http://127.0.0.1:3001/) - choose preset, Esc to go back"
+        );
+        assert_eq!(
+            menu.items(&targets),
+            [
+                "r - reset (defaults)",
+                "s - slow (1000-2000ms)",
+                "f - flaky (500-1000ms, 33% errors)",
+                "v - very slow (3000-5000ms)",
+                "c - custom (enter values)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::BTreeMap;
+
+use crate::requests::RequestType;
+use crate::response_event::ResponseEvent;
+
+const UNKNOWN_WORKER: &str = "unknown";
+
+// Aggregates response events since the last reset.
+#[derive(Default)]
+pub struct Stats {
+    total: u64,
+    errors: u64,
+    // Also counted in `errors`.
+    timeouts: u64,
+    // Extra attempts of retried requests, which aren't counted in `total`.
+    retries: u64,
+    // Kept sorted so percentiles are a lookup.
+    latencies_ms: Vec<u64>,
+    per_worker: BTreeMap<String, u64>,
+    // Work only, other requests don't choose their target.
+    per_target: BTreeMap<String, TargetStats>,
+}
+
+#[derive(Default)]
+struct TargetStats {
+    total: u64,
+    errors: u64,
+    latency_ms_sum: u64,
+}
+
+impl TargetStats {
+    fn average(&self) -> u64 {
+        self.latency_ms_sum / self.total.max(1)
+    }
+}
+
+impl Stats {
+    pub fn record(&mut self, event: &ResponseEvent) {
+        self.total += 1;
+        if !event.is_success() {
+            self.errors += 1;
+        }
+        if event.is_timeout() {
+            self.timeouts += 1;
+        }
+        self.retries += event.retries() as u64;
+
+        let latency_ms = event.latency.as_millis() as u64;
+        let index = self.latencies_ms.partition_point(|&l| l <= latency_ms);
+        self.latencies_ms.insert(index, latency_ms);
+
+        let worker = event.worker.as_deref().unwrap_or(UNKNOWN_WORKER);
+        *self.per_worker.entry(worker.to_string()).or_default() += 1;
+
+        if event.request == RequestType::WORK {
+            let target = self.per_target.entry(event.target.clone()).or_default();
+            target.total += 1;
+            if !event.is_success() {
+                target.errors += 1;
+            }
+            target.latency_ms_sum += latency_ms;
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn per_worker(&self) -> &BTreeMap<String, u64> {
+        &self.per_worker
+    }
+
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    // Fraction of failed requests, 0 when nothing was recorded.
+    pub fn error_rate(&self) -> f64 {
+        if self.total > 0 {
+            self.errors as f64 / self.total as f64
+        } else {
+            0.0
+        }
+    }
+
+    fn average(&self) -> Option<u64> {
+        (!self.latencies_ms.is_empty())
+            .then(|| self.latencies_ms.iter().sum::<u64>() / self.latencies_ms.len() as u64)
+    }
+
+    pub fn reset(&mut self) {
+        *self = Stats::default();
+    }
+
+    // Nearest-rank percentile, `p` between 0 and 100.
+    pub fn percentile(&self, p: f64) -> Option<u64> {
+        if self.latencies_ms.is_empty() {
+            return None;
+        }
+        let rank = (p / 100.0 * self.latencies_ms.len() as f64).ceil() as usize;
+        let index = rank.clamp(1, self.latencies_ms.len()) - 1;
+        Some(self.latencies_ms[index])
+    }
+
+    pub fn render(&self) -> String {
+        let mut text = format!(
+            "Requests: {}\nSuccess:  {}\nErrors:   {} ({:.1}%)\nTimeouts: {}\nRetries:  {}\n",
+            self.total,
+            self.total - self.errors,
+            self.errors,
+            self.error_rate() * 100.0,
+            self.timeouts,
+            self.retries
+        );
+
+        let ms = |value: Option<u64>| value.map_or("-".to_string(), |v| format!("{}ms", v));
+        text.push_str(&format!(
+            "\nLatency\n min {}\n avg {}\n p50 {}\n p95 {}\n max {}\n",
+            ms(self.latencies_ms.first().copied()),
+            ms(self.average()),
+            ms(self.percentile(50.0)),
+            ms(self.percentile(95.0)),
+            ms(self.latencies_ms.last().copied()),
+        ));
+
+        if !self.per_worker.is_empty() {
+            text.push_str("\nPer worker\n");
+            for (worker, count) in &self.per_worker {
+                text.push_str(&format!(" {} {}\n", worker, count));
+            }
+        }
+
+        // Only worth a section once work went to more than one target.
+        if self.per_target.len() > 1 {
+            text.push_str("\nPer target\n");
+            for (target, stats) in &self.per_target {
+                text.push_str(&format!(
+                    " {}\n  {} req, {} err, avg {}ms\n",
+                    target,
+                    stats.total,
+                    stats.errors,
+                    stats.average()
+                ));
+            }
+        }
+        text
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "total": self.total,
+            "success": self.total - self.errors,
+            "errors": self.errors,
+            "timeouts": self.timeouts,
+            "retries": self.retries,
+            "error_rate": self.error_rate(),
+            "latency_ms": {
+                "min": self.latencies_ms.first(),
+                "avg": self.average(),
+                "p50": self.percentile(50.0),
+                "p95": self.percentile(95.0),
+                "max": self.latencies_ms.last(),
+            },
+            "per_worker": self.per_worker,
+            "per_target": self
+                .per_target
+                .iter()
+                .map(|(target, stats)| {
+                    (
+                        target.clone(),
+                        serde_json::json!({
+                            "total": stats.total,
+                            "errors": stats.errors,
+                            "avg_latency_ms": stats.average(),
+                        }),
+                    )
+                })
+                .collect::<serde_json::Map<_, _>>(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use reqwest::StatusCode;
+
+    use super::*;
+    use crate::response_event::FailureKind;
+
+    fn event(latency_ms: u64, status: Result<StatusCode, FailureKind>) -> ResponseEvent {
+        ResponseEvent {
+            request: RequestType::WORK,
+            sent_at: SystemTime::UNIX_EPOCH,
+            target: "127.0.0.1:80".to_string(),
+            status,
+            latency: Duration::from_millis(latency_ms),
+            worker: None,
+            body_snippet: String::new(),
+            error: None,
+            attempts: 1,
+        }
+    }
+
+    fn served(latency_ms: u64, worker: &str) -> ResponseEvent {
+        ResponseEvent {
+            worker: Some(worker.to_string()),
+            ..event(latency_ms, Ok(StatusCode::OK))
+        }
+    }
+
+    fn recorded(latencies_ms: impl IntoIterator<Item = u64>) -> Stats {
+        let mut stats = Stats::default();
+        for latency_ms in latencies_ms {
+            stats.record(&event(latency_ms, Ok(StatusCode::OK)));
+        }
+        stats
+    }
+
+    #[test]
+    fn percentiles_are_nearest_rank() {
+        // Recorded out of order, kept sorted.
+        let stats = recorded((1..=100).rev());
+
+        assert_eq!(stats.percentile(50.0), Some(50));
+        assert_eq!(stats.percentile(95.0), Some(95));
+        assert_eq!(stats.percentile(99.5), Some(100));
+        assert_eq!(stats.percentile(100.0), Some(100));
+        assert_eq!(stats.percentile(0.0), Some(1));
+    }
+
+    #[test]
+    fn percentiles_of_few_samples() {
+        assert_eq!(Stats::default().percentile(50.0), None);
+
+        let stats = recorded([42]);
+        assert_eq!(stats.percentile(50.0), Some(42));
+        assert_eq!(stats.percentile(95.0), Some(42));
+
+        let stats = recorded([30, 10, 20, 10]);
+        assert_eq!(stats.percentile(50.0), Some(10));
+        assert_eq!(stats.percentile(75.0), Some(20));
+        assert_eq!(stats.percentile(95.0), Some(30));
+    }
+
+    #[test]
+    fn the_summary_shows_errors_latencies_and_workers() {
+        let mut stats = Stats::default();
+        stats.record(&served(10, "127.0.0.1:3000"));
+        stats.record(&served(30, "127.0.0.1:3001"));
+        stats.record(&served(20, "127.0.0.1:3000"));
+        stats.record(&event(40, Ok(StatusCode::BAD_GATEWAY)));
+
+        assert_eq!(stats.total(), 4);
+        assert_eq!(stats.errors(), 1);
+        assert_eq!(stats.error_rate(), 0.25);
+        assert_eq!(
+            stats.render(),
+            "Requests: 4\nSuccess:  3\nErrors:   1 (25.0%)\nTimeouts: 0\nRetries:  0\n\
+             \nLatency\n min 10ms\n avg 25ms\n p50 20ms\n p95 40ms\n max 40ms\n\
+             \nPer worker\n 127.0.0.1:3000 2\n 127.0.0.1:3001 1\n unknown 1\n"
+        );
+    }
+
+    #[test]
+    fn reset_starts_over() {
+        let mut stats = recorded([10, 20]);
+        stats.reset();
+
+        assert_eq!(stats.total(), 0);
+        assert_eq!(stats.error_rate(), 0.0);
+        assert!(stats.per_worker().is_empty());
+        assert_eq!(
+            stats.render(),
+            "Requests: 0\nSuccess:  0\nErrors:   0 (0.0%)\nTimeouts: 0\nRetries:  0\n\
+             \nLatency\n min -\n avg -\n p50 -\n p95 -\n max -\n"
+        );
+    }
+
+    fn sent_to(target: &str, latency_ms: u64, status: StatusCode) -> ResponseEvent {
+        ResponseEvent {
+            target: target.to_string(),
+            ..event(latency_ms, Ok(status))
+        }
+    }
+
+    #[test]
+    fn work_is_segmented_by_target() {
+        let mut stats = Stats::default();
+        stats.record(&sent_to("127.0.0.1:8080", 10, StatusCode::OK));
+        stats.record(&sent_to(
+            "127.0.0.1:8080",
+            30,
+            StatusCode::SERVICE_UNAVAILABLE,
+        ));
+        stats.record(&sent_to("127.0.0.1:3001", 100, StatusCode::OK));
+        // Only work counts per target.
+        stats.record(&ResponseEvent {
+            request: "change_algo",
+            ..sent_to("127.0.0.1:9000", 5, StatusCode::OK)
+        });
+
+        let rendered = stats.render();
+        assert!(
+            rendered.ends_with(
+                "\nPer target\n 127.0.0.1:3001\n  1 req, 0 err, avg 100ms\n \
+                 127.0.0.1:8080\n  2 req, 1 err, avg 20ms\n"
+            ),
+            "{}",
+            rendered
+        );
+        assert_eq!(
+            stats.to_json()["per_target"],
+            serde_json::json!({
+                "127.0.0.1:3001": {"total": 1, "errors": 0, "avg_latency_ms": 100},
+                "127.0.0.1:8080": {"total": 2, "errors": 1, "avg_latency_ms": 20},
+            })
+        );
+    }
+
+    #[test]
+    fn a_single_target_has_no_segment_section() {
+        let stats = recorded([10, 20]);
+        assert!(!stats.render().contains("Per target"));
+        assert_eq!(
+            stats.to_json()["per_target"],
+            serde_json::json!({"127.0.0.1:80": {"total": 2, "errors": 0, "avg_latency_ms": 15}})
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use std::time::Instant;
+
+const WINDOW_SECS: u64 = 10;
+
+// Completed requests counted per whole second since `start`, over the last `WINDOW_SECS`.
+// Every method takes the current time so callers decide the clock.
+pub struct Throughput {
+    start: Instant,
+    // (second, count) with slot `second % WINDOW_SECS`, stale slots are reset on reuse.
+    buckets: [(u64, u64); WINDOW_SECS as usize],
+}
+
+impl Throughput {
+    pub fn new(start: Instant) -> Self {
+        Throughput {
+            start,
+            buckets: [(0, 0); WINDOW_SECS as usize],
+        }
+    }
+
+    pub fn record(&mut self, now: Instant) {
+        let second = self.second(now);
+        let bucket = &mut self.buckets[(second % WINDOW_SECS) as usize];
+        if bucket.0 != second {
+            *bucket = (second, 0);
+        }
+        bucket.1 += 1;
+    }
+
+    // Requests completed in the last whole second, the current one is still counting.
+    pub fn last_second(&self, now: Instant) -> u64 {
+        self.completed_in(now, 1)
+    }
+
+    pub fn last_ten_seconds(&self, now: Instant) -> u64 {
+        self.completed_in(now, WINDOW_SECS)
+    }
+
+    pub fn summary(&self, now: Instant) -> String {
+        format!(
+            "{}/s, {} in {}s",
+            self.last_second(now),
+            self.last_ten_seconds(now),
+            WINDOW_SECS
+        )
+    }
+
+    // Sum over the `secs` whole seconds before the current one.
+    fn completed_in(&self, now: Instant, secs: u64) -> u64 {
+        let current = self.second(now);
+        self.buckets
+            .iter()
+            .filter(|(second, _)| *second < current && *second + secs >= current)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    fn second(&self, now: Instant) -> u64 {
+        now.saturating_duration_since(self.start).as_secs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn at(start: Instant, ms: u64) -> Instant {
+        start + Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn no_traffic_reads_zero() {
+        let start = Instant::now();
+        let throughput = Throughput::new(start);
+
+        for ms in [0, 999, 1_000, 25_000] {
+            assert_eq!(throughput.summary(at(start, ms)), "0/s, 0 in 10s");
+        }
+    }
+
+    #[test]
+    fn only_whole_seconds_are_counted() {
+        let start = Instant::now();
+        let mut throughput = Throughput::new(start);
+        for ms in [100, 400, 900, 1_200] {
+            throughput.record(at(start, ms));
+        }
+
+        // The current second is still counting.
+        assert_eq!(throughput.last_second(at(start, 950)), 0);
+        assert_eq!(throughput.last_second(at(start, 1_500)), 3);
+        assert_eq!(throughput.last_second(at(start, 2_000)), 1);
+        assert_eq!(throughput.summary(at(start, 2_000)), "1/s, 4 in 10s");
+    }
+
+    #[test]
+    fn seconds_leave_the_window_after_ten_seconds() {
+        let start = Instant::now();
+        let mut throughput = Throughput::new(start);
+        for second in 0..12 {
+            for _ in 0..=second {
+                throughput.record(at(start, second * 1_000 + 500));
+            }
+        }
+
+        // Seconds 2 to 11, with 3 to 12 requests each.
+        assert_eq!(throughput.last_second(at(start, 12_000)), 12);
+        assert_eq!(throughput.last_ten_seconds(at(start, 12_000)), 75);
+        // Seconds 7 to 11 are all that's left.
+        assert_eq!(throughput.last_ten_seconds(at(start, 17_000)), 50);
+    }
+
+    #[test]
+    fn stale_values_clear_once_traffic_stops() {
+        let start = Instant::now();
+        let mut throughput = Throughput::new(start);
+        for _ in 0..5 {
+            throughput.record(at(start, 3_100));
+        }
+        assert_eq!(throughput.summary(at(start, 4_000)), "5/s, 5 in 10s");
+        assert_eq!(throughput.summary(at(start, 5_000)), "0/s, 5 in 10s");
+        assert_eq!(throughput.summary(at(start, 14_000)), "0/s, 0 in 10s");
+        assert_eq!(throughput.summary(at(start, 30_000)), "0/s, 0 in 10s");
+
+        // A reused slot starts over rather than adding to the stale count.
+        throughput.record(at(start, 33_500));
+        assert_eq!(throughput.summary(at(start, 34_000)), "1/s, 1 in 10s");
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use lb_api::StatsResponse;
+
+// How long the balancer's title stands out after the algorithm changed.
+const FLASH_FOR: Duration = Duration::from_secs(2);
+
+// The algorithm GET /lb/stats reports, e.g. `{"algorithm": "least_connections", ...}`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActiveAlgorithm {
+    pub name: String,
+}
+
+impl ActiveAlgorithm {
+    pub fn from_stats(stats: StatsResponse) -> Self {
+        ActiveAlgorithm {
+            name: stats.algorithm,
+        }
+    }
+}
+
+impl fmt::Display for ActiveAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+// The algorithm shown in the balancer's title, and when it last changed.
+#[derive(Default)]
+pub struct AlgorithmTitle {
+    // None while polling fails, the title is then the plain pane name.
+    shown: Option<ActiveAlgorithm>,
+    // Kept through failed polls so the balancer coming back isn't taken for a change.
+    last_seen: Option<ActiveAlgorithm>,
+    changed_at: Option<Instant>,
+}
+
+impl AlgorithmTitle {
+    // Returns the line noting a change, None for the first algorithm seen or the same one again.
+    pub fn update(
+        &mut self,
+        result: Result<ActiveAlgorithm, String>,
+        now: Instant,
+    ) -> Option<String> {
+        let Ok(algorithm) = result else {
+            self.shown = None;
+            return None;
+        };
+        self.shown = Some(algorithm.clone());
+        let previous = self.last_seen.replace(algorithm.clone())?;
+        (previous != algorithm).then(|| {
+            self.changed_at = Some(now);
+            format!("Algorithm changed from {} to {}", previous, algorithm)
+        })
+    }
+
+    pub fn shown(&self) -> Option<&ActiveAlgorithm> {
+        self.shown.as_ref()
+    }
+
+    pub fn is_flashing(&self, now: Instant) -> bool {
+        self.changed_at
+            .is_some_and(|at| now.duration_since(at) < FLASH_FOR)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn active(name: &str) -> Result<ActiveAlgorithm, String> {
+        Ok(ActiveAlgorithm {
+            name: name.to_string(),
+        })
+    }
+
+    #[test]
+    fn the_algorithm_is_read_from_the_stats() {
+        let stats = serde_json::from_value::<StatsResponse>(json!({
+            "algorithm": "maglev",
+            "servers": []
+        }))
+        .unwrap();
+        assert_eq!(
+            ActiveAlgorithm::from_stats(stats),
+            ActiveAlgorithm {
+                name: "maglev".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn only_changes_are_noted_and_flashed() {
+        let mut title = AlgorithmTitle::default();
+        let now = Instant::now();
+
+        assert_eq!(title.update(active("round_robin"), now), None);
+        assert_eq!(title.update(active("round_robin"), now), None);
+        assert!(!title.is_flashing(now));
+
+        assert_eq!(
+            title.update(active("maglev"), now).as_deref(),
+            Some("Algorithm changed from round_robin to maglev")
+        );
+        assert!(title.is_flashing(now + Duration::from_secs(1)));
+        assert!(!title.is_flashing(now + FLASH_FOR));
+    }
+
+    #[test]
+    fn the_balancer_coming_back_is_not_a_change() {
+        let mut title = AlgorithmTitle::default();
+        let now = Instant::now();
+
+        title.update(active("maglev"), now);
+        assert_eq!(
+            title.update(Err("connection refused".to_string()), now),
+            None
+        );
+        assert_eq!(title.shown(), None);
+        assert_eq!(title.update(active("maglev"), now), None);
+        assert_eq!(title.shown().map(|a| a.name.as_str()), Some("maglev"));
+    }
+}
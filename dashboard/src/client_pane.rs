@@ -0,0 +1,183 @@
+use std::env;
+use std::path::PathBuf;
+
+use crate::executable;
+use crate::ProcessSpec;
+
+pub const CLIENT: &str = "client";
+
+// The client running scenario files headless in its own pane, when CLIENT_SCENARIOS lists some.
+pub struct ClientPane {
+    scenarios: Vec<PathBuf>,
+    current: usize,
+    // Set when the scenario changed while the client was still running, it starts again once it exited.
+    switch_pending: bool,
+}
+
+impl ClientPane {
+    // CLIENT_SCENARIOS is a comma separated list of scenario files, 'n' goes to the next one.
+    pub fn from_env() -> Option<Self> {
+        Self::from_list(&env::var("CLIENT_SCENARIOS").ok()?)
+    }
+
+    fn from_list(list: &str) -> Option<Self> {
+        let scenarios = list
+            .split(',')
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .collect::<Vec<_>>();
+        (!scenarios.is_empty()).then_some(ClientPane {
+            scenarios,
+            current: 0,
+            switch_pending: false,
+        })
+    }
+
+    // Runs `client run --scenario <file>` against the dashboard's balancer, CLIENT_ARGS go
+    // before the subcommand since they are the client's global options.
+    pub fn spec(&self, lb_url: &str) -> ProcessSpec {
+        self.spec_with(
+            lb_url,
+            executable::extra_args(CLIENT),
+            executable::extra_env(CLIENT),
+        )
+    }
+
+    fn spec_with(
+        &self,
+        lb_url: &str,
+        mut args: Vec<String>,
+        extra_env: Vec<(String, String)>,
+    ) -> ProcessSpec {
+        args.extend([
+            "run".to_string(),
+            "--scenario".to_string(),
+            self.scenarios[self.current].display().to_string(),
+        ]);
+        let mut env = vec![("LB_URL".to_string(), lb_url.to_string())];
+        env.extend(extra_env);
+        ProcessSpec::Local {
+            name: CLIENT.to_string(),
+            args,
+            env,
+        }
+    }
+
+    pub fn scenario(&self) -> String {
+        self.scenarios[self.current].display().to_string()
+    }
+
+    // Moves to the next scenario, wrapping around. `running` tells whether the client
+    // has to exit before it can start with it.
+    pub fn next(&mut self, running: bool) {
+        self.current = (self.current + 1) % self.scenarios.len();
+        self.switch_pending = running;
+    }
+
+    // Whether the exit just reported was the one a switch waited for.
+    pub fn take_switch(&mut self) -> bool {
+        std::mem::take(&mut self.switch_pending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn scenarios_are_listed_comma_separated() {
+        let pane = ClientPane::from_list(" smoke.json, ,burst.json,").unwrap();
+        assert_eq!(
+            pane.scenarios,
+            [PathBuf::from("smoke.json"), PathBuf::from("burst.json")]
+        );
+        assert_eq!(pane.scenario(), "smoke.json");
+    }
+
+    #[test]
+    fn no_scenarios_means_no_pane() {
+        assert!(ClientPane::from_list("").is_none());
+        assert!(ClientPane::from_list(" , ").is_none());
+    }
+
+    #[test]
+    fn the_client_runs_the_current_scenario_against_the_balancer() {
+        let pane = ClientPane::from_list("smoke.json").unwrap();
+        let ProcessSpec::Local { name, args, env } =
+            pane.spec_with("http://127.0.0.1:3000", Vec::new(), Vec::new())
+        else {
+            panic!("the client runs locally");
+        };
+        assert_eq!(name, CLIENT);
+        assert_eq!(args, strings(&["run", "--scenario", "smoke.json"]));
+        assert_eq!(
+            env,
+            [("LB_URL".to_string(), "http://127.0.0.1:3000".to_string())]
+        );
+    }
+
+    #[test]
+    fn extra_args_go_before_the_subcommand() {
+        let pane = ClientPane::from_list("smoke.json").unwrap();
+        let ProcessSpec::Local { args, env, .. } = pane.spec_with(
+            "http://lb:3000",
+            strings(&["--timeout", "5"]),
+            vec![("RUST_LOG".to_string(), "debug".to_string())],
+        ) else {
+            panic!("the client runs locally");
+        };
+        assert_eq!(
+            args,
+            strings(&["--timeout", "5", "run", "--scenario", "smoke.json"])
+        );
+        assert_eq!(
+            env,
+            [
+                ("LB_URL".to_string(), "http://lb:3000".to_string()),
+                ("RUST_LOG".to_string(), "debug".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn next_wraps_around_the_scenarios() {
+        let mut pane = ClientPane::from_list("a.json,b.json,c.json").unwrap();
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            pane.next(false);
+            seen.push(pane.scenario());
+        }
+        assert_eq!(seen, ["b.json", "c.json", "a.json", "b.json"]);
+    }
+
+    #[test]
+    fn switching_while_running_restarts_on_the_next_exit_only() {
+        let mut pane = ClientPane::from_list("a.json,b.json").unwrap();
+        pane.next(true);
+        let ProcessSpec::Local { args, .. } = pane.spec_with("http://lb", Vec::new(), Vec::new())
+        else {
+            panic!("the client runs locally");
+        };
+        assert_eq!(args.last().unwrap(), "b.json");
+        assert!(pane.take_switch());
+        // Later exits are the supervisor's to restart.
+        assert!(!pane.take_switch());
+    }
+
+    #[test]
+    fn switching_an_exited_client_waits_for_no_exit() {
+        let mut pane = ClientPane::from_list("a.json,b.json").unwrap();
+        pane.next(false);
+        assert!(!pane.take_switch());
+        // A switch while running, then one after it exited, leaves nothing pending.
+        pane.next(true);
+        pane.next(false);
+        assert!(!pane.take_switch());
+        assert_eq!(pane.scenario(), "b.json");
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::Value;
+use tokio::process::Command as AsyncCommand;
+
+// Services labelled with it get a pane, all of them when none is.
+const PANE_LABEL: &str = "dashboard.pane";
+
+// How Docker Compose is invoked, current installs only ship the plugin.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compose {
+    // `docker compose`
+    Plugin,
+    // The legacy `docker-compose` binary.
+    Standalone,
+}
+
+impl Compose {
+    // Prefers the plugin, None when neither answers `version`.
+    pub async fn detect() -> Option<Self> {
+        for compose in [Compose::Plugin, Compose::Standalone] {
+            let status = compose
+                .command()
+                .arg("version")
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .status()
+                .await;
+            if status.is_ok_and(|status| status.success()) {
+                return Some(compose);
+            }
+        }
+        None
+    }
+
+    pub fn command(self) -> AsyncCommand {
+        let (program, args) = self.program();
+        let mut cmd = AsyncCommand::new(program);
+        cmd.args(args);
+        cmd
+    }
+
+    // For when there is no runtime left to await on, e.g. while dropping.
+    pub fn blocking_command(self) -> std::process::Command {
+        let (program, args) = self.program();
+        let mut cmd = std::process::Command::new(program);
+        cmd.args(args);
+        cmd
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Compose::Plugin => "docker compose",
+            Compose::Standalone => "docker-compose",
+        }
+    }
+
+    // The services to show, the balancer first, resolved by compose itself so profiles,
+    // overrides and `container_name` apply. The legacy binary can't output JSON.
+    pub async fn services(self) -> Result<Vec<String>, String> {
+        if self == Compose::Standalone {
+            return Err(format!(
+                "{} can't list its services as JSON, install the compose plugin",
+                self.name()
+            ));
+        }
+        let config = self.json(&["config", "--format", "json"]).await?;
+        let config = serde_json::from_str::<Value>(&config)
+            .map_err(|e| format!("{} config returned invalid JSON: {}", self.name(), e))?;
+        Ok(pane_services(&config))
+    }
+
+    // Container name of every running service, they differ from the service names
+    // unless `container_name` is set.
+    pub async fn containers(self) -> Result<HashMap<String, String>, String> {
+        if self == Compose::Standalone {
+            return Ok(HashMap::new());
+        }
+        Ok(container_names(
+            &self.json(&["ps", "--format", "json"]).await?,
+        ))
+    }
+
+    async fn json(self, args: &[&str]) -> Result<String, String> {
+        let output = self
+            .command()
+            .args(args)
+            .output()
+            .await
+            .map_err(|e| format!("Failed to run {}: {}", self.name(), e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "{} {} failed: {}",
+                self.name(),
+                args[0],
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn program(self) -> (&'static str, &'static [&'static str]) {
+        match self {
+            Compose::Plugin => ("docker", &["compose"]),
+            Compose::Standalone => ("docker-compose", &[]),
+        }
+    }
+}
+
+// For `docker logs --since`, e.g. `2024-12-01T10:00:00Z`.
+pub fn since(start: DateTime<Utc>) -> String {
+    start.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn pane_services(config: &Value) -> Vec<String> {
+    let Some(services) = config.get("services").and_then(Value::as_object) else {
+        return Vec::new();
+    };
+    let label = |service: &Value| {
+        service
+            .get("labels")
+            .and_then(|labels| labels.get(PANE_LABEL))
+            .and_then(Value::as_str)
+            .map(|value| value == "true")
+    };
+    let labelled = services.values().any(|service| label(service).is_some());
+    let mut names = services
+        .iter()
+        .filter(|(_, service)| !labelled || label(service) == Some(true))
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    names.sort_by_key(|name| pane_order(name));
+    names
+}
+
+// The balancer's pane comes first, the rest by name with numbers compared as such,
+// e.g. worker-server2 before worker-server10.
+fn pane_order(name: &str) -> (bool, String, u64) {
+    let prefix = name.trim_end_matches(|c: char| c.is_ascii_digit());
+    let number = name[prefix.len()..].parse().unwrap_or(0);
+    (!name.contains("balancer"), prefix.to_string(), number)
+}
+
+// `ps --format json` prints an array in older releases and one object per line in newer ones.
+fn container_names(output: &str) -> HashMap<String, String> {
+    let containers = match serde_json::from_str::<Value>(output.trim()) {
+        Ok(Value::Array(containers)) => containers,
+        _ => output
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .collect(),
+    };
+    containers
+        .iter()
+        .filter_map(|container| {
+            let service = container.get("Service")?.as_str()?;
+            let name = container.get("Name")?.as_str()?;
+            Some((service.to_string(), name.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    // Program and arguments of a command, as they would be run.
+    fn invocation(cmd: &std::process::Command) -> Vec<String> {
+        std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn the_plugin_runs_through_docker() {
+        let compose = Compose::Plugin;
+        assert_eq!(
+            invocation(compose.command().as_std()),
+            ["docker", "compose"]
+        );
+        assert_eq!(
+            invocation(&compose.blocking_command()),
+            ["docker", "compose"]
+        );
+        assert_eq!(compose.name(), "docker compose");
+    }
+
+    #[test]
+    fn the_standalone_binary_runs_on_its_own() {
+        let compose = Compose::Standalone;
+        assert_eq!(invocation(compose.command().as_std()), ["docker-compose"]);
+        assert_eq!(invocation(&compose.blocking_command()), ["docker-compose"]);
+        assert_eq!(compose.name(), "docker-compose");
+    }
+
+    #[test]
+    fn arguments_follow_the_subcommand() {
+        let mut cmd = Compose::Plugin.blocking_command();
+        cmd.args(["logs", "-f"]);
+        assert_eq!(invocation(&cmd), ["docker", "compose", "logs", "-f"]);
+    }
+
+    #[tokio::test]
+    async fn the_legacy_binary_cant_list_services() {
+        let err = Compose::Standalone.services().await.unwrap_err();
+        assert!(
+            err.starts_with("docker-compose can't list its services"),
+            "{err}"
+        );
+        assert!(Compose::Standalone.containers().await.unwrap().is_empty());
+    }
+
+    // What `config --format json` prints once compose resolved the file, a service in an
+    // inactive profile is already left out and `container_name` kept as is.
+    const CONFIG: &str = r#"{
+        "name": "load-balancer",
+        "services": {
+            "worker-server10": {"image": "worker", "container_name": "worker-ten"},
+            "worker-server2": {"image": "worker", "profiles": ["scale"]},
+            "load-balancer": {"image": "balancer"},
+            "worker-server1": {"image": "worker"}
+        }
+    }"#;
+
+    #[test]
+    fn every_service_gets_a_pane_balancer_first() {
+        let config = serde_json::from_str(CONFIG).unwrap();
+        assert_eq!(
+            pane_services(&config),
+            [
+                "load-balancer",
+                "worker-server1",
+                "worker-server2",
+                "worker-server10",
+            ]
+        );
+    }
+
+    #[test]
+    fn labels_pick_the_services_with_panes() {
+        let config = serde_json::json!({
+            "services": {
+                "load-balancer": {"labels": {"dashboard.pane": "true"}},
+                "worker-server1": {"labels": {"dashboard.pane": "true", "tier": "web"}},
+                "worker-server2": {"labels": {"dashboard.pane": "false"}},
+                "postgres": {"labels": {"tier": "db"}},
+                "redis": {}
+            }
+        });
+        assert_eq!(pane_services(&config), ["load-balancer", "worker-server1"]);
+    }
+
+    #[test]
+    fn a_config_without_services_has_no_panes() {
+        assert!(pane_services(&serde_json::json!({"name": "empty"})).is_empty());
+        assert!(pane_services(&serde_json::json!({"services": {}})).is_empty());
+    }
+
+    #[test]
+    fn container_names_come_from_ps_lines() {
+        let output = concat!(
+            r#"{"Name":"load-balancer-load-balancer-1","Service":"load-balancer","State":"running"}"#,
+            "\n",
+            r#"{"Name":"worker-ten","Service":"worker-server10","State":"running"}"#,
+            "\n",
+        );
+        let names = container_names(output);
+        assert_eq!(names.len(), 2);
+        assert_eq!(names["load-balancer"], "load-balancer-load-balancer-1");
+        assert_eq!(names["worker-server10"], "worker-ten");
+    }
+
+    #[test]
+    fn container_names_come_from_a_ps_array() {
+        let output = r#"[
+            {"Name": "lb_worker-server1_1", "Service": "worker-server1"},
+            {"Name": "worker-ten", "Service": "worker-server10"},
+            {"Service": "no-name"}
+        ]"#;
+        let names = container_names(output);
+        assert_eq!(names.len(), 2);
+        assert_eq!(names["worker-server1"], "lb_worker-server1_1");
+        assert_eq!(names["worker-server10"], "worker-ten");
+    }
+
+    #[test]
+    fn nothing_running_maps_no_containers() {
+        assert!(container_names("").is_empty());
+        assert!(container_names("[]\n").is_empty());
+        assert!(container_names("no such service").is_empty());
+    }
+
+    #[test]
+    fn since_is_utc_to_the_second() {
+        let start = Utc.with_ymd_and_hms(2024, 12, 1, 10, 0, 0).unwrap()
+            + chrono::Duration::milliseconds(750);
+        assert_eq!(since(start), "2024-12-01T10:00:00Z");
+    }
+
+    #[test]
+    fn since_keeps_single_digit_fields_padded() {
+        let start = Utc.with_ymd_and_hms(2025, 3, 4, 5, 6, 7).unwrap();
+        assert_eq!(since(start), "2025-03-04T05:06:07Z");
+    }
+}
@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use clap::builder::BoolishValueParser;
+use clap::{ArgAction, Parser};
+use reqwest::Url;
+
+use crate::layout::{self, Arrangement};
+
+const DEFAULT_MAX_LOG_LINES: usize = 1000;
+// Log files are rotated past this size, keeping one older file next to them.
+const DEFAULT_LOG_MAX_BYTES: u64 = 10 * 1024 * 1024;
+const DEFAULT_STARTUP_TIMEOUT_SECS: u64 = 30;
+
+// The processes are set up by APP_ENVIRONMENT, WORKER_COUNT, BACKENDS, CLIENT_SCENARIOS and
+// the `<NAME>_PATH`, `<NAME>_ARGS` and `<NAME>_ENV` of each executable.
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Dashboard running the load balancer and the workers, with a pane for each"
+)]
+pub struct Config {
+    /// Base URL of the load balancer, where APP_ENVIRONMENT publishes it by default
+    #[arg(long, env = "LB_URL")]
+    pub lb_url: Option<Url>,
+
+    /// How long the startup checklist waits for every process to be ready, in seconds
+    #[arg(long, env = "STARTUP_TIMEOUT_SECS", default_value_t = DEFAULT_STARTUP_TIMEOUT_SECS, value_parser = clap::value_parser!(u64).range(1..))]
+    pub startup_timeout_secs: u64,
+
+    /// Number of lines kept per pane for scrolling back
+    #[arg(long, env = "MAX_LOG_LINES", default_value_t = DEFAULT_MAX_LOG_LINES, value_parser = parse_positive)]
+    pub max_log_lines: usize,
+
+    /// Directory every pane's output is also written to, and where 'e' exports the merged logs
+    #[arg(long, env = "LOG_DIR")]
+    pub log_dir: Option<PathBuf>,
+
+    /// Size in bytes past which a log file is rotated
+    #[arg(long, env = "LOG_MAX_BYTES", default_value_t = DEFAULT_LOG_MAX_BYTES, value_parser = clap::value_parser!(u64).range(1..))]
+    pub log_max_bytes: u64,
+
+    /// Where the balancer's pane goes, `stacked` above the workers or `side-by-side` left of them
+    #[arg(long, env = "LAYOUT", default_value = "stacked", value_parser = parse_arrangement)]
+    pub layout: Arrangement,
+
+    /// Share of the height, or of the width side by side, given to the balancer's pane
+    #[arg(long, env = "LAYOUT_BALANCER_PERCENT", default_value_t = layout::DEFAULT_BALANCER_PERCENT, value_parser = parse_percent)]
+    pub layout_balancer_percent: usize,
+
+    /// Number of worker panes in a row before they wrap
+    #[arg(long, env = "LAYOUT_COLUMNS", default_value_t = layout::DEFAULT_COLUMNS, value_parser = parse_positive)]
+    pub layout_columns: usize,
+
+    /// Show the pane with the balancer's and the workers' stats
+    #[arg(long, env = "STATS_PANE", default_value_t = true, action = ArgAction::Set, value_parser = BoolishValueParser::new())]
+    pub stats_pane: bool,
+
+    /// In compose mode, leave the containers running on quit instead of bringing them down
+    #[arg(long, env = "KEEP_RUNNING", value_parser = BoolishValueParser::new())]
+    pub keep_running: bool,
+
+    /// Start processes again after they exit, waiting longer after every quick exit
+    #[arg(long, env = "AUTO_RESTART", value_parser = BoolishValueParser::new())]
+    pub auto_restart: bool,
+}
+
+impl Config {
+    pub fn load() -> Self {
+        lb_config::load("dashboard")
+    }
+
+    pub fn log_dir(&self) -> PathBuf {
+        self.log_dir.clone().unwrap_or_else(|| PathBuf::from("."))
+    }
+}
+
+fn parse_positive(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(value) if value > 0 => Ok(value),
+        _ => Err("expected a positive number".to_string()),
+    }
+}
+
+fn parse_percent(value: &str) -> Result<usize, String> {
+    match parse_positive(value)? {
+        percent if percent < 100 => Ok(percent),
+        _ => Err("expected less than 100".to_string()),
+    }
+}
+
+fn parse_arrangement(value: &str) -> Result<Arrangement, String> {
+    Arrangement::try_from(value).map_err(|_| "expected 'stacked' or 'side-by-side'".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_running_is_a_flag() {
+        let config = Config::try_parse_from(["dashboard", "--keep-running"]).unwrap();
+        assert!(config.keep_running);
+    }
+}
@@ -0,0 +1,296 @@
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+// Where the binary called `name` is run from, in order:
+// - `<NAME>_PATH`, e.g. WORKER_SERVER_PATH
+// - its debug then release build, next to the dashboard or in the parent directory
+// - the PATH, for binaries installed with `cargo install`
+pub fn resolve(name: &str) -> Result<PathBuf, String> {
+    resolve_from(name, &[Path::new("."), Path::new("..")], |var| {
+        env::var_os(var)
+    })
+}
+
+// `resolve` with the build roots and environment given.
+fn resolve_from(
+    name: &str,
+    roots: &[&Path],
+    var: impl Fn(&str) -> Option<OsString>,
+) -> Result<PathBuf, String> {
+    let variable = variable(name, "PATH");
+    if let Some(path) = var(&variable) {
+        let path = PathBuf::from(path);
+        return if path.is_file() {
+            Ok(path)
+        } else {
+            Err(format!(
+                "{} is set to {}, which doesn't exist",
+                variable,
+                path.display()
+            ))
+        };
+    }
+
+    let candidates = build_candidates(name, roots);
+    if let Some(path) = candidates.iter().find(|path| path.is_file()) {
+        return Ok(path.clone());
+    }
+    if let Some(path) = var("PATH")
+        .iter()
+        .flat_map(env::split_paths)
+        .map(|dir| dir.join(file_name(name)))
+        .find(|path| path.is_file())
+    {
+        return Ok(path);
+    }
+    Err(format!(
+        "Executable {} not found in {} or the PATH. Build it or set {}, then press r to retry",
+        file_name(name),
+        candidates
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        variable
+    ))
+}
+
+// `<NAME>_ARGS`, split on whitespace.
+pub fn extra_args(name: &str) -> Vec<String> {
+    env::var(variable(name, "ARGS"))
+        .map(|args| split_args(&args))
+        .unwrap_or_default()
+}
+
+// `<NAME>_ENV`, comma separated `KEY=VALUE` pairs, e.g. `RUST_LOG=debug,FOO=bar`.
+pub fn extra_env(name: &str) -> Vec<(String, String)> {
+    env::var(variable(name, "ENV"))
+        .map(|vars| split_env(&vars))
+        .unwrap_or_default()
+}
+
+fn split_args(args: &str) -> Vec<String> {
+    args.split_whitespace().map(str::to_string).collect()
+}
+
+fn split_env(vars: &str) -> Vec<(String, String)> {
+    vars.split(',')
+        .filter_map(|var| var.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+// e.g. `worker-server` and `PATH` make `WORKER_SERVER_PATH`.
+fn variable(name: &str, suffix: &str) -> String {
+    format!("{}_{}", name.to_ascii_uppercase().replace('-', "_"), suffix)
+}
+
+fn build_candidates(name: &str, roots: &[&Path]) -> Vec<PathBuf> {
+    roots
+        .iter()
+        .flat_map(|root| {
+            ["debug", "release"].map(|profile| {
+                root.join(name)
+                    .join("target")
+                    .join(profile)
+                    .join(file_name(name))
+            })
+        })
+        .collect()
+}
+
+fn file_name(name: &str) -> String {
+    #[cfg(target_os = "windows")]
+    {
+        format!("{}.exe", name)
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+
+    use super::*;
+
+    // A temp directory with an empty file at each of `files`, relative to it.
+    fn tree(files: &[&str]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for file in files {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        dir
+    }
+
+    // Resolves `worker-server` from `dir/here` and `dir/parent`, standing in for `.` and `..`,
+    // with only `vars` set.
+    fn resolve_in(dir: &Path, vars: &[(&str, OsString)]) -> Result<PathBuf, String> {
+        let vars = vars.iter().cloned().collect::<HashMap<_, _>>();
+        resolve_from(
+            "worker-server",
+            &[&dir.join("here"), &dir.join("parent")],
+            |var| vars.get(var).cloned(),
+        )
+    }
+
+    fn debug(root: &str) -> String {
+        format!(
+            "{root}/worker-server/target/debug/{}",
+            file_name("worker-server")
+        )
+    }
+
+    fn release(root: &str) -> String {
+        format!(
+            "{root}/worker-server/target/release/{}",
+            file_name("worker-server")
+        )
+    }
+
+    #[test]
+    fn variables_are_named_after_the_binary() {
+        assert_eq!(variable("worker-server", "PATH"), "WORKER_SERVER_PATH");
+        assert_eq!(variable("load-balancer", "ARGS"), "LOAD_BALANCER_ARGS");
+        assert_eq!(variable("client", "ENV"), "CLIENT_ENV");
+    }
+
+    #[test]
+    fn builds_are_looked_for_debug_first_then_release_per_root() {
+        let candidates = build_candidates("client", &[Path::new("."), Path::new("..")]);
+        let name = file_name("client");
+        assert_eq!(
+            candidates,
+            [
+                Path::new(".").join("client/target/debug").join(&name),
+                Path::new(".").join("client/target/release").join(&name),
+                Path::new("..").join("client/target/debug").join(&name),
+                Path::new("..").join("client/target/release").join(&name),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_path_variable_wins_over_builds() {
+        let dir = tree(&["custom/worker", &debug("here")]);
+        let custom = dir.path().join("custom/worker");
+        let resolved = resolve_in(dir.path(), &[("WORKER_SERVER_PATH", custom.clone().into())]);
+        assert_eq!(resolved.unwrap(), custom);
+    }
+
+    #[test]
+    fn a_path_variable_to_nowhere_is_an_error() {
+        let dir = tree(&[&debug("here")]);
+        let missing = dir.path().join("missing");
+        let err = resolve_in(
+            dir.path(),
+            &[("WORKER_SERVER_PATH", missing.clone().into())],
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            format!(
+                "WORKER_SERVER_PATH is set to {}, which doesn't exist",
+                missing.display()
+            )
+        );
+    }
+
+    #[test]
+    fn a_debug_build_wins_over_a_release_build() {
+        let dir = tree(&[&release("here"), &debug("here")]);
+        assert_eq!(
+            resolve_in(dir.path(), &[]).unwrap(),
+            dir.path().join(debug("here"))
+        );
+    }
+
+    #[test]
+    fn a_release_build_is_used_when_there_is_no_debug_build() {
+        let dir = tree(&[&release("here"), &debug("parent")]);
+        assert_eq!(
+            resolve_in(dir.path(), &[]).unwrap(),
+            dir.path().join(release("here"))
+        );
+    }
+
+    #[test]
+    fn the_parent_directory_is_looked_in_next() {
+        let dir = tree(&[&release("parent")]);
+        assert_eq!(
+            resolve_in(dir.path(), &[]).unwrap(),
+            dir.path().join(release("parent"))
+        );
+    }
+
+    #[test]
+    fn installed_binaries_are_found_on_the_path_last() {
+        let dir = tree(&[&format!("bin/{}", file_name("worker-server"))]);
+        let path = env::join_paths([dir.path().join("empty"), dir.path().join("bin")]).unwrap();
+        assert_eq!(
+            resolve_in(dir.path(), &[("PATH", path.clone())]).unwrap(),
+            dir.path().join("bin").join(file_name("worker-server"))
+        );
+
+        let dir = tree(&[
+            &format!("bin/{}", file_name("worker-server")),
+            &debug("parent"),
+        ]);
+        let path = env::join_paths([dir.path().join("bin")]).unwrap();
+        assert_eq!(
+            resolve_in(dir.path(), &[("PATH", path)]).unwrap(),
+            dir.path().join(debug("parent"))
+        );
+    }
+
+    #[test]
+    fn a_directory_is_not_an_executable() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join(debug("here"))).unwrap();
+        assert!(resolve_in(dir.path(), &[]).is_err());
+    }
+
+    #[test]
+    fn not_found_lists_where_it_looked() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = resolve_in(dir.path(), &[]).unwrap_err();
+        assert!(
+            err.starts_with(&format!(
+                "Executable {} not found in {}, ",
+                file_name("worker-server"),
+                dir.path().join(debug("here")).display()
+            )),
+            "{err}"
+        );
+        assert!(err.contains(&dir.path().join(release("parent")).display().to_string()));
+        assert!(err.ends_with("Build it or set WORKER_SERVER_PATH, then press r to retry"));
+    }
+
+    #[test]
+    fn extra_args_are_split_on_whitespace() {
+        assert_eq!(
+            split_args(" --port 3001\t--verbose "),
+            ["--port", "3001", "--verbose"]
+        );
+        assert!(split_args("").is_empty());
+    }
+
+    #[test]
+    fn extra_env_takes_key_value_pairs() {
+        assert_eq!(
+            split_env("RUST_LOG=debug, FOO = bar ,BROKEN,URL=http://x/?a=b"),
+            [
+                ("RUST_LOG".to_string(), "debug".to_string()),
+                ("FOO".to_string(), "bar".to_string()),
+                ("URL".to_string(), "http://x/?a=b".to_string()),
+            ]
+        );
+        assert!(split_env("").is_empty());
+    }
+}
@@ -0,0 +1,143 @@
+use crossterm::event::KeyCode;
+
+// Case-insensitive substring match, only ASCII letters are folded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Filter {
+    pattern: String,
+}
+
+impl Filter {
+    pub fn new(pattern: &str) -> Self {
+        Filter {
+            pattern: pattern.to_ascii_lowercase(),
+        }
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn matches(&self, line: &str) -> bool {
+        line.to_ascii_lowercase().contains(&self.pattern)
+    }
+}
+
+pub enum FilterInputState {
+    Editing,
+    // The filter to apply, None for an empty pattern which clears it.
+    Done(Option<Filter>),
+    Cancelled,
+}
+
+// The line typed after '/', Enter applies it and Esc leaves the pane's filter unchanged.
+#[derive(Default)]
+pub struct FilterInput {
+    text: String,
+}
+
+impl FilterInput {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode) -> FilterInputState {
+        match code {
+            KeyCode::Char(c) => self.text.push(c),
+            KeyCode::Backspace => {
+                self.text.pop();
+            }
+            KeyCode::Enter => {
+                let filter = (!self.text.is_empty()).then(|| Filter::new(&self.text));
+                return FilterInputState::Done(filter);
+            }
+            KeyCode::Esc => return FilterInputState::Cancelled,
+            _ => {}
+        }
+        FilterInputState::Editing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Feeds `keys` to a fresh input, '\n' is Enter and '\x08' Backspace.
+    fn typed(keys: &str) -> (FilterInput, FilterInputState) {
+        let mut input = FilterInput::default();
+        let mut state = FilterInputState::Editing;
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                '\x08' => KeyCode::Backspace,
+                c => KeyCode::Char(c),
+            };
+            state = input.handle_key(code);
+        }
+        (input, state)
+    }
+
+    #[test]
+    fn filters_match_substrings_ignoring_case() {
+        let filter = Filter::new("Worker 2");
+        assert_eq!(filter.pattern(), "worker 2");
+        assert!(filter.matches("INFO WORKER 2 is healthy"));
+        assert!(filter.matches("worker 2"));
+        assert!(!filter.matches("worker 3"));
+        assert!(!filter.matches("worker-2"));
+    }
+
+    #[test]
+    fn filtering_applies_to_every_retained_line() {
+        let lines = [
+            "INFO started",
+            "WARN slow response",
+            "ERROR connection refused",
+            "INFO Response 200",
+        ];
+        let filter = Filter::new("RESP");
+        let matches = lines
+            .iter()
+            .filter(|line| filter.matches(line))
+            .collect::<Vec<_>>();
+        assert_eq!(matches, [&"WARN slow response", &"INFO Response 200"]);
+    }
+
+    #[test]
+    fn typing_edits_until_enter() {
+        let (input, state) = typed("errx\x08or");
+        assert!(matches!(state, FilterInputState::Editing));
+        assert_eq!(input.text(), "error");
+
+        let (_, state) = typed("Error\n");
+        assert!(
+            matches!(state, FilterInputState::Done(Some(filter)) if filter == Filter::new("error"))
+        );
+
+        // Ignored keys leave the text alone.
+        let mut input = FilterInput::default();
+        input.handle_key(KeyCode::Char('a'));
+        assert!(matches!(
+            input.handle_key(KeyCode::Tab),
+            FilterInputState::Editing
+        ));
+        assert_eq!(input.text(), "a");
+    }
+
+    #[test]
+    fn an_empty_pattern_clears_and_esc_cancels() {
+        assert!(matches!(typed("\n").1, FilterInputState::Done(None)));
+        assert!(matches!(
+            typed("ab\x08\x08\n").1,
+            FilterInputState::Done(None)
+        ));
+        // Backspace on an empty line is harmless.
+        assert!(matches!(typed("\x08").1, FilterInputState::Editing));
+
+        let mut input = FilterInput::default();
+        input.handle_key(KeyCode::Char('w'));
+        assert!(matches!(
+            input.handle_key(KeyCode::Esc),
+            FilterInputState::Cancelled
+        ));
+    }
+}
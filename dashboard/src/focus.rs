@@ -0,0 +1,93 @@
+// Which pane keys apply to, and whether it takes the whole terminal.
+#[derive(Default)]
+pub struct Focus {
+    selected: usize,
+    maximized: bool,
+}
+
+impl Focus {
+    // Clamped so the focus stays valid if the number of panes shrinks.
+    pub fn selected(&self, pane_count: usize) -> usize {
+        self.selected.min(pane_count.saturating_sub(1))
+    }
+
+    pub fn next(&mut self, pane_count: usize) {
+        self.selected = (self.selected(pane_count) + 1) % pane_count.max(1);
+    }
+
+    pub fn previous(&mut self, pane_count: usize) {
+        let pane_count = pane_count.max(1);
+        self.selected = (self.selected(pane_count) + pane_count - 1) % pane_count;
+    }
+
+    pub fn select(&mut self, idx: usize) {
+        self.selected = idx;
+    }
+
+    pub fn is_maximized(&self) -> bool {
+        self.maximized
+    }
+
+    pub fn toggle_maximized(&mut self) {
+        self.maximized = !self.maximized;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn focus_cycles_through_every_pane() {
+        let mut focus = Focus::default();
+        assert_eq!(focus.selected(4), 0);
+
+        let mut visited = Vec::new();
+        for _ in 0..5 {
+            focus.next(4);
+            visited.push(focus.selected(4));
+        }
+        assert_eq!(visited, [1, 2, 3, 0, 1]);
+
+        focus.previous(4);
+        focus.previous(4);
+        assert_eq!(focus.selected(4), 3);
+    }
+
+    #[test]
+    fn focus_survives_the_pane_count_changing() {
+        let mut focus = Focus::default();
+        focus.select(5);
+        assert_eq!(focus.selected(3), 2);
+        assert_eq!(focus.selected(8), 5);
+
+        // Moving from a clamped focus starts at the pane shown as focused.
+        focus.next(3);
+        assert_eq!(focus.selected(3), 0);
+        focus.select(5);
+        focus.previous(3);
+        assert_eq!(focus.selected(3), 1);
+
+        assert_eq!(focus.selected(0), 0);
+        focus.next(0);
+        focus.previous(0);
+        assert_eq!(focus.selected(0), 0);
+    }
+
+    #[test]
+    fn maximize_toggles_and_keeps_the_focus() {
+        let mut focus = Focus::default();
+        focus.select(2);
+        assert!(!focus.is_maximized());
+
+        focus.toggle_maximized();
+        assert!(focus.is_maximized());
+        focus.next(4);
+        assert_eq!(focus.selected(4), 3);
+        assert!(focus.is_maximized());
+
+        focus.toggle_maximized();
+        assert!(!focus.is_maximized());
+        assert_eq!(focus.selected(4), 3);
+    }
+}
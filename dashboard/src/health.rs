@@ -0,0 +1,159 @@
+use std::time::{Duration, Instant};
+
+use ratatui::style::Color;
+use tokio::sync::mpsc;
+
+const PROBE_INTERVAL: Duration = Duration::from_secs(2);
+// Slower answers count as failures, the worker is as good as down for its callers.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+pub const SLOW_AFTER: Duration = Duration::from_millis(500);
+
+// A worker's health as its last probe saw it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Health {
+    #[default]
+    Unknown,
+    Healthy,
+    Slow,
+    Failing,
+}
+
+impl Health {
+    // `status` is None when no response came back in time.
+    pub fn classify(status: Option<u16>, elapsed: Duration) -> Self {
+        match status {
+            Some(200) if elapsed < SLOW_AFTER => Health::Healthy,
+            Some(200) => Health::Slow,
+            _ => Health::Failing,
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            Health::Unknown | Health::Healthy => Color::Green,
+            Health::Slow => Color::Yellow,
+            Health::Failing => Color::Red,
+        }
+    }
+
+    pub fn label(self) -> Option<&'static str> {
+        match self {
+            Health::Unknown | Health::Healthy => None,
+            Health::Slow => Some("slow"),
+            Health::Failing => Some("failing"),
+        }
+    }
+}
+
+// Probes `url` every couple of seconds until the UI is gone.
+pub async fn probe(idx: usize, url: String, tx: mpsc::UnboundedSender<(usize, Health)>) {
+    let client = reqwest::Client::new();
+    let mut interval = tokio::time::interval(PROBE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let start = Instant::now();
+        let status = client
+            .get(&url)
+            .timeout(PROBE_TIMEOUT)
+            .send()
+            .await
+            .ok()
+            .map(|response| response.status().as_u16());
+        if tx
+            .send((idx, Health::classify(status, start.elapsed())))
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    // Answers every request with `reply`.
+    async fn mock_worker(reply: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/health", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).await;
+                let _ = stream.write_all(reply.as_bytes()).await;
+            }
+        });
+        url
+    }
+
+    async fn first_probe(url: String) -> (usize, Health) {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let probing = tokio::spawn(probe(4, url, tx));
+        let probed = rx.recv().await.unwrap();
+        probing.abort();
+        probed
+    }
+
+    #[test]
+    fn statuses_are_classified_by_code_and_latency() {
+        let fast = Duration::from_millis(20);
+        assert_eq!(Health::classify(Some(200), fast), Health::Healthy);
+        assert_eq!(
+            Health::classify(Some(200), SLOW_AFTER - Duration::from_millis(1)),
+            Health::Healthy
+        );
+        assert_eq!(Health::classify(Some(200), SLOW_AFTER), Health::Slow);
+        assert_eq!(Health::classify(Some(200), PROBE_TIMEOUT), Health::Slow);
+        for status in [Some(204), Some(429), Some(500), Some(503), None] {
+            assert_eq!(
+                Health::classify(status, fast),
+                Health::Failing,
+                "{:?}",
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn only_problems_are_labelled() {
+        assert_eq!(
+            [
+                Health::Unknown,
+                Health::Healthy,
+                Health::Slow,
+                Health::Failing
+            ]
+            .map(|health| (health.color(), health.label())),
+            [
+                (Color::Green, None),
+                (Color::Green, None),
+                (Color::Yellow, Some("slow")),
+                (Color::Red, Some("failing")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn probes_report_what_the_worker_answered() {
+        let healthy = mock_worker("HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok").await;
+        assert_eq!(first_probe(healthy).await, (4, Health::Healthy));
+
+        let failing =
+            mock_worker("HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n").await;
+        assert_eq!(first_probe(failing).await, (4, Health::Failing));
+    }
+
+    #[tokio::test]
+    async fn unreachable_workers_are_failing() {
+        let address = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let url = format!("http://{}/health", address);
+        assert_eq!(first_probe(url).await, (4, Health::Failing));
+    }
+}
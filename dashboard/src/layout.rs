@@ -0,0 +1,270 @@
+use std::fmt;
+
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+
+// Beyond this the worker panes wrap to another row so they stay readable.
+pub const DEFAULT_COLUMNS: usize = 4;
+pub const DEFAULT_BALANCER_PERCENT: usize = 50;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arrangement {
+    // The balancer's row on top, the workers below.
+    Stacked,
+    // The balancer on the left, the workers on the right.
+    SideBySide,
+}
+
+impl TryFrom<&str> for Arrangement {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "stacked" => Ok(Arrangement::Stacked),
+            "side-by-side" => Ok(Arrangement::SideBySide),
+            _ => Err(format!(
+                "Invalid layout {}. Valid values are 'stacked' or 'side-by-side'",
+                value
+            )),
+        }
+    }
+}
+
+// Where the panes go, the balancer's first and then the workers' in a grid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GridLayout {
+    pub arrangement: Arrangement,
+    // Share of the height, or of the width side by side, given to the balancer.
+    pub balancer_percent: u16,
+    pub columns: usize,
+}
+
+impl GridLayout {
+    pub fn new(arrangement: Arrangement, balancer_percent: usize, columns: usize) -> Self {
+        GridLayout {
+            arrangement,
+            balancer_percent: balancer_percent.clamp(1, 99) as u16,
+            columns: columns.max(1),
+        }
+    }
+
+    // The configured layout first, then the presets that differ from it.
+    pub fn cycle(self) -> Vec<GridLayout> {
+        let presets = [
+            GridLayout::new(
+                Arrangement::Stacked,
+                DEFAULT_BALANCER_PERCENT,
+                DEFAULT_COLUMNS,
+            ),
+            GridLayout::new(Arrangement::Stacked, 25, DEFAULT_COLUMNS),
+            GridLayout::new(Arrangement::SideBySide, 40, 1),
+        ];
+        std::iter::once(self)
+            .chain(presets.into_iter().filter(|preset| *preset != self))
+            .collect()
+    }
+
+    // One area per pane, in pane order.
+    pub fn areas(&self, area: Rect, pane_count: usize) -> Vec<Rect> {
+        match pane_count {
+            0 => return Vec::new(),
+            1 => return vec![area],
+            _ => {}
+        }
+        let direction = match self.arrangement {
+            Arrangement::Stacked => Direction::Vertical,
+            Arrangement::SideBySide => Direction::Horizontal,
+        };
+        let halves = Layout::default()
+            .direction(direction)
+            .constraints([
+                Constraint::Percentage(self.balancer_percent),
+                Constraint::Percentage(100 - self.balancer_percent),
+            ])
+            .split(area);
+
+        let row_sizes = worker_rows(pane_count - 1, self.columns);
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Ratio(1, row_sizes.len() as u32);
+                row_sizes.len()
+            ])
+            .split(halves[1]);
+
+        let mut areas = vec![halves[0]];
+        for (row, &size) in rows.iter().zip(&row_sizes) {
+            let panes = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(vec![Constraint::Ratio(1, size as u32); size])
+                .split(*row);
+            areas.extend(panes.iter().copied());
+        }
+        areas
+    }
+}
+
+// e.g. `stacked 50%, 4 columns`.
+impl fmt::Display for GridLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arrangement = match self.arrangement {
+            Arrangement::Stacked => "stacked",
+            Arrangement::SideBySide => "side by side",
+        };
+        write!(
+            f,
+            "{} {}%, {} column{}",
+            arrangement,
+            self.balancer_percent,
+            self.columns,
+            if self.columns == 1 { "" } else { "s" }
+        )
+    }
+}
+
+// Number of worker panes in each row, spread as evenly as the row count allows.
+fn worker_rows(worker_count: usize, columns: usize) -> Vec<usize> {
+    let row_count = worker_count.div_ceil(columns);
+    (0..row_count)
+        .map(|row| worker_count / row_count + usize::from(row < worker_count % row_count))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: Rect = Rect::new(0, 0, 120, 40);
+
+    // Sizes of the worker rows in `areas`, told apart by their `y`.
+    fn row_sizes(areas: &[Rect]) -> Vec<usize> {
+        let mut rows: Vec<(u16, usize)> = Vec::new();
+        for area in areas {
+            match rows.last_mut() {
+                Some((y, count)) if *y == area.y => *count += 1,
+                _ => rows.push((area.y, 1)),
+            }
+        }
+        rows.into_iter().map(|(_, count)| count).collect()
+    }
+
+    #[test]
+    fn workers_wrap_to_balanced_rows() {
+        assert_eq!(worker_rows(1, 4), [1]);
+        assert_eq!(worker_rows(3, 4), [3]);
+        assert_eq!(worker_rows(5, 4), [3, 2]);
+        assert_eq!(worker_rows(8, 4), [4, 4]);
+        assert_eq!(worker_rows(9, 4), [3, 3, 3]);
+    }
+
+    #[test]
+    fn every_worker_gets_a_pane_below_the_balancer() {
+        let layout = GridLayout::new(Arrangement::Stacked, 50, DEFAULT_COLUMNS);
+        for (workers, rows) in [(1, vec![1]), (3, vec![3]), (5, vec![3, 2]), (8, vec![4, 4])] {
+            let areas = layout.areas(SCREEN, workers + 1);
+            assert_eq!(areas.len(), workers + 1);
+            assert_eq!(areas[0], Rect::new(0, 0, 120, 20), "{} workers", workers);
+            assert_eq!(row_sizes(&areas[1..]), rows, "{} workers", workers);
+
+            // The worker panes tile the lower half without gaps.
+            let covered: u32 = areas[1..]
+                .iter()
+                .map(|area| area.width as u32 * area.height as u32)
+                .sum();
+            assert_eq!(covered, 120 * 20, "{} workers", workers);
+            assert!(areas[1..].iter().all(|area| area.y >= 20));
+        }
+    }
+
+    #[test]
+    fn a_lone_pane_takes_the_whole_area() {
+        let layout = GridLayout::new(Arrangement::Stacked, 50, DEFAULT_COLUMNS);
+        assert_eq!(layout.areas(SCREEN, 1), [SCREEN]);
+        assert!(layout.areas(SCREEN, 0).is_empty());
+    }
+
+    #[test]
+    fn side_by_side_stacks_the_workers_right_of_the_balancer() {
+        let layout = GridLayout::new(Arrangement::SideBySide, 40, 1);
+        let areas = layout.areas(SCREEN, 4);
+
+        assert_eq!(areas[0], Rect::new(0, 0, 48, 40));
+        assert_eq!(
+            areas[1..],
+            [
+                Rect::new(48, 0, 72, 13),
+                Rect::new(48, 13, 72, 14),
+                Rect::new(48, 27, 72, 13),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_balancer_share_and_columns_follow_the_preset() {
+        let areas = GridLayout::new(Arrangement::Stacked, 25, 2).areas(SCREEN, 6);
+        assert_eq!(areas[0], Rect::new(0, 0, 120, 10));
+        assert_eq!(row_sizes(&areas[1..]), [2, 2, 1]);
+        assert_eq!(areas[5].width, 120);
+    }
+
+    #[test]
+    fn presets_cycle_from_the_configured_layout() {
+        let configured = GridLayout::new(Arrangement::SideBySide, 30, 2);
+        let cycle = configured.cycle();
+        assert_eq!(
+            cycle
+                .iter()
+                .map(|layout| layout.to_string())
+                .collect::<Vec<_>>(),
+            [
+                "side by side 30%, 2 columns",
+                "stacked 50%, 4 columns",
+                "stacked 25%, 4 columns",
+                "side by side 40%, 1 column",
+            ]
+        );
+
+        // A configured preset isn't repeated.
+        let default = GridLayout::new(Arrangement::Stacked, DEFAULT_BALANCER_PERCENT, 4);
+        assert_eq!(default.cycle().len(), 3);
+        assert_eq!(default.cycle()[0], default);
+    }
+
+    #[test]
+    fn settings_are_clamped_and_parsed() {
+        let layout = GridLayout::new(Arrangement::Stacked, 150, 0);
+        assert_eq!((layout.balancer_percent, layout.columns), (99, 1));
+        assert_eq!(
+            GridLayout::new(Arrangement::Stacked, 0, 3).balancer_percent,
+            1
+        );
+
+        assert_eq!(
+            Arrangement::try_from("side-by-side"),
+            Ok(Arrangement::SideBySide)
+        );
+        assert!(Arrangement::try_from("grid").is_err());
+    }
+
+    #[test]
+    fn tiny_terminals_get_an_area_per_pane_without_panicking() {
+        for layout in GridLayout::new(Arrangement::Stacked, 50, 4).cycle() {
+            for area in [
+                Rect::new(0, 0, 0, 0),
+                Rect::new(0, 0, 1, 1),
+                Rect::new(0, 0, 2, 2),
+                Rect::new(0, 0, 3, 40),
+                Rect::new(0, 0, 120, 1),
+            ] {
+                let areas = layout.areas(area, 9);
+                assert_eq!(areas.len(), 9, "{} in {:?}", layout, area);
+                assert!(
+                    areas.iter().all(|pane| area.union(*pane) == area),
+                    "{} in {:?}: {:?}",
+                    layout,
+                    area,
+                    areas
+                );
+            }
+        }
+    }
+}
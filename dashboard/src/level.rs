@@ -0,0 +1,219 @@
+use std::fmt;
+
+use ratatui::style::{Color, Style};
+
+// Levels are only looked for among the first few tokens, where tracing and most loggers put them.
+const LEVEL_TOKENS: usize = 4;
+
+// Ordered by severity, so a minimum level keeps everything at or above it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    // e.g. `2024-12-20T10:00:00Z  WARN load_balancer: ...` or `[ERROR] ...`, panics count as errors.
+    pub fn detect(line: &str) -> Option<Level> {
+        let level = line
+            .split_whitespace()
+            .take(LEVEL_TOKENS)
+            .find_map(
+                |token| match token.trim_matches(|c: char| !c.is_ascii_alphabetic()) {
+                    "ERROR" => Some(Level::Error),
+                    "WARN" | "WARNING" => Some(Level::Warn),
+                    "INFO" => Some(Level::Info),
+                    "DEBUG" => Some(Level::Debug),
+                    "TRACE" => Some(Level::Trace),
+                    _ => None,
+                },
+            );
+        level.or_else(|| line.contains("panicked at").then_some(Level::Error))
+    }
+
+    pub fn style(&self) -> Style {
+        match self {
+            Level::Error => Style::default().fg(Color::Red),
+            Level::Warn => Style::default().fg(Color::Yellow),
+            Level::Info | Level::Debug | Level::Trace => Style::default(),
+        }
+    }
+
+    // The minimum level after `min`, from showing everything (None) up to errors only.
+    pub fn next_min(min: Option<Level>) -> Option<Level> {
+        match min {
+            None => Some(Level::Info),
+            Some(Level::Trace | Level::Debug | Level::Info) => Some(Level::Warn),
+            Some(Level::Warn) => Some(Level::Error),
+            Some(Level::Error) => None,
+        }
+    }
+
+    // Lines without a level are always shown, they are often the rest of a multi-line message.
+    pub fn passes(level: Option<Level>, min: Option<Level>) -> bool {
+        match (level, min) {
+            (Some(level), Some(min)) => level >= min,
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_are_detected_among_the_first_tokens() {
+        for (line, level) in [
+            ("ERROR failed to bind", Some(Level::Error)),
+            ("[WARN] worker slow", Some(Level::Warn)),
+            ("WARNING: disk almost full", Some(Level::Warn)),
+            ("INFO listening on 3000", Some(Level::Info)),
+            ("DEBUG picked 127.0.0.1:3001", Some(Level::Debug)),
+            ("TRACE polled", Some(Level::Trace)),
+            ("listening on 3000", None),
+            ("", None),
+            // Only as a token, not inside a word.
+            ("ERRORS 0, INFOS 3", None),
+            // Too far into the line to be the level.
+            ("request to worker two returned ERROR", None),
+        ] {
+            assert_eq!(Level::detect(line), level, "{}", line);
+        }
+    }
+
+    #[test]
+    fn tracing_formats_are_recognized() {
+        for (line, level) in [
+            // Full, the default.
+            (
+                "2024-12-20T10:00:00.123456Z  INFO load_balancer: listening on 0.0.0.0:3000",
+                Some(Level::Info),
+            ),
+            (
+                "2024-12-20T10:00:00.123456Z  WARN request{id=7}: load_balancer: worker slow",
+                Some(Level::Warn),
+            ),
+            // Compact.
+            (
+                "2024-12-20T10:00:00.123456Z ERROR load_balancer: forward failed error=timeout",
+                Some(Level::Error),
+            ),
+            (
+                "2024-12-20T10:00:00.123456Z DEBUG request: load_balancer: picked id=7",
+                Some(Level::Debug),
+            ),
+            // Pretty, the level on the first line, followed by its location and spans.
+            (
+                "  2024-12-20T10:00:00.123456Z  WARN load_balancer::health: worker slow",
+                Some(Level::Warn),
+            ),
+            ("    at src/health.rs:42", None),
+            ("    in load_balancer::request with id: 7", None),
+            // Without timestamps.
+            (" TRACE worker_server: polled", Some(Level::Trace)),
+        ] {
+            assert_eq!(Level::detect(line), level, "{}", line);
+        }
+    }
+
+    #[test]
+    fn docker_prefixes_are_skipped() {
+        for (line, level) in [
+            // `docker compose logs`
+            (
+                "worker-server1  | 2024-12-20T10:00:00.123456Z  INFO worker_server: ready",
+                Some(Level::Info),
+            ),
+            // `docker logs --timestamps`
+            (
+                "2024-12-20T10:00:00.000000001Z 2024-12-20T10:00:00.123456Z ERROR worker_server: crash",
+                Some(Level::Error),
+            ),
+            ("load-balancer  | starting", None),
+        ] {
+            assert_eq!(Level::detect(line), level, "{}", line);
+        }
+    }
+
+    #[test]
+    fn the_minimum_level_cycles_through_all_info_warn_error() {
+        let mut min = None;
+        let mut seen = Vec::new();
+        for _ in 0..5 {
+            min = Level::next_min(min);
+            seen.push(min);
+        }
+        assert_eq!(
+            seen,
+            [
+                Some(Level::Info),
+                Some(Level::Warn),
+                Some(Level::Error),
+                None,
+                Some(Level::Info)
+            ]
+        );
+        // A level below INFO, e.g. set some other way, moves on to WARN.
+        assert_eq!(Level::next_min(Some(Level::Debug)), Some(Level::Warn));
+    }
+
+    #[test]
+    fn lines_at_or_above_the_minimum_pass() {
+        assert!(Level::passes(Some(Level::Trace), None));
+        assert!(Level::passes(Some(Level::Warn), Some(Level::Warn)));
+        assert!(Level::passes(Some(Level::Error), Some(Level::Warn)));
+        assert!(!Level::passes(Some(Level::Info), Some(Level::Warn)));
+        assert!(!Level::passes(Some(Level::Warn), Some(Level::Error)));
+        // Lines without a level always do.
+        assert!(Level::passes(None, Some(Level::Error)));
+    }
+
+    #[test]
+    fn levels_display_as_tracing_prints_them() {
+        assert_eq!(
+            [
+                Level::Trace,
+                Level::Debug,
+                Level::Info,
+                Level::Warn,
+                Level::Error
+            ]
+            .map(|level| level.to_string()),
+            ["TRACE", "DEBUG", "INFO", "WARN", "ERROR"]
+        );
+    }
+
+    #[test]
+    fn panics_are_errors() {
+        assert_eq!(
+            Level::detect("thread 'main' panicked at src/main.rs:10:5:"),
+            Some(Level::Error)
+        );
+    }
+
+    #[test]
+    fn warnings_and_errors_stand_out() {
+        assert_eq!(Level::Error.style().fg, Some(Color::Red));
+        assert_eq!(Level::Warn.style().fg, Some(Color::Yellow));
+        for level in [Level::Info, Level::Debug, Level::Trace] {
+            assert_eq!(level.style(), Style::default());
+        }
+    }
+}
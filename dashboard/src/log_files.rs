@@ -0,0 +1,348 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+
+use chrono::Local;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::logs::LogLine;
+
+// Appends every pane's lines to its own file from a writer thread, so the UI loop never waits on disk.
+pub struct LogFiles {
+    tx: mpsc::Sender<(usize, String)>,
+    writer: JoinHandle<()>,
+}
+
+impl LogFiles {
+    // Problems with a file are reported once as a line in its pane, `panes` is where they go.
+    pub fn open(
+        dir: PathBuf,
+        pane_names: &[String],
+        max_bytes: u64,
+        panes: UnboundedSender<(usize, String)>,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let paths = pane_names
+            .iter()
+            .map(|name| dir.join(file_name(name)))
+            .collect::<Vec<_>>();
+        let writer = std::thread::spawn(move || {
+            let mut files = paths
+                .into_iter()
+                .map(|path| RotatingFile::new(path, max_bytes))
+                .collect::<Vec<_>>();
+            write_lines(&mut files, rx, &panes);
+        });
+        LogFiles { tx, writer }
+    }
+
+    pub fn write(&self, idx: usize, line: String) {
+        let _ = self.tx.send((idx, line));
+    }
+
+    // Flushes everything written so far and closes the files.
+    pub fn close(self) {
+        drop(self.tx);
+        let _ = self.writer.join();
+    }
+}
+
+// Writes the merged lines to a new file in `dir`, each after the tag of its pane,
+// e.g. `[w1] 2024-12-20 10:00:00.123 Listening on 3001`. Returns the file's path.
+pub fn export(dir: &Path, lines: &[(usize, &LogLine)], tags: &[String]) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!(
+        "dashboard-{}.log",
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    let mut file = BufWriter::new(File::create(&path)?);
+    for (idx, line) in lines {
+        writeln!(file, "[{}] {}", tags[*idx], line.file_line())?;
+    }
+    file.flush()?;
+    Ok(path)
+}
+
+// e.g. `Worker 1` is written to `worker-1.log`.
+fn file_name(pane_name: &str) -> String {
+    format!("{}.log", pane_name.to_ascii_lowercase().replace(' ', "-"))
+}
+
+fn write_lines(
+    files: &mut [RotatingFile],
+    rx: mpsc::Receiver<(usize, String)>,
+    panes: &UnboundedSender<(usize, String)>,
+) {
+    while let Ok(first) = rx.recv() {
+        for (idx, line) in std::iter::once(first).chain(rx.try_iter()) {
+            files[idx].write_line(&line, idx, panes);
+        }
+        for (idx, file) in files.iter_mut().enumerate() {
+            file.flush(idx, panes);
+        }
+    }
+}
+
+// Once `max_bytes` would be exceeded the file is renamed to `<name>.1`, replacing the
+// previous one, and a new file is started.
+struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    out: Option<BufWriter<File>>,
+    size: u64,
+    // Set after the first error, the file isn't written to anymore.
+    failed: bool,
+}
+
+impl RotatingFile {
+    fn new(path: PathBuf, max_bytes: u64) -> Self {
+        RotatingFile {
+            path,
+            max_bytes,
+            out: None,
+            size: 0,
+            failed: false,
+        }
+    }
+
+    fn write_line(&mut self, line: &str, idx: usize, panes: &UnboundedSender<(usize, String)>) {
+        if self.failed {
+            return;
+        }
+        let result = self.try_write_line(line);
+        self.report(result, idx, panes);
+    }
+
+    fn flush(&mut self, idx: usize, panes: &UnboundedSender<(usize, String)>) {
+        if let Some(out) = &mut self.out {
+            let result = out.flush();
+            self.report(result, idx, panes);
+        }
+    }
+
+    fn report(
+        &mut self,
+        result: io::Result<()>,
+        idx: usize,
+        panes: &UnboundedSender<(usize, String)>,
+    ) {
+        if let Err(e) = result {
+            self.failed = true;
+            self.out = None;
+            let _ = panes.send((
+                idx,
+                format!("WARN Not writing {} anymore: {}", self.path.display(), e),
+            ));
+        }
+    }
+
+    fn try_write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.out.is_some() && self.size > 0 && self.size + len > self.max_bytes {
+            self.rotate()?;
+        }
+        let out = match &mut self.out {
+            Some(out) => out,
+            None => {
+                let (file, size) = open_append(&self.path)?;
+                self.size = size;
+                self.out.insert(BufWriter::new(file))
+            }
+        };
+        writeln!(out, "{}", line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if let Some(mut out) = self.out.take() {
+            out.flush()?;
+        }
+        fs::rename(&self.path, rotated_path(&self.path))?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<(File, u64)> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+    Ok((file, size))
+}
+
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use super::*;
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn panes_are_written_to_files_named_after_them() {
+        assert_eq!(file_name("Load Balancer"), "load-balancer.log");
+        assert_eq!(file_name("Worker 1"), "worker-1.log");
+    }
+
+    #[test]
+    fn files_rotate_once_full() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("worker-1.log");
+        let (panes, _rx) = unbounded_channel();
+        let mut file = RotatingFile::new(path.clone(), 20);
+
+        // 9 bytes with the newline, two fit in 20.
+        for line in ["line 0001", "line 0002", "line 0003"] {
+            file.write_line(line, 1, &panes);
+        }
+        file.flush(1, &panes);
+        assert_eq!(read(&rotated_path(&path)), "line 0001\nline 0002\n");
+        assert_eq!(read(&path), "line 0003\n");
+
+        // A second rotation replaces the first rotated file.
+        for line in ["line 0004", "line 0005"] {
+            file.write_line(line, 1, &panes);
+        }
+        file.flush(1, &panes);
+        assert_eq!(read(&rotated_path(&path)), "line 0003\nline 0004\n");
+        assert_eq!(read(&path), "line 0005\n");
+    }
+
+    #[test]
+    fn existing_files_are_appended_to_and_count_towards_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lb.log");
+        fs::write(&path, "earlier 1\n").unwrap();
+        let (panes, _rx) = unbounded_channel();
+        let mut file = RotatingFile::new(path.clone(), 20);
+
+        file.write_line("later 01", 0, &panes);
+        file.write_line("later 02", 0, &panes);
+        file.flush(0, &panes);
+        assert_eq!(read(&rotated_path(&path)), "earlier 1\nlater 01\n");
+        assert_eq!(read(&path), "later 02\n");
+    }
+
+    #[test]
+    fn lines_are_teed_with_their_date_and_flushed_on_close() {
+        let dir = tempfile::tempdir().unwrap();
+        let names = ["Load Balancer".to_string(), "Worker 1".to_string()];
+        let (panes, _rx) = unbounded_channel();
+        let mut logs = crate::logs::Logs::new(2, 10);
+        logs.set_files(LogFiles::open(
+            dir.path().to_path_buf(),
+            &names,
+            1 << 20,
+            panes,
+        ));
+
+        logs.push(1, "\x1b[32mINFO\x1b[0m listening".to_string());
+        logs.push(0, "started".to_string());
+        logs.close_files();
+
+        let expected = |idx: usize| format!("{}\n", logs.lines(idx).next().unwrap().file_line());
+        let worker = read(&dir.path().join("worker-1.log"));
+        assert_eq!(worker, expected(1));
+        // The date and time, then the text without escape codes.
+        assert_eq!(
+            worker.len(),
+            "2024-12-20 10:00:00.123 ".len() + "INFO listening\n".len()
+        );
+        assert!(worker.ends_with(" INFO listening\n"), "{}", worker);
+        assert_eq!(read(&dir.path().join("load-balancer.log")), expected(0));
+    }
+
+    #[test]
+    fn unwritable_files_warn_in_their_pane_once() {
+        let dir = tempfile::tempdir().unwrap();
+        // A file where the directory should be.
+        let blocked = dir.path().join("blocked");
+        fs::write(&blocked, "").unwrap();
+        let (panes, mut rx) = unbounded_channel();
+        let files = LogFiles::open(blocked.clone(), &["Worker 2".to_string()], 1 << 20, panes);
+
+        files.write(0, "one".to_string());
+        files.write(0, "two".to_string());
+        files.close();
+
+        let (idx, warning) = rx.try_recv().unwrap();
+        assert_eq!(idx, 0);
+        assert!(
+            warning.starts_with(&format!(
+                "WARN Not writing {} anymore: ",
+                blocked.join("worker-2.log").display()
+            )),
+            "{}",
+            warning
+        );
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn exports_tag_every_line_with_its_pane() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut logs = crate::logs::Logs::new(3, 10);
+        for (idx, line) in [(0, "lb up"), (2, "w2 up"), (1, "w1 up"), (0, "forwarded")] {
+            logs.push(idx, line.to_string());
+        }
+        let tags = ["lb".to_string(), "w1".to_string(), "w2".to_string()];
+
+        let path = export(dir.path(), &logs.merged(), &tags).unwrap();
+        let exported = read(&path);
+        let lines = exported.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        for (line, (tag, text)) in lines.iter().zip([
+            ("[lb]", "lb up"),
+            ("[w2]", "w2 up"),
+            ("[w1]", "w1 up"),
+            ("[lb]", "forwarded"),
+        ]) {
+            // e.g. `[lb] 2024-12-20 10:00:00.123 lb up`
+            let (prefix, rest) = line.split_once(' ').unwrap();
+            assert_eq!(prefix, tag);
+            let (date, rest) = rest.split_at(23);
+            assert!(
+                chrono::NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S%.3f").is_ok(),
+                "{}",
+                line
+            );
+            assert_eq!(rest, format!(" {}", text));
+        }
+    }
+
+    #[test]
+    fn exports_go_to_a_new_dated_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("exports/today");
+        let path = export(&nested, &[], &[]).unwrap();
+        assert_eq!(path.parent(), Some(nested.as_path()));
+        let name = path.file_name().unwrap().to_str().unwrap();
+        let stamp = name
+            .strip_prefix("dashboard-")
+            .and_then(|name| name.strip_suffix(".log"))
+            .unwrap();
+        assert!(chrono::NaiveDateTime::parse_from_str(stamp, "%Y%m%d-%H%M%S").is_ok());
+        assert_eq!(read(&path), "");
+    }
+
+    #[test]
+    fn exporting_to_an_unwritable_dir_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("not-a-dir");
+        fs::write(&file, "").unwrap();
+        assert!(export(&file, &[], &[]).is_err());
+    }
+}
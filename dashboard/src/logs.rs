@@ -0,0 +1,484 @@
+use std::collections::VecDeque;
+
+use ansi_to_tui::IntoText;
+use chrono::{DateTime, Local};
+use ratatui::{style::Style, text::Line};
+use tokio::sync::mpsc;
+
+use crate::level::Level;
+use crate::log_files::LogFiles;
+
+#[derive(Clone)]
+pub struct LogLine {
+    // As the process wrote it without escape codes, for filtering and files.
+    pub text: String,
+    // Styled by the escape codes, over the color of its level.
+    pub line: Line<'static>,
+    pub level: Option<Level>,
+    // Taken when the main loop received the line, so every pane shares the clock.
+    pub received: DateTime<Local>,
+    // Receive order across every pane, timestamps can tie.
+    pub seq: u64,
+}
+
+impl LogLine {
+    pub fn timestamp(&self) -> String {
+        self.received.format("%H:%M:%S%.3f ").to_string()
+    }
+
+    // What the pane shows before the line, nothing with timestamps toggled off.
+    pub fn prefix(&self, timestamps: bool) -> String {
+        if timestamps {
+            self.timestamp()
+        } else {
+            String::new()
+        }
+    }
+
+    // The whole date in files, they outlive the session.
+    pub fn file_line(&self) -> String {
+        format!(
+            "{} {}",
+            self.received.format("%Y-%m-%d %H:%M:%S%.3f"),
+            self.text
+        )
+    }
+}
+
+// The last `max_lines` lines of every pane, the load balancer's first, styled by their level.
+pub struct Logs {
+    panes: Vec<VecDeque<LogLine>>,
+    max_lines: usize,
+    // Messages still queued after the last drain hit its cap.
+    behind: usize,
+    // Lines received since the pane was paused, None while it's live.
+    paused: Vec<Option<usize>>,
+    // Where every received line is also written, when LOG_DIR is set.
+    files: Option<LogFiles>,
+    received: u64,
+}
+
+impl Logs {
+    pub fn new(pane_count: usize, max_lines: usize) -> Self {
+        Logs {
+            panes: vec![VecDeque::new(); pane_count],
+            max_lines,
+            behind: 0,
+            paused: vec![None; pane_count],
+            files: None,
+            received: 0,
+        }
+    }
+
+    pub fn set_files(&mut self, files: LogFiles) {
+        self.files = Some(files);
+    }
+
+    // Waits until the lines written so far are on disk.
+    pub fn close_files(&mut self) {
+        if let Some(files) = self.files.take() {
+            files.close();
+        }
+    }
+
+    pub fn push(&mut self, idx: usize, line: String) {
+        let pane = &mut self.panes[idx];
+        if pane.len() == self.max_lines {
+            pane.pop_front();
+        }
+        let (text, styled) = parse_ansi(line);
+        let level = Level::detect(&text);
+        let line = LogLine {
+            line: styled.style(level.map_or(Style::default(), |level| level.style())),
+            level,
+            text,
+            received: Local::now(),
+            seq: self.received,
+        };
+        self.received += 1;
+        if let Some(files) = &self.files {
+            files.write(idx, line.file_line());
+        }
+        pane.push_back(line);
+        if let Some(new) = &mut self.paused[idx] {
+            // Can't count more than the pane keeps, the oldest new lines are trimmed like any other.
+            *new = (*new + 1).min(self.max_lines);
+        }
+    }
+
+    pub fn toggle_paused(&mut self, idx: usize) {
+        self.paused[idx] = match self.paused[idx] {
+            Some(_) => None,
+            None => Some(0),
+        };
+    }
+
+    // Pauses every pane, or resumes them all when they already are.
+    pub fn toggle_paused_all(&mut self) {
+        let pause = self.paused.iter().any(Option::is_none);
+        for paused in self.paused.iter_mut() {
+            *paused = match (pause, *paused) {
+                (true, Some(new)) => Some(new),
+                (true, None) => Some(0),
+                (false, _) => None,
+            };
+        }
+    }
+
+    pub fn is_paused(&self, idx: usize) -> bool {
+        self.paused[idx].is_some()
+    }
+
+    // Lines received while paused that aren't shown yet.
+    pub fn new_while_paused(&self, idx: usize) -> usize {
+        self.paused[idx].unwrap_or(0)
+    }
+
+    // Applies queued messages, at most `cap` so a flood can't stall the UI for long.
+    pub fn drain(&mut self, rx: &mut mpsc::UnboundedReceiver<(usize, String)>, cap: usize) {
+        for _ in 0..cap {
+            match rx.try_recv() {
+                Ok((idx, line)) => self.push(idx, line),
+                Err(_) => break,
+            }
+        }
+        self.behind = rx.len();
+    }
+
+    pub fn behind(&self) -> usize {
+        self.behind
+    }
+
+    pub fn pane_count(&self) -> usize {
+        self.panes.len()
+    }
+
+    // The lines to show, without those received while paused.
+    pub fn lines(&self, idx: usize) -> impl Iterator<Item = &LogLine> {
+        let pane = &self.panes[idx];
+        pane.iter()
+            .take(pane.len() - self.new_while_paused(idx).min(pane.len()))
+    }
+
+    // Every retained line of every pane in the order they were received, paused ones included.
+    pub fn merged(&self) -> Vec<(usize, &LogLine)> {
+        let mut lines = self
+            .panes
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, pane)| pane.iter().map(move |line| (idx, line)))
+            .collect::<Vec<_>>();
+        lines.sort_by_key(|(_, line)| line.seq);
+        lines
+    }
+
+    pub fn clear(&mut self) {
+        for pane in self.panes.iter_mut() {
+            pane.clear();
+        }
+        for new in self.paused.iter_mut().flatten() {
+            *new = 0;
+        }
+    }
+}
+
+// Parsed once on receipt. Malformed escape codes shouldn't take the dashboard down,
+// such a line is shown as it came.
+fn parse_ansi(line: String) -> (String, Line<'static>) {
+    match line.into_text() {
+        Ok(text) => {
+            let styled = Line::from(
+                text.lines
+                    .into_iter()
+                    .flat_map(|line| line.spans)
+                    .collect::<Vec<_>>(),
+            );
+            (styled.to_string(), styled)
+        }
+        Err(_) => (line.clone(), Line::raw(line)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(logs: &Logs, idx: usize) -> Vec<String> {
+        logs.lines(idx).map(|line| line.text.clone()).collect()
+    }
+
+    #[test]
+    fn a_frame_applies_every_queued_message() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for i in 0..1000 {
+            tx.send((i % 3, format!("line {}", i))).unwrap();
+        }
+        let mut logs = Logs::new(3, 1000);
+
+        logs.drain(&mut rx, 1000);
+
+        assert_eq!(logs.behind(), 0);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(
+            (0..3).map(|idx| logs.lines(idx).count()).sum::<usize>(),
+            1000
+        );
+        assert_eq!(texts(&logs, 1)[..2], ["line 1", "line 4"]);
+    }
+
+    #[test]
+    fn a_capped_drain_reports_how_far_behind_it_is() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for i in 0..250 {
+            tx.send((0, format!("line {}", i))).unwrap();
+        }
+        let mut logs = Logs::new(1, 1000);
+
+        logs.drain(&mut rx, 100);
+        assert_eq!((logs.lines(0).count(), logs.behind()), (100, 150));
+
+        logs.drain(&mut rx, 100);
+        logs.drain(&mut rx, 100);
+        assert_eq!((logs.lines(0).count(), logs.behind()), (250, 0));
+    }
+
+    #[test]
+    fn panes_keep_their_last_lines() {
+        let mut logs = Logs::new(2, 3);
+        for i in 0..5 {
+            logs.push(1, format!("line {}", i));
+        }
+        logs.push(0, "balancer".to_string());
+
+        assert_eq!(texts(&logs, 1), ["line 2", "line 3", "line 4"]);
+        assert_eq!(texts(&logs, 0), ["balancer"]);
+
+        logs.clear();
+        assert_eq!(logs.lines(1).count(), 0);
+    }
+
+    fn pushed(logs: &mut Logs, idx: usize, range: std::ops::Range<usize>) {
+        for i in range {
+            logs.push(idx, format!("line {}", i));
+        }
+    }
+
+    #[test]
+    fn paused_panes_buffer_new_lines_out_of_view() {
+        let mut logs = Logs::new(2, 100);
+        pushed(&mut logs, 1, 0..3);
+
+        logs.toggle_paused(1);
+        assert!(logs.is_paused(1) && !logs.is_paused(0));
+        pushed(&mut logs, 1, 3..8);
+        pushed(&mut logs, 0, 0..2);
+
+        assert_eq!(logs.new_while_paused(1), 5);
+        assert_eq!(texts(&logs, 1), ["line 0", "line 1", "line 2"]);
+        assert_eq!(logs.lines(0).count(), 2);
+
+        logs.toggle_paused(1);
+        assert_eq!(logs.new_while_paused(1), 0);
+        assert_eq!(logs.lines(1).count(), 8);
+        assert_eq!(texts(&logs, 1).last().unwrap(), "line 7");
+    }
+
+    #[test]
+    fn paused_buffering_keeps_to_the_retention_cap() {
+        let mut logs = Logs::new(1, 10);
+        pushed(&mut logs, 0, 0..4);
+        logs.toggle_paused(0);
+
+        pushed(&mut logs, 0, 4..12);
+        // Two of the old lines were trimmed to make room.
+        assert_eq!(logs.new_while_paused(0), 8);
+        assert_eq!(texts(&logs, 0), ["line 2", "line 3"]);
+
+        // More new lines than the pane keeps, only the most recent remain.
+        pushed(&mut logs, 0, 12..30);
+        assert_eq!(logs.new_while_paused(0), 10);
+        assert_eq!(logs.lines(0).count(), 0);
+
+        logs.toggle_paused(0);
+        assert_eq!(texts(&logs, 0).first().unwrap(), "line 20");
+        assert_eq!(texts(&logs, 0).last().unwrap(), "line 29");
+    }
+
+    #[test]
+    fn pausing_all_pauses_every_pane_then_resumes_them() {
+        let mut logs = Logs::new(3, 100);
+        logs.toggle_paused(1);
+        pushed(&mut logs, 1, 0..2);
+
+        logs.toggle_paused_all();
+        assert!((0..3).all(|idx| logs.is_paused(idx)));
+        // A pane paused before keeps its count.
+        assert_eq!(logs.new_while_paused(1), 2);
+        pushed(&mut logs, 2, 0..1);
+        assert_eq!(logs.new_while_paused(2), 1);
+
+        logs.toggle_paused_all();
+        assert!((0..3).all(|idx| !logs.is_paused(idx)));
+        assert_eq!(logs.lines(2).count(), 1);
+    }
+
+    #[test]
+    fn clearing_a_paused_pane_resets_its_count() {
+        let mut logs = Logs::new(1, 100);
+        logs.toggle_paused(0);
+        pushed(&mut logs, 0, 0..3);
+
+        logs.clear();
+        assert!(logs.is_paused(0));
+        assert_eq!(logs.new_while_paused(0), 0);
+        pushed(&mut logs, 0, 3..4);
+        assert_eq!(logs.new_while_paused(0), 1);
+    }
+
+    #[test]
+    fn lines_are_styled_by_their_level() {
+        let mut logs = Logs::new(1, 10);
+        logs.push(
+            0,
+            "2024-12-20T10:00:00Z  WARN load_balancer: slow".to_string(),
+        );
+        logs.push(0, "\x1b[31mERROR\x1b[0m bind failed".to_string());
+        logs.push(0, "listening".to_string());
+
+        let lines = logs.lines(0).collect::<Vec<_>>();
+        assert_eq!(
+            lines.iter().map(|line| line.level).collect::<Vec<_>>(),
+            [Some(Level::Warn), Some(Level::Error), None]
+        );
+        assert_eq!(lines[0].line.style, Level::Warn.style());
+        assert_eq!(lines[1].text, "ERROR bind failed");
+        assert_eq!(lines[1].line.style, Level::Error.style());
+        assert_eq!(lines[2].line.style, Style::default());
+    }
+
+    fn received_at(text: &str, time: &str) -> LogLine {
+        let received = DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .with_timezone(&Local);
+        LogLine {
+            text: text.to_string(),
+            line: Line::raw(text.to_string()),
+            level: None,
+            received,
+            seq: 0,
+        }
+    }
+
+    #[test]
+    fn timestamps_are_local_time_to_the_millisecond() {
+        let line = received_at("listening", "2024-05-01T14:03:12.045+00:00");
+        let local = line.received.format("%H:%M:%S").to_string();
+
+        assert_eq!(line.timestamp(), format!("{}.045 ", local));
+        assert_eq!(line.prefix(true), line.timestamp());
+        assert_eq!(line.prefix(false), "");
+        assert_eq!(
+            line.file_line(),
+            format!(
+                "{} {}.045 listening",
+                line.received.format("%Y-%m-%d"),
+                local
+            )
+        );
+    }
+
+    #[test]
+    fn lines_are_stamped_when_received() {
+        let before = Local::now();
+        let mut logs = Logs::new(2, 10);
+        logs.push(1, "first".to_string());
+        logs.push(0, "second".to_string());
+        let after = Local::now();
+
+        let first = logs.lines(1).next().unwrap();
+        let second = logs.lines(0).next().unwrap();
+        assert!(before <= first.received && first.received <= second.received);
+        assert!(second.received <= after);
+        assert_eq!((first.seq, second.seq), (0, 1));
+        // Every pane shares the same prefix width.
+        assert_eq!(first.prefix(true).len(), "14:03:12.045 ".len());
+    }
+
+    // Where a merged line came from and what it said.
+    fn merged(logs: &Logs) -> Vec<(usize, String)> {
+        logs.merged()
+            .into_iter()
+            .map(|(idx, line)| (idx, line.text.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn panes_are_merged_in_receive_order() {
+        let mut logs = Logs::new(3, 10);
+        for (idx, line) in [
+            (1, "w1 listening"),
+            (0, "lb listening"),
+            (2, "w2 listening"),
+            (0, "lb forwarded to w2"),
+            (2, "w2 handled"),
+            (1, "w1 idle"),
+        ] {
+            logs.push(idx, line.to_string());
+        }
+        assert_eq!(
+            merged(&logs),
+            [
+                (1, "w1 listening".to_string()),
+                (0, "lb listening".to_string()),
+                (2, "w2 listening".to_string()),
+                (0, "lb forwarded to w2".to_string()),
+                (2, "w2 handled".to_string()),
+                (1, "w1 idle".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn receive_order_wins_over_timestamps() {
+        let mut logs = Logs::new(2, 10);
+        for (idx, line) in [(0, "first"), (1, "second"), (0, "third")] {
+            logs.push(idx, line.to_string());
+        }
+        // The same millisecond for all of them, and one clock going backwards.
+        let at = logs.panes[0][0].received;
+        logs.panes[0][1].received = at;
+        logs.panes[1][0].received = at - chrono::Duration::seconds(1);
+        let order = merged(&logs)
+            .into_iter()
+            .map(|(_, text)| text)
+            .collect::<Vec<_>>();
+        assert_eq!(order, ["first", "second", "third"]);
+    }
+
+    #[test]
+    fn merging_takes_only_what_is_retained_paused_lines_included() {
+        let mut logs = Logs::new(2, 2);
+        logs.toggle_paused(1);
+        for (idx, line) in [
+            (0, "lb 1"),
+            (1, "w1 1"),
+            (0, "lb 2"),
+            (0, "lb 3"),
+            (1, "w1 2"),
+        ] {
+            logs.push(idx, line.to_string());
+        }
+        assert!(logs.lines(1).next().is_none());
+        assert_eq!(
+            merged(&logs),
+            [
+                (1, "w1 1".to_string()),
+                (0, "lb 2".to_string()),
+                (0, "lb 3".to_string()),
+                (1, "w1 2".to_string()),
+            ]
+        );
+        logs.clear();
+        assert!(logs.merged().is_empty());
+    }
+}
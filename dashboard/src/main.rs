@@ -1,62 +1,469 @@
-use ansi_to_tui::IntoText;
-use crossterm::event::{self, Event, KeyCode};
-use environment::Environment;
+mod algorithm;
+mod client_pane;
+mod compose;
+mod config;
+mod executable;
+mod filter;
+mod focus;
+mod health;
+mod layout;
+mod level;
+mod log_files;
+mod logs;
+mod mouse;
+mod reaper;
+mod resources;
+mod startup;
+mod stats;
+mod supervisor;
+mod traffic;
+
+use algorithm::{ActiveAlgorithm, AlgorithmTitle};
+use chrono::{Local, Utc};
+use client_pane::ClientPane;
+use compose::Compose;
+use config::Config;
+use crossterm::event::{self, Event, KeyCode, MouseButton, MouseEventKind};
+use crossterm::execute;
+use environment::{Environment, EnvironmentError, Topology};
+use filter::{Filter, FilterInput, FilterInputState};
+use focus::Focus;
+use health::Health;
+use layout::GridLayout;
+use level::Level;
+use log_files::LogFiles;
+use logs::Logs;
+use mouse::Hit;
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
-    style::{Color, Style},
-    widgets::{Block, Borders, Paragraph},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Wrap},
     Terminal,
 };
+use reaper::{Reaper, SharedChild};
+use resources::{Target, Usage};
+use startup::{ReadyCheck, Startup};
+use stats::StatsPane;
 use std::{
+    collections::HashMap,
+    fmt,
     io::{self, Stdout},
-    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
-use tui_utils::{cleanup_terminal, get_end_of_wrapped_text, setup_terminal};
+use supervisor::Supervisor;
+use traffic::{TestTraffic, TrafficSender};
+use tui_utils::{cleanup_terminal, setup_terminal, Scrollback};
 
-use tokio::process::Command as AsyncCommand;
+use tokio::io::AsyncBufReadExt;
+use tokio::process::{ChildStderr, ChildStdout, Command as AsyncCommand};
 use tokio::sync::mpsc;
 use tokio::task;
-use tokio::io::AsyncBufReadExt;
 
-const MAX_LOG_LINES: usize = 100;
+// Below this the panes are replaced by a message asking for a bigger terminal.
+const MIN_WIDTH: u16 = 40;
+const MIN_HEIGHT: u16 = 10;
+// Borders and one line of text, smaller panes are left out.
+const MIN_PANE_SIZE: u16 = 3;
+// Marks lines a process wrote to stderr, where tracing and panics end up.
+const STDERR_PREFIX: &str = "[stderr] ";
+// Enough for every process being chatty, the rest waits for the next frame.
+const MAX_MESSAGES_PER_FRAME: usize = 1000;
+
+// How to start the process behind a pane again after it exited.
+#[derive(Clone, Debug)]
+enum ProcessSpec {
+    Local {
+        name: String,
+        args: Vec<String>,
+        env: Vec<(String, String)>,
+    },
+    // Restarting starts the container again and follows its new logs.
+    Container {
+        name: String,
+        // Logs from before are skipped, `docker logs` would replay the container's whole history.
+        since: String,
+    },
+}
+
+impl ProcessSpec {
+    // Only local workers are probed, compose keeps the workers' ports inside its network.
+    fn health_url(&self) -> Option<String> {
+        match self {
+            ProcessSpec::Local { env, .. } => env
+                .iter()
+                .find(|(key, _)| key == "PORT")
+                .map(|(_, port)| format!("http://127.0.0.1:{}/health", port)),
+            ProcessSpec::Container { .. } => None,
+        }
+    }
+}
+
+type LogSender = mpsc::UnboundedSender<(usize, String)>;
+type ExitSender = mpsc::UnboundedSender<(usize, Option<i32>)>;
+type SpawnedSender = mpsc::UnboundedSender<(usize, Option<SharedChild>)>;
 
 #[tokio::main]
 async fn main() -> Result<(), io::Error> {
+    let config = Config::load();
+    lb_telemetry::init_file(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+        .map_err(io::Error::other)?;
     let (tx, mut rx) = mpsc::unbounded_channel();
+    let (exits_tx, mut exits_rx) = mpsc::unbounded_channel();
+    // Restarts run in the background, docker start can take a while, the children come back here.
+    let (spawned_tx, mut spawned_rx) = mpsc::unbounded_channel();
 
-    launch_load_balancer(tx.clone()).await;
+    let topology = topology(&tx).unwrap_or_else(|e| exit_with(e));
+    // Where the stats pane polls and test traffic goes.
+    let lb_url = lb_url(&config, &topology).unwrap_or_else(|e| exit_with(e));
+    // The load balancer first, then one per worker.
+    // Compose mode shows the services the compose file defines, whatever WORKER_COUNT says.
+    let (mut specs, compose) = launch_load_balancer(&topology, &tx).await;
+    // The client's pane comes last, after the workers'.
+    let mut client_pane = ClientPane::from_env();
+    if let Some(client) = &client_pane {
+        specs.push(client.spec(lb_url.as_str()));
+    }
+    let client_idx = client_pane.as_ref().map(|_| specs.len() - 1);
+    let pane_names = specs
+        .iter()
+        .enumerate()
+        .map(|(idx, spec)| pane_name(idx, spec))
+        .collect::<Vec<_>>();
+    let compose_down = compose.filter(|_| !config.keep_running);
+    let mut reaper = Reaper::new(specs.len(), compose_down);
+    for (idx, spec) in specs.iter().enumerate() {
+        let child = spawn_process(spec, idx, false, tx.clone(), exits_tx.clone()).await;
+        reaper.set_child(idx, child);
+    }
+    let (health_tx, mut health_rx) = mpsc::unbounded_channel();
+    for (idx, spec) in specs.iter().enumerate() {
+        if let Some(url) = spec.health_url() {
+            task::spawn(health::probe(idx, url, health_tx.clone()));
+        }
+    }
+    let mut health = vec![Health::Unknown; specs.len()];
+    // The checklist is shown instead of the panes until everything is up.
+    let (ready_tx, mut ready_rx) = mpsc::unbounded_channel();
+    for (idx, spec) in specs.iter().enumerate() {
+        match ready_check(idx, spec, &lb_url) {
+            Some(check) => {
+                task::spawn(startup::wait_ready(idx, check, ready_tx.clone()));
+            }
+            None => {
+                let _ = ready_tx.send(idx);
+            }
+        }
+    }
+    let mut startup = Some(Startup::new(
+        pane_names.clone(),
+        Instant::now(),
+        Duration::from_secs(config.startup_timeout_secs),
+    ));
+    let mut supervisor = Supervisor::new(specs.len(), config.auto_restart, Instant::now());
+    let mut focus = Focus::default();
 
     let mut terminal = setup_terminal()?;
+    execute!(io::stdout(), event::EnableMouseCapture)?;
     terminal.clear()?;
 
-    let mut logs: Vec<String> = vec![String::new(); 4];
+    let mut logs = Logs::new(specs.len(), config.max_log_lines);
+    if let Some(dir) = &config.log_dir {
+        logs.set_files(LogFiles::open(
+            dir.clone(),
+            &pane_names,
+            config.log_max_bytes,
+            tx.clone(),
+        ));
+    }
+    let mut scrollbacks = (0..specs.len())
+        .map(|_| Scrollback::default())
+        .collect::<Vec<_>>();
+    let mut filters: Vec<Option<Filter>> = vec![None; specs.len()];
+    // Lines below it are hidden, None shows every level.
+    let mut min_levels: Vec<Option<Level>> = vec![None; specs.len()];
+    // Set while typing a filter for the focused pane, it gets every key until done.
+    let mut filter_input: Option<FilterInput> = None;
+    // The pane whose process 'k' is about to kill, waiting for y/n.
+    let mut pending_kill: Option<usize> = None;
+    let mut show_timestamps = true;
+    // The configured layout and the presets 'L' cycles through.
+    let layouts = grid_layout(&config).cycle();
+    let mut layout_idx = 0;
+    let traffic = TrafficSender::new(lb_url.clone(), &topology);
+    let (stats_tx, mut stats_rx) = mpsc::unbounded_channel();
+    let mut stats = config.stats_pane.then(|| {
+        task::spawn(stats::poll_stats(
+            lb_url.to_string(),
+            std::convert::identity,
+            stats_tx,
+        ));
+        StatsPane::default()
+    });
+    let (algorithm_tx, mut algorithm_rx) = mpsc::unbounded_channel();
+    task::spawn(stats::poll_stats(
+        lb_url.to_string(),
+        ActiveAlgorithm::from_stats,
+        algorithm_tx,
+    ));
+    let mut algorithm = AlgorithmTitle::default();
+    let (usage_tx, mut usage_rx) = mpsc::unbounded_channel();
+    let mut usage = Usage::new(specs.len());
+    let mut last_sample: Option<Instant> = None;
 
     loop {
-        if let Ok((idx, log)) = rx.try_recv() {
-            logs[idx].push_str(&format!("{}\n", log));
-            let log_lines: Vec<&str> = logs[idx].lines().collect();
-            if log_lines.len() > MAX_LOG_LINES {
-                logs[idx] = log_lines[log_lines.len() - MAX_LOG_LINES..].join("\n");
+        logs.drain(&mut rx, MAX_MESSAGES_PER_FRAME);
+        while let Ok(result) = stats_rx.try_recv() {
+            if let Some(stats) = &mut stats {
+                stats.update(result);
             }
         }
 
-        if let Err(e) = draw_ui(&mut terminal, &logs) {
-            eprintln!("Error drawing UI: {}", e);
-            break;
+        // Sampled in the background since ps and docker stats can take a while to answer.
+        if last_sample.is_none_or(|at| at.elapsed() >= resources::SAMPLE_INTERVAL) {
+            last_sample = Some(Instant::now());
+            task::spawn(resources::sample(
+                sample_targets(&specs, &reaper, &supervisor),
+                usage_tx.clone(),
+            ));
+        }
+        while let Ok(result) = algorithm_rx.try_recv() {
+            if let Some(change) = algorithm.update(result, Instant::now()) {
+                logs.push(0, change);
+            }
+        }
+        while let Ok(samples) = usage_rx.try_recv() {
+            usage.update(samples, Instant::now());
+        }
+        while let Ok((idx, probed)) = health_rx.try_recv() {
+            health[idx] = probed;
+        }
+        while let Ok((idx, code)) = exits_rx.try_recv() {
+            supervisor.exited(idx, code, Instant::now(), Local::now());
+            if let Some(checklist) = &mut startup {
+                checklist.exited(idx);
+            }
+            let switched = Some(idx) == client_idx
+                && client_pane.as_mut().is_some_and(ClientPane::take_switch);
+            if switched {
+                restart_process(&specs[idx], idx, &tx, &exits_tx, &spawned_tx);
+                supervisor.restarted(idx, Instant::now());
+            }
+        }
+        for idx in supervisor.due(Instant::now()) {
+            logs.push(idx, "Restarting...".to_string());
+            restart_process(&specs[idx], idx, &tx, &exits_tx, &spawned_tx);
+            supervisor.restarted(idx, Instant::now());
+        }
+        while let Ok((idx, child)) = spawned_rx.try_recv() {
+            reaper.set_child(idx, child);
         }
 
+        if let Some(checklist) = &mut startup {
+            while let Ok(idx) = ready_rx.try_recv() {
+                checklist.ready(idx, Instant::now());
+            }
+            checklist.update(Instant::now());
+            if checklist.is_done() {
+                // Not aborting, the panes show why and the processes may still come up.
+                for idx in checklist.timed_out() {
+                    logs.push(
+                        idx,
+                        format!(
+                            "ERROR {} wasn't ready after {}s",
+                            pane_names[idx],
+                            checklist.timeout().as_secs()
+                        ),
+                    );
+                }
+                startup = None;
+            }
+        }
+
+        let now = Instant::now();
+        let footers = (0..specs.len())
+            .map(|idx| {
+                let pid = match specs[idx] {
+                    ProcessSpec::Local { .. } if !supervisor.is_exited(idx) => reaper.pid(idx),
+                    _ => None,
+                };
+                usage.footer(idx, pid, supervisor.uptime(idx, now))
+            })
+            .collect::<Vec<_>>();
+        let panes = Panes {
+            names: &pane_names,
+            logs: &logs,
+            filters: &filters,
+            min_levels: &min_levels,
+            layout: layouts[layout_idx],
+            supervisor: &supervisor,
+            health: &health,
+            focus: &focus,
+            filter_input: filter_input.as_ref(),
+            pending_kill,
+            show_timestamps,
+            stats: stats.as_ref(),
+            footers: &footers,
+            algorithm: &algorithm,
+        };
+        let drawn = match &startup {
+            Some(checklist) => draw_startup(&mut terminal, checklist).map(|_| Vec::new()),
+            None => draw_ui(&mut terminal, &panes, &mut scrollbacks),
+        };
+        // Where each pane was drawn, for finding the one under the mouse.
+        let pane_areas = match drawn {
+            Ok(areas) => areas,
+            Err(e) => {
+                eprintln!("Error drawing UI: {}", e);
+                break;
+            }
+        };
+
         if event::poll(std::time::Duration::from_millis(100))? {
-            if let Event::Key(key_event) = event::read()? {
+            let event = event::read()?;
+            // Ignored while a prompt waits for keys, clicking away shouldn't answer it.
+            if let Event::Mouse(mouse_event) = event {
+                let hit = mouse::hit(&pane_areas, mouse_event.column, mouse_event.row);
+                match (mouse_event.kind, hit) {
+                    _ if filter_input.is_some() || pending_kill.is_some() => {}
+                    (MouseEventKind::Down(MouseButton::Left), Some(hit)) => {
+                        focus.select(hit.pane());
+                        if let Hit::Title(_) = hit {
+                            focus.toggle_maximized();
+                        }
+                    }
+                    (MouseEventKind::ScrollUp, Some(hit)) => {
+                        scrollbacks[hit.pane()].scroll_up(mouse::SCROLL_LINES)
+                    }
+                    (MouseEventKind::ScrollDown, Some(hit)) => {
+                        scrollbacks[hit.pane()].scroll_down(mouse::SCROLL_LINES)
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            if let Event::Key(key_event) = event {
                 if key_event.kind == event::KeyEventKind::Release {
                     continue;
                 }
+                if startup.is_some() {
+                    match key_event.code {
+                        KeyCode::Char('q') => break,
+                        KeyCode::Enter | KeyCode::Esc => startup = None,
+                        _ => {}
+                    }
+                    continue;
+                }
+                if let Some(idx) = pending_kill.take() {
+                    if key_event.code == KeyCode::Char('y') {
+                        logs.push(idx, "Killing...".to_string());
+                        stop_process(&specs[idx], idx, reaper.pid(idx), &pane_names[idx], &tx);
+                    }
+                    continue;
+                }
+                if let Some(input) = &mut filter_input {
+                    let selected = focus.selected(specs.len());
+                    match input.handle_key(key_event.code) {
+                        FilterInputState::Editing => {}
+                        FilterInputState::Done(filter) => {
+                            filters[selected] = filter;
+                            scrollbacks[selected].end();
+                            filter_input = None;
+                        }
+                        FilterInputState::Cancelled => filter_input = None,
+                    }
+                    continue;
+                }
                 match key_event.code {
-                    KeyCode::Char('c') => {
-                        for i in 0..4 {
-                            logs[i] = String::new();
+                    KeyCode::Char('/') => filter_input = Some(FilterInput::default()),
+                    KeyCode::Esc => {
+                        let selected = focus.selected(specs.len());
+                        filters[selected] = None;
+                        scrollbacks[selected].end();
+                    }
+                    KeyCode::Char('c') => logs.clear(),
+                    KeyCode::Char('e') => {
+                        let tags = specs
+                            .iter()
+                            .enumerate()
+                            .map(|(idx, spec)| pane_tag(idx, spec))
+                            .collect::<Vec<_>>();
+                        let lines = logs.merged();
+                        let exported = log_files::export(&config.log_dir(), &lines, &tags);
+                        let message = match exported {
+                            Ok(path) => {
+                                format!("Exported {} lines to {}", lines.len(), path.display())
+                            }
+                            Err(e) => format!("ERROR Failed to export the logs: {}", e),
+                        };
+                        logs.push(0, message);
+                    }
+                    KeyCode::Char('t') => show_timestamps = !show_timestamps,
+                    KeyCode::Tab | KeyCode::Right => focus.next(specs.len()),
+                    KeyCode::BackTab | KeyCode::Left => focus.previous(specs.len()),
+                    KeyCode::Char('z') | KeyCode::Enter => focus.toggle_maximized(),
+                    KeyCode::Char('p') => {
+                        let selected = focus.selected(specs.len());
+                        logs.toggle_paused(selected);
+                        if !logs.is_paused(selected) {
+                            scrollbacks[selected].end();
+                        }
+                    }
+                    KeyCode::Char('P') => {
+                        logs.toggle_paused_all();
+                        for (idx, scrollback) in scrollbacks.iter_mut().enumerate() {
+                            if !logs.is_paused(idx) {
+                                scrollback.end();
+                            }
+                        }
+                    }
+                    KeyCode::PageUp => scrollbacks[focus.selected(specs.len())].page_up(),
+                    KeyCode::PageDown => scrollbacks[focus.selected(specs.len())].page_down(),
+                    KeyCode::Home => scrollbacks[focus.selected(specs.len())].home(),
+                    KeyCode::End => scrollbacks[focus.selected(specs.len())].end(),
+                    KeyCode::Char('r') if supervisor.is_exited(focus.selected(specs.len())) => {
+                        let selected = focus.selected(specs.len());
+                        logs.push(selected, "Restarting...".to_string());
+                        restart_process(&specs[selected], selected, &tx, &exits_tx, &spawned_tx);
+                        supervisor.restarted(selected, Instant::now());
+                    }
+                    KeyCode::Char('k') if !supervisor.is_exited(focus.selected(specs.len())) => {
+                        pending_kill = Some(focus.selected(specs.len()));
+                    }
+                    KeyCode::Char('L') => {
+                        layout_idx = (layout_idx + 1) % layouts.len();
+                        logs.push(0, format!("Layout: {}", layouts[layout_idx]));
+                    }
+                    KeyCode::Char('v') => {
+                        let selected = focus.selected(specs.len());
+                        min_levels[selected] = Level::next_min(min_levels[selected]);
+                        scrollbacks[selected].end();
+                    }
+                    KeyCode::Char('n') => {
+                        if let (Some(client), Some(idx)) = (&mut client_pane, client_idx) {
+                            let running = !supervisor.is_exited(idx);
+                            client.next(running);
+                            specs[idx] = client.spec(lb_url.as_str());
+                            logs.push(idx, format!("Switching to {}", client.scenario()));
+                            if running {
+                                stop_process(
+                                    &specs[idx],
+                                    idx,
+                                    reaper.pid(idx),
+                                    &pane_names[idx],
+                                    &tx,
+                                );
+                            } else {
+                                restart_process(&specs[idx], idx, &tx, &exits_tx, &spawned_tx);
+                                supervisor.restarted(idx, Instant::now());
+                            }
                         }
                     }
+                    KeyCode::Char('s') => traffic.send(TestTraffic::Short, tx.clone()),
+                    KeyCode::Char('l') => traffic.send(TestTraffic::Long, tx.clone()),
+                    KeyCode::Char('b') => traffic.send(TestTraffic::Burst, tx.clone()),
                     KeyCode::Char('q') => {
                         break;
                     }
@@ -66,234 +473,955 @@ async fn main() -> Result<(), io::Error> {
         }
     }
 
+    // Stopping the children can take a couple of seconds, the terminal is restored after.
+    let _ = draw_shutting_down(&mut terminal);
+    while let Ok((idx, child)) = spawned_rx.try_recv() {
+        reaper.set_child(idx, child);
+    }
+    reaper.shutdown();
+    logs.close_files();
     cleanup_terminal()?;
     Ok(())
 }
 
-fn draw_ui(
+fn draw_startup(
     terminal: &mut Terminal<CrosstermBackend<Stdout>>,
-    logs: &[String],
+    startup: &Startup,
 ) -> Result<(), io::Error> {
     terminal.draw(|f| {
-        let size = f.area();
-
-        let background = Block::default().style(Style::default().bg(Color::Black).fg(Color::White));
-        f.render_widget(background, size);
-
-        let rows = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-            .split(f.area());
-
-        let upper_row = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(100)])
-            .split(rows[0]);
-
-        let lower_row = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([
-                Constraint::Percentage(33),
-                Constraint::Percentage(34),
-                Constraint::Percentage(33),
-            ])
-            .split(rows[1]);
-
-        let lb_output = get_end_of_wrapped_text(&logs[0], upper_row[0]);
-        let lb_block = Paragraph::new(lb_output)
+        let checklist = Paragraph::new(startup.render(Instant::now()))
             .block(
                 Block::default()
-                    .title("Load Balancer")
+                    .title("Starting up, Enter to skip")
                     .borders(Borders::ALL)
-                    .border_style(Style::default().fg(Color::Yellow)),
+                    .border_style(Style::default().fg(Color::Cyan)),
             )
-            .style(Style::default().fg(Color::White));
-        f.render_widget(lb_block, upper_row[0]);
+            .style(Style::default().bg(Color::Black).fg(Color::White));
+        f.render_widget(checklist, f.area());
+    })?;
+    Ok(())
+}
 
-        let worker1_output = get_end_of_wrapped_text(&logs[1], lower_row[0]);
-        let worker1_block = Paragraph::new(worker1_output)
-            .block(
-                Block::default()
-                    .title("Worker 1")
-                    .borders(Borders::ALL)
-                    .border_style(Style::default().fg(Color::Green)),
-            )
-            .style(Style::default().fg(Color::White));
-        f.render_widget(worker1_block, lower_row[0]);
+fn draw_shutting_down(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<(), io::Error> {
+    terminal.draw(|f| {
+        let size = f.area();
+        let message = Paragraph::new("Shutting down...")
+            .block(Block::default().borders(Borders::ALL))
+            .style(Style::default().bg(Color::Black).fg(Color::White));
+        f.render_widget(message, size);
+    })?;
+    Ok(())
+}
 
-        let worker2_output = get_end_of_wrapped_text(&logs[2], lower_row[1]);
-        let worker2_block = Paragraph::new(worker2_output)
-            .block(
+// What the panes show, borrowed from the main loop's state.
+struct Panes<'a> {
+    names: &'a [String],
+    logs: &'a Logs,
+    filters: &'a [Option<Filter>],
+    min_levels: &'a [Option<Level>],
+    layout: GridLayout,
+    supervisor: &'a Supervisor,
+    health: &'a [Health],
+    focus: &'a Focus,
+    filter_input: Option<&'a FilterInput>,
+    pending_kill: Option<usize>,
+    show_timestamps: bool,
+    // None when STATS_PANE turned it off.
+    stats: Option<&'a StatsPane>,
+    // PID, uptime, CPU and memory of each pane's process.
+    footers: &'a [String],
+    // Shown in the balancer's title.
+    algorithm: &'a AlgorithmTitle,
+}
+
+fn draw_ui(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    panes: &Panes,
+    scrollbacks: &mut [Scrollback],
+) -> Result<Vec<(usize, Rect)>, io::Error> {
+    let Panes {
+        names,
+        logs,
+        filters,
+        min_levels,
+        layout,
+        supervisor,
+        health,
+        focus,
+        filter_input,
+        pending_kill,
+        show_timestamps,
+        stats,
+        footers,
+        algorithm,
+    } = *panes;
+    let now = Instant::now();
+    let selected = focus.selected(logs.pane_count());
+    // Exited processes get a red marker and the selected pane a highlighted border,
+    // the workers' borders show how their last health probe went.
+    let pane_block = |idx: usize, scrollback: &Scrollback, matches: usize| {
+        let mut spans = vec![Span::raw(names[idx].clone())];
+        if let (0, Some(active)) = (idx, algorithm.shown()) {
+            let style = if algorithm.is_flashing(now) {
+                Style::default()
+                    .fg(Color::Black)
+                    .bg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            spans.push(Span::raw(" - "));
+            spans.push(Span::styled(active.to_string(), style));
+        }
+        let mut title = String::new();
+        let color = if idx == 0 {
+            Color::Yellow
+        } else if supervisor.is_exited(idx) {
+            Color::Red
+        } else {
+            health[idx].color()
+        };
+        if let Some(label) = health[idx].label().filter(|_| !supervisor.is_exited(idx)) {
+            title.push_str(&format!(" - {}", label));
+        }
+        if idx == 0 && logs.behind() > 0 {
+            title.push_str(&format!(" - {} log lines behind", logs.behind()));
+        }
+        if let Some(filter) = &filters[idx] {
+            title.push_str(&format!(
+                " - filter \"{}\": {} matches, Esc to clear",
+                filter.pattern(),
+                matches
+            ));
+        }
+        if let Some(min) = min_levels[idx] {
+            title.push_str(&format!(" - {}+", min));
+        }
+        if logs.is_paused(idx) {
+            title.push_str(&format!(
+                " - paused, +{} new lines",
+                logs.new_while_paused(idx)
+            ));
+        }
+        if !scrollback.is_following() {
+            let (line, total) = scrollback.position();
+            title.push_str(&format!(" - line {}/{}, End to follow", line, total));
+        }
+        spans.push(Span::raw(title));
+        let mut title = spans;
+        if let Some(marker) = supervisor.marker(idx, now) {
+            title.push(Span::raw(" "));
+            title.push(Span::styled(marker, Style::default().fg(Color::Red)));
+        }
+        let border_style = if idx == selected {
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(color)
+        };
+        Block::default()
+            .title(Line::from(title))
+            .title_bottom(Line::styled(
+                footers[idx].clone(),
+                Style::default().fg(Color::DarkGray),
+            ))
+            .borders(Borders::ALL)
+            .border_style(border_style)
+    };
+
+    let mut drawn = Vec::new();
+    terminal.draw(|f| {
+        let size = f.area();
+
+        let background = Block::default().style(Style::default().bg(Color::Black).fg(Color::White));
+        f.render_widget(background, size);
+
+        if size.width < MIN_WIDTH || size.height < MIN_HEIGHT {
+            let message = format!(
+                "terminal too small (need at least {}x{})",
+                MIN_WIDTH, MIN_HEIGHT
+            );
+            // Centered vertically too, on as many lines as wrapping it takes.
+            let lines = (message.len() as u16)
+                .div_ceil(size.width.max(1))
+                .min(size.height);
+            let middle = Rect {
+                y: size.y + (size.height - lines) / 2,
+                height: lines,
+                ..size
+            };
+            f.render_widget(
+                Paragraph::new(message)
+                    .alignment(Alignment::Center)
+                    .wrap(Wrap { trim: true }),
+                middle,
+            );
+            return;
+        }
+
+        // The filter being typed or the kill waiting for confirmation, below the panes.
+        let prompt = match (filter_input, pending_kill) {
+            (Some(input), _) => Some((
+                format!("Filter {}, Enter to apply, Esc to cancel", names[selected]),
+                format!("/{}_", input.text()),
+            )),
+            (None, Some(idx)) => Some((
+                format!("Kill {}", names[idx]),
+                format!("Stop the process behind {}? y/n", names[idx]),
+            )),
+            (None, None) => None,
+        };
+        let (size, input_area) = match prompt {
+            Some(_) => {
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(0), Constraint::Length(3)])
+                    .split(size);
+                (chunks[0], Some(chunks[1]))
+            }
+            None => (size, None),
+        };
+        if let (Some((title, text)), Some(area)) = (prompt, input_area) {
+            let prompt = Paragraph::new(text).block(
                 Block::default()
-                    .title("Worker 2")
+                    .title(title)
                     .borders(Borders::ALL)
-                    .border_style(Style::default().fg(Color::Green)),
-            )
-            .style(Style::default().fg(Color::White));
-        f.render_widget(worker2_block, lower_row[1]);
+                    .border_style(Style::default().fg(Color::Cyan)),
+            );
+            f.render_widget(prompt, area);
+        }
 
-        let worker3_output = get_end_of_wrapped_text(&logs[3], lower_row[2]);
-        let worker3_block = Paragraph::new(worker3_output)
-            .block(
+        let mut areas = if focus.is_maximized() {
+            vec![(selected, size)]
+        } else {
+            layout
+                .areas(size, logs.pane_count())
+                .into_iter()
+                .enumerate()
+                .collect::<Vec<_>>()
+        };
+        // Shares the load balancer's row, it makes room for any pane that is maximized.
+        if let (Some(stats), false) = (stats, focus.is_maximized()) {
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .split(areas[0].1);
+            areas[0].1 = chunks[0];
+            let table = Paragraph::new(stats.render()).block(
                 Block::default()
-                    .title("Worker 3")
+                    .title("Balancer stats")
                     .borders(Borders::ALL)
-                    .border_style(Style::default().fg(Color::Green)),
-            )
-            .style(Style::default().fg(Color::White));
-        f.render_widget(worker3_block, lower_row[2]);
+                    .border_style(Style::default().fg(Color::Magenta)),
+            );
+            f.render_widget(table, chunks[1]);
+        }
+        areas.retain(|(_, area)| area.width >= MIN_PANE_SIZE && area.height >= MIN_PANE_SIZE);
+        drawn.clone_from(&areas);
+        for (idx, area) in areas {
+            let lines = logs
+                .lines(idx)
+                .filter(|line| {
+                    Level::passes(line.level, min_levels[idx])
+                        && filters[idx]
+                            .as_ref()
+                            .is_none_or(|filter| filter.matches(&line.text))
+                })
+                .collect::<Vec<_>>();
+            let timestamps = lines
+                .iter()
+                .map(|line| line.prefix(show_timestamps))
+                .collect::<Vec<_>>();
+            let prefixed = lines
+                .iter()
+                .zip(&timestamps)
+                .map(|(line, timestamp)| (timestamp.as_str(), &line.line))
+                .collect::<Vec<_>>();
+            let output = scrollbacks[idx].view_prefixed(
+                &prefixed,
+                Style::default().fg(Color::DarkGray),
+                area,
+            );
+            let block = Paragraph::new(output)
+                .block(pane_block(idx, &scrollbacks[idx], lines.len()))
+                .style(Style::default().fg(Color::White));
+            f.render_widget(block, area);
+        }
     })?;
 
-    Ok(())
+    Ok(drawn)
+}
+
+// What to sample for each running pane. Containers are sampled through docker,
+// the pid of a container's pane is the `docker logs` following it.
+fn sample_targets(
+    specs: &[ProcessSpec],
+    reaper: &Reaper,
+    supervisor: &Supervisor,
+) -> Vec<(usize, Target)> {
+    specs
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| !supervisor.is_exited(*idx))
+        .filter_map(|(idx, spec)| match spec {
+            ProcessSpec::Local { .. } => reaper.pid(idx).map(|pid| (idx, Target::Pid(pid))),
+            ProcessSpec::Container { name, .. } => Some((idx, Target::Container(name.clone()))),
+        })
+        .collect()
+}
+
+// Marks the lines of each pane in exported logs, e.g. `[lb]`, `[w1]`.
+fn pane_tag(idx: usize, spec: &ProcessSpec) -> String {
+    match (idx, spec) {
+        (_, ProcessSpec::Local { name, .. }) if name == client_pane::CLIENT => "client".to_string(),
+        (0, _) => "lb".to_string(),
+        _ => format!("w{}", idx),
+    }
+}
+
+fn pane_name(idx: usize, spec: &ProcessSpec) -> String {
+    match (idx, spec) {
+        (_, ProcessSpec::Local { name, .. }) if name == client_pane::CLIENT => "Client".to_string(),
+        (0, _) => "Load Balancer".to_string(),
+        (worker, _) => format!("Worker {}", worker),
+    }
+}
+
+fn grid_layout(config: &Config) -> GridLayout {
+    GridLayout::new(
+        config.layout,
+        config.layout_balancer_percent,
+        config.layout_columns,
+    )
+}
+
+// The balancer is ready once its /lb/health says enough workers are.
+// None when there is nothing to wait for.
+fn ready_check(idx: usize, spec: &ProcessSpec, url: &reqwest::Url) -> Option<ReadyCheck> {
+    match spec {
+        _ if idx == 0 => url
+            .join("/lb/health")
+            .ok()
+            .map(|url| ReadyCheck::Http(url.into())),
+        ProcessSpec::Local { .. } => spec.health_url().map(ReadyCheck::Http),
+        ProcessSpec::Container { name, .. } => Some(ReadyCheck::Container(name.clone())),
+    }
+}
+
+// Where the balancer is, the dashboard can't start anything with a topology it can't read.
+// A bad APP_ENVIRONMENT and kubernetes, whose pods are kubectl's to start and follow,
+// run everything locally.
+fn topology(tx: &LogSender) -> Result<Topology, EnvironmentError> {
+    let env = Environment::from_env().unwrap_or_else(|e| {
+        let _ = tx.send((0, format!("WARN {}, running everything locally", e)));
+        Environment::Local
+    });
+    let env = if env == Environment::Kubernetes {
+        let _ = tx.send((
+            0,
+            "WARN The dashboard can't launch the balancer in kubernetes, running everything locally"
+                .to_string(),
+        ));
+        Environment::Local
+    } else {
+        env
+    };
+    Topology::from_env(env)
+}
+
+fn lb_url(config: &Config, topology: &Topology) -> Result<reqwest::Url, String> {
+    match &config.lb_url {
+        Some(url) => Ok(url.clone()),
+        None => {
+            let value = topology.load_balancer_url();
+            value
+                .parse()
+                .map_err(|_| format!("Invalid load balancer URL {}", value))
+        }
+    }
+}
+
+// Before the terminal is taken over, the message shows in the shell the dashboard was run from.
+fn exit_with(msg: impl fmt::Display) -> ! {
+    eprintln!("{}", msg);
+    std::process::exit(1);
 }
 
+// Starts the process and reports its exit, `restart` skips what it already logged.
+// Returns the child, for a container the `docker logs` following it.
 async fn spawn_process(
-    tx: mpsc::UnboundedSender<(usize, String)>,
-    name: String,
+    spec: &ProcessSpec,
     idx: usize,
-    env: Option<Vec<(String, String)>>,
+    restart: bool,
+    tx: LogSender,
+    exits: ExitSender,
+) -> Option<SharedChild> {
+    let mut cmd = match spec {
+        // Resolved on every start, so restarting picks up a binary built in the meantime.
+        ProcessSpec::Local { name, args, env } => match executable::resolve(name) {
+            Ok(path) => {
+                let mut cmd = AsyncCommand::new(path);
+                cmd.args(args).envs(env.iter().cloned());
+                cmd
+            }
+            Err(e) => {
+                let _ = tx.send((idx, format!("ERROR {}", e)));
+                let _ = exits.send((idx, None));
+                return None;
+            }
+        },
+        ProcessSpec::Container { name, since } => {
+            if restart {
+                // Still following the logs on failure, they show why the container can't run.
+                match AsyncCommand::new("docker")
+                    .arg("start")
+                    .arg(name)
+                    .output()
+                    .await
+                {
+                    Ok(output) if !output.status.success() => {
+                        let _ = tx.send((
+                            idx,
+                            format!(
+                                "ERROR Failed to start {}: {}",
+                                name,
+                                String::from_utf8_lossy(&output.stderr).trim()
+                            ),
+                        ));
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        let _ = tx.send((idx, format!("ERROR Failed to run docker: {}", e)));
+                    }
+                }
+            }
+            let mut cmd = AsyncCommand::new("docker");
+            cmd.arg("logs").arg("-f"); // Follow logs
+            if restart {
+                cmd.arg("--tail").arg("0");
+            } else {
+                cmd.arg("--since").arg(since);
+            }
+            cmd.arg(name);
+            cmd
+        }
+    };
+    let name = match spec {
+        ProcessSpec::Local { name, .. } | ProcessSpec::Container { name, .. } => name.clone(),
+    };
+
+    // docker logs replays the container's stderr on its own stderr.
+    let mut child = match cmd
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = tx.send((idx, format!("ERROR Failed to start {}: {}", name, e)));
+            let _ = exits.send((idx, None));
+            return None;
+        }
+    };
+    let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+    let child = Arc::new(Mutex::new(child));
+    task::spawn({
+        let child = child.clone();
+        async move {
+            forward_output(stdout, stderr, tx, idx).await;
+            let _ = exits.send((idx, exit_code(&child).await));
+        }
+    });
+    Some(child)
+}
+
+// Polled rather than waited for, the reaper needs the child too.
+async fn exit_code(child: &SharedChild) -> Option<i32> {
+    loop {
+        let exited = child.lock().unwrap().try_wait();
+        match exited {
+            Ok(Some(status)) => return status.code(),
+            Ok(None) => tokio::time::sleep(reaper::POLL_INTERVAL).await,
+            Err(_) => return None,
+        }
+    }
+}
+
+// Restarts the process behind a pane without holding up the UI, the child is sent to `spawned`
+// once it runs. Its output, exit and any failure to start are reported like the first time.
+fn restart_process(
+    spec: &ProcessSpec,
+    idx: usize,
+    tx: &LogSender,
+    exits: &ExitSender,
+    spawned: &SpawnedSender,
 ) {
-    let executable_file = {
-        #[cfg(target_os = "windows")]
-        {
-            format!("{}.exe", name.clone())
+    let (spec, tx, exits, spawned) = (spec.clone(), tx.clone(), exits.clone(), spawned.clone());
+    task::spawn(async move {
+        let child = spawn_process(&spec, idx, true, tx, exits).await;
+        let _ = spawned.send((idx, child));
+    });
+}
+
+// Kills the process behind a pane in the background, `docker stop` waits for the container
+// to exit. Failures are written to the pane.
+fn stop_process(spec: &ProcessSpec, idx: usize, pid: Option<u32>, name: &str, tx: &LogSender) {
+    let (spec, name, tx) = (spec.clone(), name.to_string(), tx.clone());
+    task::spawn(async move {
+        if let Err(e) = kill_process(&spec, pid).await {
+            let _ = tx.send((idx, format!("Failed to kill {}: {}", name, e)));
         }
-        #[cfg(not(target_os = "windows"))]
-        {
-            name.clone()
+    });
+}
+
+// Stops the process behind a pane, its exit is reported like any other.
+// Containers are stopped with docker, which also ends the `docker logs` following them.
+async fn kill_process(spec: &ProcessSpec, pid: Option<u32>) -> Result<(), String> {
+    let mut cmd = match spec {
+        ProcessSpec::Local { .. } => {
+            let pid = pid.ok_or("The process isn't running")?.to_string();
+            #[cfg(target_os = "windows")]
+            {
+                let mut cmd = AsyncCommand::new("taskkill");
+                cmd.arg("/PID").arg(pid).arg("/F");
+                cmd
+            }
+            #[cfg(not(target_os = "windows"))]
+            {
+                let mut cmd = AsyncCommand::new("kill");
+                cmd.arg("-TERM").arg(pid);
+                cmd
+            }
+        }
+        ProcessSpec::Container { name, .. } => {
+            let mut cmd = AsyncCommand::new("docker");
+            cmd.arg("stop").arg(name);
+            cmd
         }
     };
-    let sibling_path = Path::new(".")
-        .join(name.clone())
-        .join("target")
-        .join("debug")
-        .join(executable_file.clone());
-    let parent_path = Path::new("..")
-        .join(name.clone())
-        .join("target")
-        .join("debug")
-        .join(executable_file.clone());
-    let executable_path = if sibling_path.exists() {
-        sibling_path.to_str().unwrap().to_string()
-    } else if parent_path.exists() {
-        parent_path.to_str().unwrap().to_string()
+    let output = cmd.output().await.map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
     } else {
-        panic!(
-            "Executable not found in expected locations: {:?} or {:?}",
-            sibling_path, parent_path
-        );
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+// Sends both output streams of the child to its pane as lines arrive, so they stay roughly in order.
+async fn forward_output(
+    stdout: Option<ChildStdout>,
+    stderr: Option<ChildStderr>,
+    tx: LogSender,
+    idx: usize,
+) {
+    tokio::join!(
+        forward_lines(stdout, tx.clone(), idx, ""),
+        forward_lines(stderr, tx, idx, STDERR_PREFIX),
+    );
+}
+
+async fn forward_lines(
+    stream: Option<impl tokio::io::AsyncRead + Unpin>,
+    tx: LogSender,
+    idx: usize,
+    prefix: &str,
+) {
+    let Some(stream) = stream else {
+        return;
     };
+    let reader = tokio::io::BufReader::new(stream);
+    let mut lines = reader.lines();
+    while let Some(line) = lines.next_line().await.unwrap_or(None) {
+        // Escape codes are kept, the pane turns them into styles.
+        let _ = tx.send((idx, format!("{}{}", prefix, line)));
+    }
+}
 
-    task::spawn(async move {
-        let mut cmd = AsyncCommand::new(executable_path);
+// Also returns how compose was run, None in local mode or when it isn't installed.
+async fn launch_load_balancer(
+    topology: &Topology,
+    tx: &LogSender,
+) -> (Vec<ProcessSpec>, Option<Compose>) {
+    match topology.environment() {
+        Environment::DockerCompose => {
+            launch_load_balancer_docker_compose(topology.worker_count(), tx).await
+        }
+        // topology() already swapped kubernetes for local.
+        Environment::Local | Environment::Kubernetes => {
+            (launch_load_balancer_local(topology), None)
+        }
+    }
+}
 
-        if let Some(env_vars) = env {
-            for (key, value) in env_vars {
-                cmd.env(key, value);
-            }
+// Extra arguments and environment come from `<NAME>_ARGS` and `<NAME>_ENV`.
+fn launch_load_balancer_local(topology: &Topology) -> Vec<ProcessSpec> {
+    let local = |name: &str, env: (&str, String)| {
+        let mut vars = vec![(env.0.to_string(), env.1)];
+        vars.extend(executable::extra_env(name));
+        ProcessSpec::Local {
+            name: name.to_string(),
+            args: executable::extra_args(name),
+            env: vars,
         }
+    };
+    let load_balancer = local(
+        "load-balancer",
+        ("WORKER_COUNT", topology.worker_count().to_string()),
+    );
+    let workers = topology
+        .worker_addresses()
+        .into_iter()
+        .map(|worker| local("worker-server", ("PORT", worker.port.to_string())));
+    std::iter::once(load_balancer).chain(workers).collect()
+}
 
-        let mut child = cmd
-            .stdout(std::process::Stdio::piped())
-            .spawn()
-            .unwrap_or_else(|e| {
-                panic!("Failed to spawn process {}: {}", name.clone(), e);
-            });
-        if let Some(stdout) = child.stdout.take() {
-            let reader = tokio::io::BufReader::new(stdout);
-            let mut lines = reader.lines();
-            while let Some(line) = lines.next_line().await.unwrap_or_else(|_| None) {
-                let line = line.into_text().unwrap();
-                let _ = tx.send((idx, format!("{}", line)));
+// The panes follow the services the compose file defines, the balancer's first.
+// How it went is reported in the load balancer's pane, the terminal is taken over by then.
+async fn launch_load_balancer_docker_compose(
+    worker_count: usize,
+    tx: &LogSender,
+) -> (Vec<ProcessSpec>, Option<Compose>) {
+    let since = compose::since(Utc::now());
+    let compose = Compose::detect().await;
+    match compose {
+        Some(compose) => match compose.command().arg("up").arg("-d").output().await {
+            // The panes still follow the containers, their logs show what is missing.
+            Ok(output) if output.status.success() => {
+                let _ = tx.send((0, format!("Launched with {}", compose.name())));
             }
+            Ok(output) => {
+                let _ = tx.send((
+                    0,
+                    format!(
+                        "ERROR {} up failed: {}",
+                        compose.name(),
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ),
+                ));
+            }
+            Err(e) => {
+                let _ = tx.send((0, format!("ERROR Failed to run {}: {}", compose.name(), e)));
+            }
+        },
+        None => {
+            let _ = tx.send((
+                0,
+                "ERROR Neither `docker compose` nor `docker-compose` is available, install Docker Compose to run the containers".to_string(),
+            ));
         }
+    }
+
+    let services = match compose {
+        Some(compose) => match compose.services().await {
+            Ok(services) if services.len() > 1 => Some(services),
+            Ok(_) => {
+                let _ = tx.send((
+                    0,
+                    "ERROR The compose file needs a balancer and at least one worker service"
+                        .to_string(),
+                ));
+                None
+            }
+            Err(e) => {
+                let _ = tx.send((0, format!("ERROR {}", e)));
+                None
+            }
+        },
+        None => None,
+    };
+    // Without them the default service names are followed, for WORKER_COUNT workers.
+    let services = services.unwrap_or_else(|| {
+        std::iter::once("load-balancer".to_string())
+            .chain((1..=worker_count).map(|i| format!("worker-server{}", i)))
+            .collect()
     });
+    let containers = match compose {
+        Some(compose) => compose.containers().await.unwrap_or_else(|e| {
+            let _ = tx.send((0, format!("ERROR {}", e)));
+            HashMap::new()
+        }),
+        None => HashMap::new(),
+    };
+
+    let specs = services
+        .into_iter()
+        .map(|service| ProcessSpec::Container {
+            name: containers.get(&service).cloned().unwrap_or(service),
+            since: since.clone(),
+        })
+        .collect();
+    (specs, compose)
 }
 
-async fn launch_load_balancer(tx: mpsc::UnboundedSender<(usize, String)>) {
-    let env = Environment::from_env();
-    match env {
-        Environment::Local => launch_load_balancer_local(tx).await,
-        Environment::DockerCompose => launch_load_balancer_docker_compose(tx).await,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shell(script: &str) -> ProcessSpec {
+        ProcessSpec::Local {
+            name: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            env: Vec::new(),
+        }
     }
-}
 
-async fn launch_load_balancer_local(tx: mpsc::UnboundedSender<(usize, String)>) {
-    spawn_process(tx.clone(), "load-balancer".to_string(), 0, None).await;
+    // Runs `spec` in pane `idx` until it exits, returning its lines and exit code.
+    async fn run(spec: &ProcessSpec, idx: usize) -> (Vec<(usize, String)>, (usize, Option<i32>)) {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (exits, mut exit_rx) = mpsc::unbounded_channel();
+        spawn_process(spec, idx, false, tx, exits).await;
 
-    for (i, port) in (3000..3003).enumerate() {
-        spawn_process(
-            tx.clone(),
-            "worker-server".to_string(),
-            i + 1,
-            Some(vec![("PORT".to_string(), port.to_string())]),
+        let exit = tokio::time::timeout(Duration::from_secs(5), exit_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let mut lines = Vec::new();
+        while let Ok(line) = rx.try_recv() {
+            lines.push(line);
+        }
+        (lines, exit)
+    }
+
+    #[tokio::test]
+    async fn both_output_streams_reach_the_pane() {
+        let (lines, exit) = run(
+            &shell("echo listening; echo 'bind failed' >&2; echo done; exit 2"),
+            3,
         )
         .await;
+
+        assert_eq!(exit, (3, Some(2)));
+        let stdout = lines
+            .iter()
+            .filter(|(_, line)| !line.starts_with(STDERR_PREFIX))
+            .map(|(idx, line)| (*idx, line.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(stdout, [(3, "listening"), (3, "done")]);
+        assert!(lines.contains(&(3, "[stderr] bind failed".to_string())));
+        assert_eq!(lines.len(), 3);
     }
-}
 
-async fn launch_load_balancer_docker_compose(tx: mpsc::UnboundedSender<(usize, String)>) {
-    let output = AsyncCommand::new("docker-compose")
-        .arg("up")
-        .arg("-d")
-        .output()
-        .await
-        .expect("Failed to execute docker-compose");
-
-    if !output.status.success() {
-        eprintln!(
-            "Error launching Docker Compose: {}",
-            String::from_utf8_lossy(&output.stderr)
+    #[tokio::test]
+    async fn exited_processes_are_marked_and_restarted() {
+        let spec = shell("echo started; exit 1");
+        let mut supervisor = Supervisor::new(2, false, Instant::now());
+
+        let (lines, (idx, code)) = run(&spec, 1).await;
+        assert_eq!(lines, [(1, "started".to_string())]);
+        supervisor.exited(idx, code, Instant::now(), Local::now());
+        let marker = supervisor.marker(1, Instant::now()).unwrap();
+        assert!(marker.starts_with("[exited: code 1 at "), "{}", marker);
+
+        // A restart runs the same spec again, capturing its output like the first time.
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (exits, mut exit_rx) = mpsc::unbounded_channel();
+        let child = spawn_process(&spec, 1, true, tx, exits).await;
+        assert!(child.is_some());
+        supervisor.restarted(1, Instant::now());
+        assert!(!supervisor.is_exited(1));
+
+        assert_eq!(exit_rx.recv().await, Some((1, Some(1))));
+        assert_eq!(rx.recv().await, Some((1, "started".to_string())));
+    }
+
+    #[test]
+    fn workers_are_probed_on_the_port_they_were_started_with() {
+        let topology = Topology::new(Environment::Local)
+            .with_worker_count(2)
+            .with_worker_base_port(4000);
+        let specs = launch_load_balancer_local(&topology);
+
+        assert_eq!(
+            specs
+                .iter()
+                .map(|spec| spec.health_url())
+                .collect::<Vec<_>>(),
+            [
+                None,
+                Some("http://127.0.0.1:4000/health".to_string()),
+                Some("http://127.0.0.1:4001/health".to_string()),
+            ]
         );
-        return;
+        let container = ProcessSpec::Container {
+            name: "worker-1".to_string(),
+            since: "2024-05-01T00:00:00Z".to_string(),
+        };
+        assert_eq!(container.health_url(), None);
     }
 
-    println!("Docker Compose launched successfully!");
-
-    tokio::spawn(async move {
-        let containers = vec![
-            "load-balancer",
-            "worker-server1",
-            "worker-server2",
-            "worker-server3",
-        ];
-        let mut tasks = Vec::new();
-
-        for (idx, container) in containers.iter().enumerate() {
-            let tx = tx.clone();
-            let container_name = container.to_string();
-
-            let task = tokio::spawn(async move {
-                let mut cmd = AsyncCommand::new("docker");
-                cmd.arg("logs")
-                    .arg("-f") // Follow logs
-                    .arg(&container_name);
-
-                let mut child = cmd
-                    .stdout(std::process::Stdio::piped())
-                    .spawn()
-                    .expect("Failed to spawn docker logs command");
-
-                if let Some(stdout) = child.stdout.take() {
-                    let reader = tokio::io::BufReader::new(stdout);
-                    let mut lines = reader.lines();
-
-                    while let Some(line) = lines.next_line().await.unwrap_or_else(|_| None) {
-                        let line = line.into_text().unwrap();
-                        let _ = tx.send((idx, format!("{}", line)));
-                    }
-                }
+    #[test]
+    fn exported_lines_are_tagged_by_pane() {
+        let topology = Topology::new(Environment::Local).with_worker_count(2);
+        let mut specs = launch_load_balancer_local(&topology);
+        specs.push(ProcessSpec::Local {
+            name: client_pane::CLIENT.to_string(),
+            args: Vec::new(),
+            env: Vec::new(),
+        });
+        assert_eq!(
+            specs
+                .iter()
+                .enumerate()
+                .map(|(idx, spec)| pane_tag(idx, spec))
+                .collect::<Vec<_>>(),
+            ["lb", "w1", "w2", "client"]
+        );
+    }
 
-                child.wait().await.expect("Failed to wait for docker logs");
-            });
+    #[tokio::test]
+    async fn killing_a_pane_terminates_its_process() {
+        let spec = shell("echo up; exec sleep 30");
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (exits, mut exit_rx) = mpsc::unbounded_channel();
+        let child = spawn_process(&spec, 2, false, tx, exits).await.unwrap();
+        assert_eq!(rx.recv().await, Some((2, "up".to_string())));
+        let pid = child.lock().unwrap().id();
 
-            tasks.push(task);
-        }
+        kill_process(&spec, pid).await.unwrap();
 
-        tasks.into_iter().for_each(|t| {
-            tokio::spawn(async move {
-                t.await.expect("Failed to wait for task");
-            });
-        });
-    });
+        // Killed by a signal, so there is no exit code.
+        let exit = tokio::time::timeout(Duration::from_secs(5), exit_rx.recv()).await;
+        assert_eq!(exit.unwrap(), Some((2, None)));
+        let mut supervisor = Supervisor::new(3, false, Instant::now());
+        supervisor.exited(2, None, Instant::now(), Local::now());
+        assert!(supervisor
+            .marker(2, Instant::now())
+            .unwrap()
+            .starts_with("[exited: no code at "));
+    }
+
+    #[tokio::test]
+    async fn a_missing_executable_is_reported_in_its_pane() {
+        let missing = ProcessSpec::Local {
+            name: "definitely-missing-binary".to_string(),
+            args: Vec::new(),
+            env: Vec::new(),
+        };
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (exits, mut exit_rx) = mpsc::unbounded_channel();
+        assert!(spawn_process(&missing, 1, false, tx, exits).await.is_none());
+
+        let (idx, line) = rx.try_recv().unwrap();
+        assert_eq!(idx, 1);
+        assert!(
+            line.starts_with("ERROR Executable definitely-missing-binary not found in "),
+            "{}",
+            line
+        );
+        assert!(line.contains("DEFINITELY_MISSING_BINARY_PATH"), "{}", line);
+        assert_eq!(exit_rx.try_recv(), Ok((1, None)));
+    }
+
+    #[tokio::test]
+    async fn a_failed_spawn_leaves_the_supervisor_working() {
+        let missing = ProcessSpec::Local {
+            name: "definitely-missing-binary".to_string(),
+            args: Vec::new(),
+            env: Vec::new(),
+        };
+        let now = Instant::now();
+        let mut supervisor = Supervisor::new(2, true, now);
+
+        let (lines, (idx, code)) = run(&missing, 0).await;
+        assert_eq!(lines.len(), 1);
+        supervisor.exited(idx, code, now, Local::now());
+        // Retried like any process that exited, once its backoff passed.
+        assert!(supervisor.due(now).is_empty());
+        assert_eq!(supervisor.due(now + Duration::from_secs(1)), [0]);
+        assert!(supervisor
+            .marker(0, now)
+            .unwrap()
+            .starts_with("[exited: no code at "));
+
+        // The other panes still run and report their output and exits.
+        let (lines, exit) = run(&shell("echo still here"), 1).await;
+        assert_eq!(lines, [(1, "still here".to_string())]);
+        assert_eq!(exit, (1, Some(0)));
+        assert!(!supervisor.is_exited(1));
+
+        // A retry that fails again is reported the same way.
+        supervisor.restarted(0, now + Duration::from_secs(1));
+        let (lines, exit) = run(&missing, 0).await;
+        assert!(lines[0].1.starts_with("ERROR "));
+        assert_eq!(exit, (0, None));
+    }
+
+    #[tokio::test]
+    async fn restarts_run_in_the_background_and_hand_back_the_child() {
+        let spec = shell("echo restarted");
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (exits, mut exit_rx) = mpsc::unbounded_channel();
+        let (spawned, mut spawned_rx) = mpsc::unbounded_channel();
+
+        restart_process(&spec, 2, &tx, &exits, &spawned);
+        let (idx, child) = tokio::time::timeout(Duration::from_secs(5), spawned_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(idx, 2);
+        assert!(child.is_some());
+        assert_eq!(rx.recv().await, Some((2, "restarted".to_string())));
+        assert_eq!(exit_rx.recv().await, Some((2, Some(0))));
+    }
+
+    #[tokio::test]
+    async fn failed_background_restarts_are_reported() {
+        let missing = ProcessSpec::Local {
+            name: "definitely-missing-binary".to_string(),
+            args: Vec::new(),
+            env: Vec::new(),
+        };
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (exits, mut exit_rx) = mpsc::unbounded_channel();
+        let (spawned, mut spawned_rx) = mpsc::unbounded_channel();
+
+        restart_process(&missing, 1, &tx, &exits, &spawned);
+        assert_eq!(
+            spawned_rx
+                .recv()
+                .await
+                .map(|(idx, child)| (idx, child.is_none())),
+            Some((1, true))
+        );
+        assert!(rx.recv().await.unwrap().1.starts_with("ERROR "));
+        assert_eq!(exit_rx.recv().await, Some((1, None)));
+    }
+
+    #[tokio::test]
+    async fn background_kills_report_failures_to_the_pane() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        stop_process(&shell("true"), 3, None, "Worker 3", &tx);
+        assert_eq!(
+            rx.recv().await,
+            Some((
+                3,
+                "Failed to kill Worker 3: The process isn't running".to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn background_kills_stop_the_process() {
+        let spec = shell("echo up; exec sleep 30");
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (exits, mut exit_rx) = mpsc::unbounded_channel();
+        let child = spawn_process(&spec, 0, false, tx.clone(), exits)
+            .await
+            .unwrap();
+        assert_eq!(rx.recv().await, Some((0, "up".to_string())));
+        let pid = child.lock().unwrap().id();
+
+        stop_process(&spec, 0, pid, "Load Balancer", &tx);
+        let exit = tokio::time::timeout(Duration::from_secs(5), exit_rx.recv()).await;
+        assert_eq!(exit.unwrap(), Some((0, None)));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn killing_a_pane_without_a_process_fails() {
+        assert_eq!(
+            kill_process(&shell("true"), None).await,
+            Err("The process isn't running".to_string())
+        );
+    }
 }
@@ -0,0 +1,145 @@
+use ratatui::layout::Rect;
+
+// Lines the wheel moves a pane by per notch.
+pub const SCROLL_LINES: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Hit {
+    // The top border, where the pane's title is.
+    Title(usize),
+    Body(usize),
+}
+
+// The pane under a terminal cell, from the areas of the last draw. Those don't overlap,
+// the stats pane isn't among them so it doesn't take clicks.
+pub fn hit(areas: &[(usize, Rect)], column: u16, row: u16) -> Option<Hit> {
+    areas
+        .iter()
+        .find(|(_, area)| {
+            (area.x..area.right()).contains(&column) && (area.y..area.bottom()).contains(&row)
+        })
+        .map(|&(idx, area)| {
+            if row == area.y {
+                Hit::Title(idx)
+            } else {
+                Hit::Body(idx)
+            }
+        })
+}
+
+impl Hit {
+    pub fn pane(self) -> usize {
+        match self {
+            Hit::Title(idx) | Hit::Body(idx) => idx,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{Arrangement, GridLayout};
+
+    // The areas the layout gives `pane_count` panes on a 120x40 terminal, as draw_ui keeps them.
+    fn drawn(layout: GridLayout, pane_count: usize) -> Vec<(usize, Rect)> {
+        layout
+            .areas(Rect::new(0, 0, 120, 40), pane_count)
+            .into_iter()
+            .enumerate()
+            .collect()
+    }
+
+    fn layouts() -> [GridLayout; 3] {
+        [
+            GridLayout::new(Arrangement::Stacked, 50, 4),
+            GridLayout::new(Arrangement::Stacked, 25, 2),
+            GridLayout::new(Arrangement::SideBySide, 40, 1),
+        ]
+    }
+
+    #[test]
+    fn every_cell_of_a_pane_hits_it() {
+        for layout in layouts() {
+            for pane_count in [1, 4, 9] {
+                let areas = drawn(layout, pane_count);
+                for &(idx, area) in &areas {
+                    let last_column = area.right() - 1;
+                    let last_row = area.bottom() - 1;
+                    for (column, row) in [
+                        (area.x, area.y + 1),
+                        (last_column, last_row),
+                        (area.x + area.width / 2, area.y + area.height / 2),
+                    ] {
+                        assert_eq!(
+                            hit(&areas, column, row),
+                            Some(Hit::Body(idx)),
+                            "{:?} with {} panes at {},{}",
+                            layout,
+                            pane_count,
+                            column,
+                            row
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_top_border_is_the_title() {
+        for layout in layouts() {
+            let areas = drawn(layout, 5);
+            for &(idx, area) in &areas {
+                assert_eq!(hit(&areas, area.x, area.y), Some(Hit::Title(idx)));
+                assert_eq!(hit(&areas, area.right() - 1, area.y), Some(Hit::Title(idx)));
+            }
+        }
+    }
+
+    #[test]
+    fn neighbouring_panes_split_at_their_edge() {
+        let areas = drawn(GridLayout::new(Arrangement::Stacked, 50, 4), 3);
+        let (_, left) = areas[1];
+        let (_, right) = areas[2];
+        assert_eq!(left.right(), right.x);
+        assert_eq!(
+            hit(&areas, left.right() - 1, left.y + 2),
+            Some(Hit::Body(1))
+        );
+        assert_eq!(hit(&areas, right.x, right.y + 2), Some(Hit::Body(2)));
+    }
+
+    #[test]
+    fn cells_outside_the_panes_hit_nothing() {
+        // The panes above a stats pane, which doesn't take clicks.
+        let areas = vec![(0, Rect::new(0, 0, 60, 10)), (1, Rect::new(60, 0, 60, 10))];
+        assert_eq!(hit(&areas, 30, 10), None);
+        assert_eq!(hit(&areas, 120, 5), None);
+        assert_eq!(hit(&areas, 200, 200), None);
+        assert_eq!(hit(&[], 0, 0), None);
+    }
+
+    #[test]
+    fn a_maximized_pane_keeps_its_index() {
+        let areas = vec![(3, Rect::new(0, 0, 120, 40))];
+        assert_eq!(hit(&areas, 0, 0), Some(Hit::Title(3)));
+        assert_eq!(hit(&areas, 60, 20), Some(Hit::Body(3)));
+    }
+
+    #[test]
+    fn offset_areas_are_hit_where_they_are() {
+        let areas = vec![(0, Rect::new(10, 5, 20, 10))];
+        assert_eq!(hit(&areas, 9, 6), None);
+        assert_eq!(hit(&areas, 10, 4), None);
+        assert_eq!(hit(&areas, 10, 5), Some(Hit::Title(0)));
+        assert_eq!(hit(&areas, 29, 14), Some(Hit::Body(0)));
+        assert_eq!(hit(&areas, 30, 14), None);
+        assert_eq!(hit(&areas, 29, 15), None);
+    }
+
+    #[test]
+    fn both_hits_name_their_pane() {
+        assert_eq!(Hit::Title(2).pane(), 2);
+        assert_eq!(Hit::Body(5).pane(), 5);
+    }
+}
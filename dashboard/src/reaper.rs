@@ -0,0 +1,203 @@
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tokio::process::Child;
+
+use crate::compose::Compose;
+
+// How long terminated children get to exit before they are killed.
+const GRACE_PERIOD: Duration = Duration::from_secs(2);
+pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// A spawned process, shared by the task reporting its exit and the reaper stopping it.
+pub type SharedChild = Arc<Mutex<Child>>;
+
+// Stops every child the dashboard started when it quits, or when it panics since this runs on drop too.
+pub struct Reaper {
+    children: Vec<Option<SharedChild>>,
+    // Brings compose down after the children, which then are only `docker logs` followers.
+    compose_down: Option<Compose>,
+    done: bool,
+}
+
+impl Reaper {
+    pub fn new(process_count: usize, compose_down: Option<Compose>) -> Self {
+        Reaper {
+            children: vec![None; process_count],
+            compose_down,
+            done: false,
+        }
+    }
+
+    pub fn set_child(&mut self, idx: usize, child: Option<SharedChild>) {
+        self.children[idx] = child;
+    }
+
+    // None once the child exited and was waited for, so its pid can't be another process's.
+    pub fn pid(&self, idx: usize) -> Option<u32> {
+        self.children[idx]
+            .as_ref()
+            .and_then(|child| child.lock().unwrap().id())
+    }
+
+    // Blocks until everything is stopped, at most the grace period plus what docker takes.
+    pub fn shutdown(&mut self) {
+        if self.done {
+            return;
+        }
+        self.done = true;
+
+        let mut alive = self
+            .children
+            .iter()
+            .flatten()
+            .filter(|child| is_running(child))
+            .collect::<Vec<_>>();
+        for child in &alive {
+            terminate(&child.lock().unwrap());
+        }
+        let deadline = Instant::now() + GRACE_PERIOD;
+        loop {
+            alive.retain(|child| is_running(child));
+            if alive.is_empty() || Instant::now() >= deadline {
+                break;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        for child in alive {
+            let mut child = child.lock().unwrap();
+            let _ = child.start_kill();
+            let _ = child.try_wait();
+        }
+
+        if let Some(compose) = self.compose_down {
+            let _ = compose
+                .blocking_command()
+                .arg("down")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+        }
+    }
+}
+
+impl Drop for Reaper {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+// Reaps the child when it exited, like waiting for it would.
+pub fn is_running(child: &SharedChild) -> bool {
+    matches!(child.lock().unwrap().try_wait(), Ok(None))
+}
+
+fn run(program: &str, args: &[&str]) -> bool {
+    Command::new(program)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn terminate(child: &Child) {
+    if let Some(pid) = child.id() {
+        run("kill", &["-TERM", &pid.to_string()]);
+    }
+}
+
+// Windows has no SIGTERM, the children are killed right away.
+#[cfg(target_os = "windows")]
+fn terminate(child: &Child) {
+    if let Some(pid) = child.id() {
+        run("taskkill", &["/PID", &pid.to_string(), "/F"]);
+    }
+}
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod tests {
+    use super::*;
+
+    fn spawn(script: &str) -> SharedChild {
+        let child = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(script)
+            .spawn()
+            .unwrap();
+        Arc::new(Mutex::new(child))
+    }
+
+    #[tokio::test]
+    async fn children_are_terminated_then_killed() {
+        let polite = spawn("sleep 30");
+        let stubborn = spawn("trap '' TERM; sleep 30");
+        let mut reaper = Reaper::new(3, None);
+        reaper.set_child(0, Some(polite.clone()));
+        reaper.set_child(1, Some(stubborn.clone()));
+        assert!(reaper.pid(0).is_some());
+        // Give the shell time to set its trap.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let started = Instant::now();
+        reaper.shutdown();
+
+        // The stubborn one held the shutdown up for the grace period, then was killed.
+        assert!(started.elapsed() >= GRACE_PERIOD);
+        assert!(!is_running(&polite) && !is_running(&stubborn));
+        assert_eq!(
+            (reaper.pid(0), reaper.pid(1), reaper.pid(2)),
+            (None, None, None)
+        );
+    }
+
+    #[tokio::test]
+    async fn children_already_gone_are_left_alone() {
+        let child = spawn("exit 3");
+        while is_running(&child) {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        let mut reaper = Reaper::new(1, None);
+        reaper.set_child(0, Some(child.clone()));
+
+        reaper.shutdown();
+        let status = child.lock().unwrap().try_wait().unwrap();
+        assert_eq!(status.and_then(|status| status.code()), Some(3));
+        assert_eq!(reaper.pid(0), None);
+    }
+
+    #[tokio::test]
+    async fn a_panic_still_stops_the_children() {
+        let child = spawn("sleep 30");
+        let pid = child.lock().unwrap().id();
+        assert!(pid.is_some());
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut reaper = Reaper::new(2, None);
+            reaper.set_child(1, Some(child.clone()));
+            panic!("drawing failed");
+        }));
+
+        assert!(result.is_err());
+        assert!(!is_running(&child));
+    }
+
+    #[tokio::test]
+    async fn shutting_down_twice_is_harmless() {
+        let first = spawn("sleep 30");
+        let mut reaper = Reaper::new(2, None);
+        reaper.set_child(0, Some(first.clone()));
+        reaper.shutdown();
+        assert!(!is_running(&first));
+
+        // A child set afterwards is left for its owner, the reaper is done.
+        let later = spawn("sleep 30");
+        reaper.set_child(1, Some(later.clone()));
+        drop(reaper);
+        assert!(is_running(&later));
+        later.lock().unwrap().start_kill().unwrap();
+    }
+}
@@ -0,0 +1,516 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use tokio::process::Command as AsyncCommand;
+use tokio::sync::mpsc;
+
+// docker stats takes about as long to answer, sampling more often would only queue them up.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+// What to sample for a pane, the local process or the container behind it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    Pid(u32),
+    Container(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cpu {
+    // CPU time the process used so far, as ps reports it. The percentage comes from two samples.
+    Total { pid: u32, time: Duration },
+    // docker stats works it out itself.
+    Percent(f64),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Sample {
+    pub cpu: Option<Cpu>,
+    pub rss_bytes: Option<u64>,
+}
+
+// Samples every target and sends what it found by pane, leaving out the ones that couldn't be.
+pub async fn sample(
+    targets: Vec<(usize, Target)>,
+    tx: mpsc::UnboundedSender<Vec<(usize, Sample)>>,
+) {
+    let pids = targets
+        .iter()
+        .filter_map(|(_, target)| match target {
+            Target::Pid(pid) => Some(*pid),
+            Target::Container(_) => None,
+        })
+        .collect::<Vec<_>>();
+    let containers = targets
+        .iter()
+        .filter_map(|(_, target)| match target {
+            Target::Container(name) => Some(name.as_str()),
+            Target::Pid(_) => None,
+        })
+        .collect::<Vec<_>>();
+    let by_pid = if pids.is_empty() {
+        HashMap::new()
+    } else {
+        sample_pids(&pids).await
+    };
+    let by_container = if containers.is_empty() {
+        HashMap::new()
+    } else {
+        sample_containers(&containers).await
+    };
+    let _ = tx.send(by_pane(&targets, &by_pid, &by_container));
+}
+
+fn by_pane(
+    targets: &[(usize, Target)],
+    by_pid: &HashMap<u32, Sample>,
+    by_container: &HashMap<String, Sample>,
+) -> Vec<(usize, Sample)> {
+    targets
+        .iter()
+        .filter_map(|(idx, target)| {
+            let sample = match target {
+                Target::Pid(pid) => by_pid.get(pid),
+                Target::Container(name) => by_container.get(name),
+            };
+            sample.map(|sample| (*idx, *sample))
+        })
+        .collect()
+}
+
+// ps exits with an error when some of the processes are gone, the others are still listed.
+#[cfg(not(target_os = "windows"))]
+async fn sample_pids(pids: &[u32]) -> HashMap<u32, Sample> {
+    let pids = pids
+        .iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join(",");
+    match AsyncCommand::new("ps")
+        .arg("-o")
+        .arg("pid=,rss=,time=")
+        .arg("-p")
+        .arg(pids)
+        .output()
+        .await
+    {
+        Ok(output) => parse_ps(&String::from_utf8_lossy(&output.stdout)),
+        Err(_) => HashMap::new(),
+    }
+}
+
+// There is no ps to ask, the footers show dashes.
+#[cfg(target_os = "windows")]
+async fn sample_pids(_pids: &[u32]) -> HashMap<u32, Sample> {
+    HashMap::new()
+}
+
+async fn sample_containers(names: &[&str]) -> HashMap<String, Sample> {
+    match AsyncCommand::new("docker")
+        .arg("stats")
+        .arg("--no-stream")
+        .arg("--format")
+        .arg("{{json .}}")
+        .args(names)
+        .output()
+        .await
+    {
+        Ok(output) => parse_docker_stats(&String::from_utf8_lossy(&output.stdout)),
+        Err(_) => HashMap::new(),
+    }
+}
+
+// e.g. `12345  20480 00:01:02`, the resident size in KiB and the CPU time so far.
+fn parse_ps(output: &str) -> HashMap<u32, Sample> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let rss_kib = fields.next().and_then(|rss| rss.parse::<u64>().ok());
+            let time = fields.next().and_then(parse_cpu_time);
+            Some((
+                pid,
+                Sample {
+                    cpu: time.map(|time| Cpu::Total { pid, time }),
+                    rss_bytes: rss_kib.map(|kib| kib * 1024),
+                },
+            ))
+        })
+        .collect()
+}
+
+// `[[dd-]hh:]mm:ss`, with a fraction of a second on macOS, e.g. `1-02:03:04` or `0:01.25`.
+fn parse_cpu_time(time: &str) -> Option<Duration> {
+    let (days, time) = match time.split_once('-') {
+        Some((days, time)) => (days.parse::<f64>().ok()?, time),
+        None => (0.0, time),
+    };
+    let mut seconds = days * 86400.0;
+    for (part, unit) in time.rsplit(':').zip([1.0, 60.0, 3600.0]) {
+        seconds += part.parse::<f64>().ok()? * unit;
+    }
+    Some(Duration::from_secs_f64(seconds))
+}
+
+// One JSON object per container, e.g. `{"Name":"worker-server1","CPUPerc":"12.50%","MemUsage":"20.5MiB / 7.6GiB",...}`.
+fn parse_docker_stats(output: &str) -> HashMap<String, Sample> {
+    output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter_map(|stats| {
+            let name = stats.get("Name")?.as_str()?.to_string();
+            let cpu = stats
+                .get("CPUPerc")
+                .and_then(Value::as_str)
+                .and_then(|cpu| cpu.trim_end_matches('%').parse().ok())
+                .map(Cpu::Percent);
+            let rss_bytes = stats
+                .get("MemUsage")
+                .and_then(Value::as_str)
+                .and_then(|usage| usage.split('/').next())
+                .and_then(parse_size);
+            Some((name, Sample { cpu, rss_bytes }))
+        })
+        .collect()
+}
+
+// e.g. `20.5MiB` or `1.2GB`, as docker prints them.
+fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let unit_start = size.find(|c: char| c.is_ascii_alphabetic())?;
+    let value = size[..unit_start].parse::<f64>().ok()?;
+    let multiplier = match &size[unit_start..] {
+        "B" => 1.0,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        "kB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        _ => return None,
+    };
+    Some((value * multiplier) as u64)
+}
+
+// What the pane footers show, kept from one sample to the next.
+pub struct Usage {
+    cpu_percent: Vec<Option<f64>>,
+    rss_bytes: Vec<Option<u64>>,
+    // The last CPU time seen for each local process, a restarted one starts over.
+    previous: Vec<Option<(u32, Duration, Instant)>>,
+}
+
+impl Usage {
+    pub fn new(pane_count: usize) -> Self {
+        Usage {
+            cpu_percent: vec![None; pane_count],
+            rss_bytes: vec![None; pane_count],
+            previous: vec![None; pane_count],
+        }
+    }
+
+    // Panes missing from the samples weren't running or couldn't be sampled, they show dashes.
+    pub fn update(&mut self, samples: Vec<(usize, Sample)>, now: Instant) {
+        let mut sampled = vec![None; self.cpu_percent.len()];
+        for (idx, sample) in samples {
+            sampled[idx] = Some(sample);
+        }
+        for (idx, sample) in sampled.into_iter().enumerate() {
+            let sample = sample.unwrap_or_default();
+            self.rss_bytes[idx] = sample.rss_bytes;
+            self.cpu_percent[idx] = match sample.cpu {
+                Some(Cpu::Total { pid, time }) => {
+                    let percent = match self.previous[idx] {
+                        Some((previous_pid, previous_time, at)) if previous_pid == pid => {
+                            let elapsed = now.duration_since(at).as_secs_f64();
+                            (elapsed > 0.0).then(|| {
+                                time.saturating_sub(previous_time).as_secs_f64() / elapsed * 100.0
+                            })
+                        }
+                        _ => None,
+                    };
+                    self.previous[idx] = Some((pid, time, now));
+                    percent
+                }
+                Some(Cpu::Percent(percent)) => {
+                    self.previous[idx] = None;
+                    Some(percent)
+                }
+                None => {
+                    self.previous[idx] = None;
+                    None
+                }
+            };
+        }
+    }
+
+    // e.g. `PID 4242 | up 3m05s | CPU 12.5% | RSS 20.5 MiB`.
+    pub fn footer(&self, idx: usize, pid: Option<u32>, uptime: Option<Duration>) -> String {
+        footer(pid, uptime, self.cpu_percent[idx], self.rss_bytes[idx])
+    }
+}
+
+fn footer(
+    pid: Option<u32>,
+    uptime: Option<Duration>,
+    cpu_percent: Option<f64>,
+    rss_bytes: Option<u64>,
+) -> String {
+    let dash = || "-".to_string();
+    format!(
+        "PID {} | up {} | CPU {} | RSS {}",
+        pid.map_or_else(dash, |pid| pid.to_string()),
+        uptime.map_or_else(dash, format_uptime),
+        cpu_percent.map_or_else(dash, |cpu| format!("{:.1}%", cpu)),
+        rss_bytes.map_or_else(dash, format_bytes),
+    )
+}
+
+// e.g. `45s`, `3m05s`, `2h07m`.
+fn format_uptime(uptime: Duration) -> String {
+    let seconds = uptime.as_secs();
+    match seconds {
+        0..60 => format!("{}s", seconds),
+        60..3600 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sampled_as(cpu: Option<Cpu>, rss_bytes: Option<u64>) -> Sample {
+        Sample { cpu, rss_bytes }
+    }
+
+    #[test]
+    fn footers_show_every_field() {
+        assert_eq!(
+            footer(
+                Some(4242),
+                Some(Duration::from_secs(185)),
+                Some(12.46),
+                Some(20 * 1024 * 1024 + 512 * 1024)
+            ),
+            "PID 4242 | up 3m05s | CPU 12.5% | RSS 20.5 MiB"
+        );
+    }
+
+    #[test]
+    fn unavailable_fields_are_dashes() {
+        assert_eq!(
+            footer(None, None, None, None),
+            "PID - | up - | CPU - | RSS -"
+        );
+        assert_eq!(
+            footer(Some(7), None, Some(0.0), None),
+            "PID 7 | up - | CPU 0.0% | RSS -"
+        );
+    }
+
+    #[test]
+    fn uptimes_use_the_two_largest_units() {
+        for (seconds, formatted) in [
+            (0, "0s"),
+            (59, "59s"),
+            (60, "1m00s"),
+            (3599, "59m59s"),
+            (3600, "1h00m"),
+            (7620, "2h07m"),
+            (90000, "25h00m"),
+        ] {
+            assert_eq!(format_uptime(Duration::from_secs(seconds)), formatted);
+        }
+    }
+
+    #[test]
+    fn sizes_use_binary_units() {
+        for (bytes, formatted) in [
+            (0, "0 B"),
+            (1023, "1023 B"),
+            (1024, "1.0 KiB"),
+            (1536, "1.5 KiB"),
+            (5 * 1024 * 1024, "5.0 MiB"),
+            (3 * 1024 * 1024 * 1024, "3.0 GiB"),
+            (2048 * 1024 * 1024 * 1024, "2048.0 GiB"),
+        ] {
+            assert_eq!(format_bytes(bytes), formatted);
+        }
+    }
+
+    #[test]
+    fn ps_lines_give_rss_and_cpu_time() {
+        let samples = parse_ps("  4242  20480 00:01:02\n 4243 1024 1-02:03:04\ngarbage\n");
+        assert_eq!(samples.len(), 2);
+        assert_eq!(
+            samples[&4242],
+            sampled_as(
+                Some(Cpu::Total {
+                    pid: 4242,
+                    time: Duration::from_secs(62)
+                }),
+                Some(20480 * 1024)
+            )
+        );
+        assert_eq!(
+            samples[&4243].cpu,
+            Some(Cpu::Total {
+                pid: 4243,
+                time: Duration::from_secs(86400 + 2 * 3600 + 3 * 60 + 4)
+            })
+        );
+    }
+
+    #[test]
+    fn cpu_times_in_every_ps_format() {
+        assert_eq!(parse_cpu_time("00:00"), Some(Duration::ZERO));
+        assert_eq!(parse_cpu_time("0:01.25"), Some(Duration::from_millis(1250)));
+        assert_eq!(parse_cpu_time("01:02:03"), Some(Duration::from_secs(3723)));
+        assert_eq!(
+            parse_cpu_time("2-00:00:01"),
+            Some(Duration::from_secs(172801))
+        );
+        assert_eq!(parse_cpu_time("soon"), None);
+    }
+
+    #[test]
+    fn docker_stats_give_cpu_percent_and_memory() {
+        let output = concat!(
+            r#"{"Name":"worker-server1","CPUPerc":"12.50%","MemUsage":"20.5MiB / 7.6GiB"}"#,
+            "\n",
+            r#"{"Name":"load-balancer","CPUPerc":"--","MemUsage":"-- / --"}"#,
+            "\n",
+            "not json\n",
+        );
+        let samples = parse_docker_stats(output);
+        assert_eq!(samples.len(), 2);
+        assert_eq!(
+            samples["worker-server1"],
+            sampled_as(
+                Some(Cpu::Percent(12.5)),
+                Some((20.5 * 1024.0 * 1024.0) as u64)
+            )
+        );
+        assert_eq!(samples["load-balancer"], sampled_as(None, None));
+    }
+
+    #[test]
+    fn docker_sizes_in_binary_and_decimal_units() {
+        assert_eq!(parse_size("512B"), Some(512));
+        assert_eq!(parse_size(" 1.5KiB "), Some(1536));
+        assert_eq!(parse_size("2GiB"), Some(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("1.2GB"), Some(1_200_000_000));
+        assert_eq!(parse_size("3kB"), Some(3000));
+        assert_eq!(parse_size("3TB"), None);
+        assert_eq!(parse_size("--"), None);
+    }
+
+    #[test]
+    fn samples_are_mapped_back_to_their_panes() {
+        let targets = vec![
+            (0, Target::Container("load-balancer".to_string())),
+            (1, Target::Pid(100)),
+            (2, Target::Pid(200)),
+            (3, Target::Pid(300)),
+        ];
+        let by_pid = HashMap::from([
+            (300, sampled_as(None, Some(3))),
+            (100, sampled_as(None, Some(1))),
+            (999, sampled_as(None, Some(9))),
+        ]);
+        let by_container = HashMap::from([
+            (
+                "load-balancer".to_string(),
+                sampled_as(Some(Cpu::Percent(5.0)), None),
+            ),
+            ("other".to_string(), sampled_as(None, None)),
+        ]);
+        assert_eq!(
+            by_pane(&targets, &by_pid, &by_container),
+            [
+                (0, sampled_as(Some(Cpu::Percent(5.0)), None)),
+                (1, sampled_as(None, Some(1))),
+                (3, sampled_as(None, Some(3))),
+            ]
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[tokio::test]
+    async fn the_dashboards_own_process_can_be_sampled() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        sample(vec![(2, Target::Pid(std::process::id()))], tx).await;
+        let samples = rx.recv().await.unwrap();
+        assert_eq!(samples.len(), 1);
+        let (idx, sampled) = samples[0];
+        assert_eq!(idx, 2);
+        assert!(sampled.rss_bytes.is_some_and(|rss| rss > 0));
+        assert!(matches!(sampled.cpu, Some(Cpu::Total { pid, .. }) if pid == std::process::id()));
+    }
+
+    #[test]
+    fn cpu_percent_comes_from_two_samples_of_the_same_process() {
+        let now = Instant::now();
+        let mut usage = Usage::new(2);
+        let total = |pid, millis| {
+            sampled_as(
+                Some(Cpu::Total {
+                    pid,
+                    time: Duration::from_millis(millis),
+                }),
+                Some(2048),
+            )
+        };
+
+        usage.update(vec![(1, total(42, 1000))], now);
+        assert_eq!(
+            usage.footer(1, Some(42), Some(Duration::from_secs(5))),
+            "PID 42 | up 5s | CPU - | RSS 2.0 KiB"
+        );
+        usage.update(vec![(1, total(42, 1500))], now + Duration::from_secs(2));
+        assert_eq!(usage.cpu_percent[1], Some(25.0));
+
+        // A restarted process starts over rather than comparing with the old one.
+        usage.update(vec![(1, total(43, 100))], now + Duration::from_secs(4));
+        assert_eq!(usage.cpu_percent[1], None);
+        usage.update(vec![(1, total(43, 2100))], now + Duration::from_secs(6));
+        assert_eq!(usage.cpu_percent[1], Some(100.0));
+        assert_eq!(usage.footer(0, None, None), "PID - | up - | CPU - | RSS -");
+    }
+
+    #[test]
+    fn panes_missing_from_a_sample_go_back_to_dashes() {
+        let now = Instant::now();
+        let mut usage = Usage::new(1);
+        usage.update(
+            vec![(0, sampled_as(Some(Cpu::Percent(40.0)), Some(1024)))],
+            now,
+        );
+        assert_eq!(
+            usage.footer(0, Some(1), None),
+            "PID 1 | up - | CPU 40.0% | RSS 1.0 KiB"
+        );
+        usage.update(Vec::new(), now + SAMPLE_INTERVAL);
+        assert_eq!(
+            usage.footer(0, Some(1), None),
+            "PID 1 | up - | CPU - | RSS -"
+        );
+    }
+}
@@ -0,0 +1,320 @@
+use std::time::{Duration, Instant};
+
+use ratatui::{
+    style::{Color, Style},
+    text::Line,
+};
+use tokio::process::Command as AsyncCommand;
+use tokio::sync::mpsc;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(1);
+
+// What tells that a component is up.
+#[derive(Clone, Debug)]
+pub enum ReadyCheck {
+    // GET answering 200, the workers' /health and the balancer's /lb/health.
+    Http(String),
+    // Running according to docker, the workers' ports aren't published in compose mode.
+    Container(String),
+}
+
+impl ReadyCheck {
+    async fn passes(&self, client: &reqwest::Client) -> bool {
+        match self {
+            ReadyCheck::Http(url) => client
+                .get(url)
+                .timeout(ATTEMPT_TIMEOUT)
+                .send()
+                .await
+                .is_ok_and(|response| response.status().is_success()),
+            ReadyCheck::Container(name) => AsyncCommand::new("docker")
+                .arg("inspect")
+                .arg("--format")
+                .arg("{{.State.Running}}")
+                .arg(name)
+                .output()
+                .await
+                .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).trim() == "true"),
+        }
+    }
+}
+
+// Sends `idx` once the check passes, checking again every half second until then.
+pub async fn wait_ready(idx: usize, check: ReadyCheck, tx: mpsc::UnboundedSender<usize>) {
+    let client = reqwest::Client::new();
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        if check.passes(&client).await {
+            let _ = tx.send(idx);
+            return;
+        }
+        if tx.is_closed() {
+            return;
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Readiness {
+    Waiting,
+    // How long it took since startup.
+    Ready(Duration),
+    TimedOut,
+    // Failed to start or stopped before it got ready, its pane says why.
+    Exited,
+}
+
+// The checklist shown until every component is ready or the timeout passed.
+pub struct Startup {
+    names: Vec<String>,
+    states: Vec<Readiness>,
+    started: Instant,
+    timeout: Duration,
+}
+
+impl Startup {
+    pub fn new(names: Vec<String>, started: Instant, timeout: Duration) -> Self {
+        Startup {
+            states: vec![Readiness::Waiting; names.len()],
+            names,
+            started,
+            timeout,
+        }
+    }
+
+    pub fn ready(&mut self, idx: usize, now: Instant) {
+        if self.states[idx] == Readiness::Waiting {
+            self.states[idx] = Readiness::Ready(now.duration_since(self.started));
+        }
+    }
+
+    pub fn exited(&mut self, idx: usize) {
+        if self.states[idx] == Readiness::Waiting {
+            self.states[idx] = Readiness::Exited;
+        }
+    }
+
+    // Gives up on everything still waiting once the timeout passed.
+    pub fn update(&mut self, now: Instant) {
+        if now.duration_since(self.started) < self.timeout {
+            return;
+        }
+        for state in self.states.iter_mut() {
+            if *state == Readiness::Waiting {
+                *state = Readiness::TimedOut;
+            }
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        !self.states.contains(&Readiness::Waiting)
+    }
+
+    pub fn timed_out(&self) -> Vec<usize> {
+        (0..self.states.len())
+            .filter(|&idx| self.states[idx] == Readiness::TimedOut)
+            .collect()
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    // e.g. `Worker 1: waiting... 3s`, `Worker 2: ready in 1.2s`.
+    pub fn render(&self, now: Instant) -> Vec<Line<'static>> {
+        let elapsed = now.duration_since(self.started).as_secs();
+        self.names
+            .iter()
+            .zip(&self.states)
+            .map(|(name, state)| match state {
+                Readiness::Waiting => Line::raw(format!("{}: waiting... {}s", name, elapsed)),
+                Readiness::Ready(after) => Line::styled(
+                    format!("{}: ready in {:.1}s", name, after.as_secs_f64()),
+                    Style::default().fg(Color::Green),
+                ),
+                Readiness::TimedOut => Line::styled(
+                    format!("{}: not ready after {}s", name, self.timeout.as_secs()),
+                    Style::default().fg(Color::Red),
+                ),
+                Readiness::Exited => Line::styled(
+                    format!("{}: exited, see its pane", name),
+                    Style::default().fg(Color::Red),
+                ),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    // Answers 503 to the first `failures` requests and 200 afterwards, counting them.
+    async fn mock_component(failures: usize) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/health", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counted = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).await;
+                let status = if counted.fetch_add(1, Ordering::SeqCst) < failures {
+                    "503 Service Unavailable"
+                } else {
+                    "200 OK"
+                };
+                let reply = format!("HTTP/1.1 {status}\r\ncontent-length: 0\r\n\r\n");
+                let _ = stream.write_all(reply.as_bytes()).await;
+            }
+        });
+        (url, requests)
+    }
+
+    fn startup(count: usize, timeout: Duration) -> (Startup, Instant) {
+        let started = Instant::now();
+        let names = (1..=count).map(|n| format!("Worker {n}")).collect();
+        (Startup::new(names, started, timeout), started)
+    }
+
+    fn texts(lines: Vec<Line<'static>>) -> Vec<String> {
+        lines.iter().map(ToString::to_string).collect()
+    }
+
+    #[tokio::test]
+    async fn a_healthy_component_is_reported_on_the_first_poll() {
+        let (url, requests) = mock_component(0).await;
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tokio::spawn(wait_ready(3, ReadyCheck::Http(url), tx));
+        let idx = tokio::time::timeout(Duration::from_millis(400), rx.recv()).await;
+        assert_eq!(idx.unwrap(), Some(3));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn polling_continues_until_the_component_answers_200() {
+        let (url, requests) = mock_component(2).await;
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let waiting = tokio::spawn(wait_ready(1, ReadyCheck::Http(url), tx));
+        let idx = tokio::time::timeout(Duration::from_secs(3), rx.recv()).await;
+        assert_eq!(idx.unwrap(), Some(1));
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+        // Reported once, then the poller is done.
+        waiting.await.unwrap();
+        assert_eq!(rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn an_unreachable_component_is_never_reported_ready() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/health", listener.local_addr().unwrap());
+        drop(listener);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let waiting = tokio::spawn(wait_ready(0, ReadyCheck::Http(url), tx));
+        let idx = tokio::time::timeout(Duration::from_millis(1200), rx.recv()).await;
+        assert!(idx.is_err(), "reported {:?}", idx);
+        assert!(!waiting.is_finished());
+        waiting.abort();
+    }
+
+    #[tokio::test]
+    async fn polling_stops_once_nobody_listens() {
+        let (url, _) = mock_component(usize::MAX).await;
+        let (tx, rx) = mpsc::unbounded_channel();
+        drop(rx);
+        let waiting = tokio::spawn(wait_ready(0, ReadyCheck::Http(url), tx));
+        let finished = tokio::time::timeout(Duration::from_secs(1), waiting).await;
+        assert!(finished.is_ok());
+    }
+
+    #[test]
+    fn startup_is_done_when_every_component_is_ready_or_exited() {
+        let (mut startup, started) = startup(3, Duration::from_secs(30));
+        assert!(!startup.is_done());
+        startup.ready(0, started + Duration::from_millis(1200));
+        startup.exited(2);
+        assert!(!startup.is_done());
+        startup.ready(1, started + Duration::from_secs(2));
+        assert!(startup.is_done());
+        assert!(startup.timed_out().is_empty());
+        assert_eq!(
+            texts(startup.render(started + Duration::from_secs(3))),
+            [
+                "Worker 1: ready in 1.2s",
+                "Worker 2: ready in 2.0s",
+                "Worker 3: exited, see its pane",
+            ]
+        );
+    }
+
+    #[test]
+    fn a_settled_component_keeps_its_first_state() {
+        let (mut startup, started) = startup(2, Duration::from_secs(30));
+        startup.ready(0, started + Duration::from_secs(1));
+        startup.ready(0, started + Duration::from_secs(5));
+        startup.exited(0);
+        startup.exited(1);
+        startup.ready(1, started + Duration::from_secs(2));
+        assert_eq!(
+            texts(startup.render(started)),
+            ["Worker 1: ready in 1.0s", "Worker 2: exited, see its pane"]
+        );
+    }
+
+    #[test]
+    fn components_still_waiting_time_out() {
+        let timeout = Duration::from_secs(10);
+        let (mut startup, started) = startup(3, timeout);
+        startup.ready(1, started + Duration::from_secs(1));
+        startup.update(started + timeout - Duration::from_millis(1));
+        assert!(!startup.is_done());
+        assert_eq!(
+            texts(startup.render(started + Duration::from_secs(4))),
+            [
+                "Worker 1: waiting... 4s",
+                "Worker 2: ready in 1.0s",
+                "Worker 3: waiting... 4s",
+            ]
+        );
+
+        startup.update(started + timeout);
+        assert!(startup.is_done());
+        assert_eq!(startup.timed_out(), [0, 2]);
+        assert_eq!(
+            texts(startup.render(started + timeout)),
+            [
+                "Worker 1: not ready after 10s",
+                "Worker 2: ready in 1.0s",
+                "Worker 3: not ready after 10s",
+            ]
+        );
+        // Late readiness doesn't undo the timeout.
+        startup.ready(0, started + timeout + Duration::from_secs(1));
+        assert_eq!(startup.timed_out(), [0, 2]);
+    }
+
+    #[test]
+    fn failures_are_shown_in_red() {
+        let (mut startup, started) = startup(3, Duration::from_secs(1));
+        startup.ready(0, started);
+        startup.exited(1);
+        startup.update(started + Duration::from_secs(1));
+        let colors = startup
+            .render(started)
+            .iter()
+            .map(|line| line.style.fg)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            colors,
+            [Some(Color::Green), Some(Color::Red), Some(Color::Red)]
+        );
+    }
+}
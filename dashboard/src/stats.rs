@@ -0,0 +1,300 @@
+use std::time::Duration;
+
+use lb_api::{ServerStats, StatsResponse};
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use crate::resources::format_bytes;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+pub const STATS_PATH: &str = "/lb/stats";
+
+pub type StatsResult = Result<StatsResponse, String>;
+
+// Sends the balancer's stats as `parse` makes them out, or why there are none, every second
+// until the UI is gone.
+pub async fn poll_stats<T>(
+    lb_url: String,
+    parse: fn(StatsResponse) -> T,
+    tx: mpsc::UnboundedSender<Result<T, String>>,
+) {
+    let client = reqwest::Client::new();
+    let url = format!("{}{}", lb_url.trim_end_matches('/'), STATS_PATH);
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        let result = fetch(&client, &url, STATS_PATH).await.and_then(|value| {
+            serde_json::from_value::<StatsResponse>(value)
+                .map(parse)
+                .map_err(|e| format!("GET {} returned unexpected JSON: {}", STATS_PATH, e))
+        });
+        if tx.send(result).is_err() {
+            break;
+        }
+    }
+}
+
+async fn fetch(client: &reqwest::Client, url: &str, path: &str) -> Result<Value, String> {
+    let response = client
+        .get(url)
+        .timeout(POLL_INTERVAL)
+        .send()
+        .await
+        .map_err(|e| format!("balancer unreachable: {}", e))?;
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(format!("the balancer doesn't serve GET {}", path));
+    }
+    if !status.is_success() {
+        return Err(format!("GET {} returned HTTP {}", path, status.as_u16()));
+    }
+    response
+        .json::<Value>()
+        .await
+        .map_err(|e| format!("GET {} returned invalid JSON: {}", path, e))
+}
+
+// The latest poll result, values that differ from the snapshot before stand out.
+#[derive(Default)]
+pub struct StatsPane {
+    latest: Option<StatsResult>,
+    previous: Option<StatsResponse>,
+}
+
+impl StatsPane {
+    pub fn update(&mut self, result: StatsResult) {
+        if let Some(Ok(snapshot)) = self.latest.take() {
+            self.previous = Some(snapshot);
+        }
+        self.latest = Some(result);
+    }
+
+    pub fn render(&self) -> Vec<Line<'static>> {
+        let snapshot = match &self.latest {
+            None => return vec![Line::raw("Waiting for the first poll...")],
+            // No stale numbers, they would look like the balancer is fine.
+            Some(Err(e)) => {
+                return vec![
+                    Line::styled(e.clone(), Style::default().fg(Color::Red)),
+                    Line::styled(
+                        "STATS_PANE=false hides this pane",
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ]
+            }
+            Some(Ok(snapshot)) => snapshot,
+        };
+        let previous = self.previous.as_ref();
+        let mut lines = vec![
+            Line::from(vec![
+                Span::raw("Algorithm: "),
+                emphasized(
+                    snapshot.algorithm.clone(),
+                    previous.is_some_and(|p| p.algorithm != snapshot.algorithm),
+                ),
+            ]),
+            Line::styled(
+                format!(
+                    "{:<21} {:<9} {:>5} {:>10} {:>10}",
+                    "Server", "Health", "Conns", "In", "Out"
+                ),
+                Style::default().add_modifier(Modifier::UNDERLINED),
+            ),
+        ];
+        for server in &snapshot.servers {
+            let before =
+                previous.and_then(|p| p.servers.iter().find(|s| s.address == server.address));
+            let changed = |value: fn(&ServerStats) -> String| {
+                before.is_some_and(|before| value(before) != value(server))
+            };
+            let health = |s: &ServerStats| {
+                if s.healthy {
+                    "healthy".to_string()
+                } else {
+                    "unhealthy".to_string()
+                }
+            };
+            let health_style = if server.healthy {
+                Style::default()
+            } else {
+                Style::default().fg(Color::Red)
+            };
+            lines.push(Line::from(vec![
+                Span::raw(format!("{:<21} ", server.address)),
+                Span::styled(format!("{:<9}", health(server)), health_style)
+                    .patch_style(emphasis(changed(health))),
+                Span::raw(" "),
+                emphasized(
+                    format!("{:>5}", server.connections),
+                    changed(|s| s.connections.to_string()),
+                ),
+                Span::raw(" "),
+                emphasized(
+                    format!("{:>10}", format_bytes(server.bytes_in)),
+                    changed(|s| s.bytes_in.to_string()),
+                ),
+                Span::raw(" "),
+                emphasized(
+                    format!("{:>10}", format_bytes(server.bytes_out)),
+                    changed(|s| s.bytes_out.to_string()),
+                ),
+            ]));
+        }
+        lines
+    }
+}
+
+fn emphasis(changed: bool) -> Style {
+    if changed {
+        Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    }
+}
+
+fn emphasized(text: String, changed: bool) -> Span<'static> {
+    Span::styled(text, emphasis(changed))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    // What the balancer serves, two servers of which the second's numbers vary.
+    fn fixture(bytes_in: u64, healthy: bool) -> StatsResponse {
+        serde_json::from_value(json!({
+            "algorithm": "round_robin",
+            "servers": [
+                {"address": "127.0.0.1:3000", "healthy": true, "connections": 2,
+                 "bytes_in": 40, "bytes_out": 2048},
+                {"address": "127.0.0.1:3001", "healthy": healthy, "connections": 0,
+                 "bytes_in": bytes_in, "bytes_out": 0}
+            ]
+        }))
+        .unwrap()
+    }
+
+    fn rendered(pane: &StatsPane) -> Vec<String> {
+        pane.render().iter().map(|line| line.to_string()).collect()
+    }
+
+    // The span showing `text`, so its style can be checked.
+    fn span<'a>(lines: &'a [Line<'static>], text: &str) -> &'a Span<'static> {
+        lines
+            .iter()
+            .flat_map(|line| &line.spans)
+            .find(|span| span.content.trim() == text)
+            .unwrap()
+    }
+
+    #[test]
+    fn snapshots_render_as_a_table() {
+        let mut pane = StatsPane::default();
+        assert_eq!(rendered(&pane), ["Waiting for the first poll..."]);
+
+        pane.update(Ok(fixture(25, false)));
+        assert_eq!(
+            rendered(&pane),
+            [
+                "Algorithm: round_robin",
+                "Server                Health    Conns         In        Out",
+                "127.0.0.1:3000        healthy       2       40 B    2.0 KiB",
+                "127.0.0.1:3001        unhealthy     0       25 B        0 B",
+            ]
+        );
+        let lines = pane.render();
+        assert_eq!(span(&lines, "unhealthy").style.fg, Some(Color::Red));
+        // Nothing to compare the first snapshot with.
+        assert_eq!(span(&lines, "25 B").style, Style::default());
+    }
+
+    #[test]
+    fn changed_values_stand_out() {
+        let mut pane = StatsPane::default();
+        pane.update(Ok(fixture(25, true)));
+        pane.update(Ok(fixture(30, false)));
+
+        let lines = pane.render();
+        assert_eq!(span(&lines, "30 B").style, emphasis(true));
+        assert_eq!(
+            span(&lines, "unhealthy").style,
+            Style::default().fg(Color::Red).patch(emphasis(true))
+        );
+        assert_eq!(span(&lines, "40 B").style, Style::default());
+        assert_eq!(span(&lines, "round_robin").style, Style::default());
+    }
+
+    #[test]
+    fn failed_polls_show_no_stale_numbers() {
+        let mut pane = StatsPane::default();
+        pane.update(Ok(fixture(25, true)));
+        pane.update(Err("balancer unreachable: connection refused".to_string()));
+        assert_eq!(
+            rendered(&pane),
+            [
+                "balancer unreachable: connection refused",
+                "STATS_PANE=false hides this pane",
+            ]
+        );
+
+        // Changes are still measured against the last good snapshot.
+        pane.update(Ok(fixture(26, true)));
+        assert_eq!(span(&pane.render(), "26 B").style, emphasis(true));
+    }
+
+    // Serves `body` as JSON to every request, returning the balancer's URL.
+    async fn mock_balancer(body: String) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).await;
+                let reply = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(reply.as_bytes()).await;
+            }
+        });
+        url
+    }
+
+    async fn first_poll(body: String) -> StatsResult {
+        let lb_url = mock_balancer(body).await;
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let polling = tokio::spawn(poll_stats(lb_url, std::convert::identity, tx));
+        let result = rx.recv().await.unwrap();
+        polling.abort();
+        result
+    }
+
+    #[tokio::test]
+    async fn polls_read_the_balancers_stats_response() {
+        let stats = fixture(25, true);
+        let polled = first_poll(serde_json::to_string(&stats).unwrap()).await;
+        assert_eq!(polled, Ok(stats));
+    }
+
+    #[tokio::test]
+    async fn responses_of_another_shape_are_an_error() {
+        let polled = first_poll(r#"{"algo": "round_robin", "servers": []}"#.to_string()).await;
+        let e = polled.unwrap_err();
+        assert!(
+            e.starts_with("GET /lb/stats returned unexpected JSON: missing field `algorithm`"),
+            "{}",
+            e
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+// A process that ran this long before exiting starts over from the initial backoff.
+const STABLE_AFTER: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProcessState {
+    Running {
+        since: Instant,
+    },
+    Exited {
+        // None when the process was killed by a signal or never started.
+        code: Option<i32>,
+        at: DateTime<Local>,
+        // Only set in auto-restart mode.
+        restart_at: Option<Instant>,
+    },
+}
+
+// Tracks the state of every pane's process and when to restart the exited ones.
+pub struct Supervisor {
+    states: Vec<ProcessState>,
+    backoffs: Vec<Duration>,
+    auto_restart: bool,
+}
+
+impl Supervisor {
+    pub fn new(process_count: usize, auto_restart: bool, now: Instant) -> Self {
+        Supervisor {
+            states: vec![ProcessState::Running { since: now }; process_count],
+            backoffs: vec![INITIAL_BACKOFF; process_count],
+            auto_restart,
+        }
+    }
+
+    pub fn exited(&mut self, idx: usize, code: Option<i32>, now: Instant, at: DateTime<Local>) {
+        if let ProcessState::Running { since } = self.states[idx] {
+            if now.duration_since(since) >= STABLE_AFTER {
+                self.backoffs[idx] = INITIAL_BACKOFF;
+            }
+        }
+        let restart_at = self.auto_restart.then(|| now + self.backoffs[idx]);
+        if self.auto_restart {
+            self.backoffs[idx] = (self.backoffs[idx] * 2).min(MAX_BACKOFF);
+        }
+        self.states[idx] = ProcessState::Exited {
+            code,
+            at,
+            restart_at,
+        };
+    }
+
+    pub fn restarted(&mut self, idx: usize, now: Instant) {
+        self.states[idx] = ProcessState::Running { since: now };
+    }
+
+    // How long the process has been running since it was last started, None once it exited.
+    pub fn uptime(&self, idx: usize, now: Instant) -> Option<Duration> {
+        match self.states[idx] {
+            ProcessState::Running { since } => Some(now.duration_since(since)),
+            ProcessState::Exited { .. } => None,
+        }
+    }
+
+    pub fn is_exited(&self, idx: usize) -> bool {
+        matches!(self.states[idx], ProcessState::Exited { .. })
+    }
+
+    // Exited processes whose automatic restart is due.
+    pub fn due(&self, now: Instant) -> Vec<usize> {
+        self.states
+            .iter()
+            .enumerate()
+            .filter(|(_, state)| {
+                matches!(state, ProcessState::Exited { restart_at: Some(at), .. } if *at <= now)
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    // e.g. `[exited: code 1 at 14:03:12, restarting in 4s]`, None while running.
+    pub fn marker(&self, idx: usize, now: Instant) -> Option<String> {
+        let ProcessState::Exited {
+            code,
+            at,
+            restart_at,
+        } = &self.states[idx]
+        else {
+            return None;
+        };
+        let code = code.map_or("no code".to_string(), |code| format!("code {}", code));
+        let restart = match restart_at {
+            Some(restart_at) => format!(
+                ", restarting in {}s",
+                restart_at.saturating_duration_since(now).as_secs()
+            ),
+            None => String::new(),
+        };
+        Some(format!(
+            "[exited: {} at {}{}]",
+            code,
+            at.format("%H:%M:%S"),
+            restart
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at() -> DateTime<Local> {
+        DateTime::parse_from_rfc3339("2024-05-01T14:03:12+00:00")
+            .unwrap()
+            .with_timezone(&Local)
+    }
+
+    fn marker_time() -> String {
+        at().format("%H:%M:%S").to_string()
+    }
+
+    #[test]
+    fn exits_are_marked_until_restarted() {
+        let start = Instant::now();
+        let mut supervisor = Supervisor::new(2, false, start);
+        assert_eq!(supervisor.marker(0, start), None);
+        assert_eq!(
+            supervisor.uptime(1, start + Duration::from_secs(5)),
+            Some(Duration::from_secs(5))
+        );
+
+        let now = start + Duration::from_secs(5);
+        supervisor.exited(1, Some(1), now, at());
+        assert!(supervisor.is_exited(1) && !supervisor.is_exited(0));
+        assert_eq!(supervisor.uptime(1, now), None);
+        assert_eq!(
+            supervisor.marker(1, now),
+            Some(format!("[exited: code 1 at {}]", marker_time()))
+        );
+        // Without auto-restart only a key press restarts it.
+        assert!(supervisor.due(now + MAX_BACKOFF).is_empty());
+
+        supervisor.restarted(1, now);
+        assert!(!supervisor.is_exited(1));
+        assert_eq!(supervisor.marker(1, now), None);
+    }
+
+    #[test]
+    fn signals_have_no_exit_code() {
+        let start = Instant::now();
+        let mut supervisor = Supervisor::new(1, false, start);
+        supervisor.exited(0, None, start, at());
+        assert_eq!(
+            supervisor.marker(0, start),
+            Some(format!("[exited: no code at {}]", marker_time()))
+        );
+    }
+
+    #[test]
+    fn auto_restarts_back_off_exponentially() {
+        let mut now = Instant::now();
+        let mut supervisor = Supervisor::new(1, true, now);
+
+        let mut delays = Vec::new();
+        for _ in 0..7 {
+            now += Duration::from_secs(1);
+            supervisor.exited(0, Some(1), now, at());
+            let ProcessState::Exited {
+                restart_at: Some(restart_at),
+                ..
+            } = supervisor.states[0]
+            else {
+                panic!("no restart scheduled");
+            };
+            delays.push((restart_at - now).as_secs());
+
+            assert!(supervisor
+                .due(restart_at - Duration::from_millis(1))
+                .is_empty());
+            assert_eq!(supervisor.due(restart_at), [0]);
+            now = restart_at;
+            supervisor.restarted(0, now);
+        }
+        assert_eq!(delays, [1, 2, 4, 8, 16, 30, 30]);
+    }
+
+    #[test]
+    fn a_stable_run_resets_the_backoff() {
+        let mut now = Instant::now();
+        let mut supervisor = Supervisor::new(1, true, now);
+        for _ in 0..3 {
+            supervisor.exited(0, Some(1), now, at());
+            supervisor.restarted(0, now);
+        }
+
+        now += STABLE_AFTER;
+        supervisor.exited(0, Some(1), now, at());
+        assert_eq!(
+            supervisor.marker(0, now),
+            Some(format!(
+                "[exited: code 1 at {}, restarting in 1s]",
+                marker_time()
+            ))
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
+use std::time::Duration;
+
+use client::burst::{self, BurstSettings};
+use client::config::{Targets, WorkTarget};
+use client::requests::{self, RequestType, WorkOverrides};
+use client::retry::RetryPolicy;
+use environment::Topology;
+use reqwest::Url;
+use tokio::sync::mpsc;
+
+// The same amounts of work as the client's short and long work items.
+const SHORT_MULTIPLIER: u64 = 1;
+const LONG_MULTIPLIER: u64 = 10;
+const BURST_COUNT: usize = 10;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// A bit of work for the balancer without running the client.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TestTraffic {
+    Short,
+    Long,
+    Burst,
+}
+
+impl TestTraffic {
+    fn name(self) -> &'static str {
+        match self {
+            TestTraffic::Short => "short work",
+            TestTraffic::Long => "long work",
+            TestTraffic::Burst => "burst",
+        }
+    }
+
+    fn request(self) -> RequestType {
+        let multiplier = match self {
+            TestTraffic::Long => LONG_MULTIPLIER,
+            TestTraffic::Short | TestTraffic::Burst => SHORT_MULTIPLIER,
+        };
+        RequestType::Work {
+            multiplier,
+            overrides: WorkOverrides::default(),
+            target: WorkTarget::LoadBalancer,
+        }
+    }
+}
+
+// Builds and sends requests the way the client does, always through the balancer.
+pub struct TrafficSender {
+    client: Arc<reqwest::Client>,
+    targets: Targets,
+}
+
+impl TrafficSender {
+    pub fn new(load_balancer: Url, topology: &Topology) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("Failed to build the HTTP client");
+        TrafficSender {
+            client: Arc::new(client),
+            targets: Targets {
+                load_balancer,
+                compare: None,
+                workers: topology
+                    .worker_addresses()
+                    .iter()
+                    .filter_map(|worker| format!("http://{}", worker).parse().ok())
+                    .collect(),
+            },
+        }
+    }
+
+    // The outcome goes to the load balancer's pane as one line,
+    // e.g. `[traffic] short work: HTTP 200 worker-1 12ms ...`.
+    pub fn send(&self, traffic: TestTraffic, tx: mpsc::UnboundedSender<(usize, String)>) {
+        let client = self.client.clone();
+        let targets = self.targets.clone();
+        tokio::spawn(async move {
+            let outcome = match traffic {
+                TestTraffic::Short | TestTraffic::Long => {
+                    requests::perform(&client, &targets, traffic.request(), RetryPolicy::NONE)
+                        .await
+                        .to_string()
+                }
+                TestTraffic::Burst => {
+                    // The summary is all that's shown, the single events aren't needed.
+                    let (events_tx, _) = mpsc::channel(1);
+                    let settings = BurstSettings {
+                        count: BURST_COUNT,
+                        multiplier: SHORT_MULTIPLIER,
+                    };
+                    let send = || {
+                        requests::perform(&client, &targets, traffic.request(), RetryPolicy::NONE)
+                    };
+                    burst::run(settings, Arc::new(AtomicUsize::new(0)), send, events_tx)
+                        .await
+                        .to_string()
+                }
+            };
+            let _ = tx.send((0, format!("[traffic] {}: {}", traffic.name(), outcome)));
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use environment::Environment;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    // A balancer answering 200 from worker-1, keeping the head and body of every request.
+    async fn mock_balancer() -> (Url, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let received = received.clone();
+                tokio::spawn(async move {
+                    let mut request = vec![0; 4096];
+                    let read = stream.read(&mut request).await.unwrap_or(0);
+                    received
+                        .lock()
+                        .unwrap()
+                        .push(String::from_utf8_lossy(&request[..read]).to_string());
+                    let _ = stream
+                        .write_all(
+                            b"HTTP/1.1 200 OK\r\nx-served-by: worker-1\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                        )
+                        .await;
+                });
+            }
+        });
+        (url.parse().unwrap(), requests)
+    }
+
+    fn sender(load_balancer: Url) -> TrafficSender {
+        let topology = Topology::new(Environment::Local).with_worker_count(2);
+        TrafficSender::new(load_balancer, &topology)
+    }
+
+    async fn sent(traffic: TestTraffic) -> (String, Vec<String>) {
+        let (url, requests) = mock_balancer().await;
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        sender(url).send(traffic, tx);
+        let (idx, line) = rx.recv().await.unwrap();
+        assert_eq!(idx, 0);
+        let requests = requests.lock().unwrap().clone();
+        (line, requests)
+    }
+
+    #[test]
+    fn traffic_is_work_through_the_balancer() {
+        let sender = sender("http://lb.example.com:8080/".parse().unwrap());
+        for (traffic, body) in [
+            (TestTraffic::Short, r#"{"multiplier":1}"#),
+            (TestTraffic::Long, r#"{"multiplier":10}"#),
+            (TestTraffic::Burst, r#"{"multiplier":1}"#),
+        ] {
+            let req = traffic
+                .request()
+                .build(sender.client.clone(), &sender.targets)
+                .unwrap();
+            assert_eq!(req.method(), reqwest::Method::POST);
+            assert_eq!(req.url().as_str(), "http://lb.example.com:8080/work");
+            assert_eq!(
+                req.body().and_then(|body| body.as_bytes()),
+                Some(body.as_bytes()),
+                "{:?}",
+                traffic
+            );
+        }
+        assert_eq!(sender.targets.workers.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn single_requests_report_their_response() {
+        let (line, requests) = sent(TestTraffic::Long).await;
+
+        assert!(
+            line.starts_with("[traffic] long work: HTTP 200 worker-1 "),
+            "{}",
+            line
+        );
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].starts_with("POST /work HTTP/1.1\r\n"));
+    }
+
+    #[tokio::test]
+    async fn bursts_report_a_summary() {
+        let (line, requests) = sent(TestTraffic::Burst).await;
+
+        assert!(
+            line.starts_with("[traffic] burst: 10 requests in "),
+            "{}",
+            line
+        );
+        assert!(
+            line.ends_with("ms, statuses [200: 10], workers [worker-1: 10]"),
+            "{}",
+            line
+        );
+        assert_eq!(requests.len(), 10);
+    }
+}
@@ -0,0 +1,279 @@
+// Runs the real balancer and workers, built from their crates next to this one, on free ports of
+// this machine. Each process's output goes to a file that's printed when a test fails.
+//
+// The tests are ignored by default, run them with `cargo test -- --ignored`.
+
+use std::fs;
+use std::future::Future;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use lb_api::{StatsResponse, WorkResponse};
+use reqwest::StatusCode;
+use serde_json::Value;
+use tempfile::NamedTempFile;
+
+const BALANCER: &str = "load-balancer";
+const WORKER: &str = "worker-server";
+// How long a process gets to answer its readiness check.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+// Short for the tests not to wait on the balancer noticing a worker come or go.
+pub const HEALTH_CHECK_INTERVAL_MS: u64 = 200;
+
+// Builds a crate's binary once per test run, with the cargo running the tests.
+fn binary(name: &str) -> PathBuf {
+    static BUILT: OnceLock<()> = OnceLock::new();
+    BUILT.get_or_init(|| {
+        for name in [BALANCER, WORKER] {
+            let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+            let status = Command::new(cargo)
+                .arg("build")
+                .arg("--quiet")
+                .arg("--manifest-path")
+                .arg(crate_dir(name).join("Cargo.toml"))
+                .status()
+                .unwrap_or_else(|e| panic!("Failed to run cargo: {}", e));
+            assert!(status.success(), "Failed to build {}", name);
+        }
+    });
+    crate_dir(name).join("target").join("debug").join(name)
+}
+
+fn crate_dir(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("..").join(name)
+}
+
+// A port nothing listens on right now. Another process could take it before ours binds it,
+// unlikely enough for tests.
+pub fn free_port() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind a free port");
+    listener.local_addr().unwrap().port()
+}
+
+// Calls `check` until it returns Some, panicking with `what` after `timeout`.
+pub async fn poll_until<T, F, Fut>(what: &str, timeout: Duration, mut check: F) -> T
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Option<T>>,
+{
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(value) = check().await {
+            return value;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "Timed out after {}ms waiting for {}",
+            timeout.as_millis(),
+            what
+        );
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+// A running binary, killed when dropped. Its output is printed if that happens during a panic,
+// e.g. an assertion that failed.
+pub struct Process {
+    name: String,
+    child: Child,
+    log: NamedTempFile,
+}
+
+impl Process {
+    // Runs the crate's binary with only `env` set, nothing leaks from the shell running the tests.
+    pub fn spawn(binary_name: &str, name: &str, env: &[(&str, String)]) -> Process {
+        let log = NamedTempFile::new().expect("create a log file");
+        let mut command = Command::new(binary(binary_name));
+        command
+            .env_clear()
+            .envs(env.iter().map(|(key, value)| (key, value)))
+            .stdin(Stdio::null())
+            .stdout(log.reopen().expect("open the log file"))
+            .stderr(log.reopen().expect("open the log file"));
+        if let Ok(filter) = std::env::var("RUST_LOG") {
+            command.env("RUST_LOG", filter);
+        }
+        let child = command
+            .spawn()
+            .unwrap_or_else(|e| panic!("Failed to start {}: {}", name, e));
+        Process {
+            name: name.to_string(),
+            child,
+            log,
+        }
+    }
+
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    pub fn kill(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+
+    pub fn logs(&self) -> String {
+        fs::read_to_string(self.log.path()).unwrap_or_default()
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        self.kill();
+        if thread::panicking() {
+            eprintln!("----- {} -----\n{}", self.name, self.logs());
+        }
+    }
+}
+
+pub struct Worker {
+    pub process: Process,
+    pub name: String,
+    pub address: String,
+}
+
+impl Worker {
+    // Waits until the worker answers GET /ready.
+    pub async fn start(name: &str) -> Worker {
+        let port = free_port();
+        let process = Process::spawn(
+            WORKER,
+            name,
+            &[
+                ("PORT", port.to_string()),
+                ("WORKER_NAME", name.to_string()),
+                ("RANDOM_SEED", "1".to_string()),
+            ],
+        );
+        let worker = Worker {
+            process,
+            name: name.to_string(),
+            address: format!("127.0.0.1:{}", port),
+        };
+        let url = worker.url("/ready");
+        poll_until(&format!("{} to be ready", name), STARTUP_TIMEOUT, || {
+            is_ok(&url)
+        })
+        .await;
+        worker
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.address, path)
+    }
+
+    // POST /setup, straight to the worker. Panics unless it's accepted.
+    pub async fn setup(&self, request: Value) {
+        let response = reqwest::Client::new()
+            .post(self.url("/setup"))
+            .json(&request)
+            .send()
+            .await
+            .expect("POST /setup");
+        assert_eq!(
+            response.status(),
+            StatusCode::OK,
+            "{:?}",
+            response.text().await
+        );
+    }
+}
+
+pub struct Balancer {
+    pub process: Process,
+    pub url: String,
+    client: reqwest::Client,
+}
+
+impl Balancer {
+    // Balances over `workers`, with `env` on top of the defaults. Waits until GET /lb/health
+    // answers 200, once the health checks found a worker.
+    pub async fn start(workers: &[&Worker], env: &[(&str, &str)]) -> Balancer {
+        let port = free_port();
+        let backends = workers
+            .iter()
+            .map(|worker| worker.address.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut vars = vec![
+            ("APP_ENVIRONMENT", "local".to_string()),
+            ("PORT", port.to_string()),
+            ("BACKENDS", backends),
+            (
+                "HEALTH_CHECK_INTERVAL_MS",
+                HEALTH_CHECK_INTERVAL_MS.to_string(),
+            ),
+        ];
+        vars.extend(env.iter().map(|(key, value)| (*key, value.to_string())));
+        let balancer = Balancer {
+            process: Process::spawn(BALANCER, BALANCER, &vars),
+            url: format!("http://127.0.0.1:{}", port),
+            client: reqwest::Client::new(),
+        };
+        let url = balancer.url("/lb/health");
+        poll_until("the balancer to be healthy", STARTUP_TIMEOUT, || {
+            is_ok(&url)
+        })
+        .await;
+        balancer
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.url, path)
+    }
+
+    // GET /work through the balancer: the status and, when a worker answered, which one.
+    pub async fn work(&self) -> Result<(StatusCode, Option<String>), reqwest::Error> {
+        let response = self
+            .client
+            .get(self.url("/work"))
+            .header(reqwest::header::ACCEPT, "application/json")
+            .send()
+            .await?;
+        let status = response.status();
+        let worker = response
+            .json::<WorkResponse>()
+            .await
+            .ok()
+            .map(|work| work.worker);
+        Ok((status, worker))
+    }
+
+    // POST /algo. Panics unless the balancer switched.
+    pub async fn set_algorithm(&self, algorithm: &str) {
+        let response = self
+            .client
+            .post(self.url("/algo"))
+            .json(&serde_json::json!({ "algo": algorithm }))
+            .send()
+            .await
+            .expect("POST /algo");
+        assert_eq!(
+            response.status(),
+            StatusCode::OK,
+            "{:?}",
+            response.text().await
+        );
+    }
+
+    pub async fn stats(&self) -> StatsResponse {
+        self.client
+            .get(self.url("/lb/stats"))
+            .send()
+            .await
+            .expect("GET /lb/stats")
+            .json()
+            .await
+            .expect("a stats response")
+    }
+}
+
+async fn is_ok(url: &str) -> Option<()> {
+    let response = reqwest::get(url).await.ok()?;
+    response.status().is_success().then_some(())
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use e2e::{poll_until, Balancer, Worker, HEALTH_CHECK_INTERVAL_MS};
+use reqwest::StatusCode;
+use serde_json::json;
+
+// How many requests each worker answered.
+async fn spread(balancer: &Balancer, requests: usize) -> HashMap<String, usize> {
+    let mut served = HashMap::new();
+    for _ in 0..requests {
+        let (status, worker) = balancer.work().await.expect("GET /work");
+        assert_eq!(status, StatusCode::OK);
+        *served.entry(worker.expect("a worker name")).or_default() += 1;
+    }
+    served
+}
+
+#[tokio::test]
+#[ignore]
+async fn round_robin_spreads_requests_evenly() {
+    let workers = [
+        Worker::start("w1").await,
+        Worker::start("w2").await,
+        Worker::start("w3").await,
+    ];
+    let balancer = Balancer::start(&workers.iter().collect::<Vec<_>>(), &[]).await;
+
+    let served = spread(&balancer, 30).await;
+
+    for worker in &workers {
+        assert_eq!(served.get(&worker.name), Some(&10), "{:?}", served);
+    }
+}
+
+// Requests a little apart, each taking longer at the slow worker than the whole run: round robin
+// hands it every other one, least connections only those that find it idle.
+#[tokio::test]
+#[ignore]
+async fn least_connections_steers_around_a_slow_worker() {
+    let slow = Worker::start("slow").await;
+    let fast = Worker::start("fast").await;
+    slow.setup(json!({ "min_duration": 2000, "max_duration": 2000 }))
+        .await;
+    // Only switched by hand, the balancer would otherwise pick its own.
+    let balancer = Balancer::start(
+        &[&slow, &fast],
+        &[("ALLOWED_ALGORITHMS", "least_connections,round_robin")],
+    )
+    .await;
+    balancer.set_algorithm("least_connections").await;
+
+    let mut requests = tokio::task::JoinSet::new();
+    for _ in 0..20 {
+        let url = balancer.url("/work");
+        requests.spawn(async move {
+            let response = reqwest::Client::new()
+                .get(url)
+                .header(reqwest::header::ACCEPT, "application/json")
+                .send()
+                .await
+                .expect("GET /work");
+            let work = response.json::<lb_api::WorkResponse>().await.unwrap();
+            work.worker
+        });
+        tokio::time::sleep(Duration::from_millis(30)).await;
+    }
+    let served_by_slow = requests
+        .join_all()
+        .await
+        .iter()
+        .filter(|worker| **worker == slow.name)
+        .count();
+
+    assert!(
+        served_by_slow <= 2,
+        "{} of 20 went to the slow worker",
+        served_by_slow
+    );
+}
+
+#[tokio::test]
+#[ignore]
+async fn a_dead_worker_doesnt_take_the_balancer_down() {
+    let mut dead = Worker::start("dead").await;
+    let alive = Worker::start("alive").await;
+    let mut balancer = Balancer::start(&[&dead, &alive], &[]).await;
+
+    dead.process.kill();
+    let mut answered = 0;
+    for _ in 0..10 {
+        if let Ok((StatusCode::OK, worker)) = balancer.work().await {
+            assert_eq!(worker.as_deref(), Some(alive.name.as_str()));
+            answered += 1;
+        }
+    }
+
+    assert!(answered >= 5, "only {} of 10 answered", answered);
+    assert!(balancer.process.is_running());
+    let timeout = Duration::from_millis(HEALTH_CHECK_INTERVAL_MS * 10);
+    poll_until("the dead worker to be unhealthy", timeout, || async {
+        let stats = balancer.stats().await;
+        let server = stats.servers.iter().find(|s| s.address == dead.address)?;
+        (!server.healthy).then_some(())
+    })
+    .await;
+}
+
+#[tokio::test]
+#[ignore]
+async fn worker_setup_shows_through_the_balancer() {
+    let worker = Worker::start("w1").await;
+    let balancer = Balancer::start(&[&worker], &[]).await;
+    assert_eq!(balancer.work().await.unwrap().0, StatusCode::OK);
+
+    worker.setup(json!({ "error_rate": 1.0 })).await;
+    assert_eq!(
+        balancer.work().await.unwrap().0,
+        StatusCode::INTERNAL_SERVER_ERROR
+    );
+
+    worker.setup(json!({ "error_rate": 0.0 })).await;
+    assert_eq!(balancer.work().await.unwrap().0, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore]
+async fn a_worker_marked_not_ready_fails_its_health_checks() {
+    let worker = Worker::start("w1").await;
+    let balancer = Balancer::start(&[&worker], &[]).await;
+    let timeout = Duration::from_millis(HEALTH_CHECK_INTERVAL_MS * 10);
+    let healthy = |healthy: bool| {
+        let balancer = &balancer;
+        async move {
+            let stats = balancer.stats().await;
+            (stats.servers[0].healthy == healthy).then_some(())
+        }
+    };
+    poll_until("the worker to be healthy", timeout, || healthy(true)).await;
+
+    worker.setup(json!({ "ready": false })).await;
+    poll_until("the worker to be unhealthy", timeout, || healthy(false)).await;
+
+    worker.setup(json!({ "ready": "auto" })).await;
+    poll_until("the worker to be healthy again", timeout, || healthy(true)).await;
+}
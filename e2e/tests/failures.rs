@@ -0,0 +1,84 @@
+use std::time::{Duration, Instant};
+
+use e2e::{poll_until, Balancer, Worker};
+use reqwest::StatusCode;
+use serde_json::json;
+
+async fn crash(worker: &Worker, request: serde_json::Value) -> (StatusCode, String) {
+    let response = reqwest::Client::new()
+        .post(worker.url("/crash"))
+        .json(&request)
+        .send()
+        .await
+        .expect("POST /crash");
+    (response.status(), response.text().await.unwrap())
+}
+
+#[tokio::test]
+#[ignore]
+async fn a_crash_exits_the_worker_after_its_delay() {
+    let mut worker = Worker::start("w1").await;
+
+    let requested_at = Instant::now();
+    let answer = crash(&worker, json!({ "confirm": true, "delay_ms": 300 })).await;
+    assert_eq!(answer, (StatusCode::OK, "Crashing in 300ms".to_string()));
+    assert!(worker.process.is_running());
+
+    poll_until("the worker to exit", Duration::from_secs(5), || {
+        let running = worker.process.is_running();
+        async move { (!running).then_some(()) }
+    })
+    .await;
+    assert!(requested_at.elapsed() >= Duration::from_millis(300));
+    assert!(worker.process.logs().contains("Crashing now"));
+}
+
+#[tokio::test]
+#[ignore]
+async fn an_unconfirmed_crash_leaves_the_worker_running() {
+    let mut worker = Worker::start("w1").await;
+
+    let (status, _) = crash(&worker, json!({ "delay_ms": 0 })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(worker.process.is_running());
+}
+
+// GET /work through `balancer`: the status and how reading the body went, within a deadline.
+async fn work_through(balancer: &Balancer) -> (StatusCode, Result<String, reqwest::Error>) {
+    let response = reqwest::Client::new()
+        .get(balancer.url("/work"))
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await
+        .expect("GET /work");
+    (response.status(), response.text().await)
+}
+
+#[tokio::test]
+#[ignore]
+async fn faulty_worker_bodies_fail_the_client_response() {
+    let worker = Worker::start("w1").await;
+    let balancer = Balancer::start(&[&worker], &[]).await;
+
+    for fault in [
+        json!({ "allow_overrides": true, "truncate_body_at_bytes": 10 }),
+        json!({ "allow_overrides": true, "drop_after_headers": true }),
+    ] {
+        worker.setup(fault.clone()).await;
+
+        // The head was forwarded before the worker failed the body.
+        let (status, body) = work_through(&balancer).await;
+        assert_eq!(status, StatusCode::OK, "{}", fault);
+        let error = body.expect_err("a complete body");
+        assert!(!error.is_timeout(), "{}: {:?}", fault, error);
+        assert!(
+            error.is_body() || error.is_decode(),
+            "{}: {:?}",
+            fault,
+            error
+        );
+    }
+    assert!(worker.process.logs().contains("Injected fault"));
+}
@@ -0,0 +1,61 @@
+use e2e::{Balancer, Worker};
+use reqwest::StatusCode;
+use serde_json::Value;
+
+// What the worker's /echo saw of a request sent through the balancer.
+async fn echoed(balancer: &Balancer, request: reqwest::RequestBuilder) -> Value {
+    let response = request.send().await.expect("POST /echo");
+    assert_eq!(response.status(), StatusCode::OK, "{:?}", balancer.url);
+    response.json().await.expect("an echo")
+}
+
+#[tokio::test]
+#[ignore]
+async fn requests_reach_the_worker_as_sent() {
+    let worker = Worker::start("w1").await;
+    let balancer = Balancer::start(&[&worker], &[]).await;
+    let body = (0..=255u8).collect::<Vec<_>>();
+
+    let echo = echoed(
+        &balancer,
+        reqwest::Client::new()
+            .post(balancer.url("/echo?a=1&b=two"))
+            .header("x-custom", "value")
+            .header("x-many", "one")
+            .header("x-many", "two")
+            .body(body.clone()),
+    )
+    .await;
+
+    assert_eq!(echo["method"], "POST");
+    assert_eq!(echo["path"], "/echo");
+    assert_eq!(echo["query"], "a=1&b=two");
+    assert_eq!(echo["headers"]["x-custom"], "value");
+    assert_eq!(echo["headers"]["x-many"], "one, two");
+    assert_eq!(echo["headers"]["content-length"], "256");
+    assert_eq!(echo["body_encoding"], "base64");
+    assert_eq!(echo["body_bytes"], 256);
+    assert_eq!(echo["truncated"], false);
+    let peer = echo["peer_addr"].as_str().unwrap();
+    assert!(peer.starts_with("127.0.0.1:"), "{}", peer);
+}
+
+#[tokio::test]
+#[ignore]
+async fn text_bodies_reach_the_worker_unchanged() {
+    let worker = Worker::start("w1").await;
+    let balancer = Balancer::start(&[&worker], &[]).await;
+    let body = "ünïcödé\r\nand lines\n".repeat(1000);
+
+    let echo = echoed(
+        &balancer,
+        reqwest::Client::new()
+            .post(balancer.url("/echo"))
+            .body(body.clone()),
+    )
+    .await;
+
+    assert_eq!(echo["body"], body);
+    assert_eq!(echo["body_encoding"], "utf-8");
+    assert_eq!(echo["body_bytes"], body.len());
+}
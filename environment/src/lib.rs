@@ -1,25 +1,197 @@
+mod topology;
+
 use std::env;
+use std::fmt;
+use std::net::{Ipv4Addr, SocketAddr};
+
+pub use topology::{Topology, WorkerAddress, DEFAULT_WORKER_BASE_PORT, DEFAULT_WORKER_COUNT};
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Environment {
     Local,
     DockerCompose,
+    Kubernetes,
 }
 
 const LOCAL: &str = "local";
 const DOCKER_COMPOSE: &str = "docker-compose";
+const KUBERNETES: &str = "kubernetes";
+
+#[derive(Debug, PartialEq)]
+pub enum EnvironmentError {
+    // APP_ENVIRONMENT set to something else than one of the environments.
+    Invalid(String),
+    // A variable describing the topology that doesn't parse.
+    InvalidVariable {
+        variable: &'static str,
+        value: String,
+    },
+    // More workers than there are ports from the base port up.
+    PortsOutOfRange {
+        worker_count: usize,
+        worker_base_port: u16,
+    },
+}
+
+impl fmt::Display for EnvironmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvironmentError::Invalid(value) => write!(
+                f,
+                "Invalid environment {}. Valid values are '{}', '{}' or '{}'",
+                value, LOCAL, DOCKER_COMPOSE, KUBERNETES
+            ),
+            EnvironmentError::InvalidVariable { variable, value } => {
+                write!(f, "Invalid {} {}", variable, value)
+            }
+            EnvironmentError::PortsOutOfRange {
+                worker_count,
+                worker_base_port,
+            } => write!(
+                f,
+                "{} workers from port {} on would go past port {}",
+                worker_count,
+                worker_base_port,
+                u16::MAX
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EnvironmentError {}
+
+impl TryFrom<&str> for Environment {
+    type Error = EnvironmentError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            LOCAL => Ok(Environment::Local),
+            DOCKER_COMPOSE => Ok(Environment::DockerCompose),
+            KUBERNETES => Ok(Environment::Kubernetes),
+            _ => Err(EnvironmentError::Invalid(value.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Environment::Local => LOCAL,
+            Environment::DockerCompose => DOCKER_COMPOSE,
+            Environment::Kubernetes => KUBERNETES,
+        };
+        write!(f, "{}", name)
+    }
+}
 
 impl Environment {
-    pub fn from_env() -> Self {
+    // APP_ENVIRONMENT, local when it isn't set.
+    pub fn from_env() -> Result<Self, EnvironmentError> {
         match env::var("APP_ENVIRONMENT") {
-            Ok(environment) => match environment.as_str() {
-                LOCAL => Environment::Local,
-                DOCKER_COMPOSE => Environment::DockerCompose,
-                _ => panic!(
-                    "Invalid environment {}. Valid values are '{}' or '{}'",
-                    environment, LOCAL, DOCKER_COMPOSE
-                ),
-            },
-            Err(_) => Environment::Local,
+            Ok(environment) => Environment::try_from(environment.as_str()),
+            Err(_) => Ok(Environment::Local),
+        }
+    }
+
+    pub fn is_containerized(&self) -> bool {
+        match self {
+            Environment::Local => false,
+            Environment::DockerCompose | Environment::Kubernetes => true,
+        }
+    }
+
+    // Locally only this machine can connect, in a container the other services have to.
+    pub fn bind_address(&self, port: u16) -> SocketAddr {
+        if self.is_containerized() {
+            SocketAddr::from((Ipv4Addr::UNSPECIFIED, port))
+        } else {
+            SocketAddr::from((Ipv4Addr::LOCALHOST, port))
+        }
+    }
+}
+
+// Environment variables are the process's, tests setting them take turns.
+#[cfg(test)]
+static ENV: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+// Runs `f` with only `vars` of the variables this crate reads set.
+#[cfg(test)]
+fn with_env<T>(vars: &[(&str, &str)], f: impl FnOnce() -> T) -> T {
+    const READ: [&str; 4] = [
+        "APP_ENVIRONMENT",
+        "WORKER_COUNT",
+        "WORKER_BASE_PORT",
+        "BACKENDS",
+    ];
+
+    let _env = ENV
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    for key in READ {
+        env::remove_var(key);
+    }
+    for (key, value) in vars {
+        env::set_var(key, value);
+    }
+    let result = f();
+    for key in READ {
+        env::remove_var(key);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_env(value: Option<&str>) -> Result<Environment, EnvironmentError> {
+        let vars = value.map(|value| ("APP_ENVIRONMENT", value));
+        with_env(vars.as_slice(), Environment::from_env)
+    }
+
+    #[test]
+    fn unset_is_local() {
+        assert_eq!(from_env(None), Ok(Environment::Local));
+    }
+
+    #[test]
+    fn every_environment_parses_back_from_its_name() {
+        for (value, environment) in [
+            ("local", Environment::Local),
+            ("docker-compose", Environment::DockerCompose),
+            ("kubernetes", Environment::Kubernetes),
+        ] {
+            assert_eq!(from_env(Some(value)), Ok(environment));
+            assert_eq!(environment.to_string(), value);
+        }
+    }
+
+    #[test]
+    fn anything_else_is_an_error_instead_of_a_panic() {
+        for value in ["", "Local", " local", "docker_compose", "k8s", "production"] {
+            assert_eq!(
+                from_env(Some(value)),
+                Err(EnvironmentError::Invalid(value.to_string())),
+                "{:?}",
+                value
+            );
+        }
+        assert_eq!(
+            from_env(Some("k8s")).unwrap_err().to_string(),
+            "Invalid environment k8s. Valid values are 'local', 'docker-compose' or 'kubernetes'"
+        );
+    }
+
+    #[test]
+    fn only_containers_bind_every_interface() {
+        assert!(!Environment::Local.is_containerized());
+        assert_eq!(
+            Environment::Local.bind_address(3000),
+            "127.0.0.1:3000".parse().unwrap()
+        );
+        for environment in [Environment::DockerCompose, Environment::Kubernetes] {
+            assert!(environment.is_containerized());
+            assert_eq!(environment.bind_address(80), "0.0.0.0:80".parse().unwrap());
         }
     }
 }
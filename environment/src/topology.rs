@@ -0,0 +1,326 @@
+use std::env;
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use crate::{Environment, EnvironmentError};
+
+pub const DEFAULT_WORKER_COUNT: usize = 3;
+pub const DEFAULT_WORKER_BASE_PORT: u16 = 3000;
+
+// Where a worker listens, e.g. `127.0.0.1:3001` or `worker-server2:3001`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkerAddress {
+    pub host: String,
+    pub port: u16,
+}
+
+impl WorkerAddress {
+    // Service names are resolved by compose's or the cluster's DNS.
+    pub fn resolve(&self) -> io::Result<SocketAddr> {
+        (self.host.as_str(), self.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Failed to resolve hostname '{}'", self.host),
+                )
+            })
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        let (host, port) = value.trim().rsplit_once(':')?;
+        Some(WorkerAddress {
+            host: host.to_string(),
+            port: port.parse().ok()?,
+        })
+        .filter(|address| !address.host.is_empty())
+    }
+}
+
+impl fmt::Display for WorkerAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.host, self.port)
+    }
+}
+
+// Where the balancer and the workers are, the same for every component of one environment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Topology {
+    environment: Environment,
+    worker_count: usize,
+    worker_base_port: u16,
+    // Used as they are instead of the derived addresses.
+    backends: Option<Vec<WorkerAddress>>,
+}
+
+impl Topology {
+    pub fn new(environment: Environment) -> Self {
+        Topology {
+            environment,
+            worker_count: DEFAULT_WORKER_COUNT,
+            worker_base_port: DEFAULT_WORKER_BASE_PORT,
+            backends: None,
+        }
+    }
+
+    // The defaults overridden by:
+    // - WORKER_COUNT, the number of workers
+    // - WORKER_BASE_PORT, the port of the first worker, the next ones count up from it
+    // - BACKENDS, comma separated `host:port` of every worker, e.g. `10.0.0.5:3000,10.0.0.6:3000`
+    pub fn from_env(environment: Environment) -> Result<Self, EnvironmentError> {
+        let mut topology = Topology::new(environment);
+        if let Ok(value) = env::var("WORKER_COUNT") {
+            let count = value.parse::<usize>().ok().filter(|count| *count > 0);
+            topology.worker_count = count.ok_or_else(|| invalid("WORKER_COUNT", &value))?;
+        }
+        if let Ok(value) = env::var("WORKER_BASE_PORT") {
+            let port = value.parse::<u16>().ok().filter(|port| *port > 0);
+            topology.worker_base_port = port.ok_or_else(|| invalid("WORKER_BASE_PORT", &value))?;
+        }
+        if let Ok(value) = env::var("BACKENDS") {
+            let backends = value
+                .split(',')
+                .filter(|backend| !backend.trim().is_empty())
+                .map(WorkerAddress::parse)
+                .collect::<Option<Vec<_>>>()
+                .filter(|backends| !backends.is_empty());
+            topology.backends = Some(backends.ok_or_else(|| invalid("BACKENDS", &value))?);
+        }
+        topology.validated()
+    }
+
+    // Refuses workers that would count up past port 65535, unless BACKENDS lists them.
+    pub fn validated(self) -> Result<Self, EnvironmentError> {
+        let last_port = (self.worker_base_port as usize).saturating_add(self.worker_count - 1);
+        if self.backends.is_none() && last_port > u16::MAX as usize {
+            return Err(EnvironmentError::PortsOutOfRange {
+                worker_count: self.worker_count,
+                worker_base_port: self.worker_base_port,
+            });
+        }
+        Ok(self)
+    }
+
+    pub fn with_worker_count(mut self, worker_count: usize) -> Self {
+        self.worker_count = worker_count.max(1);
+        self
+    }
+
+    pub fn with_worker_base_port(mut self, worker_base_port: u16) -> Self {
+        self.worker_base_port = worker_base_port;
+        self
+    }
+
+    pub fn environment(&self) -> Environment {
+        self.environment
+    }
+
+    pub fn worker_count(&self) -> usize {
+        match &self.backends {
+            Some(backends) => backends.len(),
+            None => self.worker_count,
+        }
+    }
+
+    // Worker N, counted from 0, listens on the base port + N. Locally it is on this machine,
+    // in containers it is the service `worker-server<N + 1>`. Those past port 65535 are left out,
+    // `validated` refuses them.
+    pub fn worker_addresses(&self) -> Vec<WorkerAddress> {
+        if let Some(backends) = &self.backends {
+            return backends.clone();
+        }
+        (0..self.worker_count)
+            .map_while(|i| {
+                let port = u16::try_from(i)
+                    .ok()
+                    .and_then(|i| self.worker_base_port.checked_add(i))?;
+                let host = if self.environment.is_containerized() {
+                    format!("worker-server{}", i + 1)
+                } else {
+                    "127.0.0.1".to_string()
+                };
+                Some(WorkerAddress { host, port })
+            })
+            .collect()
+    }
+
+    // Where clients reach the balancer. Compose publishes its port 80 on this machine,
+    // inside a cluster it is the `load-balancer` service.
+    pub fn load_balancer_url(&self) -> String {
+        match self.environment {
+            Environment::Local | Environment::DockerCompose => "http://127.0.0.1".to_string(),
+            Environment::Kubernetes => "http://load-balancer".to_string(),
+        }
+    }
+}
+
+fn invalid(variable: &'static str, value: &str) -> EnvironmentError {
+    EnvironmentError::InvalidVariable {
+        variable,
+        value: value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::with_env;
+
+    fn from_env(environment: Environment, vars: &[(&str, &str)]) -> Topology {
+        with_env(vars, || Topology::from_env(environment)).unwrap()
+    }
+
+    fn addresses(topology: &Topology) -> Vec<String> {
+        topology
+            .worker_addresses()
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn local_workers_count_up_from_3000_on_this_machine() {
+        let topology = from_env(Environment::Local, &[]);
+
+        assert_eq!(topology.worker_count(), 3);
+        assert_eq!(
+            addresses(&topology),
+            ["127.0.0.1:3000", "127.0.0.1:3001", "127.0.0.1:3002"]
+        );
+        assert_eq!(topology.load_balancer_url(), "http://127.0.0.1");
+    }
+
+    #[test]
+    fn containerized_workers_are_services() {
+        let compose = from_env(Environment::DockerCompose, &[]);
+        let expected = [
+            "worker-server1:3000",
+            "worker-server2:3001",
+            "worker-server3:3002",
+        ];
+        assert_eq!(addresses(&compose), expected);
+        assert_eq!(compose.load_balancer_url(), "http://127.0.0.1");
+
+        let kubernetes = from_env(Environment::Kubernetes, &[]);
+        assert_eq!(addresses(&kubernetes), expected);
+        assert_eq!(kubernetes.load_balancer_url(), "http://load-balancer");
+    }
+
+    #[test]
+    fn the_count_and_base_port_override_the_defaults() {
+        let count = from_env(Environment::Local, &[("WORKER_COUNT", "5")]);
+        assert_eq!(count.worker_count(), 5);
+        assert_eq!(addresses(&count)[4], "127.0.0.1:3004");
+
+        let port = from_env(Environment::DockerCompose, &[("WORKER_BASE_PORT", "8000")]);
+        assert_eq!(
+            addresses(&port),
+            [
+                "worker-server1:8000",
+                "worker-server2:8001",
+                "worker-server3:8002"
+            ]
+        );
+
+        let both = from_env(
+            Environment::Kubernetes,
+            &[("WORKER_COUNT", "1"), ("WORKER_BASE_PORT", "9000")],
+        );
+        assert_eq!(addresses(&both), ["worker-server1:9000"]);
+    }
+
+    #[test]
+    fn backends_replace_the_derived_addresses() {
+        for environment in [
+            Environment::Local,
+            Environment::DockerCompose,
+            Environment::Kubernetes,
+        ] {
+            let topology = from_env(
+                environment,
+                &[
+                    ("BACKENDS", " 10.0.0.5:3000, worker-a:80,"),
+                    ("WORKER_COUNT", "7"),
+                    ("WORKER_BASE_PORT", "65535"),
+                ],
+            );
+            assert_eq!(topology.worker_count(), 2);
+            assert_eq!(addresses(&topology), ["10.0.0.5:3000", "worker-a:80"]);
+        }
+    }
+
+    #[test]
+    fn invalid_values_name_their_variable() {
+        let error = |vars: &[(&str, &str)]| {
+            with_env(vars, || Topology::from_env(Environment::Local))
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(error(&[("WORKER_COUNT", "0")]), "Invalid WORKER_COUNT 0");
+        assert_eq!(
+            error(&[("WORKER_COUNT", "many")]),
+            "Invalid WORKER_COUNT many"
+        );
+        assert_eq!(
+            error(&[("WORKER_BASE_PORT", "0")]),
+            "Invalid WORKER_BASE_PORT 0"
+        );
+        assert_eq!(
+            error(&[("WORKER_BASE_PORT", "70000")]),
+            "Invalid WORKER_BASE_PORT 70000"
+        );
+        for backends in ["", " , ", "10.0.0.5", ":3000", "10.0.0.5:http"] {
+            assert_eq!(
+                error(&[("BACKENDS", backends)]),
+                format!("Invalid BACKENDS {}", backends)
+            );
+        }
+    }
+
+    #[test]
+    fn workers_must_fit_below_the_last_port() {
+        let error = with_env(
+            &[("WORKER_COUNT", "2"), ("WORKER_BASE_PORT", "65535")],
+            || Topology::from_env(Environment::Local),
+        );
+        assert_eq!(
+            error,
+            Err(EnvironmentError::PortsOutOfRange {
+                worker_count: 2,
+                worker_base_port: 65535,
+            })
+        );
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "2 workers from port 65535 on would go past port 65535"
+        );
+
+        let last = from_env(
+            Environment::Local,
+            &[("WORKER_COUNT", "36"), ("WORKER_BASE_PORT", "65500")],
+        );
+        assert_eq!(addresses(&last).last().unwrap(), "127.0.0.1:65535");
+    }
+
+    #[test]
+    fn overrides_past_the_last_port_are_refused_or_left_out() {
+        let topology = Topology::new(Environment::Local)
+            .with_worker_base_port(65534)
+            .with_worker_count(100_000);
+
+        assert_eq!(addresses(&topology), ["127.0.0.1:65534", "127.0.0.1:65535"]);
+        assert!(topology.validated().is_err());
+        let fits = Topology::new(Environment::Local).with_worker_base_port(65533);
+        assert_eq!(fits.clone().validated(), Ok(fits));
+    }
+
+    #[test]
+    fn addresses_resolve_through_dns() {
+        let address = WorkerAddress::parse("localhost:3000").unwrap();
+        assert_eq!(address.resolve().unwrap().port(), 3000);
+        assert!(address.resolve().unwrap().ip().is_loopback());
+    }
+}
@@ -0,0 +1,328 @@
+use serde::{Deserialize, Serialize};
+
+// POST /algo, e.g. `{"algo": "least_connections"}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChangeAlgoRequest {
+    pub algo: String,
+}
+
+// GET /lb/clients, the clients with the most requests in flight first.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ClientsResponse {
+    // None when clients aren't limited.
+    pub max_in_flight: Option<u64>,
+    pub clients: Vec<ClientInFlight>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ClientInFlight {
+    pub ip: String,
+    pub in_flight: u64,
+    // Never refused, whatever it has in flight.
+    pub exempt: bool,
+    pub since_last_request_ms: u64,
+}
+
+// GET /lb/health, answered with 200 while enough workers pass their health checks and 503
+// otherwise or while the balancer drains for shutdown.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HealthResponse {
+    pub status: BalancerHealth,
+    pub healthy: u64,
+    // How many healthy workers the balancer needs to be ready.
+    pub required: u64,
+    // Every worker's state, empty while they are all healthy.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backends: Vec<BackendHealth>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BalancerHealth {
+    Ok,
+    // Some workers are unhealthy, enough others aren't.
+    Degraded,
+    Unavailable,
+    Draining,
+}
+
+impl BalancerHealth {
+    pub fn is_ready(&self) -> bool {
+        matches!(self, BalancerHealth::Ok | BalancerHealth::Degraded)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BackendHealth {
+    pub address: String,
+    pub healthy: bool,
+    pub connections: u64,
+}
+
+// GET /lb/stats.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StatsResponse {
+    pub algorithm: String,
+    pub servers: Vec<ServerStats>,
+    // None without a global in-flight limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue: Option<QueueStats>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ServerStats {
+    pub address: String,
+    pub healthy: bool,
+    pub connections: u64,
+    // Request body bytes forwarded to the server.
+    pub bytes_in: u64,
+    // Response body bytes it sent back.
+    pub bytes_out: u64,
+}
+
+// Requests forwarded at once over every route, and the queues of those waiting for a slot.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QueueStats {
+    pub max_in_flight: u64,
+    pub in_flight: u64,
+    // Most requests a route's queue holds.
+    pub max_depth: u64,
+    pub routes: Vec<RouteQueueStats>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RouteQueueStats {
+    // The path's first segment, e.g. `/work`, or `*` for routes past the ones tracked.
+    pub route: String,
+    pub depth: u64,
+    // Requests that waited and got a slot.
+    pub dequeued: u64,
+    // Over the recent requests that waited, None until one did.
+    pub wait_p95_ms: Option<u64>,
+    pub timeouts: u64,
+    // Turned away with the queue full.
+    pub rejected: u64,
+}
+
+// PUT /lb/algorithms, the algorithms POST /algo and the automatic switch may pick,
+// e.g. `{"algorithms": ["round_robin"]}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AllowedAlgorithmsRequest {
+    pub algorithms: Vec<String>,
+}
+
+// GET and PUT /lb/algorithms, every algorithm the balancer knows.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AlgorithmsResponse {
+    pub current: String,
+    pub algorithms: Vec<AlgorithmInfo>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AlgorithmInfo {
+    pub name: String,
+    pub enabled: bool,
+    pub description: String,
+}
+
+// GET /lb/drain, how far shutdown got. The times are None until it started.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DrainResponse {
+    pub draining: bool,
+    // Client connections still open, the one asking included.
+    pub connections: u64,
+    // Requests forwarded to a worker that hasn't answered yet.
+    pub backend_requests: u64,
+    pub elapsed_ms: Option<u64>,
+    pub remaining_ms: Option<u64>,
+    // How long the drain may take in total.
+    pub deadline_ms: Option<u64>,
+    // What was still open at the deadline, None unless it passed.
+    pub aborted: Option<DrainAborted>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DrainAborted {
+    pub connections: u64,
+    pub backend_requests: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Debug;
+
+    use serde::de::DeserializeOwned;
+    use serde_json::json;
+
+    use super::*;
+
+    fn round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(value: T) {
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(serde_json::from_str::<T>(&json).unwrap(), value, "{}", json);
+    }
+
+    fn servers() -> Vec<ServerStats> {
+        vec![ServerStats {
+            address: "127.0.0.1:8081".to_string(),
+            healthy: true,
+            connections: 3,
+            bytes_in: 100,
+            bytes_out: 2048,
+        }]
+    }
+
+    #[test]
+    fn algorithm_requests_round_trip() {
+        round_trip(ChangeAlgoRequest {
+            algo: "least_connections".to_string(),
+        });
+        round_trip(AllowedAlgorithmsRequest {
+            algorithms: vec!["round_robin".to_string(), "random".to_string()],
+        });
+        assert_eq!(
+            serde_json::from_str::<ChangeAlgoRequest>(r#"{"algo": "random"}"#).unwrap(),
+            ChangeAlgoRequest {
+                algo: "random".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn algorithms_responses_round_trip() {
+        round_trip(AlgorithmsResponse {
+            current: "round_robin".to_string(),
+            algorithms: vec![AlgorithmInfo {
+                name: "round_robin".to_string(),
+                enabled: true,
+                description: "Each server in turn".to_string(),
+            }],
+        });
+    }
+
+    #[test]
+    fn clients_responses_round_trip() {
+        round_trip(ClientsResponse {
+            max_in_flight: Some(8),
+            clients: vec![ClientInFlight {
+                ip: "10.0.0.1".to_string(),
+                in_flight: 8,
+                exempt: false,
+                since_last_request_ms: 12,
+            }],
+        });
+        round_trip(ClientsResponse {
+            max_in_flight: None,
+            clients: vec![],
+        });
+    }
+
+    #[test]
+    fn health_responses_round_trip() {
+        for status in [
+            BalancerHealth::Ok,
+            BalancerHealth::Degraded,
+            BalancerHealth::Unavailable,
+            BalancerHealth::Draining,
+        ] {
+            round_trip(HealthResponse {
+                status,
+                healthy: 1,
+                required: 1,
+                backends: vec![BackendHealth {
+                    address: "127.0.0.1:8081".to_string(),
+                    healthy: false,
+                    connections: 0,
+                }],
+            });
+        }
+    }
+
+    #[test]
+    fn healthy_pools_leave_out_their_backends() {
+        let health = HealthResponse {
+            status: BalancerHealth::Ok,
+            healthy: 2,
+            required: 1,
+            backends: vec![],
+        };
+
+        assert_eq!(
+            serde_json::to_value(&health).unwrap(),
+            json!({"status": "ok", "healthy": 2, "required": 1})
+        );
+        round_trip(health);
+    }
+
+    #[test]
+    fn only_ok_and_degraded_are_ready() {
+        assert!(BalancerHealth::Ok.is_ready());
+        assert!(BalancerHealth::Degraded.is_ready());
+        assert!(!BalancerHealth::Unavailable.is_ready());
+        assert!(!BalancerHealth::Draining.is_ready());
+    }
+
+    #[test]
+    fn stats_responses_round_trip() {
+        round_trip(StatsResponse {
+            algorithm: "random".to_string(),
+            servers: servers(),
+            queue: None,
+        });
+        round_trip(StatsResponse {
+            algorithm: "random".to_string(),
+            servers: servers(),
+            queue: Some(QueueStats {
+                max_in_flight: 16,
+                in_flight: 16,
+                max_depth: 64,
+                routes: vec![RouteQueueStats {
+                    route: "/work".to_string(),
+                    depth: 3,
+                    dequeued: 40,
+                    wait_p95_ms: Some(120),
+                    timeouts: 1,
+                    rejected: 2,
+                }],
+            }),
+        });
+    }
+
+    #[test]
+    fn stats_without_a_queue_leave_it_out() {
+        let stats = StatsResponse {
+            algorithm: "random".to_string(),
+            servers: vec![],
+            queue: None,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&stats).unwrap(),
+            json!({"algorithm": "random", "servers": []})
+        );
+    }
+
+    #[test]
+    fn drain_responses_round_trip() {
+        round_trip(DrainResponse {
+            draining: false,
+            connections: 1,
+            backend_requests: 0,
+            elapsed_ms: None,
+            remaining_ms: None,
+            deadline_ms: None,
+            aborted: None,
+        });
+        round_trip(DrainResponse {
+            draining: true,
+            connections: 3,
+            backend_requests: 2,
+            elapsed_ms: Some(30_000),
+            remaining_ms: Some(0),
+            deadline_ms: Some(30_000),
+            aborted: Some(DrainAborted {
+                connections: 2,
+                backend_requests: 2,
+            }),
+        });
+    }
+}
@@ -0,0 +1,136 @@
+// Older clients sent every number and flag as a string, e.g. `{"multiplier": "2"}`.
+// Both spellings are accepted until they are gone, numbers are always sent as numbers.
+use std::str::FromStr;
+
+use serde::de::{self, DeserializeOwned};
+use serde::{Deserialize, Deserializer};
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Raw<T> {
+    Value(T),
+    Text(String),
+}
+
+impl<T: FromStr> Raw<T> {
+    fn parse<E: de::Error>(self) -> Result<T, E> {
+        match self {
+            Raw::Value(value) => Ok(value),
+            Raw::Text(text) => text.parse().map_err(|_| {
+                E::custom(format!(
+                    "invalid value '{}', expected {}",
+                    text,
+                    std::any::type_name::<T>()
+                ))
+            }),
+        }
+    }
+}
+
+// For `#[serde(default, deserialize_with = "lenient::option")]`, absent and null are None.
+pub fn option<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned + FromStr,
+{
+    Option::<Raw<T>>::deserialize(deserializer)?
+        .map(Raw::parse)
+        .transpose()
+}
+
+// For `#[serde(default, deserialize_with = "lenient::nullable")]`, telling an absent key (None)
+// from an explicit null (Some(None)).
+pub fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned + FromStr,
+{
+    option(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq, Deserialize)]
+    #[serde(default)]
+    struct Fields {
+        #[serde(deserialize_with = "option")]
+        count: Option<u64>,
+        #[serde(deserialize_with = "option")]
+        rate: Option<f64>,
+        #[serde(deserialize_with = "option")]
+        flag: Option<bool>,
+        #[serde(deserialize_with = "nullable")]
+        limit: Option<Option<u64>>,
+    }
+
+    fn fields(json: &str) -> serde_json::Result<Fields> {
+        serde_json::from_str(json)
+    }
+
+    #[test]
+    fn numbers_and_flags_are_taken_as_they_are() {
+        let parsed = fields(r#"{"count": 2, "rate": 0.5, "flag": true, "limit": 10}"#).unwrap();
+
+        assert_eq!(
+            parsed,
+            Fields {
+                count: Some(2),
+                rate: Some(0.5),
+                flag: Some(true),
+                limit: Some(Some(10)),
+            }
+        );
+    }
+
+    #[test]
+    fn legacy_strings_are_parsed() {
+        let parsed =
+            fields(r#"{"count": "2", "rate": "0.5", "flag": "true", "limit": "10"}"#).unwrap();
+
+        assert_eq!(
+            parsed,
+            Fields {
+                count: Some(2),
+                rate: Some(0.5),
+                flag: Some(true),
+                limit: Some(Some(10)),
+            }
+        );
+    }
+
+    #[test]
+    fn absent_and_null_options_are_none() {
+        assert_eq!(fields("{}").unwrap(), Fields::default());
+        let nulls = fields(r#"{"count": null, "rate": null, "flag": null}"#).unwrap();
+        assert_eq!(nulls, Fields::default());
+    }
+
+    #[test]
+    fn a_nullable_null_is_told_from_an_absent_key() {
+        assert_eq!(fields("{}").unwrap().limit, None);
+        assert_eq!(fields(r#"{"limit": null}"#).unwrap().limit, Some(None));
+    }
+
+    #[test]
+    fn strings_that_do_not_parse_are_rejected() {
+        let error = fields(r#"{"count": "two"}"#).unwrap_err().to_string();
+        assert!(
+            error.starts_with("invalid value 'two', expected u64"),
+            "{}",
+            error
+        );
+        assert!(fields(r#"{"count": "-1"}"#).is_err());
+        assert!(fields(r#"{"flag": "yes"}"#).is_err());
+        assert!(fields(r#"{"limit": "none"}"#).is_err());
+    }
+
+    #[test]
+    fn other_types_are_rejected() {
+        assert!(fields(r#"{"count": -1}"#).is_err());
+        assert!(fields(r#"{"count": 1.5}"#).is_err());
+        assert!(fields(r#"{"flag": 1}"#).is_err());
+        assert!(fields(r#"{"rate": [1]}"#).is_err());
+    }
+}
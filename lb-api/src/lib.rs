@@ -0,0 +1,12 @@
+mod balancer;
+pub mod lenient;
+mod worker;
+
+pub use balancer::{
+    AlgorithmInfo, AlgorithmsResponse, AllowedAlgorithmsRequest, BackendHealth, BalancerHealth,
+    ChangeAlgoRequest, ClientInFlight, ClientsResponse, DrainAborted, DrainResponse,
+    HealthResponse, QueueStats, RouteQueueStats, ServerStats, StatsResponse,
+};
+pub use worker::{
+    MemoryStats, Ready, SetupRequest, SetupResponse, WorkRequest, WorkResponse, WorkerStats,
+};
@@ -0,0 +1,518 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::lenient;
+
+// POST /work, the same fields GET /work takes as query parameters.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkRequest {
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub multiplier: Option<u64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub stream: Option<bool>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub tick_ms: Option<u64>,
+}
+
+// What a worker answers to work when JSON is accepted.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WorkResponse {
+    pub worker: String,
+    pub duration_ms: u64,
+    pub work_mode: String,
+    pub base_duration_ms: u64,
+    pub effective_duration_ms: u64,
+    // e.g. `delay_ms=200, status=503`, None when nothing was overridden.
+    pub overrides: Option<String>,
+}
+
+// POST /setup. Absent keys keep the worker's current value, the others replace it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SetupRequest {
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub min_duration: Option<u64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_duration: Option<u64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub error_rate: Option<f64>,
+    // `uniform`, `normal`, `pareto`, `lognormal` or `bimodal`, with the parameters below it needs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_distribution: Option<String>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub mean_ms: Option<f64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub stddev_ms: Option<f64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub scale_ms: Option<f64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub shape: Option<f64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub median_ms: Option<f64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub sigma: Option<f64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub fast_ms: Option<f64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub slow_ms: Option<f64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub slow_probability: Option<f64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub header_delay_ms: Option<u64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub body_chunks: Option<u64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub body_chunk_delay_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub work_mode: Option<String>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub memory_mb: Option<u64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub retain_mb: Option<u64>,
+    #[serde(deserialize_with = "present", skip_serializing_if = "Option::is_none")]
+    pub ready: Option<Ready>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub allow_overrides: Option<bool>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub burst_error_rate: Option<f64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub burst_duration_ms: Option<u64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub burst_interval_ms: Option<u64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub degrade_ms_per_minute: Option<f64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub concurrency_k: Option<f64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub concurrency_threshold: Option<usize>,
+    // Some(None), sent as null, removes the limit.
+    #[serde(
+        deserialize_with = "lenient::nullable",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub truncate_body_at_bytes: Option<Option<u64>>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub drop_after_headers: Option<bool>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub apply_after_ms: Option<u64>,
+    #[serde(
+        deserialize_with = "lenient::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub revert_after_ms: Option<u64>,
+}
+
+// Whether a worker reports itself ready, forced or left to its warmup and hang checks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ready {
+    Auto,
+    Forced(bool),
+}
+
+impl Serialize for Ready {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Ready::Auto => serializer.serialize_str("auto"),
+            Ready::Forced(ready) => serializer.serialize_bool(*ready),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawReady {
+    Forced(bool),
+    Text(String),
+}
+
+// `"auto"` and null are both auto.
+impl<'de> Deserialize<'de> for Ready {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Option::<RawReady>::deserialize(deserializer)? {
+            None => Ok(Ready::Auto),
+            Some(RawReady::Forced(ready)) => Ok(Ready::Forced(ready)),
+            Some(RawReady::Text(text)) if text == "auto" => Ok(Ready::Auto),
+            Some(RawReady::Text(text)) => text
+                .parse()
+                .map(Ready::Forced)
+                .map_err(|_| de::Error::custom(format!("invalid value '{}'", text))),
+        }
+    }
+}
+
+// Only called for keys that are there, so a null isn't taken for an absent key.
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+// What a worker answers to setup when JSON is accepted.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SetupResponse {
+    // `done`, or `scheduled` for a setup applied later.
+    pub status: String,
+    pub apply_after_ms: u64,
+    pub revert_after_ms: Option<u64>,
+    pub cancelled_schedule: Option<String>,
+    // The whole configuration the worker ends up with.
+    pub config: serde_json::Value,
+}
+
+// GET /stats of a worker.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WorkerStats {
+    pub in_flight: u64,
+    pub error_phase: String,
+    pub effective_min_duration: u64,
+    pub effective_max_duration: u64,
+    pub memory: MemoryStats,
+    // Requests and errors per minute by path.
+    pub timeseries: serde_json::Value,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MemoryStats {
+    pub allocated_bytes: u64,
+    pub retained_bytes: u64,
+    pub cap_bytes: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Debug;
+
+    use serde::de::DeserializeOwned;
+    use serde_json::json;
+
+    use super::*;
+
+    fn round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(value: T) {
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(serde_json::from_str::<T>(&json).unwrap(), value, "{}", json);
+    }
+
+    fn setup(json: serde_json::Value) -> SetupRequest {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn work_requests_round_trip() {
+        round_trip(WorkRequest::default());
+        round_trip(WorkRequest {
+            multiplier: Some(3),
+            stream: Some(true),
+            tick_ms: Some(50),
+        });
+    }
+
+    #[test]
+    fn work_requests_send_numbers_and_only_what_is_set() {
+        let request = WorkRequest {
+            multiplier: Some(3),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({"multiplier": 3})
+        );
+        assert_eq!(
+            serde_json::to_string(&WorkRequest::default()).unwrap(),
+            "{}"
+        );
+    }
+
+    #[test]
+    fn legacy_work_requests_are_accepted() {
+        let request: WorkRequest =
+            serde_json::from_str(r#"{"multiplier": "3", "stream": "false", "tick_ms": null}"#)
+                .unwrap();
+
+        assert_eq!(
+            request,
+            WorkRequest {
+                multiplier: Some(3),
+                stream: Some(false),
+                tick_ms: None,
+            }
+        );
+    }
+
+    #[test]
+    fn work_responses_round_trip() {
+        let response = WorkResponse {
+            worker: "worker-1".to_string(),
+            duration_ms: 240,
+            work_mode: "sleep".to_string(),
+            base_duration_ms: 120,
+            effective_duration_ms: 240,
+            overrides: None,
+        };
+        round_trip(response.clone());
+        round_trip(WorkResponse {
+            overrides: Some("delay_ms=200, status=503".to_string()),
+            ..response
+        });
+    }
+
+    #[test]
+    fn setup_requests_round_trip() {
+        round_trip(SetupRequest::default());
+        round_trip(SetupRequest {
+            min_duration: Some(10),
+            max_duration: Some(20),
+            error_rate: Some(0.25),
+            latency_distribution: Some("bimodal".to_string()),
+            mean_ms: Some(15.0),
+            stddev_ms: Some(2.5),
+            scale_ms: Some(5.0),
+            shape: Some(1.5),
+            median_ms: Some(12.0),
+            sigma: Some(0.5),
+            fast_ms: Some(5.0),
+            slow_ms: Some(500.0),
+            slow_probability: Some(0.1),
+            header_delay_ms: Some(30),
+            body_chunks: Some(4),
+            body_chunk_delay_ms: Some(25),
+            work_mode: Some("cpu".to_string()),
+            memory_mb: Some(8),
+            retain_mb: Some(2),
+            ready: Some(Ready::Forced(false)),
+            allow_overrides: Some(true),
+            burst_error_rate: Some(0.9),
+            burst_duration_ms: Some(1000),
+            burst_interval_ms: Some(5000),
+            degrade_ms_per_minute: Some(1.5),
+            concurrency_k: Some(0.2),
+            concurrency_threshold: Some(4),
+            truncate_body_at_bytes: Some(Some(100)),
+            drop_after_headers: Some(false),
+            apply_after_ms: Some(250),
+            revert_after_ms: Some(1000),
+        });
+        round_trip(SetupRequest {
+            ready: Some(Ready::Auto),
+            truncate_body_at_bytes: Some(None),
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn legacy_setup_requests_are_accepted() {
+        let legacy = setup(json!({
+            "min_duration": "10",
+            "max_duration": "20",
+            "error_rate": "0.25",
+            "latency_distribution": "normal",
+            "mean_ms": "15",
+            "stddev_ms": "2.5",
+            "concurrency_threshold": "4",
+            "truncate_body_at_bytes": "100",
+            "allow_overrides": "true",
+            "ready": "false",
+        }));
+
+        assert_eq!(
+            legacy,
+            SetupRequest {
+                min_duration: Some(10),
+                max_duration: Some(20),
+                error_rate: Some(0.25),
+                latency_distribution: Some("normal".to_string()),
+                mean_ms: Some(15.0),
+                stddev_ms: Some(2.5),
+                concurrency_threshold: Some(4),
+                truncate_body_at_bytes: Some(Some(100)),
+                allow_overrides: Some(true),
+                ready: Some(Ready::Forced(false)),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn setup_nulls_keep_or_clear_as_each_field_says() {
+        let nulls = setup(json!({
+            "min_duration": null,
+            "error_rate": null,
+            "truncate_body_at_bytes": null,
+            "ready": null,
+        }));
+
+        assert_eq!(nulls.min_duration, None);
+        assert_eq!(nulls.error_rate, None);
+        assert_eq!(nulls.truncate_body_at_bytes, Some(None));
+        assert_eq!(nulls.ready, Some(Ready::Auto));
+        assert_eq!(setup(json!({})), SetupRequest::default());
+    }
+
+    #[test]
+    fn cleared_limits_are_sent_as_null() {
+        let request = SetupRequest {
+            truncate_body_at_bytes: Some(None),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({"truncate_body_at_bytes": null})
+        );
+    }
+
+    #[test]
+    fn ready_is_a_flag_or_auto() {
+        let ready = |json| setup(json).ready;
+
+        assert_eq!(ready(json!({"ready": true})), Some(Ready::Forced(true)));
+        assert_eq!(ready(json!({"ready": "true"})), Some(Ready::Forced(true)));
+        assert_eq!(ready(json!({"ready": "auto"})), Some(Ready::Auto));
+        assert_eq!(ready(json!({})), None);
+        assert_eq!(serde_json::to_value(Ready::Auto).unwrap(), json!("auto"));
+        assert_eq!(
+            serde_json::to_value(Ready::Forced(false)).unwrap(),
+            json!(false)
+        );
+        assert!(serde_json::from_value::<SetupRequest>(json!({"ready": "maybe"})).is_err());
+    }
+
+    #[test]
+    fn setup_requests_with_bad_values_are_rejected() {
+        for json in [
+            json!({"min_duration": "ten"}),
+            json!({"error_rate": "high"}),
+            json!({"drop_after_headers": 1}),
+            json!({"truncate_body_at_bytes": -1}),
+        ] {
+            assert!(
+                serde_json::from_value::<SetupRequest>(json.clone()).is_err(),
+                "{}",
+                json
+            );
+        }
+    }
+
+    #[test]
+    fn setup_responses_round_trip() {
+        round_trip(SetupResponse {
+            status: "scheduled".to_string(),
+            apply_after_ms: 250,
+            revert_after_ms: Some(1000),
+            cancelled_schedule: Some("min_duration=10".to_string()),
+            config: json!({"min_duration": 10, "max_duration": 20}),
+        });
+        round_trip(SetupResponse {
+            status: "done".to_string(),
+            apply_after_ms: 0,
+            revert_after_ms: None,
+            cancelled_schedule: None,
+            config: json!({}),
+        });
+    }
+
+    #[test]
+    fn worker_stats_round_trip() {
+        round_trip(WorkerStats {
+            in_flight: 2,
+            error_phase: "burst".to_string(),
+            effective_min_duration: 10,
+            effective_max_duration: 20,
+            memory: MemoryStats {
+                allocated_bytes: 1024,
+                retained_bytes: 512,
+                cap_bytes: 4096,
+            },
+            timeseries: json!({"/work": [{"requests": 3, "errors": 1}]}),
+        });
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::process;
+
+use crate::{Source, SECTIONS};
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        error: serde_json::Error,
+    },
+    UnknownSection {
+        path: PathBuf,
+        section: String,
+    },
+    UnknownKey {
+        path: PathBuf,
+        section: String,
+        key: String,
+    },
+    InvalidValue {
+        key: String,
+        value: String,
+        source: Source,
+        reason: String,
+    },
+    // Invalid flags, --help and --version, clap already says what went wrong.
+    Cli(clap::Error),
+}
+
+impl ConfigError {
+    // Like clap's own errors, --help and --version print to stdout and succeed.
+    pub fn exit(self) -> ! {
+        match self {
+            ConfigError::Cli(e) => e.exit(),
+            e => {
+                eprintln!("error: {}", e);
+                process::exit(2);
+            }
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, error } => {
+                write!(
+                    f,
+                    "Failed to read config file {}: {}",
+                    path.display(),
+                    error
+                )
+            }
+            ConfigError::Parse { path, error } => {
+                write!(f, "Invalid config file {}: {}", path.display(), error)
+            }
+            ConfigError::UnknownSection { path, section } => write!(
+                f,
+                "Unknown section '{}' in config file {}. Expected one of {}",
+                section,
+                path.display(),
+                SECTIONS.join(", ")
+            ),
+            ConfigError::UnknownKey { path, section, key } => write!(
+                f,
+                "Unknown key '{}' in the {} section of config file {}",
+                key,
+                section,
+                path.display()
+            ),
+            ConfigError::InvalidValue {
+                key,
+                value,
+                source,
+                reason,
+            } => write!(f, "Invalid {} '{}' from {}: {}", key, value, source, reason),
+            ConfigError::Cli(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
@@ -0,0 +1,74 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+use crate::{ConfigError, Source, SECTIONS};
+
+// One binary's section of a config file, every value as the text a flag would get.
+#[derive(Clone, Debug, Default)]
+pub struct ConfigFile {
+    path: PathBuf,
+    values: BTreeMap<String, String>,
+}
+
+impl ConfigFile {
+    // A file holds an object per binary, e.g. `{"worker": {"min_duration": 50}}`, keyed by
+    // the settings' names. Sections and keys may be left out, null is the same as leaving it out.
+    pub fn load(path: &Path, section: &str) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|error| ConfigError::Read {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        let mut sections: BTreeMap<String, Map<String, Value>> = serde_json::from_str(&text)
+            .map_err(|error| ConfigError::Parse {
+                path: path.to_path_buf(),
+                error,
+            })?;
+        if let Some(unknown) = sections
+            .keys()
+            .find(|name| !SECTIONS.contains(&name.as_str()))
+        {
+            return Err(ConfigError::UnknownSection {
+                path: path.to_path_buf(),
+                section: unknown.clone(),
+            });
+        }
+        let mut values = BTreeMap::new();
+        for (key, value) in sections.remove(section).unwrap_or_default() {
+            let text = match value {
+                Value::Null => continue,
+                Value::String(text) => text,
+                Value::Bool(_) | Value::Number(_) => value.to_string(),
+                Value::Array(_) | Value::Object(_) => {
+                    return Err(ConfigError::InvalidValue {
+                        key,
+                        value: value.to_string(),
+                        source: Source::File(path.to_path_buf()),
+                        reason: "expected a string, a number or a boolean".to_string(),
+                    })
+                }
+            };
+            values.insert(key, text);
+        }
+        Ok(ConfigFile {
+            path: path.to_path_buf(),
+            values,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
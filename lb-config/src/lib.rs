@@ -0,0 +1,426 @@
+mod error;
+mod file;
+
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::path::PathBuf;
+use std::process;
+
+use clap::builder::PossibleValue;
+use clap::error::{ContextKind, ContextValue};
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, Command, Parser};
+
+pub use error::ConfigError;
+pub use file::ConfigFile;
+
+// Shared by every binary, so the file given to the dashboard configures the processes it starts too.
+pub const CONFIG_FILE_ENV: &str = "CONFIG_FILE";
+
+// The binaries' sections of a config file.
+pub const SECTIONS: [&str; 4] = ["load_balancer", "worker", "client", "dashboard"];
+
+const CONFIG: &str = "config";
+const PRINT_CONFIG: &str = "print_config";
+
+// Where a setting's value came from, later ones override earlier ones.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    Default,
+    File(PathBuf),
+    Env(String),
+    Cli(String),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File(path) => write!(f, "config file {}", path.display()),
+            Source::Env(name) => write!(f, "environment variable {}", name),
+            Source::Cli(flag) => write!(f, "command line {}", flag),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Setting {
+    pub key: String,
+    // None for optional settings nothing set.
+    pub value: Option<String>,
+    pub source: Source,
+}
+
+// Every setting of a binary, what --print-config shows.
+#[derive(Clone, Debug, Default)]
+pub struct Effective(pub Vec<Setting>);
+
+impl fmt::Display for Effective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.0.iter().map(|setting| setting.key.len()).max();
+        for setting in &self.0 {
+            let value = setting.value.as_deref().unwrap_or("(unset)");
+            writeln!(
+                f,
+                "{:width$} = {} ({})",
+                setting.key,
+                value,
+                setting.source,
+                width = width.unwrap_or(0)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Loaded<T> {
+    pub config: T,
+    pub effective: Effective,
+    pub print_config: bool,
+}
+
+// Parses the binary's settings from, lowest precedence first, the defaults, the `section` of
+// the config file, environment variables and flags. Exits on errors, and after printing the
+// effective configuration for --print-config.
+pub fn load<T: Parser>(section: &str) -> T {
+    let loaded = try_load_from::<T, _, _>(section, env::args_os()).unwrap_or_else(|e| e.exit());
+    if loaded.print_config {
+        print!("{}", loaded.effective);
+        process::exit(0);
+    }
+    loaded.config
+}
+
+pub fn try_load_from<T, I, A>(section: &str, args: I) -> Result<Loaded<T>, ConfigError>
+where
+    T: Parser,
+    I: IntoIterator<Item = A>,
+    A: Into<OsString> + Clone,
+{
+    let args = args.into_iter().map(Into::into).collect::<Vec<OsString>>();
+    let mut command = T::command()
+        .arg(
+            Arg::new(CONFIG)
+                .long("config")
+                .value_name("FILE")
+                .env(CONFIG_FILE_ENV)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help(format!(
+                    "JSON file whose \"{}\" object sets defaults, environment variables and flags override them",
+                    section
+                )),
+        )
+        .arg(
+            Arg::new(PRINT_CONFIG)
+                .long("print-config")
+                .action(ArgAction::SetTrue)
+                .help("Print every setting with where its value came from, then exit"),
+        );
+    // Built for the lookups, the defaults below go to the one that parses.
+    let mut built = command.clone();
+    built.build();
+
+    // Only what's needed to find the file, the full parse below reports anything else.
+    let first_pass = built
+        .clone()
+        .ignore_errors(true)
+        .try_get_matches_from(&args)
+        .ok();
+    let file = match first_pass
+        .as_ref()
+        .and_then(|matches| matches.get_one::<PathBuf>(CONFIG))
+    {
+        Some(path) => ConfigFile::load(path, section)?,
+        None => ConfigFile::default(),
+    };
+
+    for (key, value) in file.values() {
+        let arg = built
+            .get_arguments()
+            .find(|arg| arg.get_id() == key && is_setting(arg))
+            .ok_or_else(|| ConfigError::UnknownKey {
+                path: file.path().to_path_buf(),
+                section: section.to_string(),
+                key: key.to_string(),
+            })?;
+        check(arg, value, Source::File(file.path().to_path_buf()))?;
+    }
+    // A bad variable is reported as one, unless a flag overrides it anyway.
+    if let Some(matches) = &first_pass {
+        for arg in built.get_arguments().filter(|arg| is_setting(arg)) {
+            let (Some(name), Some(value)) = (arg.get_env(), arg.get_env().and_then(env::var_os))
+            else {
+                continue;
+            };
+            if matches.value_source(arg.get_id().as_str()) != Some(ValueSource::CommandLine) {
+                let source = Source::Env(name.to_string_lossy().to_string());
+                check(arg, &value.to_string_lossy(), source)?;
+            }
+        }
+    }
+
+    // The file's values replace the built-in defaults, so the environment and flags still win.
+    for (key, value) in file.values() {
+        command = command.mut_arg(key, |arg| arg.default_value(value.to_string()));
+    }
+    let matches = command
+        .clone()
+        .try_get_matches_from(&args)
+        .map_err(ConfigError::Cli)?;
+    let config =
+        T::from_arg_matches(&matches).map_err(|e| ConfigError::Cli(e.format(&mut command)))?;
+
+    let settings = built
+        .get_arguments()
+        .filter(|arg| is_setting(arg))
+        .map(|arg| {
+            let key = arg.get_id().as_str();
+            let value = matches.get_raw(key).map(|values| {
+                values
+                    .map(OsStr::to_string_lossy)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            });
+            let source = match matches.value_source(key) {
+                Some(ValueSource::CommandLine) => Source::Cli(flag(arg)),
+                Some(ValueSource::EnvVariable) => Source::Env(
+                    arg.get_env()
+                        .map_or_else(String::new, |name| name.to_string_lossy().to_string()),
+                ),
+                _ if file.get(key).is_some() => Source::File(file.path().to_path_buf()),
+                _ => Source::Default,
+            };
+            Setting {
+                key: key.to_string(),
+                value,
+                source,
+            }
+        })
+        .collect();
+    Ok(Loaded {
+        config,
+        effective: Effective(settings),
+        print_config: matches.get_flag(PRINT_CONFIG),
+    })
+}
+
+// Everything but --help, --version and the options added here.
+fn is_setting(arg: &Arg) -> bool {
+    let id = arg.get_id().as_str();
+    id != CONFIG
+        && id != PRINT_CONFIG
+        && !matches!(arg.get_action(), ArgAction::Help | ArgAction::Version)
+}
+
+fn flag(arg: &Arg) -> String {
+    match arg.get_long() {
+        Some(long) => format!("--{}", long),
+        None => arg.get_id().to_string(),
+    }
+}
+
+// Runs the value through the setting's own parser, on its own so nothing else is checked.
+fn check(arg: &Arg, value: &str, source: Source) -> Result<(), ConfigError> {
+    let probe = Arg::new(arg.get_id().clone())
+        .long("value")
+        .action(ArgAction::Set)
+        .value_parser(arg.get_value_parser().clone());
+    match Command::new("probe")
+        .arg(probe)
+        .try_get_matches_from(["probe".to_string(), format!("--value={}", value)])
+    {
+        Ok(_) => Ok(()),
+        Err(e) => Err(ConfigError::InvalidValue {
+            key: arg.get_id().to_string(),
+            value: value.to_string(),
+            source,
+            reason: reason(&e, arg),
+        }),
+    }
+}
+
+// The part of clap's message that says what's wrong, without the flag it would name.
+fn reason(error: &clap::Error, arg: &Arg) -> String {
+    if let Some(source) = std::error::Error::source(error) {
+        return source.to_string();
+    }
+    let possible = arg.get_possible_values();
+    let possible = possible
+        .iter()
+        .map(PossibleValue::get_name)
+        .collect::<Vec<_>>();
+    if !possible.is_empty() {
+        return format!("expected one of {}", possible.join(", "));
+    }
+    match error.get(ContextKind::ValidValue) {
+        Some(ContextValue::Strings(valid)) => format!("expected one of {}", valid.join(", ")),
+        _ => error.kind().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::Mutex;
+
+    use tempfile::NamedTempFile;
+
+    // Environment variables are the process's, tests setting them take turns.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    #[derive(Parser, Debug)]
+    struct Settings {
+        #[arg(long, env = "LB_CONFIG_TEST_PORT", default_value_t = 80)]
+        port: u16,
+        #[arg(long, env = "LB_CONFIG_TEST_RATE", default_value_t = 0.0)]
+        rate: f64,
+        #[arg(long, env = "LB_CONFIG_TEST_NAME")]
+        name: Option<String>,
+    }
+
+    fn file(json: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(json.as_bytes()).unwrap();
+        file
+    }
+
+    fn load(file: Option<&NamedTempFile>, flags: &[&str]) -> Result<Loaded<Settings>, ConfigError> {
+        let mut args = vec!["test".to_string()];
+        if let Some(file) = file {
+            args.push(format!("--config={}", file.path().display()));
+        }
+        args.extend(flags.iter().map(|flag| flag.to_string()));
+        try_load_from("worker", args)
+    }
+
+    fn source(loaded: &Loaded<Settings>, key: &str) -> Source {
+        let setting = loaded.effective.0.iter().find(|s| s.key == key).unwrap();
+        setting.source.clone()
+    }
+
+    #[test]
+    fn flags_override_the_environment_which_overrides_the_file() {
+        let _env = ENV.lock().unwrap();
+        let file = file(r#"{"worker": {"port": 81, "rate": 0.5}}"#);
+        let path = file.path().to_path_buf();
+
+        let loaded = load(Some(&file), &[]).unwrap();
+        assert_eq!(loaded.config.port, 81);
+        assert_eq!(source(&loaded, "port"), Source::File(path.clone()));
+
+        env::set_var("LB_CONFIG_TEST_PORT", "82");
+        let from_env = load(Some(&file), &[]);
+        let from_flag = load(Some(&file), &["--port", "83"]);
+        env::remove_var("LB_CONFIG_TEST_PORT");
+
+        let loaded = from_env.unwrap();
+        assert_eq!(loaded.config.port, 82);
+        assert_eq!(
+            source(&loaded, "port"),
+            Source::Env("LB_CONFIG_TEST_PORT".to_string())
+        );
+        let loaded = from_flag.unwrap();
+        assert_eq!(loaded.config.port, 83);
+        assert_eq!(source(&loaded, "port"), Source::Cli("--port".to_string()));
+        assert_eq!(loaded.config.rate, 0.5);
+        assert_eq!(source(&loaded, "rate"), Source::File(path));
+    }
+
+    #[test]
+    fn settings_a_file_leaves_out_keep_their_defaults() {
+        let _env = ENV.lock().unwrap();
+        let file = file(r#"{"worker": {"rate": 0.25, "name": null}, "client": {"port": 1}}"#);
+
+        let loaded = load(Some(&file), &[]).unwrap();
+        assert_eq!(loaded.config.port, 80);
+        assert_eq!(loaded.config.rate, 0.25);
+        assert_eq!(loaded.config.name, None);
+        assert_eq!(source(&loaded, "port"), Source::Default);
+        assert_eq!(source(&loaded, "name"), Source::Default);
+
+        let loaded = load(None, &[]).unwrap();
+        assert_eq!((loaded.config.port, loaded.config.rate), (80, 0.0));
+    }
+
+    #[test]
+    fn bad_values_name_the_setting_and_where_they_came_from() {
+        let _env = ENV.lock().unwrap();
+        let file = file(r#"{"worker": {"port": "eighty"}}"#);
+
+        let error = load(Some(&file), &[]).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Invalid port 'eighty' from config file {}: invalid digit found in string",
+                file.path().display()
+            )
+        );
+
+        env::set_var("LB_CONFIG_TEST_RATE", "often");
+        let from_env = load(None, &[]);
+        let overridden = load(None, &["--rate", "0.1"]);
+        env::remove_var("LB_CONFIG_TEST_RATE");
+        assert!(matches!(
+            from_env,
+            Err(ConfigError::InvalidValue { source: Source::Env(ref name), .. }) if name == "LB_CONFIG_TEST_RATE"
+        ));
+        assert_eq!(overridden.unwrap().config.rate, 0.1);
+
+        assert!(matches!(
+            load(None, &["--port", "x"]),
+            Err(ConfigError::Cli(_))
+        ));
+    }
+
+    #[test]
+    fn bad_files_are_reported() {
+        let _env = ENV.lock().unwrap();
+        let unknown_key = file(r#"{"worker": {"prot": 81}}"#);
+        let unknown_section = file(r#"{"workers": {}}"#);
+        let nested = file(r#"{"worker": {"port": [81]}}"#);
+        let malformed = file(r#"{"worker": "#);
+
+        assert!(matches!(
+            load(Some(&unknown_key), &[]),
+            Err(ConfigError::UnknownKey { ref key, .. }) if key == "prot"
+        ));
+        assert!(matches!(
+            load(Some(&unknown_section), &[]),
+            Err(ConfigError::UnknownSection { ref section, .. }) if section == "workers"
+        ));
+        assert!(matches!(
+            load(Some(&nested), &[]),
+            Err(ConfigError::InvalidValue { ref key, .. }) if key == "port"
+        ));
+        assert!(matches!(
+            load(Some(&malformed), &[]),
+            Err(ConfigError::Parse { .. })
+        ));
+        assert!(matches!(
+            load(None, &["--config", "/nonexistent/config.json"]),
+            Err(ConfigError::Read { .. })
+        ));
+    }
+
+    #[test]
+    fn the_effective_config_lines_up_values_and_sources() {
+        let effective = Effective(vec![
+            Setting {
+                key: "port".to_string(),
+                value: Some("81".to_string()),
+                source: Source::Cli("--port".to_string()),
+            },
+            Setting {
+                key: "name".to_string(),
+                value: None,
+                source: Source::Default,
+            },
+        ]);
+        assert_eq!(
+            effective.to_string(),
+            "port = 81 (command line --port)\nname = (unset) (default)\n"
+        );
+    }
+}
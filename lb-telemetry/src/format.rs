@@ -0,0 +1,93 @@
+use std::fmt;
+use std::str::FromStr;
+
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{self as subscriber_fmt, FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Format {
+    // tracing's default, colored on a terminal.
+    #[default]
+    Text,
+    // One object per line, for log collectors.
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err("expected text or json".to_string()),
+        }
+    }
+}
+
+// The format of tracing-subscriber with the service's name and version added to every event.
+pub(crate) struct ServiceFormat {
+    format: Format,
+    service: String,
+    version: String,
+}
+
+impl ServiceFormat {
+    pub(crate) fn new(format: Format, service: &str, version: &str) -> Self {
+        ServiceFormat {
+            format,
+            service: service.to_string(),
+            version: version.to_string(),
+        }
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for ServiceFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut line = String::new();
+        match self.format {
+            // After the event's own fields, e.g. `... Listening service=worker-server version=0.1.0`.
+            Format::Text => {
+                // Formatting into the buffer would otherwise lose the colors.
+                let ansi = writer.has_ansi_escapes();
+                subscriber_fmt::format().with_ansi(ansi).format_event(
+                    ctx,
+                    Writer::new(&mut line),
+                    event,
+                )?;
+                writeln!(
+                    writer,
+                    "{} service={} version={}",
+                    line.trim_end_matches('\n'),
+                    self.service,
+                    self.version
+                )
+            }
+            // First in the object, the rest keeps tracing-subscriber's order.
+            Format::Json => {
+                subscriber_fmt::format()
+                    .json()
+                    .format_event(ctx, Writer::new(&mut line), event)?;
+                let fields = line.strip_prefix('{').ok_or(fmt::Error)?;
+                write!(
+                    writer,
+                    "{{\"service\":{},\"version\":{},{}",
+                    serde_json::to_string(&self.service).map_err(|_| fmt::Error)?,
+                    serde_json::to_string(&self.version).map_err(|_| fmt::Error)?,
+                    fields
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,323 @@
+mod format;
+
+use std::env;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Mutex;
+
+use tracing::{warn, Subscriber};
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+use tracing_subscriber::fmt::format::JsonFields;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+pub use format::Format;
+use format::ServiceFormat;
+
+// What's traced without a valid RUST_LOG.
+const DEFAULT_FILTER: &str = "info";
+
+#[derive(Debug)]
+pub enum TelemetryError {
+    InvalidVariable {
+        variable: &'static str,
+        value: String,
+        reason: String,
+    },
+    File {
+        path: PathBuf,
+        error: io::Error,
+    },
+    // A subscriber was already set.
+    Init(String),
+}
+
+impl fmt::Display for TelemetryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TelemetryError::InvalidVariable {
+                variable,
+                value,
+                reason,
+            } => write!(f, "Invalid {} {}: {}", variable, value, reason),
+            TelemetryError::File { path, error } => {
+                write!(f, "Failed to open {}: {}", path.display(), error)
+            }
+            TelemetryError::Init(e) => write!(f, "Failed to initialize tracing: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TelemetryError {}
+
+// How a service traces, every event carries its name and version.
+#[derive(Clone, Debug)]
+pub struct Telemetry {
+    service: String,
+    version: String,
+    // EnvFilter directives, kept as text since the filter itself can't be cloned.
+    filter: String,
+    // RUST_LOG when it didn't parse, warned about once tracing is up.
+    invalid_filter: Option<String>,
+    format: Format,
+    // Replaces stdout.
+    file: Option<PathBuf>,
+}
+
+impl Telemetry {
+    pub fn new(service: &str, version: &str) -> Self {
+        Telemetry {
+            service: service.to_string(),
+            version: version.to_string(),
+            filter: DEFAULT_FILTER.to_string(),
+            invalid_filter: None,
+            format: Format::Text,
+            file: None,
+        }
+    }
+
+    // The defaults overridden by:
+    // - RUST_LOG, EnvFilter directives, e.g. `info,hyper=warn,[request{path=/work}]=debug`.
+    //   The default is kept when it doesn't parse, a typo shouldn't keep a service from starting
+    // - LOG_FORMAT, `text` or `json`
+    // - TRACE_FILE, a file the events are appended to instead of written to stdout
+    pub fn from_env(service: &str, version: &str) -> Result<Self, TelemetryError> {
+        let mut telemetry = Telemetry::new(service, version);
+        if let Ok(value) = env::var("RUST_LOG") {
+            match EnvFilter::try_new(&value) {
+                Ok(_) => telemetry.filter = value,
+                Err(e) => telemetry.invalid_filter = Some(format!("{} ({})", value, e)),
+            }
+        }
+        if let Ok(value) = env::var("LOG_FORMAT") {
+            telemetry.format = value
+                .parse()
+                .map_err(|e| invalid("LOG_FORMAT", &value, e))?;
+        }
+        telemetry.file = env::var_os("TRACE_FILE").map(PathBuf::from);
+        Ok(telemetry)
+    }
+
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn has_file(&self) -> bool {
+        self.file.is_some()
+    }
+
+    // Also forwards the `log` records of dependencies like reqwest.
+    pub fn init(self) -> Result<(), TelemetryError> {
+        let invalid_filter = self.invalid_filter.clone();
+        self.subscriber()?
+            .try_init()
+            .map_err(|e| TelemetryError::Init(e.to_string()))?;
+        if let Some(filter) = invalid_filter {
+            warn!(
+                "Invalid RUST_LOG {}, tracing {} instead",
+                filter, DEFAULT_FILTER
+            );
+        }
+        Ok(())
+    }
+
+    // What `init` sets as the global default.
+    fn subscriber(self) -> Result<Box<dyn Subscriber + Send + Sync>, TelemetryError> {
+        let event_format = ServiceFormat::new(self.format, &self.service, &self.version);
+        let filter = EnvFilter::new(&self.filter);
+        let builder = tracing_subscriber::fmt().with_max_level(LevelFilter::TRACE);
+        let subscriber: Box<dyn Subscriber + Send + Sync> = match (self.format, self.file) {
+            (Format::Text, None) => {
+                Box::new(builder.event_format(event_format).finish().with(filter))
+            }
+            (Format::Text, Some(path)) => Box::new(
+                builder
+                    .with_ansi(false)
+                    .with_writer(open(path)?)
+                    .event_format(event_format)
+                    .finish()
+                    .with(filter),
+            ),
+            (Format::Json, None) => Box::new(
+                builder
+                    .fmt_fields(JsonFields::new())
+                    .event_format(event_format)
+                    .finish()
+                    .with(filter),
+            ),
+            (Format::Json, Some(path)) => Box::new(
+                builder
+                    .with_writer(open(path)?)
+                    .fmt_fields(JsonFields::new())
+                    .event_format(event_format)
+                    .finish()
+                    .with(filter),
+            ),
+        };
+        Ok(subscriber)
+    }
+}
+
+// Sets up tracing from the environment, exits when it's invalid.
+pub fn init(service: &str, version: &str) {
+    if let Err(e) = Telemetry::from_env(service, version).and_then(Telemetry::init) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+// For terminal UIs, whose stdout is the UI: nothing is traced unless TRACE_FILE is set.
+pub fn init_file(service: &str, version: &str) -> Result<(), TelemetryError> {
+    let telemetry = Telemetry::from_env(service, version)?;
+    if telemetry.has_file() {
+        telemetry.init()?;
+    }
+    Ok(())
+}
+
+fn open(path: PathBuf) -> Result<Mutex<File>, TelemetryError> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map(Mutex::new)
+        .map_err(|error| TelemetryError::File { path, error })
+}
+
+fn invalid(variable: &'static str, value: &str, reason: impl fmt::Display) -> TelemetryError {
+    TelemetryError::InvalidVariable {
+        variable,
+        value: value.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+    use tracing::{debug, info, info_span};
+
+    // Environment variables are the process's, tests setting them take turns.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    fn from_env(vars: &[(&str, &str)]) -> Result<Telemetry, TelemetryError> {
+        const READ: [&str; 3] = ["RUST_LOG", "LOG_FORMAT", "TRACE_FILE"];
+
+        let _env = ENV
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        for (key, value) in vars {
+            env::set_var(key, value);
+        }
+        let telemetry = Telemetry::from_env("svc", "1.2.3");
+        for key in READ {
+            env::remove_var(key);
+        }
+        telemetry
+    }
+
+    // The lines traced while `trace` runs, to a file like TRACE_FILE would have them.
+    fn traced(vars: &[(&str, &str)], trace: impl FnOnce()) -> Vec<String> {
+        let file = NamedTempFile::new().unwrap();
+        let mut vars = vars.to_vec();
+        let path = file.path().to_str().unwrap().to_string();
+        vars.push(("TRACE_FILE", &path));
+        let subscriber = from_env(&vars).unwrap().subscriber().unwrap();
+        tracing::subscriber::with_default(subscriber, trace);
+        let lines = std::fs::read_to_string(file.path()).unwrap();
+        lines.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn defaults_trace_info_as_text_to_stdout() {
+        let telemetry = from_env(&[]).unwrap();
+
+        assert_eq!(telemetry.filter, "info");
+        assert_eq!(telemetry.invalid_filter, None);
+        assert_eq!(telemetry.format, Format::Text);
+        assert!(!telemetry.has_file());
+    }
+
+    #[test]
+    fn span_and_field_directives_are_kept() {
+        let directives = "warn,hyper=info,[request{path=/work}]=debug";
+        let telemetry = from_env(&[("RUST_LOG", directives)]).unwrap();
+
+        assert_eq!(telemetry.filter, directives);
+        assert_eq!(telemetry.invalid_filter, None);
+    }
+
+    #[test]
+    fn an_invalid_filter_falls_back_to_the_default() {
+        let telemetry = from_env(&[("RUST_LOG", "hyper=loud")]).unwrap();
+
+        assert_eq!(telemetry.filter, "info");
+        assert!(telemetry
+            .invalid_filter
+            .unwrap()
+            .starts_with("hyper=loud ("));
+    }
+
+    #[test]
+    fn the_format_is_text_or_json() {
+        let format = |value| from_env(&[("LOG_FORMAT", value)]).map(|t| t.format);
+
+        assert_eq!(format("text").unwrap(), Format::Text);
+        assert_eq!(format("json").unwrap(), Format::Json);
+        assert_eq!(
+            format("yaml").unwrap_err().to_string(),
+            "Invalid LOG_FORMAT yaml: expected text or json"
+        );
+        assert_eq!(
+            Telemetry::new("svc", "1.2.3")
+                .with_format(Format::Json)
+                .format,
+            Format::Json
+        );
+    }
+
+    #[test]
+    fn text_lines_end_with_the_service_and_version() {
+        let lines = traced(&[], || info!(port = 80, "Listening"));
+
+        assert_eq!(lines.len(), 1);
+        assert!(
+            lines[0].ends_with("Listening port=80 service=svc version=1.2.3"),
+            "{}",
+            lines[0]
+        );
+    }
+
+    #[test]
+    fn json_objects_start_with_the_service_and_version() {
+        let lines = traced(&[("LOG_FORMAT", "json")], || info!(port = 80, "Listening"));
+
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with(r#"{"service":"svc","version":"1.2.3","#));
+        let event = serde_json::from_str::<serde_json::Value>(&lines[0]).unwrap();
+        assert_eq!(event["level"], "INFO");
+        assert_eq!(event["fields"]["message"], "Listening");
+        assert_eq!(event["fields"]["port"], 80);
+    }
+
+    #[test]
+    fn events_are_filtered_by_span() {
+        let trace = || {
+            debug!("outside");
+            info_span!("request").in_scope(|| debug!("inside"));
+        };
+
+        let default = traced(&[], trace);
+        assert!(default.is_empty(), "{:?}", default);
+        let in_requests = traced(&[("RUST_LOG", "info,[request]=debug")], trace);
+        assert_eq!(in_requests.len(), 1, "{:?}", in_requests);
+        assert!(in_requests[0].contains("inside"));
+        let invalid = traced(&[("RUST_LOG", "[request=debug")], trace);
+        assert!(invalid.is_empty(), "{:?}", invalid);
+    }
+}
@@ -2,11 +2,13 @@ use std::fmt;
 
 const ROUND_ROBIN: &str = "round_robin";
 const LEAST_CONNECTIONS: &str = "least_connections";
+const MAGLEV: &str = "maglev";
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum BalancingAlgorithm {
     RoundRobin,
     LeastConnections,
+    Maglev,
 }
 
 pub struct ConversionError;
@@ -24,6 +26,7 @@ impl TryFrom<&str> for BalancingAlgorithm {
         match value {
             ROUND_ROBIN => Ok(BalancingAlgorithm::RoundRobin),
             LEAST_CONNECTIONS => Ok(BalancingAlgorithm::LeastConnections),
+            MAGLEV => Ok(BalancingAlgorithm::Maglev),
             _ => Err(ConversionError),
         }
     }
@@ -34,7 +37,105 @@ impl fmt::Display for BalancingAlgorithm {
         let name = match self {
             BalancingAlgorithm::RoundRobin => &ROUND_ROBIN,
             BalancingAlgorithm::LeastConnections => &LEAST_CONNECTIONS,
+            BalancingAlgorithm::Maglev => &MAGLEV,
         };
         write!(f, "{}", name)
     }
 }
+
+impl BalancingAlgorithm {
+    pub const ALL: [BalancingAlgorithm; 3] = [
+        BalancingAlgorithm::RoundRobin,
+        BalancingAlgorithm::LeastConnections,
+        BalancingAlgorithm::Maglev,
+    ];
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            BalancingAlgorithm::RoundRobin => "Every server in turn",
+            BalancingAlgorithm::LeastConnections => {
+                "The server with the fewest connections, ties taken in turn"
+            }
+            BalancingAlgorithm::Maglev => {
+                "The server the request's hash key maps to, kept while the healthy servers don't change"
+            }
+        }
+    }
+}
+
+/// The algorithms that may be picked, by POST /algo or the automatic switch. Never empty.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Algorithms(Vec<BalancingAlgorithm>);
+
+impl Algorithms {
+    pub fn all() -> Self {
+        Algorithms(BalancingAlgorithm::ALL.to_vec())
+    }
+
+    /// Comma separated names, e.g. `round_robin,least_connections`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        Algorithms::from_names(value.split(',').map(str::trim).filter(|n| !n.is_empty()))
+    }
+
+    pub fn from_names<'a>(names: impl Iterator<Item = &'a str>) -> Result<Self, String> {
+        let mut algorithms = Vec::new();
+        for name in names {
+            let algorithm = BalancingAlgorithm::try_from(name).map_err(|_| {
+                format!(
+                    "'{}' is not a balancing algorithm, valid ones are {}",
+                    name,
+                    Algorithms::all()
+                )
+            })?;
+            if !algorithms.contains(&algorithm) {
+                algorithms.push(algorithm);
+            }
+        }
+        if algorithms.is_empty() {
+            return Err("At least one balancing algorithm is required".to_string());
+        }
+        Ok(Algorithms(algorithms))
+    }
+
+    pub fn contains(&self, algorithm: BalancingAlgorithm) -> bool {
+        self.0.contains(&algorithm)
+    }
+
+    pub fn first(&self) -> BalancingAlgorithm {
+        self.0[0]
+    }
+}
+
+// e.g. `round_robin,least_connections`.
+impl fmt::Display for Algorithms {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = self.0.iter().map(ToString::to_string).collect::<Vec<_>>();
+        write!(f, "{}", names.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn algorithm_lists_parse_in_order_without_repeats() {
+        let algorithms = Algorithms::parse(" maglev, round_robin,maglev,").unwrap();
+        assert_eq!(algorithms.to_string(), "maglev,round_robin");
+        assert_eq!(algorithms.first(), BalancingAlgorithm::Maglev);
+        assert!(!algorithms.contains(BalancingAlgorithm::LeastConnections));
+        assert_eq!(
+            Algorithms::all().to_string(),
+            "round_robin,least_connections,maglev"
+        );
+    }
+
+    #[test]
+    fn unknown_or_no_algorithms_are_refused() {
+        assert_eq!(
+            Algorithms::parse("round_robin,random").unwrap_err(),
+            "'random' is not a balancing algorithm, valid ones are round_robin,least_connections,maglev"
+        );
+        assert!(Algorithms::parse(" , ").is_err());
+    }
+}
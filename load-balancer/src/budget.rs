@@ -0,0 +1,170 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use hyper::HeaderMap;
+
+/// Set by clients that give up sooner than the balancer's own timeout.
+pub const REQUEST_TIMEOUT_HEADER: &str = "x-request-timeout-ms";
+// Below this an attempt can't reach a worker and get an answer, it isn't made at all.
+const MIN_ATTEMPT: Duration = Duration::from_millis(10);
+
+/// How long a request may take in total, every attempt at it draws from what's left.
+#[derive(Clone, Copy, Debug)]
+pub struct RequestBudget {
+    // None without a timeout, requests wait for the worker as long as it takes.
+    allotted: Option<Duration>,
+    started: Instant,
+}
+
+impl RequestBudget {
+    pub fn new(allotted: Option<Duration>, started: Instant) -> Self {
+        RequestBudget { allotted, started }
+    }
+
+    /// The configured timeout, shortened by the request's header. Clients can't extend it.
+    pub fn from_headers(
+        headers: &HeaderMap,
+        timeout: Option<Duration>,
+        started: Instant,
+    ) -> Result<Self, String> {
+        let requested = match headers.get(REQUEST_TIMEOUT_HEADER) {
+            Some(value) => {
+                let ms = value
+                    .to_str()
+                    .ok()
+                    .and_then(|value| value.trim().parse::<u64>().ok())
+                    .ok_or_else(|| {
+                        format!("Invalid {} header {:?}", REQUEST_TIMEOUT_HEADER, value)
+                    })?;
+                Some(Duration::from_millis(ms))
+            }
+            None => None,
+        };
+        let allotted = match (timeout, requested) {
+            (Some(timeout), Some(requested)) => Some(timeout.min(requested)),
+            (timeout, requested) => timeout.or(requested),
+        };
+        Ok(RequestBudget::new(allotted, started))
+    }
+
+    pub fn used(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.started)
+    }
+
+    pub fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.allotted
+            .map(|allotted| allotted.saturating_sub(self.used(now)))
+    }
+
+    /// The most the next attempt may take, None when there's no limit.
+    /// Err once too little is left for another attempt.
+    pub fn attempt_timeout(&self, now: Instant) -> Result<Option<Duration>, Exhausted> {
+        match self.remaining(now) {
+            Some(remaining) if remaining < MIN_ATTEMPT => Err(Exhausted(self.spent(now))),
+            remaining => Ok(remaining),
+        }
+    }
+
+    /// What was used of the budget by `now`, for the logs.
+    pub fn spent(&self, now: Instant) -> Spent {
+        Spent {
+            used: self.used(now),
+            allotted: self.allotted,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spent {
+    used: Duration,
+    allotted: Option<Duration>,
+}
+
+// e.g. `120ms of 500ms` or `120ms` without a limit.
+impl fmt::Display for Spent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}ms", self.used.as_millis())?;
+        if let Some(allotted) = self.allotted {
+            write!(f, " of {}ms", allotted.as_millis())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Exhausted(Spent);
+
+impl fmt::Display for Exhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Request budget exhausted after {}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    fn budget(header: Option<&str>, timeout: Option<Duration>, started: Instant) -> RequestBudget {
+        let mut headers = HeaderMap::new();
+        if let Some(value) = header {
+            headers.insert(REQUEST_TIMEOUT_HEADER, value.parse().unwrap());
+        }
+        RequestBudget::from_headers(&headers, timeout, started).unwrap()
+    }
+
+    #[test]
+    fn the_shorter_of_the_header_and_the_config_wins() {
+        let now = Instant::now();
+        let remaining = |header, timeout| budget(header, timeout, now).remaining(now);
+
+        assert_eq!(remaining(Some("200"), Some(500 * MS)), Some(200 * MS));
+        assert_eq!(remaining(Some("900"), Some(500 * MS)), Some(500 * MS));
+        assert_eq!(remaining(Some("200"), None), Some(200 * MS));
+        assert_eq!(remaining(None, Some(500 * MS)), Some(500 * MS));
+        assert_eq!(remaining(None, None), None);
+    }
+
+    #[test]
+    fn invalid_headers_are_refused() {
+        let mut headers = HeaderMap::new();
+        headers.insert(REQUEST_TIMEOUT_HEADER, "soon".parse().unwrap());
+        assert!(RequestBudget::from_headers(&headers, None, Instant::now()).is_err());
+    }
+
+    #[test]
+    fn what_remains_saturates_at_zero() {
+        let started = Instant::now();
+        let budget = budget(None, Some(100 * MS), started);
+
+        assert_eq!(budget.remaining(started + 40 * MS), Some(60 * MS));
+        assert_eq!(budget.remaining(started + 250 * MS), Some(Duration::ZERO));
+        assert_eq!(budget.used(started + 250 * MS), 250 * MS);
+        // A clock read before the budget started hasn't used any of it.
+        assert_eq!(budget.used(started - 5 * MS), Duration::ZERO);
+    }
+
+    #[test]
+    fn no_attempt_is_made_below_the_minimum() {
+        let started = Instant::now();
+        let budget = budget(None, Some(100 * MS), started);
+
+        assert_eq!(
+            budget.attempt_timeout(started + 90 * MS).unwrap(),
+            Some(MIN_ATTEMPT)
+        );
+        let exhausted = budget.attempt_timeout(started + 91 * MS).unwrap_err();
+        assert_eq!(
+            exhausted.to_string(),
+            "Request budget exhausted after 91ms of 100ms"
+        );
+
+        let unlimited = RequestBudget::new(None, started);
+        assert_eq!(
+            unlimited.attempt_timeout(started + 3_600_000 * MS).unwrap(),
+            None
+        );
+        assert_eq!(unlimited.spent(started + 120 * MS).to_string(), "120ms");
+    }
+}
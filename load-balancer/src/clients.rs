@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use ipnet::IpNet;
+use lb_api::ClientInFlight;
+
+// Clients without requests in flight are forgotten after this long.
+const IDLE_EXPIRY: Duration = Duration::from_secs(300);
+// How often forgotten clients are looked for, not on every request.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Comma separated CIDR ranges, e.g. `127.0.0.0/8,::1/128`.
+#[derive(Clone, Debug, Default)]
+pub struct IpRanges(Vec<IpNet>);
+
+impl IpRanges {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|range| !range.is_empty())
+            .map(|range| {
+                range
+                    .parse()
+                    .map_err(|_| format!("'{}' is not a CIDR range", range))
+            })
+            .collect::<Result<_, _>>()
+            .map(IpRanges)
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        self.0.iter().any(|range| range.contains(ip))
+    }
+}
+
+struct Client {
+    in_flight: usize,
+    last_request: Instant,
+}
+
+struct Clients {
+    by_ip: HashMap<IpAddr, Client>,
+    last_prune: Instant,
+}
+
+/// Requests in flight per client IP, refusing more than `max_in_flight` at once
+/// from clients outside the exempt ranges.
+#[derive(Clone)]
+pub struct ClientLimiter {
+    max_in_flight: Option<usize>,
+    exempt: IpRanges,
+    clients: Arc<Mutex<Clients>>,
+}
+
+impl ClientLimiter {
+    pub fn new(max_in_flight: Option<usize>, exempt: IpRanges) -> Self {
+        ClientLimiter {
+            max_in_flight,
+            exempt,
+            clients: Arc::new(Mutex::new(Clients {
+                by_ip: HashMap::new(),
+                last_prune: Instant::now(),
+            })),
+        }
+    }
+
+    pub fn max_in_flight(&self) -> Option<usize> {
+        self.max_in_flight
+    }
+
+    /// Counts a request from `ip` until the guard is dropped.
+    pub fn acquire(&self, ip: IpAddr) -> Result<ClientGuard, TooManyInFlight> {
+        let mut clients = self.clients.lock().unwrap();
+        let now = Instant::now();
+        if now.duration_since(clients.last_prune) >= PRUNE_INTERVAL {
+            clients.by_ip.retain(|_, client| {
+                client.in_flight > 0 || now.duration_since(client.last_request) < IDLE_EXPIRY
+            });
+            clients.last_prune = now;
+        }
+        let client = clients.by_ip.entry(ip).or_insert(Client {
+            in_flight: 0,
+            last_request: now,
+        });
+        if let Some(max) = self.max_in_flight {
+            if client.in_flight >= max && !self.exempt.contains(&ip) {
+                return Err(TooManyInFlight { ip, max });
+            }
+        }
+        client.in_flight += 1;
+        client.last_request = now;
+        Ok(ClientGuard {
+            ip,
+            clients: self.clients.clone(),
+        })
+    }
+
+    /// The `count` clients with the most requests in flight, the most recent first among equals.
+    pub fn top(&self, count: usize) -> Vec<ClientInFlight> {
+        let clients = self.clients.lock().unwrap();
+        let mut top = clients.by_ip.iter().collect::<Vec<_>>();
+        top.sort_by(|(_, a), (_, b)| {
+            b.in_flight
+                .cmp(&a.in_flight)
+                .then(b.last_request.cmp(&a.last_request))
+        });
+        top.into_iter()
+            .take(count)
+            .map(|(ip, client)| ClientInFlight {
+                ip: ip.to_string(),
+                in_flight: client.in_flight as u64,
+                exempt: self.exempt.contains(ip),
+                since_last_request_ms: client.last_request.elapsed().as_millis() as u64,
+            })
+            .collect()
+    }
+}
+
+pub struct ClientGuard {
+    ip: IpAddr,
+    clients: Arc<Mutex<Clients>>,
+}
+
+impl Drop for ClientGuard {
+    fn drop(&mut self) {
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(client) = clients.by_ip.get_mut(&self.ip) {
+            client.in_flight = client.in_flight.saturating_sub(1);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TooManyInFlight {
+    ip: IpAddr,
+    max: usize,
+}
+
+impl fmt::Display for TooManyInFlight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Too many requests in flight from {}, at most {} are allowed",
+            self.ip, self.max
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use clap::Parser;
+
+    const GREEDY: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1));
+    const POLITE: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 2));
+
+    fn default_exempt() -> IpRanges {
+        Config::parse_from(["load-balancer"]).client_limit_exempt
+    }
+
+    #[test]
+    fn a_greedy_client_is_refused_past_the_cap_and_a_polite_one_still_served() {
+        let limiter = ClientLimiter::new(Some(2), default_exempt());
+
+        let _held = [limiter.acquire(GREEDY), limiter.acquire(GREEDY)];
+        let refused = limiter.acquire(GREEDY).err().unwrap();
+        assert_eq!(
+            refused.to_string(),
+            "Too many requests in flight from 10.0.0.1, at most 2 are allowed"
+        );
+        assert!(limiter.acquire(POLITE).is_ok());
+
+        let top = limiter.top(10);
+        assert_eq!(top[0].ip, "10.0.0.1");
+        assert_eq!((top[0].in_flight, top[0].exempt), (2, false));
+    }
+
+    #[test]
+    fn dropping_the_guard_frees_the_slot() {
+        let limiter = ClientLimiter::new(Some(1), IpRanges::default());
+
+        let held = limiter.acquire(GREEDY).unwrap();
+        assert!(limiter.acquire(GREEDY).is_err());
+        drop(held);
+        assert!(limiter.acquire(GREEDY).is_ok());
+        assert_eq!(limiter.top(1)[0].in_flight, 0);
+    }
+
+    #[test]
+    fn localhost_is_exempt_by_default() {
+        let limiter = ClientLimiter::new(Some(1), default_exempt());
+
+        for ip in ["127.0.0.1", "127.1.2.3", "::1"] {
+            let ip = ip.parse().unwrap();
+            let held = (0..5)
+                .map(|_| limiter.acquire(ip))
+                .collect::<Result<Vec<_>, _>>();
+            assert_eq!(held.map(|held| held.len()).ok(), Some(5), "{}", ip);
+        }
+        assert!(limiter.top(10).iter().all(|client| client.exempt));
+    }
+
+    #[test]
+    fn configured_ranges_are_exempt_and_only_them() {
+        let exempt = IpRanges::parse(" 10.0.0.0/31, fd00::/8 ,").unwrap();
+        let limiter = ClientLimiter::new(Some(1), exempt);
+
+        for ip in ["10.0.0.1", "10.0.0.0", "fd00::7"] {
+            let ip = ip.parse().unwrap();
+            let _held = limiter.acquire(ip).unwrap();
+            assert!(limiter.acquire(ip).is_ok(), "{}", ip);
+        }
+        let outside = "10.0.0.2".parse().unwrap();
+        let _held = limiter.acquire(outside).unwrap();
+        assert!(limiter.acquire(outside).is_err());
+        assert!(IpRanges::parse("10.0.0.0/33").is_err());
+    }
+
+    #[test]
+    fn without_a_cap_nobody_is_refused() {
+        let limiter = ClientLimiter::new(None, IpRanges::default());
+
+        let held = (0..100)
+            .map(|_| limiter.acquire(GREEDY))
+            .collect::<Result<Vec<_>, _>>();
+        assert_eq!(held.map(|held| held.len()).ok(), Some(100));
+    }
+}
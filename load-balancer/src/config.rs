@@ -0,0 +1,193 @@
+use std::time::Duration;
+
+use clap::builder::BoolishValueParser;
+use clap::Parser;
+
+use crate::balancing_algorithm::Algorithms;
+use crate::clients::IpRanges;
+use crate::cors::{AllowedOrigins, Cors};
+use crate::maglev::{HashKey, DEFAULT_TABLE_SIZE, MAX_TABLE_SIZE};
+use crate::queue::FairQueue;
+
+// The workers are where APP_ENVIRONMENT, WORKER_COUNT, WORKER_BASE_PORT and BACKENDS put them.
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Load balancer spreading requests over the worker servers"
+)]
+pub struct Config {
+    /// Port to listen on
+    #[arg(long, env = "PORT", default_value_t = 80)]
+    pub port: u16,
+
+    /// Comma separated algorithms POST /algo and the automatic switch may pick from
+    #[arg(long, env = "ALLOWED_ALGORITHMS", default_value_t = Algorithms::all(), value_parser = Algorithms::parse)]
+    pub allowed_algorithms: Algorithms,
+
+    /// What maglev hashes requests by, client_ip or `header:<name>`. Requests without the header
+    /// fall back to their client IP
+    #[arg(long, env = "HASH_KEY", default_value_t = HashKey::ClientIp, value_parser = HashKey::parse)]
+    pub hash_key: HashKey,
+
+    /// Slots in maglev's lookup table, a prime well above the number of workers and at most 1000003
+    #[arg(long, env = "MAGLEV_TABLE_SIZE", default_value_t = DEFAULT_TABLE_SIZE, value_parser = parse_prime)]
+    pub maglev_table_size: usize,
+
+    /// Most a forwarded request may take until the worker's response starts, in milliseconds.
+    /// Requests can ask for less with the X-Request-Timeout-Ms header [default: no limit]
+    #[arg(long, env = "REQUEST_TIMEOUT_MS")]
+    pub request_timeout_ms: Option<u64>,
+
+    /// Most requests a single client IP may have in flight, more are answered with 429 [default: no limit]
+    #[arg(long, env = "MAX_CLIENT_IN_FLIGHT", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_client_in_flight: Option<u64>,
+
+    /// Comma separated CIDR ranges of clients --max-client-in-flight doesn't apply to
+    #[arg(long, env = "CLIENT_LIMIT_EXEMPT", default_value = "127.0.0.0/8,::1/128", value_parser = IpRanges::parse)]
+    pub client_limit_exempt: IpRanges,
+
+    /// Most requests forwarded at once over every client, more wait in a queue per route that
+    /// take turns at the freed slots [default: no limit]
+    #[arg(long, env = "MAX_IN_FLIGHT", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_in_flight: Option<u64>,
+
+    /// Most requests waiting in a route's queue, more are answered with 503
+    #[arg(long, env = "QUEUE_MAX_DEPTH", default_value_t = 100, value_parser = clap::value_parser!(u64).range(1..))]
+    pub queue_max_depth: u64,
+
+    /// Longest a request waits in its route's queue before it's answered with 503, in
+    /// milliseconds. Less when its request budget runs out first
+    #[arg(long, env = "QUEUE_TIMEOUT_MS", default_value_t = 5000)]
+    pub queue_timeout_ms: u64,
+
+    /// Healthy workers GET /lb/health needs to answer 200, with fewer it answers 503
+    #[arg(long, env = "HEALTH_MIN_BACKENDS", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub health_min_backends: u64,
+
+    /// How often every worker is checked, in milliseconds
+    #[arg(long, env = "HEALTH_CHECK_INTERVAL_MS", default_value_t = 2000, value_parser = clap::value_parser!(u64).range(1..))]
+    pub health_check_interval_ms: u64,
+
+    /// Path the health checks get from every worker, those answering with a 2xx are healthy.
+    /// /ready also takes out workers warming up or marked not ready, /health only those down
+    #[arg(long, env = "HEALTH_CHECK_PATH", default_value = "/ready", value_parser = parse_path)]
+    pub health_check_path: String,
+
+    /// The longest open connections get to finish their requests on shutdown, in milliseconds.
+    /// GET /lb/health answers 503 meanwhile, and connections still open after it are aborted
+    #[arg(long, env = "SHUTDOWN_DRAIN_MS", default_value_t = 0)]
+    pub shutdown_drain_ms: u64,
+
+    /// Answer forwarded requests with an X-LB-Bytes header, the request body bytes the worker was
+    /// sent before it answered and the response's Content-Length when it has one
+    #[arg(long, env = "DEBUG_BYTES_HEADER", value_parser = BoolishValueParser::new())]
+    pub debug_bytes_header: bool,
+
+    /// Comma separated origins browsers may call /algo and the /lb/ routes from, `*` for any.
+    /// Forwarded requests are left to the workers [default: none]
+    #[arg(long, env = "CORS_ALLOWED_ORIGINS", default_value = "", hide_default_value = true, value_parser = AllowedOrigins::parse)]
+    pub cors_allowed_origins: AllowedOrigins,
+
+    /// Methods preflight requests for the admin routes are answered with
+    #[arg(long, env = "CORS_ALLOWED_METHODS", default_value = "GET, POST, PUT")]
+    pub cors_allowed_methods: String,
+
+    /// Request headers preflight requests for the admin routes are answered with
+    #[arg(long, env = "CORS_ALLOWED_HEADERS", default_value = "content-type")]
+    pub cors_allowed_headers: String,
+}
+
+impl Config {
+    pub fn load() -> Self {
+        lb_config::load("load_balancer")
+    }
+
+    pub fn request_timeout(&self) -> Option<Duration> {
+        self.request_timeout_ms.map(Duration::from_millis)
+    }
+
+    /// None without a global in-flight limit.
+    pub fn queue(&self) -> Option<FairQueue> {
+        self.max_in_flight
+            .map(|max| FairQueue::new(max as usize, self.queue_max_depth as usize))
+    }
+
+    pub fn queue_timeout(&self) -> Duration {
+        Duration::from_millis(self.queue_timeout_ms)
+    }
+
+    pub fn health_check_interval(&self) -> Duration {
+        Duration::from_millis(self.health_check_interval_ms)
+    }
+
+    pub fn shutdown_drain(&self) -> Duration {
+        Duration::from_millis(self.shutdown_drain_ms)
+    }
+
+    pub fn cors(&self) -> Cors {
+        Cors {
+            origins: self.cors_allowed_origins.clone(),
+            methods: self.cors_allowed_methods.clone(),
+            headers: self.cors_allowed_headers.clone(),
+        }
+    }
+}
+
+fn parse_path(value: &str) -> Result<String, String> {
+    match value.starts_with('/') && value.parse::<hyper::Uri>().is_ok() {
+        true => Ok(value.to_string()),
+        false => Err(format!("'{}' is not a path starting with /", value)),
+    }
+}
+
+fn parse_prime(value: &str) -> Result<usize, String> {
+    let number = value
+        .parse::<usize>()
+        .map_err(|_| format!("'{}' is not a number", value))?;
+    if number > MAX_TABLE_SIZE {
+        return Err(format!(
+            "{} is too large, the table may have at most {} slots",
+            number, MAX_TABLE_SIZE
+        ));
+    }
+    let prime = number >= 2
+        && (2..)
+            .take_while(|d| d * d <= number)
+            .all(|d| number % d != 0);
+    match prime {
+        true => Ok(number),
+        false => Err(format!("{} is not a prime", number)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_sizes_must_be_prime() {
+        assert_eq!(parse_prime("65537"), Ok(65537));
+        assert_eq!(parse_prime("2"), Ok(2));
+        assert!(parse_prime("65536").is_err());
+        assert!(parse_prime("1").is_err());
+        assert!(parse_prime("many").is_err());
+    }
+
+    #[test]
+    fn health_checks_get_ready_unless_told_otherwise() {
+        let config = Config::parse_from(["load-balancer"]);
+        assert_eq!(config.health_check_path, "/ready");
+        let config = Config::parse_from(["load-balancer", "--health-check-path", "/health"]);
+        assert_eq!(config.health_check_path, "/health");
+
+        assert!(parse_path("health").is_err());
+        assert!(parse_path("/with space").is_err());
+    }
+
+    #[test]
+    fn table_sizes_are_bounded_before_the_primality_check() {
+        assert_eq!(parse_prime("1000003"), Ok(MAX_TABLE_SIZE));
+        assert!(parse_prime("1000033").is_err());
+        assert!(parse_prime(&usize::MAX.to_string()).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::{Response, StatusCode};
+
+use crate::{full, BoxBody, Result};
+
+// How long browsers may reuse a preflight answer, in seconds.
+const MAX_AGE_SECS: u32 = 600;
+
+/// Origins browsers may call the admin routes from, e.g. `http://localhost:5173`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AllowedOrigins {
+    // `*`, meant for development.
+    Any,
+    // Nothing is allowed when empty.
+    List(Vec<String>),
+}
+
+impl Default for AllowedOrigins {
+    fn default() -> Self {
+        AllowedOrigins::List(Vec::new())
+    }
+}
+
+impl AllowedOrigins {
+    /// `*` or a comma separated list of origins, an empty one allows none.
+    pub fn parse(value: &str) -> std::result::Result<Self, String> {
+        if value.trim() == "*" {
+            return Ok(AllowedOrigins::Any);
+        }
+        let origins = value
+            .split(',')
+            .map(|origin| origin.trim().trim_end_matches('/'))
+            .filter(|origin| !origin.is_empty())
+            .map(|origin| match origin.split_once("://") {
+                Some((_, host)) if !host.is_empty() && !host.contains('/') => {
+                    Ok(origin.to_string())
+                }
+                _ => Err(format!(
+                    "'{}' is not an origin like http://host:port",
+                    origin
+                )),
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(AllowedOrigins::List(origins))
+    }
+}
+
+/// CORS for the admin routes only, forwarded traffic is the workers' to answer.
+#[derive(Clone, Debug, Default)]
+pub struct Cors {
+    pub origins: AllowedOrigins,
+    /// e.g. `GET, POST`.
+    pub methods: String,
+    /// e.g. `content-type`.
+    pub headers: String,
+}
+
+impl Cors {
+    // What Access-Control-Allow-Origin is set to for a request from `origin`, None when it isn't allowed.
+    fn allow_origin(&self, origin: Option<&HeaderValue>) -> Option<HeaderValue> {
+        let origin = origin?;
+        match &self.origins {
+            AllowedOrigins::Any => Some(HeaderValue::from_static("*")),
+            AllowedOrigins::List(origins) => origins
+                .iter()
+                .any(|allowed| origin.as_bytes() == allowed.as_bytes())
+                .then(|| origin.clone()),
+        }
+    }
+
+    /// Answers an OPTIONS request for an admin route, 403 for origins that aren't allowed.
+    pub fn preflight(&self, headers: &HeaderMap) -> Result<Response<BoxBody>> {
+        let origin = headers.get(header::ORIGIN);
+        let Some(allowed) = self.allow_origin(origin) else {
+            let msg = match origin {
+                Some(origin) => format!("Origin {:?} is not allowed", origin),
+                None => "Missing Origin header".to_string(),
+            };
+            let response = Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .header(header::CONTENT_TYPE, "text/plain")
+                .body(full(msg))?;
+            return Ok(response);
+        };
+        let response = Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, allowed)
+            .header(header::ACCESS_CONTROL_ALLOW_METHODS, &self.methods)
+            .header(header::ACCESS_CONTROL_ALLOW_HEADERS, &self.headers)
+            .header(header::ACCESS_CONTROL_MAX_AGE, MAX_AGE_SECS)
+            .header(header::VARY, "Origin")
+            .body(full(""))?;
+        Ok(response)
+    }
+
+    /// Lets the browser read an admin route's response, when the request's origin is allowed.
+    pub fn apply(&self, origin: Option<&HeaderValue>, response: &mut Response<BoxBody>) {
+        if let Some(allowed) = self.allow_origin(origin) {
+            let headers = response.headers_mut();
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allowed);
+            headers.insert(header::VARY, HeaderValue::from_static("Origin"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn origins_parse_as_a_list_or_the_wildcard() {
+        assert_eq!(AllowedOrigins::parse(" * "), Ok(AllowedOrigins::Any));
+        assert_eq!(AllowedOrigins::parse(""), Ok(AllowedOrigins::default()));
+        assert_eq!(
+            AllowedOrigins::parse("http://ui:5173/, https://admin.example"),
+            Ok(AllowedOrigins::List(vec![
+                "http://ui:5173".to_string(),
+                "https://admin.example".to_string(),
+            ]))
+        );
+        assert!(AllowedOrigins::parse("ui:5173").is_err());
+        assert!(AllowedOrigins::parse("http://ui/stats").is_err());
+    }
+
+    #[test]
+    fn only_listed_origins_are_echoed_back() {
+        let cors = Cors {
+            origins: AllowedOrigins::parse("http://ui").unwrap(),
+            ..Cors::default()
+        };
+        let allowed = HeaderValue::from_static("http://ui");
+
+        assert_eq!(cors.allow_origin(Some(&allowed)), Some(allowed));
+        assert_eq!(
+            cors.allow_origin(Some(&HeaderValue::from_static("http://ui:1"))),
+            None
+        );
+        assert_eq!(cors.allow_origin(None), None);
+        assert_eq!(
+            Cors::default().allow_origin(Some(&HeaderValue::from_static("http://ui"))),
+            None
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use lb_api::{DrainAborted, DrainResponse};
+use tokio::sync::{watch, Notify};
+use tokio::time::Instant;
+
+/// What client connections are told to do.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    Serving,
+    // Finish the request in flight, then close.
+    Draining,
+    // Close now, the drain deadline passed.
+    Aborted,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Started {
+    at: Instant,
+    deadline: Instant,
+    aborted: Option<DrainAborted>,
+}
+
+/// Shutdown progress: the client connections still open, fed by their guards, and when the
+/// drain started and must end.
+pub struct Drain {
+    phase: watch::Sender<Phase>,
+    connections: Arc<AtomicUsize>,
+    closed: Arc<Notify>,
+    started: Mutex<Option<Started>>,
+}
+
+impl Default for Drain {
+    fn default() -> Self {
+        Drain {
+            phase: watch::channel(Phase::Serving).0,
+            connections: Arc::default(),
+            closed: Arc::default(),
+            started: Mutex::new(None),
+        }
+    }
+}
+
+impl Drain {
+    pub fn is_draining(&self) -> bool {
+        *self.phase.borrow() != Phase::Serving
+    }
+
+    pub fn phase(&self) -> watch::Receiver<Phase> {
+        self.phase.subscribe()
+    }
+
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    /// Counts a client connection until the guard is dropped.
+    pub fn connection(&self) -> ConnectionGuard {
+        self.connections.fetch_add(1, Ordering::SeqCst);
+        ConnectionGuard {
+            connections: self.connections.clone(),
+            closed: self.closed.clone(),
+        }
+    }
+
+    /// Asks every connection to close after its request in flight, `period` from now at the latest.
+    pub fn start(&self, period: Duration) {
+        let now = Instant::now();
+        *self.started.lock().unwrap() = Some(Started {
+            at: now,
+            deadline: now + period,
+            aborted: None,
+        });
+        self.phase.send_replace(Phase::Draining);
+    }
+
+    /// Resolves once no client connection is left.
+    pub async fn idle(&self) {
+        while self.connections() > 0 {
+            self.closed.notified().await;
+        }
+    }
+
+    /// Resolves at the deadline, never before the drain started.
+    pub async fn deadline(&self) {
+        let deadline = self.started.lock().unwrap().map(|started| started.deadline);
+        match deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Closes the connections left, recording them and the requests to workers they were waiting on.
+    pub fn abort(&self, backend_requests: u64) -> DrainAborted {
+        let aborted = DrainAborted {
+            connections: self.connections() as u64,
+            backend_requests,
+        };
+        if let Some(started) = self.started.lock().unwrap().as_mut() {
+            started.aborted = Some(aborted);
+        }
+        self.phase.send_replace(Phase::Aborted);
+        aborted
+    }
+
+    /// What GET /lb/drain answers.
+    pub fn report(&self, backend_requests: u64) -> DrainResponse {
+        let started = *self.started.lock().unwrap();
+        DrainResponse {
+            draining: self.is_draining(),
+            connections: self.connections() as u64,
+            backend_requests,
+            elapsed_ms: started.map(|started| started.at.elapsed().as_millis() as u64),
+            remaining_ms: started.map(|started| {
+                started
+                    .deadline
+                    .saturating_duration_since(Instant::now())
+                    .as_millis() as u64
+            }),
+            deadline_ms: started.map(|started| (started.deadline - started.at).as_millis() as u64),
+            aborted: started.and_then(|started| started.aborted),
+        }
+    }
+}
+
+pub struct ConnectionGuard {
+    connections: Arc<AtomicUsize>,
+    closed: Arc<Notify>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if self.connections.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.closed.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WAIT: Duration = Duration::from_secs(5);
+
+    #[tokio::test]
+    async fn nothing_is_reported_before_the_drain_starts() {
+        let drain = Drain::default();
+        let _guard = drain.connection();
+
+        let report = drain.report(0);
+        assert!(!report.draining);
+        assert_eq!(report.connections, 1);
+        assert_eq!((report.elapsed_ms, report.deadline_ms), (None, None));
+        let deadline = tokio::time::timeout(Duration::from_millis(20), drain.deadline()).await;
+        assert!(deadline.is_err());
+    }
+
+    #[tokio::test]
+    async fn connections_count_down_as_their_requests_finish() {
+        let drain = Drain::default();
+        // Slow requests in flight, each holding its connection until it's answered.
+        let requests = [50, 100, 150].map(|ms| {
+            let guard = drain.connection();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(ms)).await;
+                drop(guard);
+            })
+        });
+        drain.start(WAIT);
+        assert_eq!(*drain.phase().borrow(), Phase::Draining);
+        assert_eq!(drain.report(3).connections, 3);
+
+        let mut seen = vec![3];
+        for request in requests {
+            request.await.unwrap();
+            seen.push(drain.report(0).connections);
+        }
+        assert_eq!(seen, [3, 2, 1, 0]);
+        tokio::time::timeout(WAIT, drain.idle()).await.unwrap();
+
+        let report = drain.report(0);
+        assert!(report.draining);
+        assert_eq!(report.deadline_ms, Some(WAIT.as_millis() as u64));
+        assert!(report.remaining_ms.unwrap() <= WAIT.as_millis() as u64);
+        assert_eq!(report.aborted, None);
+    }
+
+    #[tokio::test]
+    async fn what_is_left_at_the_deadline_is_aborted_and_reported() {
+        let drain = Drain::default();
+        let _slow = [drain.connection(), drain.connection()];
+        // Answered and closed before the drain started.
+        drop(drain.connection());
+        drain.start(Duration::from_millis(30));
+
+        tokio::time::timeout(WAIT, drain.deadline()).await.unwrap();
+        let aborted = drain.abort(2);
+        assert_eq!(
+            aborted,
+            DrainAborted {
+                connections: 2,
+                backend_requests: 2,
+            }
+        );
+        assert_eq!(*drain.phase().borrow(), Phase::Aborted);
+
+        let report = drain.report(2);
+        assert_eq!(report.aborted, Some(aborted));
+        assert_eq!(report.remaining_ms, Some(0));
+        assert!(report.elapsed_ms.unwrap() >= 30);
+    }
+}
@@ -0,0 +1,189 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+use http_body_util::Empty;
+use hyper::{header, Request};
+use hyper_util::rt::TokioIo;
+use lb_api::{BackendHealth, BalancerHealth, HealthResponse};
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+use crate::{GenericError, LoadBalancer, SharedState};
+
+/// Checks every worker at the state's health check path each `interval` for as long as the
+/// balancer runs, a worker is healthy when it answers with a 2xx within the interval.
+pub async fn check_workers(state: SharedState, interval: Duration) {
+    let mut ticks = tokio::time::interval(interval);
+    loop {
+        ticks.tick().await;
+        let mut checks = JoinSet::new();
+        for server in state.load_balancer.read().await.servers() {
+            let address = server.get_address().to_string();
+            let state = state.clone();
+            checks.spawn(async move {
+                let healthy = check(&address, &state.health_check_path, interval).await;
+                (address, healthy)
+            });
+        }
+        let results = checks.join_all().await;
+
+        let mut lb = state.load_balancer.write().await;
+        for (address, healthy) in results {
+            let Some(server) = lb.get_server_by_address(&address) else {
+                continue;
+            };
+            if server.is_healthy() != healthy {
+                match healthy {
+                    true => info!("Worker {} is healthy", address),
+                    false => warn!("Worker {} is unhealthy", address),
+                }
+                server.set_healthy(healthy);
+            }
+        }
+    }
+}
+
+async fn check(address: &str, path: &str, timeout: Duration) -> bool {
+    let attempt = async {
+        let stream = TcpStream::connect(address).await?;
+        let (mut sender, conn) =
+            hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+        tokio::task::spawn(conn);
+        let req = Request::get(path)
+            .header(header::HOST, address)
+            .body(Empty::<Bytes>::new())?;
+        let res = sender.send_request(req).await?;
+        Ok::<_, GenericError>(res.status().is_success())
+    };
+    matches!(tokio::time::timeout(timeout, attempt).await, Ok(Ok(true)))
+}
+
+/// What GET /lb/health answers, listing the workers only when some aren't healthy
+/// since it's polled every second or two.
+pub fn report(lb: &LoadBalancer, required: usize, draining: bool) -> HealthResponse {
+    let servers = lb.servers();
+    let healthy = servers.iter().filter(|server| server.is_healthy()).count();
+    let status = if draining {
+        BalancerHealth::Draining
+    } else if healthy < required {
+        BalancerHealth::Unavailable
+    } else if healthy < servers.len() {
+        BalancerHealth::Degraded
+    } else {
+        BalancerHealth::Ok
+    };
+    let backends = match status {
+        BalancerHealth::Ok => Vec::new(),
+        _ => servers
+            .iter()
+            .map(|server| BackendHealth {
+                address: server.get_address().to_string(),
+                healthy: server.is_healthy(),
+                connections: server.get_connections() as u64,
+            })
+            .collect(),
+    };
+    HealthResponse {
+        status,
+        healthy: healthy as u64,
+        required: required as u64,
+        backends,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Server;
+
+    // Workers on ports 3000 and up, the first `healthy` ones passed their health check.
+    fn load_balancer(servers: u16, healthy: u16) -> LoadBalancer {
+        let servers = (0..servers)
+            .map(|i| {
+                let mut server = Server::new(format!("127.0.0.1:{}", 3000 + i)).unwrap();
+                server.set_healthy(i < healthy);
+                server
+            })
+            .collect();
+        LoadBalancer::new(servers).unwrap()
+    }
+
+    #[test]
+    fn every_worker_healthy_is_ok_without_listing_them() {
+        let report = report(&load_balancer(3, 3), 1, false);
+
+        assert_eq!(report.status, BalancerHealth::Ok);
+        assert!(report.status.is_ready());
+        assert_eq!((report.healthy, report.required), (3, 1));
+        assert!(report.backends.is_empty());
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            r#"{"status":"ok","healthy":3,"required":1}"#
+        );
+    }
+
+    #[test]
+    fn enough_healthy_workers_are_ready_but_degraded() {
+        let report = report(&load_balancer(3, 1), 1, false);
+
+        assert_eq!(report.status, BalancerHealth::Degraded);
+        assert!(report.status.is_ready());
+        let healthy = report
+            .backends
+            .iter()
+            .map(|b| b.healthy)
+            .collect::<Vec<_>>();
+        assert_eq!(healthy, [true, false, false]);
+    }
+
+    #[test]
+    fn fewer_healthy_workers_than_required_are_unavailable() {
+        let report = report(&load_balancer(3, 1), 2, false);
+
+        assert_eq!(report.status, BalancerHealth::Unavailable);
+        assert!(!report.status.is_ready());
+        assert_eq!((report.healthy, report.required), (1, 2));
+        assert_eq!(report.backends.len(), 3);
+    }
+
+    // A balancer always has workers, before any passed a check it's as good as an empty pool.
+    #[test]
+    fn no_healthy_worker_is_unavailable() {
+        let mut lb = load_balancer(2, 0);
+        lb.get_server_by_address("127.0.0.1:3001")
+            .unwrap()
+            .increment_connections();
+        let report = report(&lb, 1, false);
+
+        assert_eq!(report.status, BalancerHealth::Unavailable);
+        assert_eq!(report.healthy, 0);
+        assert_eq!(
+            report.backends,
+            [
+                BackendHealth {
+                    address: "127.0.0.1:3000".to_string(),
+                    healthy: false,
+                    connections: 0,
+                },
+                BackendHealth {
+                    address: "127.0.0.1:3001".to_string(),
+                    healthy: false,
+                    connections: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn draining_is_unavailable_however_healthy_the_workers() {
+        let report = report(&load_balancer(2, 2), 1, true);
+
+        assert_eq!(report.status, BalancerHealth::Draining);
+        assert!(!report.status.is_ready());
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["status"], "draining");
+        assert_eq!(json["backends"][1]["address"], "127.0.0.1:3001");
+        assert_eq!(json["backends"][1]["healthy"], true);
+    }
+}
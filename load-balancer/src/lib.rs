@@ -0,0 +1,1491 @@
+mod balancing_algorithm;
+mod budget;
+mod clients;
+mod config;
+mod cors;
+mod drain;
+mod health;
+mod load_balancer;
+mod maglev;
+mod queue;
+mod server;
+mod transfer;
+
+use std::future::Future;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::{Buf, Bytes};
+use environment::Topology;
+use http_body_util::{BodyExt, Full};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::Uri;
+use hyper::{body::Incoming as IncomingBody, header, Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use lb_api::{
+    AlgorithmInfo, AlgorithmsResponse, AllowedAlgorithmsRequest, ChangeAlgoRequest,
+    ClientsResponse, ServerStats, StatsResponse,
+};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::RwLock;
+use tracing::{error, info, instrument, warn};
+
+pub use balancing_algorithm::{Algorithms, BalancingAlgorithm, ConversionError};
+pub use budget::{Exhausted, RequestBudget, Spent, REQUEST_TIMEOUT_HEADER};
+pub use clients::{ClientGuard, ClientLimiter, IpRanges, TooManyInFlight};
+pub use config::Config;
+pub use cors::{AllowedOrigins, Cors};
+pub use drain::{ConnectionGuard, Drain, Phase};
+pub use load_balancer::LoadBalancer;
+pub use maglev::{HashKey, Maglev};
+pub use queue::{FairQueue, Permit, QueueError};
+pub use server::Server;
+pub use transfer::{AccessLog, ByteCounts, CountingBody, Transfer};
+
+pub type GenericError = Box<dyn std::error::Error + Send + Sync>;
+pub type Result<T> = std::result::Result<T, GenericError>;
+/// Response bodies, whether answered by the balancer or streamed from a worker.
+pub type BoxBody = http_body_util::combinators::BoxBody<Bytes, hyper::Error>;
+/// The state every connection holds a handle to.
+pub type SharedState = Arc<State>;
+
+// How many clients GET /lb/clients lists.
+const TOP_CLIENTS: usize = 10;
+// Answered by the balancer itself, the only routes CORS applies to.
+const ADMIN_PATHS: [&str; 6] = [
+    "/algo",
+    "/lb/algorithms",
+    "/lb/clients",
+    "/lb/drain",
+    "/lb/health",
+    "/lb/stats",
+];
+// Debug header with a forwarded request's byte counts, when the config asks for it.
+const BYTES_HEADER: &str = "x-lb-bytes";
+// Meant for the connection they came over only, not passed on with a worker's response.
+const HOP_BY_HOP_HEADERS: [header::HeaderName; 4] = [
+    header::CONNECTION,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+    header::TE,
+];
+
+/// What every connection shares.
+pub struct State {
+    /// The workers and the algorithm picking between them.
+    pub load_balancer: RwLock<LoadBalancer>,
+    /// What a forwarded request's budget starts from, None for no limit.
+    pub request_timeout: Option<Duration>,
+    /// What Maglev keeps requests on the same worker by.
+    pub hash_key: HashKey,
+    /// Requests in flight per client IP.
+    pub clients: ClientLimiter,
+    /// None without a global in-flight limit.
+    pub queue: Option<FairQueue>,
+    /// The longest a request waits in the queue for a slot.
+    pub queue_timeout: Duration,
+    /// Which browser origins may call the admin routes.
+    pub cors: Cors,
+    /// Healthy workers needed for GET /lb/health to answer 200.
+    pub health_min_backends: usize,
+    /// How often every worker is checked.
+    pub health_check_interval: Duration,
+    /// What the health checks get from every worker.
+    pub health_check_path: String,
+    /// The longest connections get to finish on shutdown, GET /lb/health answers 503 meanwhile.
+    pub shutdown_drain: Duration,
+    /// Shutdown progress, idle until `shutdown` completes.
+    pub drain: Drain,
+    /// Adds the x-lb-bytes header with a forwarded request's byte counts.
+    pub debug_bytes_header: bool,
+}
+
+impl State {
+    /// Serves `load_balancer`'s workers with the limits, timeouts and algorithms `config` sets.
+    pub fn new(mut load_balancer: LoadBalancer, config: &Config) -> Self {
+        load_balancer.set_allowed(config.allowed_algorithms.clone());
+        load_balancer.set_maglev_table_size(config.maglev_table_size);
+        State {
+            load_balancer: RwLock::new(load_balancer),
+            request_timeout: config.request_timeout(),
+            hash_key: config.hash_key.clone(),
+            clients: ClientLimiter::new(
+                config.max_client_in_flight.map(|max| max as usize),
+                config.client_limit_exempt.clone(),
+            ),
+            queue: config.queue(),
+            queue_timeout: config.queue_timeout(),
+            cors: config.cors(),
+            health_min_backends: config.health_min_backends as usize,
+            health_check_interval: config.health_check_interval(),
+            health_check_path: config.health_check_path.clone(),
+            shutdown_drain: config.shutdown_drain(),
+            drain: Drain::default(),
+            debug_bytes_header: config.debug_bytes_header,
+        }
+    }
+}
+
+/// Balances over the topology's workers, listening on the configured port of the environment's
+/// bind address until `shutdown` completes. Port 0 picks a free one.
+pub async fn serve(
+    config: &Config,
+    topology: &Topology,
+    shutdown: impl Future<Output = ()>,
+) -> Result<()> {
+    let load_balancer = create_load_balancer(topology)?;
+    let addr = topology.environment().bind_address(config.port);
+    let listener = TcpListener::bind(addr).await.map_err(|e| e.to_string())?;
+    serve_listener(listener, State::new(load_balancer, config), shutdown).await
+}
+
+/// Like `serve`, on a listener the caller bound, e.g. to know the port before requests are sent.
+/// After `shutdown` the state's drain period starts, unless it's zero: open connections close once
+/// their request in flight is answered, new ones are served a single request, GET /lb/health
+/// answers 503 and GET /lb/drain reports progress. It ends once no connection is left, those still
+/// open at its deadline are aborted.
+pub async fn serve_listener(
+    listener: TcpListener,
+    state: State,
+    shutdown: impl Future<Output = ()>,
+) -> Result<()> {
+    let state = Arc::new(state);
+    info!("Listening on http://{}", listener.local_addr()?);
+    let checks = tokio::task::spawn(health::check_workers(
+        state.clone(),
+        state.health_check_interval,
+    ));
+    tokio::pin!(shutdown);
+
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => accepted.map_err(|e| e.to_string())?,
+            _ = &mut shutdown, if !state.drain.is_draining() => {
+                if state.shutdown_drain.is_zero() {
+                    break;
+                }
+                info!(
+                    "Draining {} connections for at most {}ms before shutting down",
+                    state.drain.connections(),
+                    state.shutdown_drain.as_millis()
+                );
+                state.drain.start(state.shutdown_drain);
+                continue;
+            }
+            _ = state.drain.idle(), if state.drain.is_draining() => {
+                info!("Drained every connection");
+                break;
+            }
+            _ = state.drain.deadline() => {
+                let aborted = state.drain.abort(backend_requests(&state).await);
+                warn!(
+                    "Aborted {} connections with {} requests to workers in flight at the drain deadline",
+                    aborted.connections, aborted.backend_requests
+                );
+                break;
+            }
+        };
+        tokio::task::spawn(serve_connection(stream, peer.ip(), state.clone()));
+    }
+    checks.abort();
+    info!("Shutting down");
+    Ok(())
+}
+
+// Serves a client's connection, counted for the drain, until it closes or the drain tells it to.
+async fn serve_connection(stream: TcpStream, client: IpAddr, state: SharedState) {
+    let _guard = state.drain.connection();
+    let mut phase = state.drain.phase();
+    let io = TokioIo::new(stream);
+    let service_state = state.clone();
+    let service = service_fn(move |req| handle_request(req, service_state.clone(), client));
+    let conn = http1::Builder::new()
+        .keep_alive(!state.drain.is_draining())
+        .serve_connection(io, service);
+    tokio::pin!(conn);
+
+    let mut listening = true;
+    loop {
+        tokio::select! {
+            served = conn.as_mut() => {
+                if let Err(err) = served {
+                    error!("Failed to serve connection: {:?}", err);
+                }
+                return;
+            }
+            changed = phase.changed(), if listening => {
+                if changed.is_err() {
+                    listening = false;
+                    continue;
+                }
+                let current = *phase.borrow_and_update();
+                match current {
+                    Phase::Serving => {}
+                    Phase::Draining => conn.as_mut().graceful_shutdown(),
+                    Phase::Aborted => return,
+                }
+            }
+        }
+    }
+}
+
+/// Resolves every worker's address, service names need the environment's DNS.
+pub fn create_load_balancer(topology: &Topology) -> Result<LoadBalancer> {
+    let servers = topology
+        .worker_addresses()
+        .iter()
+        .map(|worker| {
+            let address = worker.resolve().map_err(|e| e.to_string())?;
+            Server::new(address.to_string())
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let lb = LoadBalancer::new(servers)?;
+    Ok(lb)
+}
+
+/// Answers /algo and the /lb/ routes itself, with their CORS preflights, and forwards everything
+/// else untouched to the next worker, within the state's request timeout or the shorter one
+/// the request asks for. `client` is the IP the request came from.
+#[instrument(skip_all)]
+pub async fn handle_request(
+    req: Request<IncomingBody>,
+    state: SharedState,
+    client: IpAddr,
+) -> Result<Response<BoxBody>> {
+    info!("Received request: {} {}", req.method(), req.uri().path());
+    let origin = req.headers().get(header::ORIGIN).cloned();
+    let mut response = match (req.method(), req.uri().path()) {
+        (&Method::OPTIONS, path) if ADMIN_PATHS.contains(&path) => {
+            return state.cors.preflight(req.headers());
+        }
+        (&Method::POST, "/algo") => change_algo(req, state.clone()).await?,
+        (&Method::GET, "/lb/algorithms") => algorithms(state.clone()).await?,
+        (&Method::PUT, "/lb/algorithms") => allow_algorithms(req, state.clone()).await?,
+        (&Method::GET, "/lb/clients") => clients(state.clone())?,
+        (&Method::GET, "/lb/drain") => drain(state.clone()).await?,
+        (&Method::GET, "/lb/health") => health(state.clone()).await?,
+        (&Method::GET, "/lb/stats") => stats(state.clone()).await?,
+        _ => return forward_request(req, state, client).await,
+    };
+    state.cors.apply(origin.as_ref(), &mut response);
+    Ok(response)
+}
+
+#[instrument(skip_all)]
+async fn change_algo(req: Request<IncomingBody>, state: SharedState) -> Result<Response<BoxBody>> {
+    let whole_body = req.collect().await?.aggregate();
+    let data: serde_json::Value = serde_json::from_reader(whole_body.reader())?;
+    if let Ok(ChangeAlgoRequest { algo: algo_value }) = serde_json::from_value(data) {
+        match BalancingAlgorithm::try_from(algo_value.as_str()) {
+            Ok(algo) => {
+                {
+                    let mut lb = state.load_balancer.write().await;
+                    if !lb.allowed().contains(algo) {
+                        let msg = format!(
+                            "Algorithm '{}' is disabled, allowed are {}",
+                            algo,
+                            lb.allowed()
+                        );
+                        warn!(msg);
+                        let response = Response::builder()
+                            .status(StatusCode::FORBIDDEN)
+                            .header(header::CONTENT_TYPE, "text/plain")
+                            .body(full(msg))?;
+                        return Ok(response);
+                    }
+                    lb.set_algorithm(algo);
+                }
+
+                let msg = format!("Algorithm changed successfully to {}", algo);
+                info!(msg);
+
+                let response = Response::builder()
+                    .status(StatusCode::OK)
+                    .header(header::CONTENT_TYPE, "text/plain")
+                    .body(full(msg))?;
+                Ok(response)
+            }
+            Err(_) => {
+                let msg = format!("Invalid algorithm value '{}'", algo_value);
+                warn!(msg);
+                let response = Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .header(header::CONTENT_TYPE, "text/plain")
+                    .body(full(msg))?;
+                Ok(response)
+            }
+        }
+    } else {
+        let msg = "Missing or invalid 'algo' key";
+        warn!(msg);
+        let response = Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(full(msg))?;
+        Ok(response)
+    }
+}
+
+async fn algorithms(state: SharedState) -> Result<Response<BoxBody>> {
+    let body = {
+        let lb = state.load_balancer.read().await;
+        AlgorithmsResponse {
+            current: lb.algorithm().to_string(),
+            algorithms: BalancingAlgorithm::ALL
+                .iter()
+                .map(|algorithm| AlgorithmInfo {
+                    name: algorithm.to_string(),
+                    enabled: lb.allowed().contains(*algorithm),
+                    description: algorithm.description().to_string(),
+                })
+                .collect(),
+        }
+    };
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(full(serde_json::to_string(&body)?))?;
+    Ok(response)
+}
+
+#[instrument(skip_all)]
+async fn allow_algorithms(
+    req: Request<IncomingBody>,
+    state: SharedState,
+) -> Result<Response<BoxBody>> {
+    let whole_body = req.collect().await?.aggregate();
+    let allowed = serde_json::from_reader::<_, AllowedAlgorithmsRequest>(whole_body.reader())
+        .map_err(|_| "Missing or invalid 'algorithms' key".to_string())
+        .and_then(|request| Algorithms::from_names(request.algorithms.iter().map(String::as_str)));
+    let allowed = match allowed {
+        Ok(allowed) => allowed,
+        Err(msg) => {
+            warn!(msg);
+            let response = Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .header(header::CONTENT_TYPE, "text/plain")
+                .body(full(msg))?;
+            return Ok(response);
+        }
+    };
+    info!("Allowed algorithms changed to {}", allowed);
+    state.load_balancer.write().await.set_allowed(allowed);
+    algorithms(state).await
+}
+
+fn clients(state: SharedState) -> Result<Response<BoxBody>> {
+    let body = ClientsResponse {
+        max_in_flight: state.clients.max_in_flight().map(|max| max as u64),
+        clients: state.clients.top(TOP_CLIENTS),
+    };
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(full(serde_json::to_string(&body)?))?;
+    Ok(response)
+}
+
+async fn stats(state: SharedState) -> Result<Response<BoxBody>> {
+    let body = {
+        let lb = state.load_balancer.read().await;
+        StatsResponse {
+            algorithm: lb.algorithm().to_string(),
+            servers: lb
+                .servers()
+                .iter()
+                .map(|server| ServerStats {
+                    address: server.get_address().to_string(),
+                    healthy: server.is_healthy(),
+                    connections: server.get_connections() as u64,
+                    bytes_in: server.bytes().bytes_in(),
+                    bytes_out: server.bytes().bytes_out(),
+                })
+                .collect(),
+            queue: state.queue.as_ref().map(FairQueue::stats),
+        }
+    };
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(full(serde_json::to_string(&body)?))?;
+    Ok(response)
+}
+
+// Requests forwarded to a worker that hasn't answered yet.
+async fn backend_requests(state: &State) -> u64 {
+    let lb = state.load_balancer.read().await;
+    lb.servers()
+        .iter()
+        .map(|server| server.get_connections() as u64)
+        .sum()
+}
+
+async fn drain(state: SharedState) -> Result<Response<BoxBody>> {
+    let body = state.drain.report(backend_requests(&state).await);
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(full(serde_json::to_string(&body)?))?;
+    Ok(response)
+}
+
+async fn health(state: SharedState) -> Result<Response<BoxBody>> {
+    let report = {
+        let lb = state.load_balancer.read().await;
+        health::report(&lb, state.health_min_backends, state.drain.is_draining())
+    };
+    let status = match report.status.is_ready() {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    let response = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(full(serde_json::to_string(&report)?))?;
+    Ok(response)
+}
+
+#[instrument(skip_all)]
+async fn forward_request(
+    req: Request<IncomingBody>,
+    state: SharedState,
+    client: IpAddr,
+) -> Result<Response<BoxBody>> {
+    let budget =
+        match RequestBudget::from_headers(req.headers(), state.request_timeout, Instant::now()) {
+            Ok(budget) => budget,
+            Err(msg) => {
+                warn!(msg);
+                let response = Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .header(header::CONTENT_TYPE, "text/plain")
+                    .body(full(msg))?;
+                return Ok(response);
+            }
+        };
+    // Not worth a worker's time when the client gives up before any could answer.
+    let timeout = match budget.attempt_timeout(Instant::now()) {
+        Ok(timeout) => timeout,
+        Err(exhausted) => return gateway_timeout(exhausted.to_string()),
+    };
+    // Held until the worker answered, like the server's connection count.
+    let _client_guard = match state.clients.acquire(client) {
+        Ok(guard) => guard,
+        Err(too_many) => {
+            let msg = too_many.to_string();
+            warn!(msg);
+            let response = Response::builder()
+                .status(StatusCode::TOO_MANY_REQUESTS)
+                .header(header::CONTENT_TYPE, "text/plain")
+                .body(full(msg))?;
+            return Ok(response);
+        }
+    };
+    // Held until the worker answered too, then handed to a waiting request of the next route.
+    let _permit = match &state.queue {
+        Some(queue) => {
+            let wait = match timeout {
+                Some(timeout) => timeout.min(state.queue_timeout),
+                None => state.queue_timeout,
+            };
+            match queue.acquire(queue::route(req.uri().path()), wait).await {
+                Ok(permit) => Some(permit),
+                Err(error) => {
+                    if let Err(exhausted) = budget.attempt_timeout(Instant::now()) {
+                        return gateway_timeout(exhausted.to_string());
+                    }
+                    let msg = error.to_string();
+                    warn!(msg);
+                    let response = Response::builder()
+                        .status(StatusCode::SERVICE_UNAVAILABLE)
+                        .header(header::CONTENT_TYPE, "text/plain")
+                        .body(full(msg))?;
+                    return Ok(response);
+                }
+            }
+        }
+        None => None,
+    };
+    // What's left after the wait in the queue.
+    let timeout = match budget.attempt_timeout(Instant::now()) {
+        Ok(timeout) => timeout,
+        Err(exhausted) => return gateway_timeout(exhausted.to_string()),
+    };
+
+    let key = state.hash_key.hash(req.headers(), client);
+    let (worker_addr, transfer) = {
+        let mut lb = state.load_balancer.write().await;
+        let server = lb.next_server(key);
+        (
+            server.get_address().to_string(),
+            Transfer::new(server.bytes().clone()),
+        )
+    };
+    // Counted against the server until the worker answered, failed or the client went away.
+    let backend_request = BackendRequest {
+        state: state.clone(),
+        worker_addr: worker_addr.clone(),
+    };
+
+    let worker_uri_string = format!(
+        "http://{}{}",
+        worker_addr,
+        req.uri()
+            .path_and_query()
+            .map(|x| x.as_str())
+            .unwrap_or("/")
+    );
+
+    let worker_uri = worker_uri_string.parse::<Uri>().expect("uri parse");
+
+    let headers = req.headers().clone();
+    let mut log = AccessLog {
+        method: req.method().to_string(),
+        path: req.uri().path().to_string(),
+        worker: worker_addr.clone(),
+        transfer: transfer.clone(),
+        budget: None,
+    };
+
+    let mut worker_req = Request::builder()
+        .method(req.method())
+        .uri(worker_uri)
+        .body(CountingBody::request(req.into_body(), transfer.clone()))
+        .expect("request builder");
+
+    for (key, value) in headers.iter() {
+        worker_req.headers_mut().append(key, value.clone());
+    }
+
+    // The budget covers connecting and waiting for the response's head, the body is streamed.
+    let attempt = send_to_worker(&worker_addr, worker_req);
+    let worker_res = match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, attempt).await {
+            Ok(worker_res) => worker_res?,
+            Err(_) => {
+                return gateway_timeout(format!(
+                    "No response from {} within the request budget, gave up after {}",
+                    worker_addr,
+                    budget.spent(Instant::now())
+                ));
+            }
+        },
+        None => attempt.await?,
+    };
+    let spent = budget.spent(Instant::now());
+    info!("Response from {} after {}", worker_addr, spent);
+    log.budget = Some(spent);
+
+    // Only the request body bytes sent so far are known before the response body streams.
+    let bytes_header = state.debug_bytes_header.then(|| {
+        let mut value = format!("in={}", transfer.bytes_in());
+        if let Some(length) = worker_res.headers().get(header::CONTENT_LENGTH) {
+            value.push_str(&format!(", out={}", length.to_str().unwrap_or("?")));
+        }
+        value
+    });
+
+    let (mut parts, worker_body) = worker_res.into_parts();
+    // The worker's connection isn't the client's.
+    for name in HOP_BY_HOP_HEADERS {
+        parts.headers.remove(name);
+    }
+    let worker_addr_for_body = worker_addr.clone();
+    // An aborted worker body is passed on as an aborted response, the client must not hang.
+    let res_body = CountingBody::response(worker_body, log)
+        .map_err(move |e| {
+            warn!("Response body from {} failed: {}", worker_addr_for_body, e);
+            e
+        })
+        .boxed();
+
+    drop(backend_request);
+
+    // The worker's status and headers, e.g. a simulated 500, go to the client as they are.
+    let mut response = Response::from_parts(parts, res_body);
+    if let Some(value) = bytes_header {
+        response
+            .headers_mut()
+            .insert(BYTES_HEADER, header::HeaderValue::from_str(&value)?);
+    }
+    Ok(response)
+}
+
+async fn send_to_worker(
+    worker_addr: &str,
+    worker_req: Request<CountingBody<IncomingBody>>,
+) -> Result<Response<IncomingBody>> {
+    let client_stream = match TcpStream::connect(worker_addr).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            error!("Failed to connect to {}: {:?}", worker_addr, e);
+            return Err(Box::new(e));
+        }
+    };
+    let io = TokioIo::new(client_stream);
+
+    let (mut sender, conn) = hyper::client::conn::http1::handshake(io).await?;
+    tokio::task::spawn(async move {
+        if let Err(err) = conn.await {
+            error!("Connection failed: {:?}", err);
+        }
+    });
+
+    info!("Forwarding request to {}", worker_addr);
+    Ok(sender.send_request(worker_req).await?)
+}
+
+// A request forwarded to a worker, taken off its server's connection count when dropped.
+struct BackendRequest {
+    state: SharedState,
+    worker_addr: String,
+}
+
+impl Drop for BackendRequest {
+    fn drop(&mut self) {
+        let worker_addr = std::mem::take(&mut self.worker_addr);
+        if let Ok(mut lb) = self.state.load_balancer.try_write() {
+            release(&mut lb, &worker_addr);
+            return;
+        }
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            release(&mut *state.load_balancer.write().await, &worker_addr);
+        });
+    }
+}
+
+fn release(lb: &mut LoadBalancer, worker_addr: &str) {
+    if let Some(server) = lb.get_server_by_address(worker_addr) {
+        server.decrement_connections();
+    }
+}
+
+fn gateway_timeout(msg: String) -> Result<Response<BoxBody>> {
+    warn!(msg);
+    let response = Response::builder()
+        .status(StatusCode::GATEWAY_TIMEOUT)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(full(msg))?;
+    Ok(response)
+}
+
+fn full<T: Into<Bytes>>(chunk: T) -> BoxBody {
+    Full::new(chunk.into())
+        .map_err(|never| match never {})
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use environment::Environment;
+    use lb_api::{BalancerHealth, DrainResponse, HealthResponse, ServerStats, StatsResponse};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::sync::oneshot;
+
+    // A balancer in front of `workers`, listening on a free port until the sender is dropped.
+    async fn balancer(workers: &[String]) -> (std::net::SocketAddr, oneshot::Sender<()>) {
+        let (addr, stop, _) = balancer_with(workers, Config::parse_from(["load-balancer"])).await;
+        (addr, stop)
+    }
+
+    // Like `balancer` with `config`, and the task serving it.
+    async fn balancer_with(
+        workers: &[String],
+        config: Config,
+    ) -> (
+        std::net::SocketAddr,
+        oneshot::Sender<()>,
+        tokio::task::JoinHandle<Result<()>>,
+    ) {
+        let servers = workers
+            .iter()
+            .map(|address| Server::new(address.clone()).unwrap())
+            .collect();
+        let state = State::new(LoadBalancer::new(servers).unwrap(), &config);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stop, stopped) = oneshot::channel::<()>();
+        let served = tokio::spawn(serve_listener(listener, state, async {
+            let _ = stopped.await;
+        }));
+        (addr, stop, served)
+    }
+
+    // The whole response, empty when the connection closed without one.
+    async fn get_response(addr: std::net::SocketAddr, path: &str) -> String {
+        get_response_from([127, 0, 0, 1].into(), addr, path).await
+    }
+
+    // Like `get_response`, connecting from `client`.
+    async fn get_response_from(client: IpAddr, addr: std::net::SocketAddr, path: &str) -> String {
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: lb\r\nConnection: close\r\n\r\n",
+            path
+        );
+        exchange(client, addr, &request).await
+    }
+
+    // The whole response to a `method` request with `headers`, e.g. `Origin: http://ui`.
+    async fn send(
+        addr: std::net::SocketAddr,
+        method: &str,
+        path: &str,
+        headers: &[&str],
+    ) -> String {
+        send_body(addr, method, path, headers, "").await
+    }
+
+    // Like `send`, `body` going as it is after the head, framed by the headers.
+    async fn send_body(
+        addr: std::net::SocketAddr,
+        method: &str,
+        path: &str,
+        headers: &[&str],
+        body: &str,
+    ) -> String {
+        let mut request = format!("{} {} HTTP/1.1\r\nHost: lb\r\n", method, path);
+        for header in headers {
+            request.push_str(&format!("{}\r\n", header));
+        }
+        request.push_str("Connection: close\r\n\r\n");
+        request.push_str(body);
+        exchange([127, 0, 0, 1].into(), addr, &request).await
+    }
+
+    // Writes `request` from `client`, then reads until the connection closes.
+    async fn exchange(client: IpAddr, addr: std::net::SocketAddr, request: &str) -> String {
+        let socket = tokio::net::TcpSocket::new_v4().unwrap();
+        socket.bind((client, 0).into()).unwrap();
+        let mut stream = socket.connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response).await;
+        response
+    }
+
+    // The response's body, empty when the connection closed without one.
+    async fn get(addr: std::net::SocketAddr, path: &str) -> String {
+        match get_response(addr, path).await.split_once("\r\n\r\n") {
+            Some((_, body)) => body.to_string(),
+            None => String::new(),
+        }
+    }
+
+    // A worker answering every request with `response` as it is.
+    async fn stub_worker(response: &'static str) -> String {
+        slow_worker(Duration::ZERO, response).await
+    }
+
+    // Like `stub_worker`, answering `delay` after each request came in.
+    async fn slow_worker(delay: Duration, response: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut request = [0; 1024];
+                    let _ = stream.read(&mut request).await;
+                    tokio::time::sleep(delay).await;
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        addr
+    }
+
+    // Polls GET /lb/drain until `done` holds for its report.
+    async fn drain_until(
+        addr: std::net::SocketAddr,
+        done: impl Fn(&DrainResponse) -> bool,
+    ) -> DrainResponse {
+        loop {
+            let body = get(addr, "/lb/drain").await;
+            let report = serde_json::from_str::<DrainResponse>(&body).unwrap();
+            if done(&report) {
+                return report;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    // A worker answering GET /ready with 200 while `ready` is set and 503 otherwise, anything
+    // else with 200 since it's alive.
+    async fn readiness_worker(ready: Arc<AtomicBool>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = [0; 1024];
+                let read = stream.read(&mut request).await.unwrap_or(0);
+                let not_ready =
+                    request[..read].starts_with(b"GET /ready ") && !ready.load(Ordering::SeqCst);
+                let response = match not_ready {
+                    true => "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n",
+                    false => OK,
+                };
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        addr
+    }
+
+    // Polls GET /lb/stats until the `index`th worker's health check result is `healthy`.
+    async fn worker_healthy(addr: std::net::SocketAddr, index: usize, healthy: bool) {
+        let polled = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let body = get(addr, "/lb/stats").await;
+                let stats = serde_json::from_str::<StatsResponse>(&body).unwrap();
+                if stats.servers[index].healthy == healthy {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+        assert!(
+            polled.await.is_ok(),
+            "The worker never became healthy: {}",
+            !healthy
+        );
+    }
+
+    // A worker reading each request to the end of its body, Content-Length or chunked, before
+    // answering it with `response`.
+    async fn reading_worker(response: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !whole_request(&request) {
+                    match stream.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(read) => request.extend_from_slice(&buf[..read]),
+                    }
+                }
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        addr
+    }
+
+    // Like `reading_worker`, passing on every request it read.
+    async fn recording_worker() -> (String, tokio::sync::mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (requests, received) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !whole_request(&request) {
+                    match stream.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(read) => request.extend_from_slice(&buf[..read]),
+                    }
+                }
+                let _ = requests.send(String::from_utf8_lossy(&request).to_string());
+                let _ = stream.write_all(OK.as_bytes()).await;
+            }
+        });
+        (addr, received)
+    }
+
+    fn whole_request(request: &[u8]) -> bool {
+        let request = String::from_utf8_lossy(request).to_lowercase();
+        let Some((head, body)) = request.split_once("\r\n\r\n") else {
+            return false;
+        };
+        match head.split_once("content-length: ") {
+            Some((_, length)) => {
+                let length = length.lines().next().unwrap().parse::<usize>().unwrap();
+                body.len() >= length
+            }
+            None if head.contains("transfer-encoding: chunked") => body.ends_with("0\r\n\r\n"),
+            None => true,
+        }
+    }
+
+    async fn server_stats(addr: std::net::SocketAddr) -> ServerStats {
+        let body = get(addr, "/lb/stats").await;
+        let stats = serde_json::from_str::<StatsResponse>(&body).unwrap();
+        stats.servers.into_iter().next().unwrap()
+    }
+
+    // An address nothing listens on.
+    async fn closed_port() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn failed_forwards_release_their_server() {
+        let worker = closed_port().await;
+        let (addr, _stop) = balancer(std::slice::from_ref(&worker)).await;
+
+        for _ in 0..3 {
+            get(addr, "/work").await;
+        }
+
+        let stats = serde_json::from_str::<StatsResponse>(&get(addr, "/lb/stats").await).unwrap();
+        assert_eq!(stats.servers[0].address, worker);
+        assert_eq!(stats.servers[0].connections, 0);
+    }
+
+    #[tokio::test]
+    async fn the_workers_status_and_headers_are_passed_on() {
+        let worker = stub_worker(
+            "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 4\r\nx-worker: w1\r\n\r\nbusy",
+        )
+        .await;
+        let (addr, _stop) = balancer(&[worker]).await;
+
+        let response = get_response(addr, "/work").await;
+        assert!(response.starts_with("HTTP/1.1 503"), "{}", response);
+        assert!(response.contains("x-worker: w1\r\n"), "{}", response);
+        assert!(response.ends_with("\r\n\r\nbusy"), "{}", response);
+    }
+
+    const OK: &str = "HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok";
+
+    #[tokio::test]
+    async fn draining_counts_the_slow_requests_down_as_they_finish() {
+        let workers = [
+            slow_worker(Duration::from_millis(200), OK).await,
+            slow_worker(Duration::from_millis(600), OK).await,
+        ];
+        let config = Config::parse_from(["load-balancer", "--shutdown-drain-ms", "5000"]);
+        let (addr, stop, served) = balancer_with(&workers, config).await;
+        let requests = [
+            tokio::spawn(get(addr, "/work")),
+            tokio::spawn(get(addr, "/work")),
+        ];
+        loop {
+            let body = get(addr, "/lb/stats").await;
+            let stats = serde_json::from_str::<StatsResponse>(&body).unwrap();
+            if stats.servers.iter().all(|server| server.connections == 1) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        drop(stop);
+        let started = drain_until(addr, |report| report.draining).await;
+        // The connection asking is counted too.
+        assert_eq!((started.connections, started.backend_requests), (3, 2));
+        assert_eq!(started.deadline_ms, Some(5000));
+        let halfway = drain_until(addr, |report| report.backend_requests < 2).await;
+        assert_eq!((halfway.connections, halfway.backend_requests), (2, 1));
+        assert!(halfway.elapsed_ms >= started.elapsed_ms);
+        assert_eq!(halfway.aborted, None);
+
+        for request in requests {
+            assert_eq!(request.await.unwrap(), "ok");
+        }
+        let served = tokio::time::timeout(Duration::from_secs(5), served).await;
+        assert!(matches!(served, Ok(Ok(Ok(())))));
+    }
+
+    #[tokio::test]
+    async fn requests_still_in_flight_at_the_drain_deadline_are_aborted() {
+        let worker = slow_worker(Duration::from_secs(30), OK).await;
+        let config = Config::parse_from(["load-balancer", "--shutdown-drain-ms", "100"]);
+        let (addr, stop, served) = balancer_with(&[worker], config).await;
+        let request = tokio::spawn(get_response(addr, "/work"));
+        drain_until(addr, |report| report.backend_requests == 1).await;
+
+        drop(stop);
+        let served = tokio::time::timeout(Duration::from_secs(5), served).await;
+        assert!(matches!(served, Ok(Ok(Ok(())))));
+        // Closed without an answer instead of waiting for the worker.
+        assert_eq!(request.await.unwrap(), "");
+    }
+
+    #[tokio::test]
+    async fn workers_are_only_healthy_while_ready() {
+        let ready = Arc::new(AtomicBool::new(false));
+        let worker = readiness_worker(ready.clone()).await;
+        let config = Config::parse_from(["load-balancer", "--health-check-interval-ms", "20"]);
+        let (addr, _stop, _) = balancer_with(&[worker], config).await;
+
+        // Warming up, then ready, then marked not ready.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        worker_healthy(addr, 0, false).await;
+        ready.store(true, Ordering::SeqCst);
+        worker_healthy(addr, 0, true).await;
+        ready.store(false, Ordering::SeqCst);
+        worker_healthy(addr, 0, false).await;
+    }
+
+    #[tokio::test]
+    async fn lb_health_follows_the_workers_and_the_drain() {
+        let ready = Arc::new(AtomicBool::new(false));
+        let workers = [
+            slow_worker(Duration::from_secs(2), OK).await,
+            readiness_worker(ready.clone()).await,
+        ];
+        let config = Config::parse_from([
+            "load-balancer",
+            "--health-check-interval-ms",
+            "20",
+            "--shutdown-drain-ms",
+            "5000",
+        ]);
+        let (addr, stop, _) = balancer_with(&workers, config).await;
+        let health = || async {
+            let response = get_response(addr, "/lb/health").await;
+            let (head, body) = response.split_once("\r\n\r\n").unwrap();
+            let status = head[9..12].parse::<u16>().unwrap();
+            (
+                status,
+                serde_json::from_str::<HealthResponse>(body).unwrap(),
+            )
+        };
+
+        let (status, unavailable) = health().await;
+        assert_eq!(
+            (status, unavailable.status),
+            (503, BalancerHealth::Unavailable)
+        );
+        assert_eq!(unavailable.backends.len(), 2);
+
+        // The slow worker never answers its health check in time.
+        ready.store(true, Ordering::SeqCst);
+        worker_healthy(addr, 1, true).await;
+        let (status, degraded) = health().await;
+        assert_eq!((status, degraded.status), (200, BalancerHealth::Degraded));
+
+        // Keeps the drain going.
+        let _request = tokio::spawn(get(addr, "/work"));
+        drain_until(addr, |report| report.backend_requests == 1).await;
+        drop(stop);
+        drain_until(addr, |report| report.draining).await;
+        let (status, draining) = health().await;
+        assert_eq!((status, draining.status), (503, BalancerHealth::Draining));
+        assert_eq!(draining.backends[1].address, workers[1]);
+        assert!(draining.backends[1].healthy);
+    }
+
+    #[tokio::test]
+    async fn the_health_check_path_is_configurable() {
+        let worker = readiness_worker(Arc::default()).await;
+        let config = Config::parse_from([
+            "load-balancer",
+            "--health-check-interval-ms",
+            "20",
+            "--health-check-path",
+            "/health",
+        ]);
+        let (addr, _stop, _) = balancer_with(&[worker], config).await;
+
+        worker_healthy(addr, 0, true).await;
+    }
+
+    #[tokio::test]
+    async fn only_the_greedy_client_is_told_to_back_off() {
+        let worker = slow_worker(Duration::from_millis(300), OK).await;
+        let config = Config::parse_from([
+            "load-balancer",
+            "--max-client-in-flight",
+            "2",
+            "--client-limit-exempt",
+            "127.0.0.3/32",
+        ]);
+        let (addr, _stop, _) = balancer_with(&[worker], config).await;
+        let (greedy, polite, exempt) = ([127, 0, 0, 1], [127, 0, 0, 2], [127, 0, 0, 3]);
+
+        let requests = [
+            greedy, greedy, greedy, greedy, polite, exempt, exempt, exempt,
+        ]
+        .map(|client| tokio::spawn(get_response_from(client.into(), addr, "/work")));
+        let mut refused = Vec::new();
+        for (i, request) in requests.into_iter().enumerate() {
+            if request.await.unwrap().starts_with("HTTP/1.1 429") {
+                refused.push(i);
+            }
+        }
+
+        assert_eq!(refused.len(), 2, "{:?}", refused);
+        assert!(refused.iter().all(|&i| i < 4), "{:?}", refused);
+        let again = get_response_from(greedy.into(), addr, "/work").await;
+        assert!(again.starts_with("HTTP/1.1 200"), "{}", again);
+    }
+
+    // The value of the response's `name` header, None without it.
+    fn header_value<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+        let head = response
+            .split_once("\r\n\r\n")
+            .map_or(response, |(head, _)| head);
+        head.lines()
+            .filter_map(|line| line.split_once(": "))
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    // A balancer in front of `worker` letting `origins` call its admin routes.
+    async fn cors_balancer(
+        origins: &str,
+        worker: String,
+    ) -> (std::net::SocketAddr, oneshot::Sender<()>) {
+        let config = Config::parse_from(["load-balancer", "--cors-allowed-origins", origins]);
+        let (addr, stop, _) = balancer_with(&[worker], config).await;
+        (addr, stop)
+    }
+
+    #[tokio::test]
+    async fn admin_preflights_are_answered_for_allowed_origins() {
+        let (addr, _stop) = cors_balancer("http://ui:5173", stub_worker(OK).await).await;
+
+        let preflight = [
+            "Origin: http://ui:5173",
+            "Access-Control-Request-Method: GET",
+        ];
+        let allowed = send(addr, "OPTIONS", "/lb/stats", &preflight).await;
+        assert!(allowed.starts_with("HTTP/1.1 204"), "{}", allowed);
+        let header = |name| header_value(&allowed, name);
+        assert_eq!(
+            header("access-control-allow-origin"),
+            Some("http://ui:5173")
+        );
+        assert_eq!(
+            header("access-control-allow-methods"),
+            Some("GET, POST, PUT")
+        );
+        assert_eq!(header("access-control-allow-headers"), Some("content-type"));
+        assert_eq!(header("access-control-max-age"), Some("600"));
+        assert_eq!(header("vary"), Some("Origin"));
+
+        let refused = send(addr, "OPTIONS", "/lb/stats", &["Origin: http://evil"]).await;
+        assert!(refused.starts_with("HTTP/1.1 403"), "{}", refused);
+        assert_eq!(header_value(&refused, "access-control-allow-origin"), None);
+    }
+
+    #[tokio::test]
+    async fn admin_responses_let_only_allowed_origins_read_them() {
+        let (addr, _stop) = cors_balancer("http://ui:5173", stub_worker(OK).await).await;
+
+        let allowed = send(addr, "GET", "/lb/stats", &["Origin: http://ui:5173"]).await;
+        assert!(allowed.starts_with("HTTP/1.1 200"), "{}", allowed);
+        assert_eq!(
+            header_value(&allowed, "access-control-allow-origin"),
+            Some("http://ui:5173")
+        );
+
+        // Still answered, the browser is what keeps the page from reading it.
+        let other = send(addr, "GET", "/lb/stats", &["Origin: http://evil"]).await;
+        assert!(other.starts_with("HTTP/1.1 200"), "{}", other);
+        assert_eq!(header_value(&other, "access-control-allow-origin"), None);
+        let no_origin = send(addr, "GET", "/lb/stats", &[]).await;
+        assert_eq!(
+            header_value(&no_origin, "access-control-allow-origin"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn any_origin_is_allowed_with_the_wildcard() {
+        let (addr, _stop) = cors_balancer("*", stub_worker(OK).await).await;
+
+        let response = send(addr, "GET", "/lb/stats", &["Origin: http://anywhere"]).await;
+        assert_eq!(
+            header_value(&response, "access-control-allow-origin"),
+            Some("*")
+        );
+        let preflight = send(addr, "OPTIONS", "/algo", &["Origin: http://anywhere"]).await;
+        assert!(preflight.starts_with("HTTP/1.1 204"), "{}", preflight);
+        assert_eq!(
+            header_value(&preflight, "access-control-allow-origin"),
+            Some("*")
+        );
+    }
+
+    #[tokio::test]
+    async fn forwarded_requests_keep_the_workers_cors_headers() {
+        let worker = stub_worker(
+            "HTTP/1.1 200 OK\r\naccess-control-allow-origin: http://worker\r\ncontent-length: 2\r\n\r\nok",
+        )
+        .await;
+        let (addr, _stop) = cors_balancer("*", worker).await;
+
+        let response = send(addr, "GET", "/work", &["Origin: http://anywhere"]).await;
+        assert_eq!(
+            header_value(&response, "access-control-allow-origin"),
+            Some("http://worker")
+        );
+        assert_eq!(header_value(&response, "vary"), None);
+
+        // Preflights for worker routes are the worker's to answer too.
+        let preflight = send(addr, "OPTIONS", "/work", &["Origin: http://anywhere"]).await;
+        assert!(preflight.starts_with("HTTP/1.1 200"), "{}", preflight);
+        assert_eq!(
+            header_value(&preflight, "access-control-allow-origin"),
+            Some("http://worker")
+        );
+        assert!(preflight.ends_with("\r\n\r\nok"), "{}", preflight);
+    }
+
+    #[tokio::test]
+    async fn forwarded_requests_get_no_cors_headers_of_the_balancer() {
+        let (addr, _stop) = cors_balancer("*", stub_worker(OK).await).await;
+
+        let response = send(addr, "GET", "/work", &["Origin: http://anywhere"]).await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert_eq!(header_value(&response, "access-control-allow-origin"), None);
+    }
+
+    #[tokio::test]
+    async fn known_lengths_are_counted_for_the_server_and_the_header() {
+        let worker = reading_worker(OK).await;
+        let config = Config::parse_from(["load-balancer", "--debug-bytes-header"]);
+        let (addr, _stop, _) = balancer_with(&[worker], config).await;
+
+        let headers = ["Content-Length: 11"];
+        let response = send_body(addr, "POST", "/work", &headers, "hello world").await;
+        assert!(response.ends_with("\r\n\r\nok"), "{}", response);
+        assert_eq!(header_value(&response, "x-lb-bytes"), Some("in=11, out=2"));
+
+        let stats = server_stats(addr).await;
+        assert_eq!((stats.bytes_in, stats.bytes_out), (11, 2));
+    }
+
+    #[tokio::test]
+    async fn repeated_request_headers_all_reach_the_worker() {
+        let (worker, mut received) = recording_worker().await;
+        let (addr, _stop) = balancer(&[worker]).await;
+
+        let headers = ["X-Many: one", "X-Many: two", "X-Custom: value"];
+        let response = send(addr, "GET", "/work?a=1", &headers).await;
+        assert!(response.ends_with("\r\n\r\nok"), "{}", response);
+
+        let request = received.recv().await.unwrap().to_lowercase();
+        assert!(request.contains("/work?a=1 http/1.1\r\n"), "{}", request);
+        assert!(request.contains("x-many: one\r\n"), "{}", request);
+        assert!(request.contains("x-many: two\r\n"), "{}", request);
+        assert!(request.contains("x-custom: value\r\n"), "{}", request);
+    }
+
+    #[tokio::test]
+    async fn simulation_override_headers_reach_the_worker() {
+        let (worker, mut received) = recording_worker().await;
+        let (addr, _stop) = balancer(&[worker]).await;
+
+        let headers = [
+            "X-Simulate-Delay-Ms: 250",
+            "X-Simulate-Status: 503",
+            "X-Simulate-Error: true",
+        ];
+        send(addr, "GET", "/work", &headers).await;
+
+        let request = received.recv().await.unwrap().to_lowercase();
+        for header in headers {
+            assert!(
+                request.contains(&format!("{}\r\n", header.to_lowercase())),
+                "{} missing from {}",
+                header,
+                request
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn chunked_bodies_are_counted_without_their_framing() {
+        let worker = reading_worker(
+            "HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n2\r\nok\r\n3\r\n!!!\r\n0\r\n\r\n",
+        )
+        .await;
+        let config = Config::parse_from(["load-balancer", "--debug-bytes-header"]);
+        let (addr, _stop, _) = balancer_with(&[worker], config).await;
+
+        let headers = ["Transfer-Encoding: chunked"];
+        let body = "5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n";
+        let response = send_body(addr, "POST", "/work", &headers, body).await;
+        assert_eq!(
+            header_value(&response, "transfer-encoding"),
+            Some("chunked")
+        );
+        // No Content-Length to tell the response's size before it streamed.
+        assert_eq!(header_value(&response, "x-lb-bytes"), Some("in=11"));
+
+        let stats = server_stats(addr).await;
+        assert_eq!((stats.bytes_in, stats.bytes_out), (11, 5));
+    }
+
+    #[tokio::test]
+    async fn the_bytes_header_is_only_added_when_asked_for() {
+        let (addr, _stop) = balancer(&[reading_worker(OK).await]).await;
+
+        let response = send_body(addr, "POST", "/work", &["Content-Length: 3"], "abc").await;
+        assert_eq!(header_value(&response, "x-lb-bytes"), None);
+        let stats = server_stats(addr).await;
+        assert_eq!((stats.bytes_in, stats.bytes_out), (3, 2));
+    }
+
+    #[tokio::test]
+    async fn response_bodies_stream_through_as_they_come() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let worker = listener.local_addr().unwrap().to_string();
+        let (finish, finished) = oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).await;
+            let head = "HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n";
+            stream.write_all(head.as_bytes()).await.unwrap();
+            stream.write_all(b"5\r\nfirst\r\n").await.unwrap();
+            let _ = finished.await;
+            stream.write_all(b"4\r\nlast\r\n0\r\n\r\n").await.unwrap();
+        });
+        let (addr, _stop) = balancer(&[worker]).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = "GET /work HTTP/1.1\r\nHost: lb\r\nConnection: close\r\n\r\n";
+        stream.write_all(request.as_bytes()).await.unwrap();
+        // The first chunk arrives while the worker holds back the rest.
+        let mut response = Vec::new();
+        let mut buf = [0; 1024];
+        while !String::from_utf8_lossy(&response).contains("first") {
+            let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf))
+                .await
+                .expect("the first chunk before the last one was sent")
+                .unwrap();
+            assert!(read > 0);
+            response.extend_from_slice(&buf[..read]);
+        }
+        assert_eq!(server_stats(addr).await.bytes_out, 5);
+
+        finish.send(()).unwrap();
+        stream.read_to_end(&mut response).await.unwrap();
+        assert!(String::from_utf8_lossy(&response).contains("last"));
+        assert_eq!(server_stats(addr).await.bytes_out, 9);
+    }
+
+    // The response's status code and body.
+    async fn send_json(
+        addr: std::net::SocketAddr,
+        method: &str,
+        path: &str,
+        body: &str,
+    ) -> (u16, String) {
+        let length = format!("Content-Length: {}", body.len());
+        let response = send_body(addr, method, path, &[&length], body).await;
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head[9..12].parse().unwrap(), body.to_string())
+    }
+
+    async fn algorithms_response(addr: std::net::SocketAddr) -> AlgorithmsResponse {
+        serde_json::from_str(&get(addr, "/lb/algorithms").await).unwrap()
+    }
+
+    #[tokio::test]
+    async fn disallowed_algorithms_are_forbidden() {
+        let worker = stub_worker(OK).await;
+        let config = Config::parse_from([
+            "load-balancer",
+            "--allowed-algorithms",
+            "round_robin,least_connections",
+        ]);
+        let (addr, _stop, _) = balancer_with(&[worker], config).await;
+
+        let (status, body) = send_json(addr, "POST", "/algo", r#"{"algo": "maglev"}"#).await;
+        assert_eq!(status, 403);
+        assert_eq!(
+            body,
+            "Algorithm 'maglev' is disabled, allowed are round_robin,least_connections"
+        );
+        assert_eq!(algorithms_response(addr).await.current, "round_robin");
+
+        let (status, _) =
+            send_json(addr, "POST", "/algo", r#"{"algo": "least_connections"}"#).await;
+        assert_eq!(status, 200);
+        assert_eq!(algorithms_response(addr).await.current, "least_connections");
+        let (status, _) = send_json(addr, "POST", "/algo", r#"{"algo": "random"}"#).await;
+        assert_eq!(status, 400);
+    }
+
+    #[tokio::test]
+    async fn algorithms_are_listed_with_their_enabled_flags() {
+        let worker = stub_worker(OK).await;
+        let config = Config::parse_from(["load-balancer", "--allowed-algorithms", "maglev"]);
+        let (addr, _stop, _) = balancer_with(&[worker], config).await;
+
+        let listed = algorithms_response(addr).await;
+        // The first allowed one, round robin isn't.
+        assert_eq!(listed.current, "maglev");
+        let flags = listed
+            .algorithms
+            .iter()
+            .map(|a| (a.name.as_str(), a.enabled))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            flags,
+            [
+                ("round_robin", false),
+                ("least_connections", false),
+                ("maglev", true)
+            ]
+        );
+        assert!(listed.algorithms.iter().all(|a| !a.description.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn allowed_algorithms_change_at_runtime() {
+        let (addr, _stop) = balancer(&[stub_worker(OK).await]).await;
+
+        let body = r#"{"algorithms": ["least_connections", "maglev"]}"#;
+        let (status, body) = send_json(addr, "PUT", "/lb/algorithms", body).await;
+        assert_eq!(status, 200);
+        let listed = serde_json::from_str::<AlgorithmsResponse>(&body).unwrap();
+        assert_eq!(listed, algorithms_response(addr).await);
+        assert_eq!(listed.current, "least_connections");
+        assert!(!listed.algorithms[0].enabled);
+        let (status, _) = send_json(addr, "POST", "/algo", r#"{"algo": "round_robin"}"#).await;
+        assert_eq!(status, 403);
+
+        for invalid in [
+            r#"{"algorithms": []}"#,
+            r#"{"algorithms": ["random"]}"#,
+            "{}",
+        ] {
+            let (status, _) = send_json(addr, "PUT", "/lb/algorithms", invalid).await;
+            assert_eq!(status, 400, "{}", invalid);
+        }
+        assert_eq!(algorithms_response(addr).await, listed);
+    }
+
+    #[tokio::test]
+    async fn serve_runs_on_a_free_port_until_shutdown() {
+        let config = Config::parse_from(["load-balancer", "--port", "0"]);
+        let topology = Topology::new(Environment::Local).with_worker_count(1);
+
+        let served = serve(
+            &config,
+            &topology,
+            tokio::time::sleep(Duration::from_millis(50)),
+        );
+        let served = tokio::time::timeout(Duration::from_secs(5), served).await;
+        assert!(matches!(served, Ok(Ok(()))));
+    }
+
+    #[tokio::test]
+    async fn serve_reports_a_port_in_use() {
+        let taken = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = taken.local_addr().unwrap().port().to_string();
+        let config = Config::parse_from(["load-balancer", "--port", &port]);
+        let topology = Topology::new(Environment::Local).with_worker_count(1);
+
+        assert!(serve(&config, &topology, std::future::pending())
+            .await
+            .is_err());
+    }
+}
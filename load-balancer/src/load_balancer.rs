@@ -1,14 +1,22 @@
-use crate::{balancing_algorithm::BalancingAlgorithm, server::Server};
+use crate::balancing_algorithm::{Algorithms, BalancingAlgorithm};
+use crate::maglev::{Maglev, DEFAULT_TABLE_SIZE};
+use crate::server::Server;
 use chrono::{DateTime, Utc};
 use tracing::info;
 
 const MIN_SECONDS_BETWEEN_ALGO_CHANGES: u64 = 5;
+// Round robin gives way to least connections past this difference between the busiest and the
+// idlest server, and comes back once it's down to the other one.
+const SPREAD_FOR_LEAST_CONNECTIONS: usize = 3;
+const SPREAD_FOR_ROUND_ROBIN: usize = 1;
 
 #[derive(Debug)]
 pub struct LoadBalancer {
     servers: Vec<Server>,
     current_server: usize,
     algorithm: BalancingAlgorithm,
+    allowed: Algorithms,
+    maglev: Maglev,
     last_check: DateTime<Utc>,
 }
 
@@ -22,11 +30,14 @@ impl LoadBalancer {
             servers,
             current_server: 0,
             algorithm: BalancingAlgorithm::RoundRobin,
+            allowed: Algorithms::all(),
+            maglev: Maglev::new(DEFAULT_TABLE_SIZE),
             last_check: Utc::now(),
         })
     }
 
-    pub fn next_server(&mut self) -> &Server {
+    /// `key` is the request's hash key, only Maglev goes by it.
+    pub fn next_server(&mut self, key: u64) -> &Server {
         self.check_conditions_and_set_best_algo();
 
         match self.algorithm {
@@ -37,25 +48,64 @@ impl LoadBalancer {
                 server.increment_connections();
                 server
             }
+            // Ties are broken by rotating from the last pick, idle servers would otherwise
+            // all lose to the first one.
             BalancingAlgorithm::LeastConnections => {
-                let (index, _) = self
-                    .servers
-                    .iter()
-                    .enumerate()
-                    .min_by_key(|(_, s)| s.get_connections())
+                let min_connections = self.servers.iter().map(Server::get_connections).min();
+                let servers_count = self.servers.len();
+                let index = (1..=servers_count)
+                    .map(|offset| (self.current_server + offset) % servers_count)
+                    .find(|&i| Some(self.servers[i].get_connections()) == min_connections)
                     .unwrap();
                 self.current_server = index;
                 let server = &mut self.servers[self.current_server];
                 server.increment_connections();
                 server
             }
+            BalancingAlgorithm::Maglev => {
+                self.maglev.update(&self.servers);
+                self.current_server = self.maglev.lookup(key);
+                let server = &mut self.servers[self.current_server];
+                server.increment_connections();
+                server
+            }
         }
     }
 
+    pub fn servers(&self) -> &[Server] {
+        &self.servers
+    }
+
+    pub fn algorithm(&self) -> BalancingAlgorithm {
+        self.algorithm
+    }
+
     pub fn set_algorithm(&mut self, algorithm: BalancingAlgorithm) {
         self.algorithm = algorithm;
     }
 
+    pub fn allowed(&self) -> &Algorithms {
+        &self.allowed
+    }
+
+    /// Switches to the first allowed algorithm when the current one no longer is.
+    pub fn set_allowed(&mut self, allowed: Algorithms) {
+        if !allowed.contains(self.algorithm) {
+            info!(
+                "Algorithm changed to {} since {} is no longer allowed",
+                allowed.first(),
+                self.algorithm
+            );
+            self.algorithm = allowed.first();
+        }
+        self.allowed = allowed;
+    }
+
+    /// `size` must be prime, the table is rebuilt on the next Maglev pick.
+    pub fn set_maglev_table_size(&mut self, size: usize) {
+        self.maglev = Maglev::new(size);
+    }
+
     pub fn get_server_by_address(&mut self, address: &str) -> Option<&mut Server> {
         self.servers.iter_mut().find(|s| s.get_address() == address)
     }
@@ -66,36 +116,21 @@ impl LoadBalancer {
         }
 
         let mut recommended_algo = self.algorithm;
+        let spread = self.connection_spread();
 
         match self.algorithm {
             BalancingAlgorithm::RoundRobin => {
-                let max_connections = self
-                    .servers
-                    .iter()
-                    .max_by_key(|s| s.get_connections())
-                    .unwrap()
-                    .get_connections();
-                let min_connections = self
-                    .servers
-                    .iter()
-                    .min_by_key(|s| s.get_connections())
-                    .unwrap()
-                    .get_connections();
-                if max_connections - min_connections > 3 {
+                if spread > SPREAD_FOR_LEAST_CONNECTIONS {
                     recommended_algo = BalancingAlgorithm::LeastConnections;
                 }
             }
             BalancingAlgorithm::LeastConnections => {
-                let (new_server, _) = self
-                    .servers
-                    .iter()
-                    .enumerate()
-                    .min_by_key(|(_, s)| s.get_connections())
-                    .unwrap();
-                if new_server == self.current_server {
+                if spread <= SPREAD_FOR_ROUND_ROBIN {
                     recommended_algo = BalancingAlgorithm::RoundRobin;
                 }
             }
+            // Picked for its affinity, which switching away would break.
+            BalancingAlgorithm::Maglev => {}
         }
 
         let now = Utc::now();
@@ -104,7 +139,7 @@ impl LoadBalancer {
             return;
         }
 
-        if recommended_algo == self.algorithm {
+        if recommended_algo == self.algorithm || !self.allowed.contains(recommended_algo) {
             return;
         }
 
@@ -117,4 +152,149 @@ impl LoadBalancer {
             recommended_algo
         );
     }
+
+    // Connections of the busiest server minus those of the idlest one.
+    fn connection_spread(&self) -> usize {
+        let connections = self.servers.iter().map(Server::get_connections);
+        connections.clone().max().unwrap_or(0) - connections.min().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn load_balancer(servers: usize, algorithm: BalancingAlgorithm) -> LoadBalancer {
+        let servers = (0..servers)
+            .map(|i| Server::new(format!("127.0.0.1:{}", 3000 + i)).unwrap())
+            .collect();
+        let mut lb = LoadBalancer::new(servers).unwrap();
+        lb.set_algorithm(algorithm);
+        lb
+    }
+
+    // Lets the next pick switch algorithms, the last switch was long enough ago.
+    fn switch_allowed(lb: &mut LoadBalancer) {
+        lb.last_check = Utc::now() - Duration::seconds(MIN_SECONDS_BETWEEN_ALGO_CHANGES as i64);
+    }
+
+    #[test]
+    fn idle_servers_take_turns_under_least_connections() {
+        let mut lb = load_balancer(3, BalancingAlgorithm::LeastConnections);
+        lb.set_allowed(Algorithms::parse("least_connections").unwrap());
+        let mut picks = [0; 3];
+
+        // Every request answered before the next one comes in, so all servers stay tied at zero.
+        for _ in 0..300 {
+            let address = lb.next_server(0).get_address().to_string();
+            let index = lb
+                .servers()
+                .iter()
+                .position(|s| s.get_address() == address)
+                .unwrap();
+            picks[index] += 1;
+            lb.get_server_by_address(&address)
+                .unwrap()
+                .decrement_connections();
+        }
+
+        assert!(
+            picks.iter().all(|&count| (90..=110).contains(&count)),
+            "{:?}",
+            picks
+        );
+    }
+
+    #[test]
+    fn the_fewest_connections_still_win_over_the_turn() {
+        let mut lb = load_balancer(3, BalancingAlgorithm::LeastConnections);
+        lb.set_allowed(Algorithms::parse("least_connections").unwrap());
+        for _ in 0..2 {
+            lb.get_server_by_address("127.0.0.1:3000")
+                .unwrap()
+                .increment_connections();
+        }
+        lb.get_server_by_address("127.0.0.1:3001")
+            .unwrap()
+            .increment_connections();
+
+        assert_eq!(lb.next_server(0).get_address(), "127.0.0.1:3002");
+        assert_eq!(lb.next_server(0).get_address(), "127.0.0.1:3001");
+        assert_eq!(lb.next_server(0).get_address(), "127.0.0.1:3002");
+    }
+
+    #[test]
+    fn rotated_ties_dont_switch_to_least_connections() {
+        let mut lb = load_balancer(3, BalancingAlgorithm::RoundRobin);
+        switch_allowed(&mut lb);
+
+        // Held requests piling up evenly never spread the servers apart by more than one.
+        for _ in 0..30 {
+            lb.next_server(0);
+            assert_eq!(lb.algorithm(), BalancingAlgorithm::RoundRobin);
+        }
+        assert_eq!(lb.connection_spread(), 0);
+    }
+
+    #[test]
+    fn a_busy_server_switches_to_least_connections_and_evened_out_back() {
+        let mut lb = load_balancer(3, BalancingAlgorithm::RoundRobin);
+        switch_allowed(&mut lb);
+        for _ in 0..=SPREAD_FOR_LEAST_CONNECTIONS {
+            lb.get_server_by_address("127.0.0.1:3000")
+                .unwrap()
+                .increment_connections();
+        }
+
+        lb.next_server(0);
+        assert_eq!(lb.algorithm(), BalancingAlgorithm::LeastConnections);
+
+        // The idle servers catch up, until they're within one connection of the busy one.
+        switch_allowed(&mut lb);
+        while lb.connection_spread() > SPREAD_FOR_ROUND_ROBIN {
+            assert_eq!(lb.algorithm(), BalancingAlgorithm::LeastConnections);
+            lb.next_server(0);
+        }
+        lb.next_server(0);
+        assert_eq!(lb.algorithm(), BalancingAlgorithm::RoundRobin);
+    }
+
+    #[test]
+    fn the_automatic_switch_keeps_to_the_allowed_algorithms() {
+        let mut lb = load_balancer(3, BalancingAlgorithm::RoundRobin);
+        lb.set_allowed(Algorithms::parse("round_robin,maglev").unwrap());
+        switch_allowed(&mut lb);
+        for _ in 0..=SPREAD_FOR_LEAST_CONNECTIONS {
+            lb.get_server_by_address("127.0.0.1:3000")
+                .unwrap()
+                .increment_connections();
+        }
+
+        lb.next_server(0);
+        assert_eq!(lb.algorithm(), BalancingAlgorithm::RoundRobin);
+    }
+
+    #[test]
+    fn disallowing_the_current_algorithm_switches_to_the_first_allowed() {
+        let mut lb = load_balancer(3, BalancingAlgorithm::LeastConnections);
+
+        lb.set_allowed(Algorithms::parse("least_connections,maglev").unwrap());
+        assert_eq!(lb.algorithm(), BalancingAlgorithm::LeastConnections);
+        lb.set_allowed(Algorithms::parse("maglev,round_robin").unwrap());
+        assert_eq!(lb.algorithm(), BalancingAlgorithm::Maglev);
+    }
+
+    #[test]
+    fn algorithms_dont_switch_more_often_than_the_minimum_interval() {
+        let mut lb = load_balancer(3, BalancingAlgorithm::RoundRobin);
+        for _ in 0..=SPREAD_FOR_LEAST_CONNECTIONS {
+            lb.get_server_by_address("127.0.0.1:3000")
+                .unwrap()
+                .increment_connections();
+        }
+
+        lb.next_server(0);
+        assert_eq!(lb.algorithm(), BalancingAlgorithm::RoundRobin);
+    }
 }
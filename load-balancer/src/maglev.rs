@@ -0,0 +1,278 @@
+use std::fmt;
+use std::hash::Hasher;
+use std::net::IpAddr;
+
+use fnv::FnvHasher;
+use hyper::header::{HeaderMap, HeaderName};
+
+use crate::server::Server;
+
+/// The smallest prime above 2^16, plenty of slots per server for an even share.
+pub const DEFAULT_TABLE_SIZE: usize = 65537;
+/// A prime table of a million slots or so is 8MB, more than any balancer needs.
+pub const MAX_TABLE_SIZE: usize = 1_000_003;
+
+/// What requests are hashed by to keep landing on the same server, e.g. `client_ip` or
+/// `header:x-user-id`. Requests without the header fall back to their client IP.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum HashKey {
+    #[default]
+    ClientIp,
+    Header(HeaderName),
+}
+
+impl HashKey {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim() {
+            "client_ip" => Ok(HashKey::ClientIp),
+            value => value
+                .strip_prefix("header:")
+                .and_then(|name| HeaderName::try_from(name.trim()).ok())
+                .map(HashKey::Header)
+                .ok_or_else(|| {
+                    format!(
+                        "'{}' is not a hash key, use client_ip or header:<name>",
+                        value
+                    )
+                }),
+        }
+    }
+
+    /// FNV-1a of the header's value or the client's address, the same for every build and run so
+    /// balancers sharing a topology agree on where a key goes.
+    pub fn hash(&self, headers: &HeaderMap, client: IpAddr) -> u64 {
+        let mut hasher = FnvHasher::default();
+        match (self, client) {
+            (HashKey::Header(name), _) if headers.contains_key(name) => {
+                hasher.write(headers[name].as_bytes())
+            }
+            (_, IpAddr::V4(ip)) => hasher.write(&ip.octets()),
+            (_, IpAddr::V6(ip)) => hasher.write(&ip.octets()),
+        }
+        hasher.finish()
+    }
+}
+
+impl fmt::Display for HashKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashKey::ClientIp => write!(f, "client_ip"),
+            HashKey::Header(name) => write!(f, "header:{}", name),
+        }
+    }
+}
+
+/// Maglev's lookup table (Eisenbud et al., NSDI 2016): every server takes slots in turn, each
+/// following its own permutation of the table, so they end up with a near equal share and a server
+/// leaving only moves the keys it held, give or take a few.
+#[derive(Debug)]
+pub struct Maglev {
+    size: usize,
+    // Indexes of the servers the table was built from, in order.
+    members: Vec<usize>,
+    table: Vec<usize>,
+}
+
+impl Maglev {
+    /// `size` is prime, for every server's permutation to cover the whole table.
+    pub fn new(size: usize) -> Self {
+        Maglev {
+            size,
+            members: Vec::new(),
+            table: Vec::new(),
+        }
+    }
+
+    /// Rebuilds the table when the healthy servers changed. Until the health checks found any, the
+    /// table is built from every server.
+    pub fn update(&mut self, servers: &[Server]) {
+        let healthy = (0..servers.len())
+            .filter(|&i| servers[i].is_healthy())
+            .collect::<Vec<_>>();
+        let members = match healthy.is_empty() {
+            true => (0..servers.len()).collect(),
+            false => healthy,
+        };
+        if members == self.members && !self.table.is_empty() {
+            return;
+        }
+        let size = self.size as u64;
+        // Where each member's permutation is at, and by how much it moves on.
+        let mut permutations = members
+            .iter()
+            .map(|&member| {
+                let offset = hash(0, servers[member].get_address()) % size;
+                let skip = hash(1, servers[member].get_address()) % (size - 1).max(1) + 1;
+                (offset, skip)
+            })
+            .collect::<Vec<_>>();
+        let mut table = vec![None; self.size];
+        let mut filled = 0;
+        'fill: loop {
+            for (i, (position, skip)) in permutations.iter_mut().enumerate() {
+                while table[*position as usize].is_some() {
+                    *position = (*position + *skip) % size;
+                }
+                table[*position as usize] = Some(members[i]);
+                filled += 1;
+                if filled == self.size {
+                    break 'fill;
+                }
+            }
+        }
+        self.table = table.into_iter().flatten().collect();
+        self.members = members;
+    }
+
+    /// The index of the server `key` maps to.
+    pub fn lookup(&self, key: u64) -> usize {
+        self.table[(key % self.size as u64) as usize]
+    }
+}
+
+fn hash(seed: u8, address: &str) -> u64 {
+    let mut hasher = FnvHasher::default();
+    hasher.write_u8(seed);
+    hasher.write(address.as_bytes());
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn servers(count: u16) -> Vec<Server> {
+        (0..count)
+            .map(|i| {
+                let mut server = Server::new(format!("10.0.0.{}:3000", i + 1)).unwrap();
+                server.set_healthy(true);
+                server
+            })
+            .collect()
+    }
+
+    // The hash keys of `count` distinct clients.
+    fn keys(count: u32) -> impl Iterator<Item = u64> {
+        (0..count).map(|i| HashKey::ClientIp.hash(&HeaderMap::new(), Ipv4Addr::from(i).into()))
+    }
+
+    // The busiest server's share over the idlest's.
+    fn imbalance(picks: impl Iterator<Item = usize>, servers: usize) -> f64 {
+        let mut counts = vec![0; servers];
+        picks.for_each(|i| counts[i] += 1);
+        *counts.iter().max().unwrap() as f64 / *counts.iter().min().unwrap() as f64
+    }
+
+    #[test]
+    fn every_server_gets_an_equal_share_of_the_table() {
+        let servers = servers(12);
+        let mut maglev = Maglev::new(DEFAULT_TABLE_SIZE);
+        maglev.update(&servers);
+
+        let mut slots = vec![0; servers.len()];
+        maglev.table.iter().for_each(|&i| slots[i] += 1);
+        let fair = DEFAULT_TABLE_SIZE / servers.len();
+        assert!(
+            slots.iter().all(|&n| n == fair || n == fair + 1),
+            "{:?}",
+            slots
+        );
+    }
+
+    #[test]
+    fn keys_spread_more_evenly_than_on_a_simple_ring() {
+        let servers = servers(12);
+        let mut maglev = Maglev::new(DEFAULT_TABLE_SIZE);
+        maglev.update(&servers);
+
+        // Each server at a single point of the ring, a key goes to the next one clockwise.
+        let mut ring = servers
+            .iter()
+            .enumerate()
+            .map(|(i, server)| (hash(0, server.get_address()), i))
+            .collect::<Vec<_>>();
+        ring.sort();
+        let on_ring = |key: u64| {
+            ring.iter()
+                .find(|&&(point, _)| point >= key)
+                .unwrap_or(&ring[0])
+                .1
+        };
+
+        let maglev_imbalance = imbalance(keys(100_000).map(|key| maglev.lookup(key)), 12);
+        let ring_imbalance = imbalance(keys(100_000).map(on_ring), 12);
+        assert!(maglev_imbalance < 1.1, "maglev {}", maglev_imbalance);
+        assert!(
+            maglev_imbalance < ring_imbalance,
+            "maglev {}, ring {}",
+            maglev_imbalance,
+            ring_imbalance
+        );
+    }
+
+    #[test]
+    fn the_table_is_rebuilt_without_a_server_gone_unhealthy() {
+        let mut servers = servers(10);
+        let mut maglev = Maglev::new(DEFAULT_TABLE_SIZE);
+        maglev.update(&servers);
+        let before = maglev.table.clone();
+
+        servers[3].set_healthy(false);
+        maglev.update(&servers);
+
+        assert!(!maglev.table.contains(&3));
+        // Keys held by the servers still there mostly stay on them.
+        let kept = before.iter().filter(|&&i| i != 3).count();
+        let moved = before
+            .iter()
+            .zip(&maglev.table)
+            .filter(|&(&old, &new)| old != 3 && old != new)
+            .count();
+        assert!(moved * 10 < kept, "{} of {} moved", moved, kept);
+
+        servers[3].set_healthy(true);
+        maglev.update(&servers);
+        assert_eq!(maglev.table, before);
+    }
+
+    #[test]
+    fn every_server_is_in_the_table_until_one_is_healthy() {
+        let mut servers = servers(3);
+        servers
+            .iter_mut()
+            .for_each(|server| server.set_healthy(false));
+        let mut maglev = Maglev::new(7);
+        maglev.update(&servers);
+        assert_eq!(maglev.members, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn header_keys_fall_back_to_the_client_ip() {
+        let key = HashKey::parse("header:x-user-id").unwrap();
+        let client = IpAddr::from(Ipv4Addr::new(192, 168, 1, 7));
+        let mut headers = HeaderMap::new();
+
+        assert_eq!(
+            key.hash(&headers, client),
+            HashKey::ClientIp.hash(&headers, client)
+        );
+        headers.insert("x-user-id", "alice".parse().unwrap());
+        assert_ne!(
+            key.hash(&headers, client),
+            HashKey::ClientIp.hash(&headers, client)
+        );
+        assert_eq!(
+            key.hash(&headers, client),
+            key.hash(&headers, Ipv4Addr::LOCALHOST.into())
+        );
+    }
+
+    #[test]
+    fn hash_keys_parse_and_display_alike() {
+        for value in ["client_ip", "header:x-user-id"] {
+            assert_eq!(HashKey::parse(value).unwrap().to_string(), value);
+        }
+        assert!(HashKey::parse("cookie:session").is_err());
+    }
+}
@@ -0,0 +1,519 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use lb_api::{QueueStats, RouteQueueStats};
+use tokio::sync::oneshot;
+
+// Routes queued separately, requests to any further ones share the `*` queue.
+const MAX_ROUTES: usize = 32;
+const OTHER_ROUTES: &str = "*";
+// Recent waits per route the p95 is taken over.
+const WAIT_SAMPLES: usize = 256;
+
+/// The route a request queues under, its path's first segment, e.g. `/work` for `/work/fast`.
+pub fn route(path: &str) -> &str {
+    match path.get(1..).and_then(|rest| rest.find('/')) {
+        Some(end) => &path[..end + 1],
+        None => path,
+    }
+}
+
+struct Waiter {
+    id: u64,
+    since: Instant,
+    granted: oneshot::Sender<()>,
+}
+
+struct RouteQueue {
+    route: String,
+    waiters: VecDeque<Waiter>,
+    // How long the last requests handed a permit waited for it.
+    waits: VecDeque<Duration>,
+    dequeued: u64,
+    timeouts: u64,
+    rejected: u64,
+}
+
+struct Queues {
+    in_flight: usize,
+    routes: Vec<RouteQueue>,
+    // Where the round robin looks first on the next release.
+    next: usize,
+    next_id: u64,
+}
+
+impl Queues {
+    fn route_index(&mut self, route: &str) -> usize {
+        let route = match self.routes.iter().position(|queue| queue.route == route) {
+            Some(index) => return index,
+            None if self.routes.len() < MAX_ROUTES => route,
+            None => OTHER_ROUTES,
+        };
+        if let Some(index) = self.routes.iter().position(|queue| queue.route == route) {
+            return index;
+        }
+        self.routes.push(RouteQueue {
+            route: route.to_string(),
+            waiters: VecDeque::new(),
+            waits: VecDeque::new(),
+            dequeued: 0,
+            timeouts: 0,
+            rejected: 0,
+        });
+        self.routes.len() - 1
+    }
+
+    // Hands a released permit to the oldest waiter of the next route in turn that has one,
+    // frees it when nobody waits.
+    fn release(&mut self) {
+        let count = self.routes.len();
+        loop {
+            let next = (0..count)
+                .map(|offset| (self.next + offset) % count)
+                .find(|&index| !self.routes[index].waiters.is_empty());
+            let Some(index) = next else {
+                self.in_flight -= 1;
+                return;
+            };
+            self.next = (index + 1) % count;
+            let queue = &mut self.routes[index];
+            let waiter = queue.waiters.pop_front().expect("route has a waiter");
+            if waiter.granted.send(()).is_ok() {
+                queue.dequeued += 1;
+                if queue.waits.len() == WAIT_SAMPLES {
+                    queue.waits.pop_front();
+                }
+                queue.waits.push_back(waiter.since.elapsed());
+                return;
+            }
+        }
+    }
+}
+
+/// Caps the requests forwarded at once over every route. Past the cap requests wait in their
+/// route's queue, and each permit released goes to the next route with a waiter in turn, so a burst
+/// on one route holds up the others by at most a permit per route.
+pub struct FairQueue {
+    max_in_flight: usize,
+    max_depth: usize,
+    queues: Arc<Mutex<Queues>>,
+}
+
+impl FairQueue {
+    pub fn new(max_in_flight: usize, max_depth: usize) -> Self {
+        FairQueue {
+            max_in_flight,
+            max_depth,
+            queues: Arc::new(Mutex::new(Queues {
+                in_flight: 0,
+                routes: Vec::new(),
+                next: 0,
+                next_id: 0,
+            })),
+        }
+    }
+
+    /// A permit to forward a request to `route`, waiting at most `timeout` for one.
+    pub async fn acquire(&self, route: &str, timeout: Duration) -> Result<Permit, QueueError> {
+        let mut ticket = {
+            let mut queues = self.queues.lock().unwrap();
+            let index = queues.route_index(route);
+            let waiting = queues.routes.iter().any(|queue| !queue.waiters.is_empty());
+            if queues.in_flight < self.max_in_flight && !waiting {
+                queues.in_flight += 1;
+                return Ok(self.permit());
+            }
+            let queue = &mut queues.routes[index];
+            if queue.waiters.len() >= self.max_depth {
+                queue.rejected += 1;
+                return Err(QueueError::Full {
+                    route: queue.route.clone(),
+                    depth: self.max_depth,
+                });
+            }
+            let (granted, receiver) = oneshot::channel();
+            queues.next_id += 1;
+            let id = queues.next_id;
+            queues.routes[index].waiters.push_back(Waiter {
+                id,
+                since: Instant::now(),
+                granted,
+            });
+            Ticket {
+                queues: &self.queues,
+                index,
+                id,
+                granted: receiver,
+            }
+        };
+
+        if let Ok(Ok(())) = tokio::time::timeout(timeout, &mut ticket.granted).await {
+            return Ok(self.permit());
+        }
+        let mut queues = self.queues.lock().unwrap();
+        let queue = &mut queues.routes[ticket.index];
+        match queue
+            .waiters
+            .iter()
+            .position(|waiter| waiter.id == ticket.id)
+        {
+            Some(position) => {
+                queue.waiters.remove(position);
+                queue.timeouts += 1;
+                Err(QueueError::TimedOut {
+                    route: queue.route.clone(),
+                    waited: timeout,
+                })
+            }
+            // Handed a permit just as the wait ran out.
+            None => {
+                let _ = ticket.granted.try_recv();
+                Ok(self.permit())
+            }
+        }
+    }
+
+    fn permit(&self) -> Permit {
+        Permit {
+            queues: self.queues.clone(),
+        }
+    }
+
+    pub fn stats(&self) -> QueueStats {
+        let queues = self.queues.lock().unwrap();
+        QueueStats {
+            max_in_flight: self.max_in_flight as u64,
+            in_flight: queues.in_flight as u64,
+            max_depth: self.max_depth as u64,
+            routes: queues
+                .routes
+                .iter()
+                .map(|queue| RouteQueueStats {
+                    route: queue.route.clone(),
+                    depth: queue.waiters.len() as u64,
+                    dequeued: queue.dequeued,
+                    wait_p95_ms: p95(&queue.waits).map(|wait| wait.as_millis() as u64),
+                    timeouts: queue.timeouts,
+                    rejected: queue.rejected,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn p95(waits: &VecDeque<Duration>) -> Option<Duration> {
+    let mut waits = waits.iter().copied().collect::<Vec<_>>();
+    waits.sort();
+    let rank = (waits.len() * 95).div_ceil(100);
+    waits.get(rank.checked_sub(1)?).copied()
+}
+
+// A place in a route's queue, given up when the waiting request is, e.g. by a client going away.
+struct Ticket<'a> {
+    queues: &'a Mutex<Queues>,
+    index: usize,
+    id: u64,
+    granted: oneshot::Receiver<()>,
+}
+
+impl Drop for Ticket<'_> {
+    fn drop(&mut self) {
+        let mut queues = self.queues.lock().unwrap();
+        let waiters = &mut queues.routes[self.index].waiters;
+        match waiters.iter().position(|waiter| waiter.id == self.id) {
+            Some(position) => {
+                waiters.remove(position);
+            }
+            // A permit handed over that nobody took is passed on.
+            None if self.granted.try_recv().is_ok() => queues.release(),
+            None => {}
+        }
+    }
+}
+
+/// Held until the worker answered, then passed to the next waiter.
+pub struct Permit {
+    queues: Arc<Mutex<Queues>>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.queues.lock().unwrap().release();
+    }
+}
+
+#[derive(Debug)]
+pub enum QueueError {
+    Full { route: String, depth: usize },
+    TimedOut { route: String, waited: Duration },
+}
+
+impl fmt::Display for QueueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueueError::Full { route, depth } => write!(
+                f,
+                "Too many requests queued for {}, at most {} may wait",
+                route, depth
+            ),
+            QueueError::TimedOut { route, waited } => write!(
+                f,
+                "No request slot freed up for {} within {}ms",
+                route,
+                waited.as_millis()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const WAIT: Duration = Duration::from_secs(5);
+
+    // Yields until `count` requests wait over every route.
+    async fn queued(queue: &FairQueue, count: u64) {
+        while queue.stats().routes.iter().map(|r| r.depth).sum::<u64>() < count {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    fn route_stats(queue: &FairQueue, route: &str) -> RouteQueueStats {
+        let stats = queue.stats();
+        stats.routes.into_iter().find(|r| r.route == route).unwrap()
+    }
+
+    #[test]
+    fn routes_are_the_first_path_segment() {
+        assert_eq!(route("/work/fast"), "/work");
+        assert_eq!(route("/work"), "/work");
+        assert_eq!(route("/"), "/");
+    }
+
+    #[tokio::test]
+    async fn a_busy_route_doesnt_hold_up_a_quiet_one() {
+        let queue = Arc::new(FairQueue::new(1, 100));
+        let held = queue.acquire("/busy", WAIT).await.unwrap();
+        let served = Arc::new(Mutex::new(Vec::new()));
+
+        let mut tasks = Vec::new();
+        for route in ["/busy"; 10].into_iter().chain(["/quiet"; 2]) {
+            let (queue, served) = (queue.clone(), served.clone());
+            tasks.push(tokio::spawn(async move {
+                let _permit = queue.acquire(route, WAIT).await.unwrap();
+                served.lock().unwrap().push(route);
+            }));
+        }
+        queued(&queue, 12).await;
+        drop(held);
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let served = served.lock().unwrap();
+        assert_eq!(served[..4], ["/busy", "/quiet", "/busy", "/quiet"]);
+        assert_eq!(route_stats(&queue, "/busy").dequeued, 10);
+        assert_eq!(route_stats(&queue, "/quiet").dequeued, 2);
+        assert_eq!(queue.stats().in_flight, 0);
+    }
+
+    #[tokio::test]
+    async fn waits_time_out_and_leave_the_queue() {
+        let queue = FairQueue::new(1, 100);
+        let held = queue.acquire("/work", WAIT).await.unwrap();
+
+        let waited = queue.acquire("/work", Duration::from_millis(10)).await;
+        assert!(matches!(waited, Err(QueueError::TimedOut { .. })));
+        let stats = route_stats(&queue, "/work");
+        assert_eq!((stats.depth, stats.timeouts), (0, 1));
+
+        drop(held);
+        assert_eq!(queue.stats().in_flight, 0);
+        assert!(queue.acquire("/work", Duration::ZERO).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn full_queues_turn_requests_away() {
+        let queue = Arc::new(FairQueue::new(1, 1));
+        let _held = queue.acquire("/work", WAIT).await.unwrap();
+        let waiting = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.acquire("/work", WAIT).await.map(drop) }
+        });
+        queued(&queue, 1).await;
+
+        let refused = queue.acquire("/work", WAIT).await;
+        assert!(matches!(refused, Err(QueueError::Full { depth: 1, .. })));
+        assert_eq!(route_stats(&queue, "/work").rejected, 1);
+        waiting.abort();
+    }
+
+    #[tokio::test]
+    async fn a_waiter_going_away_gives_up_its_place() {
+        let queue = Arc::new(FairQueue::new(1, 100));
+        let held = queue.acquire("/work", WAIT).await.unwrap();
+        let waiting = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.acquire("/work", WAIT).await.map(drop) }
+        });
+        queued(&queue, 1).await;
+
+        waiting.abort();
+        assert!(waiting.await.unwrap_err().is_cancelled());
+        assert_eq!(route_stats(&queue, "/work").depth, 0);
+
+        drop(held);
+        assert_eq!(queue.stats().in_flight, 0);
+    }
+
+    #[tokio::test]
+    async fn a_permit_handed_to_a_waiter_gone_is_passed_on() {
+        let queue = Arc::new(FairQueue::new(1, 100));
+        let held = queue.acquire("/work", WAIT).await.unwrap();
+        let waiting = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.acquire("/work", WAIT).await.map(drop) }
+        });
+        queued(&queue, 1).await;
+
+        // Handed the permit, then dropped before it could take it.
+        drop(held);
+        waiting.abort();
+        let _ = waiting.await;
+
+        assert_eq!(queue.stats().in_flight, 0);
+        assert!(queue.acquire("/work", Duration::ZERO).await.is_ok());
+    }
+
+    // Waits running out as permits are handed over, on every thread: each request is either
+    // served or timed out, never more at once than the cap, and no permit is lost.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn timeouts_racing_handovers_lose_no_permit() {
+        let queue = Arc::new(FairQueue::new(2, 1000));
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+
+        let tasks = (0..400)
+            .map(|i| {
+                let (queue, in_flight, most) = (queue.clone(), in_flight.clone(), most.clone());
+                tokio::spawn(async move {
+                    let timeout = Duration::from_micros(200 * (i % 10));
+                    let permit = queue.acquire("/work", timeout).await.ok()?;
+                    let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    most.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_micros(300)).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    drop(permit);
+                    Some(())
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut served = 0;
+        for task in tasks {
+            served += task.await.unwrap().is_some() as u64;
+        }
+
+        let stats = queue.stats();
+        let work = route_stats(&queue, "/work");
+        assert!(most.load(Ordering::SeqCst) <= 2);
+        assert_eq!((stats.in_flight, work.depth), (0, 0));
+        assert_eq!(served + work.timeouts, 400);
+        assert!(served > 0 && work.timeouts > 0, "{} served", served);
+        assert!(queue.acquire("/work", Duration::ZERO).await.is_ok());
+    }
+
+    // Waiter queued straight into `queues`, as `acquire` would past the cap.
+    fn enqueue(queues: &mut Queues, route: &str) -> oneshot::Receiver<()> {
+        let (granted, receiver) = oneshot::channel();
+        let index = queues.route_index(route);
+        queues.next_id += 1;
+        let id = queues.next_id;
+        queues.routes[index].waiters.push_back(Waiter {
+            id,
+            since: Instant::now(),
+            granted,
+        });
+        receiver
+    }
+
+    // Over generated runs of 2 to 5 routes, each with its own arrival rate and a permit freed per
+    // step: every route waiting is served within a permit per other route, in arrival order, so
+    // routes make progress in proportion to their share however busy the others are.
+    #[test]
+    fn routes_at_any_arrival_rates_all_make_progress() {
+        for seed in 0..200 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let routes = (0..rng.gen_range(2..=5))
+                .map(|i| (format!("/route{}", i), rng.gen_range(0.01..1.0)))
+                .collect::<Vec<(String, f64)>>();
+            let mut queues = Queues {
+                in_flight: 1,
+                routes: Vec::new(),
+                next: 0,
+                next_id: 0,
+            };
+            // Per route: receivers in arrival order, grants to others since its last one.
+            let mut waiting = routes.iter().map(|_| VecDeque::new()).collect::<Vec<_>>();
+            let mut skipped = vec![0; routes.len()];
+            let mut arrived = vec![0u64; routes.len()];
+
+            for step in 0..rng.gen_range(100..1000) {
+                for (i, (route, rate)) in routes.iter().enumerate() {
+                    if rng.gen_bool(*rate) {
+                        waiting[i].push_back(enqueue(&mut queues, route));
+                        arrived[i] += 1;
+                    }
+                }
+                if waiting.iter().all(VecDeque::is_empty) {
+                    continue;
+                }
+                queues.release();
+
+                let served = (0..routes.len())
+                    .filter(|&i| waiting[i].front_mut().is_some_and(|r| r.try_recv().is_ok()))
+                    .collect::<Vec<_>>();
+                assert_eq!(served.len(), 1, "seed {} step {}", seed, step);
+                waiting[served[0]].pop_front();
+                for i in 0..routes.len() {
+                    if i == served[0] || waiting[i].is_empty() {
+                        skipped[i] = 0;
+                    } else {
+                        skipped[i] += 1;
+                        assert!(
+                            skipped[i] < routes.len(),
+                            "seed {}: {} passed over {} times in a row",
+                            seed,
+                            routes[i].0,
+                            skipped[i]
+                        );
+                    }
+                }
+            }
+
+            for _ in waiting.iter().flatten() {
+                queues.release();
+            }
+            assert_eq!(queues.in_flight, 1, "seed {}", seed);
+            for (i, (route, _)) in routes.iter().enumerate() {
+                let queue = queues.routes.iter().find(|q| &q.route == route);
+                let dequeued = queue.map_or(0, |q| q.dequeued);
+                assert_eq!(dequeued, arrived[i], "seed {}: {}", seed, route);
+            }
+        }
+    }
+
+    #[test]
+    fn p95_is_the_wait_95_in_100_stay_under() {
+        let waits = (1..=100)
+            .map(Duration::from_millis)
+            .collect::<VecDeque<_>>();
+        assert_eq!(p95(&waits), Some(Duration::from_millis(95)));
+        assert_eq!(p95(&VecDeque::new()), None);
+    }
+}
@@ -1,9 +1,16 @@
 use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::transfer::ByteCounts;
 
 #[derive(Debug)]
 pub struct Server {
     address: String,
     connections: usize,
+    // Whether the last health check passed, false until the first one did.
+    healthy: bool,
+    // Shared with the bodies of the requests forwarded to it while they stream.
+    bytes: Arc<ByteCounts>,
 }
 
 impl Server {
@@ -16,6 +23,8 @@ impl Server {
             Ok(Server {
                 address,
                 connections: 0,
+                healthy: false,
+                bytes: Arc::default(),
             })
         } else {
             Err(format!("Invalid address: {}", address))
@@ -30,6 +39,18 @@ impl Server {
         self.connections
     }
 
+    pub fn bytes(&self) -> &Arc<ByteCounts> {
+        &self.bytes
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy
+    }
+
+    pub fn set_healthy(&mut self, healthy: bool) {
+        self.healthy = healthy;
+    }
+
     pub fn increment_connections(&mut self) {
         self.connections += 1;
     }
@@ -39,4 +60,4 @@ impl Server {
             self.connections -= 1;
         }
     }
-}
\ No newline at end of file
+}
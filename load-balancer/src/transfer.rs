@@ -0,0 +1,282 @@
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use hyper::body::{Body, Frame, SizeHint};
+use tracing::info;
+
+use crate::budget::Spent;
+
+/// Body bytes a server was sent and sent back, over every request forwarded to it.
+#[derive(Debug, Default)]
+pub struct ByteCounts {
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+}
+
+impl ByteCounts {
+    pub fn bytes_in(&self) -> u64 {
+        self.bytes_in.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out.load(Ordering::Relaxed)
+    }
+}
+
+/// The body bytes of a single forwarded request, tallied into its server's counts as they stream.
+#[derive(Debug)]
+pub struct Transfer {
+    server: Arc<ByteCounts>,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+}
+
+impl Transfer {
+    pub fn new(server: Arc<ByteCounts>) -> Arc<Self> {
+        Arc::new(Transfer {
+            server,
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+        })
+    }
+
+    pub fn bytes_in(&self) -> u64 {
+        self.bytes_in.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out.load(Ordering::Relaxed)
+    }
+
+    fn count(&self, direction: Direction, len: u64) {
+        let (request, server) = match direction {
+            Direction::In => (&self.bytes_in, &self.server.bytes_in),
+            Direction::Out => (&self.bytes_out, &self.server.bytes_out),
+        };
+        request.fetch_add(len, Ordering::Relaxed);
+        server.fetch_add(len, Ordering::Relaxed);
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Direction {
+    // The request body, on its way to the server.
+    In,
+    // The response body, on its way back.
+    Out,
+}
+
+/// Logs a forwarded request's byte counts and budget use once its response body is done with,
+/// streamed to the end or dropped by a client that went away.
+pub struct AccessLog {
+    pub method: String,
+    pub path: String,
+    pub worker: String,
+    pub transfer: Arc<Transfer>,
+    /// What the request used of its budget when the response's head arrived, None before.
+    pub budget: Option<Spent>,
+}
+
+impl AccessLog {
+    // e.g. `Forwarded POST /work to 127.0.0.1:3000: 11 bytes in, 2 bytes out, budget 120ms of 500ms`.
+    fn message(&self) -> String {
+        let mut message = format!(
+            "Forwarded {} {} to {}: {} bytes in, {} bytes out",
+            self.method,
+            self.path,
+            self.worker,
+            self.transfer.bytes_in(),
+            self.transfer.bytes_out()
+        );
+        if let Some(budget) = self.budget {
+            message.push_str(&format!(", budget {}", budget));
+        }
+        message
+    }
+}
+
+impl Drop for AccessLog {
+    fn drop(&mut self) {
+        info!("{}", self.message());
+    }
+}
+
+/// Passes a body through frame by frame, counting its data as it goes. Nothing is buffered and
+/// the size hint is the wrapped body's, a Content-Length stays as it was.
+pub struct CountingBody<B> {
+    inner: B,
+    transfer: Arc<Transfer>,
+    direction: Direction,
+    _log: Option<AccessLog>,
+}
+
+impl<B> CountingBody<B> {
+    pub fn request(inner: B, transfer: Arc<Transfer>) -> Self {
+        CountingBody {
+            inner,
+            transfer,
+            direction: Direction::In,
+            _log: None,
+        }
+    }
+
+    pub fn response(inner: B, log: AccessLog) -> Self {
+        CountingBody {
+            inner,
+            transfer: log.transfer.clone(),
+            direction: Direction::Out,
+            _log: Some(log),
+        }
+    }
+}
+
+impl<B> Body for CountingBody<B>
+where
+    B: Body<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let frame = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &frame {
+            if let Some(data) = frame.data_ref() {
+                self.transfer.count(self.direction, data.len() as u64);
+            }
+        }
+        frame
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::{BodyExt, Full};
+    use std::collections::VecDeque;
+    use std::convert::Infallible;
+
+    // Hands out its chunks one frame at a time, with no length known up front like a chunked body.
+    struct Chunked(VecDeque<&'static str>);
+
+    impl Body for Chunked {
+        type Data = Bytes;
+        type Error = Infallible;
+
+        fn poll_frame(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+            Poll::Ready(
+                self.0
+                    .pop_front()
+                    .map(|chunk| Ok(Frame::data(Bytes::from(chunk)))),
+            )
+        }
+    }
+
+    fn log(transfer: &Arc<Transfer>) -> AccessLog {
+        AccessLog {
+            method: "POST".to_string(),
+            path: "/work".to_string(),
+            worker: "127.0.0.1:3000".to_string(),
+            transfer: transfer.clone(),
+            budget: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn known_lengths_are_counted_and_kept() {
+        let server = Arc::<ByteCounts>::default();
+        let transfer = Transfer::new(server.clone());
+
+        let request =
+            CountingBody::request(Full::new(Bytes::from("hello world")), transfer.clone());
+        assert_eq!(request.size_hint().exact(), Some(11));
+        assert_eq!(request.collect().await.unwrap().to_bytes(), "hello world");
+        let response = CountingBody::response(Full::new(Bytes::from("ok")), log(&transfer));
+        assert_eq!(response.size_hint().exact(), Some(2));
+        response.collect().await.unwrap();
+
+        assert_eq!((transfer.bytes_in(), transfer.bytes_out()), (11, 2));
+        assert_eq!((server.bytes_in(), server.bytes_out()), (11, 2));
+    }
+
+    #[tokio::test]
+    async fn chunks_are_counted_as_they_stream() {
+        let server = Arc::<ByteCounts>::default();
+        let transfer = Transfer::new(server.clone());
+        let chunks = Chunked(VecDeque::from(["he", "llo", " world"]));
+        let mut body = CountingBody::response(chunks, log(&transfer));
+        assert_eq!(body.size_hint().exact(), None);
+
+        // Each frame is passed on as it comes, nothing is read ahead.
+        let mut seen = Vec::new();
+        while let Some(frame) = body.frame().await {
+            let data = frame.unwrap().into_data().unwrap();
+            seen.push((data, transfer.bytes_out()));
+        }
+        assert_eq!(
+            seen,
+            [("he".into(), 2), ("llo".into(), 5), (" world".into(), 11)]
+        );
+        assert_eq!(server.bytes_out(), 11);
+    }
+
+    #[tokio::test]
+    async fn server_counts_add_up_over_requests() {
+        let server = Arc::<ByteCounts>::default();
+        for body in ["abc", "defgh"] {
+            let transfer = Transfer::new(server.clone());
+            let request = CountingBody::request(Full::new(Bytes::from(body)), transfer.clone());
+            request.collect().await.unwrap();
+            assert_eq!(transfer.bytes_in(), body.len() as u64);
+        }
+
+        assert_eq!((server.bytes_in(), server.bytes_out()), (8, 0));
+    }
+
+    #[test]
+    fn the_log_line_has_the_budget_used_of_the_allotted() {
+        let transfer = Transfer::new(Arc::default());
+        transfer.count(Direction::In, 11);
+        transfer.count(Direction::Out, 2);
+        let started = std::time::Instant::now();
+        let used = started + std::time::Duration::from_millis(120);
+
+        let mut access = log(&transfer);
+        access.budget = Some(
+            crate::RequestBudget::new(Some(std::time::Duration::from_millis(500)), started)
+                .spent(used),
+        );
+        assert_eq!(
+            access.message(),
+            "Forwarded POST /work to 127.0.0.1:3000: 11 bytes in, 2 bytes out, budget 120ms of 500ms"
+        );
+
+        access.budget = Some(crate::RequestBudget::new(None, started).spent(used));
+        assert!(access.message().ends_with("2 bytes out, budget 120ms"));
+    }
+
+    #[test]
+    fn requests_without_a_response_log_no_budget() {
+        let transfer = Transfer::new(Arc::default());
+        assert_eq!(
+            log(&transfer).message(),
+            "Forwarded POST /work to 127.0.0.1:3000: 0 bytes in, 0 bytes out"
+        );
+    }
+}
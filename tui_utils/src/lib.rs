@@ -2,11 +2,20 @@ use crossterm::{
     execute,
     terminal::{disable_raw_mode, enable_raw_mode},
 };
-use ratatui::{backend::CrosstermBackend, layout::Rect, Terminal};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::Rect,
+    style::Style,
+    text::{Line, Span},
+    Terminal,
+};
 
 use std::io::{self, Stdout};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
+// Also makes panics restore the terminal, see `cleanup_terminal_on_panic`.
 pub fn setup_terminal() -> Result<Terminal<CrosstermBackend<Stdout>>, io::Error> {
+    cleanup_terminal_on_panic();
     enable_raw_mode()?;
     let stdout = io::stdout();
     let backend = CrosstermBackend::new(stdout);
@@ -14,8 +23,10 @@ pub fn setup_terminal() -> Result<Terminal<CrosstermBackend<Stdout>>, io::Error>
     Ok(terminal)
 }
 
+// Also turns off mouse reporting, a no-op for terminals that never enabled it.
 pub fn cleanup_terminal() -> Result<(), io::Error> {
     disable_raw_mode()?;
+    execute!(io::stdout(), crossterm::event::DisableMouseCapture)?;
     execute!(io::stdout(), crossterm::cursor::Show)?;
     execute!(
         io::stdout(),
@@ -29,37 +40,718 @@ pub fn cleanup_terminal() -> Result<(), io::Error> {
     Ok(())
 }
 
+// Restores the terminal before the default hook prints the panic, otherwise the message is
+// garbled by raw mode and the terminal keeps reporting the mouse after the process is gone.
+fn cleanup_terminal_on_panic() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = cleanup_terminal();
+        default_hook(info);
+    }));
+}
+
 pub fn get_end_of_wrapped_text(text: &str, area: Rect) -> String {
-    let mut wrapped_lines = Vec::new();
+    let wrapped_lines = wrap_text(text, area);
+    // Areas too small for the borders show nothing rather than underflowing.
+    let height = (area.height as usize).saturating_sub(2);
+
+    let start = if wrapped_lines.len() > height {
+        wrapped_lines.len() - height
+    } else {
+        0
+    };
+
+    wrapped_lines[start..].join("\n")
+}
+
+// Splits the text into the lines it takes up inside a bordered block covering `area`.
+pub fn wrap_text(text: &str, area: Rect) -> Vec<String> {
+    let width = (area.width as usize).saturating_sub(2);
+    text.lines()
+        .flat_map(|line| wrap_line(&Line::raw(line), width))
+        .map(|line| {
+            line.spans
+                .iter()
+                .map(|span| span.content.as_ref())
+                .collect::<String>()
+        })
+        .collect()
+}
+
+// Splits a styled line into lines at most `width` columns wide, every part of a span keeps
+// its style. Words go to the next line when they don't fit and runs of whitespace become
+// one space. Widths are measured in terminal columns, wide characters such as CJK take two.
+pub fn wrap_line(line: &Line, width: usize) -> Vec<Line<'static>> {
+    let mut wrapper = Wrapper {
+        width,
+        lines: Vec::new(),
+        current: Vec::new(),
+        current_width: 0,
+    };
+    let chars = line.spans.iter().flat_map(|span| {
+        let style = line.style.patch(span.style);
+        span.content.chars().map(move |c| (c, style))
+    });
+    let mut word = Vec::new();
+    // The style of the whitespace before the word, for the space that replaces it.
+    let mut separator = Style::default();
+    for (c, style) in chars {
+        if !c.is_whitespace() {
+            word.push((c, style));
+        } else if !word.is_empty() {
+            wrapper.push_word(&word, separator);
+            word.clear();
+            separator = style;
+        } else {
+            separator = style;
+        }
+    }
+    if !word.is_empty() {
+        wrapper.push_word(&word, separator);
+    }
+    wrapper.finish()
+}
+
+struct Wrapper {
+    width: usize,
+    lines: Vec<Vec<(char, Style)>>,
+    current: Vec<(char, Style)>,
+    current_width: usize,
+}
+
+impl Wrapper {
+    fn push_word(&mut self, word: &[(char, Style)], separator: Style) {
+        let word_width = word
+            .iter()
+            .map(|(c, _)| c.width().unwrap_or(0))
+            .sum::<usize>();
+        if !self.current.is_empty() && self.current_width + word_width + 1 > self.width {
+            self.break_line();
+        }
+
+        if !self.current.is_empty() {
+            self.current.push((' ', separator));
+            self.current_width += 1;
+        }
+        // Only a word wider than the whole line gets here without fitting, e.g. a URL,
+        // it is broken at the edge rather than clipped.
+        for &(c, style) in word {
+            let char_width = c.width().unwrap_or(0);
+            if !self.current.is_empty() && self.current_width + char_width > self.width {
+                self.break_line();
+            }
+            self.current.push((c, style));
+            self.current_width += char_width;
+        }
+    }
+
+    fn break_line(&mut self) {
+        self.lines.push(std::mem::take(&mut self.current));
+        self.current_width = 0;
+    }
+
+    // Consecutive characters with the same style make up one span.
+    fn finish(mut self) -> Vec<Line<'static>> {
+        if !self.current.is_empty() {
+            self.break_line();
+        }
+        self.lines
+            .into_iter()
+            .map(|chars| {
+                let mut spans: Vec<(String, Style)> = Vec::new();
+                for (c, style) in chars {
+                    match spans.last_mut() {
+                        Some((text, last)) if *last == style => text.push(c),
+                        _ => spans.push((c.to_string(), style)),
+                    }
+                }
+                Line::from(
+                    spans
+                        .into_iter()
+                        .map(|(text, style)| Span::styled(text, style))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect()
+    }
+}
+
+// Scroll position of a pane showing wrapped text. While following it shows the tail,
+// otherwise it stays on the same first line as new text arrives.
+pub struct Scrollback {
+    top: usize,
+    following: bool,
+    // Both are taken from the last render, keys are handled between renders.
+    page: usize,
+    max_top: usize,
+    total: usize,
+}
+
+impl Default for Scrollback {
+    fn default() -> Self {
+        Scrollback {
+            top: 0,
+            following: true,
+            page: 1,
+            max_top: 0,
+            total: 0,
+        }
+    }
+}
+
+impl Scrollback {
+    pub fn is_following(&self) -> bool {
+        self.following
+    }
+
+    // Returns the visible part of the lines, each keeping its styles on every wrapped part,
+    // and remembers the pane size for scrolling.
+    pub fn view(&mut self, lines: &[Line], area: Rect) -> Vec<Line<'static>> {
+        let width = (area.width as usize).saturating_sub(2);
+        let wrapped_lines = lines
+            .iter()
+            .flat_map(|line| wrap_line(line, width))
+            .collect::<Vec<_>>();
+        self.visible(wrapped_lines, area)
+    }
+
+    // Like `view` with a prefix in `prefix_style` before every line, e.g. a timestamp.
+    // Wrapped parts are indented to line up with the text after the prefix.
+    pub fn view_prefixed(
+        &mut self,
+        lines: &[(&str, &Line)],
+        prefix_style: Style,
+        area: Rect,
+    ) -> Vec<Line<'static>> {
+        let wrapped_lines = lines
+            .iter()
+            .flat_map(|(prefix, line)| {
+                let prefix_width = prefix.width();
+                let width = (area.width as usize)
+                    .saturating_sub(2)
+                    .saturating_sub(prefix_width);
+                wrap_line(line, width)
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(i, part)| {
+                        let lead = if i == 0 {
+                            prefix.to_string()
+                        } else {
+                            " ".repeat(prefix_width)
+                        };
+                        let mut spans = vec![Span::styled(lead, prefix_style)];
+                        spans.extend(part.spans);
+                        Line::from(spans)
+                    })
+            })
+            .collect::<Vec<_>>();
+        self.visible(wrapped_lines, area)
+    }
+
+    fn visible(&mut self, wrapped_lines: Vec<Line<'static>>, area: Rect) -> Vec<Line<'static>> {
+        self.resize(
+            wrapped_lines.len(),
+            (area.height as usize).saturating_sub(2),
+        );
+
+        let end = (self.top + self.page).min(wrapped_lines.len());
+        wrapped_lines[self.top..end].to_vec()
+    }
+
+    pub fn resize(&mut self, total: usize, height: usize) {
+        self.total = total;
+        self.page = height.max(1);
+        self.max_top = total.saturating_sub(height);
+        if self.following {
+            self.top = self.max_top;
+        } else {
+            self.top = self.top.min(self.max_top);
+        }
+    }
+
+    pub fn page_up(&mut self) {
+        self.scroll_up(self.page);
+    }
+
+    pub fn page_down(&mut self) {
+        self.scroll_down(self.page);
+    }
+
+    // Everything fits on screen when `max_top` is 0, there is nothing to scroll back to.
+    pub fn scroll_up(&mut self, lines: usize) {
+        if self.max_top == 0 {
+            return;
+        }
+        self.following = false;
+        self.top = self.top.saturating_sub(lines);
+    }
+
+    pub fn scroll_down(&mut self, lines: usize) {
+        self.top = (self.top + lines).min(self.max_top);
+        self.following = self.top == self.max_top;
+    }
+
+    pub fn home(&mut self) {
+        if self.max_top == 0 {
+            return;
+        }
+        self.following = false;
+        self.top = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.following = true;
+        self.top = self.max_top;
+    }
+
+    // First visible line, counted from 1, and the total number of lines.
+    pub fn position(&self) -> (usize, usize) {
+        ((self.top + 1).min(self.total), self.total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::style::{Color, Modifier};
+
+    // A bordered pane with `width` x `height` cells inside the borders.
+    fn pane(width: u16, height: u16) -> Rect {
+        Rect::new(0, 0, width + 2, height + 2)
+    }
+
+    #[test]
+    fn words_wrap_at_the_pane_width() {
+        assert_eq!(
+            wrap_text("the quick brown fox jumps", pane(10, 5)),
+            vec!["the quick", "brown fox", "jumps"]
+        );
+        assert_eq!(
+            wrap_text("the quick brown fox jumps", pane(25, 5)),
+            vec!["the quick brown fox jumps"]
+        );
+    }
+
+    #[test]
+    fn a_word_filling_the_width_exactly_fits() {
+        assert_eq!(wrap_text("abcde fghij", pane(5, 5)), vec!["abcde", "fghij"]);
+        assert_eq!(wrap_text("ab cd", pane(5, 5)), vec!["ab cd"]);
+    }
+
+    #[test]
+    fn line_breaks_are_kept_and_whitespace_runs_collapse() {
+        assert_eq!(
+            wrap_text("one\ntwo   three\n  four", pane(20, 5)),
+            vec!["one", "two three", "four"]
+        );
+    }
+
+    #[test]
+    fn empty_input_wraps_to_nothing() {
+        assert!(wrap_text("", pane(10, 5)).is_empty());
+        assert!(wrap_text("   ", pane(10, 5)).is_empty());
+        assert_eq!(get_end_of_wrapped_text("", pane(10, 5)), "");
+    }
 
-    let height = area.height as usize - 2;
-    let width = area.width as usize - 2;
+    #[test]
+    fn the_end_of_wrapped_text_is_what_fits_in_the_pane() {
+        let text = "one two three four five six";
 
-    for line in text.lines() {
-        let mut current_line = String::new();
+        assert_eq!(get_end_of_wrapped_text(text, pane(9, 2)), "four five\nsix");
+        assert_eq!(
+            get_end_of_wrapped_text(text, pane(9, 10)),
+            "one two\nthree\nfour five\nsix"
+        );
+    }
 
-        for word in line.split_whitespace() {
-            if current_line.len() + word.len() + 1 > width {
-                wrapped_lines.push(current_line);
-                current_line = String::new();
+    #[test]
+    fn tiny_areas_never_panic() {
+        let text = "one two\nthree";
+        for width in 0..=2 {
+            for height in 0..=2 {
+                let area = Rect::new(0, 0, width, height);
+                let wrapped = wrap_text(text, area);
+                // No room inside the borders, words are broken one character per line.
+                assert_eq!(wrapped.concat(), "onetwothree", "{:?}", area);
+                assert!(wrapped.iter().all(|line| line.len() == 1), "{:?}", area);
+                assert_eq!(get_end_of_wrapped_text(text, area), "", "{:?}", area);
+                let mut scrollback = Scrollback::default();
+                assert!(scrollback.view(&[Line::raw(text)], area).is_empty());
+                assert!(scrollback.is_following());
             }
+        }
+    }
+
+    #[test]
+    fn one_cell_inside_the_borders_shows_the_last_character() {
+        let area = pane(1, 1);
+        assert_eq!(wrap_text("a bc", area), vec!["a", "b", "c"]);
+        assert_eq!(get_end_of_wrapped_text("a bc", area), "c");
+        assert_eq!(get_end_of_wrapped_text("a bc", pane(0, 1)), "c");
+        assert_eq!(get_end_of_wrapped_text("a bc", pane(1, 0)), "");
+    }
+
+    #[test]
+    fn lines_wrap_to_a_zero_width_one_character_at_a_time() {
+        let parts = wrap_line(&Line::raw("ab  c"), 0);
+        assert_eq!(
+            parts.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            ["a", "b", "c"]
+        );
+        assert!(wrap_line(&Line::raw(""), 0).is_empty());
+    }
 
-            if !current_line.is_empty() {
-                current_line.push(' ');
+    #[test]
+    fn prefixed_views_fit_tiny_areas() {
+        let line = Line::raw("text");
+        for width in 0..=2 {
+            for height in 0..=2 {
+                let area = Rect::new(0, 0, width, height);
+                let mut scrollback = Scrollback::default();
+                let visible =
+                    scrollback.view_prefixed(&[("12:00:00 ", &line)], Style::default(), area);
+                assert!(visible.is_empty(), "{:?}", area);
+                // Scrolling what can't be shown stays in bounds.
+                scrollback.page_up();
+                scrollback.scroll_down(10);
+                assert_eq!(scrollback.position(), (4, 4), "{:?}", area);
             }
-            current_line.push_str(word);
         }
+    }
+
+    #[test]
+    fn panes_too_small_for_their_borders_show_nothing() {
+        assert_eq!(get_end_of_wrapped_text("text", Rect::new(0, 0, 10, 2)), "");
+        assert_eq!(get_end_of_wrapped_text("text", Rect::new(0, 0, 10, 0)), "");
+        assert_eq!(get_end_of_wrapped_text("text", Rect::new(0, 0, 0, 0)), "");
+    }
+    #[test]
+    fn words_wider_than_the_pane_are_broken_at_its_edge() {
+        assert_eq!(
+            wrap_text("abcdefghij", pane(4, 5)),
+            vec!["abcd", "efgh", "ij"]
+        );
+        assert_eq!(
+            wrap_text("see https://example.com/a/long/path ok", pane(10, 5)),
+            vec!["see", "https://ex", "ample.com/", "a/long/pat", "h ok"]
+        );
+    }
+
+    #[test]
+    fn accented_letters_take_one_column() {
+        assert_eq!(wrap_text("héllo wörld", pane(5, 5)), vec!["héllo", "wörld"]);
+        assert_eq!(wrap_text("héllo wörld", pane(11, 5)), vec!["héllo wörld"]);
+    }
+
+    #[test]
+    fn cjk_characters_take_two_columns() {
+        assert_eq!(wrap_text("你好世界", pane(4, 5)), vec!["你好", "世界"]);
+        assert_eq!(wrap_text("你好世界", pane(5, 5)), vec!["你好", "世界"]);
+        assert_eq!(
+            wrap_text("你好世界", pane(3, 5)),
+            vec!["你", "好", "世", "界"]
+        );
+        assert_eq!(wrap_text("你好 世界", pane(9, 5)), vec!["你好 世界"]);
+    }
+
+    #[test]
+    fn emoji_take_two_columns() {
+        assert_eq!(wrap_text("🚀🚀🚀", pane(2, 5)), vec!["🚀", "🚀", "🚀"]);
+        assert_eq!(wrap_text("🚀🚀🚀", pane(4, 5)), vec!["🚀🚀", "🚀"]);
+        assert_eq!(wrap_text("go 🚀", pane(5, 5)), vec!["go 🚀"]);
+        assert_eq!(wrap_text("go 🚀", pane(4, 5)), vec!["go", "🚀"]);
+    }
 
-        if !current_line.is_empty() {
-            wrapped_lines.push(current_line);
+    #[test]
+    fn mixed_text_never_overflows_the_pane() {
+        let text = "ok 你好 go https://例え.jp/🚀/path héllo 世界🚀wörld";
+
+        assert_eq!(wrap_text(text, pane(5, 5))[..3], ["ok", "你好", "go"]);
+        for width in 2..=20 {
+            let lines = wrap_text(text, pane(width, 5));
+            for line in &lines {
+                assert!(
+                    line.width() <= width as usize,
+                    "{:?} wider than {}",
+                    line,
+                    width
+                );
+            }
+            let joined = lines.concat().replace(' ', "");
+            assert_eq!(joined, text.replace(' ', ""), "at width {}", width);
         }
     }
 
-    let start = if wrapped_lines.len() > height {
-        wrapped_lines.len() - height
-    } else {
-        0
-    };
+    #[test]
+    fn a_character_wider_than_the_pane_still_shows() {
+        assert_eq!(wrap_text("你好", pane(1, 5)), vec!["你", "好"]);
+    }
 
-    wrapped_lines[start..].join("\n")
+    #[test]
+    fn the_end_of_wrapped_text_counts_broken_words() {
+        assert_eq!(
+            get_end_of_wrapped_text("abcdefghij 你好世界", pane(4, 2)),
+            "你好\n世界"
+        );
+        assert_eq!(
+            get_end_of_wrapped_text("abcdefghij 你好世界", pane(4, 3)),
+            "ij\n你好\n世界"
+        );
+    }
+    fn styled(spans: &[(&'static str, Style)]) -> Line<'static> {
+        Line::from(
+            spans
+                .iter()
+                .map(|&(text, style)| Span::styled(text, style))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn styled_lines_wrap_with_each_span_keeping_its_style() {
+        let red = Style::default().fg(Color::Red);
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let line = styled(&[
+            ("error:", red),
+            (" disk ", Style::default()),
+            ("full now", bold),
+        ]);
+
+        assert_eq!(
+            wrap_line(&line, 10),
+            vec![
+                styled(&[("error:", red)]),
+                styled(&[("disk ", Style::default()), ("full", bold)]),
+                styled(&[("now", bold)]),
+            ]
+        );
+        assert_eq!(
+            wrap_line(&line, 20),
+            vec![styled(&[
+                ("error:", red),
+                (" disk ", Style::default()),
+                ("full now", bold)
+            ])]
+        );
+    }
+
+    #[test]
+    fn a_span_is_split_at_the_width_keeping_its_style() {
+        let green = Style::default().fg(Color::Green);
+        let blue = Style::default().fg(Color::Blue);
+
+        assert_eq!(
+            wrap_line(&styled(&[("abcdef", green)]), 4),
+            vec![styled(&[("abcd", green)]), styled(&[("ef", green)])]
+        );
+        assert_eq!(
+            wrap_line(&styled(&[("ab", green), ("cd", blue)]), 3),
+            vec![
+                styled(&[("ab", green), ("c", blue)]),
+                styled(&[("d", blue)])
+            ]
+        );
+    }
+
+    #[test]
+    fn wide_characters_split_by_columns_keeping_their_style() {
+        let yellow = Style::default().fg(Color::Yellow);
+
+        assert_eq!(
+            wrap_line(
+                &styled(&[("ok ", Style::default()), ("你好世界", yellow)]),
+                6
+            ),
+            vec![
+                styled(&[("ok", Style::default())]),
+                styled(&[("你好世", yellow)]),
+                styled(&[("界", yellow)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_line_style_is_patched_into_its_spans() {
+        let line = styled(&[
+            ("warn", Style::default().fg(Color::Yellow)),
+            (" done", Style::default()),
+        ])
+        .style(Style::default().bg(Color::Blue));
+
+        assert_eq!(
+            wrap_line(&line, 4),
+            vec![
+                styled(&[("warn", Style::default().fg(Color::Yellow).bg(Color::Blue))]),
+                styled(&[("done", Style::default().bg(Color::Blue))]),
+            ]
+        );
+    }
+
+    #[test]
+    fn an_empty_line_wraps_to_nothing() {
+        assert!(wrap_line(&Line::default(), 10).is_empty());
+        assert!(wrap_line(&styled(&[("  ", Style::default().fg(Color::Red))]), 10).is_empty());
+    }
+
+    #[test]
+    fn prefixed_lines_indent_their_wrapped_parts() {
+        let dim = Style::default().add_modifier(Modifier::DIM);
+        let red = Style::default().fg(Color::Red);
+        let line = styled(&[("hello you", red)]);
+        let mut scrollback = Scrollback::default();
+
+        assert_eq!(
+            scrollback.view_prefixed(&[("12:00 ", &line)], dim, pane(10, 5)),
+            vec![
+                styled(&[("12:00 ", dim), ("hell", red)]),
+                styled(&[("      ", dim), ("o", red)]),
+                styled(&[("      ", dim), ("you", red)]),
+            ]
+        );
+    }
+
+    // 100 lines in a 10 line pane.
+    fn scrolled() -> Scrollback {
+        let mut scrollback = Scrollback::default();
+        scrollback.resize(100, 10);
+        scrollback
+    }
+
+    #[test]
+    fn following_shows_the_tail() {
+        let mut scrollback = scrolled();
+        assert!(scrollback.is_following());
+        assert_eq!(scrollback.position(), (91, 100));
+
+        scrollback.resize(150, 10);
+        assert_eq!(scrollback.position(), (141, 150));
+    }
+
+    #[test]
+    fn scrolling_up_stays_put_as_lines_arrive() {
+        let mut scrollback = scrolled();
+        scrollback.page_up();
+        assert!(!scrollback.is_following());
+        assert_eq!(scrollback.position(), (81, 100));
+
+        scrollback.resize(150, 10);
+        assert_eq!(scrollback.position(), (81, 150));
+    }
+
+    #[test]
+    fn scrolling_stops_at_the_top() {
+        let mut scrollback = scrolled();
+        scrollback.scroll_up(95);
+        assert_eq!(scrollback.position(), (1, 100));
+        scrollback.page_up();
+        assert_eq!(scrollback.position(), (1, 100));
+
+        scrollback.end();
+        scrollback.home();
+        assert_eq!(scrollback.position(), (1, 100));
+        assert!(!scrollback.is_following());
+    }
+
+    #[test]
+    fn reaching_the_bottom_follows_again() {
+        let mut scrollback = scrolled();
+        scrollback.home();
+        scrollback.page_down();
+        assert_eq!(scrollback.position(), (11, 100));
+        assert!(!scrollback.is_following());
+
+        scrollback.scroll_down(1000);
+        assert_eq!(scrollback.position(), (91, 100));
+        assert!(scrollback.is_following());
+
+        scrollback.home();
+        scrollback.end();
+        assert!(scrollback.is_following());
+        assert_eq!(scrollback.position(), (91, 100));
+    }
+
+    #[test]
+    fn content_shorter_than_the_pane_does_not_scroll() {
+        let mut scrollback = Scrollback::default();
+        scrollback.resize(5, 10);
+
+        for scroll in [Scrollback::page_up, Scrollback::home, Scrollback::page_down] {
+            scroll(&mut scrollback);
+            assert!(scrollback.is_following());
+            assert_eq!(scrollback.position(), (1, 5));
+        }
+
+        scrollback.resize(0, 10);
+        assert_eq!(scrollback.position(), (0, 0));
+    }
+
+    #[test]
+    fn shrinking_content_or_growing_panes_clamp_the_offset() {
+        let mut scrollback = scrolled();
+        scrollback.page_up();
+        scrollback.resize(50, 10);
+        assert_eq!(scrollback.position(), (41, 50));
+
+        scrollback.resize(50, 60);
+        assert_eq!(scrollback.position(), (1, 50));
+    }
+
+    #[test]
+    fn the_view_is_the_scrolled_window() {
+        let lines = (1..=20)
+            .map(|i| Line::from(i.to_string()))
+            .collect::<Vec<_>>();
+        let mut scrollback = Scrollback::default();
+
+        let view = scrollback.view(&lines, pane(10, 5));
+        assert_eq!(view.first(), Some(&Line::from("16")));
+        assert_eq!(view.len(), 5);
+
+        scrollback.page_up();
+        let view = scrollback.view(&lines, pane(10, 5));
+        assert_eq!(view.first(), Some(&Line::from("11")));
+        assert_eq!(view.last(), Some(&Line::from("15")));
+    }
+
+    #[test]
+    fn resizing_a_scrolled_pane_rewraps_and_clamps_the_offset() {
+        let lines = ('a'..='j')
+            .map(|c| Line::from(c.to_string().repeat(20)))
+            .collect::<Vec<_>>();
+        let mut scrollback = Scrollback::default();
+        scrollback.view(&lines, pane(20, 5));
+        assert_eq!(scrollback.position(), (6, 10));
+        scrollback.scroll_up(3);
+
+        // Narrower, every line takes two rows and the offset counts rows.
+        let view = scrollback.view(&lines, pane(10, 5));
+        assert_eq!(scrollback.position(), (3, 20));
+        assert_eq!(view[0], Line::from("bbbbbbbbbb"));
+
+        // Wider again, the top row is past the last page and clamps to it.
+        scrollback.scroll_down(10);
+        assert!(!scrollback.is_following());
+        let view = scrollback.view(&lines, pane(20, 5));
+        assert_eq!(scrollback.position(), (6, 10));
+        assert_eq!(view.len(), 5);
+        assert_eq!(view[4], lines[9]);
+    }
+
+    #[test]
+    fn trimming_the_buffer_under_a_scrolled_view_keeps_a_full_page() {
+        let lines = (1..=30)
+            .map(|i| Line::from(i.to_string()))
+            .collect::<Vec<_>>();
+        let mut scrollback = Scrollback::default();
+        scrollback.view(&lines, pane(10, 5));
+        scrollback.page_up();
+        scrollback.page_up();
+        assert_eq!(scrollback.position(), (16, 30));
+
+        // The oldest lines dropped for retention, fewer than the offset remain.
+        let view = scrollback.view(&lines[22..], pane(10, 5));
+        assert_eq!(scrollback.position(), (4, 8));
+        assert_eq!(view.len(), 5);
+        assert_eq!(view[4], Line::from("30"));
+    }
 }
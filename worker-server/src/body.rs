@@ -0,0 +1,33 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use hyper::body::{Body, Frame};
+use tokio::sync::mpsc;
+
+use crate::{GenericError, Result};
+
+pub struct ChannelBody {
+    rx: mpsc::Receiver<Result<Bytes>>,
+}
+
+impl ChannelBody {
+    pub fn new(buffer: usize) -> (mpsc::Sender<Result<Bytes>>, Self) {
+        let (tx, rx) = mpsc::channel(buffer);
+        (tx, ChannelBody { rx })
+    }
+}
+
+impl Body for ChannelBody {
+    type Data = Bytes;
+    type Error = GenericError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>>>> {
+        self.rx
+            .poll_recv(cx)
+            .map(|chunk| chunk.map(|res| res.map(Frame::data)))
+    }
+}
@@ -0,0 +1,76 @@
+use std::fmt;
+
+use tokio::time::Duration;
+
+const BASE: &str = "base";
+const BURST: &str = "burst";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorPhase {
+    Base,
+    Burst,
+}
+
+impl fmt::Display for ErrorPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ErrorPhase::Base => BASE,
+            ErrorPhase::Burst => BURST,
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Each interval starts in the base phase and spends its last `burst_duration_ms` in the burst phase.
+pub fn error_phase(
+    elapsed: Duration,
+    burst_duration_ms: u64,
+    burst_interval_ms: u64,
+) -> ErrorPhase {
+    if burst_duration_ms == 0 || burst_interval_ms == 0 {
+        return ErrorPhase::Base;
+    }
+
+    let position = elapsed.as_millis() as u64 % burst_interval_ms;
+    if position >= burst_interval_ms.saturating_sub(burst_duration_ms) {
+        ErrorPhase::Burst
+    } else {
+        ErrorPhase::Base
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phase_at(ms: u64) -> ErrorPhase {
+        error_phase(Duration::from_millis(ms), 200, 1000)
+    }
+
+    #[test]
+    fn bursts_at_the_end_of_every_interval() {
+        assert_eq!(phase_at(0), ErrorPhase::Base);
+        assert_eq!(phase_at(799), ErrorPhase::Base);
+        assert_eq!(phase_at(800), ErrorPhase::Burst);
+        assert_eq!(phase_at(999), ErrorPhase::Burst);
+        assert_eq!(phase_at(1000), ErrorPhase::Base);
+        assert_eq!(phase_at(2850), ErrorPhase::Burst);
+    }
+
+    #[test]
+    fn never_bursts_without_a_duration_or_interval() {
+        let elapsed = Duration::from_millis(999);
+        assert_eq!(error_phase(elapsed, 0, 1000), ErrorPhase::Base);
+        assert_eq!(error_phase(elapsed, 200, 0), ErrorPhase::Base);
+    }
+
+    #[test]
+    fn bursts_all_the_time_when_as_long_as_the_interval() {
+        for ms in [0, 500, 1000, 1999] {
+            assert_eq!(
+                error_phase(Duration::from_millis(ms), 1000, 1000),
+                ErrorPhase::Burst
+            );
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use clap::builder::BoolishValueParser;
+use clap::error::ErrorKind;
+use clap::{ArgAction, CommandFactory, Parser, ValueEnum};
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Worker server simulating configurable backend behavior"
+)]
+pub struct Config {
+    /// Port to listen on
+    #[arg(long, env = "PORT", default_value_t = 3000, value_parser = clap::value_parser!(u16).range(1..))]
+    pub port: u16,
+
+    /// Name used to identify this worker in logs and responses
+    #[arg(long, env = "WORKER_NAME")]
+    pub name: Option<String>,
+
+    /// Address to bind to, defaults to one derived from APP_ENVIRONMENT
+    #[arg(long, env = "BIND")]
+    pub bind: Option<IpAddr>,
+
+    /// Initial minimum simulated work duration in milliseconds
+    #[arg(long, env = "MIN_DURATION", default_value_t = 10)]
+    pub min_duration: u64,
+
+    /// Initial maximum simulated work duration in milliseconds
+    #[arg(long, env = "MAX_DURATION", default_value_t = 10)]
+    pub max_duration: u64,
+
+    /// Initial probability of a /work request failing, between 0 and 1
+    #[arg(long, env = "ERROR_RATE", default_value_t = 0.0, value_parser = parse_error_rate)]
+    pub error_rate: f64,
+
+    /// Seed for the simulated durations and errors, the same seed replays the same sequence.
+    /// A random one is picked and logged when unset
+    #[arg(long, env = "RANDOM_SEED")]
+    pub random_seed: Option<u64>,
+
+    /// Time after startup during which /ready reports not ready
+    #[arg(long, env = "STARTUP_DELAY_MS", default_value_t = 0)]
+    pub startup_delay_ms: u64,
+
+    /// Hard cap on memory allocated by the memory pressure simulation
+    #[arg(long, env = "MEMORY_CAP_MB", default_value_t = 512)]
+    pub memory_cap_mb: usize,
+
+    /// Maximum number of request body bytes echoed back by /echo
+    #[arg(long, env = "ECHO_MAX_BYTES", default_value_t = 64 * 1024)]
+    pub echo_max_bytes: usize,
+
+    /// Maximum accepted request body size, larger bodies are rejected with 413
+    #[arg(long, env = "MAX_BODY_BYTES", default_value_t = 4 * 1024 * 1024)]
+    pub max_body_bytes: usize,
+
+    /// How long /work responses are replayed for a repeated Idempotency-Key
+    #[arg(long, env = "IDEMPOTENCY_TTL_MS", default_value_t = 60_000)]
+    pub idempotency_ttl_ms: u64,
+
+    /// Maximum number of cached Idempotency-Key responses
+    #[arg(long, env = "IDEMPOTENCY_CAPACITY", default_value_t = 1000)]
+    pub idempotency_capacity: usize,
+
+    /// Serve HTTP/2 with prior knowledge (h2c) instead of HTTP/1.1
+    #[arg(long, env = "HTTP2", value_parser = BoolishValueParser::new())]
+    pub http2: bool,
+
+    /// Keep HTTP/1.1 connections open between requests
+    #[arg(long, env = "KEEP_ALIVE", default_value_t = true, action = ArgAction::Set, value_parser = BoolishValueParser::new())]
+    pub keep_alive: bool,
+
+    /// Close connections that have been idle between requests for this long
+    #[arg(long, env = "IDLE_TIMEOUT_MS")]
+    pub idle_timeout_ms: Option<u64>,
+
+    /// Time an HTTP/1.1 client has to send the complete request headers
+    #[arg(long, env = "HEADER_READ_TIMEOUT_MS", default_value_t = 30_000)]
+    pub header_read_timeout_ms: u64,
+
+    /// Maximum number of concurrently open connections, further clients wait to be accepted
+    #[arg(long, env = "MAX_CONNECTIONS", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_connections: Option<u32>,
+
+    /// Log output format, json emits one structured object per line
+    #[arg(long, env = "LOG_FORMAT", value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// File used to persist /setup changes across restarts
+    #[arg(long, env = "STATE_FILE")]
+    pub state_file: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl From<LogFormat> for lb_telemetry::Format {
+    fn from(format: LogFormat) -> Self {
+        match format {
+            LogFormat::Text => lb_telemetry::Format::Text,
+            LogFormat::Json => lb_telemetry::Format::Json,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Self {
+        let config = lb_config::load::<Config>("worker");
+        if let Err(msg) = config.validate() {
+            Config::command()
+                .error(ErrorKind::ArgumentConflict, msg)
+                .exit();
+        }
+        config
+    }
+
+    pub fn name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("worker-{}", self.port))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.min_duration > self.max_duration {
+            return Err(format!(
+                "--min-duration ({}) must not be greater than --max-duration ({})",
+                self.min_duration, self.max_duration
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn parse_error_rate(value: &str) -> Result<f64, String> {
+    let rate = value
+        .parse::<f64>()
+        .map_err(|_| format!("'{}' is not a number", value))?;
+    if (0.0..=1.0).contains(&rate) {
+        Ok(rate)
+    } else {
+        Err(format!("error rate must be between 0 and 1, got {}", rate))
+    }
+}
+
+// Environment variables are the process's, tests setting them take turns.
+#[cfg(test)]
+static ENV: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+// Parses `args` with only `vars` of the variables the config reads set.
+#[cfg(test)]
+pub(crate) fn parse_with_env(vars: &[(&str, &str)], args: &[&str]) -> Result<Config, clap::Error> {
+    let _env = ENV
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    let read = Config::command()
+        .get_arguments()
+        .filter_map(|arg| arg.get_env().map(|env| env.to_os_string()))
+        .collect::<Vec<_>>();
+    for key in &read {
+        std::env::remove_var(key);
+    }
+    for (key, value) in vars {
+        std::env::set_var(key, value);
+    }
+    let config = Config::try_parse_from(["worker-server"].iter().chain(args));
+    for key in &read {
+        std::env::remove_var(key);
+    }
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(vars: &[(&str, &str)], args: &[&str]) -> String {
+        parse_with_env(vars, args).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn defaults_apply_without_flags_or_environment() {
+        let config = parse_with_env(&[], &[]).unwrap();
+
+        assert_eq!(config.port, 3000);
+        assert_eq!(config.name(), "worker-3000");
+        assert_eq!(config.bind, None);
+        assert_eq!((config.min_duration, config.max_duration), (10, 10));
+        assert_eq!(config.error_rate, 0.0);
+        assert!(config.keep_alive);
+        assert!(!config.http2);
+        assert_eq!(config.log_format, LogFormat::Text);
+    }
+
+    #[test]
+    fn the_environment_overrides_defaults() {
+        let vars = [
+            ("PORT", "3001"),
+            ("WORKER_NAME", "worker-a"),
+            ("BIND", "0.0.0.0"),
+            ("MIN_DURATION", "5"),
+            ("MAX_DURATION", "50"),
+            ("ERROR_RATE", "0.25"),
+            ("KEEP_ALIVE", "false"),
+            ("HTTP2", "1"),
+        ];
+        let config = parse_with_env(&vars, &[]).unwrap();
+
+        assert_eq!(config.port, 3001);
+        assert_eq!(config.name(), "worker-a");
+        assert_eq!(config.bind, Some(IpAddr::from([0, 0, 0, 0])));
+        assert_eq!((config.min_duration, config.max_duration), (5, 50));
+        assert_eq!(config.error_rate, 0.25);
+        assert!(!config.keep_alive);
+        assert!(config.http2);
+    }
+
+    #[test]
+    fn flags_override_the_environment() {
+        let vars = [
+            ("PORT", "3001"),
+            ("ERROR_RATE", "0.25"),
+            ("KEEP_ALIVE", "false"),
+        ];
+        let args = [
+            "--port",
+            "3002",
+            "--error-rate",
+            "0.5",
+            "--keep-alive",
+            "true",
+        ];
+        let config = parse_with_env(&vars, &args).unwrap();
+
+        assert_eq!(config.port, 3002);
+        assert_eq!(config.name(), "worker-3002");
+        assert_eq!(config.error_rate, 0.5);
+        assert!(config.keep_alive);
+    }
+
+    #[test]
+    fn error_rates_are_between_0_and_1() {
+        assert!(error(&[], &["--error-rate", "1.5"])
+            .contains("error rate must be between 0 and 1, got 1.5"));
+        assert!(error(&[("ERROR_RATE", "-0.1")], &[])
+            .contains("error rate must be between 0 and 1, got -0.1"));
+        assert!(error(&[], &["--error-rate", "often"]).contains("'often' is not a number"));
+        assert_eq!(
+            parse_with_env(&[], &["--error-rate", "1"])
+                .unwrap()
+                .error_rate,
+            1.0
+        );
+    }
+
+    #[test]
+    fn ports_are_between_1_and_65535() {
+        assert!(error(&[], &["--port", "0"]).contains("0 is not in 1..=65535"));
+        assert!(error(&[("PORT", "65536")], &[]).contains("invalid value '65536'"));
+        assert_eq!(
+            parse_with_env(&[], &["--port", "65535"]).unwrap().port,
+            65535
+        );
+    }
+
+    #[test]
+    fn min_duration_must_not_exceed_max_duration() {
+        let config = parse_with_env(&[], &["--min-duration", "20"]).unwrap();
+
+        assert_eq!(
+            config.validate(),
+            Err("--min-duration (20) must not be greater than --max-duration (10)".to_string())
+        );
+        let config = parse_with_env(&[("MAX_DURATION", "20")], &["--min-duration", "20"]);
+        assert_eq!(config.unwrap().validate(), Ok(()));
+    }
+
+    #[test]
+    fn help_lists_every_flag_with_its_variable() {
+        let help = Config::command().render_long_help().to_string();
+
+        for (flag, env) in [
+            ("--port", "PORT"),
+            ("--name", "WORKER_NAME"),
+            ("--min-duration", "MIN_DURATION"),
+            ("--max-duration", "MAX_DURATION"),
+            ("--error-rate", "ERROR_RATE"),
+            ("--bind", "BIND"),
+        ] {
+            assert!(help.contains(flag), "{} missing from\n{}", flag, help);
+            assert!(
+                help.contains(&format!("[env: {}", env)),
+                "{} missing from\n{}",
+                env,
+                help
+            );
+        }
+    }
+}
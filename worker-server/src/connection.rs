@@ -0,0 +1,76 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use tokio::time::{sleep_until, Duration, Instant};
+
+// Tracks whether a connection is between requests and for how long.
+pub struct IdleTracker {
+    in_flight: AtomicUsize,
+    idle_since: Mutex<Instant>,
+}
+
+impl IdleTracker {
+    pub fn new() -> Self {
+        IdleTracker {
+            in_flight: AtomicUsize::new(0),
+            idle_since: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn request_started(&self) {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn request_finished(&self) {
+        *self.idle_since.lock().unwrap() = Instant::now();
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    fn idle_deadline(&self, timeout: Duration) -> Option<Instant> {
+        if self.in_flight.load(Ordering::SeqCst) > 0 {
+            None
+        } else {
+            Some(*self.idle_since.lock().unwrap() + timeout)
+        }
+    }
+}
+
+// Drives `conn` to completion, starting a graceful shutdown once it has been idle for `idle_timeout`.
+pub async fn serve_with_idle_timeout<C, E>(
+    conn: C,
+    graceful_shutdown: fn(Pin<&mut C>),
+    tracker: &IdleTracker,
+    idle_timeout: Option<Duration>,
+) -> Result<bool, E>
+where
+    C: Future<Output = Result<(), E>>,
+{
+    tokio::pin!(conn);
+
+    let Some(idle_timeout) = idle_timeout else {
+        return conn.await.map(|_| false);
+    };
+
+    let mut closed_for_idle = false;
+    loop {
+        // While a request is running, re-check periodically instead of sleeping forever.
+        let deadline = tracker
+            .idle_deadline(idle_timeout)
+            .unwrap_or_else(|| Instant::now() + idle_timeout);
+
+        tokio::select! {
+            res = conn.as_mut() => return res.map(|_| closed_for_idle),
+            _ = sleep_until(deadline), if !closed_for_idle => {
+                if tracker
+                    .idle_deadline(idle_timeout)
+                    .is_some_and(|deadline| deadline <= Instant::now())
+                {
+                    graceful_shutdown(conn.as_mut());
+                    closed_for_idle = true;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use tokio::time::Duration;
+
+pub fn degraded_duration(base: u64, degrade_ms_per_minute: f64, elapsed: Duration) -> u64 {
+    let creep = degrade_ms_per_minute * elapsed.as_secs_f64() / 60.0;
+    base.saturating_add(creep.max(0.0).round() as u64)
+}
+
+pub fn concurrency_adjusted_duration(base: u64, k: f64, in_flight: usize, threshold: usize) -> u64 {
+    let excess = in_flight.saturating_sub(threshold) as f64;
+    (base as f64 * (1.0 + k * excess)).round() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_grow_past_the_threshold_only() {
+        assert_eq!(concurrency_adjusted_duration(100, 0.5, 0, 2), 100);
+        assert_eq!(concurrency_adjusted_duration(100, 0.5, 2, 2), 100);
+        assert_eq!(concurrency_adjusted_duration(100, 0.5, 3, 2), 150);
+        assert_eq!(concurrency_adjusted_duration(100, 0.5, 6, 2), 300);
+        assert_eq!(concurrency_adjusted_duration(100, 0.0, 6, 2), 100);
+    }
+}
@@ -0,0 +1,150 @@
+use std::fmt;
+
+use http_body_util::BodyExt;
+use tokio::time::{sleep, Duration};
+use tracing::{error, warn};
+
+use crate::body::ChannelBody;
+use crate::BoxBody;
+
+// Gives hyper a chance to flush the response head before the connection is torn down.
+const HEADER_FLUSH_DELAY: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BodyFault {
+    DropAfterHeaders,
+    TruncateAt(u64),
+}
+
+impl BodyFault {
+    pub fn new(truncate_body_at_bytes: Option<u64>, drop_after_headers: bool) -> Option<Self> {
+        match (drop_after_headers, truncate_body_at_bytes) {
+            (true, _) => Some(BodyFault::DropAfterHeaders),
+            (false, Some(bytes)) => Some(BodyFault::TruncateAt(bytes)),
+            (false, None) => None,
+        }
+    }
+
+    // Forwards `body` until the fault triggers, then fails it so hyper aborts the connection.
+    pub fn inject(self, mut body: BoxBody) -> BoxBody {
+        let limit = match self {
+            BodyFault::DropAfterHeaders => 0,
+            BodyFault::TruncateAt(bytes) => bytes,
+        };
+        let (tx, channel_body) = ChannelBody::new(1);
+
+        tokio::task::spawn(async move {
+            let mut sent = 0u64;
+            sleep(HEADER_FLUSH_DELAY).await;
+
+            while sent < limit {
+                let chunk = match body.frame().await {
+                    Some(Ok(frame)) => match frame.into_data() {
+                        Ok(chunk) => chunk,
+                        Err(_) => continue,
+                    },
+                    Some(Err(e)) => {
+                        let _ = tx.send(Err(e)).await;
+                        return;
+                    }
+                    None => {
+                        warn!("Body ended after {} bytes, before the injected fault", sent);
+                        return;
+                    }
+                };
+
+                let remaining = (limit - sent) as usize;
+                let chunk = if chunk.len() > remaining {
+                    chunk.slice(..remaining)
+                } else {
+                    chunk
+                };
+                sent += chunk.len() as u64;
+                if tx.send(Ok(chunk)).await.is_err() {
+                    return;
+                }
+            }
+
+            sleep(HEADER_FLUSH_DELAY).await;
+            error!(
+                "Injected fault: {}, dropping connection after {} body bytes",
+                self, sent
+            );
+            let _ = tx
+                .send(Err(format!("injected fault: {}", self).into()))
+                .await;
+        });
+
+        channel_body.boxed()
+    }
+}
+
+impl fmt::Display for BodyFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BodyFault::DropAfterHeaders => write!(f, "drop_after_headers"),
+            BodyFault::TruncateAt(bytes) => write!(f, "truncate_body_at_bytes={}", bytes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The data frames `fault` lets through `body`, then the error it ended with if any.
+    async fn injected(fault: BodyFault, body: &'static str) -> (Vec<u8>, Option<String>) {
+        let mut body = fault.inject(crate::full(body));
+        let mut data = Vec::new();
+        while let Some(frame) = body.frame().await {
+            match frame {
+                Ok(frame) => data.extend_from_slice(&frame.into_data().unwrap()),
+                Err(e) => return (data, Some(e.to_string())),
+            }
+        }
+        (data, None)
+    }
+
+    #[test]
+    fn dropping_after_headers_wins_over_truncating() {
+        assert_eq!(BodyFault::new(None, false), None);
+        assert_eq!(
+            BodyFault::new(Some(5), false),
+            Some(BodyFault::TruncateAt(5))
+        );
+        assert_eq!(
+            BodyFault::new(Some(5), true),
+            Some(BodyFault::DropAfterHeaders)
+        );
+    }
+
+    #[tokio::test]
+    async fn truncated_bodies_fail_after_the_limit() {
+        assert_eq!(
+            injected(BodyFault::TruncateAt(4), "0123456789").await,
+            (
+                b"0123".to_vec(),
+                Some("injected fault: truncate_body_at_bytes=4".to_string())
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn dropped_bodies_fail_before_any_byte() {
+        assert_eq!(
+            injected(BodyFault::DropAfterHeaders, "0123456789").await,
+            (
+                Vec::new(),
+                Some("injected fault: drop_after_headers".to_string())
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn bodies_shorter_than_the_limit_end_normally() {
+        assert_eq!(
+            injected(BodyFault::TruncateAt(100), "0123456789").await,
+            (b"0123456789".to_vec(), None)
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+
+const ALL: &str = "all";
+const WORK: &str = "work";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HangScope {
+    All,
+    Work,
+}
+
+pub struct ConversionError;
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid hang scope. Valid values are '{}' or '{}'",
+            ALL, WORK
+        )
+    }
+}
+
+impl TryFrom<&str> for HangScope {
+    type Error = ConversionError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            ALL => Ok(HangScope::All),
+            WORK => Ok(HangScope::Work),
+            _ => Err(ConversionError),
+        }
+    }
+}
+
+impl fmt::Display for HangScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            HangScope::All => ALL,
+            HangScope::Work => WORK,
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl HangScope {
+    pub fn applies_to(&self, path: &str) -> bool {
+        match self {
+            HangScope::All => path != "/reset",
+            HangScope::Work => path == "/work",
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use bytes::Bytes;
+use hyper::{HeaderMap, StatusCode};
+use tokio::sync::watch;
+use tokio::time::{Duration, Instant};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+pub const REPLAY_HEADER: &str = "x-idempotent-replay";
+
+#[derive(Clone)]
+pub struct CachedResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+enum Entry {
+    Pending(watch::Receiver<()>),
+    Done {
+        response: CachedResponse,
+        stored_at: Instant,
+        last_used: Instant,
+    },
+}
+
+pub enum Claim<'a> {
+    Replay(CachedResponse),
+    Owner(Ticket<'a>),
+}
+
+pub struct IdempotencyCache {
+    entries: Mutex<HashMap<String, Entry>>,
+    capacity: usize,
+    ttl: Duration,
+}
+
+impl IdempotencyCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        IdempotencyCache {
+            entries: Mutex::new(HashMap::new()),
+            capacity: capacity.max(1),
+            ttl,
+        }
+    }
+
+    // Returns the cached response for `key`, or a ticket making the caller responsible
+    // for producing it. Concurrent callers with the same key wait for the ticket holder.
+    pub async fn claim(&self, key: String) -> Claim<'_> {
+        loop {
+            let mut pending = {
+                let mut entries = self.entries.lock().unwrap();
+                match entries.get_mut(&key) {
+                    Some(Entry::Pending(rx)) => rx.clone(),
+                    Some(Entry::Done {
+                        response,
+                        stored_at,
+                        last_used,
+                    }) if stored_at.elapsed() < self.ttl => {
+                        *last_used = Instant::now();
+                        return Claim::Replay(response.clone());
+                    }
+                    _ => {
+                        let (tx, rx) = watch::channel(());
+                        entries.insert(key.clone(), Entry::Pending(rx));
+                        return Claim::Owner(Ticket {
+                            cache: self,
+                            key,
+                            _done: tx,
+                            completed: false,
+                        });
+                    }
+                }
+            };
+
+            // Resolves once the ticket is completed or dropped, either way look again.
+            let _ = pending.changed().await;
+        }
+    }
+
+    fn store(&self, key: &str, response: CachedResponse) {
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        entries.insert(
+            key.to_string(),
+            Entry::Done {
+                response,
+                stored_at: now,
+                last_used: now,
+            },
+        );
+
+        entries.retain(|_, entry| match entry {
+            Entry::Pending(_) => true,
+            Entry::Done { stored_at, .. } => stored_at.elapsed() < self.ttl,
+        });
+
+        while entries.len() > self.capacity {
+            let least_recently_used = entries
+                .iter()
+                .filter_map(|(key, entry)| match entry {
+                    Entry::Done { last_used, .. } => Some((key, *last_used)),
+                    Entry::Pending(_) => None,
+                })
+                .min_by_key(|(_, last_used)| *last_used)
+                .map(|(key, _)| key.clone());
+            match least_recently_used {
+                Some(key) => entries.remove(&key),
+                None => break,
+            };
+        }
+    }
+
+    fn abandon(&self, key: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(Entry::Pending(_)) = entries.get(key) {
+            entries.remove(key);
+        }
+    }
+}
+
+pub struct Ticket<'a> {
+    cache: &'a IdempotencyCache,
+    key: String,
+    _done: watch::Sender<()>,
+    completed: bool,
+}
+
+impl Ticket<'_> {
+    pub fn complete(mut self, response: CachedResponse) {
+        self.cache.store(&self.key, response);
+        self.completed = true;
+    }
+}
+
+impl Drop for Ticket<'_> {
+    fn drop(&mut self) {
+        // A failed request is not cached, so waiting duplicates get to run it themselves.
+        if !self.completed {
+            self.cache.abandon(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(body: &'static str) -> CachedResponse {
+        CachedResponse {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(body.as_bytes()),
+        }
+    }
+
+    fn replayed(claim: Claim<'_>) -> Option<Bytes> {
+        match claim {
+            Claim::Replay(cached) => Some(cached.body),
+            Claim::Owner(_) => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn completed_keys_are_replayed() {
+        let cache = IdempotencyCache::new(10, Duration::from_secs(60));
+
+        let Claim::Owner(ticket) = cache.claim("a".to_string()).await else {
+            panic!("a new key is replayed");
+        };
+        ticket.complete(response("first"));
+
+        assert_eq!(
+            replayed(cache.claim("a".to_string()).await),
+            Some(Bytes::from_static(b"first"))
+        );
+        assert_eq!(replayed(cache.claim("b".to_string()).await), None);
+    }
+
+    #[tokio::test]
+    async fn expired_keys_are_claimed_again() {
+        let cache = IdempotencyCache::new(10, Duration::from_millis(20));
+        let Claim::Owner(ticket) = cache.claim("a".to_string()).await else {
+            panic!("a new key is replayed");
+        };
+        ticket.complete(response("first"));
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(replayed(cache.claim("a".to_string()).await), None);
+    }
+
+    #[tokio::test]
+    async fn abandoned_keys_are_not_cached() {
+        let cache = IdempotencyCache::new(10, Duration::from_secs(60));
+        drop(cache.claim("a".to_string()).await);
+
+        assert_eq!(replayed(cache.claim("a".to_string()).await), None);
+    }
+
+    #[tokio::test]
+    async fn the_least_recently_used_key_is_evicted() {
+        let cache = IdempotencyCache::new(2, Duration::from_secs(60));
+        for key in ["a", "b"] {
+            if let Claim::Owner(ticket) = cache.claim(key.to_string()).await {
+                ticket.complete(response(key));
+            }
+        }
+        // Used after b, so b goes first.
+        assert!(replayed(cache.claim("a".to_string()).await).is_some());
+        if let Claim::Owner(ticket) = cache.claim("c".to_string()).await {
+            ticket.complete(response("c"));
+        }
+
+        assert!(replayed(cache.claim("a".to_string()).await).is_some());
+        assert!(replayed(cache.claim("c".to_string()).await).is_some());
+        assert_eq!(replayed(cache.claim("b".to_string()).await), None);
+    }
+
+    #[tokio::test]
+    async fn duplicates_wait_for_the_first_arrival() {
+        let cache = IdempotencyCache::new(10, Duration::from_secs(60));
+        let Claim::Owner(ticket) = cache.claim("a".to_string()).await else {
+            panic!("a new key is replayed");
+        };
+
+        let (duplicate, ()) = tokio::join!(cache.claim("a".to_string()), async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            ticket.complete(response("first"));
+        });
+        assert_eq!(replayed(duplicate), Some(Bytes::from_static(b"first")));
+    }
+
+    #[tokio::test]
+    async fn duplicates_run_the_work_when_the_first_arrival_fails() {
+        let cache = IdempotencyCache::new(10, Duration::from_secs(60));
+        let ticket = cache.claim("a".to_string()).await;
+
+        let (duplicate, ()) = tokio::join!(cache.claim("a".to_string()), async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            drop(ticket);
+        });
+        assert_eq!(replayed(duplicate), None);
+    }
+}
@@ -0,0 +1,208 @@
+use std::f64::consts::PI;
+use std::fmt;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+pub const UNIFORM: &str = "uniform";
+pub const NORMAL: &str = "normal";
+pub const PARETO: &str = "pareto";
+pub const LOGNORMAL: &str = "lognormal";
+pub const BIMODAL: &str = "bimodal";
+
+const MAX_SAMPLE_MS: f64 = 60_000.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum LatencyDistribution {
+    #[default]
+    Uniform,
+    Normal {
+        mean: f64,
+        stddev: f64,
+    },
+    Pareto {
+        scale: f64,
+        shape: f64,
+    },
+    LogNormal {
+        median: f64,
+        sigma: f64,
+    },
+    Bimodal {
+        fast: f64,
+        slow: f64,
+        slow_probability: f64,
+    },
+}
+
+impl LatencyDistribution {
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            LatencyDistribution::Uniform => true,
+            LatencyDistribution::Normal { mean, stddev } => mean >= 0.0 && stddev >= 0.0,
+            LatencyDistribution::Pareto { scale, shape } => scale > 0.0 && shape > 0.0,
+            LatencyDistribution::LogNormal { median, sigma } => median > 0.0 && sigma >= 0.0,
+            LatencyDistribution::Bimodal {
+                fast,
+                slow,
+                slow_probability,
+            } => fast >= 0.0 && slow >= 0.0 && (0.0..=1.0).contains(&slow_probability),
+        }
+    }
+
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R, min: u64, max: u64) -> u64 {
+        let value = match *self {
+            LatencyDistribution::Uniform => return rng.gen_range(min..=max),
+            LatencyDistribution::Normal { mean, stddev } => mean + stddev * standard_normal(rng),
+            LatencyDistribution::Pareto { scale, shape } => {
+                scale / open_unit_interval(rng).powf(1.0 / shape)
+            }
+            LatencyDistribution::LogNormal { median, sigma } => {
+                median * (sigma * standard_normal(rng)).exp()
+            }
+            LatencyDistribution::Bimodal {
+                fast,
+                slow,
+                slow_probability,
+            } => {
+                if rng.gen_bool(slow_probability) {
+                    slow
+                } else {
+                    fast
+                }
+            }
+        };
+        value.clamp(0.0, MAX_SAMPLE_MS).round() as u64
+    }
+}
+
+impl fmt::Display for LatencyDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LatencyDistribution::Uniform => write!(f, "{}", UNIFORM),
+            LatencyDistribution::Normal { mean, stddev } => {
+                write!(f, "{}(mean: {}, stddev: {})", NORMAL, mean, stddev)
+            }
+            LatencyDistribution::Pareto { scale, shape } => {
+                write!(f, "{}(scale: {}, shape: {})", PARETO, scale, shape)
+            }
+            LatencyDistribution::LogNormal { median, sigma } => {
+                write!(f, "{}(median: {}, sigma: {})", LOGNORMAL, median, sigma)
+            }
+            LatencyDistribution::Bimodal {
+                fast,
+                slow,
+                slow_probability,
+            } => write!(
+                f,
+                "{}(fast: {}, slow: {}, slow_probability: {})",
+                BIMODAL, fast, slow, slow_probability
+            ),
+        }
+    }
+}
+
+// Uniform sample in (0, 1], safe to feed into ln() and powf().
+fn open_unit_interval<R: Rng + ?Sized>(rng: &mut R) -> f64 {
+    1.0 - rng.gen::<f64>()
+}
+
+// Box-Muller transform.
+fn standard_normal<R: Rng + ?Sized>(rng: &mut R) -> f64 {
+    let u1 = open_unit_interval(rng);
+    let u2 = rng.gen::<f64>();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    const SAMPLES: usize = 100_000;
+
+    fn mean(distribution: LatencyDistribution, min: u64, max: u64) -> f64 {
+        let mut rng = StdRng::seed_from_u64(7);
+        let total: u64 = (0..SAMPLES)
+            .map(|_| distribution.sample(&mut rng, min, max))
+            .sum();
+        total as f64 / SAMPLES as f64
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        let tolerance = expected * 0.02;
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "mean {} is not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn uniform_mean_is_halfway() {
+        assert_close(mean(LatencyDistribution::Uniform, 20, 80), 50.0);
+    }
+
+    #[test]
+    fn uniform_stays_within_bounds() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..1000 {
+            let sample = LatencyDistribution::Uniform.sample(&mut rng, 20, 80);
+            assert!((20..=80).contains(&sample));
+        }
+    }
+
+    #[test]
+    fn normal_mean() {
+        let distribution = LatencyDistribution::Normal {
+            mean: 100.0,
+            stddev: 10.0,
+        };
+        assert_close(mean(distribution, 0, 0), 100.0);
+    }
+
+    #[test]
+    fn pareto_mean() {
+        // shape * scale / (shape - 1)
+        let distribution = LatencyDistribution::Pareto {
+            scale: 10.0,
+            shape: 3.0,
+        };
+        assert_close(mean(distribution, 0, 0), 15.0);
+    }
+
+    #[test]
+    fn lognormal_mean() {
+        // median * e^(sigma^2 / 2)
+        let distribution = LatencyDistribution::LogNormal {
+            median: 50.0,
+            sigma: 0.5,
+        };
+        assert_close(mean(distribution, 0, 0), 50.0 * (0.125f64).exp());
+    }
+
+    #[test]
+    fn bimodal_mean() {
+        let distribution = LatencyDistribution::Bimodal {
+            fast: 10.0,
+            slow: 200.0,
+            slow_probability: 0.1,
+        };
+        assert_close(mean(distribution, 0, 0), 29.0);
+    }
+
+    #[test]
+    fn samples_are_capped() {
+        let distribution = LatencyDistribution::Normal {
+            mean: 1_000_000.0,
+            stddev: 0.0,
+        };
+        let mut rng = StdRng::seed_from_u64(7);
+        assert_eq!(distribution.sample(&mut rng, 0, 0), MAX_SAMPLE_MS as u64);
+    }
+}
@@ -0,0 +1,3102 @@
+mod body;
+mod burst;
+mod config;
+mod connection;
+mod degradation;
+mod fault;
+mod hang;
+mod idempotency;
+mod latency;
+mod memory;
+mod negotiate;
+mod overrides;
+mod persistence;
+mod schedule;
+mod timeseries;
+mod work_mode;
+mod work_params;
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use body::ChannelBody;
+use burst::ErrorPhase;
+use bytes::Bytes;
+use connection::IdleTracker;
+use environment::Environment;
+use fault::BodyFault;
+use hang::HangScope;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper::server::conn::{http1, http2};
+use hyper::service::service_fn;
+use hyper::{body::Incoming as IncomingBody, header, Method, Request, Response, StatusCode};
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use idempotency::{CachedResponse, Claim, IdempotencyCache};
+use latency::LatencyDistribution;
+use lb_api::{MemoryStats, Ready, SetupRequest, SetupResponse, WorkResponse, WorkerStats};
+use memory::{MemorySimulator, BYTES_PER_MB};
+use negotiate::ResponseFormat;
+use overrides::Overrides;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use schedule::{Schedule, SchedulePhase};
+use serde::{Deserialize, Serialize};
+use timeseries::MinuteSeries;
+use tokio::net::TcpListener;
+use tokio::sync::{watch, RwLock, Semaphore};
+use tokio::time::{sleep, sleep_until, Duration, Instant};
+use tracing::{debug, error, info, instrument, warn, Instrument};
+use work_mode::WorkMode;
+use work_params::WorkParams;
+
+pub use config::{Config, LogFormat};
+
+pub type GenericError = Box<dyn std::error::Error + Send + Sync>;
+pub type Result<T> = std::result::Result<T, GenericError>;
+pub type BoxBody = http_body_util::combinators::BoxBody<Bytes, GenericError>;
+// What requests are read from, an in memory body maps its error from Infallible.
+pub type RequestBody = http_body_util::combinators::BoxBody<Bytes, hyper::Error>;
+
+const DEFAULT_MIN_DURATION: u64 = 10;
+const DEFAULT_MAX_DURATION: u64 = 10;
+const DEFAULT_ERROR_RATE: f64 = 0.0;
+const DEFAULT_BURST_ERROR_RATE: f64 = 0.0;
+const DEFAULT_BURST_DURATION: u64 = 0;
+const DEFAULT_BURST_INTERVAL: u64 = 0;
+const DEFAULT_DEGRADE_MS_PER_MINUTE: f64 = 0.0;
+const DEFAULT_CONCURRENCY_K: f64 = 0.0;
+const DEFAULT_CONCURRENCY_THRESHOLD: usize = 0;
+const DEFAULT_HEADER_DELAY: u64 = 0;
+const DEFAULT_BODY_CHUNKS: u64 = 1;
+const DEFAULT_BODY_CHUNK_DELAY: u64 = 0;
+const MAX_BODY_CHUNKS: u64 = 1000;
+const DEFAULT_MEMORY_MB: u64 = 0;
+const DEFAULT_RETAIN_MB: u64 = 0;
+
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+const NDJSON: &str = "application/x-ndjson";
+
+// Endpoints answering in the format requested by the Accept header.
+const NEGOTIATED_PATHS: [&str; 3] = ["/health", "/setup", "/work"];
+
+const CONFIRMATION_REQUIRED: &str = "Confirmation required, send {\"confirm\": true}";
+
+// What every request to one worker shares: its configuration, the state /setup changes and what
+// the simulations keep track of. Tests can build one around a state of their own.
+pub struct Worker {
+    config: Config,
+    state: Arc<RwLock<GlobalState>>,
+    in_flight: Arc<AtomicUsize>,
+    memory: Arc<MemorySimulator>,
+    hang: watch::Sender<Option<HangScope>>,
+    started_at: Instant,
+    timeseries: Mutex<MinuteSeries>,
+    idempotency: IdempotencyCache,
+    schedule: Mutex<Option<Schedule>>,
+    next_schedule_id: AtomicU64,
+    // Every simulated duration and error is drawn from it, in the order requests ask.
+    seed: u64,
+    rng: Mutex<StdRng>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GlobalState {
+    min_duration: u64,
+    max_duration: u64,
+    error_rate: f64,
+    latency_distribution: LatencyDistribution,
+    header_delay: u64,
+    body_chunks: u64,
+    body_chunk_delay: u64,
+    work_mode: WorkMode,
+    memory_mb: u64,
+    retain_mb: u64,
+    ready: Option<bool>,
+    allow_overrides: bool,
+    burst_error_rate: f64,
+    burst_duration: u64,
+    burst_interval: u64,
+    degrade_ms_per_minute: f64,
+    concurrency_k: f64,
+    concurrency_threshold: usize,
+    truncate_body_at_bytes: Option<u64>,
+    drop_after_headers: bool,
+    // Where the burst schedule starts from, only new burst settings or /reset restart it.
+    #[serde(skip, default = "Instant::now")]
+    burst_started_at: Instant,
+    // When the creep started, only a new degrade_ms_per_minute or /reset restarts it.
+    #[serde(skip, default = "Instant::now")]
+    degrade_started_at: Instant,
+}
+
+impl Default for GlobalState {
+    fn default() -> Self {
+        GlobalState {
+            min_duration: DEFAULT_MIN_DURATION,
+            max_duration: DEFAULT_MAX_DURATION,
+            error_rate: DEFAULT_ERROR_RATE,
+            latency_distribution: LatencyDistribution::default(),
+            header_delay: DEFAULT_HEADER_DELAY,
+            body_chunks: DEFAULT_BODY_CHUNKS,
+            body_chunk_delay: DEFAULT_BODY_CHUNK_DELAY,
+            work_mode: WorkMode::default(),
+            memory_mb: DEFAULT_MEMORY_MB,
+            retain_mb: DEFAULT_RETAIN_MB,
+            ready: None,
+            allow_overrides: false,
+            burst_error_rate: DEFAULT_BURST_ERROR_RATE,
+            burst_duration: DEFAULT_BURST_DURATION,
+            burst_interval: DEFAULT_BURST_INTERVAL,
+            degrade_ms_per_minute: DEFAULT_DEGRADE_MS_PER_MINUTE,
+            concurrency_k: DEFAULT_CONCURRENCY_K,
+            concurrency_threshold: DEFAULT_CONCURRENCY_THRESHOLD,
+            truncate_body_at_bytes: None,
+            drop_after_headers: false,
+            burst_started_at: Instant::now(),
+            degrade_started_at: Instant::now(),
+        }
+    }
+}
+
+impl GlobalState {
+    pub fn from_config(config: &Config) -> Self {
+        GlobalState {
+            min_duration: config.min_duration,
+            max_duration: config.max_duration,
+            error_rate: config.error_rate,
+            ..GlobalState::default()
+        }
+    }
+
+    fn error_phase(&self, now: Instant) -> ErrorPhase {
+        burst::error_phase(
+            now.saturating_duration_since(self.burst_started_at),
+            self.burst_duration,
+            self.burst_interval,
+        )
+    }
+
+    // The durations degraded by what crept in until `now`.
+    fn effective_durations(&self, now: Instant) -> (u64, u64) {
+        let elapsed = now.saturating_duration_since(self.degrade_started_at);
+        (
+            degradation::degraded_duration(self.min_duration, self.degrade_ms_per_minute, elapsed),
+            degradation::degraded_duration(self.max_duration, self.degrade_ms_per_minute, elapsed),
+        )
+    }
+
+    // `self` about to replace `previous` at `now`. The creep and the burst schedule carry on
+    // unless their own settings changed.
+    fn continuing(self, previous: &GlobalState, now: Instant) -> Self {
+        let same_bursts = self.burst_error_rate == previous.burst_error_rate
+            && self.burst_duration == previous.burst_duration
+            && self.burst_interval == previous.burst_interval;
+        let burst_started_at = if same_bursts {
+            previous.burst_started_at
+        } else {
+            now
+        };
+        let degrade_started_at = if self.degrade_ms_per_minute == previous.degrade_ms_per_minute {
+            previous.degrade_started_at
+        } else {
+            now
+        };
+        GlobalState {
+            burst_started_at,
+            degrade_started_at,
+            ..self
+        }
+    }
+
+    fn effective_error_rate(&self, now: Instant) -> f64 {
+        match self.error_phase(now) {
+            ErrorPhase::Base => self.error_rate,
+            ErrorPhase::Burst => self.burst_error_rate,
+        }
+    }
+}
+
+struct InFlightGuard(Arc<AtomicUsize>);
+
+impl InFlightGuard {
+    fn acquire(in_flight: &Arc<AtomicUsize>) -> Self {
+        in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(in_flight.clone())
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Clone)]
+struct Streamed;
+
+#[derive(Clone)]
+struct Replayed;
+
+impl Worker {
+    pub fn new(config: Config) -> Self {
+        let state = GlobalState::from_config(&config);
+        Worker::with_state(config, Arc::new(RwLock::new(state)))
+    }
+
+    pub fn with_state(config: Config, state: Arc<RwLock<GlobalState>>) -> Self {
+        let seed = config
+            .random_seed
+            .unwrap_or_else(|| rand::thread_rng().gen());
+        Worker {
+            memory: Arc::new(MemorySimulator::new(config.memory_cap_mb * BYTES_PER_MB)),
+            idempotency: IdempotencyCache::new(
+                config.idempotency_capacity,
+                Duration::from_millis(config.idempotency_ttl_ms),
+            ),
+            config,
+            state,
+            in_flight: Arc::default(),
+            hang: watch::channel(None).0,
+            started_at: Instant::now(),
+            timeseries: Mutex::new(MinuteSeries::new(Instant::now())),
+            schedule: Mutex::new(None),
+            next_schedule_id: AtomicU64::new(0),
+            seed,
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn state(&self) -> &Arc<RwLock<GlobalState>> {
+        &self.state
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // How long the next piece of work takes and whether it fails, drawn from the seeded RNG.
+    fn draw(&self, state: &GlobalState, now: Instant) -> (u64, StatusCode) {
+        let (min_duration, max_duration) = state.effective_durations(now);
+        // gen_bool panics outside [0, 1], setup rejects such rates but a panic here would
+        // take the RNG down with it.
+        let error_rate = state.effective_error_rate(now);
+        let error_rate = if error_rate.is_nan() {
+            0.0
+        } else {
+            error_rate.clamp(0.0, 1.0)
+        };
+        let mut rng = self.rng();
+        let duration = state
+            .latency_distribution
+            .sample(&mut *rng, min_duration, max_duration);
+        let status_code = if rng.gen_bool(error_rate) {
+            StatusCode::INTERNAL_SERVER_ERROR
+        } else {
+            StatusCode::OK
+        };
+        (duration, status_code)
+    }
+
+    // A draw that panicked left the sequence as it was, so a poisoned lock is still usable.
+    fn rng(&self) -> MutexGuard<'_, StdRng> {
+        self.rng.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn startup_delay(&self) -> Duration {
+        Duration::from_millis(self.config.startup_delay_ms)
+    }
+}
+
+// Serves on the configured bind address, or the environment's, until `shutdown` completes.
+// The state saved in the state file is restored first.
+pub async fn serve(config: Config, shutdown: impl Future<Output = ()>) -> Result<()> {
+    let worker = Worker::new(config);
+    info!("Random seed {}, set RANDOM_SEED to replay it", worker.seed);
+    if !worker.startup_delay().is_zero() {
+        info!("Warming up for {}ms", worker.startup_delay().as_millis());
+    }
+
+    if let Some(path) = &worker.config.state_file {
+        let memory_cap_mb = (worker.memory.cap() / BYTES_PER_MB) as u64;
+        let loaded = persistence::load::<GlobalState>(path)
+            .await
+            .and_then(|state| {
+                state
+                    .map(|state| validated(&state, memory_cap_mb))
+                    .transpose()
+                    .map_err(|msg| format!("Invalid state in {}: {}", path.display(), msg))
+            });
+        match loaded {
+            Ok(Some(state)) => {
+                info!(
+                    "Restored state from {}: {}",
+                    path.display(),
+                    serde_json::to_string(&state)?
+                );
+                *worker.state.write().await = state;
+            }
+            Ok(None) => info!("No saved state found at {}", path.display()),
+            Err(msg) => warn!("{}, starting with the initial state", msg),
+        }
+    }
+
+    let port = worker.config.port;
+    let addr = match worker.config.bind {
+        Some(ip) => SocketAddr::new(ip, port),
+        None => Environment::from_env()?.bind_address(port),
+    };
+    let listener = TcpListener::bind(addr).await?;
+    serve_listener(listener, Arc::new(worker), shutdown).await
+}
+
+// Like `serve`, on a listener the caller bound. Connections already accepted are served
+// to the end after `shutdown`.
+pub async fn serve_listener(
+    listener: TcpListener,
+    worker: Arc<Worker>,
+    shutdown: impl Future<Output = ()>,
+) -> Result<()> {
+    info!(
+        "Worker '{}' listening on http://{} ({})",
+        worker.config.name(),
+        listener.local_addr()?,
+        if worker.config.http2 {
+            "h2c"
+        } else {
+            "HTTP/1.1"
+        }
+    );
+
+    let connection_limit = worker
+        .config
+        .max_connections
+        .map(|max| Arc::new(Semaphore::new(max as usize)));
+    let idle_timeout = worker.config.idle_timeout_ms.map(Duration::from_millis);
+    tokio::pin!(shutdown);
+
+    loop {
+        let accepted = async {
+            let permit = match &connection_limit {
+                Some(limit) => Some(limit.clone().acquire_owned().await?),
+                None => None,
+            };
+            let (stream, peer_addr) = listener.accept().await?;
+            Ok::<_, GenericError>((permit, stream, peer_addr))
+        };
+        let (permit, stream, peer_addr) = tokio::select! {
+            accepted = accepted => accepted?,
+            _ = &mut shutdown => {
+                info!("Shutting down");
+                return Ok(());
+            }
+        };
+        let io = TokioIo::new(stream);
+        let worker = worker.clone();
+
+        tokio::task::spawn(async move {
+            let _permit = permit;
+            let tracker = Arc::new(IdleTracker::new());
+            let service = {
+                let tracker = tracker.clone();
+                let worker = worker.clone();
+                service_fn(move |req| {
+                    tracked_router(req, peer_addr, tracker.clone(), worker.clone())
+                })
+            };
+
+            let served = if worker.config.http2 {
+                let conn = http2::Builder::new(TokioExecutor::new()).serve_connection(io, service);
+                connection::serve_with_idle_timeout(
+                    conn,
+                    |conn| conn.graceful_shutdown(),
+                    &tracker,
+                    idle_timeout,
+                )
+                .await
+            } else {
+                let conn = http1::Builder::new()
+                    .timer(TokioTimer::new())
+                    .keep_alive(worker.config.keep_alive)
+                    .header_read_timeout(Duration::from_millis(
+                        worker.config.header_read_timeout_ms,
+                    ))
+                    .serve_connection(io, service);
+                connection::serve_with_idle_timeout(
+                    conn,
+                    |conn| conn.graceful_shutdown(),
+                    &tracker,
+                    idle_timeout,
+                )
+                .await
+            };
+            match served {
+                Ok(true) => debug!("Closed idle connection from {}", peer_addr),
+                Ok(false) => {}
+                Err(err) => error!("Failed to serve connection: {:?}", err),
+            }
+        });
+    }
+}
+
+async fn tracked_router(
+    req: Request<IncomingBody>,
+    peer_addr: SocketAddr,
+    tracker: Arc<IdleTracker>,
+    worker: Arc<Worker>,
+) -> Result<Response<BoxBody>> {
+    tracker.request_started();
+    let req = req.map(BodyExt::boxed);
+    let res = router(req, peer_addr, worker).await;
+    tracker.request_finished();
+    res
+}
+
+// Answers a request to `worker`, whose body may as well be an in memory one.
+#[instrument(
+    name = "request",
+    skip_all,
+    fields(
+        method = %req.method(),
+        path = req.uri().path(),
+        request_id = req.headers().get(REQUEST_ID_HEADER).and_then(|v| v.to_str().ok()),
+        worker = %worker.config.name(),
+    )
+)]
+pub async fn router(
+    req: Request<RequestBody>,
+    peer_addr: SocketAddr,
+    worker: Arc<Worker>,
+) -> Result<Response<BoxBody>> {
+    info!("Received request: {} {}", req.method(), req.uri().path());
+
+    worker.wait_while_hung(req.uri().path()).await;
+
+    if req.method() == Method::POST {
+        if let Some(length) = content_length(&req) {
+            if length > worker.config.max_body_bytes as u64 {
+                return payload_too_large(worker.config.max_body_bytes);
+            }
+        }
+    }
+
+    let format = match ResponseFormat::from_headers(req.headers()) {
+        Some(format) => format,
+        None if NEGOTIATED_PATHS.contains(&req.uri().path()) => return not_acceptable(),
+        None => ResponseFormat::Text,
+    };
+
+    let started_at = Instant::now();
+    let (endpoint, res) = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/health") => ("/health", health_check(format, &worker).await),
+        (&Method::GET, "/ready") => ("/ready", readiness_check(&worker).await),
+        (&Method::GET, "/setup") => ("/setup", setup_info(&worker).await),
+        (&Method::POST, "/setup") => ("/setup", setup(req, format, &worker).await),
+        (&Method::POST | &Method::GET | &Method::HEAD, "/work") => {
+            ("/work", idempotent_work(req, format, &worker).await)
+        }
+        (&Method::GET, "/stats") => ("/stats", stats(&worker).await),
+        (&Method::POST, "/reset") => ("/reset", reset(&worker).await),
+        (&Method::POST, "/crash") => ("/crash", crash(req, &worker).await),
+        (&Method::POST, "/hang") => ("/hang", hang(req, &worker).await),
+        (&Method::POST, "/echo") => ("/echo", echo(req, peer_addr, &worker).await),
+        _ => {
+            let res = Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(full("Not Found"))
+                .unwrap();
+            ("other", Ok(res))
+        }
+    };
+
+    // Chunked bodies are only checked against the limit while being collected.
+    let res = match res {
+        Err(e) if e.downcast_ref::<LengthLimitError>().is_some() => {
+            payload_too_large(worker.config.max_body_bytes)
+        }
+        res => res,
+    };
+
+    if let Ok(ref r) = res {
+        info!("Response status: {}", r.status());
+    }
+
+    // Streamed responses record themselves once the body is complete,
+    // replays are not new work.
+    let skip_record = res.as_ref().is_ok_and(|r| {
+        r.extensions().get::<Streamed>().is_some() || r.extensions().get::<Replayed>().is_some()
+    });
+    if !skip_record {
+        let error = res.as_ref().map_or(true, |r| r.status().is_server_error());
+        worker.timeseries.lock().unwrap().record(
+            Instant::now(),
+            endpoint,
+            started_at.elapsed().as_millis() as u64,
+            error,
+        );
+    }
+
+    res
+}
+
+#[instrument(skip_all)]
+async fn health_check(format: ResponseFormat, worker: &Worker) -> Result<Response<BoxBody>> {
+    respond(
+        StatusCode::OK,
+        format,
+        "OK".to_string(),
+        serde_json::json!({ "status": "ok", "worker": worker.config.name() }),
+    )
+}
+
+#[instrument(skip_all)]
+async fn readiness_check(worker: &Worker) -> Result<Response<BoxBody>> {
+    let forced = worker.state.read().await.ready;
+
+    let not_ready_reason = match forced {
+        Some(true) => None,
+        Some(false) => Some("Marked not ready"),
+        None if worker.started_at.elapsed() < worker.startup_delay() => Some("Warming up"),
+        None if worker.hang.borrow().is_some() => Some("Hung"),
+        None => None,
+    };
+
+    let response = match not_ready_reason {
+        Some(reason) => Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(full(reason))?,
+        None => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(full("Ready"))?,
+    };
+    Ok(response)
+}
+
+#[instrument(skip_all)]
+async fn setup_info(worker: &Worker) -> Result<Response<BoxBody>> {
+    let schedule = worker
+        .schedule
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map_or(serde_json::Value::Null, Schedule::info);
+    let info = {
+        let state = worker.state.read().await;
+        let mut info = serde_json::to_value(&*state)?;
+        let now = Instant::now();
+        let (effective_min_duration, effective_max_duration) = state.effective_durations(now);
+        info["error_phase"] = serde_json::json!(state.error_phase(now).to_string());
+        info["effective_min_duration"] = serde_json::json!(effective_min_duration);
+        info["effective_max_duration"] = serde_json::json!(effective_max_duration);
+        info["schedule"] = schedule;
+        info
+    };
+
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(full(info.to_string()))?;
+    Ok(response)
+}
+
+#[instrument(skip_all)]
+async fn setup(
+    req: Request<RequestBody>,
+    format: ResponseFormat,
+    worker: &Arc<Worker>,
+) -> Result<Response<BoxBody>> {
+    let whole_body = read_body(req, worker.config.max_body_bytes).await?;
+    let data: serde_json::Value = serde_json::from_slice(&whole_body)?;
+    let request = match serde_json::from_value::<SetupRequest>(data) {
+        Ok(request) => request,
+        Err(e) => return bad_request(format!("Invalid setup: {}", e)),
+    };
+
+    let current = worker.state.read().await.clone();
+    let memory_cap_mb = (worker.memory.cap() / BYTES_PER_MB) as u64;
+    let next = match merged_state(&request, &current, memory_cap_mb) {
+        Ok(next) => next,
+        Err(msg) => return bad_request(msg),
+    };
+    let apply_after = request.apply_after_ms.unwrap_or(0);
+    let revert_after = request.revert_after_ms;
+
+    let config = serde_json::to_value(&next)?;
+    let state = serde_json::to_string(&next)?;
+    let mut msg = match apply_after {
+        0 => format!("Setup done with {}", state),
+        _ => format!("Setup scheduled in {}ms with {}", apply_after, state),
+    };
+    let cancelled = worker.cancel_schedule();
+    let applied = if apply_after == 0 {
+        Some(worker.apply_state(next.clone()).await)
+    } else {
+        None
+    };
+    if applied.is_none() || revert_after.is_some() {
+        worker.schedule_setup(next, applied, apply_after, revert_after);
+    }
+
+    if let Some(revert_after) = revert_after {
+        msg.push_str(&format!(", reverting after {}ms", revert_after));
+    }
+    if let Some(phase) = cancelled {
+        msg.push_str(&format!(", cancelled {} schedule", phase));
+    }
+
+    info!("{}", msg);
+
+    let json = serde_json::to_value(SetupResponse {
+        status: if apply_after == 0 {
+            "done"
+        } else {
+            "scheduled"
+        }
+        .to_string(),
+        apply_after_ms: apply_after,
+        revert_after_ms: revert_after,
+        cancelled_schedule: cancelled.map(|phase| phase.to_string()),
+        config,
+    })?;
+    respond(StatusCode::OK, format, msg, json)
+}
+
+#[instrument(skip_all)]
+async fn idempotent_work(
+    req: Request<RequestBody>,
+    format: ResponseFormat,
+    worker: &Arc<Worker>,
+) -> Result<Response<BoxBody>> {
+    let key = req
+        .headers()
+        .get(idempotency::IDEMPOTENCY_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    let Some(key) = key else {
+        return work(req, format, worker).await;
+    };
+
+    let ticket = match worker.idempotency.claim(key.clone()).await {
+        Claim::Replay(cached) => {
+            info!("Replaying cached response for Idempotency-Key '{}'", key);
+            let mut response = Response::builder()
+                .status(cached.status)
+                .body(full(cached.body))?;
+            *response.headers_mut() = cached.headers;
+            response.headers_mut().insert(
+                idempotency::REPLAY_HEADER,
+                header::HeaderValue::from_static("true"),
+            );
+            response.extensions_mut().insert(Replayed);
+            return Ok(response);
+        }
+        Claim::Owner(ticket) => ticket,
+    };
+
+    // The body has to be buffered to be cached, so slow and streamed bodies arrive at once.
+    let (mut parts, body) = work(req, format, worker).await?.into_parts();
+    let body = body.collect().await?.to_bytes();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    ticket.complete(CachedResponse {
+        status: parts.status,
+        headers: parts.headers.clone(),
+        body: body.clone(),
+    });
+
+    Ok(Response::from_parts(parts, full(body)))
+}
+
+#[instrument(skip_all, fields(duration_ms, status))]
+async fn work(
+    req: Request<RequestBody>,
+    format: ResponseFormat,
+    worker: &Arc<Worker>,
+) -> Result<Response<BoxBody>> {
+    let in_flight = InFlightGuard::acquire(&worker.in_flight);
+
+    let requested_overrides = Overrides::from_headers(req.headers());
+
+    let params = if req.method() == Method::POST {
+        let whole_body = read_body(req, worker.config.max_body_bytes).await?;
+        let data: serde_json::Value = serde_json::from_slice(&whole_body)?;
+        WorkParams::from_json(data)
+    } else {
+        WorkParams::from_query(req.uri().query())
+    };
+    let WorkParams {
+        multiplier,
+        stream,
+        tick_ms,
+    } = match params {
+        Ok(params) => params,
+        Err(msg) => return bad_request(msg),
+    };
+
+    let (random_duration, random_status_code) =
+        worker.draw(&*worker.state.read().await, Instant::now());
+
+    let overrides = if worker.state.read().await.allow_overrides {
+        requested_overrides
+    } else {
+        if !requested_overrides.is_empty() {
+            warn!("Ignoring simulation overrides, allow_overrides is disabled");
+        }
+        Overrides::default()
+    };
+    // Durations set up near u64::MAX would overflow, they are long enough as they are.
+    let base_duration = multiplier.saturating_mul(random_duration);
+    let effective_duration = {
+        let state = worker.state.read().await;
+        degradation::concurrency_adjusted_duration(
+            base_duration,
+            state.concurrency_k,
+            worker.in_flight.load(Ordering::SeqCst),
+            state.concurrency_threshold,
+        )
+    };
+    let duration = overrides.apply_duration(effective_duration);
+    let status_code = overrides.apply_status(random_status_code);
+
+    let span = tracing::Span::current();
+    span.record("duration_ms", duration);
+    span.record("status", status_code.as_u16());
+    info!(
+        base_duration_ms = base_duration,
+        duration_ms = duration,
+        status = status_code.as_u16(),
+        outcome = if status_code.is_server_error() {
+            "error"
+        } else {
+            "success"
+        },
+        overridden = !overrides.is_empty(),
+        "Work scheduled"
+    );
+
+    let (work_mode, header_delay, body_chunks, body_chunk_delay, memory_mb, retain_mb) = {
+        let state = worker.state.read().await;
+        (
+            state.work_mode,
+            state.header_delay,
+            state.body_chunks,
+            state.body_chunk_delay,
+            state.memory_mb,
+            state.retain_mb,
+        )
+    };
+    let body_fault = {
+        let state = worker.state.read().await;
+        if state.allow_overrides {
+            BodyFault::new(state.truncate_body_at_bytes, state.drop_after_headers)
+        } else {
+            None
+        }
+    };
+
+    let allocation = match worker.memory.allocate(memory_mb as usize * BYTES_PER_MB) {
+        Ok(allocation) => allocation,
+        Err(msg) => {
+            warn!("{}", msg);
+            let response = Response::builder()
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .header(header::CONTENT_TYPE, "text/plain")
+                .body(full(msg))?;
+            return Ok(response);
+        }
+    };
+    if let Err(msg) = worker.memory.retain(retain_mb as usize * BYTES_PER_MB) {
+        warn!("{}", msg);
+    }
+
+    if stream {
+        sleep(Duration::from_millis(header_delay)).await;
+
+        let (ticks, tick) = stream_ticks(duration, tick_ms);
+        let (tx, body) = ChannelBody::new(1);
+        let worker = worker.clone();
+
+        tokio::task::spawn(
+            async move {
+                let _in_flight = in_flight;
+                let _allocation = allocation;
+                let started_at = Instant::now();
+
+                for i in 1..=ticks {
+                    if let Err(e) = work_mode.simulate(tick).await {
+                        let _ = tx.send(Err(e)).await;
+                        break;
+                    }
+                    let line = match format {
+                        ResponseFormat::Text => format!("tick {}/{}\n", i, ticks),
+                        ResponseFormat::Json => {
+                            format!("{}\n", serde_json::json!({ "tick": i, "ticks": ticks }))
+                        }
+                    };
+                    let line = Bytes::from(line);
+                    if tx.send(Ok(line)).await.is_err() {
+                        warn!("Client disconnected during streamed work");
+                        break;
+                    }
+                }
+                let done = match format {
+                    ResponseFormat::Text => "done\n".to_string(),
+                    ResponseFormat::Json => format!("{}\n", serde_json::json!({ "done": true })),
+                };
+                let _ = tx.send(Ok(Bytes::from(done))).await;
+
+                worker.timeseries.lock().unwrap().record(
+                    Instant::now(),
+                    "/work",
+                    started_at.elapsed().as_millis() as u64,
+                    status_code.is_server_error(),
+                );
+            }
+            .instrument(span),
+        );
+
+        let content_type = match format {
+            ResponseFormat::Text => negotiate::TEXT_PLAIN,
+            ResponseFormat::Json => NDJSON,
+        };
+        let mut response = Response::builder()
+            .status(status_code)
+            .header(header::CONTENT_TYPE, content_type)
+            .body(match body_fault {
+                Some(fault) => fault.inject(body.boxed()),
+                None => body.boxed(),
+            })?;
+        response.extensions_mut().insert(Streamed);
+        return Ok(response);
+    }
+
+    let started_at = Instant::now();
+    work_mode.simulate(Duration::from_millis(duration)).await?;
+    let elapsed = started_at.elapsed();
+
+    sleep(Duration::from_millis(header_delay)).await;
+
+    let mut details = vec![format!("{} mode", work_mode)];
+    if effective_duration != base_duration {
+        details.push(format!("base: {}ms", base_duration));
+        details.push(format!("effective: {}ms", effective_duration));
+    }
+    if !overrides.is_empty() {
+        details.push(format!("overrides: {}", overrides));
+    }
+    let msg = format!(
+        "Work done in {}ms ({})",
+        elapsed.as_millis(),
+        details.join(", ")
+    );
+    let json = serde_json::to_value(WorkResponse {
+        worker: worker.config.name(),
+        duration_ms: elapsed.as_millis() as u64,
+        work_mode: work_mode.to_string(),
+        base_duration_ms: base_duration,
+        effective_duration_ms: effective_duration,
+        overrides: (!overrides.is_empty()).then(|| overrides.to_string()),
+    })?;
+    let msg = render(format, msg, json);
+    let body = if body_chunks > 1 {
+        slow_body(
+            format,
+            msg,
+            body_chunks,
+            Duration::from_millis(body_chunk_delay),
+        )
+    } else {
+        full(msg)
+    };
+
+    let mut response = Response::builder()
+        .status(status_code)
+        .header(header::CONTENT_TYPE, format.content_type());
+    let body = match body_fault {
+        Some(fault) => {
+            // Announce the full length so clients can tell the body was cut short.
+            if let Some(length) = hyper::body::Body::size_hint(&body).exact() {
+                response = response.header(header::CONTENT_LENGTH, length);
+            }
+            fault.inject(body)
+        }
+        None => body,
+    };
+    Ok(response.body(body)?)
+}
+
+#[instrument(skip_all)]
+async fn stats(worker: &Worker) -> Result<Response<BoxBody>> {
+    let (error_phase, (effective_min_duration, effective_max_duration)) = {
+        let state = worker.state.read().await;
+        let now = Instant::now();
+        (state.error_phase(now), state.effective_durations(now))
+    };
+    let stats = WorkerStats {
+        in_flight: worker.in_flight.load(Ordering::SeqCst) as u64,
+        error_phase: error_phase.to_string(),
+        effective_min_duration,
+        effective_max_duration,
+        memory: MemoryStats {
+            allocated_bytes: worker.memory.allocated_bytes() as u64,
+            retained_bytes: worker.memory.retained_bytes() as u64,
+            cap_bytes: worker.memory.cap() as u64,
+        },
+        timeseries: worker.timeseries.lock().unwrap().snapshot(Instant::now()),
+    };
+
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(full(serde_json::to_string(&stats)?))?;
+    Ok(response)
+}
+
+#[instrument(skip_all)]
+async fn reset(worker: &Worker) -> Result<Response<BoxBody>> {
+    worker.cancel_schedule();
+    {
+        let mut state = worker.state.write().await;
+        *state = GlobalState::from_config(&worker.config);
+    }
+    worker.persist_state().await;
+    worker.memory.reset();
+    worker.hang.send_replace(None);
+    // Replays the sequence from the start, like after a restart with the same seed.
+    *worker.rng() = StdRng::seed_from_u64(worker.seed);
+
+    let msg = "Reset done";
+    info!(msg);
+
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(full(msg))?;
+    Ok(response)
+}
+
+#[instrument(skip_all)]
+async fn crash(req: Request<RequestBody>, worker: &Worker) -> Result<Response<BoxBody>> {
+    let whole_body = read_body(req, worker.config.max_body_bytes).await?;
+    let data: serde_json::Value = serde_json::from_slice(&whole_body)?;
+
+    if !is_confirmed(&data) {
+        return bad_request(CONFIRMATION_REQUIRED.to_string());
+    }
+
+    let delay = optional_field(&data, "delay_ms").unwrap_or(0);
+
+    error!("Crash requested, exiting in {}ms", delay);
+    tokio::task::spawn(async move {
+        sleep(Duration::from_millis(delay)).await;
+        error!("Crashing now");
+        std::process::exit(1);
+    });
+
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(full(format!("Crashing in {}ms", delay)))?;
+    Ok(response)
+}
+
+#[instrument(skip_all)]
+async fn hang(req: Request<RequestBody>, worker: &Worker) -> Result<Response<BoxBody>> {
+    let whole_body = read_body(req, worker.config.max_body_bytes).await?;
+    let data: serde_json::Value = serde_json::from_slice(&whole_body)?;
+
+    if !is_confirmed(&data) {
+        return bad_request(CONFIRMATION_REQUIRED.to_string());
+    }
+
+    let scope = match data.get("scope").and_then(|v| v.as_str()) {
+        Some(str) => match HangScope::try_from(str) {
+            Ok(scope) => scope,
+            Err(e) => return bad_request(e.to_string()),
+        },
+        None => HangScope::All,
+    };
+
+    error!(
+        "Hang requested, '{}' requests will block until /reset is called",
+        scope
+    );
+    worker.hang.send_replace(Some(scope));
+
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(full(format!("Hanging '{}' requests", scope)))?;
+    Ok(response)
+}
+
+#[instrument(skip_all, fields(peer_addr = %peer_addr))]
+async fn echo(
+    req: Request<RequestBody>,
+    peer_addr: SocketAddr,
+    worker: &Worker,
+) -> Result<Response<BoxBody>> {
+    let method = req.method().to_string();
+    let path = req.uri().path().to_string();
+    let query = req.uri().query().map(|q| q.to_string());
+
+    let mut headers = serde_json::Map::new();
+    for (name, value) in req.headers() {
+        let value = String::from_utf8_lossy(value.as_bytes()).to_string();
+        match headers.get_mut(name.as_str()) {
+            Some(serde_json::Value::String(existing)) => {
+                existing.push_str(", ");
+                existing.push_str(&value);
+            }
+            _ => {
+                headers.insert(name.to_string(), serde_json::Value::String(value));
+            }
+        }
+    }
+
+    let whole_body = read_body(req, worker.config.max_body_bytes).await?;
+    let body_bytes = whole_body.len();
+    let truncated = body_bytes > worker.config.echo_max_bytes;
+    let echoed_body = whole_body.slice(..body_bytes.min(worker.config.echo_max_bytes));
+    let (body, body_encoding) = match std::str::from_utf8(&echoed_body) {
+        Ok(text) => (text.to_string(), "utf-8"),
+        Err(_) => (BASE64_STANDARD.encode(&echoed_body), "base64"),
+    };
+
+    let echo = serde_json::json!({
+        "method": method,
+        "path": path,
+        "query": query,
+        "headers": headers,
+        "body": body,
+        "body_encoding": body_encoding,
+        "body_bytes": body_bytes,
+        "truncated": truncated,
+        "peer_addr": peer_addr.to_string(),
+    });
+
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(full(echo.to_string()))?;
+    Ok(response)
+}
+
+async fn read_body(req: Request<RequestBody>, limit: usize) -> Result<Bytes> {
+    let body = Limited::new(req.into_body(), limit);
+    Ok(body.collect().await?.to_bytes())
+}
+
+fn content_length<T>(req: &Request<T>) -> Option<u64> {
+    req.headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+}
+
+impl Worker {
+    async fn wait_while_hung(&self, path: &str) {
+        let mut hang = self.hang.subscribe();
+        if hang.borrow().is_some_and(|scope| scope.applies_to(path)) {
+            warn!("Worker is hung, blocking request to {}", path);
+            let _ = hang
+                .wait_for(|hang| !hang.is_some_and(|scope| scope.applies_to(path)))
+                .await;
+            info!("Worker released, resuming request to {}", path);
+        }
+    }
+
+    // Replaces the active state and returns the one it replaced.
+    async fn apply_state(&self, state: GlobalState) -> GlobalState {
+        let previous = {
+            let mut current = self.state.write().await;
+            let next = state.continuing(&current, Instant::now());
+            std::mem::replace(&mut *current, next)
+        };
+        self.persist_state().await;
+        previous
+    }
+
+    // Applies `next` after `apply_after` ms unless it was `applied` already, then restores
+    // the replaced state after `revert_after` ms.
+    fn schedule_setup(
+        self: &Arc<Self>,
+        next: GlobalState,
+        applied: Option<GlobalState>,
+        apply_after: u64,
+        revert_after: Option<u64>,
+    ) {
+        let id = self.next_schedule_id.fetch_add(1, Ordering::SeqCst);
+        let (apply_at, revert_at) = schedule::deadlines(
+            Duration::from_millis(apply_after),
+            revert_after.map(Duration::from_millis),
+        );
+
+        let mut schedule = self.schedule.lock().unwrap();
+        let worker = self.clone();
+        let task = tokio::task::spawn(async move {
+            let previous = match applied {
+                Some(previous) => previous,
+                None => {
+                    sleep_until(apply_at).await;
+                    info!("Applying scheduled setup");
+                    worker.apply_state(next).await
+                }
+            };
+
+            if let Some(revert_at) = revert_at {
+                sleep_until(revert_at).await;
+                info!("Reverting scheduled setup");
+                worker.apply_state(previous).await;
+            }
+
+            let mut schedule = worker.schedule.lock().unwrap();
+            if schedule.as_ref().is_some_and(|s| s.id == id) {
+                *schedule = None;
+            }
+        });
+        *schedule = Some(Schedule {
+            id,
+            apply_at,
+            revert_at,
+            handle: task.abort_handle(),
+        });
+    }
+
+    fn cancel_schedule(&self) -> Option<SchedulePhase> {
+        let cancelled = self
+            .schedule
+            .lock()
+            .unwrap()
+            .take()
+            .and_then(Schedule::cancel);
+        if let Some(phase) = cancelled {
+            info!("Cancelled {} setup schedule", phase);
+        }
+        cancelled
+    }
+
+    async fn persist_state(&self) {
+        let Some(path) = &self.config.state_file else {
+            return;
+        };
+
+        let state = self.state.read().await.clone();
+        if let Err(e) = persistence::save(path, &state).await {
+            error!("Failed to save state to {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn is_confirmed(data: &serde_json::Value) -> bool {
+    data.get("confirm").and_then(|v| v.as_bool()) == Some(true)
+}
+
+fn slow_body(format: ResponseFormat, msg: String, chunks: u64, chunk_delay: Duration) -> BoxBody {
+    let (tx, body) = ChannelBody::new(1);
+
+    tokio::task::spawn(async move {
+        let msg = Bytes::from(msg);
+        for i in 1..=chunks {
+            let chunk = match format {
+                ResponseFormat::Text if i < chunks => {
+                    Bytes::from(format!("part {}/{}\n", i, chunks))
+                }
+                ResponseFormat::Text => msg.clone(),
+                // Progress lines would break the document, so it is split into the chunks instead.
+                ResponseFormat::Json => {
+                    let chunk_len = msg.len().div_ceil(chunks as usize);
+                    let start = (chunk_len * (i - 1) as usize).min(msg.len());
+                    let end = (chunk_len * i as usize).min(msg.len());
+                    msg.slice(start..end)
+                }
+            };
+            if tx.send(Ok(chunk)).await.is_err() {
+                return;
+            }
+            if i < chunks {
+                sleep(chunk_delay).await;
+            }
+        }
+    });
+
+    body.boxed()
+}
+
+fn render(format: ResponseFormat, text: String, json: serde_json::Value) -> String {
+    match format {
+        ResponseFormat::Text => text,
+        ResponseFormat::Json => json.to_string(),
+    }
+}
+
+fn respond(
+    status: StatusCode,
+    format: ResponseFormat,
+    text: String,
+    json: serde_json::Value,
+) -> Result<Response<BoxBody>> {
+    let response = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, format.content_type())
+        .body(full(render(format, text, json)))?;
+    Ok(response)
+}
+
+fn not_acceptable() -> Result<Response<BoxBody>> {
+    let msg = format!(
+        "Supported response types are '{}' and '{}'",
+        negotiate::TEXT_PLAIN,
+        negotiate::APPLICATION_JSON
+    );
+    warn!("{}", msg);
+    let response = Response::builder()
+        .status(StatusCode::NOT_ACCEPTABLE)
+        .header(header::CONTENT_TYPE, negotiate::TEXT_PLAIN)
+        .body(full(msg))?;
+    Ok(response)
+}
+
+fn payload_too_large(limit: usize) -> Result<Response<BoxBody>> {
+    let msg = format!("Request body exceeds the limit of {} bytes", limit);
+    warn!("{}", msg);
+    let response = Response::builder()
+        .status(StatusCode::PAYLOAD_TOO_LARGE)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(full(msg))?;
+    Ok(response)
+}
+
+// How many ticks streamed work takes and how long each is, at least one. Past u32::MAX ticks,
+// which Duration can divide by, they get longer than `tick_ms` rather than wrapping around.
+fn stream_ticks(duration: u64, tick_ms: u64) -> (u32, Duration) {
+    let ticks = u32::try_from((duration / tick_ms).max(1)).unwrap_or(u32::MAX);
+    (ticks, Duration::from_millis(duration) / ticks)
+}
+
+fn bad_request(msg: String) -> Result<Response<BoxBody>> {
+    warn!("{}", msg);
+    let response = Response::builder()
+        .status(StatusCode::BAD_REQUEST)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(full(msg))?;
+    Ok(response)
+}
+
+// Applies the keys present in `request` on top of `current`, absent keys keep their current value.
+fn merged_state(
+    request: &SetupRequest,
+    current: &GlobalState,
+    memory_cap_mb: u64,
+) -> std::result::Result<GlobalState, String> {
+    let min_duration = request.min_duration.unwrap_or(current.min_duration);
+    let max_duration = request.max_duration.unwrap_or(current.max_duration);
+    if min_duration > max_duration {
+        return Err(format!(
+            "min_duration ({}) must not be greater than max_duration ({})",
+            min_duration, max_duration
+        ));
+    }
+
+    let latency_distribution = match &request.latency_distribution {
+        Some(name) => parse_latency_distribution(name, request)?,
+        None if !current.latency_distribution.is_valid() => {
+            return Err(format!(
+                "Invalid parameters for latency_distribution {}",
+                current.latency_distribution
+            ));
+        }
+        None => current.latency_distribution,
+    };
+
+    let work_mode = match &request.work_mode {
+        Some(str) => WorkMode::try_from(str.as_str()).map_err(|e| e.to_string())?,
+        None => current.work_mode,
+    };
+
+    let memory_mb = request.memory_mb.unwrap_or(current.memory_mb);
+    let retain_mb = request.retain_mb.unwrap_or(current.retain_mb);
+    if memory_mb > memory_cap_mb || retain_mb > memory_cap_mb {
+        return Err(format!(
+            "memory_mb and retain_mb must not exceed the memory cap of {} MB",
+            memory_cap_mb
+        ));
+    }
+
+    // Auto hands readiness back to the warmup and hang checks.
+    let ready = match request.ready {
+        None => current.ready,
+        Some(Ready::Auto) => None,
+        Some(Ready::Forced(ready)) => Some(ready),
+    };
+
+    let allow_overrides = request.allow_overrides.unwrap_or(current.allow_overrides);
+    let truncate_body_at_bytes = request
+        .truncate_body_at_bytes
+        .unwrap_or(current.truncate_body_at_bytes);
+    let drop_after_headers = request
+        .drop_after_headers
+        .unwrap_or(current.drop_after_headers);
+    if BodyFault::new(truncate_body_at_bytes, drop_after_headers).is_some() && !allow_overrides {
+        return Err(
+            "truncate_body_at_bytes and drop_after_headers require allow_overrides".to_string(),
+        );
+    }
+
+    // Clamping lets NaN through, lenient parsing accepts "NaN" and "inf".
+    let error_rate = finite(
+        request.error_rate.unwrap_or(current.error_rate),
+        "error_rate",
+    )?;
+    let burst_error_rate = finite(
+        request.burst_error_rate.unwrap_or(current.burst_error_rate),
+        "burst_error_rate",
+    )?;
+    let degrade_ms_per_minute = finite(
+        request
+            .degrade_ms_per_minute
+            .unwrap_or(current.degrade_ms_per_minute),
+        "degrade_ms_per_minute",
+    )?;
+    let concurrency_k = finite(
+        request.concurrency_k.unwrap_or(current.concurrency_k),
+        "concurrency_k",
+    )?;
+
+    Ok(GlobalState {
+        min_duration,
+        max_duration,
+        error_rate: error_rate.clamp(0.0, 1.0),
+        latency_distribution,
+        header_delay: request.header_delay_ms.unwrap_or(current.header_delay),
+        body_chunks: request
+            .body_chunks
+            .unwrap_or(current.body_chunks)
+            .clamp(1, MAX_BODY_CHUNKS),
+        body_chunk_delay: request
+            .body_chunk_delay_ms
+            .unwrap_or(current.body_chunk_delay),
+        work_mode,
+        memory_mb,
+        retain_mb,
+        ready,
+        allow_overrides,
+        burst_error_rate: burst_error_rate.clamp(0.0, 1.0),
+        burst_duration: request.burst_duration_ms.unwrap_or(current.burst_duration),
+        burst_interval: request.burst_interval_ms.unwrap_or(current.burst_interval),
+        degrade_ms_per_minute: degrade_ms_per_minute.max(0.0),
+        concurrency_k: concurrency_k.max(0.0),
+        concurrency_threshold: request
+            .concurrency_threshold
+            .unwrap_or(current.concurrency_threshold),
+        truncate_body_at_bytes,
+        drop_after_headers,
+        burst_started_at: current.burst_started_at,
+        degrade_started_at: current.degrade_started_at,
+    })
+}
+
+fn finite(value: f64, name: &str) -> std::result::Result<f64, String> {
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(format!("{} must be a finite number, got {}", name, value))
+    }
+}
+
+// A state that didn't come through /setup, e.g. from the state file, checked like a /setup
+// changing nothing. Hand edits could otherwise make every /work panic.
+fn validated(state: &GlobalState, memory_cap_mb: u64) -> std::result::Result<GlobalState, String> {
+    merged_state(&SetupRequest::default(), state, memory_cap_mb)
+}
+
+fn parse_latency_distribution(
+    name: &str,
+    request: &SetupRequest,
+) -> std::result::Result<LatencyDistribution, String> {
+    let distribution = match name {
+        latency::UNIFORM => LatencyDistribution::Uniform,
+        latency::NORMAL => LatencyDistribution::Normal {
+            mean: required(request.mean_ms, "mean_ms")?,
+            stddev: required(request.stddev_ms, "stddev_ms")?,
+        },
+        latency::PARETO => LatencyDistribution::Pareto {
+            scale: required(request.scale_ms, "scale_ms")?,
+            shape: required(request.shape, "shape")?,
+        },
+        latency::LOGNORMAL => LatencyDistribution::LogNormal {
+            median: required(request.median_ms, "median_ms")?,
+            sigma: required(request.sigma, "sigma")?,
+        },
+        latency::BIMODAL => LatencyDistribution::Bimodal {
+            fast: required(request.fast_ms, "fast_ms")?,
+            slow: required(request.slow_ms, "slow_ms")?,
+            slow_probability: required(request.slow_probability, "slow_probability")?,
+        },
+        _ => {
+            return Err(format!(
+            "Invalid latency_distribution '{}'. Valid values are '{}', '{}', '{}', '{}' or '{}'",
+            name,
+            latency::UNIFORM,
+            latency::NORMAL,
+            latency::PARETO,
+            latency::LOGNORMAL,
+            latency::BIMODAL
+        ))
+        }
+    };
+
+    if !distribution.is_valid() {
+        return Err(format!(
+            "Invalid parameters for latency_distribution '{}'",
+            name
+        ));
+    }
+
+    Ok(distribution)
+}
+
+fn required<T>(value: Option<T>, key: &str) -> std::result::Result<T, String> {
+    value.ok_or_else(|| format!("Missing '{}' key", key))
+}
+
+fn optional_field<T: FromStr>(data: &serde_json::Value, key: &str) -> Option<T> {
+    match data.get(key) {
+        Some(serde_json::Value::String(str)) => str.parse::<T>().ok(),
+        Some(value @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => {
+            value.to_string().parse::<T>().ok()
+        }
+        _ => None,
+    }
+}
+
+fn full<T: Into<Bytes>>(chunk: T) -> BoxBody {
+    Full::new(chunk.into())
+        .map_err(|never| match never {})
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const MEMORY_CAP_MB: u64 = 512;
+    const PEER: SocketAddr =
+        SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), 4000);
+
+    fn worker(args: &[&str]) -> Arc<Worker> {
+        let config = config::parse_with_env(&[], args).unwrap();
+        Arc::new(Worker::new(config))
+    }
+
+    fn request(method: Method, path: &str, body: &str) -> Request<RequestBody> {
+        Request::builder()
+            .method(method)
+            .uri(path)
+            .body(
+                Full::new(Bytes::from(body.to_string()))
+                    .map_err(|never| match never {})
+                    .boxed(),
+            )
+            .unwrap()
+    }
+
+    async fn send(worker: &Arc<Worker>, req: Request<RequestBody>) -> (StatusCode, String) {
+        let res = router(req, PEER, worker.clone()).await.unwrap();
+        let status = res.status();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn json_request(method: Method, path: &str, body: &str) -> Request<RequestBody> {
+        let mut req = request(method, path, body);
+        req.headers_mut().insert(
+            header::ACCEPT,
+            header::HeaderValue::from_static(negotiate::APPLICATION_JSON),
+        );
+        req
+    }
+
+    // The status once the head arrived, how long that took and each body frame with how long
+    // after the request it arrived.
+    async fn timed(
+        worker: &Arc<Worker>,
+        req: Request<RequestBody>,
+    ) -> (StatusCode, Duration, Vec<(Duration, Bytes)>) {
+        let sent_at = Instant::now();
+        let res = router(req, PEER, worker.clone()).await.unwrap();
+        let head = sent_at.elapsed();
+        let status = res.status();
+        let mut body = res.into_body();
+        let mut frames = Vec::new();
+        while let Some(frame) = body.frame().await {
+            if let Ok(data) = frame.unwrap().into_data() {
+                frames.push((sent_at.elapsed(), data));
+            }
+        }
+        (status, head, frames)
+    }
+
+    async fn post_setup(worker: &Arc<Worker>, body: &str) {
+        let (status, msg) = send(worker, request(Method::POST, "/setup", body)).await;
+        assert_eq!(status, StatusCode::OK, "{}", msg);
+    }
+
+    async fn setup_info_json(worker: &Arc<Worker>) -> serde_json::Value {
+        let (status, body) = send(worker, request(Method::GET, "/setup", "")).await;
+        assert_eq!(status, StatusCode::OK);
+        serde_json::from_str(&body).unwrap()
+    }
+
+    fn setup(current: &GlobalState, request: serde_json::Value) -> GlobalState {
+        let request = serde_json::from_value::<SetupRequest>(request).unwrap();
+        merged_state(&request, current, MEMORY_CAP_MB).unwrap()
+    }
+
+    #[test]
+    fn loaded_state_is_validated() {
+        let state = |json| serde_json::from_value::<GlobalState>(json).unwrap();
+
+        let valid = validated(
+            &state(json!({ "min_duration": 5, "max_duration": 50 })),
+            MEMORY_CAP_MB,
+        );
+        assert_eq!(valid.map(|s| (s.min_duration, s.max_duration)), Ok((5, 50)));
+
+        let swapped = state(json!({ "min_duration": 50, "max_duration": 5 }));
+        assert!(validated(&swapped, MEMORY_CAP_MB).is_err());
+
+        let bimodal = state(json!({
+            "latency_distribution": { "type": "bimodal", "fast": 1.0, "slow": 100.0, "slow_probability": 2.0 }
+        }));
+        assert!(validated(&bimodal, MEMORY_CAP_MB).is_err());
+
+        let too_much_memory = state(json!({ "memory_mb": MEMORY_CAP_MB + 1 }));
+        assert!(validated(&too_much_memory, MEMORY_CAP_MB).is_err());
+
+        let error_rate = validated(&state(json!({ "error_rate": 3.0 })), MEMORY_CAP_MB);
+        assert_eq!(error_rate.map(|s| s.error_rate), Ok(1.0));
+    }
+
+    #[test]
+    fn unrelated_setup_keeps_the_burst_schedule() {
+        let start = Instant::now();
+        let initial = GlobalState::default();
+        let bursting = setup(
+            &initial,
+            json!({ "burst_error_rate": 1.0, "burst_duration_ms": 100, "burst_interval_ms": 1000 }),
+        )
+        .continuing(&initial, start);
+        assert_eq!(bursting.error_phase(start), ErrorPhase::Base);
+
+        let in_burst = start + Duration::from_millis(950);
+        let state = setup(&bursting, json!({ "error_rate": 0.1 })).continuing(&bursting, in_burst);
+        assert_eq!(state.error_phase(in_burst), ErrorPhase::Burst);
+        assert_eq!(state.effective_error_rate(in_burst), 1.0);
+        assert_eq!(
+            state.effective_error_rate(in_burst + Duration::from_millis(100)),
+            0.1
+        );
+    }
+
+    #[test]
+    fn new_burst_settings_restart_the_schedule() {
+        let start = Instant::now();
+        let initial = GlobalState::default();
+        let bursting = setup(
+            &initial,
+            json!({ "burst_error_rate": 1.0, "burst_duration_ms": 100, "burst_interval_ms": 1000 }),
+        )
+        .continuing(&initial, start);
+
+        let in_burst = start + Duration::from_millis(950);
+        let state =
+            setup(&bursting, json!({ "burst_interval_ms": 2000 })).continuing(&bursting, in_burst);
+        assert_eq!(state.error_phase(in_burst), ErrorPhase::Base);
+        assert_eq!(
+            state.error_phase(in_burst + Duration::from_millis(1950)),
+            ErrorPhase::Burst
+        );
+    }
+
+    #[test]
+    fn degradation_creeps_with_wall_time() {
+        let start = Instant::now();
+        let initial = GlobalState::default();
+        let state =
+            setup(&initial, json!({ "degrade_ms_per_minute": 6000 })).continuing(&initial, start);
+
+        assert_eq!(state.effective_durations(start), (10, 10));
+        assert_eq!(
+            state.effective_durations(start + Duration::from_secs(2)),
+            (210, 210)
+        );
+        assert_eq!(
+            state.effective_durations(start + Duration::from_secs(60)),
+            (6010, 6010)
+        );
+    }
+
+    #[test]
+    fn unrelated_setup_keeps_the_creep_going() {
+        let start = Instant::now();
+        let initial = GlobalState::default();
+        let creeping =
+            setup(&initial, json!({ "degrade_ms_per_minute": 6000 })).continuing(&initial, start);
+
+        let later = start + Duration::from_secs(2);
+        let state = setup(&creeping, json!({ "error_rate": 0.1 })).continuing(&creeping, later);
+        assert_eq!(state.effective_durations(later), (210, 210));
+
+        let same_rate = setup(&state, json!({ "degrade_ms_per_minute": 6000 }));
+        let state = same_rate.continuing(&state, later);
+        assert_eq!(state.effective_durations(later), (210, 210));
+    }
+
+    #[test]
+    fn new_degradation_rate_restarts_the_creep() {
+        let start = Instant::now();
+        let initial = GlobalState::default();
+        let creeping =
+            setup(&initial, json!({ "degrade_ms_per_minute": 6000 })).continuing(&initial, start);
+
+        let later = start + Duration::from_secs(2);
+        let state =
+            setup(&creeping, json!({ "degrade_ms_per_minute": 600 })).continuing(&creeping, later);
+        assert_eq!(state.effective_durations(later), (10, 10));
+        assert_eq!(
+            state.effective_durations(later + Duration::from_secs(6)),
+            (70, 70)
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_are_routed_by_method_and_path() {
+        let worker = worker(&[]);
+        let status = |method, path, body| {
+            let worker = worker.clone();
+            async move { send(&worker, request(method, path, body)).await.0 }
+        };
+
+        assert_eq!(status(Method::GET, "/health", "").await, StatusCode::OK);
+        assert_eq!(status(Method::GET, "/ready", "").await, StatusCode::OK);
+        assert_eq!(status(Method::GET, "/setup", "").await, StatusCode::OK);
+        assert_eq!(status(Method::GET, "/work", "").await, StatusCode::OK);
+        assert_eq!(status(Method::GET, "/stats", "").await, StatusCode::OK);
+        assert_eq!(status(Method::POST, "/reset", "").await, StatusCode::OK);
+        assert_eq!(
+            status(Method::GET, "/nowhere", "").await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(Method::DELETE, "/health", "").await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(Method::GET, "/crash", "").await,
+            StatusCode::NOT_FOUND
+        );
+
+        let (status, body) = send(&worker, request(Method::POST, "/crash", "{}")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, CONFIRMATION_REQUIRED);
+    }
+
+    #[tokio::test]
+    async fn not_ready_while_warming_up_but_alive() {
+        let worker = worker(&["--startup-delay-ms", "100"]);
+
+        let (status, body) = send(&worker, request(Method::GET, "/ready", "")).await;
+        assert_eq!(
+            (status, body.as_str()),
+            (StatusCode::SERVICE_UNAVAILABLE, "Warming up")
+        );
+        let (status, _) = send(&worker, request(Method::GET, "/health", "")).await;
+        assert_eq!(status, StatusCode::OK);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let (status, _) = send(&worker, request(Method::GET, "/ready", "")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn readiness_is_forced_by_setup_until_set_back_to_auto() {
+        let worker = worker(&["--startup-delay-ms", "60000"]);
+        let ready = |body: &'static str| {
+            let worker = worker.clone();
+            async move {
+                let (status, _) = send(&worker, request(Method::POST, "/setup", body)).await;
+                assert_eq!(status, StatusCode::OK, "{}", body);
+                send(&worker, request(Method::GET, "/ready", "")).await
+            }
+        };
+
+        assert_eq!(ready(r#"{"ready": true}"#).await.0, StatusCode::OK);
+        assert_eq!(
+            ready(r#"{"ready": false}"#).await,
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Marked not ready".to_string()
+            )
+        );
+        // Other fields leave it forced.
+        assert_eq!(ready(r#"{"error_rate": 0.1}"#).await.1, "Marked not ready");
+        assert_eq!(
+            ready(r#"{"ready": "auto"}"#).await,
+            (StatusCode::SERVICE_UNAVAILABLE, "Warming up".to_string())
+        );
+        let (status, _) = send(&worker, request(Method::GET, "/health", "")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn setup_changes_only_the_fields_sent() {
+        let worker = worker(&["--min-duration", "5", "--max-duration", "50"]);
+
+        let body = r#"{"error_rate": 0.5}"#;
+        let (status, _) = send(&worker, request(Method::POST, "/setup", body)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let info = setup_info_json(&worker).await;
+        assert_eq!(info["error_rate"], json!(0.5));
+        assert_eq!(info["min_duration"], json!(5));
+        assert_eq!(info["max_duration"], json!(50));
+    }
+
+    #[tokio::test]
+    async fn invalid_setups_are_rejected_and_change_nothing() {
+        let worker = worker(&["--min-duration", "5", "--max-duration", "50"]);
+
+        for body in [
+            r#"{"min_duration": 100}"#,
+            r#"{"min_duration": "fast"}"#,
+            r#"{"latency_distribution": {"type": "bimodal", "fast": 1.0, "slow": 100.0, "slow_probability": 2.0}}"#,
+        ] {
+            let (status, _) = send(&worker, request(Method::POST, "/setup", body)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+        }
+
+        let info = setup_info_json(&worker).await;
+        assert_eq!(info["min_duration"], json!(5));
+        assert_eq!(info["max_duration"], json!(50));
+        assert_eq!(info["latency_distribution"], json!({ "type": "uniform" }));
+    }
+
+    #[tokio::test]
+    async fn non_finite_rates_are_rejected() {
+        let worker = worker(&["--min-duration", "0", "--max-duration", "0"]);
+        post_setup(&worker, r#"{"error_rate": 0.25}"#).await;
+
+        for body in [
+            r#"{"error_rate": "NaN"}"#,
+            r#"{"error_rate": "inf"}"#,
+            r#"{"burst_error_rate": "-inf"}"#,
+            r#"{"degrade_ms_per_minute": "inf"}"#,
+            r#"{"concurrency_k": "NaN"}"#,
+        ] {
+            let (status, msg) = send(&worker, request(Method::POST, "/setup", body)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+            assert!(msg.contains("must be a finite number"), "{}", msg);
+        }
+
+        assert_eq!(setup_info_json(&worker).await["error_rate"], json!(0.25));
+        let (status, _) = send(&worker, request(Method::GET, "/work", "")).await;
+        assert!(status == StatusCode::OK || status == StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn restored_states_with_non_finite_rates_are_refused() {
+        let state = GlobalState {
+            error_rate: f64::NAN,
+            ..GlobalState::default()
+        };
+        let Err(e) = validated(&state, MEMORY_CAP_MB) else {
+            panic!("a NaN error rate was restored");
+        };
+        assert_eq!(e, "error_rate must be a finite number, got NaN");
+    }
+
+    #[tokio::test]
+    async fn work_fails_at_the_error_rate_set_up() {
+        let worker = worker(&["--min-duration", "0", "--max-duration", "0"]);
+
+        let (status, _) = send(&worker, request(Method::GET, "/work", "")).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let body = r#"{"error_rate": 1.0}"#;
+        send(&worker, request(Method::POST, "/setup", body)).await;
+        let (status, _) = send(&worker, request(Method::GET, "/work", "")).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn the_same_seed_draws_the_same_work() {
+        let state = setup(
+            &GlobalState::default(),
+            json!({ "min_duration": 1, "max_duration": 100, "error_rate": 0.5 }),
+        );
+        let draws = |seed: &str| {
+            let worker = worker(&["--random-seed", seed]);
+            let now = Instant::now();
+            (0..50)
+                .map(|_| worker.draw(&state, now))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(draws("7"), draws("7"));
+        assert_ne!(draws("7"), draws("8"));
+    }
+
+    #[test]
+    fn draws_keep_to_the_durations_and_error_rate() {
+        let worker = worker(&["--random-seed", "42"]);
+        let state = setup(
+            &GlobalState::default(),
+            json!({ "min_duration": 5, "max_duration": 20, "error_rate": 0.25 }),
+        );
+        let now = Instant::now();
+
+        let draws = (0..10_000)
+            .map(|_| worker.draw(&state, now))
+            .collect::<Vec<_>>();
+        assert!(draws
+            .iter()
+            .all(|&(duration, _)| (5..=20).contains(&duration)));
+        let errors = draws
+            .iter()
+            .filter(|&&(_, status)| status == StatusCode::INTERNAL_SERVER_ERROR)
+            .count();
+        assert!((2_300..2_700).contains(&errors), "{} errors", errors);
+
+        let never = setup(&state, json!({ "error_rate": 0.0 }));
+        let always = setup(&state, json!({ "error_rate": 1.0 }));
+        assert!((0..100).all(|_| worker.draw(&never, now).1 == StatusCode::OK));
+        assert!((0..100).all(|_| worker.draw(&always, now).1 == StatusCode::INTERNAL_SERVER_ERROR));
+    }
+
+    #[tokio::test]
+    async fn reset_replays_the_seeded_sequence() {
+        let worker = worker(&["--random-seed", "7"]);
+        let state = setup(
+            &GlobalState::default(),
+            json!({ "min_duration": 1, "max_duration": 100, "error_rate": 0.5 }),
+        );
+        let now = Instant::now();
+        let first = (0..20)
+            .map(|_| worker.draw(&state, now))
+            .collect::<Vec<_>>();
+
+        let (status, _) = send(&worker, request(Method::POST, "/reset", "")).await;
+        assert_eq!(status, StatusCode::OK);
+        let replayed = (0..20)
+            .map(|_| worker.draw(&state, now))
+            .collect::<Vec<_>>();
+        assert_eq!(first, replayed);
+    }
+
+    #[tokio::test]
+    async fn a_bad_error_rate_doesnt_break_the_worker() {
+        let worker = worker(&["--min-duration", "0", "--max-duration", "0"]);
+        for rate in [f64::NAN, f64::INFINITY, -1.0, 2.0] {
+            worker.state.write().await.error_rate = rate;
+            let (status, body) = send(&worker, request(Method::GET, "/work", "")).await;
+            assert!(
+                status == StatusCode::OK || status == StatusCode::INTERNAL_SERVER_ERROR,
+                "{} with rate {}: {}",
+                status,
+                rate,
+                body
+            );
+        }
+
+        post_setup(&worker, r#"{"error_rate": 0}"#).await;
+        let (status, _) = send(&worker, request(Method::GET, "/work", "")).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&worker, request(Method::POST, "/reset", "")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn a_poisoned_rng_still_draws() {
+        let worker = worker(&["--min-duration", "0", "--max-duration", "0"]);
+        let poisoner = worker.clone();
+        let _ = std::thread::spawn(move || {
+            let _rng = poisoner.rng.lock().unwrap();
+            panic!("poisoning the RNG");
+        })
+        .join();
+        assert!(worker.rng.is_poisoned());
+
+        let (status, _) = send(&worker, request(Method::GET, "/work", "")).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&worker, request(Method::POST, "/reset", "")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn slow_bodies_arrive_in_chunks_after_the_header_delay() {
+        let worker = worker(&["--min-duration", "0", "--max-duration", "0"]);
+        post_setup(
+            &worker,
+            r#"{"header_delay_ms": 100, "body_chunks": 3, "body_chunk_delay_ms": 50}"#,
+        )
+        .await;
+
+        let (status, head, frames) = timed(&worker, request(Method::GET, "/work", "")).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(head >= Duration::from_millis(100), "head after {:?}", head);
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].1, "part 1/3\n");
+        assert_eq!(frames[1].1, "part 2/3\n");
+        assert!(String::from_utf8_lossy(&frames[2].1).starts_with("Work done in "));
+        for pair in frames.windows(2) {
+            let gap = pair[1].0 - pair[0].0;
+            assert!(gap >= Duration::from_millis(40), "chunks {:?} apart", gap);
+        }
+    }
+
+    #[tokio::test]
+    async fn slow_json_bodies_split_the_document() {
+        let worker = worker(&["--min-duration", "0", "--max-duration", "0"]);
+        post_setup(&worker, r#"{"body_chunks": 4, "body_chunk_delay_ms": 20}"#).await;
+
+        let (status, _, frames) = timed(&worker, json_request(Method::GET, "/work", "")).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(frames.len(), 4);
+        let document = frames
+            .iter()
+            .flat_map(|(_, chunk)| chunk.to_vec())
+            .collect::<Vec<_>>();
+        let work = serde_json::from_slice::<WorkResponse>(&document).unwrap();
+        assert_eq!(work.worker, "worker-3000");
+        assert!(frames[3].0 - frames[0].0 >= Duration::from_millis(55));
+    }
+
+    #[tokio::test]
+    async fn unchunked_bodies_arrive_whole() {
+        let worker = worker(&["--min-duration", "0", "--max-duration", "0"]);
+
+        let (status, _, frames) = timed(&worker, request(Method::GET, "/work", "")).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(frames.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn cpu_work_leaves_the_runtime_free_for_other_requests() {
+        let worker = worker(&["--min-duration", "300", "--max-duration", "300"]);
+        post_setup(&worker, r#"{"work_mode": "cpu"}"#).await;
+
+        let busy = tokio::spawn({
+            let worker = worker.clone();
+            async move { send(&worker, json_request(Method::GET, "/work", "")).await }
+        });
+        sleep(Duration::from_millis(50)).await;
+        let (status, _) = send(&worker, request(Method::GET, "/health", "")).await;
+
+        // Answered while the work still runs.
+        assert_eq!(status, StatusCode::OK);
+        assert!(!busy.is_finished());
+        let (status, body) = busy.await.unwrap();
+        assert_eq!(status, StatusCode::OK);
+        let work = serde_json::from_str::<WorkResponse>(&body).unwrap();
+        assert_eq!(work.work_mode, "cpu");
+        assert!(work.duration_ms >= 300, "{}", body);
+    }
+
+    #[tokio::test]
+    async fn work_reports_its_mode_and_elapsed_time() {
+        let worker = worker(&["--min-duration", "100", "--max-duration", "100"]);
+
+        for mode in ["sleep", "cpu", "mixed"] {
+            post_setup(&worker, &format!(r#"{{"work_mode": "{}"}}"#, mode)).await;
+            let (_, body) = send(&worker, json_request(Method::GET, "/work", "")).await;
+            let work = serde_json::from_str::<WorkResponse>(&body).unwrap();
+            assert_eq!(work.work_mode, mode);
+            assert!((100..500).contains(&work.duration_ms), "{}", body);
+
+            let (_, text) = send(&worker, request(Method::GET, "/work", "")).await;
+            assert!(text.ends_with(&format!("({} mode)", mode)), "{}", text);
+        }
+
+        let (status, body) = send(
+            &worker,
+            request(Method::POST, "/setup", r#"{"work_mode": "gpu"}"#),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body,
+            "Invalid work mode. Valid values are 'sleep', 'cpu' or 'mixed'"
+        );
+    }
+
+    async fn stats_json(worker: &Arc<Worker>) -> WorkerStats {
+        let (status, body) = send(worker, request(Method::GET, "/stats", "")).await;
+        assert_eq!(status, StatusCode::OK);
+        serde_json::from_str(&body).unwrap()
+    }
+
+    // The /work requests recorded this minute.
+    async fn work_requests(worker: &Arc<Worker>) -> serde_json::Value {
+        let stats = stats_json(worker).await;
+        let minutes = stats.timeseries.as_array().unwrap();
+        minutes.last().unwrap()["endpoints"]["/work"]["requests"].clone()
+    }
+
+    #[tokio::test]
+    async fn work_memory_is_reported_while_held_and_retained_memory_until_reset() {
+        const MB: u64 = BYTES_PER_MB as u64;
+        let worker = worker(&[
+            "--min-duration",
+            "200",
+            "--max-duration",
+            "200",
+            "--memory-cap-mb",
+            "8",
+        ]);
+        post_setup(&worker, r#"{"memory_mb": 2, "retain_mb": 1}"#).await;
+
+        let working = tokio::spawn({
+            let worker = worker.clone();
+            async move { send(&worker, request(Method::GET, "/work", "")).await }
+        });
+        sleep(Duration::from_millis(100)).await;
+        let memory = stats_json(&worker).await.memory;
+        assert_eq!(
+            (
+                memory.allocated_bytes,
+                memory.retained_bytes,
+                memory.cap_bytes
+            ),
+            (2 * MB, MB, 8 * MB)
+        );
+
+        assert_eq!(working.await.unwrap().0, StatusCode::OK);
+        send(&worker, request(Method::GET, "/work", "")).await;
+        let memory = stats_json(&worker).await.memory;
+        assert_eq!((memory.allocated_bytes, memory.retained_bytes), (0, 2 * MB));
+
+        send(&worker, request(Method::POST, "/reset", "")).await;
+        let memory = stats_json(&worker).await.memory;
+        assert_eq!((memory.allocated_bytes, memory.retained_bytes), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn work_past_the_memory_cap_is_refused() {
+        let worker = worker(&[
+            "--min-duration",
+            "0",
+            "--max-duration",
+            "0",
+            "--memory-cap-mb",
+            "4",
+        ]);
+
+        let (status, body) = send(
+            &worker,
+            request(Method::POST, "/setup", r#"{"memory_mb": 5}"#),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body,
+            "memory_mb and retain_mb must not exceed the memory cap of 4 MB"
+        );
+
+        post_setup(&worker, r#"{"retain_mb": 3}"#).await;
+        assert_eq!(
+            send(&worker, request(Method::GET, "/work", "")).await.0,
+            StatusCode::OK
+        );
+        post_setup(&worker, r#"{"retain_mb": 0, "memory_mb": 2}"#).await;
+        assert_eq!(
+            send(&worker, request(Method::GET, "/work", "")).await,
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Memory cap of 4 MB reached, refusing to allocate 2 MB".to_string()
+            )
+        );
+
+        send(&worker, request(Method::POST, "/reset", "")).await;
+        post_setup(&worker, r#"{"memory_mb": 2}"#).await;
+        assert_eq!(
+            send(&worker, request(Method::GET, "/work", "")).await.0,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn hung_work_waits_for_reset() {
+        let worker = worker(&["--min-duration", "0", "--max-duration", "0"]);
+        let body = r#"{"confirm": true, "scope": "work"}"#;
+        assert_eq!(
+            send(&worker, request(Method::POST, "/hang", body)).await,
+            (StatusCode::OK, "Hanging 'work' requests".to_string())
+        );
+
+        let hung = tokio::spawn({
+            let worker = worker.clone();
+            async move { send(&worker, request(Method::GET, "/work", "")).await }
+        });
+        sleep(Duration::from_millis(200)).await;
+        assert!(!hung.is_finished());
+        assert_eq!(
+            send(&worker, request(Method::GET, "/health", "")).await.0,
+            StatusCode::OK
+        );
+        assert_eq!(
+            send(&worker, request(Method::GET, "/ready", "")).await,
+            (StatusCode::SERVICE_UNAVAILABLE, "Hung".to_string())
+        );
+
+        assert_eq!(
+            send(&worker, request(Method::POST, "/reset", "")).await.0,
+            StatusCode::OK
+        );
+        let (status, _) = tokio::time::timeout(Duration::from_secs(1), hung)
+            .await
+            .expect("released by /reset")
+            .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            send(&worker, request(Method::GET, "/ready", "")).await.0,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn hanging_everything_still_lets_reset_through() {
+        let worker = worker(&[]);
+        let (status, _) = send(
+            &worker,
+            request(Method::POST, "/hang", r#"{"confirm": true}"#),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let hung = tokio::spawn({
+            let worker = worker.clone();
+            async move { send(&worker, request(Method::GET, "/health", "")).await }
+        });
+        sleep(Duration::from_millis(100)).await;
+        assert!(!hung.is_finished());
+
+        send(&worker, request(Method::POST, "/reset", "")).await;
+        let (status, _) = tokio::time::timeout(Duration::from_secs(1), hung)
+            .await
+            .expect("released by /reset")
+            .unwrap();
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn hangs_need_confirming_and_a_known_scope() {
+        let worker = worker(&[]);
+
+        for body in ["{}", r#"{"confirm": false}"#, r#"{"confirm": "true"}"#] {
+            assert_eq!(
+                send(&worker, request(Method::POST, "/hang", body)).await,
+                (StatusCode::BAD_REQUEST, CONFIRMATION_REQUIRED.to_string()),
+                "{}",
+                body
+            );
+        }
+        let body = r#"{"confirm": true, "scope": "setup"}"#;
+        assert_eq!(
+            send(&worker, request(Method::POST, "/hang", body)).await,
+            (
+                StatusCode::BAD_REQUEST,
+                "Invalid hang scope. Valid values are 'all' or 'work'".to_string()
+            )
+        );
+        assert_eq!(
+            send(&worker, request(Method::GET, "/work", "")).await.0,
+            StatusCode::OK
+        );
+    }
+
+    async fn echo(worker: &Arc<Worker>, req: Request<RequestBody>) -> serde_json::Value {
+        let (status, body) = send(worker, req).await;
+        assert_eq!(status, StatusCode::OK);
+        serde_json::from_str(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn echo_returns_the_request_as_received() {
+        let worker = worker(&[]);
+        let mut req = request(Method::POST, "/echo?a=1&b=two", "hello");
+        let headers = req.headers_mut();
+        headers.insert("x-custom", header::HeaderValue::from_static("value"));
+        headers.append("x-many", header::HeaderValue::from_static("one"));
+        headers.append("x-many", header::HeaderValue::from_static("two"));
+
+        let echo = echo(&worker, req).await;
+
+        assert_eq!(echo["method"], "POST");
+        assert_eq!(echo["path"], "/echo");
+        assert_eq!(echo["query"], "a=1&b=two");
+        assert_eq!(
+            echo["headers"],
+            json!({ "x-custom": "value", "x-many": "one, two" })
+        );
+        assert_eq!(echo["body"], "hello");
+        assert_eq!(echo["body_encoding"], "utf-8");
+        assert_eq!(echo["body_bytes"], 5);
+        assert_eq!(echo["truncated"], false);
+        assert_eq!(echo["peer_addr"], PEER.to_string());
+    }
+
+    #[tokio::test]
+    async fn echo_encodes_binary_bodies_in_base64() {
+        let worker = worker(&[]);
+        let body = Full::new(Bytes::from_static(&[0xff, 0x00, 0xfe]))
+            .map_err(|never| match never {})
+            .boxed();
+        let req = Request::post("/echo").body(body).unwrap();
+
+        let echo = echo(&worker, req).await;
+
+        assert_eq!(echo["query"], serde_json::Value::Null);
+        assert_eq!(echo["body"], BASE64_STANDARD.encode([0xff, 0x00, 0xfe]));
+        assert_eq!(echo["body_encoding"], "base64");
+        assert_eq!(echo["body_bytes"], 3);
+    }
+
+    #[tokio::test]
+    async fn echo_truncates_bodies_past_its_limit() {
+        let worker = worker(&["--echo-max-bytes", "4"]);
+
+        let echo = echo(&worker, request(Method::POST, "/echo", "abcdefgh")).await;
+
+        assert_eq!(echo["body"], "abcd");
+        assert_eq!(echo["body_bytes"], 8);
+        assert_eq!(echo["truncated"], true);
+    }
+
+    #[tokio::test]
+    async fn streamed_work_ticks_until_its_duration_elapsed() {
+        let worker = worker(&["--min-duration", "300", "--max-duration", "300"]);
+        let body = r#"{"stream": true, "tick_ms": 100}"#;
+
+        let (status, head, frames) = timed(&worker, request(Method::POST, "/work", body)).await;
+
+        assert_eq!(status, StatusCode::OK);
+        // The head went out before the work, not once it was done.
+        assert!(
+            frames[3].0 - head >= Duration::from_millis(200),
+            "head after {:?}",
+            head
+        );
+        let lines = frames
+            .iter()
+            .map(|(_, chunk)| String::from_utf8_lossy(chunk).to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(lines, ["tick 1/3\n", "tick 2/3\n", "tick 3/3\n", "done\n"]);
+        for (i, (at, _)) in frames[..3].iter().enumerate() {
+            let due = Duration::from_millis(100 * (i as u64 + 1));
+            assert!(*at >= due, "tick {} after {:?}", i + 1, at);
+        }
+    }
+
+    #[tokio::test]
+    async fn streamed_json_work_sends_a_line_per_tick() {
+        let worker = worker(&["--min-duration", "100", "--max-duration", "100"]);
+
+        let req = json_request(Method::GET, "/work?stream=true&tick_ms=50", "");
+        let res = router(req, PEER, worker.clone()).await.unwrap();
+        assert_eq!(res.headers()[header::CONTENT_TYPE], NDJSON);
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+
+        let lines = String::from_utf8_lossy(&body)
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                json!({ "tick": 1, "ticks": 2 }),
+                json!({ "tick": 2, "ticks": 2 }),
+                json!({ "done": true })
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn streamed_work_stays_in_flight_and_is_recorded_when_done() {
+        let worker = worker(&["--min-duration", "300", "--max-duration", "300"]);
+        let body = r#"{"stream": true, "tick_ms": 100}"#;
+
+        let res = router(request(Method::POST, "/work", body), PEER, worker.clone())
+            .await
+            .unwrap();
+        assert_eq!(stats_json(&worker).await.in_flight, 1);
+        // This minute's requests.
+        let work = |stats: &WorkerStats| {
+            let minutes = stats.timeseries.as_array().unwrap();
+            minutes.last().unwrap()["endpoints"]["/work"].clone()
+        };
+        assert_eq!(work(&stats_json(&worker).await), serde_json::Value::Null);
+
+        res.into_body().collect().await.unwrap();
+        // The body ends as the task sends its last line, give it the time to finish.
+        sleep(Duration::from_millis(20)).await;
+        let stats = stats_json(&worker).await;
+        assert_eq!(stats.in_flight, 0);
+        let work = work(&stats);
+        assert_eq!(work["requests"], 1);
+        let duration = work["avg_duration_ms"].as_u64().unwrap();
+        assert!((300..500).contains(&duration), "{}", work);
+    }
+
+    // GET /work with the simulation override headers, and JSON accepted.
+    async fn overridden_work(
+        worker: &Arc<Worker>,
+        headers: &[(&'static str, &'static str)],
+    ) -> (StatusCode, WorkResponse) {
+        let mut req = json_request(Method::GET, "/work", "");
+        for (name, value) in headers {
+            req.headers_mut()
+                .insert(*name, header::HeaderValue::from_static(value));
+        }
+        let (status, body) = send(worker, req).await;
+        (status, serde_json::from_str(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn override_headers_are_ignored_unless_allowed() {
+        let worker = worker(&["--min-duration", "0", "--max-duration", "0"]);
+
+        let (status, work) = overridden_work(
+            &worker,
+            &[
+                (overrides::DELAY_HEADER, "60000"),
+                (overrides::STATUS_HEADER, "503"),
+            ],
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(work.duration_ms < 60_000, "{:?}", work);
+        assert_eq!(work.overrides, None);
+    }
+
+    #[tokio::test]
+    async fn huge_durations_saturate_instead_of_overflowing() {
+        let worker = worker(&[]);
+        let max = u64::MAX.to_string();
+        post_setup(
+            &worker,
+            &format!(
+                r#"{{"min_duration": {max}, "max_duration": {max}, "allow_overrides": true}}"#
+            ),
+        )
+        .await;
+
+        let mut req = json_request(Method::POST, "/work", r#"{"multiplier": 10}"#);
+        req.headers_mut().insert(
+            overrides::DELAY_HEADER,
+            header::HeaderValue::from_static("0"),
+        );
+        let (status, body) = send(&worker, req).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let work = serde_json::from_str::<WorkResponse>(&body).unwrap();
+        assert_eq!(work.base_duration_ms, u64::MAX);
+    }
+
+    #[test]
+    fn stream_ticks_cover_the_duration() {
+        assert_eq!(stream_ticks(1000, 100), (10, Duration::from_millis(100)));
+        assert_eq!(stream_ticks(50, 100), (1, Duration::from_millis(50)));
+        assert_eq!(stream_ticks(0, 100), (1, Duration::ZERO));
+        assert_eq!(stream_ticks(1050, 100), (10, Duration::from_millis(105)));
+    }
+
+    #[test]
+    fn stream_ticks_past_u32_stretch_instead_of_wrapping() {
+        let duration = u64::from(u32::MAX) + 5;
+        let (ticks, tick) = stream_ticks(duration, 1);
+        assert_eq!(ticks, u32::MAX);
+        assert!(tick >= Duration::from_millis(1));
+        assert!(tick * ticks <= Duration::from_millis(duration));
+
+        let (ticks, tick) = stream_ticks(u64::MAX, 1);
+        assert_eq!(ticks, u32::MAX);
+        assert!(tick > Duration::from_secs(4_000_000));
+    }
+
+    #[tokio::test]
+    async fn allowed_overrides_apply_to_their_request_only() {
+        let worker = worker(&["--min-duration", "0", "--max-duration", "0"]);
+        post_setup(&worker, r#"{"allow_overrides": true}"#).await;
+
+        let (status, work) = overridden_work(&worker, &[(overrides::DELAY_HEADER, "150")]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(work.duration_ms >= 150, "{:?}", work);
+        assert_eq!(work.overrides.as_deref(), Some("delay_ms=150"));
+
+        let (status, work) = overridden_work(&worker, &[(overrides::STATUS_HEADER, "503")]).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(work.overrides.as_deref(), Some("status=503"));
+
+        let (status, work) = overridden_work(&worker, &[(overrides::ERROR_HEADER, "true")]).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(work.overrides.as_deref(), Some("error=true"));
+
+        let (status, work) = overridden_work(&worker, &[]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(work.effective_duration_ms, 0);
+        assert_eq!(work.overrides, None);
+        let (_, text) = send(&worker, request(Method::GET, "/work", "")).await;
+        assert!(!text.contains("overrides"), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn concurrent_work_is_slowed_past_the_threshold() {
+        let worker = worker(&["--min-duration", "100", "--max-duration", "100"]);
+        post_setup(
+            &worker,
+            r#"{"concurrency_k": 1.0, "concurrency_threshold": 1}"#,
+        )
+        .await;
+
+        let mut requests = tokio::task::JoinSet::new();
+        for _ in 0..3 {
+            let worker = worker.clone();
+            requests.spawn(async move {
+                let (_, body) = send(&worker, json_request(Method::GET, "/work", "")).await;
+                serde_json::from_str::<WorkResponse>(&body).unwrap()
+            });
+        }
+        let mut responses = requests.join_all().await;
+        responses.sort_by_key(|work| work.effective_duration_ms);
+
+        let durations = responses
+            .iter()
+            .map(|work| (work.base_duration_ms, work.effective_duration_ms))
+            .collect::<Vec<_>>();
+        assert_eq!(durations, [(100, 100), (100, 200), (100, 300)]);
+        for work in &responses {
+            assert!(work.duration_ms >= work.effective_duration_ms, "{:?}", work);
+        }
+        let (_, text) = send(&worker, request(Method::GET, "/work", "")).await;
+        assert!(!text.contains("effective"), "{}", text);
+    }
+
+    // Serves `worker` on a free port of this machine until the test ends.
+    async fn served(worker: Arc<Worker>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_listener(listener, worker, std::future::pending()));
+        addr
+    }
+
+    // Writes `request` as it is and reads until the worker closes the connection.
+    async fn exchange(addr: SocketAddr, request: &[u8]) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request).await.unwrap();
+        let mut response = Vec::new();
+        let _ = stream.read_to_end(&mut response).await;
+        String::from_utf8_lossy(&response).to_string()
+    }
+
+    const POST_PATHS: [&str; 5] = ["/setup", "/work", "/echo", "/hang", "/crash"];
+
+    #[tokio::test]
+    async fn bodies_announced_past_the_limit_are_refused_up_front() {
+        let worker = worker(&["--max-body-bytes", "16"]);
+
+        for path in POST_PATHS {
+            let mut req = request(Method::POST, path, "{}");
+            req.headers_mut().insert(
+                header::CONTENT_LENGTH,
+                header::HeaderValue::from_static("17"),
+            );
+            assert_eq!(
+                send(&worker, req).await,
+                (
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    "Request body exceeds the limit of 16 bytes".to_string()
+                ),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn bodies_without_a_length_are_cut_off_at_the_limit() {
+        let worker = worker(&["--max-body-bytes", "16"]);
+        let oversized = format!(r#"{{"padding": "{}"}}"#, "x".repeat(16));
+
+        for path in POST_PATHS {
+            let (status, _) = send(&worker, request(Method::POST, path, &oversized)).await;
+            assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE, "{}", path);
+        }
+        let (status, _) = send(&worker, request(Method::POST, "/work", "{}")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn chunked_bodies_past_the_limit_are_refused() {
+        let addr = served(worker(&["--max-body-bytes", "16"])).await;
+
+        let response = exchange(
+            addr,
+            b"POST /echo HTTP/1.1\r\nHost: worker\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+              a\r\n0123456789\r\na\r\n0123456789\r\n0\r\n\r\n",
+        )
+        .await;
+        assert!(
+            response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"),
+            "{}",
+            response
+        );
+
+        let response = exchange(
+            addr,
+            b"POST /echo HTTP/1.1\r\nHost: worker\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+              a\r\n0123456789\r\n0\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.contains(r#""body":"0123456789""#), "{}", response);
+    }
+
+    // Everything a JSON subscriber writes, one object per line.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Captured {
+        fn events(&self) -> Vec<serde_json::Value> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn json_logs_carry_the_request_and_work_fields() {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_span_list(true)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _default = tracing::subscriber::set_default(subscriber);
+        let worker = worker(&[
+            "--name",
+            "logged",
+            "--min-duration",
+            "0",
+            "--max-duration",
+            "0",
+        ]);
+
+        let mut req = request(Method::GET, "/work", "");
+        req.headers_mut().insert(
+            REQUEST_ID_HEADER,
+            header::HeaderValue::from_static("req-124"),
+        );
+        let (status, _) = send(&worker, req).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let events = captured.events();
+        let scheduled = events
+            .iter()
+            .find(|event| event["fields"]["message"] == "Work scheduled")
+            .unwrap_or_else(|| panic!("{:?}", events));
+        let fields = &scheduled["fields"];
+        assert_eq!(fields["duration_ms"], 0, "{}", scheduled);
+        assert_eq!(fields["status"], 200, "{}", scheduled);
+        assert_eq!(fields["outcome"], "success", "{}", scheduled);
+
+        let request_span = &scheduled["spans"][0];
+        assert_eq!(request_span["name"], "request", "{}", scheduled);
+        assert_eq!(request_span["method"], "GET", "{}", scheduled);
+        assert_eq!(request_span["path"], "/work", "{}", scheduled);
+        assert_eq!(request_span["request_id"], "req-124", "{}", scheduled);
+        assert_eq!(request_span["worker"], "logged", "{}", scheduled);
+        let work_span = &scheduled["span"];
+        assert_eq!(work_span["name"], "work", "{}", scheduled);
+        assert_eq!(work_span["duration_ms"], 0, "{}", scheduled);
+        assert_eq!(work_span["status"], 200, "{}", scheduled);
+    }
+
+    #[tokio::test]
+    async fn get_work_takes_its_parameters_from_the_query() {
+        let worker = worker(&["--min-duration", "20", "--max-duration", "20"]);
+
+        let (status, body) =
+            send(&worker, json_request(Method::GET, "/work?multiplier=3", "")).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let queried: WorkResponse = serde_json::from_str(&body).unwrap();
+        let (status, body) = send(
+            &worker,
+            json_request(Method::POST, "/work", r#"{"multiplier": 3}"#),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let posted: WorkResponse = serde_json::from_str(&body).unwrap();
+        assert_eq!(queried.base_duration_ms, 60);
+        assert_eq!(queried.base_duration_ms, posted.base_duration_ms);
+
+        let (status, body) = send(&worker, json_request(Method::GET, "/work", "")).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let defaulted: WorkResponse = serde_json::from_str(&body).unwrap();
+        assert_eq!(defaulted.base_duration_ms, 20);
+    }
+
+    #[tokio::test]
+    async fn invalid_query_parameters_are_refused_like_invalid_bodies() {
+        let worker = worker(&["--min-duration", "0", "--max-duration", "0"]);
+
+        for (query, body) in [
+            ("multiplier=abc", r#"{"multiplier": "abc"}"#),
+            ("stream=maybe", r#"{"stream": "maybe"}"#),
+        ] {
+            let queried = send(
+                &worker,
+                request(Method::GET, &format!("/work?{}", query), ""),
+            )
+            .await;
+            let posted = send(&worker, request(Method::POST, "/work", body)).await;
+            assert_eq!(queried.0, StatusCode::BAD_REQUEST, "{}", queried.1);
+            assert_eq!(queried, posted);
+        }
+    }
+
+    #[tokio::test]
+    async fn head_work_answers_with_headers_only() {
+        let worker = worker(&["--min-duration", "0", "--max-duration", "0"]);
+        let addr = served(worker.clone()).await;
+
+        let response = exchange(
+            addr,
+            b"HEAD /work?multiplier=2 HTTP/1.1\r\nHost: worker\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.ends_with("\r\n\r\n"), "{}", response);
+        assert_eq!(work_requests(&worker).await, 1);
+    }
+
+    // POST /work with an Idempotency-Key, how long it took, whether it was a replay and its body.
+    async fn keyed_work(worker: &Arc<Worker>, key: &'static str) -> (Duration, bool, String) {
+        let mut req = json_request(Method::POST, "/work", "{}");
+        req.headers_mut().insert(
+            idempotency::IDEMPOTENCY_KEY_HEADER,
+            header::HeaderValue::from_static(key),
+        );
+        let sent_at = Instant::now();
+        let res = router(req, PEER, worker.clone()).await.unwrap();
+        let replayed = res.headers().get(idempotency::REPLAY_HEADER).is_some();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        (
+            sent_at.elapsed(),
+            replayed,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn repeated_idempotency_keys_replay_without_working() {
+        let worker = worker(&["--min-duration", "100", "--max-duration", "100"]);
+
+        let (worked, replayed, first) = keyed_work(&worker, "order-1").await;
+        assert!(worked >= Duration::from_millis(100), "{:?}", worked);
+        assert!(!replayed);
+
+        let (elapsed, replayed, replay) = keyed_work(&worker, "order-1").await;
+        assert!(
+            elapsed < worked,
+            "{:?} replaying, {:?} working",
+            elapsed,
+            worked
+        );
+        assert!(replayed);
+        assert_eq!(replay, first);
+        assert_eq!(work_requests(&worker).await, 1);
+
+        let (_, replayed, _) = keyed_work(&worker, "order-2").await;
+        assert!(!replayed);
+        assert_eq!(work_requests(&worker).await, 2);
+    }
+
+    #[tokio::test]
+    async fn idempotency_keys_expire_after_their_ttl() {
+        let worker = worker(&[
+            "--min-duration",
+            "0",
+            "--max-duration",
+            "0",
+            "--idempotency-ttl-ms",
+            "50",
+        ]);
+
+        assert!(!keyed_work(&worker, "order-1").await.1);
+        assert!(keyed_work(&worker, "order-1").await.1);
+        sleep(Duration::from_millis(60)).await;
+        assert!(!keyed_work(&worker, "order-1").await.1);
+        assert_eq!(work_requests(&worker).await, 2);
+    }
+
+    #[tokio::test]
+    async fn concurrent_duplicates_are_worked_once() {
+        let worker = worker(&["--min-duration", "100", "--max-duration", "100"]);
+
+        let (first, second) = tokio::join!(
+            keyed_work(&worker, "order-1"),
+            keyed_work(&worker, "order-1")
+        );
+        // The duplicate waited for the first arrival's response.
+        assert_eq!(
+            [first.1, second.1]
+                .iter()
+                .filter(|replayed| **replayed)
+                .count(),
+            1
+        );
+        assert!(second.0 >= Duration::from_millis(100), "{:?}", second.0);
+        assert_eq!(first.2, second.2);
+        assert_eq!(work_requests(&worker).await, 1);
+    }
+
+    async fn post_setup_json(worker: &Arc<Worker>, body: &str) -> SetupResponse {
+        let (status, body) = send(worker, json_request(Method::POST, "/setup", body)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        serde_json::from_str(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn scheduled_setups_apply_then_revert() {
+        let worker = worker(&["--error-rate", "0"]);
+
+        let response = post_setup_json(
+            &worker,
+            r#"{"error_rate": 1.0, "apply_after_ms": 100, "revert_after_ms": 100}"#,
+        )
+        .await;
+        assert_eq!(response.status, "scheduled");
+        assert_eq!(
+            (response.apply_after_ms, response.revert_after_ms),
+            (100, Some(100))
+        );
+
+        // Before.
+        let info = setup_info_json(&worker).await;
+        assert_eq!(info["error_rate"], 0.0);
+        assert_eq!(info["schedule"]["phase"], "pending", "{}", info);
+        assert!(info["schedule"]["apply_in_ms"].as_u64().unwrap() <= 100);
+
+        // During.
+        sleep(Duration::from_millis(150)).await;
+        let info = setup_info_json(&worker).await;
+        assert_eq!(info["error_rate"], 1.0);
+        assert_eq!(info["schedule"]["phase"], "reverting", "{}", info);
+        let (status, _) = send(&worker, request(Method::GET, "/work", "")).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+        // After.
+        sleep(Duration::from_millis(100)).await;
+        let info = setup_info_json(&worker).await;
+        assert_eq!(info["error_rate"], 0.0);
+        assert_eq!(info["schedule"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn later_setups_cancel_the_schedule() {
+        let worker = worker(&["--error-rate", "0"]);
+
+        post_setup(&worker, r#"{"error_rate": 1.0, "apply_after_ms": 50}"#).await;
+        let response = post_setup_json(&worker, r#"{"min_duration": 5}"#).await;
+        assert_eq!(response.cancelled_schedule.as_deref(), Some("pending"));
+        sleep(Duration::from_millis(80)).await;
+        let info = setup_info_json(&worker).await;
+        assert_eq!(info["error_rate"], 0.0);
+        assert_eq!(info["schedule"], serde_json::Value::Null);
+
+        // A cancelled revert keeps the change.
+        post_setup(&worker, r#"{"error_rate": 1.0, "revert_after_ms": 50}"#).await;
+        let (_, msg) = send(&worker, request(Method::POST, "/setup", "{}")).await;
+        assert!(msg.ends_with(", cancelled reverting schedule"), "{}", msg);
+        sleep(Duration::from_millis(80)).await;
+        assert_eq!(setup_info_json(&worker).await["error_rate"], 1.0);
+    }
+
+    #[tokio::test]
+    async fn body_faults_require_allow_overrides() {
+        let worker = worker(&[]);
+
+        for setup in [
+            r#"{"truncate_body_at_bytes": 10}"#,
+            r#"{"drop_after_headers": true}"#,
+        ] {
+            assert_eq!(
+                send(&worker, request(Method::POST, "/setup", setup)).await,
+                (
+                    StatusCode::BAD_REQUEST,
+                    "truncate_body_at_bytes and drop_after_headers require allow_overrides"
+                        .to_string()
+                ),
+                "{}",
+                setup
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn truncated_work_closes_the_connection_mid_body() {
+        let worker = worker(&["--min-duration", "0", "--max-duration", "0"]);
+        post_setup(
+            &worker,
+            r#"{"allow_overrides": true, "truncate_body_at_bytes": 10}"#,
+        )
+        .await;
+        let addr = served(worker).await;
+
+        // Without `Connection: close`, only the fault ends the exchange.
+        let response = exchange(addr, b"GET /work HTTP/1.1\r\nHost: worker\r\n\r\n").await;
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        let length = head
+            .lines()
+            .find_map(|line| line.strip_prefix("content-length: "))
+            .unwrap_or_else(|| panic!("{}", response))
+            .parse::<usize>()
+            .unwrap();
+        assert!(length > 10, "{}", response);
+        assert_eq!(body.len(), 10, "{}", response);
+    }
+
+    #[tokio::test]
+    async fn dropped_work_closes_the_connection_after_the_head() {
+        let worker = worker(&["--min-duration", "0", "--max-duration", "0"]);
+        post_setup(
+            &worker,
+            r#"{"allow_overrides": true, "drop_after_headers": true}"#,
+        )
+        .await;
+        let addr = served(worker).await;
+
+        let response = exchange(addr, b"GET /work HTTP/1.1\r\nHost: worker\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.ends_with("\r\n\r\n"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn http2_workers_answer_over_h2c() {
+        let addr = served(worker(&[
+            "--http2",
+            "--min-duration",
+            "0",
+            "--max-duration",
+            "0",
+        ]))
+        .await;
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let (mut sender, conn) =
+            hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+                .await
+                .unwrap();
+        tokio::spawn(conn);
+
+        for (method, path, body) in [
+            (Method::GET, "/health", ""),
+            (Method::GET, "/work", ""),
+            (Method::POST, "/work", r#"{"multiplier": 2}"#),
+        ] {
+            let req = Request::builder()
+                .method(method.clone())
+                .uri(format!("http://{}{}", addr, path))
+                .header(header::ACCEPT, negotiate::APPLICATION_JSON)
+                .body(Full::new(Bytes::from(body)))
+                .unwrap();
+            let res = sender.send_request(req).await.unwrap();
+            assert_eq!(res.version(), hyper::Version::HTTP_2);
+            assert_eq!(res.status(), StatusCode::OK, "{} {}", method, path);
+            let body = res.into_body().collect().await.unwrap().to_bytes();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap();
+        }
+    }
+
+    // An HTTP/1.1 client connection to `addr` and the task driving it, which ends with the connection.
+    async fn http1_client(
+        addr: SocketAddr,
+    ) -> (
+        hyper::client::conn::http1::SendRequest<Full<Bytes>>,
+        tokio::task::JoinHandle<hyper::Result<()>>,
+    ) {
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let (sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+            .await
+            .unwrap();
+        (sender, tokio::spawn(conn))
+    }
+
+    fn health(addr: SocketAddr) -> Request<Full<Bytes>> {
+        Request::builder()
+            .uri(format!("http://{}/health", addr))
+            .body(Full::default())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn idle_connections_are_closed_after_the_timeout() {
+        let addr = served(worker(&["--idle-timeout-ms", "100"])).await;
+
+        let (mut sender, conn) = http1_client(addr).await;
+        let res = sender.send_request(health(addr)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        res.into_body().collect().await.unwrap();
+
+        // Closed cleanly, the client sees the connection end rather than a failed request.
+        tokio::time::timeout(Duration::from_secs(1), conn)
+            .await
+            .expect("the idle connection to close")
+            .unwrap()
+            .unwrap();
+        assert!(sender.is_closed());
+
+        let (mut sender, _conn) = http1_client(addr).await;
+        let res = sender.send_request(health(addr)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn requests_outlasting_the_idle_timeout_complete() {
+        let addr = served(worker(&[
+            "--idle-timeout-ms",
+            "50",
+            "--min-duration",
+            "150",
+            "--max-duration",
+            "150",
+        ]))
+        .await;
+
+        let (mut sender, _conn) = http1_client(addr).await;
+        let req = Request::builder()
+            .uri(format!("http://{}/work", addr))
+            .body(Full::default())
+            .unwrap();
+        let res = sender.send_request(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        res.into_body().collect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn connections_past_the_limit_wait_to_be_accepted() {
+        let addr = served(worker(&["--max-connections", "1"])).await;
+
+        let (mut first, first_conn) = http1_client(addr).await;
+        first.send_request(health(addr)).await.unwrap();
+
+        let (mut second, _second_conn) = http1_client(addr).await;
+        let waiting = tokio::spawn(async move { second.send_request(health(addr)).await });
+        sleep(Duration::from_millis(100)).await;
+        assert!(!waiting.is_finished());
+
+        drop(first);
+        first_conn.await.unwrap().unwrap();
+        let res = tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .expect("the second connection to be accepted")
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn slow_request_headers_are_cut_off() {
+        let addr = served(worker(&["--header-read-timeout-ms", "100"])).await;
+
+        let sent_at = Instant::now();
+        let response = tokio::time::timeout(
+            Duration::from_secs(1),
+            exchange(addr, b"GET /health HTTP/1.1\r\nHost: worker\r\n"),
+        )
+        .await
+        .expect("the connection to be closed");
+        assert!(sent_at.elapsed() >= Duration::from_millis(100));
+        assert!(!response.contains("200 OK"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn health_setup_and_work_negotiate_their_format() {
+        let worker = worker(&["--min-duration", "0", "--max-duration", "0"]);
+
+        for (method, path, body) in [
+            (Method::GET, "/health", ""),
+            (Method::POST, "/setup", "{}"),
+            (Method::POST, "/work", "{}"),
+        ] {
+            for (accept, expected) in [
+                (None, Some(negotiate::TEXT_PLAIN)),
+                (Some("text/plain"), Some(negotiate::TEXT_PLAIN)),
+                (Some("application/json"), Some(negotiate::APPLICATION_JSON)),
+                (
+                    Some("text/plain;q=0.5, application/json"),
+                    Some(negotiate::APPLICATION_JSON),
+                ),
+                (Some("text/plain;q=0, application/json;q=0"), None),
+            ] {
+                let mut req = request(method.clone(), path, body);
+                if let Some(accept) = accept {
+                    req.headers_mut()
+                        .insert(header::ACCEPT, header::HeaderValue::from_static(accept));
+                }
+                let res = router(req, PEER, worker.clone()).await.unwrap();
+                let status = res.status();
+                let content_type = res.headers()[header::CONTENT_TYPE].clone();
+                let body = res.into_body().collect().await.unwrap().to_bytes();
+                let is_json = serde_json::from_slice::<serde_json::Value>(&body)
+                    .is_ok_and(|value| value.is_object());
+
+                let case = format!("{} {} with {:?}", method, path, accept);
+                match expected {
+                    Some(expected_type) => {
+                        assert_eq!(status, StatusCode::OK, "{}", case);
+                        assert_eq!(content_type, expected_type, "{}", case);
+                        assert_eq!(
+                            is_json,
+                            expected_type == negotiate::APPLICATION_JSON,
+                            "{}",
+                            case
+                        );
+                    }
+                    None => assert_eq!(status, StatusCode::NOT_ACCEPTABLE, "{}", case),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub const BYTES_PER_MB: usize = 1024 * 1024;
+
+const PAGE_SIZE: usize = 4096;
+
+pub struct MemorySimulator {
+    cap: usize,
+    allocated: AtomicUsize,
+    retained: Mutex<Vec<Vec<u8>>>,
+    retained_bytes: AtomicUsize,
+}
+
+// Held for as long as the work it was made for, streamed work outlives the handler.
+pub struct Allocation {
+    simulator: Arc<MemorySimulator>,
+    _buffer: Vec<u8>,
+    bytes: usize,
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        self.simulator
+            .allocated
+            .fetch_sub(self.bytes, Ordering::SeqCst);
+    }
+}
+
+impl MemorySimulator {
+    pub fn new(cap: usize) -> Self {
+        MemorySimulator {
+            cap,
+            allocated: AtomicUsize::new(0),
+            retained: Mutex::new(Vec::new()),
+            retained_bytes: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn cap(&self) -> usize {
+        self.cap
+    }
+
+    pub fn allocated_bytes(&self) -> usize {
+        self.allocated.load(Ordering::SeqCst)
+    }
+
+    pub fn retained_bytes(&self) -> usize {
+        self.retained_bytes.load(Ordering::SeqCst)
+    }
+
+    pub fn allocate(self: &Arc<Self>, bytes: usize) -> Result<Allocation, String> {
+        self.reserve(&self.allocated, bytes)?;
+        Ok(Allocation {
+            simulator: self.clone(),
+            _buffer: touched_buffer(bytes),
+            bytes,
+        })
+    }
+
+    pub fn retain(&self, bytes: usize) -> Result<(), String> {
+        self.reserve(&self.retained_bytes, bytes)?;
+        self.retained.lock().unwrap().push(touched_buffer(bytes));
+        Ok(())
+    }
+
+    pub fn reset(&self) {
+        let mut retained = self.retained.lock().unwrap();
+        retained.clear();
+        retained.shrink_to_fit();
+        self.retained_bytes.store(0, Ordering::SeqCst);
+    }
+
+    fn reserve(&self, counter: &AtomicUsize, bytes: usize) -> Result<(), String> {
+        if bytes == 0 {
+            return Ok(());
+        }
+
+        counter.fetch_add(bytes, Ordering::SeqCst);
+        if self.allocated_bytes() + self.retained_bytes() > self.cap {
+            counter.fetch_sub(bytes, Ordering::SeqCst);
+            return Err(format!(
+                "Memory cap of {} MB reached, refusing to allocate {} MB",
+                self.cap / BYTES_PER_MB,
+                bytes / BYTES_PER_MB
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn touched_buffer(bytes: usize) -> Vec<u8> {
+    let mut buffer = vec![0u8; bytes];
+    for i in (0..bytes).step_by(PAGE_SIZE) {
+        buffer[i] = 1;
+    }
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simulator(cap_mb: usize) -> Arc<MemorySimulator> {
+        Arc::new(MemorySimulator::new(cap_mb * BYTES_PER_MB))
+    }
+
+    #[test]
+    fn allocations_count_until_dropped() {
+        let memory = simulator(4);
+
+        let first = memory.allocate(BYTES_PER_MB).unwrap();
+        let second = memory.allocate(2 * BYTES_PER_MB).unwrap();
+        assert_eq!(memory.allocated_bytes(), 3 * BYTES_PER_MB);
+
+        drop(first);
+        assert_eq!(memory.allocated_bytes(), 2 * BYTES_PER_MB);
+        drop(second);
+        assert_eq!(memory.allocated_bytes(), 0);
+    }
+
+    #[test]
+    fn retained_memory_grows_until_reset() {
+        let memory = simulator(4);
+
+        memory.retain(BYTES_PER_MB).unwrap();
+        memory.retain(BYTES_PER_MB).unwrap();
+        assert_eq!(memory.retained_bytes(), 2 * BYTES_PER_MB);
+        assert_eq!(memory.allocated_bytes(), 0);
+
+        memory.reset();
+        assert_eq!(memory.retained_bytes(), 0);
+    }
+
+    #[test]
+    fn the_cap_covers_allocated_and_retained_memory() {
+        let memory = simulator(4);
+        memory.retain(3 * BYTES_PER_MB).unwrap();
+
+        assert_eq!(
+            memory.allocate(2 * BYTES_PER_MB).err().unwrap(),
+            "Memory cap of 4 MB reached, refusing to allocate 2 MB"
+        );
+        assert!(memory.retain(2 * BYTES_PER_MB).is_err());
+        // Refused memory isn't counted.
+        assert_eq!(memory.allocated_bytes(), 0);
+        assert_eq!(memory.retained_bytes(), 3 * BYTES_PER_MB);
+
+        let exactly_the_cap = memory.allocate(BYTES_PER_MB).unwrap();
+        assert_eq!(
+            memory.allocated_bytes() + memory.retained_bytes(),
+            memory.cap()
+        );
+        assert!(memory.allocate(0).is_ok());
+        drop(exactly_the_cap);
+    }
+}
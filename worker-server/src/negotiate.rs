@@ -0,0 +1,150 @@
+use hyper::{header, HeaderMap};
+
+pub const TEXT_PLAIN: &str = "text/plain";
+pub const APPLICATION_JSON: &str = "application/json";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResponseFormat {
+    Text,
+    Json,
+}
+
+impl ResponseFormat {
+    // Picks JSON only when the client ranks it above plain text, None when both are excluded.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let accept = headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",");
+        if accept.trim().is_empty() {
+            return Some(ResponseFormat::Text);
+        }
+
+        let ranges = accept
+            .split(',')
+            .filter_map(parse_media_range)
+            .collect::<Vec<_>>();
+        let text = quality(&ranges, "text", "plain");
+        let json = quality(&ranges, "application", "json");
+
+        if text <= 0.0 && json <= 0.0 {
+            None
+        } else if json > text {
+            Some(ResponseFormat::Json)
+        } else {
+            Some(ResponseFormat::Text)
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ResponseFormat::Text => TEXT_PLAIN,
+            ResponseFormat::Json => APPLICATION_JSON,
+        }
+    }
+}
+
+struct MediaRange {
+    kind: String,
+    subtype: String,
+    q: f32,
+}
+
+fn parse_media_range(range: &str) -> Option<MediaRange> {
+    let mut params = range.split(';');
+    let (kind, subtype) = params.next()?.trim().split_once('/')?;
+    let q = params
+        .filter_map(|param| param.trim().strip_prefix("q="))
+        .find_map(|q| q.trim().parse::<f32>().ok())
+        .unwrap_or(1.0);
+
+    Some(MediaRange {
+        kind: kind.trim().to_ascii_lowercase(),
+        subtype: subtype.trim().to_ascii_lowercase(),
+        q,
+    })
+}
+
+// Quality of the most specific range matching `kind/subtype`, 0 if none does.
+fn quality(ranges: &[MediaRange], kind: &str, subtype: &str) -> f32 {
+    ranges
+        .iter()
+        .filter_map(|range| {
+            let specificity = match (range.kind.as_str(), range.subtype.as_str()) {
+                (k, s) if k == kind && s == subtype => 2,
+                (k, "*") if k == kind => 1,
+                ("*", "*") => 0,
+                _ => return None,
+            };
+            Some((specificity, range.q))
+        })
+        .max_by_key(|(specificity, _)| *specificity)
+        .map_or(0.0, |(_, q)| q)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn negotiated(accept: &[&str]) -> Option<ResponseFormat> {
+        let mut headers = HeaderMap::new();
+        for value in accept {
+            headers.append(header::ACCEPT, value.parse().unwrap());
+        }
+        ResponseFormat::from_headers(&headers)
+    }
+
+    #[test]
+    fn plain_text_is_kept_without_a_preference() {
+        assert_eq!(negotiated(&[]), Some(ResponseFormat::Text));
+        assert_eq!(negotiated(&[" "]), Some(ResponseFormat::Text));
+        assert_eq!(negotiated(&["*/*"]), Some(ResponseFormat::Text));
+        assert_eq!(negotiated(&["text/html"]), None);
+    }
+
+    #[test]
+    fn json_is_picked_when_preferred() {
+        assert_eq!(
+            negotiated(&["application/json"]),
+            Some(ResponseFormat::Json)
+        );
+        assert_eq!(
+            negotiated(&["Application/JSON"]),
+            Some(ResponseFormat::Json)
+        );
+        assert_eq!(negotiated(&["application/*"]), Some(ResponseFormat::Json));
+        assert_eq!(
+            negotiated(&["text/plain", "application/json"]),
+            Some(ResponseFormat::Text)
+        );
+    }
+
+    #[test]
+    fn q_values_rank_the_formats() {
+        assert_eq!(
+            negotiated(&["text/plain;q=0.5, application/json;q=0.9"]),
+            Some(ResponseFormat::Json)
+        );
+        assert_eq!(
+            negotiated(&["application/json; q=0.2, */*"]),
+            Some(ResponseFormat::Text)
+        );
+        assert_eq!(
+            negotiated(&["*/*;q=0.1, application/json"]),
+            Some(ResponseFormat::Json)
+        );
+        assert_eq!(
+            negotiated(&["text/*;q=0, application/json;q=0.3"]),
+            Some(ResponseFormat::Json)
+        );
+    }
+
+    #[test]
+    fn excluding_both_formats_is_not_acceptable() {
+        assert_eq!(negotiated(&["text/plain;q=0, application/json;q=0"]), None);
+        assert_eq!(negotiated(&["*/*;q=0"]), None);
+        assert_eq!(negotiated(&["image/png"]), None);
+    }
+}
@@ -0,0 +1,133 @@
+use std::fmt;
+
+use hyper::{HeaderMap, StatusCode};
+
+pub const DELAY_HEADER: &str = "x-simulate-delay-ms";
+pub const STATUS_HEADER: &str = "x-simulate-status";
+pub const ERROR_HEADER: &str = "x-simulate-error";
+
+const MAX_DELAY_MS: u64 = 60_000;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Overrides {
+    pub delay_ms: Option<u64>,
+    pub status: Option<StatusCode>,
+    pub error: bool,
+}
+
+impl Overrides {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+        Overrides {
+            delay_ms: header(DELAY_HEADER)
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(|delay| delay.min(MAX_DELAY_MS)),
+            status: header(STATUS_HEADER)
+                .and_then(|v| v.parse::<u16>().ok())
+                .and_then(|code| StatusCode::from_u16(code).ok()),
+            error: header(ERROR_HEADER).is_some_and(|v| v.eq_ignore_ascii_case("true")),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.delay_ms.is_none() && self.status.is_none() && !self.error
+    }
+
+    pub fn apply_duration(&self, duration: u64) -> u64 {
+        self.delay_ms.unwrap_or(duration)
+    }
+
+    pub fn apply_status(&self, status: StatusCode) -> StatusCode {
+        match (self.status, self.error) {
+            (Some(status), _) => status,
+            (None, true) => StatusCode::INTERNAL_SERVER_ERROR,
+            (None, false) => status,
+        }
+    }
+}
+
+impl fmt::Display for Overrides {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut applied = Vec::new();
+        if let Some(delay_ms) = self.delay_ms {
+            applied.push(format!("delay_ms={}", delay_ms));
+        }
+        if let Some(status) = self.status {
+            applied.push(format!("status={}", status.as_u16()));
+        }
+        if self.error {
+            applied.push("error=true".to_string());
+        }
+        write!(f, "{}", applied.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::header::HeaderValue;
+
+    use super::*;
+
+    fn overrides(headers: &[(&'static str, &'static str)]) -> Overrides {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.insert(*name, HeaderValue::from_static(value));
+        }
+        Overrides::from_headers(&map)
+    }
+
+    #[test]
+    fn no_headers_override_nothing() {
+        let none = overrides(&[]);
+
+        assert!(none.is_empty());
+        assert_eq!(none.apply_duration(120), 120);
+        assert_eq!(none.apply_status(StatusCode::OK), StatusCode::OK);
+        assert_eq!(none.to_string(), "");
+    }
+
+    #[test]
+    fn headers_replace_the_delay_and_status() {
+        let all = overrides(&[
+            (DELAY_HEADER, "250"),
+            (STATUS_HEADER, "503"),
+            (ERROR_HEADER, "TRUE"),
+        ]);
+
+        assert!(!all.is_empty());
+        assert_eq!(all.apply_duration(10), 250);
+        // A status given wins over the error flag.
+        assert_eq!(
+            all.apply_status(StatusCode::OK),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(all.to_string(), "delay_ms=250, status=503, error=true");
+
+        let error = overrides(&[(ERROR_HEADER, "true")]);
+        assert_eq!(
+            error.apply_status(StatusCode::OK),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[test]
+    fn delays_are_capped() {
+        let long = overrides(&[(DELAY_HEADER, "3600000")]);
+
+        assert_eq!(long.delay_ms, Some(MAX_DELAY_MS));
+    }
+
+    #[test]
+    fn invalid_values_are_ignored() {
+        for headers in [
+            [(DELAY_HEADER, "-1")],
+            [(DELAY_HEADER, "soon")],
+            [(STATUS_HEADER, "99")],
+            [(STATUS_HEADER, "teapot")],
+            [(ERROR_HEADER, "yes")],
+        ] {
+            assert!(overrides(&headers).is_empty(), "{:?}", headers);
+        }
+    }
+}
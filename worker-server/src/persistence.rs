@@ -0,0 +1,108 @@
+use std::ffi::OsString;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::fs;
+
+use crate::Result;
+
+pub async fn save<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let contents = serde_json::to_string_pretty(value)?;
+
+    let tmp_path = tmp_path(path);
+    fs::write(&tmp_path, contents).await?;
+    fs::rename(&tmp_path, path).await?;
+    Ok(())
+}
+
+pub async fn load<T: DeserializeOwned>(path: &Path) -> std::result::Result<Option<T>, String> {
+    let contents = match fs::read_to_string(path).await {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+
+    serde_json::from_str(&contents)
+        .map(Some)
+        .map_err(|e| format!("Invalid state in {}: {}", path.display(), e))
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut tmp_path = OsString::from(path.as_os_str());
+    tmp_path.push(".tmp");
+    PathBuf::from(tmp_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct State {
+        min_duration: u64,
+        error_rate: f64,
+    }
+
+    const STATE: State = State {
+        min_duration: 5,
+        error_rate: 0.5,
+    };
+
+    #[tokio::test]
+    async fn loads_what_was_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+
+        save(&path, &STATE).await.unwrap();
+        assert_eq!(load::<State>(&path).await, Ok(Some(STATE)));
+        assert!(!tmp_path(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn save_replaces_the_previous_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+
+        save(
+            &path,
+            &State {
+                min_duration: 1,
+                error_rate: 0.0,
+            },
+        )
+        .await
+        .unwrap();
+        save(&path, &STATE).await.unwrap();
+        assert_eq!(load::<State>(&path).await, Ok(Some(STATE)));
+    }
+
+    #[tokio::test]
+    async fn missing_file_is_no_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+
+        assert_eq!(load::<State>(&path).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn corrupt_file_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        std::fs::write(&path, "{\"min_duration\": 5, \"error_").unwrap();
+
+        let error = load::<State>(&path).await.unwrap_err();
+        assert!(error.starts_with("Invalid state in "), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn unreadable_file_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let error = load::<State>(dir.path()).await.unwrap_err();
+        assert!(error.starts_with("Failed to read "), "{}", error);
+    }
+}
@@ -0,0 +1,82 @@
+use std::fmt;
+
+use tokio::task::AbortHandle;
+use tokio::time::{Duration, Instant};
+
+const PENDING: &str = "pending";
+const REVERTING: &str = "reverting";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SchedulePhase {
+    Pending,
+    Reverting,
+}
+
+impl fmt::Display for SchedulePhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SchedulePhase::Pending => PENDING,
+            SchedulePhase::Reverting => REVERTING,
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// A setup change that activates at `apply_at` and, if `revert_at` is set,
+// restores the configuration it replaced at that point.
+pub struct Schedule {
+    pub id: u64,
+    pub apply_at: Instant,
+    pub revert_at: Option<Instant>,
+    pub handle: AbortHandle,
+}
+
+impl Schedule {
+    pub fn phase(&self) -> Option<SchedulePhase> {
+        let now = Instant::now();
+        if self.handle.is_finished() {
+            None
+        } else if now < self.apply_at {
+            Some(SchedulePhase::Pending)
+        } else if self.revert_at.is_some_and(|revert_at| now < revert_at) {
+            Some(SchedulePhase::Reverting)
+        } else {
+            None
+        }
+    }
+
+    pub fn info(&self) -> serde_json::Value {
+        let remaining =
+            |at: Instant| at.saturating_duration_since(Instant::now()).as_millis() as u64;
+
+        match self.phase() {
+            Some(SchedulePhase::Pending) => serde_json::json!({
+                "phase": SchedulePhase::Pending.to_string(),
+                "apply_in_ms": remaining(self.apply_at),
+                "revert_in_ms": self.revert_at.map(remaining),
+            }),
+            Some(SchedulePhase::Reverting) => serde_json::json!({
+                "phase": SchedulePhase::Reverting.to_string(),
+                "revert_in_ms": self.revert_at.map(remaining),
+            }),
+            None => serde_json::Value::Null,
+        }
+    }
+
+    pub fn cancel(self) -> Option<SchedulePhase> {
+        let phase = self.phase();
+        self.handle.abort();
+        phase
+    }
+}
+
+pub fn deadlines(
+    apply_after: Duration,
+    revert_after: Option<Duration>,
+) -> (Instant, Option<Instant>) {
+    let apply_at = Instant::now() + apply_after;
+    (
+        apply_at,
+        revert_after.map(|revert_after| apply_at + revert_after),
+    )
+}
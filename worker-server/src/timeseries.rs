@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+
+use tokio::time::Instant;
+
+pub const BUCKET_COUNT: usize = 5;
+
+#[derive(Clone, Copy, Default)]
+struct EndpointCounts {
+    requests: u64,
+    errors: u64,
+    total_duration_ms: u64,
+}
+
+#[derive(Default)]
+struct Bucket {
+    minute: u64,
+    endpoints: BTreeMap<&'static str, EndpointCounts>,
+}
+
+// Minutes are counted from `started_at`, the times recorded at are passed in.
+pub struct MinuteSeries {
+    started_at: Instant,
+    buckets: [Bucket; BUCKET_COUNT],
+}
+
+impl MinuteSeries {
+    pub fn new(started_at: Instant) -> Self {
+        MinuteSeries {
+            started_at,
+            buckets: Default::default(),
+        }
+    }
+
+    fn minute(&self, now: Instant) -> u64 {
+        now.saturating_duration_since(self.started_at).as_secs() / 60
+    }
+
+    pub fn record(&mut self, now: Instant, endpoint: &'static str, duration_ms: u64, error: bool) {
+        let minute = self.minute(now);
+        let bucket = &mut self.buckets[minute as usize % BUCKET_COUNT];
+        if bucket.minute != minute {
+            bucket.minute = minute;
+            bucket.endpoints.clear();
+        }
+
+        let counts = bucket.endpoints.entry(endpoint).or_default();
+        counts.requests += 1;
+        counts.total_duration_ms += duration_ms;
+        if error {
+            counts.errors += 1;
+        }
+    }
+
+    // Oldest to newest, minutes without traffic are reported as empty buckets.
+    pub fn snapshot(&self, now: Instant) -> serde_json::Value {
+        let minute = self.minute(now);
+        let first = minute.saturating_sub(BUCKET_COUNT as u64 - 1);
+        let buckets: Vec<serde_json::Value> = (first..=minute)
+            .map(|m| {
+                let bucket = &self.buckets[m as usize % BUCKET_COUNT];
+                let mut endpoints = serde_json::Map::new();
+                if bucket.minute == m {
+                    for (name, counts) in &bucket.endpoints {
+                        endpoints.insert(
+                            name.to_string(),
+                            serde_json::json!({
+                                "requests": counts.requests,
+                                "errors": counts.errors,
+                                "avg_duration_ms": counts.total_duration_ms / counts.requests,
+                            }),
+                        );
+                    }
+                }
+                serde_json::json!({ "minute": m, "endpoints": endpoints })
+            })
+            .collect();
+        serde_json::Value::Array(buckets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tokio::time::Duration;
+
+    use super::*;
+
+    fn minutes(count: u64) -> Duration {
+        Duration::from_secs(60 * count)
+    }
+
+    #[test]
+    fn counts_requests_per_endpoint_and_minute() {
+        let start = Instant::now();
+        let mut series = MinuteSeries::new(start);
+        series.record(start, "/work", 10, false);
+        series.record(start + Duration::from_secs(30), "/work", 30, true);
+        series.record(start + Duration::from_secs(59), "/health", 1, false);
+        series.record(start + minutes(1), "/work", 50, false);
+
+        let snapshot = series.snapshot(start + minutes(1));
+        assert_eq!(
+            snapshot,
+            json!([
+                {
+                    "minute": 0,
+                    "endpoints": {
+                        "/health": { "requests": 1, "errors": 0, "avg_duration_ms": 1 },
+                        "/work": { "requests": 2, "errors": 1, "avg_duration_ms": 20 },
+                    },
+                },
+                {
+                    "minute": 1,
+                    "endpoints": {
+                        "/work": { "requests": 1, "errors": 0, "avg_duration_ms": 50 },
+                    },
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn keeps_the_last_minutes_oldest_first() {
+        let start = Instant::now();
+        let mut series = MinuteSeries::new(start);
+        for minute in 0..8 {
+            series.record(start + minutes(minute), "/work", minute, false);
+        }
+
+        let snapshot = series.snapshot(start + minutes(7));
+        let reported = snapshot
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|bucket| bucket["minute"].as_u64().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(reported, vec![3, 4, 5, 6, 7]);
+        assert_eq!(snapshot[0]["endpoints"]["/work"]["avg_duration_ms"], 3);
+    }
+
+    #[test]
+    fn idle_minutes_are_empty() {
+        let start = Instant::now();
+        let mut series = MinuteSeries::new(start);
+        series.record(start, "/work", 10, false);
+
+        // Minute 5 reuses minute 0's bucket, which must not leak into it.
+        let snapshot = series.snapshot(start + minutes(5));
+        assert_eq!(snapshot.as_array().unwrap().len(), BUCKET_COUNT);
+        for bucket in snapshot.as_array().unwrap() {
+            assert_eq!(bucket["endpoints"], json!({}));
+        }
+    }
+
+    #[test]
+    fn reused_bucket_starts_over() {
+        let start = Instant::now();
+        let mut series = MinuteSeries::new(start);
+        series.record(start, "/work", 10, true);
+        series.record(start + minutes(5), "/work", 40, false);
+
+        let snapshot = series.snapshot(start + minutes(5));
+        assert_eq!(
+            snapshot[4]["endpoints"]["/work"],
+            json!({ "requests": 1, "errors": 0, "avg_duration_ms": 40 })
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use tokio::time::{sleep, Duration};
+
+use crate::Result;
+
+const SLEEP: &str = "sleep";
+const CPU: &str = "cpu";
+const MIXED: &str = "mixed";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkMode {
+    #[default]
+    Sleep,
+    Cpu,
+    Mixed,
+}
+
+pub struct ConversionError;
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid work mode. Valid values are '{}', '{}' or '{}'",
+            SLEEP, CPU, MIXED
+        )
+    }
+}
+
+impl TryFrom<&str> for WorkMode {
+    type Error = ConversionError;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        match value {
+            SLEEP => Ok(WorkMode::Sleep),
+            CPU => Ok(WorkMode::Cpu),
+            MIXED => Ok(WorkMode::Mixed),
+            _ => Err(ConversionError),
+        }
+    }
+}
+
+impl fmt::Display for WorkMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            WorkMode::Sleep => SLEEP,
+            WorkMode::Cpu => CPU,
+            WorkMode::Mixed => MIXED,
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl WorkMode {
+    pub async fn simulate(&self, duration: Duration) -> Result<()> {
+        match self {
+            WorkMode::Sleep => sleep(duration).await,
+            WorkMode::Cpu => burn_cpu(duration).await?,
+            WorkMode::Mixed => {
+                let cpu_duration = duration / 2;
+                burn_cpu(cpu_duration).await?;
+                sleep(duration - cpu_duration).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn burn_cpu(duration: Duration) -> Result<()> {
+    tokio::task::spawn_blocking(move || {
+        let deadline = Instant::now() + duration;
+        let mut value = 0u64;
+        while Instant::now() < deadline {
+            for _ in 0..1000 {
+                let mut hasher = DefaultHasher::new();
+                value.hash(&mut hasher);
+                value = hasher.finish();
+            }
+        }
+        std::hint::black_box(value);
+    })
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use lb_api::WorkRequest;
+
+const DEFAULT_MULTIPLIER: u64 = 1;
+const MAX_MULTIPLIER: u64 = 10;
+const DEFAULT_TICK_MS: u64 = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorkParams {
+    pub multiplier: u64,
+    pub stream: bool,
+    pub tick_ms: u64,
+}
+
+impl WorkParams {
+    pub fn from_json(data: serde_json::Value) -> Result<Self, String> {
+        let request = serde_json::from_value::<WorkRequest>(data)
+            .map_err(|e| format!("Invalid work: {}", e))?;
+
+        Ok(WorkParams {
+            multiplier: request
+                .multiplier
+                .unwrap_or(DEFAULT_MULTIPLIER)
+                .clamp(1, MAX_MULTIPLIER),
+            stream: request.stream.unwrap_or(false),
+            tick_ms: request.tick_ms.unwrap_or(DEFAULT_TICK_MS).max(1),
+        })
+    }
+
+    // Query values are always strings, which the JSON path already accepts,
+    // so both entry points share the same parsing and validation.
+    pub fn from_query(query: Option<&str>) -> Result<Self, String> {
+        let mut data = serde_json::Map::new();
+        for pair in query
+            .unwrap_or_default()
+            .split('&')
+            .filter(|p| !p.is_empty())
+        {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            data.insert(
+                key.to_string(),
+                serde_json::Value::String(value.to_string()),
+            );
+        }
+        Self::from_json(serde_json::Value::Object(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn query_values_parse_like_their_json_form() {
+        assert_eq!(
+            WorkParams::from_query(Some("multiplier=3&stream=true&tick_ms=50")),
+            WorkParams::from_json(json!({ "multiplier": 3, "stream": true, "tick_ms": 50 }))
+        );
+        assert_eq!(
+            WorkParams::from_query(Some("multiplier=3&stream=true&tick_ms=50")),
+            Ok(WorkParams {
+                multiplier: 3,
+                stream: true,
+                tick_ms: 50
+            })
+        );
+    }
+
+    #[test]
+    fn missing_parameters_take_the_defaults() {
+        let defaults = Ok(WorkParams {
+            multiplier: DEFAULT_MULTIPLIER,
+            stream: false,
+            tick_ms: DEFAULT_TICK_MS,
+        });
+        assert_eq!(WorkParams::from_query(None), defaults);
+        assert_eq!(WorkParams::from_query(Some("")), defaults);
+        assert_eq!(WorkParams::from_query(Some("&&")), defaults);
+        assert_eq!(WorkParams::from_json(json!({})), defaults);
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped() {
+        let params = WorkParams::from_query(Some("multiplier=0&tick_ms=0")).unwrap();
+        assert_eq!((params.multiplier, params.tick_ms), (1, 1));
+        let params = WorkParams::from_query(Some("multiplier=50")).unwrap();
+        assert_eq!(params.multiplier, MAX_MULTIPLIER);
+    }
+
+    #[test]
+    fn invalid_values_are_rejected_by_both_entry_points() {
+        for (query, body) in [
+            ("multiplier=abc", json!({ "multiplier": "abc" })),
+            ("multiplier=-1", json!({ "multiplier": -1 })),
+            ("stream=maybe", json!({ "stream": "maybe" })),
+            ("tick_ms=", json!({ "tick_ms": "" })),
+        ] {
+            let from_query = WorkParams::from_query(Some(query));
+            assert!(
+                from_query
+                    .as_ref()
+                    .is_err_and(|e| e.starts_with("Invalid work: ")),
+                "{}: {:?}",
+                query,
+                from_query
+            );
+            assert!(WorkParams::from_json(body).is_err(), "{}", query);
+        }
+    }
+}